@@ -68,6 +68,16 @@ impl DiskCache {
         }
     }
 
+    /// Reads the whole block stored for the request, regardless of the range
+    /// requested within it.
+    pub async fn get_block(&self, request: &BlockReadRequest) -> Result<Vec<u8>, CacheError> {
+        let key = request_to_key(request);
+        match self.cache.get(key.as_str()) {
+            Some(val) => self.disk_manager.read(&val, 0..val.size).await,
+            None => Err(BlockNotFound),
+        }
+    }
+
     /// Reads the root dir for the cache, adding the entries it finds into the cache.
     fn load_cache(&self) -> Result<(), CacheError> {
         let mut err = Ok(());
@@ -107,7 +117,7 @@ impl BlockReader for DiskCache {
     }
 }
 
-fn request_to_key(request: &BlockReadRequest) -> String {
+pub(crate) fn request_to_key(request: &BlockReadRequest) -> String {
     // Note that this format is used in the Header class to attemp to parse out this metadata.
     format!(
         "{}.{}.{}",
@@ -166,6 +176,22 @@ mod tests {
         assert_eq!(data, val);
     }
 
+    #[tokio::test]
+    async fn test_put_get_long_path() {
+        // Deeper than the 260 characters of MAX_PATH on Windows.
+        let dir = CacheDirTest::new("cache_put_get_long_path");
+        let root = dir
+            .get_path()
+            .join("d".repeat(100))
+            .join("e".repeat(100))
+            .join("f".repeat(100));
+        let c = DiskCache::from_config(root.to_str().unwrap(), 50).unwrap();
+        let req = get_block_req("a", 10, 1);
+        let val = get_bytes(10);
+        assert!(c.put(&req, val.as_slice()).await.unwrap());
+        assert_eq!(c.get(&req).await.unwrap(), val);
+    }
+
     #[tokio::test]
     async fn test_put_get_multiple() {
         let dir = CacheDirTest::new("cache_put_get_multiple");
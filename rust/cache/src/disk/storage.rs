@@ -1,16 +1,16 @@
 use std::fs;
 use std::fs::{remove_file, DirEntry, File};
 use std::io::ErrorKind;
-use std::io::Write;
 use std::ops::Range;
 #[cfg(unix)]
-use std::os::unix::fs::{FileExt, MetadataExt};
+use std::os::unix::fs::MetadataExt;
 #[cfg(windows)]
-use std::os::windows::fs::{FileExt, MetadataExt};
+use std::os::windows::fs::MetadataExt;
 use std::path::{Path, PathBuf};
 use std::str;
 
 use byteorder::LittleEndian;
+use cas::fileio;
 use tracing::{debug, info, warn};
 
 use crate::disk::cache::EvictAction;
@@ -72,9 +72,13 @@ impl DiskManager {
 
     /// Tries to load the indicated directory entry as a cache file. If this entry cannot
     /// be loaded as a cache file, then we will log this and will remove the file.
+    /// Hidden files are skipped, as they are blocks another process is writing.
     ///
     /// Note: we don't throw an error because this isn't a problem to surface further up.
     fn try_load_entry(entry: DirEntry) -> Option<CacheValue> {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            return None;
+        }
         let path = entry.path();
         match to_cache_value(entry) {
             Ok(v) => Some(v),
@@ -100,7 +104,7 @@ impl DiskManager {
             key: item.key.clone(),
         };
         header.write_to(&mut f)?;
-        f.write_all(val)
+        fileio::write_all_at(f, val, header.get_header_len())
             .map_err(IOError)
             .map(|_| observe_data_added(size))?;
 
@@ -112,32 +116,13 @@ impl DiskManager {
         Ok(())
     }
 
-    #[cfg(unix)]
-    fn read_impl(f: &mut File, buf: &mut [u8], start: u64) -> Result<(), CacheError> {
-        f.read_exact_at(buf, start)?;
-        Ok(())
-    }
-
-    #[cfg(windows)]
-    fn read_impl(f: &mut File, buf: &mut Vec<u8>, start: u64) -> Result<(), CacheError> {
-        let num_read = f.seek_read(buf, start)?;
-        // replicate behavior of unix read_exact_at
-        if num_read != buf.len() {
-            return Err(IOError(std::io::Error::new(
-                ErrorKind::UnexpectedEof,
-                "failed to fill whole buffer",
-            )));
-        }
-        Ok(())
-    }
-
     pub async fn read(&self, item: &CacheValue, range: Range<u64>) -> Result<Vec<u8>, CacheError> {
         let path = self.to_filepath(item);
         let mut f = File::open(path)?;
         let mut buf = vec![0u8; (range.end - range.start) as usize];
         let header = Header::read_from(&mut f)?;
         let start_off = header.get_header_len() + range.start;
-        Self::read_impl(&mut f, &mut buf, start_off)?;
+        fileio::read_exact_at(&f, &mut buf, start_off)?;
         Ok(buf)
     }
 
@@ -364,7 +349,7 @@ impl Header {
 
 #[cfg(test)]
 mod tests {
-    use std::io::Cursor;
+    use std::io::{Cursor, Write};
 
     use crate::util::test_utils::CacheDirTest;
 
@@ -457,6 +442,20 @@ mod tests {
         assert_eq!(vals[1].key.as_str(), "bcd");
     }
 
+    #[tokio::test]
+    async fn test_manager_load_skips_partial_writes() {
+        let dir = CacheDirTest::new("load_partial");
+        let m = DiskManager::new(dir.get_path().to_path_buf());
+        let key = CacheValue::new(5, 0, "abc".to_string(), 1024, 0);
+        m.write(&key, &[1, 2, 3, 4, 5]).await.unwrap();
+        let partial = dir.get_path().join(".tmpABCDEF");
+        fs::write(&partial, [1, 2, 3]).unwrap();
+
+        let m2 = DiskManager::new(dir.get_path().to_path_buf());
+        assert_eq!(m2.init().unwrap().count(), 1);
+        assert!(partial.exists());
+    }
+
     #[test]
     fn test_header_serde() {
         let header = Header {
@@ -10,7 +10,11 @@ use cas::singleflight;
 pub use disk::DiskCache;
 pub use error::CacheError;
 pub use interface::{BlockReadRequest, BlockReader, FileMetadata};
-pub use metrics::set_metrics_service_name;
+pub use memory::{MemoryCache, MEMORY_CACHE};
+pub use metrics::{
+    reads_from, set_metrics_service_name, SOURCE_DISK_CACHE, SOURCE_MEMORY_CACHE, SOURCE_REMOTE,
+    SOURCE_SINGLEFLIGHT,
+};
 pub use xorb_cache::XorbCacheImpl;
 
 mod block;
@@ -18,6 +22,7 @@ mod disk;
 mod error;
 mod interface;
 pub mod lru;
+mod memory;
 mod metrics;
 mod util;
 mod xorb_cache;
@@ -0,0 +1,122 @@
+use std::sync::{Arc, Mutex};
+
+use lazy_static::lazy_static;
+use lru::LruCache;
+
+use crate::metrics::{LRU_REQUESTS, STATUS_HIT, STATUS_MISS};
+
+const MEMORY_CACHE_NAME: &str = "memory_block";
+
+lazy_static! {
+    /// The blocks held in memory, shared by every cache in the process so
+    /// that mounts and smudges reading the same xorbs don't each hold a copy.
+    /// Disabled until given a capacity.
+    pub static ref MEMORY_CACHE: MemoryCache = MemoryCache::new(0);
+}
+
+/// An LRU of whole cache blocks bounded by their total size in bytes.
+#[derive(Debug)]
+pub struct MemoryCache {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    blocks: LruCache<String, Arc<Vec<u8>>>,
+    size: u64,
+    capacity: u64,
+}
+
+impl MemoryCache {
+    pub fn new(capacity: u64) -> Self {
+        MemoryCache {
+            inner: Mutex::new(Inner {
+                blocks: LruCache::unbounded(),
+                size: 0,
+                capacity,
+            }),
+        }
+    }
+
+    /// Sets the most bytes of blocks held, evicting blocks to fit; 0
+    /// disables the cache.
+    pub fn set_capacity(&self, capacity: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.capacity = capacity;
+        inner.evict();
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.inner.lock().unwrap().capacity > 0
+    }
+
+    pub fn get(&self, key: &str) -> Option<Arc<Vec<u8>>> {
+        let mut inner = self.inner.lock().unwrap();
+        let block = inner.blocks.get(key).cloned();
+        let status = if block.is_some() {
+            STATUS_HIT
+        } else {
+            STATUS_MISS
+        };
+        LRU_REQUESTS
+            .with_label_values(&[MEMORY_CACHE_NAME, status])
+            .inc();
+        block
+    }
+
+    /// Inserts a block, unless it's larger than the whole cache.
+    pub fn put(&self, key: &str, block: Arc<Vec<u8>>) {
+        let mut inner = self.inner.lock().unwrap();
+        let len = block.len() as u64;
+        if len > inner.capacity {
+            return;
+        }
+        if let Some(old) = inner.blocks.put(key.to_string(), block) {
+            inner.size -= old.len() as u64;
+        }
+        inner.size += len;
+        inner.evict();
+    }
+}
+
+impl Inner {
+    fn evict(&mut self) {
+        while self.size > self.capacity {
+            match self.blocks.pop_lru() {
+                Some((_, block)) => self.size -= block.len() as u64,
+                None => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_cache_eviction() {
+        let cache = MemoryCache::new(100);
+        assert!(cache.enabled());
+        cache.put("a", Arc::new(vec![0; 40]));
+        cache.put("b", Arc::new(vec![1; 40]));
+        // a becomes the most recently used, so b is evicted to fit c.
+        assert!(cache.get("a").is_some());
+        cache.put("c", Arc::new(vec![2; 40]));
+        assert!(cache.get("b").is_none());
+        assert_eq!(cache.get("c").unwrap().len(), 40);
+
+        // Blocks larger than the cache aren't held.
+        cache.put("d", Arc::new(vec![3; 101]));
+        assert!(cache.get("d").is_none());
+        assert!(cache.get("a").is_some());
+
+        cache.set_capacity(50);
+        assert!(cache.get("a").is_none());
+        assert!(cache.get("c").is_some());
+
+        cache.set_capacity(0);
+        assert!(!cache.enabled());
+        assert!(cache.get("c").is_none());
+    }
+}
@@ -14,6 +14,7 @@ lazy_static! {
     static ref SERVICE: Mutex<String> = Mutex::new(DEFAULT_SERVICE.to_string());
 }
 
+pub const SOURCE_MEMORY_CACHE: &str = "memory_cache";
 pub const SOURCE_DISK_CACHE: &str = "disk_cache";
 pub const SOURCE_REMOTE: &str = "remote";
 pub const SOURCE_SINGLEFLIGHT: &str = "singleflight";
@@ -100,6 +101,16 @@ lazy_static! {
     .unwrap();
 }
 
+/// The blocks and bytes the cache served this process from source, one of
+/// the SOURCE_* values.
+pub fn reads_from(source: &str) -> (u64, u64) {
+    let labels = [source];
+    (
+        BLOCKS_READ.with_label_values(&labels).get(),
+        DATA_READ.with_label_values(&labels).get(),
+    )
+}
+
 fn prefix_name(namespace: &str, name: &str) -> String {
     let service = SERVICE
         .lock()
@@ -9,11 +9,13 @@ use tracing_futures::Instrument;
 use cas::key::Key;
 use cas::singleflight;
 
+use crate::disk::cache::request_to_key;
 use crate::metrics::{
     BLOCKS_READ, DATA_READ, READ_ERROR_COUNT, REQUEST_LATENCY_MS, REQUEST_THROUGHPUT,
-    SOURCE_DISK_CACHE, SOURCE_REMOTE, SOURCE_SINGLEFLIGHT, WRITE_ERROR_COUNT,
+    SOURCE_DISK_CACHE, SOURCE_MEMORY_CACHE, SOURCE_REMOTE, SOURCE_SINGLEFLIGHT, WRITE_ERROR_COUNT,
 };
 use crate::{BlockConverter, BlockReadRequest, BlockReader, CacheError, DiskCache, FileMetadata};
+use crate::{MemoryCache, MEMORY_CACHE};
 use crate::{Remote, XorbCache};
 
 #[derive(Debug)]
@@ -22,6 +24,7 @@ pub struct XorbCacheImpl {
     remote: Arc<dyn Remote>,
     block_converter: BlockConverter,
     request_merger: singleflight::Group<Arc<Vec<u8>>, CacheError>,
+    memory: &'static MemoryCache,
 }
 
 impl XorbCacheImpl {
@@ -36,9 +39,17 @@ impl XorbCacheImpl {
             remote,
             block_converter,
             request_merger,
+            memory: &MEMORY_CACHE,
         }
     }
 
+    /// Holds blocks in the given in-memory cache instead of the process-wide
+    /// one.
+    pub fn with_memory_cache(mut self, memory: &'static MemoryCache) -> Self {
+        self.memory = memory;
+        self
+    }
+
     async fn read_block_from_cache(&self, request: &BlockReadRequest) -> Option<Vec<u8>> {
         let block_id = request.block_range().idx();
         // check cache for block
@@ -106,6 +117,40 @@ impl XorbCacheImpl {
         Ok(data)
     }
 
+    /// Reads a whole block through the in-memory cache, loading it from the
+    /// disk cache on a miss. Returns None if neither holds the block.
+    async fn read_block_from_memory(
+        &self,
+        full_block_request: &BlockReadRequest,
+    ) -> Option<(Arc<Vec<u8>>, &'static str)> {
+        let memory_key = request_to_key(full_block_request);
+        if let Some(data) = self.memory.get(&memory_key) {
+            return Some((data, SOURCE_MEMORY_CACHE));
+        }
+        let block_id = full_block_request.block_range().idx();
+        match self
+            .cache
+            .get_block(full_block_request)
+            .instrument(info_span!("cache_read_block", %block_id))
+            .await
+        {
+            Ok(data) => {
+                let data = Arc::new(data);
+                self.memory.put(&memory_key, data.clone());
+                Some((data, SOURCE_DISK_CACHE))
+            }
+            Err(CacheError::BlockNotFound) => None,
+            Err(e) => {
+                READ_ERROR_COUNT.inc();
+                debug!(
+                    "Unexpected issue reading block: {} from cache: {:?}",
+                    block_id, e
+                );
+                None
+            }
+        }
+    }
+
     /// Note: assumes that the range has been sanitized (i.e. 0 <= start <= end <= length)
     /// TODO: Break this up into more logical pieces, parallelize reads for better performance,
     ///       instrument with cache hit-rates (BHR/OHR), and stream results to callers.
@@ -124,16 +169,7 @@ impl XorbCacheImpl {
             let request = BlockReadRequest::from_block_range(block_range, md.clone());
             let block_id = request.block_range().idx();
             debug!("Block {} requested", block_id);
-            // check cache for block
-            if let Some(mut data) = self.read_block_from_cache(&request).await {
-                let len = data.len();
-                vec.append(&mut data);
-                observe_read(SOURCE_DISK_CACHE, start, len);
-                continue;
-            }
-
-            // load data from remote
-            // expand read to load entire block so we can cache it and merge the request
+            // expand read to the entire block so we can cache it and merge the request
             // with any concurrent reads trying to fetch the same block.
             let full_block = self
                 .block_converter
@@ -143,6 +179,25 @@ impl XorbCacheImpl {
                 })?;
             let full_block_request = BlockReadRequest::from_block_range(full_block, md.clone());
 
+            // check the caches for block, holding whole blocks in memory
+            // so that later reads within them don't go to disk.
+            let use_memory = self.memory.enabled();
+            if use_memory {
+                if let Some((data, source)) = self.read_block_from_memory(&full_block_request).await
+                {
+                    let len = append_sub_range(&mut vec, &request, &data);
+                    observe_read(source, start, len);
+                    continue;
+                }
+            } else if let Some(mut data) = self.read_block_from_cache(&request).await {
+                let len = data.len();
+                vec.append(&mut data);
+                observe_read(SOURCE_DISK_CACHE, start, len);
+                continue;
+            }
+
+            // load data from remote
+            let memory_key = request_to_key(&full_block_request);
             let request_key = format!("remote_{full_block_request}");
             let (res, is_owner) = self
                 .request_merger
@@ -163,13 +218,12 @@ impl XorbCacheImpl {
                 SOURCE_SINGLEFLIGHT
             };
             observe_read(source, start, data.len());
+            if use_memory {
+                self.memory.put(&memory_key, data.clone());
+            }
 
             // return sub-range of data the caller requested
-            if (request.start_off() as usize) < data.len() {
-                let request_end_off = min(request.end_off() as usize, data.len());
-                let return_data = &data[(request.start_off() as usize)..request_end_off];
-                vec.extend(return_data.iter());
-            }
+            append_sub_range(&mut vec, &request, &data);
         }
         debug!("Finished reading data. Read: {} bytes", vec.len());
         Ok(vec)
@@ -212,6 +266,17 @@ impl XorbCacheImpl {
     }
 }
 
+/// Appends the sub-range of the block the request is for, returning its length.
+fn append_sub_range(vec: &mut Vec<u8>, request: &BlockReadRequest, data: &[u8]) -> usize {
+    if (request.start_off() as usize) >= data.len() {
+        return 0;
+    }
+    let request_end_off = min(request.end_off() as usize, data.len());
+    let return_data = &data[(request.start_off() as usize)..request_end_off];
+    vec.extend_from_slice(return_data);
+    return_data.len()
+}
+
 fn observe_read(source: &str, start: SystemTime, size: usize) {
     let labels = [source];
     BLOCKS_READ.with_label_values(&labels).inc();
@@ -447,4 +512,39 @@ mod test {
         assert_eq!(1, times_called.load(Ordering::SeqCst));
         assert_eq!(1, dir.get_entries().len());
     }
+
+    #[tokio::test]
+    async fn test_read_through_memory() {
+        let mock_remote = FetchRecorder::default();
+        let times_called = mock_remote.times_called.clone();
+        let (_dir, test_xc) = new_test_xc("__tmp_xorb_memory", 143, 457, Arc::new(mock_remote));
+        let memory: &'static MemoryCache = Box::leak(Box::new(MemoryCache::new(1000)));
+        let test_xc = test_xc.with_memory_cache(memory);
+
+        let test_key = Key::default();
+
+        let res = test_xc
+            .fetch_xorb_range(&test_key, 0..30, Some(57))
+            .await
+            .unwrap();
+        assert_eq!(res, (0..30).collect::<Vec<u8>>());
+        assert_eq!(1, times_called.load(Ordering::SeqCst));
+
+        // The whole block was kept in memory, so other ranges of it are served
+        // from there.
+        let full_block = BlockReadRequest::from_block_range(
+            test_xc
+                .block_converter
+                .get_full_block_range_for(0, Some(57))
+                .unwrap(),
+            Arc::new(FileMetadata::new(test_key.to_string(), Some(57), 1)),
+        );
+        assert_eq!(memory.get(&request_to_key(&full_block)).unwrap().len(), 57);
+        let res = test_xc
+            .fetch_xorb_range(&test_key, 30..47, None)
+            .await
+            .unwrap();
+        assert_eq!(res, (30..47).collect::<Vec<u8>>());
+        assert_eq!(1, times_called.load(Ordering::SeqCst));
+    }
 }
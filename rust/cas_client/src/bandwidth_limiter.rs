@@ -0,0 +1,116 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+use tracing::info;
+
+lazy_static! {
+    /// The limiter shared by every transfer this process makes to the CAS,
+    /// so that a limit set at runtime applies to all of them.
+    pub static ref CAS_BANDWIDTH_LIMITER: BandwidthLimiter = BandwidthLimiter::new(None);
+}
+
+/// Limits the bytes per second transferred to and from the CAS.
+///
+/// Transfers are not split, so a transfer larger than the bytes available
+/// is let through and the debt is paid off by delaying the ones after it.
+/// This keeps the average rate at the limit.
+#[derive(Debug)]
+pub struct BandwidthLimiter {
+    state: Mutex<LimiterState>,
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    limit: Option<u64>,
+    available: f64,
+    last_refill: Instant,
+    total_bytes: u64,
+}
+
+impl BandwidthLimiter {
+    pub fn new(limit: Option<u64>) -> Self {
+        Self {
+            state: Mutex::new(LimiterState {
+                limit,
+                available: 0.0,
+                last_refill: Instant::now(),
+                total_bytes: 0,
+            }),
+        }
+    }
+
+    /// The limit in bytes per second, or None if unlimited.
+    pub fn limit(&self) -> Option<u64> {
+        self.state.lock().unwrap().limit
+    }
+
+    /// Sets the limit in bytes per second, None or 0 removing it. Applies
+    /// to transfers already waiting.
+    pub fn set_limit(&self, limit: Option<u64>) {
+        let limit = limit.filter(|&l| l > 0);
+        let mut state = self.state.lock().unwrap();
+        state.limit = limit;
+        state.available = state.available.min(0.0);
+        state.last_refill = Instant::now();
+        info!("CAS bandwidth limit set to {limit:?} bytes/s");
+    }
+
+    /// The total bytes transferred through the limiter.
+    pub fn total_bytes(&self) -> u64 {
+        self.state.lock().unwrap().total_bytes
+    }
+
+    /// Waits until bytes may be transferred.
+    pub async fn consume(&self, bytes: usize) {
+        while let Some(wait) = self.try_consume(bytes, Instant::now()) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Takes bytes if the limiter is not in debt at now, otherwise returns
+    /// how long to wait before trying again.
+    fn try_consume(&self, bytes: usize, now: Instant) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(limit) = state.limit {
+            let limit = limit as f64;
+            let elapsed = now.saturating_duration_since(state.last_refill);
+            // At most one second of transfer can be saved up while idle.
+            state.available = (state.available + elapsed.as_secs_f64() * limit).min(limit);
+            state.last_refill = now;
+            if state.available < 0.0 {
+                return Some(Duration::from_secs_f64(-state.available / limit));
+            }
+            state.available -= bytes as f64;
+        }
+        state.total_bytes += bytes as u64;
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bandwidth_limit() {
+        let limiter = BandwidthLimiter::new(None);
+        let now = Instant::now();
+        assert!(limiter.try_consume(1 << 30, now).is_none());
+
+        limiter.set_limit(Some(1000));
+        let now = Instant::now();
+        // One second of transfer is let through, putting the limiter in debt.
+        let later = now + Duration::from_secs(1);
+        assert!(limiter.try_consume(3000, later).is_none());
+        assert_eq!(limiter.try_consume(1, later), Some(Duration::from_secs(2)));
+        assert!(limiter
+            .try_consume(1, later + Duration::from_secs(2))
+            .is_none());
+
+        limiter.set_limit(Some(0));
+        assert_eq!(limiter.limit(), None);
+        assert!(limiter.try_consume(1, Instant::now()).is_none());
+        assert_eq!(limiter.total_bytes(), (1 << 30) + 3002);
+    }
+}
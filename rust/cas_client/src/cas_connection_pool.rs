@@ -48,6 +48,8 @@ pub struct CasConnectionConfig {
     pub repo_paths: String,
     pub git_xet_version: String,
     pub root_ca: Option<Arc<String>>,
+    /// The token a CAS proxy requires of its clients, if any.
+    pub proxy_token: Option<String>,
 }
 
 impl CasConnectionConfig {
@@ -66,6 +68,7 @@ impl CasConnectionConfig {
             repo_paths: serde_json::to_string(&repo_paths).unwrap_or_else(|_| "[]".to_string()),
             git_xet_version,
             root_ca: None,
+            proxy_token: None,
         }
     }
 
@@ -73,6 +76,11 @@ impl CasConnectionConfig {
         self.root_ca = Some(Arc::new(root_ca.into()));
         self
     }
+
+    pub fn with_proxy_token(mut self, proxy_token: Option<String>) -> Self {
+        self.proxy_token = proxy_token;
+        self
+    }
 }
 
 /// to be impl'ed by Connection types (DataTransport, GrpcClient)so that
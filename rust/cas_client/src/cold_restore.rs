@@ -0,0 +1,158 @@
+//! Reads of xorbs the CAS keeps in its cold tier, e.g. after `git xet tier`
+//! moved them there. The CAS refuses the read and starts restoring the
+//! xorb, and the read is retried until the xorb is back, which may take
+//! minutes; the callback set with [set_restore_progress] is told, so that
+//! the user can see a smudge waiting on it is not stuck.
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+use merklehash::MerkleHash;
+use tracing::info;
+
+use crate::error::{CasClientError, Result};
+
+const DEFAULT_RESTORE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+/// How often a restoring xorb is read again if the CAS doesn't say.
+const DEFAULT_RESTORE_POLL: Duration = Duration::from_secs(30);
+
+static RESTORE_TIMEOUT_SECS: AtomicU64 = AtomicU64::new(DEFAULT_RESTORE_TIMEOUT.as_secs());
+
+/// What reads waiting on the cold tier report, once per xorb.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreProgress {
+    /// The CAS is restoring the xorb from its cold tier.
+    Restoring(MerkleHash),
+    /// The xorb was restored, after waiting this long.
+    Restored(MerkleHash, Duration),
+}
+
+type RestoreProgressCallback = Box<dyn Fn(RestoreProgress) + Send + Sync>;
+
+lazy_static! {
+    /// The xorbs reported as being restored, so that the reads of several
+    /// ranges of one xorb report it once.
+    static ref RESTORING: Mutex<HashSet<MerkleHash>> = Mutex::new(HashSet::new());
+    static ref RESTORE_PROGRESS: RwLock<Option<RestoreProgressCallback>> = RwLock::new(None);
+}
+
+/// Sets how long a read waits for a xorb to be restored from the cold tier
+/// before failing with [CasClientError::XORBArchived].
+pub fn set_restore_timeout(timeout: Duration) {
+    RESTORE_TIMEOUT_SECS.store(timeout.as_secs(), Ordering::Relaxed);
+}
+
+/// Sets the callback told when reads start and finish waiting for a xorb to
+/// be restored. Without one, this is only logged.
+pub fn set_restore_progress(progress: impl Fn(RestoreProgress) + Send + Sync + 'static) {
+    *RESTORE_PROGRESS.write().unwrap() = Some(Box::new(progress));
+}
+
+fn report(progress: RestoreProgress) {
+    info!("{progress:?}");
+    if let Some(callback) = RESTORE_PROGRESS.read().unwrap().as_ref() {
+        callback(progress);
+    }
+}
+
+/// Runs read, reading the xorb hash again while the CAS is restoring it.
+pub(crate) async fn read_restoring<T, F, Fut>(hash: &MerkleHash, read: F) -> Result<T>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let timeout = Duration::from_secs(RESTORE_TIMEOUT_SECS.load(Ordering::Relaxed));
+    read_restoring_impl(hash, timeout, read).await
+}
+
+async fn read_restoring_impl<T, F, Fut>(hash: &MerkleHash, timeout: Duration, read: F) -> Result<T>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut started: Option<Instant> = None;
+    loop {
+        match read().await {
+            Err(CasClientError::XORBArchived(_, retry_after)) => {
+                let start = *started.get_or_insert_with(|| {
+                    if RESTORING.lock().unwrap().insert(*hash) {
+                        report(RestoreProgress::Restoring(*hash));
+                    }
+                    Instant::now()
+                });
+                let waited = start.elapsed();
+                if waited >= timeout {
+                    RESTORING.lock().unwrap().remove(hash);
+                    return Err(CasClientError::XORBArchived(*hash, retry_after));
+                }
+                let wait = retry_after.unwrap_or(DEFAULT_RESTORE_POLL);
+                info!("Xorb {hash} is being restored; reading it again in {wait:?}");
+                tokio::time::sleep(wait.min(timeout - waited)).await;
+            }
+            result => {
+                if let (Some(start), Ok(_)) = (started, &result) {
+                    if RESTORING.lock().unwrap().remove(hash) {
+                        report(RestoreProgress::Restored(*hash, start.elapsed()));
+                    }
+                }
+                return result;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[tokio::test]
+    async fn test_read_restoring() {
+        let hash = MerkleHash::default();
+        let reported = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        set_restore_progress(move |p| sink.lock().unwrap().push(p));
+        let reads = AtomicUsize::new(0);
+        let read = || async {
+            if reads.fetch_add(1, Ordering::Relaxed) < 2 {
+                Err(CasClientError::XORBArchived(
+                    hash,
+                    Some(Duration::from_millis(1)),
+                ))
+            } else {
+                Ok(5)
+            }
+        };
+        let value = read_restoring_impl(&hash, Duration::from_secs(10), read)
+            .await
+            .unwrap();
+        assert_eq!(value, 5);
+        assert_eq!(reads.load(Ordering::Relaxed), 3);
+        assert!(!RESTORING.lock().unwrap().contains(&hash));
+        // The user is told once the restore starts and once it's done.
+        let progress = reported.lock().unwrap().clone();
+        assert_eq!(progress.len(), 2);
+        assert_eq!(progress[0], RestoreProgress::Restoring(hash));
+        assert!(matches!(progress[1], RestoreProgress::Restored(h, _) if h == hash));
+
+        // A restore taking longer than the timeout fails the read.
+        let reads = AtomicUsize::new(0);
+        let result: Result<()> = read_restoring_impl(&hash, Duration::ZERO, || async {
+            reads.fetch_add(1, Ordering::Relaxed);
+            Err(CasClientError::XORBArchived(hash, None))
+        })
+        .await;
+        assert!(matches!(result, Err(CasClientError::XORBArchived(..))));
+        assert_eq!(reads.load(Ordering::Relaxed), 1);
+
+        // Other errors are returned at once.
+        let result: Result<()> = read_restoring_impl(&hash, Duration::from_secs(10), || async {
+            Err(CasClientError::XORBNotFound(hash))
+        })
+        .await;
+        assert_eq!(result.unwrap_err(), CasClientError::XORBNotFound(hash));
+    }
+}
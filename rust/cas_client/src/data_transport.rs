@@ -2,16 +2,19 @@ use cas::constants::*;
 use std::time::Duration;
 
 use crate::{
+    bandwidth_limiter::CAS_BANDWIDTH_LIMITER,
     cas_connection_pool::CasConnectionConfig,
     grpc::{get_request_id, trace_forwarding},
     remote_client::CAS_PROTOCOL_VERSION,
+    request_scheduler::{parse_retry_after, CAS_REQUEST_SCHEDULER},
+    transfer_stats::CAS_TRANSFER_STATS,
 };
 use anyhow::{anyhow, Result};
 use http_body_util::{BodyExt, Full};
 use hyper::body::Bytes;
 use hyper::{
-    header::RANGE,
     header::{HeaderMap, HeaderName, HeaderValue},
+    header::{RANGE, RETRY_AFTER},
     Method, Request, Version,
 };
 use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
@@ -83,10 +86,27 @@ fn retry_http_status_code(stat: &hyper::StatusCode) -> bool {
     stat.is_server_error() || *stat == hyper::StatusCode::TOO_MANY_REQUESTS
 }
 
+/// Reports a response to the shared request scheduler. A 429, or a 503
+/// carrying Retry-After, pauses every request to the CAS.
+fn schedule_response(status: hyper::StatusCode, headers: &HeaderMap) {
+    let retry_after = headers
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_retry_after);
+    if status == hyper::StatusCode::TOO_MANY_REQUESTS
+        || (status == hyper::StatusCode::SERVICE_UNAVAILABLE && retry_after.is_some())
+    {
+        CAS_REQUEST_SCHEDULER.throttle(retry_after);
+    } else if status.is_success() {
+        CAS_REQUEST_SCHEDULER.on_success();
+    }
+}
+
 fn is_status_retriable_and_print(err: &RetryError) -> bool {
     let ret = is_status_retriable(err);
     if ret {
         info!("{}. Retrying...", err);
+        CAS_TRANSFER_STATS.add_retry();
     }
     ret
 }
@@ -197,6 +217,9 @@ impl DataTransport {
             .header(cas_protocol_version_header, cas_protocol_version)
             .uri(&dest)
             .version(Version::HTTP_2);
+        if let Some(token) = &self.cas_connection_config.proxy_token {
+            req = req.header(HeaderName::from_static(PROXY_TOKEN_HEADER), token);
+        }
         if trace_forwarding() {
             if let Some(headers) = req.headers_mut() {
                 let mut injector = HeaderInjector(headers);
@@ -223,6 +246,7 @@ impl DataTransport {
                         .setup_request(Method::GET, prefix, hash, None)
                         .map_err(RetryError::from)?;
 
+                    CAS_REQUEST_SCHEDULER.acquire().await;
                     let resp = self
                         .http2_client
                         .request(req)
@@ -233,6 +257,7 @@ impl DataTransport {
                             RetryError::from(e)
                         })?;
 
+                    schedule_response(resp.status(), resp.headers());
                     if retry_http_status_code(&resp.status()) {
                         return Err(RetryError::Status(resp.status()));
                     }
@@ -257,6 +282,8 @@ impl DataTransport {
             .instrument(info_span!("transport.read_body"))
             .await?
             .to_bytes();
+        CAS_BANDWIDTH_LIMITER.consume(bytes.len()).await;
+        CAS_TRANSFER_STATS.add_downloaded(bytes.len());
         Ok(bytes.to_vec())
     }
 
@@ -280,6 +307,7 @@ impl DataTransport {
                             .map_err(RetryError::from)?;
                     req.headers_mut().insert(RANGE, header_value);
 
+                    CAS_REQUEST_SCHEDULER.acquire().await;
                     let resp = self
                         .http2_client
                         .request(req)
@@ -287,6 +315,7 @@ impl DataTransport {
                         .await
                         .map_err(RetryError::from)?;
 
+                    schedule_response(resp.status(), resp.headers());
                     if retry_http_status_code(&resp.status()) {
                         return Err(RetryError::Status(resp.status()));
                     }
@@ -307,6 +336,8 @@ impl DataTransport {
                         .instrument(info_span!("transport.read_body"))
                         .await?
                         .to_bytes();
+                    CAS_BANDWIDTH_LIMITER.consume(bytes.len()).await;
+                    CAS_TRANSFER_STATS.add_downloaded(bytes.len());
                     Ok(bytes.to_vec())
                 },
                 is_status_retriable_and_print,
@@ -319,6 +350,7 @@ impl DataTransport {
 
     // Single put to the H2 server
     pub async fn put(&self, prefix: &str, hash: &MerkleHash, data: &[u8]) -> Result<()> {
+        CAS_BANDWIDTH_LIMITER.consume(data.len()).await;
         let resp = self
             .retry_strategy
             .retry(
@@ -327,6 +359,7 @@ impl DataTransport {
                         .setup_request(Method::POST, prefix, hash, Some(data.to_owned()))
                         .map_err(RetryError::from)?;
 
+                    CAS_REQUEST_SCHEDULER.acquire().await;
                     let resp = self
                         .http2_client
                         .request(req)
@@ -334,6 +367,7 @@ impl DataTransport {
                         .await
                         .map_err(RetryError::from)?;
 
+                    schedule_response(resp.status(), resp.headers());
                     if retry_http_status_code(&resp.status()) {
                         return Err(RetryError::Status(resp.status()));
                     }
@@ -352,6 +386,7 @@ impl DataTransport {
             ));
         }
         debug!("Received Response from HTTP2 POST: {}", status);
+        CAS_TRANSFER_STATS.add_uploaded(data.len());
 
         Ok(())
     }
@@ -406,6 +441,7 @@ mod tests {
             repo_paths: "repo".to_string(),
             git_xet_version: "0.1.0".to_string(),
             root_ca: None,
+            proxy_token: None,
         }
         .with_root_ca(CERT.serialize_pem().unwrap());
         let dt = DataTransport::from_config(config).await.unwrap();
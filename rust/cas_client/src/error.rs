@@ -1,6 +1,8 @@
 use cache::CacheError;
 use http::uri::InvalidUri;
 use merklehash::MerkleHash;
+use std::path::PathBuf;
+use std::time::Duration;
 use tonic::metadata::errors::InvalidMetadataValue;
 use xet_error::Error;
 
@@ -45,6 +47,9 @@ pub enum CasClientError {
     #[error("CAS Hash not found")]
     XORBNotFound(MerkleHash),
 
+    #[error("Xorb {0} is in cold storage and not restored yet; try again later.")]
+    XORBArchived(MerkleHash, Option<Duration>),
+
     #[error("Data transfer timeout")]
     DataTransferTimeout,
 
@@ -59,6 +64,9 @@ pub enum CasClientError {
 
     #[error("Runtime Error (Temp files): {0}")]
     RuntimeErrorTempFileError(#[from] tempfile::PersistError),
+
+    #[error("Out of space staging data in {0:?}: {1} bytes free, {2} needed. Free up space, or set staging.path to a larger volume.")]
+    StagingOutOfSpace(PathBuf, u64, u64),
 }
 
 // Define our own result type here (this seems to be the standard).
@@ -1,5 +1,6 @@
 use crate::error::Result;
 use std::env::VarError;
+use std::future::Future;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
@@ -7,6 +8,8 @@ use std::time::Duration;
 
 use crate::cas_connection_pool::CasConnectionConfig;
 use crate::remote_client::CAS_PROTOCOL_VERSION;
+use crate::request_scheduler::{parse_retry_after, CAS_REQUEST_SCHEDULER};
+use crate::transfer_stats::CAS_TRANSFER_STATS;
 use http::Uri;
 use opentelemetry::propagation::{Injector, TextMapPropagator};
 use retry_strategy::RetryStrategy;
@@ -38,6 +41,9 @@ const DEFAULT_PUT_COMPLETE_PORT: u16 = 5000;
 const HTTP2_KEEPALIVE_TIMEOUT_SEC: u64 = 20;
 const HTTP2_KEEPALIVE_INTERVAL_SEC: u64 = 1;
 const NUM_RETRIES: usize = 5;
+const RETRY_AFTER_METADATA: &str = "retry-after";
+const TIER_METADATA: &str = "xet-tier";
+const COLD_TIER: &str = "cold";
 const BASE_RETRY_DELAY_MS: u64 = 3000;
 
 // production ready settings
@@ -132,6 +138,12 @@ impl Interceptor for MetadataHeaderInterceptor {
         let repo_paths = get_repo_paths_metadata_value(&self.config.repo_paths);
         metadata.insert_bin(REPO_PATHS_HEADER, repo_paths);
 
+        if let Some(token) = &self.config.proxy_token {
+            let token = MetadataValue::from_str(token)
+                .map_err(|e| Status::internal(format!("Metadata error: {e:?}")))?;
+            metadata.insert(PROXY_TOKEN_HEADER, token);
+        }
+
         let git_xet_version =
             get_metadata_ascii_from_str_with_default(&self.config.git_xet_version, DEFAULT_VERSION);
         metadata.insert(GIT_XET_VERSION_HEADER, git_xet_version);
@@ -269,10 +281,60 @@ pub fn is_status_retriable(err: &Status) -> bool {
     }
 }
 
+/// Sends a request once the shared request scheduler allows it. A
+/// ResourceExhausted status, or an Unavailable status carrying retry-after
+/// metadata, pauses every request to the CAS.
+async fn scheduled<T>(
+    request: impl Future<Output = std::result::Result<T, Status>>,
+) -> std::result::Result<T, Status> {
+    CAS_REQUEST_SCHEDULER.acquire().await;
+    let result = request.await;
+    match &result {
+        Ok(_) => CAS_REQUEST_SCHEDULER.on_success(),
+        Err(status) => {
+            let retry_after = status
+                .metadata()
+                .get(RETRY_AFTER_METADATA)
+                .and_then(|v| v.to_str().ok())
+                .and_then(parse_retry_after);
+            match status.code() {
+                Code::ResourceExhausted => CAS_REQUEST_SCHEDULER.throttle(retry_after),
+                Code::Unavailable if retry_after.is_some() => {
+                    CAS_REQUEST_SCHEDULER.throttle(retry_after)
+                }
+                _ => {}
+            }
+        }
+    }
+    result
+}
+
+/// The error of a read of a xorb the CAS keeps in its cold tier: a
+/// FailedPrecondition status with the xet-tier metadata set to "cold". The
+/// CAS starts restoring the xorb when it first refuses a read of it, and
+/// may set the retry-after metadata, in seconds, to say when to read it
+/// again; reads are otherwise retried every 30 seconds.
+/// Reads of a xorb being restored are refused the same way until it is
+/// back. A FailedPrecondition without the xet-tier metadata, or with any
+/// other value, is an ordinary error.
+fn archived_error(status: &Status, hash: &MerkleHash) -> Option<CasClientError> {
+    let tier = status.metadata().get(TIER_METADATA)?.to_str().ok()?;
+    if status.code() != Code::FailedPrecondition || tier != COLD_TIER {
+        return None;
+    }
+    let retry_after = status
+        .metadata()
+        .get(RETRY_AFTER_METADATA)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_retry_after);
+    Some(CasClientError::XORBArchived(*hash, retry_after))
+}
+
 pub fn is_status_retriable_and_print(err: &Status) -> bool {
     let ret = is_status_retriable(err);
     if ret {
         info!("GRPC Error {}. Retrying...", err);
+        CAS_TRANSFER_STATS.add_retry();
     }
     ret
 }
@@ -308,6 +370,7 @@ impl GrpcClient {
             hash,
             data.len(),
         );
+        let len = data.len();
         let request = PutRequest {
             key: Some(get_key_for_request(prefix, hash)),
             data,
@@ -319,7 +382,7 @@ impl GrpcClient {
             .retry(
                 || async {
                     let req = Request::new(request.clone());
-                    self.client.clone().put(req).await
+                    scheduled(self.client.clone().put(req)).await
                 },
                 is_status_retriable_and_print,
             )
@@ -342,6 +405,7 @@ impl GrpcClient {
             prefix,
             hash,
         );
+        CAS_TRANSFER_STATS.add_uploaded(len);
 
         if !response.into_inner().was_inserted {
             info!(
@@ -383,13 +447,16 @@ impl GrpcClient {
             .retry(
                 || async {
                     let req = Request::new(request.clone());
-                    self.client.clone().initiate(req).await
+                    scheduled(self.client.clone().initiate(req)).await
                 },
                 is_status_retriable_and_print,
             )
             .await
             .map_err(print_final_retry_error)
-            .map_err(|e| CasClientError::Grpc(anyhow::Error::from(e)))?;
+            .map_err(|e| {
+                archived_error(&e, hash)
+                    .unwrap_or_else(|| CasClientError::Grpc(anyhow::Error::from(e)))
+            })?;
 
         let InitiateResponse {
             data_plane_endpoint,
@@ -449,7 +516,7 @@ impl GrpcClient {
             .retry(
                 || async {
                     let req = Request::new(request.clone());
-                    self.client.clone().put_complete(req).await
+                    scheduled(self.client.clone().put_complete(req)).await
                 },
                 is_status_retriable_and_print,
             )
@@ -484,7 +551,7 @@ impl GrpcClient {
             .retry(
                 || async {
                     let req = Request::new(request.clone());
-                    self.client.clone().get(req).await
+                    scheduled(self.client.clone().get(req)).await
                 },
                 is_status_retriable_and_print,
             )
@@ -508,7 +575,9 @@ impl GrpcClient {
             hash
         );
 
-        Ok(response.into_inner().data)
+        let data = response.into_inner().data;
+        CAS_TRANSFER_STATS.add_downloaded(data.len());
+        Ok(data)
     }
 
     #[tracing::instrument(skip_all, name = "cas.client", err, fields(prefix = prefix, hash = hash.hex().as_str(), api = "get_range", request_id = tracing::field::Empty))]
@@ -544,7 +613,7 @@ impl GrpcClient {
             .retry(
                 || async {
                     let req = Request::new(request.clone());
-                    self.client.clone().get_range(req).await
+                    scheduled(self.client.clone().get_range(req)).await
                 },
                 is_status_retriable_and_print,
             )
@@ -568,7 +637,9 @@ impl GrpcClient {
             hash
         );
 
-        Ok(response.into_inner().data)
+        let data = response.into_inner().data;
+        CAS_TRANSFER_STATS.add_downloaded(data.len());
+        Ok(data)
     }
 
     #[tracing::instrument(skip_all, name = "cas.client", fields(prefix = prefix, hash = hash.hex().as_str(), api = "get_length", request_id = tracing::field::Empty))]
@@ -590,7 +661,7 @@ impl GrpcClient {
                 || async {
                     let req = Request::new(request.clone());
 
-                    self.client.clone().head(req).await
+                    scheduled(self.client.clone().head(req)).await
                 },
                 is_status_retriable_and_print,
             )
@@ -636,6 +707,31 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_archived_error() {
+        let hash = MerkleHash::default();
+        let mut status = Status::failed_precondition("restoring");
+        assert!(archived_error(&status, &hash).is_none());
+
+        status
+            .metadata_mut()
+            .insert(TIER_METADATA, COLD_TIER.parse().unwrap());
+        status
+            .metadata_mut()
+            .insert(RETRY_AFTER_METADATA, "120".parse().unwrap());
+        assert!(matches!(
+            archived_error(&status, &hash),
+            Some(CasClientError::XORBArchived(h, Some(d))) if h == hash && d == Duration::from_secs(120)
+        ));
+
+        // Only reads refused for the tier are restores.
+        let mut status = Status::not_found("missing");
+        status
+            .metadata_mut()
+            .insert(TIER_METADATA, COLD_TIER.parse().unwrap());
+        assert!(archived_error(&status, &hash).is_none());
+    }
+
     #[tokio::test]
     async fn test_put_with_retry() {
         let count = Arc::new(AtomicU32::new(0));
@@ -1,28 +1,43 @@
 #![cfg_attr(feature = "strict", deny(warnings))]
 
 pub use crate::error::CasClientError;
+pub use bandwidth_limiter::{BandwidthLimiter, CAS_BANDWIDTH_LIMITER};
+pub use cache::MEMORY_CACHE as CAS_MEMORY_CACHE;
 pub use caching_client::CachingClient;
+pub use cold_restore::{set_restore_progress, set_restore_timeout, RestoreProgress};
 pub use grpc::set_trace_forwarding;
 pub use grpc::GrpcClient;
 pub use interface::Client;
-pub use local_client::LocalClient;
+pub use local_client::{validate_root_hash, LocalClient};
 pub use merklehash::MerkleHash; // re-export since this is required for the client API.
+pub use mirror_client::{MirrorClient, Replication};
 pub use passthrough_staging_client::PassthroughStagingClient;
 pub use remote_client::RemoteClient;
 pub use remote_client::CAS_PROTOCOL_VERSION;
-pub use staging_client::{new_staging_client, new_staging_client_with_progressbar, StagingClient};
+pub use request_scheduler::{RequestScheduler, CAS_REQUEST_SCHEDULER};
+pub use staging_client::{
+    new_staging_client, new_staging_client_with_progressbar, set_staging_min_free, StagingClient,
+};
 pub use staging_trait::{Staging, StagingBypassable};
+pub use transfer_stats::{TransferCounts, TransferStats, CAS_TRANSFER_STATS};
+pub use upload_pipeline::UploadConcurrency;
 
+mod bandwidth_limiter;
 mod caching_client;
 mod cas_connection_pool;
 mod client_adapter;
+mod cold_restore;
 mod data_transport;
 mod error;
 pub mod grpc;
 mod interface;
 mod local_client;
+mod mirror_client;
 mod passthrough_staging_client;
 mod remote_client;
+mod request_scheduler;
 mod staging_client;
 mod staging_trait;
+mod transfer_stats;
+mod upload_pipeline;
 mod util;
@@ -3,9 +3,10 @@ use crate::interface::Client;
 use anyhow::anyhow;
 use async_trait::async_trait;
 use cas::key::Key;
+use merkledb::aggregate_hashes::cas_node_hash_with_algorithm;
 use merkledb::prelude::*;
 use merkledb::{Chunk, MerkleMemDB};
-use merklehash::MerkleHash;
+use merklehash::{HashAlgorithm, MerkleHash};
 use std::fs::{metadata, File};
 use std::io::{BufReader, BufWriter, Read, Seek, Write};
 use std::path::{Path, PathBuf};
@@ -20,6 +21,9 @@ pub struct LocalClient {
     tempdir: Option<TempDir>,
     pub path: PathBuf,
     pub silence_errors: bool,
+    /// The hash algorithm of the repository, which the Merkle root of each
+    /// xorb put must be computed with.
+    pub hash_algorithm: HashAlgorithm,
 }
 impl Default for LocalClient {
     /// Creates a default local client that writes to a temporary directory
@@ -31,6 +35,7 @@ impl Default for LocalClient {
             tempdir: Some(tempdir),
             path,
             silence_errors: false,
+            hash_algorithm: HashAlgorithm::default(),
         }
     }
 }
@@ -50,14 +55,27 @@ impl LocalClient {
             tempdir: None,
             path: path.to_path_buf(),
             silence_errors,
+            hash_algorithm: HashAlgorithm::default(),
         }
     }
 
-    /// Internal function to get the path for a given hash entry
-    fn get_path_for_entry(&self, prefix: &str, hash: &MerkleHash) -> PathBuf {
+    /// Validates the xorbs put against hash_algorithm instead of the default.
+    pub fn with_hash_algorithm(mut self, hash_algorithm: HashAlgorithm) -> LocalClient {
+        self.hash_algorithm = hash_algorithm;
+        self
+    }
+
+    /// Returns the path of the file holding the given entry
+    pub fn get_path_for_entry(&self, prefix: &str, hash: &MerkleHash) -> PathBuf {
         self.path.join(format!("{}.{}", prefix, hash.hex()))
     }
 
+    /// Returns the path of the file locked while entries are uploaded or
+    /// deleted, next to the directory so it isn't listed as an entry.
+    pub fn get_lock_path(&self) -> PathBuf {
+        self.path.with_extension("lock")
+    }
+
     /// File format handling Functions
     ///
     /// The local disk format for each Xorb is:
@@ -202,13 +220,43 @@ impl LocalClient {
     }
 }
 
-fn validate_root_hash(data: &[u8], chunk_boundaries: &[u64], hash: &MerkleHash) -> bool {
+/// Checks that the Merkle root of data, split into chunks at chunk_boundaries,
+/// is hash, as computed by hash_algorithm.
+pub fn validate_root_hash(
+    data: &[u8],
+    chunk_boundaries: &[u64],
+    hash: &MerkleHash,
+    hash_algorithm: HashAlgorithm,
+) -> bool {
     // at least 1 chunk, and last entry in chunk boundary must match the length
     if chunk_boundaries.is_empty()
         || chunk_boundaries[chunk_boundaries.len() - 1] as usize != data.len()
     {
         return false;
     }
+    match hash_algorithm {
+        HashAlgorithm::Xet => validate_default_root_hash(data, chunk_boundaries, hash),
+        _ => root_hash_with_algorithm(data, chunk_boundaries, hash_algorithm) == Some(*hash),
+    }
+}
+
+fn root_hash_with_algorithm(
+    data: &[u8],
+    chunk_boundaries: &[u64],
+    hash_algorithm: HashAlgorithm,
+) -> Option<MerkleHash> {
+    let mut chunks = Vec::with_capacity(chunk_boundaries.len());
+    let mut left_edge: usize = 0;
+    for i in chunk_boundaries {
+        let right_edge = *i as usize;
+        let hash = hash_algorithm.data_hash(&data[left_edge..right_edge]);
+        chunks.push((hash, (left_edge, right_edge)));
+        left_edge = right_edge;
+    }
+    cas_node_hash_with_algorithm(&chunks, hash_algorithm).ok()
+}
+
+fn validate_default_root_hash(data: &[u8], chunk_boundaries: &[u64], hash: &MerkleHash) -> bool {
     let mut chunks: Vec<Chunk> = Vec::new();
     let mut left_edge: usize = 0;
     for i in chunk_boundaries {
@@ -249,7 +297,7 @@ impl Client for LocalClient {
             return Err(CasClientError::InvalidArguments);
         }
         // validate hash
-        if !validate_root_hash(&data, &chunk_boundaries, hash) {
+        if !validate_root_hash(&data, &chunk_boundaries, hash, self.hash_algorithm) {
             return Err(CasClientError::HashMismatch);
         }
         if let Ok(xorb_size) = self.get_length(prefix, hash).await {
@@ -459,6 +507,27 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_long_path_read_write() {
+        // Staging directories deeper than the 260 characters of MAX_PATH on
+        // Windows.
+        let tempdir = TempDir::new().unwrap();
+        let path = tempdir
+            .path()
+            .join("d".repeat(100))
+            .join("e".repeat(100))
+            .join("f".repeat(100));
+        std::fs::create_dir_all(&path).unwrap();
+        let client = LocalClient::new(&path, false);
+        let hello = "hello world".as_bytes().to_vec();
+        let hello_hash = merklehash::compute_data_hash(&hello[..]);
+        client
+            .put("key", &hello_hash, hello.clone(), vec![hello.len() as u64])
+            .await
+            .unwrap();
+        assert_eq!(hello, client.get("key", &hello_hash).await.unwrap());
+    }
+
     #[tokio::test]
     async fn test_failures() {
         let client = LocalClient::default();
@@ -599,4 +668,30 @@ mod tests {
             .await
             .unwrap();
     }
+
+    #[tokio::test]
+    async fn test_put_with_hash_algorithms() {
+        let hello = "hello world".as_bytes().to_vec();
+        for algorithm in [HashAlgorithm::Blake3, HashAlgorithm::Sha256] {
+            let chunk_hash = algorithm.data_hash(&hello[..]);
+            let hash =
+                cas_node_hash_with_algorithm(&[(chunk_hash, (0, hello.len()))], algorithm).unwrap();
+
+            // Only the algorithm of the repository is accepted.
+            let client = LocalClient::default();
+            assert!(matches!(
+                client
+                    .put("key", &hash, hello.clone(), vec![hello.len() as u64])
+                    .await,
+                Err(CasClientError::HashMismatch)
+            ));
+
+            let client = LocalClient::default().with_hash_algorithm(algorithm);
+            client
+                .put("key", &hash, hello.clone(), vec![hello.len() as u64])
+                .await
+                .unwrap();
+            assert_eq!(hello, client.get("key", &hash).await.unwrap());
+        }
+    }
 }
@@ -0,0 +1,452 @@
+use futures::future::{join_all, BoxFuture};
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+use tracing::{debug, info, warn};
+
+use async_trait::async_trait;
+
+use merklehash::MerkleHash;
+
+use crate::error::{CasClientError, Result};
+use crate::interface::Client;
+
+const MIRROR_MAX_CONCURRENT_PUTS: usize = 16;
+
+// Reads are hedged after the p95 latency of the last LATENCY_WINDOW reads,
+// or DEFAULT_HEDGE_DELAY until there are MIN_LATENCY_SAMPLES of them.
+const HEDGE_LATENCY_PERCENTILE: f64 = 0.95;
+const LATENCY_WINDOW: usize = 256;
+const MIN_LATENCY_SAMPLES: usize = 16;
+const DEFAULT_HEDGE_DELAY: Duration = Duration::from_millis(500);
+
+type FutureCollectionType = FuturesUnordered<BoxFuture<'static, (String, Result<()>)>>;
+
+/// How a [MirrorClient] writes XORBs to its remotes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Replication {
+    /// A put goes to every remote, and fails if any of them fails.
+    #[default]
+    All,
+    /// A put goes to the primary remote; the mirrors are written in the
+    /// background, and their failures are logged rather than returned.
+    Async,
+}
+
+/// A Client replicating XORBs across several CAS remotes: a primary one and
+/// its mirrors. Writes follow the [Replication]; reads try the remotes in
+/// turn, starting from a preferred one (e.g. a mirror on the local network),
+/// until one has the XORB, optionally hedging slow reads; see
+/// [MirrorClient::with_hedged_reads].
+#[derive(Debug)]
+pub struct MirrorClient {
+    // The remotes by name, the primary first.
+    remotes: Vec<(String, Arc<dyn Client + Sync + Send>)>,
+    // The indices into remotes in the order reads try them.
+    read_order: Vec<usize>,
+    replication: Replication,
+    mirror_puts: Mutex<FutureCollectionType>,
+    latencies: LatencyTracker,
+    // Bounds the hedged requests in flight; None if reads aren't hedged.
+    hedge_permits: Option<Arc<Semaphore>>,
+}
+
+/// The latencies of the most recent successful reads.
+#[derive(Debug, Default)]
+struct LatencyTracker {
+    samples: std::sync::Mutex<VecDeque<Duration>>,
+}
+
+impl LatencyTracker {
+    fn record(&self, latency: Duration) {
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() == LATENCY_WINDOW {
+            samples.pop_front();
+        }
+        samples.push_back(latency);
+    }
+
+    /// The latency below which the fraction p of the recent reads were,
+    /// if there are enough of them to tell.
+    fn percentile(&self, p: f64) -> Option<Duration> {
+        let mut samples: Vec<Duration> = self.samples.lock().unwrap().iter().copied().collect();
+        if samples.len() < MIN_LATENCY_SAMPLES {
+            return None;
+        }
+        samples.sort_unstable();
+        let index = ((samples.len() - 1) as f64 * p).round() as usize;
+        Some(samples[index])
+    }
+}
+
+impl MirrorClient {
+    /// Creates a client over the named remotes, the primary first. Reads
+    /// start from the remote named fetch_first, or the primary if None.
+    pub fn new(
+        remotes: Vec<(String, Arc<dyn Client + Sync + Send>)>,
+        fetch_first: Option<&str>,
+        replication: Replication,
+    ) -> Result<MirrorClient> {
+        if remotes.is_empty() {
+            return Err(CasClientError::ConfigurationError(
+                "A mirrored CAS needs at least one remote".to_string(),
+            ));
+        }
+        let first = match fetch_first {
+            Some(name) => remotes.iter().position(|(n, _)| n == name).ok_or_else(|| {
+                CasClientError::ConfigurationError(format!("Unknown CAS remote {name}"))
+            })?,
+            None => 0,
+        };
+        let read_order = std::iter::once(first)
+            .chain((0..remotes.len()).filter(|i| *i != first))
+            .collect();
+        Ok(MirrorClient {
+            remotes,
+            read_order,
+            replication,
+            mirror_puts: Mutex::new(FutureCollectionType::new()),
+            latencies: LatencyTracker::default(),
+            hedge_permits: None,
+        })
+    }
+
+    /// Hedges reads: when a read takes longer than the p95 latency of
+    /// recent reads, the next remote is queried too and the first to answer
+    /// is used. At most max_inflight hedged requests run at once across all
+    /// reads of this client; 0 disables hedging.
+    pub fn with_hedged_reads(mut self, max_inflight: usize) -> MirrorClient {
+        self.hedge_permits = (max_inflight > 0).then(|| Arc::new(Semaphore::new(max_inflight)));
+        self
+    }
+
+    // The time after which a read is hedged.
+    fn hedge_delay(&self) -> Duration {
+        self.latencies
+            .percentile(HEDGE_LATENCY_PERCENTILE)
+            .unwrap_or(DEFAULT_HEDGE_DELAY)
+    }
+
+    /// Runs read against the remotes in the read order until one succeeds,
+    /// returning the last error if none does. A remote that fails is failed
+    /// over from at once; one that is slow is hedged, if enabled.
+    async fn read_with_failover<'a, T: Send + 'a>(
+        &'a self,
+        hash: &MerkleHash,
+        read: impl Fn(&'a Arc<dyn Client + Sync + Send>) -> BoxFuture<'a, Result<T>>,
+    ) -> Result<T> {
+        let mut remaining = self.read_order.iter().copied();
+        let mut inflight = FuturesUnordered::new();
+        let start = |i: usize, permit: Option<OwnedSemaphorePermit>| {
+            let request = read(&self.remotes[i].1);
+            Box::pin(async move {
+                let _permit = permit;
+                let started = Instant::now();
+                (i, request.await, started.elapsed())
+            })
+        };
+
+        let mut last_error = None;
+        loop {
+            if inflight.is_empty() {
+                let Some(i) = remaining.next() else {
+                    break;
+                };
+                inflight.push(start(i, None));
+            }
+
+            let hedge = match &self.hedge_permits {
+                Some(permits) if remaining.len() > 0 => Some(permits),
+                _ => None,
+            };
+            let delay = self.hedge_delay();
+            tokio::select! {
+                Some((i, res, latency)) = inflight.next() => {
+                    let name = &self.remotes[i].0;
+                    match res {
+                        Ok(v) => {
+                            self.latencies.record(latency);
+                            return Ok(v);
+                        }
+                        Err(CasClientError::XORBNotFound(_)) => {
+                            debug!("XORB {hash} not found on CAS remote {name}");
+                            last_error = Some(CasClientError::XORBNotFound(*hash));
+                        }
+                        Err(e) => {
+                            warn!("Unable to read XORB {hash} from CAS remote {name}: {e}");
+                            last_error = Some(e);
+                        }
+                    }
+                }
+                _ = tokio::time::sleep(delay), if hedge.is_some() => {
+                    // Without a permit, the read waits for the requests in
+                    // flight, trying again after another delay.
+                    if let Some(permit) = hedge.and_then(|p| p.clone().try_acquire_owned().ok()) {
+                        if let Some(i) = remaining.next() {
+                            debug!("Hedging read of XORB {hash} after {delay:?} with CAS remote {}", &self.remotes[i].0);
+                            inflight.push(start(i, Some(permit)));
+                        }
+                    }
+                }
+            }
+        }
+        Err(last_error.unwrap_or(CasClientError::XORBNotFound(*hash)))
+    }
+
+    // Waits for background mirror puts until at most max are running,
+    // logging the failures.
+    async fn drain_mirror_puts(&self, max: usize) {
+        let mut mirror_puts = self.mirror_puts.lock().await;
+        while mirror_puts.len() > max {
+            if let Some((name, Err(e))) = mirror_puts.next().await {
+                warn!("Unable to copy a XORB to CAS remote {name}: {e}");
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Client for MirrorClient {
+    async fn put(
+        &self,
+        prefix: &str,
+        hash: &MerkleHash,
+        data: Vec<u8>,
+        chunk_boundaries: Vec<u64>,
+    ) -> Result<()> {
+        match self.replication {
+            Replication::All => {
+                let puts = self.remotes.iter().map(|(name, client)| {
+                    let data = data.clone();
+                    let chunk_boundaries = chunk_boundaries.clone();
+                    async move { (name, client.put(prefix, hash, data, chunk_boundaries).await) }
+                });
+                for (name, res) in join_all(puts).await {
+                    if let Err(e) = res {
+                        info!("Error writing XORB {hash} to CAS remote {name}.");
+                        return Err(e);
+                    }
+                }
+                Ok(())
+            }
+            Replication::Async => {
+                let (_, primary) = &self.remotes[0];
+                primary
+                    .put(prefix, hash, data.clone(), chunk_boundaries.clone())
+                    .await?;
+
+                self.drain_mirror_puts(MIRROR_MAX_CONCURRENT_PUTS - 1).await;
+                let mut mirror_puts = self.mirror_puts.lock().await;
+                for (name, client) in &self.remotes[1..] {
+                    let name = name.clone();
+                    let client = client.clone();
+                    let prefix = prefix.to_string();
+                    let hash = *hash;
+                    let data = data.clone();
+                    let chunk_boundaries = chunk_boundaries.clone();
+                    mirror_puts.push(Box::pin(async move {
+                        let res = client.put(&prefix, &hash, data, chunk_boundaries).await;
+                        (name, res)
+                    }));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.drain_mirror_puts(0).await;
+        let flushes = join_all(self.remotes.iter().map(|(_, c)| c.flush())).await;
+        for (i, ((name, _), res)) in self.remotes.iter().zip(flushes).enumerate() {
+            match res {
+                // Mirrors written in the background don't fail the flush.
+                Err(e) if self.replication == Replication::Async && i > 0 => {
+                    warn!("Unable to copy XORBs to CAS remote {name}: {e}");
+                }
+                Err(e) => return Err(e),
+                Ok(()) => {}
+            }
+        }
+        Ok(())
+    }
+
+    async fn get(&self, prefix: &str, hash: &MerkleHash) -> Result<Vec<u8>> {
+        self.read_with_failover(hash, |c| c.get(prefix, hash)).await
+    }
+
+    async fn get_object_range(
+        &self,
+        prefix: &str,
+        hash: &MerkleHash,
+        ranges: Vec<(u64, u64)>,
+    ) -> Result<Vec<Vec<u8>>> {
+        self.read_with_failover(hash, |c| c.get_object_range(prefix, hash, ranges.clone()))
+            .await
+    }
+
+    async fn get_length(&self, prefix: &str, hash: &MerkleHash) -> Result<u64> {
+        self.read_with_failover(hash, |c| c.get_length(prefix, hash))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LocalClient;
+
+    fn remotes() -> Vec<(String, Arc<dyn Client + Sync + Send>)> {
+        vec![
+            ("primary".to_string(), Arc::new(LocalClient::default())),
+            ("onprem".to_string(), Arc::new(LocalClient::default())),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_replication() {
+        let hello = "hello world".as_bytes().to_vec();
+        let hello_hash = merklehash::compute_data_hash(&hello[..]);
+
+        for replication in [Replication::All, Replication::Async] {
+            let remotes = remotes();
+            let client = MirrorClient::new(remotes.clone(), None, replication).unwrap();
+            client
+                .put("key", &hello_hash, hello.clone(), vec![hello.len() as u64])
+                .await
+                .unwrap();
+            client.flush().await.unwrap();
+
+            for (_, remote) in &remotes {
+                assert_eq!(hello, remote.get("key", &hello_hash).await.unwrap());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_failover() {
+        let hello = "hello world".as_bytes().to_vec();
+        let hello_hash = merklehash::compute_data_hash(&hello[..]);
+
+        // Only the primary has the XORB; reads start from the mirror.
+        let remotes = remotes();
+        remotes[0]
+            .1
+            .put("key", &hello_hash, hello.clone(), vec![hello.len() as u64])
+            .await
+            .unwrap();
+        let client = MirrorClient::new(remotes, Some("onprem"), Replication::All).unwrap();
+
+        assert_eq!(hello, client.get("key", &hello_hash).await.unwrap());
+        assert_eq!(11, client.get_length("key", &hello_hash).await.unwrap());
+        assert_eq!(
+            client
+                .get_object_range("key", &hello_hash, vec![(0, 5)])
+                .await
+                .unwrap(),
+            vec!["hello".as_bytes().to_vec()]
+        );
+
+        let missing = merklehash::compute_data_hash("missing".as_bytes());
+        assert!(matches!(
+            client.get("key", &missing).await,
+            Err(CasClientError::XORBNotFound(_))
+        ));
+
+        assert!(MirrorClient::new(remotes(), Some("elsewhere"), Replication::All).is_err());
+    }
+
+    /// A client answering reads after a delay.
+    #[derive(Debug)]
+    struct SlowClient {
+        client: LocalClient,
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl Client for SlowClient {
+        async fn put(
+            &self,
+            prefix: &str,
+            hash: &MerkleHash,
+            data: Vec<u8>,
+            chunk_boundaries: Vec<u64>,
+        ) -> Result<()> {
+            self.client.put(prefix, hash, data, chunk_boundaries).await
+        }
+
+        async fn flush(&self) -> Result<()> {
+            self.client.flush().await
+        }
+
+        async fn get(&self, prefix: &str, hash: &MerkleHash) -> Result<Vec<u8>> {
+            tokio::time::sleep(self.delay).await;
+            self.client.get(prefix, hash).await
+        }
+
+        async fn get_object_range(
+            &self,
+            prefix: &str,
+            hash: &MerkleHash,
+            ranges: Vec<(u64, u64)>,
+        ) -> Result<Vec<Vec<u8>>> {
+            tokio::time::sleep(self.delay).await;
+            self.client.get_object_range(prefix, hash, ranges).await
+        }
+
+        async fn get_length(&self, prefix: &str, hash: &MerkleHash) -> Result<u64> {
+            tokio::time::sleep(self.delay).await;
+            self.client.get_length(prefix, hash).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hedged_reads() {
+        let hello = "hello world".as_bytes().to_vec();
+        let hello_hash = merklehash::compute_data_hash(&hello[..]);
+
+        let slow = Arc::new(SlowClient {
+            client: LocalClient::default(),
+            delay: Duration::from_secs(10),
+        });
+        let remotes: Vec<(String, Arc<dyn Client + Sync + Send>)> = vec![
+            ("primary".to_string(), slow),
+            ("onprem".to_string(), Arc::new(LocalClient::default())),
+        ];
+        let client = MirrorClient::new(remotes, None, Replication::All)
+            .unwrap()
+            .with_hedged_reads(4);
+        client
+            .put("key", &hello_hash, hello.clone(), vec![hello.len() as u64])
+            .await
+            .unwrap();
+
+        // The slow primary is hedged with the mirror after the default delay.
+        let start = Instant::now();
+        assert_eq!(hello, client.get("key", &hello_hash).await.unwrap());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_latency_percentile() {
+        let latencies = LatencyTracker::default();
+        for ms in 1..MIN_LATENCY_SAMPLES as u64 {
+            latencies.record(Duration::from_millis(ms));
+        }
+        assert_eq!(latencies.percentile(0.95), None);
+
+        let latencies = LatencyTracker::default();
+        for ms in 1..=100 {
+            latencies.record(Duration::from_millis(ms));
+        }
+        assert_eq!(latencies.percentile(0.95), Some(Duration::from_millis(95)));
+
+        // Only the most recent latencies count.
+        for _ in 0..LATENCY_WINDOW {
+            latencies.record(Duration::from_millis(1));
+        }
+        assert_eq!(latencies.percentile(0.95), Some(Duration::from_millis(1)));
+    }
+}
@@ -15,6 +15,7 @@ use merklehash::MerkleHash;
 use crate::error::{CasClientError, Result};
 use crate::interface::Client;
 use crate::staging_trait::*;
+use crate::upload_pipeline::UploadConcurrency;
 
 const PASSTHROUGH_STAGING_MAX_CONCURRENT_UPLOADS: usize = 16;
 
@@ -48,7 +49,11 @@ impl StagingUpload for PassthroughStagingClient {
     /// Upload all staged will upload everything to the remote client.
     /// TODO : Caller may need to be wary of a HashMismatch error which will
     /// indicate that the local staging environment has been corrupted somehow.
-    async fn upload_all_staged(&self, _max_concurrent: usize, _retain: bool) -> Result<()> {
+    async fn upload_all_staged(
+        &self,
+        _concurrency: UploadConcurrency,
+        _retain: bool,
+    ) -> Result<()> {
         Ok(())
     }
 }
@@ -12,9 +12,11 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use crate::cas_connection_pool::{self, CasConnectionConfig, FromConnectionConfig};
+use crate::cold_restore::read_restoring;
 use crate::data_transport::DataTransport;
 use crate::error::{CasClientError, Result};
 use crate::grpc::GrpcClient;
+use crate::transfer_stats::CAS_TRANSFER_STATS;
 use crate::Client;
 use retry_strategy::RetryStrategy;
 
@@ -84,6 +86,25 @@ impl FromConnectionConfig for GrpcClient {
     }
 }
 
+/// Splits the token off an endpoint of the form scheme://<token>@host:port,
+/// which is how clients of a CAS proxy requiring a token are configured.
+fn split_proxy_token(endpoint: &str) -> (String, Option<String>) {
+    let (scheme, rest) = match endpoint.split_once("://") {
+        Some((scheme, rest)) => (Some(scheme), rest),
+        None => (None, endpoint),
+    };
+    match rest.split_once('@') {
+        Some((token, host)) if !token.is_empty() && !token.contains('/') => {
+            let endpoint = match scheme {
+                Some(scheme) => format!("{scheme}://{host}"),
+                None => host.to_string(),
+            };
+            (endpoint, Some(token.to_string()))
+        }
+        _ => (endpoint.to_string(), None),
+    }
+}
+
 /// CAS Remote client. This negotiates between the control plane (gRPC)
 /// and data plane (HTTP) to optimize the uploads and fetches according to
 /// the network, file size, and other dynamic qualities.
@@ -98,6 +119,7 @@ pub struct RemoteClient {
     length_singleflight: singleflight::Group<u64, CasClientError>,
     length_cache: Arc<Mutex<HashMap<String, u64>>>,
     git_xet_version: String,
+    proxy_token: Option<String>,
 }
 
 // DTO's for organization moving around endpoint info
@@ -133,6 +155,7 @@ impl RemoteClient {
             length_singleflight: singleflight::Group::new(),
             length_cache: Arc::new(Mutex::new(HashMap::new())),
             git_xet_version,
+            proxy_token: None,
         }
     }
 
@@ -145,15 +168,19 @@ impl RemoteClient {
     ) -> Self {
         // optionally switch between a CAS and a local server running on CAS_GRPC_PORT and
         // CAS_HTTP_PORT
-        Self::new(
-            endpoint.to_string(),
-            String::from(user_id),
-            String::from(auth),
-            repo_paths,
-            Mutex::new(HashMap::new()),
-            cas_connection_pool::ConnectionPoolMap::new_with_pool_size(H2_TRANSPORT_POOL_SIZE),
-            git_xet_version,
-        )
+        let (endpoint, proxy_token) = split_proxy_token(endpoint);
+        Self {
+            proxy_token,
+            ..Self::new(
+                endpoint,
+                String::from(user_id),
+                String::from(auth),
+                repo_paths,
+                Mutex::new(HashMap::new()),
+                cas_connection_pool::ConnectionPoolMap::new_with_pool_size(H2_TRANSPORT_POOL_SIZE),
+                git_xet_version,
+            )
+        }
     }
 
     /// utility to generate connection config for an endpoint and other owned information
@@ -166,6 +193,7 @@ impl RemoteClient {
             self.repo_paths.clone(),
             self.git_xet_version.clone(),
         )
+        .with_proxy_token(self.proxy_token.clone())
     }
 
     async fn get_grpc_connection_for_config(
@@ -420,6 +448,7 @@ impl Client for RemoteClient {
                     let retry = cas_client_error_retriable(e);
                     if retry {
                         info!("Put error {:?}. Retrying...", e);
+                        CAS_TRANSFER_STATS.add_retry();
                     }
                     retry
                 },
@@ -440,7 +469,7 @@ impl Client for RemoteClient {
     }
 
     async fn get(&self, prefix: &str, hash: &MerkleHash) -> Result<Vec<u8>> {
-        self.get_impl_h2(prefix, hash).await
+        read_restoring(hash, || self.get_impl_h2(prefix, hash)).await
     }
 
     async fn get_object_range(
@@ -449,7 +478,10 @@ impl Client for RemoteClient {
         hash: &MerkleHash,
         ranges: Vec<(u64, u64)>,
     ) -> Result<Vec<Vec<u8>>> {
-        self.get_object_range_impl_h2(prefix, hash, ranges).await
+        read_restoring(hash, || {
+            self.get_object_range_impl_h2(prefix, hash, ranges.clone())
+        })
+        .await
     }
 
     async fn get_length(&self, prefix: &str, hash: &MerkleHash) -> Result<u64> {
@@ -553,3 +585,27 @@ impl RemoteClient {
         Ok(new_client)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_proxy_token() {
+        assert_eq!(
+            split_proxy_token("http://secret@cache.lan:4040"),
+            (
+                "http://cache.lan:4040".to_string(),
+                Some("secret".to_string())
+            )
+        );
+        assert_eq!(
+            split_proxy_token("cache.lan:4040"),
+            ("cache.lan:4040".to_string(), None)
+        );
+        assert_eq!(
+            split_proxy_token("https://cas.xethub.com/a@b"),
+            ("https://cas.xethub.com/a@b".to_string(), None)
+        );
+    }
+}
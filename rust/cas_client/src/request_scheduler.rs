@@ -0,0 +1,198 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+use tracing::{debug, warn};
+
+use crate::transfer_stats::CAS_TRANSFER_STATS;
+
+/// The request rate the scheduler starts at and recovers to, per second.
+/// High enough that it does not slow down an unthrottled client.
+const MAX_REQUESTS_PER_SEC: f64 = 1000.0;
+
+/// The number of requests that can be sent at once after an idle period.
+const BURST_SIZE: f64 = 64.0;
+
+/// The rate is never reduced below this, per second.
+const MIN_REQUESTS_PER_SEC: f64 = 1.0;
+
+/// The rate regained with every successful request, per second.
+const RATE_RECOVERY_PER_SUCCESS: f64 = 1.0;
+
+/// The pause used when the server throttles without a usable Retry-After.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// Retry-After values are capped at this, so that a misbehaving server
+/// cannot stall the client indefinitely.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+
+lazy_static! {
+    /// The scheduler shared by every request this process makes to the CAS,
+    /// so that a throttle response seen by one transfer paces all of them.
+    pub static ref CAS_REQUEST_SCHEDULER: RequestScheduler = RequestScheduler::new(
+        MAX_REQUESTS_PER_SEC,
+        BURST_SIZE
+    );
+}
+
+/// A token bucket pacing requests to the CAS.
+///
+/// When the server answers with 429 or 503, all requests are paused for the
+/// Retry-After period and the rate is halved. Each success then raises the
+/// rate again, up to the maximum it started at.
+#[derive(Debug)]
+pub struct RequestScheduler {
+    max_rate: f64,
+    burst: f64,
+    state: Mutex<SchedulerState>,
+}
+
+#[derive(Debug)]
+struct SchedulerState {
+    tokens: f64,
+    rate: f64,
+    last_refill: Instant,
+    paused_until: Option<Instant>,
+}
+
+impl RequestScheduler {
+    pub fn new(max_rate: f64, burst: f64) -> Self {
+        Self {
+            max_rate,
+            burst,
+            state: Mutex::new(SchedulerState {
+                tokens: burst,
+                rate: max_rate,
+                last_refill: Instant::now(),
+                paused_until: None,
+            }),
+        }
+    }
+
+    /// Waits until a request may be sent.
+    pub async fn acquire(&self) {
+        while let Some(wait) = self.try_acquire(Instant::now()) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Takes a token if one is available at now, otherwise returns how long
+    /// to wait before trying again.
+    fn try_acquire(&self, now: Instant) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(paused_until) = state.paused_until {
+            if paused_until > now {
+                return Some(paused_until - now);
+            }
+            state.paused_until = None;
+        }
+
+        let elapsed = now.saturating_duration_since(state.last_refill);
+        state.tokens = (state.tokens + elapsed.as_secs_f64() * state.rate).min(self.burst);
+        state.last_refill = now;
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - state.tokens) / state.rate))
+        }
+    }
+
+    /// Records a successful request, letting the rate recover.
+    pub fn on_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.rate = (state.rate + RATE_RECOVERY_PER_SUCCESS).min(self.max_rate);
+    }
+
+    /// Records a throttle response, pausing all requests for retry_after, or
+    /// DEFAULT_RETRY_AFTER if the server did not say.
+    pub fn throttle(&self, retry_after: Option<Duration>) {
+        CAS_TRANSFER_STATS.add_throttle();
+        self.throttle_at(retry_after, Instant::now())
+    }
+
+    fn throttle_at(&self, retry_after: Option<Duration>, now: Instant) {
+        let retry_after = retry_after
+            .unwrap_or(DEFAULT_RETRY_AFTER)
+            .min(MAX_RETRY_AFTER);
+        let until = now + retry_after;
+
+        let mut state = self.state.lock().unwrap();
+        // Concurrent requests are usually throttled together; only the
+        // first response of a burst lowers the rate.
+        if state.paused_until.map(|p| p >= until).unwrap_or(false) {
+            debug!("CAS throttled a request during an existing pause");
+            return;
+        }
+        state.paused_until = Some(until);
+        state.tokens = 0.0;
+        state.last_refill = until;
+        state.rate = (state.rate / 2.0).max(MIN_REQUESTS_PER_SEC);
+        warn!(
+            "CAS is throttling requests; pausing all requests for {:.1}s and reducing the rate to {:.0}/s",
+            retry_after.as_secs_f64(),
+            state.rate
+        );
+    }
+}
+
+/// Parses a Retry-After value given in seconds. HTTP dates are not
+/// supported and yield None, so the default pause is used.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_then_pace() {
+        let scheduler = RequestScheduler::new(10.0, 2.0);
+        let now = Instant::now();
+        assert!(scheduler.try_acquire(now).is_none());
+        assert!(scheduler.try_acquire(now).is_none());
+        let wait = scheduler.try_acquire(now).unwrap();
+        assert!(wait <= Duration::from_millis(100));
+
+        // A token is refilled after 100ms at 10/s.
+        assert!(scheduler
+            .try_acquire(now + Duration::from_millis(100))
+            .is_none());
+    }
+
+    #[test]
+    fn test_throttle_pauses_and_recovers() {
+        let scheduler = RequestScheduler::new(10.0, 2.0);
+        let now = Instant::now();
+        scheduler.throttle_at(Some(Duration::from_secs(5)), now);
+        assert_eq!(
+            scheduler.try_acquire(now + Duration::from_secs(1)),
+            Some(Duration::from_secs(4))
+        );
+        assert_eq!(scheduler.state.lock().unwrap().rate, 5.0);
+
+        // A second throttle within the pause does not lower the rate again.
+        scheduler.throttle_at(Some(Duration::from_secs(2)), now);
+        assert_eq!(scheduler.state.lock().unwrap().rate, 5.0);
+
+        // After the pause, tokens refill at the reduced rate.
+        let after = now + Duration::from_secs(5);
+        assert!(scheduler.try_acquire(after).is_some());
+        assert!(scheduler
+            .try_acquire(after + Duration::from_millis(200))
+            .is_none());
+
+        for _ in 0..10 {
+            scheduler.on_success();
+        }
+        assert_eq!(scheduler.state.lock().unwrap().rate, 10.0);
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after(" 3 "), Some(Duration::from_secs(3)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+    }
+}
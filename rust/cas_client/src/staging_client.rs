@@ -1,29 +1,41 @@
 use std::fmt::Debug;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
-use anyhow::anyhow;
 use async_trait::async_trait;
+use cas::fileio::available_space;
+use cas::filelock::{FileLock, DEFAULT_LOCK_TIMEOUT};
 use progress_reporting::DataProgressReporter;
 use tokio::sync::Mutex;
 use tracing::{info, info_span, Instrument};
-use tracing_opentelemetry::OpenTelemetrySpanExt;
 
-use merklehash::MerkleHash;
-use parutils::{tokio_par_for_each, ParallelError};
+use merklehash::{HashAlgorithm, MerkleHash};
 
 use crate::error::CasClientError;
 use crate::interface::Client;
 
 use crate::local_client::LocalClient;
 use crate::staging_trait::*;
+use crate::upload_pipeline::{upload_staged_xorbs, UploadConcurrency};
 use crate::PassthroughStagingClient;
 use common_constants::XET_PROGRAM_NAME;
 
+/// The bytes staging leaves free on the volume of the staging directory.
+static STAGING_MIN_FREE: AtomicU64 = AtomicU64::new(0);
+
+/// Sets the bytes staging leaves free on the volume of the staging
+/// directory. Puts that would leave less fail with
+/// [CasClientError::StagingOutOfSpace] rather than fill the volume up.
+pub fn set_staging_min_free(bytes: u64) {
+    STAGING_MIN_FREE.store(bytes, Ordering::Relaxed);
+}
+
 #[derive(Debug)]
 pub struct StagingClient {
     client: Arc<dyn Client + Sync + Send>,
     staging_client: LocalClient,
+    stage_path: PathBuf,
     progressbar: bool,
 }
 
@@ -41,6 +53,7 @@ impl StagingClient {
         StagingClient {
             client,
             staging_client: LocalClient::new(stage_path, true), // silence warnings=true
+            stage_path: stage_path.to_path_buf(),
             progressbar: false,
         }
     }
@@ -63,21 +76,46 @@ impl StagingClient {
         StagingClient {
             client,
             staging_client: LocalClient::new(stage_path, true), // silence warnings=true
+            stage_path: stage_path.to_path_buf(),
             progressbar: true,
         }
     }
+
+    /// Checks there's room to stage len more bytes.
+    fn check_free_space(&self, len: u64) -> Result<(), CasClientError> {
+        let needed = len + STAGING_MIN_FREE.load(Ordering::Relaxed);
+        match available_space(&self.stage_path) {
+            Ok(available) if available < needed => Err(CasClientError::StagingOutOfSpace(
+                self.stage_path.clone(),
+                available,
+                needed,
+            )),
+            // Filesystems that don't report their free space are written
+            // to regardless.
+            _ => Ok(()),
+        }
+    }
+
+    /// Validates the xorbs staged against hash_algorithm instead of the
+    /// default.
+    pub fn with_hash_algorithm(mut self, hash_algorithm: HashAlgorithm) -> StagingClient {
+        self.staging_client = self.staging_client.with_hash_algorithm(hash_algorithm);
+        self
+    }
 }
 
 /// Creates a new staging client wraping a staging directory.
 /// If a staging directory is provided, it will be used for staging.
 /// Otherwise all queries are passed through to the remote directly
-/// using the PassthroughStagingClient.
+/// using the PassthroughStagingClient. Staged xorbs are validated against
+/// hash_algorithm, the hash algorithm of the repository.
 pub fn new_staging_client<T: Client + Debug + Sync + Send + 'static>(
     client: T,
     stage_path: Option<&Path>,
+    hash_algorithm: HashAlgorithm,
 ) -> Arc<dyn Staging + Send + Sync> {
     if let Some(path) = stage_path {
-        Arc::new(StagingClient::new(Arc::new(client), path))
+        Arc::new(StagingClient::new(Arc::new(client), path).with_hash_algorithm(hash_algorithm))
     } else {
         Arc::new(PassthroughStagingClient::new(Arc::new(client)))
     }
@@ -86,13 +124,18 @@ pub fn new_staging_client<T: Client + Debug + Sync + Send + 'static>(
 /// Creates a new staging client wraping a staging directory.
 /// If a staging directory is provided, it will be used for staging.
 /// Otherwise all queries are passed through to the remote directly
-/// using the PassthroughStagingClient.
+/// using the PassthroughStagingClient. Staged xorbs are validated against
+/// hash_algorithm, the hash algorithm of the repository.
 pub fn new_staging_client_with_progressbar<T: Client + Debug + Sync + Send + 'static>(
     client: T,
     stage_path: Option<&Path>,
+    hash_algorithm: HashAlgorithm,
 ) -> Arc<dyn Staging + Send + Sync> {
     if let Some(path) = stage_path {
-        Arc::new(StagingClient::new_with_progressbar(Arc::new(client), path))
+        Arc::new(
+            StagingClient::new_with_progressbar(Arc::new(client), path)
+                .with_hash_algorithm(hash_algorithm),
+        )
     } else {
         Arc::new(PassthroughStagingClient::new(Arc::new(client)))
     }
@@ -107,11 +150,19 @@ impl StagingUpload for StagingClient {
     /// indicate that the local staging environment has been corrupted somehow.
     async fn upload_all_staged(
         &self,
-        max_concurrent: usize,
+        concurrency: UploadConcurrency,
         retain: bool,
     ) -> Result<(), CasClientError> {
         let client = &self.client;
         let stage = &self.staging_client;
+        // Concurrent uploads would delete the xorbs the other is reading.
+        let _lock = FileLock::acquire_async(stage.get_lock_path(), DEFAULT_LOCK_TIMEOUT)
+            .await
+            .map_err(|e| {
+                CasClientError::InternalError(anyhow::anyhow!(
+                    "Unable to lock the staging directory: {e}"
+                ))
+            })?;
         let entries = stage.get_all_entries()?;
         info!(
             "XET StagingClient: {} entries to upload to remote.",
@@ -131,54 +182,9 @@ impl StagingUpload for StagingClient {
         } else {
             None
         };
-        let cur_span = info_span!("staging_client.upload_all_staged");
-        let ctx = cur_span.context();
-        // TODO: This can probably be re-written cleaner with futures::stream
-        // ex: https://patshaughnessy.net/2020/1/20/downloading-100000-files-using-async-rust
-        tokio_par_for_each(entries, max_concurrent, |entry, _| {
-            let pb = pb.clone();
-            let span = info_span!("upload_staged_xorb");
-            span.set_parent(ctx.clone());
-            async move {
-                // if remote does not have the object
-                // read the object from staging
-                // and write the object out to remote
-                let (cb, val) = stage
-                    .get_detailed(&entry.prefix, &entry.hash)
-                    .instrument(info_span!("read_staged"))
-                    .await?;
-                let xorb_length = val.len();
-                info!(
-                    "Uploading XORB {}/{} of length {}.",
-                    &entry.prefix,
-                    &entry.hash,
-                    val.len()
-                );
-                client.put(&entry.prefix, &entry.hash, val, cb).await?;
-
-                if !retain {
-                    info!(
-                        "Clearing XORB {}/{} from staging area.",
-                        &entry.prefix, &entry.hash,
-                    );
-                    // Delete it from staging
-                    stage.delete(&entry.prefix, &entry.hash);
-                }
-                if let Some(bar) = &pb {
-                    bar.lock()
-                        .await
-                        .register_progress(Some(1), Some(xorb_length));
-                }
-                Ok(())
-            }
-            .instrument(span)
-        })
-        .instrument(cur_span)
-        .await
-        .map_err(|e| match e {
-            ParallelError::JoinError => CasClientError::InternalError(anyhow!("Join Error")),
-            ParallelError::TaskError(e) => e,
-        })?;
+        upload_staged_xorbs(client, stage, entries, concurrency, retain, pb.clone())
+            .instrument(info_span!("staging_client.upload_all_staged"))
+            .await?;
         self.client.flush().await?;
 
         if let Some(bar) = &pb {
@@ -278,10 +284,18 @@ impl Client for StagingClient {
         data: Vec<u8>,
         chunk_boundaries: Vec<u64>,
     ) -> Result<(), CasClientError> {
-        self.staging_client
+        let len = data.len() as u64;
+        self.check_free_space(len)?;
+        let result = self
+            .staging_client
             .put(prefix, hash, data, chunk_boundaries)
             .instrument(info_span!("staging_client.put"))
-            .await
+            .await;
+        // A write that failed as the volume filled up reports it as such.
+        if result.is_err() {
+            self.check_free_space(len)?;
+        }
+        result
     }
 
     async fn flush(&self) -> Result<(), CasClientError> {
@@ -339,6 +353,7 @@ mod tests {
     use std::path::Path;
     use std::sync::Arc;
 
+    use merklehash::HashAlgorithm;
     use tempfile::TempDir;
 
     use crate::staging_client::{StagingClient, StagingUpload};
@@ -520,7 +535,10 @@ mod tests {
         );
 
         // upload staged
-        client.upload_all_staged(1, false).await.unwrap();
+        client
+            .upload_all_staged(UploadConcurrency::uniform(1), false)
+            .await
+            .unwrap();
 
         // we can still read it
         // get length "hello world"
@@ -547,7 +565,7 @@ mod tests {
         let localdir = TempDir::new().unwrap();
         let local = LocalClient::new(localdir.path(), true);
         // no staging directory
-        let client = new_staging_client(local, None);
+        let client = new_staging_client(local, None, HashAlgorithm::default());
 
         // put an object in and make sure it is there
 
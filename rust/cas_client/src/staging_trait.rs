@@ -1,5 +1,6 @@
 use crate::error::CasClientError;
 use crate::interface::Client;
+use crate::upload_pipeline::UploadConcurrency;
 use async_trait::async_trait;
 use merklehash::MerkleHash;
 use std::path::PathBuf;
@@ -8,7 +9,7 @@ use std::path::PathBuf;
 pub trait StagingUpload {
     async fn upload_all_staged(
         &self,
-        max_concurrent: usize,
+        concurrency: UploadConcurrency,
         retain: bool,
     ) -> Result<(), CasClientError>;
 }
@@ -0,0 +1,99 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use cache::{
+    reads_from, SOURCE_DISK_CACHE, SOURCE_MEMORY_CACHE, SOURCE_REMOTE, SOURCE_SINGLEFLIGHT,
+};
+use lazy_static::lazy_static;
+
+lazy_static! {
+    /// The counts of every transfer this process makes to and from the CAS,
+    /// for reports of the data a command transferred.
+    pub static ref CAS_TRANSFER_STATS: TransferStats = TransferStats::default();
+}
+
+/// Counts the bytes sent to and received from the CAS, and the requests
+/// retried or throttled on the way.
+#[derive(Debug, Default)]
+pub struct TransferStats {
+    uploaded_bytes: AtomicU64,
+    downloaded_bytes: AtomicU64,
+    retries: AtomicU64,
+    throttles: AtomicU64,
+}
+
+/// The counts of a [TransferStats] at a point in time.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TransferCounts {
+    pub uploaded_bytes: u64,
+    pub downloaded_bytes: u64,
+    pub retries: u64,
+    pub throttles: u64,
+    /// The blocks read through the cache that it held, or that another read
+    /// was already fetching, and their bytes.
+    pub cache_hits: u64,
+    pub cache_hit_bytes: u64,
+    /// The blocks read through the cache that it fetched from the CAS.
+    pub cache_misses: u64,
+    pub cache_miss_bytes: u64,
+}
+
+impl TransferStats {
+    pub fn add_uploaded(&self, bytes: usize) {
+        self.uploaded_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_downloaded(&self, bytes: usize) {
+        self.downloaded_bytes
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Records a failed request about to be sent again.
+    pub fn add_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a response asking to slow down.
+    pub fn add_throttle(&self) {
+        self.throttles.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn counts(&self) -> TransferCounts {
+        let (hits, hit_bytes) = [SOURCE_MEMORY_CACHE, SOURCE_DISK_CACHE, SOURCE_SINGLEFLIGHT]
+            .into_iter()
+            .map(reads_from)
+            .fold((0, 0), |(b, d), (blocks, bytes)| (b + blocks, d + bytes));
+        let (misses, miss_bytes) = reads_from(SOURCE_REMOTE);
+        TransferCounts {
+            uploaded_bytes: self.uploaded_bytes.load(Ordering::Relaxed),
+            downloaded_bytes: self.downloaded_bytes.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            throttles: self.throttles.load(Ordering::Relaxed),
+            cache_hits: hits,
+            cache_hit_bytes: hit_bytes,
+            cache_misses: misses,
+            cache_miss_bytes: miss_bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transfer_counts() {
+        let stats = TransferStats::default();
+        stats.add_uploaded(100);
+        stats.add_uploaded(20);
+        stats.add_downloaded(7);
+        stats.add_retry();
+        stats.add_throttle();
+        stats.add_throttle();
+        let counts = stats.counts();
+        assert_eq!(counts.uploaded_bytes, 120);
+        assert_eq!(counts.downloaded_bytes, 7);
+        assert_eq!(counts.retries, 1);
+        assert_eq!(counts.throttles, 2);
+    }
+}
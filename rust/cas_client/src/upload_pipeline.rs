@@ -0,0 +1,221 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::{future, StreamExt, TryStreamExt};
+use progress_reporting::DataProgressReporter;
+use tokio::sync::Mutex;
+use tracing::{debug, info, info_span, Instrument};
+
+use crate::error::CasClientError;
+use crate::interface::Client;
+use crate::local_client::LocalClient;
+use cas::key::Key;
+
+/// The number of xorbs each stage of uploading the staging area works on at
+/// once. Stages are connected so that a xorb moves to the next stage as soon
+/// as it is done with the previous one; a slow stage holds back the ones
+/// before it rather than letting xorbs pile up in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UploadConcurrency {
+    /// Queries to the CAS for whether a xorb is already stored.
+    pub dedup_check: usize,
+    /// Xorbs being read from the staging directory.
+    pub read: usize,
+    /// Xorbs being sent to the CAS.
+    pub upload: usize,
+}
+
+impl UploadConcurrency {
+    /// Runs every stage with the same concurrency.
+    pub fn uniform(n: usize) -> Self {
+        Self {
+            dedup_check: n,
+            read: n,
+            upload: n,
+        }
+    }
+}
+
+/// The time spent in one stage, summed over all the xorbs that went through
+/// it. A stage whose time is close to the total wall time is the bottleneck.
+#[derive(Debug, Default)]
+struct StageTimer {
+    nanos: AtomicU64,
+    count: AtomicUsize,
+}
+
+impl StageTimer {
+    fn record(&self, start: Instant) {
+        self.nanos
+            .fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn summary(&self, concurrency: usize) -> String {
+        format!(
+            "{} xorbs in {:.2?} (concurrency {concurrency})",
+            self.count.load(Ordering::Relaxed),
+            Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct PipelineTimings {
+    dedup_check: StageTimer,
+    read: StageTimer,
+    upload: StageTimer,
+}
+
+/// Uploads the xorbs in stage to client as a pipeline of
+/// enumerate -> dedup check -> read -> upload.
+///
+/// Xorbs the CAS already stores are not read or uploaded. Unless retain is
+/// set, each xorb is removed from staging once the CAS has it, so an upload
+/// stopped partway, e.g. by [parutils::shutdown_token], resumes where it
+/// left off.
+pub(crate) async fn upload_staged_xorbs(
+    client: &Arc<dyn Client + Sync + Send>,
+    stage: &LocalClient,
+    entries: Vec<Key>,
+    concurrency: UploadConcurrency,
+    retain: bool,
+    pb: Option<Arc<Mutex<DataProgressReporter>>>,
+) -> Result<(), CasClientError> {
+    let start = Instant::now();
+    let num_entries = entries.len();
+    let timings = PipelineTimings::default();
+    let finish = |entry: &Key, xorb_length: usize| {
+        let pb = pb.clone();
+        let (prefix, hash) = (entry.prefix.clone(), entry.hash);
+        async move {
+            if !retain {
+                info!("Clearing XORB {}/{} from staging area.", &prefix, &hash);
+                stage.delete(&prefix, &hash);
+            }
+            if let Some(bar) = &pb {
+                bar.lock()
+                    .await
+                    .register_progress(Some(1), Some(xorb_length));
+            }
+        }
+    };
+
+    let shutdown = parutils::shutdown_token();
+    futures::stream::iter(entries)
+        // Once interrupted, no more xorbs are started; the ones in flight
+        // finish, and those left stay staged for the next push.
+        .take_while(|_| future::ready(!shutdown.is_cancelled()))
+        // Skip the xorbs the CAS already stores, e.g. pushed from another
+        // clone, without reading them from disk.
+        .map(|entry| {
+            let timings = &timings;
+            let finish = &finish;
+            async move {
+                let stage_start = Instant::now();
+                let stored =
+                    matches!(client.get_length(&entry.prefix, &entry.hash).await, Ok(n) if n > 0);
+                timings.dedup_check.record(stage_start);
+                if stored {
+                    debug!("XORB {}/{} is already stored.", &entry.prefix, &entry.hash);
+                    finish(&entry, 0).await;
+                    None
+                } else {
+                    Some(entry)
+                }
+            }
+        })
+        .buffer_unordered(concurrency.dedup_check.max(1))
+        .filter_map(future::ready)
+        .map(|entry| {
+            let timings = &timings;
+            async move {
+                let stage_start = Instant::now();
+                let (cb, val) = stage
+                    .get_detailed(&entry.prefix, &entry.hash)
+                    .instrument(info_span!("read_staged"))
+                    .await?;
+                timings.read.record(stage_start);
+                Ok::<_, CasClientError>((entry, cb, val))
+            }
+        })
+        .buffer_unordered(concurrency.read.max(1))
+        .map_ok(|(entry, cb, val)| {
+            let timings = &timings;
+            let finish = &finish;
+            async move {
+                let stage_start = Instant::now();
+                let xorb_length = val.len();
+                info!(
+                    "Uploading XORB {}/{} of length {}.",
+                    &entry.prefix, &entry.hash, xorb_length
+                );
+                client
+                    .put(&entry.prefix, &entry.hash, val, cb)
+                    .instrument(info_span!("upload_staged_xorb"))
+                    .await?;
+                timings.upload.record(stage_start);
+                finish(&entry, xorb_length).await;
+                Ok::<(), CasClientError>(())
+            }
+        })
+        .try_buffer_unordered(concurrency.upload.max(1))
+        .try_collect::<()>()
+        .await?;
+
+    info!(
+        "Uploaded {num_entries} staged XORBs in {:.2?}; dedup check: {}; read: {}; upload: {}.",
+        start.elapsed(),
+        timings.dedup_check.summary(concurrency.dedup_check),
+        timings.read.summary(concurrency.read),
+        timings.upload.summary(concurrency.upload),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_pipeline_skips_stored_xorbs() {
+        let remote_dir = TempDir::new().unwrap();
+        let stage_dir = TempDir::new().unwrap();
+        let remote = Arc::new(LocalClient::new(remote_dir.path(), false));
+        let client: Arc<dyn Client + Sync + Send> = remote.clone();
+        let stage = LocalClient::new(stage_dir.path(), false);
+
+        let hello = "hello world".as_bytes().to_vec();
+        let hello_hash = merklehash::compute_data_hash(&hello[..]);
+        let other = "other bytes".as_bytes().to_vec();
+        let other_hash = merklehash::compute_data_hash(&other[..]);
+        for (data, hash) in [(&hello, &hello_hash), (&other, &other_hash)] {
+            stage
+                .put("key", hash, data.clone(), vec![data.len() as u64])
+                .await
+                .unwrap();
+        }
+        // The CAS already has the first xorb.
+        remote
+            .put("key", &hello_hash, hello.clone(), vec![hello.len() as u64])
+            .await
+            .unwrap();
+
+        let entries = stage.get_all_entries().unwrap();
+        upload_staged_xorbs(
+            &client,
+            &stage,
+            entries,
+            UploadConcurrency::uniform(2),
+            false,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(stage.get_all_entries().unwrap().is_empty());
+        assert_eq!(other, client.get("key", &other_hash).await.unwrap());
+    }
+}
@@ -41,7 +41,7 @@ async fn main() {
         ConfigGitPathOption::CurdirDiscover,
     )
     .unwrap();
-    initialize_tracing_subscriber(&cfg).unwrap();
+    initialize_tracing_subscriber(&cfg, "xetmnt").unwrap();
     if let Err(e) = perform_mount_and_wait_for_ctrlc(
         cfg,
         &cli.xet,
@@ -0,0 +1,53 @@
+#!/usr/bin/env bash
+set -e
+set -x
+
+SCRIPT_DIR="$( cd -- "$( dirname -- "${BASH_SOURCE[0]:-$0}"; )" &> /dev/null && pwd 2> /dev/null; )";
+. "$SCRIPT_DIR/initialize.sh"
+setup_basic_run_environment
+
+remote=$(create_bare_repo)
+
+git clone $remote repo_1
+
+pushd repo_1
+[[ $(git branch) == *"main"* ]] || git checkout -b main
+git xet init --force
+
+create_data_file data.dat 100000
+cp data.dat ../data_v1.dat
+echo "small text file" > small.txt
+git add data.dat small.txt
+git commit -a -m "Adding data."
+
+append_data_file data.dat 100000
+git add data.dat
+git commit -a -m "Changing data."
+git push --set-upstream origin main
+
+# The old version is streamed without touching the working directory.
+git xet cat HEAD~1:data.dat > ../cat_v1.dat
+assert_files_equal ../cat_v1.dat ../data_v1.dat
+
+git xet cat main:data.dat > ../cat_v2.dat
+assert_files_equal ../cat_v2.dat data.dat
+
+# Byte ranges.
+git xet cat HEAD~1:data.dat --range 100-1100 > ../cat_range.dat
+dd if=../data_v1.dat of=../expected_range.dat bs=1 skip=100 count=1000
+assert_files_equal ../cat_range.dat ../expected_range.dat
+
+# Files stored directly in git pass through as is.
+[[ "$(git xet cat HEAD:small.txt)" == "small text file" ]] || die "Unexpected contents for small.txt"
+
+# Missing paths are an error.
+git xet cat HEAD:does_not_exist.dat && die "Expected cat of a missing path to fail."
+popd
+
+# A fresh clone can read files without running a checkout.
+XET_NO_SMUDGE=1 git clone $remote repo_2
+pushd repo_2
+git xet init --force
+git xet cat HEAD~1:data.dat > ../cat_clone_v1.dat
+assert_files_equal ../cat_clone_v1.dat ../data_v1.dat
+popd
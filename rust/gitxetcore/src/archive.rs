@@ -0,0 +1,409 @@
+//! Reed–Solomon repair blocks for offline archives, so that a cold-storage
+//! copy of a dataset survives bit rot.
+//!
+//! The archive is split into blocks of `block_size` bytes, the last one
+//! zero padded, and every `data_blocks` consecutive blocks form a stripe
+//! protected by `parity_blocks` repair blocks. Any `parity_blocks` blocks of
+//! a stripe, archive or repair blocks, can be corrupted and rebuilt from the
+//! rest. The archive is read one stripe at a time, so the memory needed
+//! doesn't grow with its size.
+//!
+//! The repair blocks are written to `<archive>.parity`, stripe after
+//! stripe, followed by a JSON trailer with the layout and the BLAKE3 hash of
+//! every block, by which the corrupted ones are found, then the length of
+//! the trailer and [PARITY_MAGIC].
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use cas::fileio::{read_exact_at, write_all_at};
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::constants::ARCHIVE_PARITY_SUFFIX;
+use crate::errors::{GitXetRepoError, Result};
+
+/// The last bytes of a parity file.
+const PARITY_MAGIC: &[u8; 8] = b"XETPAR01";
+const PARITY_VERSION: u32 = 1;
+
+/// How an archive is split into stripes of blocks and repair blocks.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParityLayout {
+    pub block_size: usize,
+    /// The archive blocks in a stripe.
+    pub data_blocks: usize,
+    /// The repair blocks of a stripe: how many of its blocks can be lost.
+    pub parity_blocks: usize,
+}
+
+impl ParityLayout {
+    fn encoder(&self) -> Result<ReedSolomon> {
+        ReedSolomon::new(self.data_blocks, self.parity_blocks).map_err(|e| {
+            GitXetRepoError::InvalidOperation(format!(
+                "Unable to protect {} blocks with {} repair blocks: {e:?}",
+                self.data_blocks, self.parity_blocks
+            ))
+        })
+    }
+}
+
+/// The trailer of a parity file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct ParityTrailer {
+    version: u32,
+    layout: ParityLayout,
+    archive_size: u64,
+    /// The hash of each block of the archive, zero padded, in hex.
+    data_hashes: Vec<String>,
+    /// The hash of each repair block, stripe after stripe, in hex.
+    parity_hashes: Vec<String>,
+}
+
+impl ParityTrailer {
+    fn num_stripes(&self) -> usize {
+        self.data_hashes.len().div_ceil(self.layout.data_blocks)
+    }
+}
+
+/// What checking an archive against its repair blocks found.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ParityReport {
+    /// The blocks of the archive.
+    pub blocks: usize,
+    /// The blocks of the archive that are corrupted or missing.
+    pub corrupt_blocks: usize,
+    /// The repair blocks that are corrupted or missing.
+    pub corrupt_parity_blocks: usize,
+    /// The stripes with more corrupted blocks than repair blocks.
+    pub unrepairable_stripes: usize,
+}
+
+impl ParityReport {
+    pub fn is_intact(&self) -> bool {
+        self.corrupt_blocks == 0 && self.corrupt_parity_blocks == 0
+    }
+
+    pub fn is_repairable(&self) -> bool {
+        self.unrepairable_stripes == 0
+    }
+}
+
+/// The path of the parity file of the archive at path.
+pub fn parity_path(archive: &Path) -> PathBuf {
+    let mut name = archive.as_os_str().to_owned();
+    name.push(ARCHIVE_PARITY_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// Writes the repair blocks of the archive to its parity file.
+pub fn write_parity(archive: &Path, layout: &ParityLayout) -> Result<ParityReport> {
+    let encoder = layout.encoder()?;
+    let file = File::open(archive)?;
+    let archive_size = file.metadata()?.len();
+    let num_blocks = archive_size.div_ceil(layout.block_size as u64) as usize;
+
+    let mut out = BufWriter::new(File::create(parity_path(archive))?);
+    let mut trailer = ParityTrailer {
+        version: PARITY_VERSION,
+        layout: *layout,
+        archive_size,
+        data_hashes: Vec::with_capacity(num_blocks),
+        parity_hashes: Vec::new(),
+    };
+    for stripe in 0..num_blocks.div_ceil(layout.data_blocks) {
+        let mut shards = vec![vec![0u8; layout.block_size]; layout.data_blocks];
+        for (i, shard) in shards.iter_mut().enumerate() {
+            let block = stripe * layout.data_blocks + i;
+            if block < num_blocks {
+                read_block(
+                    &file,
+                    archive_size,
+                    archive_size,
+                    layout.block_size,
+                    block,
+                    shard,
+                )?;
+                trailer.data_hashes.push(hash_hex(shard));
+            }
+        }
+        shards.resize(
+            layout.data_blocks + layout.parity_blocks,
+            vec![0u8; layout.block_size],
+        );
+        encoder
+            .encode(&mut shards)
+            .map_err(|e| anyhow::anyhow!("Unable to encode repair blocks: {e:?}"))?;
+        for shard in &shards[layout.data_blocks..] {
+            out.write_all(shard)?;
+            trailer.parity_hashes.push(hash_hex(shard));
+        }
+    }
+
+    let trailer_bytes = serde_json::to_vec(&trailer)?;
+    out.write_all(&trailer_bytes)?;
+    out.write_all(&(trailer_bytes.len() as u64).to_le_bytes())?;
+    out.write_all(PARITY_MAGIC)?;
+    out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    info!("Wrote the repair blocks of {archive:?} for {num_blocks} blocks");
+
+    Ok(ParityReport {
+        blocks: num_blocks,
+        ..Default::default()
+    })
+}
+
+/// Checks the archive against its parity file, without changing either.
+pub fn verify_parity(archive: &Path) -> Result<ParityReport> {
+    check_parity(archive, false)
+}
+
+/// Rebuilds the corrupted blocks of the archive, and of its parity file,
+/// from the rest of their stripes. Stripes with more corrupted blocks than
+/// repair blocks are left as they are.
+pub fn repair_with_parity(archive: &Path) -> Result<ParityReport> {
+    check_parity(archive, true)
+}
+
+fn check_parity(archive: &Path, repair: bool) -> Result<ParityReport> {
+    let parity_file = OpenOptions::new()
+        .read(true)
+        .write(repair)
+        .open(parity_path(archive))?;
+    let trailer = read_trailer(&parity_file)?;
+    let layout = trailer.layout;
+    let encoder = layout.encoder()?;
+
+    let file = OpenOptions::new().read(true).write(repair).open(archive)?;
+    let file_size = file.metadata()?.len();
+    if file_size != trailer.archive_size {
+        warn!(
+            "{archive:?} is {file_size} bytes long, {} expected",
+            trailer.archive_size
+        );
+    }
+
+    let num_blocks = trailer.data_hashes.len();
+    let mut report = ParityReport {
+        blocks: num_blocks,
+        ..Default::default()
+    };
+    for stripe in 0..trailer.num_stripes() {
+        let mut shards: Vec<Option<Vec<u8>>> =
+            Vec::with_capacity(layout.data_blocks + layout.parity_blocks);
+        let mut corrupt = Vec::new();
+        for i in 0..layout.data_blocks {
+            let block = stripe * layout.data_blocks + i;
+            let mut shard = vec![0u8; layout.block_size];
+            // The padding of the last stripe is known.
+            let intact = block >= num_blocks
+                || (read_block(
+                    &file,
+                    file_size,
+                    trailer.archive_size,
+                    layout.block_size,
+                    block,
+                    &mut shard,
+                )? && hash_hex(&shard) == trailer.data_hashes[block]);
+            if !intact {
+                report.corrupt_blocks += 1;
+                corrupt.push(i);
+            }
+            shards.push(intact.then_some(shard));
+        }
+        for j in 0..layout.parity_blocks {
+            let index = stripe * layout.parity_blocks + j;
+            let mut shard = vec![0u8; layout.block_size];
+            let offset = (index * layout.block_size) as u64;
+            let intact = read_exact_at(&parity_file, &mut shard, offset).is_ok()
+                && hash_hex(&shard) == trailer.parity_hashes[index];
+            if !intact {
+                report.corrupt_parity_blocks += 1;
+                corrupt.push(layout.data_blocks + j);
+            }
+            shards.push(intact.then_some(shard));
+        }
+
+        if corrupt.is_empty() {
+            continue;
+        }
+        if corrupt.len() > layout.parity_blocks {
+            warn!(
+                "Stripe {stripe} of {archive:?} has {} corrupted blocks, more than can be repaired",
+                corrupt.len()
+            );
+            report.unrepairable_stripes += 1;
+            continue;
+        }
+        if !repair {
+            continue;
+        }
+
+        encoder.reconstruct(&mut shards).map_err(|e| {
+            GitXetRepoError::IntegrityCheckFailed(format!(
+                "Unable to rebuild stripe {stripe} of {archive:?}: {e:?}"
+            ))
+        })?;
+        for i in corrupt {
+            let Some(shard) = &shards[i] else {
+                continue;
+            };
+            if i < layout.data_blocks {
+                let block = stripe * layout.data_blocks + i;
+                let len = block_len(trailer.archive_size, layout.block_size, block);
+                write_all_at(&file, &shard[..len], (block * layout.block_size) as u64)?;
+            } else {
+                let index = stripe * layout.parity_blocks + i - layout.data_blocks;
+                write_all_at(&parity_file, shard, (index * layout.block_size) as u64)?;
+            }
+        }
+    }
+
+    if repair && report.is_repairable() {
+        file.set_len(trailer.archive_size)?;
+        file.sync_all()?;
+        parity_file.sync_all()?;
+    }
+    Ok(report)
+}
+
+/// The length of block of an archive of archive_size bytes; the last block
+/// may be short.
+fn block_len(archive_size: u64, block_size: usize, block: usize) -> usize {
+    let offset = (block * block_size) as u64;
+    archive_size.saturating_sub(offset).min(block_size as u64) as usize
+}
+
+/// Reads block of the archive of archive_size bytes into buf, zero padding
+/// the last block. Returns false if the file, of file_size bytes, is too
+/// short to hold it.
+fn read_block(
+    file: &File,
+    file_size: u64,
+    archive_size: u64,
+    block_size: usize,
+    block: usize,
+    buf: &mut [u8],
+) -> Result<bool> {
+    let offset = (block * block_size) as u64;
+    let expected = block_len(archive_size, block_size, block);
+    let len = block_len(file_size, block_size, block).min(expected);
+    buf.fill(0);
+    if len > 0 {
+        read_exact_at(file, &mut buf[..len], offset)?;
+    }
+    Ok(len == expected)
+}
+
+fn read_trailer(parity_file: &File) -> Result<ParityTrailer> {
+    let corrupt = || GitXetRepoError::DataParsingError("The parity file is corrupted".to_string());
+    let mut file = parity_file;
+    let size = file.metadata()?.len();
+    if size < 16 {
+        return Err(corrupt());
+    }
+    let mut footer = [0u8; 16];
+    file.seek(SeekFrom::Start(size - 16))?;
+    file.read_exact(&mut footer)?;
+    if &footer[8..] != PARITY_MAGIC {
+        return Err(corrupt());
+    }
+    let trailer_len = u64::from_le_bytes(footer[..8].try_into().unwrap());
+    if trailer_len > size - 16 {
+        return Err(corrupt());
+    }
+    let mut trailer = vec![0u8; trailer_len as usize];
+    file.seek(SeekFrom::Start(size - 16 - trailer_len))?;
+    file.read_exact(&mut trailer)?;
+    let trailer: ParityTrailer = serde_json::from_slice(&trailer).map_err(|_| corrupt())?;
+    if trailer.version != PARITY_VERSION {
+        return Err(GitXetRepoError::DataParsingError(format!(
+            "Unsupported parity file version {}",
+            trailer.version
+        )));
+    }
+    if trailer.parity_hashes.len() != trailer.num_stripes() * trailer.layout.parity_blocks {
+        return Err(corrupt());
+    }
+    Ok(trailer)
+}
+
+fn hash_hex(block: &[u8]) -> String {
+    blake3::hash(block).to_hex().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use tempfile::TempDir;
+
+    const LAYOUT: ParityLayout = ParityLayout {
+        block_size: 16,
+        data_blocks: 4,
+        parity_blocks: 2,
+    };
+
+    fn corrupt_byte(path: &Path, offset: u64) {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .unwrap();
+        let mut byte = [0u8];
+        read_exact_at(&file, &mut byte, offset).unwrap();
+        write_all_at(&file, &[byte[0] ^ 0xff], offset).unwrap();
+    }
+
+    #[test]
+    fn test_repair_archive() {
+        let dir = TempDir::new().unwrap();
+        let archive = dir.path().join("data.tar");
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        // Two full stripes and a partial one, ending in a partial block.
+        let data: Vec<u8> = (0..16 * 9 + 5).map(|_| rng.gen()).collect();
+        std::fs::write(&archive, &data).unwrap();
+
+        assert_eq!(write_parity(&archive, &LAYOUT).unwrap().blocks, 10);
+        assert!(verify_parity(&archive).unwrap().is_intact());
+
+        // Two blocks of the first stripe, a repair block of the second, and
+        // the partial last block.
+        corrupt_byte(&archive, 3);
+        corrupt_byte(&archive, 16 * 2 + 7);
+        corrupt_byte(&parity_path(&archive), 16 * 2 + 1);
+        corrupt_byte(&archive, 16 * 9 + 2);
+        let report = verify_parity(&archive).unwrap();
+        assert_eq!(report.corrupt_blocks, 3);
+        assert_eq!(report.corrupt_parity_blocks, 1);
+        assert!(report.is_repairable());
+
+        assert!(repair_with_parity(&archive).unwrap().is_repairable());
+        assert_eq!(std::fs::read(&archive).unwrap(), data);
+        assert!(verify_parity(&archive).unwrap().is_intact());
+
+        // A truncated archive is rebuilt too.
+        let file = OpenOptions::new().write(true).open(&archive).unwrap();
+        file.set_len(16 * 9).unwrap();
+        assert!(repair_with_parity(&archive).unwrap().is_repairable());
+        assert_eq!(std::fs::read(&archive).unwrap(), data);
+    }
+
+    #[test]
+    fn test_unrepairable_stripe() {
+        let dir = TempDir::new().unwrap();
+        let archive = dir.path().join("data.tar");
+        std::fs::write(&archive, vec![7u8; 16 * 8]).unwrap();
+        write_parity(&archive, &LAYOUT).unwrap();
+
+        for block in 0..3 {
+            corrupt_byte(&archive, 16 * block);
+        }
+        let report = repair_with_parity(&archive).unwrap();
+        assert_eq!(report.unrepairable_stripes, 1);
+        assert_eq!(report.corrupt_blocks, 3);
+        // The stripe is left as it is.
+        assert_eq!(std::fs::read(&archive).unwrap()[0], !7u8);
+    }
+}
@@ -0,0 +1,179 @@
+//! An opt-in, append-only log of the files whose contents were materialized,
+//! either by the smudge filter, checkout and materialize, or by a read through
+//! a mount, `git xet cat` or `git xet serve`.
+//!
+//! Each access is recorded as one JSON line. When the log grows past the
+//! configured size it is rotated to `<path>.1`, shifting older logs up to
+//! `<path>.<max_files>`.
+use crate::config::{AuditSettings, XetConfig};
+use crate::data::PointerFile;
+use crate::errors::{GitXetRepoError, Result};
+use serde::Serialize;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+/// The file was materialized by the smudge filter.
+pub const AUDIT_EVENT_SMUDGE: &str = "smudge";
+/// The file was first read through a mount.
+pub const AUDIT_EVENT_MOUNT_READ: &str = "mount_read";
+/// The file was read without being checked out, e.g. by cat or serve.
+pub const AUDIT_EVENT_READ: &str = "read";
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct AuditRecord {
+    pub timestamp: String,
+    pub event: String,
+    #[serde(rename = "ref", skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    pub path: String,
+    pub hash: String,
+    pub bytes: u64,
+    pub principal: String,
+    pub pid: u32,
+}
+
+pub struct AuditLog {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    principal: String,
+    // Serializes appends and rotations from this process.
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    /// Returns the audit log configured for this repository, if auditing is
+    /// enabled.
+    pub fn from_config(cfg: &XetConfig) -> Option<Arc<Self>> {
+        let principal = cfg.user.login_id.clone().unwrap_or_else(whoami::username);
+        Self::new(&cfg.audit, principal).map(Arc::new)
+    }
+
+    pub fn new(settings: &AuditSettings, principal: String) -> Option<Self> {
+        Some(Self {
+            path: settings.path.clone()?,
+            max_size: settings.max_size,
+            max_files: settings.max_files,
+            principal,
+            lock: Mutex::new(()),
+        })
+    }
+
+    /// Records that the contents of the pointer file at `path` were
+    /// materialized. Failing to record fails the access.
+    pub fn record(
+        &self,
+        event: &str,
+        reference: Option<&str>,
+        path: &Path,
+        pointer: &PointerFile,
+    ) -> Result<()> {
+        let record = AuditRecord {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            event: event.to_string(),
+            reference: reference.map(str::to_string),
+            path: path.to_string_lossy().to_string(),
+            hash: pointer.hash_string().clone(),
+            bytes: pointer.filesize(),
+            principal: self.principal.clone(),
+            pid: std::process::id(),
+        };
+        self.append(&record)
+    }
+
+    fn append(&self, record: &AuditRecord) -> Result<()> {
+        let mut line =
+            serde_json::to_vec(record).map_err(|e| GitXetRepoError::Other(e.to_string()))?;
+        line.push(b'\n');
+
+        let _guard = self.lock.lock().unwrap();
+        // Other processes may share the log, so the size on disk is checked
+        // rather than tracked.
+        let size = fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        if size > 0 && size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        // A single write keeps lines from concurrent appenders whole.
+        file.write_all(&line)?;
+        Ok(())
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{index}"));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&self) -> Result<()> {
+        debug!("Rotating audit log {:?}", self.path);
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+            return Ok(());
+        }
+        let oldest = self.rotated_path(self.max_files);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+        for index in (1..self.max_files).rev() {
+            let from = self.rotated_path(index);
+            if from.exists() {
+                fs::rename(&from, self.rotated_path(index + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated_path(1))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_audit_log_appends_and_rotates() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let settings = AuditSettings {
+            path: Some(path.clone()),
+            max_size: 400,
+            max_files: 2,
+        };
+        let log = AuditLog::new(&settings, "alice".to_string()).unwrap();
+        let pointer = PointerFile::init_from_info("", "abcd", 1234);
+
+        log.record(
+            AUDIT_EVENT_MOUNT_READ,
+            Some("main"),
+            Path::new("data/a.csv"),
+            &pointer,
+        )
+        .unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let value: serde_json::Value = serde_json::from_str(contents.trim()).unwrap();
+        assert_eq!(value["event"], "mount_read");
+        assert_eq!(value["ref"], "main");
+        assert_eq!(value["path"], "data/a.csv");
+        assert_eq!(value["bytes"], 1234);
+        assert_eq!(value["principal"], "alice");
+
+        for _ in 0..10 {
+            log.record(AUDIT_EVENT_SMUDGE, None, Path::new("b.csv"), &pointer)
+                .unwrap();
+        }
+        assert!(fs::metadata(&path).unwrap().len() <= 400);
+        assert!(log.rotated_path(1).exists());
+        assert!(log.rotated_path(2).exists());
+        assert!(!log.rotated_path(3).exists());
+    }
+}
@@ -0,0 +1,191 @@
+use std::sync::Arc;
+
+use cas::key::Key;
+use cas_client::CasClientError;
+use hyper::body::HttpBody;
+use hyper::header::{CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE};
+use hyper::{Body, HeaderMap, Method, Request, Response, StatusCode};
+use merklehash::MerkleHash;
+use tonic::body::BoxBody;
+use tonic::codegen::Service;
+use tonic::Status;
+use tracing::{debug, error};
+
+use super::grpc::CasServerType;
+use super::metrics::encode_metrics;
+use super::{CasProxy, MAX_PENDING_PUT_BYTES};
+use crate::xetserve::RequestedRange;
+
+/// The path Prometheus metrics are served at.
+pub const METRICS_PATH: &str = "/metrics";
+
+const UNAUTHORIZED_MESSAGE: &str = "Missing or wrong CAS proxy token";
+
+fn simple_response(status: StatusCode, msg: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "text/plain")
+        .body(Body::from(format!("{msg}\n")))
+        .unwrap()
+}
+
+fn is_grpc(req: &Request<Body>) -> bool {
+    req.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.starts_with("application/grpc"))
+        .unwrap_or(false)
+}
+
+/// Parses the /<prefix>/<hash> path used by the data plane.
+pub(crate) fn parse_key(path: &str) -> Option<Key> {
+    let (prefix, hash) = path.trim_start_matches('/').rsplit_once('/')?;
+    let hash = MerkleHash::from_hex(hash).ok()?;
+    Some(Key {
+        prefix: prefix.to_string(),
+        hash,
+    })
+}
+
+fn upstream_error_response(key: &Key, e: CasClientError) -> Response<Body> {
+    match e {
+        CasClientError::XORBNotFound(_) => simple_response(StatusCode::NOT_FOUND, "Not found"),
+        e => {
+            error!("Error reading {key} from upstream: {e:?}");
+            simple_response(StatusCode::BAD_GATEWAY, &e.to_string())
+        }
+    }
+}
+
+/// Dispatches a request to either port of the proxy: gRPC calls to the CAS
+/// service, and everything else to the HTTP data plane.
+pub async fn handle_request(
+    proxy: Arc<CasProxy>,
+    mut grpc: CasServerType,
+    req: Request<Body>,
+) -> Response<BoxBody> {
+    // The metrics are served to anyone, so they can be scraped.
+    let authorized = req.uri().path() == METRICS_PATH || proxy.authorized(req.headers());
+    if is_grpc(&req) {
+        if !authorized {
+            return Status::unauthenticated(UNAUTHORIZED_MESSAGE).to_http();
+        }
+        return match grpc.call(req).await {
+            Ok(response) => response,
+            Err(never) => match never {},
+        };
+    }
+
+    debug!("{} {}", req.method(), req.uri());
+    let response = match *req.method() {
+        _ if !authorized => simple_response(StatusCode::UNAUTHORIZED, UNAUTHORIZED_MESSAGE),
+        Method::GET if req.uri().path() == METRICS_PATH => Response::builder()
+            .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+            .body(Body::from(encode_metrics()))
+            .unwrap(),
+        Method::GET => get_xorb(&proxy, req.uri().path(), req.headers()).await,
+        Method::POST => post_xorb(&proxy, req).await,
+        _ => simple_response(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "Only GET and POST are supported",
+        ),
+    };
+    response.map(|body| {
+        body.map_err(|e| Status::internal(e.to_string()))
+            .boxed_unsync()
+    })
+}
+
+/// Reads a xorb, or a single range of it. As with the CAS, range reads are
+/// answered with 200 rather than 206.
+async fn get_xorb(proxy: &CasProxy, path: &str, headers: &HeaderMap) -> Response<Body> {
+    let Some(key) = parse_key(path) else {
+        return simple_response(
+            StatusCode::NOT_FOUND,
+            "Xorbs are served at /<prefix>/<hash>",
+        );
+    };
+
+    let size = match proxy.get_length(&key).await {
+        Ok(size) => size,
+        Err(e) => return upstream_error_response(&key, e),
+    };
+    let (start, end) = match RequestedRange::from_headers(headers, size) {
+        RequestedRange::Full => (0, size),
+        RequestedRange::Partial(start, end) => (start, end),
+        RequestedRange::Unsatisfiable => {
+            return Response::builder()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(CONTENT_RANGE, format!("bytes */{size}"))
+                .body(Body::empty())
+                .unwrap();
+        }
+    };
+
+    let data = if (start, end) == (0, size) {
+        proxy.get(&key).await
+    } else {
+        proxy
+            .get_ranges(&key, vec![(start, end)])
+            .await
+            .map(|mut ranges| ranges.pop().unwrap_or_default())
+    };
+    match data {
+        Ok(data) => Response::builder()
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .header(CONTENT_LENGTH, data.len())
+            .body(Body::from(data))
+            .unwrap(),
+        Err(e) => upstream_error_response(&key, e),
+    }
+}
+
+/// Receives the data of an upload, to be stored upstream on PutComplete.
+async fn post_xorb(proxy: &CasProxy, req: Request<Body>) -> Response<Body> {
+    let Some(key) = parse_key(req.uri().path()) else {
+        return simple_response(
+            StatusCode::NOT_FOUND,
+            "Xorbs are uploaded to /<prefix>/<hash>",
+        );
+    };
+    let mut body = req.into_body();
+    let mut data = Vec::new();
+    while let Some(chunk) = body.data().await {
+        match chunk {
+            Ok(chunk) if data.len() + chunk.len() <= MAX_PENDING_PUT_BYTES => {
+                data.extend_from_slice(&chunk)
+            }
+            Ok(_) => return simple_response(StatusCode::PAYLOAD_TOO_LARGE, "Upload too large"),
+            Err(e) => {
+                return simple_response(StatusCode::BAD_REQUEST, &format!("Upload failed: {e}"))
+            }
+        }
+    }
+    if proxy.begin_put(key, data).await {
+        simple_response(StatusCode::OK, "")
+    } else {
+        simple_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Too much data is waiting to be stored upstream; retry later",
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key() {
+        let hash = MerkleHash::default();
+        let key = parse_key(&format!("/default/{}", hash.hex())).unwrap();
+        assert_eq!(key.prefix, "default");
+        assert_eq!(key.hash, hash);
+
+        let key = parse_key(&format!("/a/b/{}", hash.hex())).unwrap();
+        assert_eq!(key.prefix, "a/b");
+
+        assert!(parse_key("/default").is_none());
+        assert!(parse_key("/default/xyz").is_none());
+    }
+}
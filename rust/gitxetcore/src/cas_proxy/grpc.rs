@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use cas::cas::cas_server::{Cas, CasServer};
+use cas::cas::{
+    GetRangeRequest, GetRangeResponse, GetRequest, GetResponse, HeadRequest, HeadResponse,
+    PutCompleteRequest, PutCompleteResponse, PutRequest, PutResponse,
+};
+use cas::common::{InitiateRequest, InitiateResponse};
+use cas::key::Key;
+use cas_client::CasClientError;
+use tonic::{Request, Response, Status};
+
+use super::metrics::CAS_PROXY_REQUESTS;
+use super::CasProxy;
+
+pub type CasServerType = CasServer<CasProxyService>;
+
+/// The CAS gRPC service, answered by the proxy.
+pub struct CasProxyService {
+    proxy: Arc<CasProxy>,
+}
+
+impl CasProxyService {
+    /// Creates the gRPC server for proxy. Xorbs are sent whole in Put and Get
+    /// calls, so the default message size limit does not apply.
+    pub fn new_server(proxy: Arc<CasProxy>) -> CasServerType {
+        CasServer::new(CasProxyService { proxy })
+            .max_decoding_message_size(usize::MAX)
+            .max_encoding_message_size(usize::MAX)
+    }
+}
+
+fn parse_key(key: Option<&cas::common::Key>) -> Result<Key, Status> {
+    let key = key.ok_or_else(|| Status::invalid_argument("Missing key"))?;
+    Key::try_from(key).map_err(|e| Status::invalid_argument(format!("{e:?}")))
+}
+
+/// Converts an error from the upstream CAS to the status returned to clients.
+pub fn upstream_error_status(e: CasClientError) -> Status {
+    match e {
+        CasClientError::XORBNotFound(hash) => Status::not_found(format!("{hash} not found")),
+        CasClientError::InvalidRange | CasClientError::InvalidArguments => {
+            Status::invalid_argument(e.to_string())
+        }
+        CasClientError::HashMismatch => Status::failed_precondition(e.to_string()),
+        _ => Status::unavailable(format!("Upstream CAS error: {e}")),
+    }
+}
+
+#[tonic::async_trait]
+impl Cas for CasProxyService {
+    async fn initiate(
+        &self,
+        _request: Request<InitiateRequest>,
+    ) -> Result<Response<InitiateResponse>, Status> {
+        CAS_PROXY_REQUESTS.with_label_values(&["initiate"]).inc();
+        let endpoint = self.proxy.endpoint.clone();
+        Ok(Response::new(InitiateResponse {
+            cas_hostname: endpoint.host.clone(),
+            data_plane_endpoint: Some(endpoint.clone()),
+            put_complete_endpoint: Some(endpoint),
+        }))
+    }
+
+    async fn put(&self, request: Request<PutRequest>) -> Result<Response<PutResponse>, Status> {
+        let request = request.into_inner();
+        let key = parse_key(request.key.as_ref())?;
+        self.proxy
+            .put(&key, request.data, request.chunk_boundaries)
+            .await
+            .map_err(upstream_error_status)?;
+        Ok(Response::new(PutResponse { was_inserted: true }))
+    }
+
+    async fn put_complete(
+        &self,
+        request: Request<PutCompleteRequest>,
+    ) -> Result<Response<PutCompleteResponse>, Status> {
+        let request = request.into_inner();
+        let key = parse_key(request.key.as_ref())?;
+        let completed = self
+            .proxy
+            .complete_put(&key, request.chunk_boundaries)
+            .await
+            .map_err(upstream_error_status)?;
+        if !completed {
+            return Err(Status::failed_precondition(format!(
+                "No data uploaded for {key}"
+            )));
+        }
+        Ok(Response::new(PutCompleteResponse { was_inserted: true }))
+    }
+
+    async fn get(&self, request: Request<GetRequest>) -> Result<Response<GetResponse>, Status> {
+        let key = parse_key(request.get_ref().key.as_ref())?;
+        let data = self.proxy.get(&key).await.map_err(upstream_error_status)?;
+        Ok(Response::new(GetResponse { data }))
+    }
+
+    async fn get_range(
+        &self,
+        request: Request<GetRangeRequest>,
+    ) -> Result<Response<GetRangeResponse>, Status> {
+        let request = request.into_inner();
+        let key = parse_key(request.key.as_ref())?;
+        let ranges = request.ranges.iter().map(|r| (r.start, r.end)).collect();
+        let data = self
+            .proxy
+            .get_ranges(&key, ranges)
+            .await
+            .map_err(upstream_error_status)?;
+        Ok(Response::new(GetRangeResponse { data }))
+    }
+
+    async fn head(&self, request: Request<HeadRequest>) -> Result<Response<HeadResponse>, Status> {
+        let key = parse_key(request.get_ref().key.as_ref())?;
+        let size = self
+            .proxy
+            .get_length(&key)
+            .await
+            .map_err(upstream_error_status)?;
+        Ok(Response::new(HeadResponse { size }))
+    }
+}
@@ -0,0 +1,71 @@
+use lazy_static::lazy_static;
+use prometheus::{
+    register_gauge, register_int_counter, register_int_counter_vec, Encoder, Gauge, IntCounter,
+    IntCounterVec, TextEncoder,
+};
+
+lazy_static! {
+    pub static ref CAS_PROXY_REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "cas_proxy_requests",
+        "Number of requests received by the CAS proxy",
+        &["op"]
+    )
+    .unwrap();
+    pub static ref CAS_PROXY_BYTES_SERVED: IntCounter = register_int_counter!(
+        "cas_proxy_bytes_served",
+        "Number of xorb bytes returned to clients of the CAS proxy",
+    )
+    .unwrap();
+    pub static ref CAS_PROXY_UPSTREAM_REQUESTS: IntCounter = register_int_counter!(
+        "cas_proxy_upstream_requests",
+        "Number of reads the CAS proxy forwarded to the upstream CAS",
+    )
+    .unwrap();
+    pub static ref CAS_PROXY_UPSTREAM_BYTES: IntCounter = register_int_counter!(
+        "cas_proxy_upstream_bytes",
+        "Number of xorb bytes the CAS proxy downloaded from the upstream CAS",
+    )
+    .unwrap();
+    pub static ref CAS_PROXY_BYTE_HIT_RATE: Gauge = register_gauge!(
+        "cas_proxy_byte_hit_rate",
+        "Fraction of bytes served by the CAS proxy that did not have to be downloaded",
+    )
+    .unwrap();
+}
+
+/// The fraction of served bytes that were answered from the cache. Upstream
+/// reads are whole cache blocks, so they may exceed what was served for
+/// sparse range reads, in which case the hit rate is 0.
+pub fn byte_hit_rate(served: u64, upstream: u64) -> f64 {
+    if served == 0 {
+        return 0.0;
+    }
+    1.0 - (upstream as f64 / served as f64).min(1.0)
+}
+
+/// Renders all registered metrics, including those of the block cache, in
+/// the Prometheus text format.
+pub fn encode_metrics() -> String {
+    CAS_PROXY_BYTE_HIT_RATE.set(byte_hit_rate(
+        CAS_PROXY_BYTES_SERVED.get(),
+        CAS_PROXY_UPSTREAM_BYTES.get(),
+    ));
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&prometheus::gather(), &mut buffer) {
+        tracing::error!("Unable to encode metrics: {e:?}");
+    }
+    String::from_utf8_lossy(&buffer).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_hit_rate() {
+        assert_eq!(byte_hit_rate(0, 0), 0.0);
+        assert_eq!(byte_hit_rate(1000, 0), 1.0);
+        assert_eq!(byte_hit_rate(1000, 250), 0.75);
+        assert_eq!(byte_hit_rate(1000, 4000), 0.0);
+    }
+}
@@ -0,0 +1,377 @@
+//! A read-through caching proxy for the CAS, shared by a team on a LAN.
+//!
+//! The proxy speaks the same protocol as the CAS itself, so clients only
+//! need their `cas.server` setting pointed at it. Reads are served from a disk
+//! block cache with a size quota, fetching missing blocks from the upstream
+//! CAS with the credentials of the user running the proxy. Writes are passed
+//! through to the upstream CAS.
+//!
+//! Since anyone reaching the proxy reads with those credentials, every request
+//! but the metrics must carry the proxy's token, which clients configure as
+//! the user of their `cas.server` URL.
+//!
+//! The control plane (gRPC Initiate, Get, Head, ...) is served over plain
+//! HTTP/2 on the main port. The Initiate response directs data transfers and
+//! PutComplete calls to a second, TLS port with a certificate the proxy
+//! issues itself on startup, since the CAS client requires TLS there. Both
+//! ports also serve Prometheus metrics at /metrics, including the hit rate.
+mod data_plane;
+mod grpc;
+pub mod metrics;
+mod tls;
+mod upstream;
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use cas::common::{EndpointConfig, Scheme};
+use cas::constants::PROXY_TOKEN_HEADER;
+use cas::key::Key;
+use cas_client::{CasClientError, Client};
+use hyper::server::conn::Http;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::HeaderMap;
+use hyper::Server;
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use self::data_plane::handle_request;
+use self::grpc::CasProxyService;
+use self::metrics::{CAS_PROXY_BYTES_SERVED, CAS_PROXY_REQUESTS};
+use crate::errors::{GitXetRepoError, Result};
+pub(crate) use data_plane::parse_key;
+pub use tls::ProxyCertificate;
+pub use upstream::CountingClient;
+
+/// The most data held for uploads waiting for their PutComplete call.
+const MAX_PENDING_PUT_BYTES: usize = 1 << 30;
+
+/// How long the data of an upload is held waiting for its PutComplete call.
+const PENDING_PUT_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// The state shared by all requests to the proxy.
+pub struct CasProxy {
+    upstream: Arc<dyn Client + Send + Sync>,
+
+    /// The TLS endpoint handed out to clients for data transfers.
+    endpoint: EndpointConfig,
+
+    /// The token clients must send in the PROXY_TOKEN_HEADER header.
+    token: String,
+
+    /// Xorbs uploaded over the data plane, waiting for their PutComplete
+    /// call which carries the chunk boundaries needed to store them upstream.
+    pending_puts: Mutex<PendingPuts>,
+}
+
+struct PendingPut {
+    data: Vec<u8>,
+    received: Instant,
+}
+
+struct PendingPuts {
+    puts: HashMap<Key, PendingPut>,
+    bytes: usize,
+    max_bytes: usize,
+}
+
+impl PendingPuts {
+    fn new(max_bytes: usize) -> Self {
+        Self {
+            puts: HashMap::new(),
+            bytes: 0,
+            max_bytes,
+        }
+    }
+
+    /// Drops the uploads whose PutComplete never arrived.
+    fn expire(&mut self, now: Instant) {
+        let bytes = &mut self.bytes;
+        self.puts.retain(|key, put| {
+            let live = now.duration_since(put.received) < PENDING_PUT_TTL;
+            if !live {
+                warn!("Dropping upload of {key} with no PutComplete");
+                *bytes -= put.data.len();
+            }
+            live
+        });
+    }
+
+    fn remove(&mut self, key: &Key) -> Option<Vec<u8>> {
+        let put = self.puts.remove(key)?;
+        self.bytes -= put.data.len();
+        Some(put.data)
+    }
+}
+
+impl CasProxy {
+    /// Creates a proxy in front of upstream, which should be a caching client.
+    /// Clients reach the TLS port at hostname, which the certificate is for.
+    /// Clients must send token with every request.
+    pub fn new(
+        upstream: Arc<dyn Client + Send + Sync>,
+        hostname: &str,
+        tls_port: u16,
+        certificate: &ProxyCertificate,
+        token: &str,
+    ) -> Self {
+        Self {
+            upstream,
+            endpoint: EndpointConfig {
+                host: hostname.to_string(),
+                port: tls_port as i32,
+                scheme: Scheme::Https.into(),
+                root_ca_certificate: certificate.pem.clone(),
+            },
+            token: token.to_string(),
+            pending_puts: Mutex::new(PendingPuts::new(MAX_PENDING_PUT_BYTES)),
+        }
+    }
+
+    /// Whether the request headers carry the proxy's token.
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        headers
+            .get(PROXY_TOKEN_HEADER)
+            .map_or(false, |token| token.as_bytes() == self.token.as_bytes())
+    }
+
+    async fn get_length(&self, key: &Key) -> std::result::Result<u64, CasClientError> {
+        CAS_PROXY_REQUESTS.with_label_values(&["head"]).inc();
+        self.upstream.get_length(&key.prefix, &key.hash).await
+    }
+
+    async fn get_ranges(
+        &self,
+        key: &Key,
+        ranges: Vec<(u64, u64)>,
+    ) -> std::result::Result<Vec<Vec<u8>>, CasClientError> {
+        CAS_PROXY_REQUESTS.with_label_values(&["get"]).inc();
+        let data = self
+            .upstream
+            .get_object_range(&key.prefix, &key.hash, ranges)
+            .await?;
+        CAS_PROXY_BYTES_SERVED.inc_by(data.iter().map(|d| d.len() as u64).sum());
+        Ok(data)
+    }
+
+    async fn get(&self, key: &Key) -> std::result::Result<Vec<u8>, CasClientError> {
+        CAS_PROXY_REQUESTS.with_label_values(&["get"]).inc();
+        let data = self.upstream.get(&key.prefix, &key.hash).await?;
+        CAS_PROXY_BYTES_SERVED.inc_by(data.len() as u64);
+        Ok(data)
+    }
+
+    async fn put(
+        &self,
+        key: &Key,
+        data: Vec<u8>,
+        chunk_boundaries: Vec<u64>,
+    ) -> std::result::Result<(), CasClientError> {
+        CAS_PROXY_REQUESTS.with_label_values(&["put"]).inc();
+        self.upstream
+            .put(&key.prefix, &key.hash, data, chunk_boundaries)
+            .await
+    }
+
+    /// Holds the data of a data plane upload until its PutComplete arrives,
+    /// for up to PENDING_PUT_TTL. Returns false if holding it would exceed
+    /// MAX_PENDING_PUT_BYTES in all.
+    async fn begin_put(&self, key: Key, data: Vec<u8>) -> bool {
+        let mut pending = self.pending_puts.lock().await;
+        let now = Instant::now();
+        pending.expire(now);
+        pending.remove(&key);
+        if pending.bytes + data.len() > pending.max_bytes {
+            return false;
+        }
+        pending.bytes += data.len();
+        pending.puts.insert(
+            key,
+            PendingPut {
+                data,
+                received: now,
+            },
+        );
+        true
+    }
+
+    /// Stores an upload started with begin_put upstream. Returns false if no
+    /// data was uploaded for the key.
+    async fn complete_put(
+        &self,
+        key: &Key,
+        chunk_boundaries: Vec<u64>,
+    ) -> std::result::Result<bool, CasClientError> {
+        let Some(data) = self.pending_puts.lock().await.remove(key) else {
+            return Ok(false);
+        };
+        self.put(key, data, chunk_boundaries).await?;
+        Ok(true)
+    }
+}
+
+/// Runs the proxy, serving the control plane on addr and the TLS data plane
+/// on tls_addr, until the process is interrupted.
+pub async fn run_cas_proxy(
+    proxy: CasProxy,
+    certificate: ProxyCertificate,
+    addr: SocketAddr,
+    tls_addr: SocketAddr,
+) -> Result<()> {
+    let proxy = Arc::new(proxy);
+    let grpc = CasProxyService::new_server(proxy.clone());
+
+    let (plain_proxy, plain_grpc) = (proxy.clone(), grpc.clone());
+    let make_svc = make_service_fn(move |_conn| {
+        let (proxy, grpc) = (plain_proxy.clone(), plain_grpc.clone());
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let fut = handle_request(proxy.clone(), grpc.clone(), req);
+                async move { Ok::<_, Infallible>(fut.await) }
+            }))
+        }
+    });
+    let plain_server = Server::try_bind(&addr)
+        .map_err(|source| GitXetRepoError::HttpServerError { addr, source })?
+        .serve(make_svc);
+
+    let listener = TcpListener::bind(tls_addr)
+        .await
+        .map_err(|e| GitXetRepoError::Other(format!("Unable to bind to {tls_addr}: {e}")))?;
+
+    info!("Serving CAS proxy on http://{addr}, data plane on {tls_addr}");
+    eprintln!("Serving CAS proxy on http://{}", plain_server.local_addr());
+    eprintln!(
+        "Data transfers are directed to {} on port {}",
+        proxy.endpoint.host, proxy.endpoint.port
+    );
+
+    tokio::select! {
+        result = plain_server => {
+            result.map_err(|source| GitXetRepoError::HttpServerError { addr, source })
+        }
+        result = serve_tls(listener, certificate, proxy, grpc) => result,
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    }
+}
+
+/// Accepts TLS connections on listener, serving each over HTTP/2.
+async fn serve_tls(
+    listener: TcpListener,
+    certificate: ProxyCertificate,
+    proxy: Arc<CasProxy>,
+    grpc: grpc::CasServerType,
+) -> Result<()> {
+    loop {
+        let (stream, remote) = listener.accept().await?;
+        let acceptor = certificate.acceptor.clone();
+        let (proxy, grpc) = (proxy.clone(), grpc.clone());
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    debug!("TLS handshake with {remote} failed: {e}");
+                    return;
+                }
+            };
+            let service = service_fn(move |req| {
+                let fut = handle_request(proxy.clone(), grpc.clone(), req);
+                async move { Ok::<_, Infallible>(fut.await) }
+            });
+            if let Err(e) = Http::new()
+                .http2_only(true)
+                .http2_adaptive_window(true)
+                .serve_connection(stream, service)
+                .await
+            {
+                debug!("Connection from {remote} closed: {e}");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use cas_client::LocalClient;
+    use merklehash::MerkleHash;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn local_proxy(dir: &Path) -> CasProxy {
+        let certificate = ProxyCertificate::generate("localhost").unwrap();
+        let upstream = Arc::new(CountingClient::new(LocalClient::new(dir, false)));
+        CasProxy::new(upstream, "localhost", 4041, &certificate, "secret")
+    }
+
+    #[tokio::test]
+    async fn test_pending_put() {
+        let dir = TempDir::new().unwrap();
+        let proxy = local_proxy(dir.path());
+        let data = vec![7u8; 100];
+        let key = Key {
+            prefix: "default".to_string(),
+            hash: merklehash::compute_data_hash(&data),
+        };
+
+        // PutComplete without an upload is rejected.
+        assert!(!proxy.complete_put(&key, vec![100]).await.unwrap());
+
+        assert!(proxy.begin_put(key.clone(), data.clone()).await);
+        assert!(proxy.complete_put(&key, vec![100]).await.unwrap());
+        assert!(proxy.pending_puts.lock().await.puts.is_empty());
+        assert_eq!(proxy.pending_puts.lock().await.bytes, 0);
+
+        assert_eq!(proxy.get_length(&key).await.unwrap(), 100);
+        let ranges = proxy.get_ranges(&key, vec![(10, 20)]).await.unwrap();
+        assert_eq!(ranges, vec![data[10..20].to_vec()]);
+        assert!(proxy
+            .get(&Key {
+                prefix: "default".to_string(),
+                hash: MerkleHash::default(),
+            })
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pending_puts_capped_and_expired() {
+        let dir = TempDir::new().unwrap();
+        let proxy = local_proxy(dir.path());
+        proxy.pending_puts.lock().await.max_bytes = 100;
+        let key = |i: u8| Key {
+            prefix: "default".to_string(),
+            hash: merklehash::compute_data_hash(&[i]),
+        };
+
+        assert!(proxy.begin_put(key(0), vec![0u8; 50]).await);
+        // Uploading the same xorb again replaces its data.
+        assert!(proxy.begin_put(key(0), vec![0u8; 50]).await);
+        assert!(!proxy.begin_put(key(1), vec![0u8; 100]).await);
+        assert!(proxy.begin_put(key(1), vec![0u8; 50]).await);
+
+        let mut pending = proxy.pending_puts.lock().await;
+        assert_eq!(pending.bytes, 100);
+        pending.expire(Instant::now() + PENDING_PUT_TTL);
+        assert!(pending.puts.is_empty());
+        assert_eq!(pending.bytes, 0);
+    }
+
+    #[test]
+    fn test_authorized() {
+        let dir = TempDir::new().unwrap();
+        let proxy = local_proxy(dir.path());
+        let mut headers = HeaderMap::new();
+        assert!(!proxy.authorized(&headers));
+        headers.insert(PROXY_TOKEN_HEADER, "wrong".parse().unwrap());
+        assert!(!proxy.authorized(&headers));
+        headers.insert(PROXY_TOKEN_HEADER, "secret".parse().unwrap());
+        assert!(proxy.authorized(&headers));
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+
+use crate::errors::{GitXetRepoError, Result};
+
+/// A self-signed certificate for the proxy's TLS endpoint.
+///
+/// The CAS client only transfers data over TLS, trusting the root certificate
+/// sent along with the endpoint in the Initiate response. The proxy therefore
+/// issues itself a certificate on startup and hands it out the same way,
+/// so no certificates have to be distributed to clients.
+pub struct ProxyCertificate {
+    /// The certificate in PEM format, as sent to clients.
+    pub pem: String,
+
+    /// Accepts TLS connections using the certificate, negotiating HTTP/2.
+    pub acceptor: TlsAcceptor,
+}
+
+impl ProxyCertificate {
+    /// Generates a certificate for hostname, which is a DNS name or an IP address.
+    pub fn generate(hostname: &str) -> Result<Self> {
+        let cert = rcgen::generate_simple_self_signed(vec![hostname.to_string()])
+            .map_err(|e| GitXetRepoError::Other(format!("Unable to generate certificate: {e}")))?;
+        let cert_der = cert
+            .serialize_der()
+            .map_err(|e| GitXetRepoError::Other(format!("Unable to serialize certificate: {e}")))?;
+        let key_der = cert.serialize_private_key_der();
+
+        let mut config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![CertificateDer::from(cert_der.clone())],
+                PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_der)),
+            )
+            .map_err(|e| GitXetRepoError::Other(format!("Invalid TLS configuration: {e}")))?;
+        config.alpn_protocols = vec![b"h2".to_vec()];
+
+        Ok(Self {
+            pem: der_to_pem(&cert_der),
+            acceptor: TlsAcceptor::from(Arc::new(config)),
+        })
+    }
+}
+
+/// Encodes a DER certificate as PEM. rcgen signs anew on every
+/// serialization, so the PEM sent to clients is derived from the same DER
+/// presented in the handshake.
+fn der_to_pem(der: &[u8]) -> String {
+    let encoded = base64::encode(der);
+    let mut pem = String::from("-----BEGIN CERTIFICATE-----\n");
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(&String::from_utf8_lossy(line));
+        pem.push('\n');
+    }
+    pem.push_str("-----END CERTIFICATE-----\n");
+    pem
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_der_to_pem() {
+        let pem = der_to_pem(&[0u8; 60]);
+        let lines: Vec<&str> = pem.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "-----BEGIN CERTIFICATE-----");
+        assert_eq!(lines[1].len(), 64);
+        assert_eq!(lines[2], "AAAAAAAAAAAAAAAA");
+        assert_eq!(lines[3], "-----END CERTIFICATE-----");
+    }
+
+    #[test]
+    fn test_generate() {
+        let cert = ProxyCertificate::generate("cas-proxy.local").unwrap();
+        assert!(cert.pem.starts_with("-----BEGIN CERTIFICATE-----\n"));
+    }
+}
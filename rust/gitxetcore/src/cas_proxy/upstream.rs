@@ -0,0 +1,58 @@
+use async_trait::async_trait;
+use cas_client::{CasClientError, Client};
+use merklehash::MerkleHash;
+
+use super::metrics::{CAS_PROXY_UPSTREAM_BYTES, CAS_PROXY_UPSTREAM_REQUESTS};
+
+/// Wraps the client to the upstream CAS, counting what has to be downloaded
+/// because it was not found in the block cache sitting in front of it.
+#[derive(Debug)]
+pub struct CountingClient<T: Client> {
+    client: T,
+}
+
+impl<T: Client> CountingClient<T> {
+    pub fn new(client: T) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl<T: Client + Send + Sync> Client for CountingClient<T> {
+    async fn put(
+        &self,
+        prefix: &str,
+        hash: &MerkleHash,
+        data: Vec<u8>,
+        chunk_boundaries: Vec<u64>,
+    ) -> Result<(), CasClientError> {
+        self.client.put(prefix, hash, data, chunk_boundaries).await
+    }
+
+    async fn flush(&self) -> Result<(), CasClientError> {
+        self.client.flush().await
+    }
+
+    async fn get(&self, prefix: &str, hash: &MerkleHash) -> Result<Vec<u8>, CasClientError> {
+        let data = self.client.get(prefix, hash).await?;
+        CAS_PROXY_UPSTREAM_REQUESTS.inc();
+        CAS_PROXY_UPSTREAM_BYTES.inc_by(data.len() as u64);
+        Ok(data)
+    }
+
+    async fn get_object_range(
+        &self,
+        prefix: &str,
+        hash: &MerkleHash,
+        ranges: Vec<(u64, u64)>,
+    ) -> Result<Vec<Vec<u8>>, CasClientError> {
+        let data = self.client.get_object_range(prefix, hash, ranges).await?;
+        CAS_PROXY_UPSTREAM_REQUESTS.inc();
+        CAS_PROXY_UPSTREAM_BYTES.inc_by(data.iter().map(|d| d.len() as u64).sum());
+        Ok(data)
+    }
+
+    async fn get_length(&self, prefix: &str, hash: &MerkleHash) -> Result<u64, CasClientError> {
+        self.client.get_length(prefix, hash).await
+    }
+}
@@ -0,0 +1,160 @@
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Subcommand};
+use utils::output_bytes::output_bytes;
+
+use crate::archive::{
+    parity_path, repair_with_parity, verify_parity, write_parity, ParityLayout, ParityReport,
+};
+use crate::config::XetConfig;
+use crate::constants::{
+    ARCHIVE_PARITY_BLOCK_SIZE, ARCHIVE_PARITY_DATA_BLOCKS, DEFAULT_ARCHIVE_PARITY_BLOCKS,
+};
+use crate::errors::{GitXetRepoError, Result};
+use crate::git_integration::GitXetRepo;
+
+#[non_exhaustive]
+#[derive(Subcommand, Debug)]
+enum ArchiveCommand {
+    Create(ArchiveCreateArgs),
+    Verify(ArchiveCheckArgs),
+    Repair(ArchiveCheckArgs),
+}
+
+/// Exports the files at a commit, smudged, into an archive, as by
+/// `git archive`; the format is taken from the extension of the output,
+/// e.g. .tar, .tar.gz or .zip.
+#[derive(Args, Debug)]
+struct ArchiveCreateArgs {
+    /// The archive to write.
+    output: PathBuf,
+
+    /// The commit to export.
+    #[clap(default_value = "HEAD")]
+    reference: String,
+
+    /// Also write Reed–Solomon repair blocks to <output>.parity, with which
+    /// corrupted parts of the archive can be rebuilt.
+    #[clap(long)]
+    with_parity: bool,
+
+    /// The repair blocks of every 32 blocks of the archive: how many of
+    /// them can be rebuilt.
+    #[clap(long, default_value_t = DEFAULT_ARCHIVE_PARITY_BLOCKS)]
+    parity_blocks: usize,
+}
+
+/// Checks, or repairs, an archive against its <archive>.parity file.
+#[derive(Args, Debug)]
+struct ArchiveCheckArgs {
+    archive: PathBuf,
+}
+
+/// Offline archives of the files at a commit, which can carry repair blocks
+/// so that cold-storage copies of a dataset survive bit rot.
+///
+/// ```ignore
+/// git xet archive create data-v1.tar v1.0 --with-parity
+/// git xet archive verify data-v1.tar
+/// git xet archive repair data-v1.tar
+/// ```
+// THIS "SHIM" STRUCT IS MANDATORY
+#[derive(Args, Debug)]
+pub struct ArchiveCommandShim {
+    #[clap(subcommand)]
+    subcommand: ArchiveCommand,
+}
+
+impl ArchiveCommandShim {
+    pub fn subcommand_name(&self) -> String {
+        match self.subcommand {
+            ArchiveCommand::Create(_) => "create".to_string(),
+            ArchiveCommand::Verify(_) => "verify".to_string(),
+            ArchiveCommand::Repair(_) => "repair".to_string(),
+        }
+    }
+}
+
+pub async fn archive_command(cfg: XetConfig, command: &ArchiveCommandShim) -> Result<()> {
+    match &command.subcommand {
+        ArchiveCommand::Create(args) => archive_create_command(cfg, args),
+        ArchiveCommand::Verify(args) => archive_verify_command(&args.archive),
+        ArchiveCommand::Repair(args) => archive_repair_command(&args.archive),
+    }
+}
+
+fn archive_create_command(cfg: XetConfig, args: &ArchiveCreateArgs) -> Result<()> {
+    if args.with_parity && args.parity_blocks == 0 {
+        return Err(GitXetRepoError::InvalidOperation(
+            "--parity-blocks must be at least 1".to_string(),
+        ));
+    }
+    let repo = GitXetRepo::open(cfg)?;
+    // git runs in the repository, not the current directory.
+    let output = std::env::current_dir()?.join(&args.output);
+    let output_str = output.to_string_lossy();
+    // git archive smudges the files through the xet filter, as a checkout
+    // would.
+    repo.run_git_checked_in_repo("archive", &["-o", &output_str, &args.reference])?;
+    let size = std::fs::metadata(&output)?.len();
+    println!(
+        "Archived {} to {output_str} ({})",
+        args.reference,
+        output_bytes(size as usize)
+    );
+
+    if args.with_parity {
+        let layout = ParityLayout {
+            block_size: ARCHIVE_PARITY_BLOCK_SIZE,
+            data_blocks: ARCHIVE_PARITY_DATA_BLOCKS,
+            parity_blocks: args.parity_blocks,
+        };
+        write_parity(&output, &layout)?;
+        let parity = parity_path(&output);
+        let parity_size = std::fs::metadata(&parity)?.len();
+        println!(
+            "Wrote the repair blocks to {} ({})",
+            parity.to_string_lossy(),
+            output_bytes(parity_size as usize)
+        );
+    }
+    Ok(())
+}
+
+fn archive_verify_command(archive: &Path) -> Result<()> {
+    let report = verify_parity(archive)?;
+    print_report(archive, &report);
+    if report.is_intact() {
+        return Ok(());
+    }
+    let hint = if report.is_repairable() {
+        "; run git xet archive repair to rebuild them"
+    } else {
+        ", more than can be repaired"
+    };
+    Err(GitXetRepoError::IntegrityCheckFailed(format!(
+        "{archive:?} has corrupted blocks{hint}"
+    )))
+}
+
+fn archive_repair_command(archive: &Path) -> Result<()> {
+    let report = repair_with_parity(archive)?;
+    print_report(archive, &report);
+    if !report.is_repairable() {
+        return Err(GitXetRepoError::IntegrityCheckFailed(format!(
+            "{} stripes of {archive:?} have more corrupted blocks than repair blocks",
+            report.unrepairable_stripes
+        )));
+    }
+    if !report.is_intact() {
+        println!("Repaired {archive:?}");
+    }
+    Ok(())
+}
+
+fn print_report(archive: &Path, report: &ParityReport) {
+    println!(
+        "{archive:?}: {} blocks, {} corrupted, {} repair blocks corrupted",
+        report.blocks, report.corrupt_blocks, report.corrupt_parity_blocks
+    );
+}
@@ -0,0 +1,80 @@
+//! The `--batch` mode shared by the plumbing commands, to process many paths
+//! in one process rather than a process per path, as `git cat-file --batch`
+//! does.
+//!
+//! The inputs are read from stdin, one per line, or separated by NUL bytes
+//! with `-z`; empty inputs are skipped. The answer to each input is written
+//! to stdout as a line of JSON, with an "error" field for the inputs that
+//! failed, so that a failure doesn't stop the batch; only cat writes the
+//! content of each file instead, as `git cat-file --batch` does.
+use std::io::{self, BufRead, Write};
+
+use serde::Serialize;
+
+use crate::errors::Result;
+
+/// The inputs read from reader, separated by newlines, or by NUL bytes if
+/// nul is set. Trailing carriage returns of lines are dropped.
+pub fn batch_inputs(reader: impl BufRead, nul: bool) -> impl Iterator<Item = io::Result<String>> {
+    let delimiter = if nul { b'\0' } else { b'\n' };
+    reader
+        .split(delimiter)
+        .map(move |input| {
+            let mut input = input?;
+            if !nul && input.last() == Some(&b'\r') {
+                input.pop();
+            }
+            String::from_utf8(input).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        })
+        .filter(|input| !matches!(input, Ok(s) if s.is_empty()))
+}
+
+/// Reads all the inputs from stdin, for commands answering them together.
+pub fn read_batch_inputs(nul: bool) -> Result<Vec<String>> {
+    Ok(batch_inputs(io::stdin().lock(), nul).collect::<io::Result<_>>()?)
+}
+
+/// The answer to an input that failed.
+#[derive(Serialize, Debug)]
+pub struct BatchError<'a> {
+    pub input: &'a str,
+    pub error: String,
+}
+
+/// Writes value as a line of JSON and flushes it, so that a script can read
+/// the answer to an input before writing the next.
+pub fn write_batch_line(out: &mut impl Write, value: &impl Serialize) -> Result<()> {
+    serde_json::to_writer(&mut *out, value)?;
+    out.write_all(b"\n")?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_batch_inputs() {
+        let inputs = |data: &[u8], nul| -> Vec<String> {
+            batch_inputs(Cursor::new(data.to_vec()), nul)
+                .collect::<io::Result<_>>()
+                .unwrap()
+        };
+        assert_eq!(
+            inputs(b"a.csv\nb c.bin\r\n\nd", false),
+            ["a.csv", "b c.bin", "d"]
+        );
+        assert_eq!(inputs(b"a\nb\0c\0", true), ["a\nb", "c"]);
+        assert!(inputs(b"", false).is_empty());
+
+        let mut out = Vec::new();
+        let error = BatchError {
+            input: "x",
+            error: "not found".to_string(),
+        };
+        write_batch_line(&mut out, &error).unwrap();
+        assert_eq!(out, b"{\"input\":\"x\",\"error\":\"not found\"}\n");
+    }
+}
@@ -0,0 +1,235 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::anyhow;
+use clap::Args;
+use rand::{RngCore, SeedableRng};
+use serde::Serialize;
+use tracing::info;
+
+use crate::config::XetConfig;
+use crate::constants::{GIT_MAX_PACKET_SIZE, LOCAL_CAS_SCHEME};
+use crate::data::remote_shard_interface::{GlobalDedupPolicy, SmudgeQueryPolicy};
+use crate::data::{PendingUpload, PointerFile, PointerFileTranslator};
+use crate::errors::{GitXetRepoError, Result};
+use crate::git_integration::git_repo_salt::generate_repo_salt;
+use crate::stream::data_iterators::AsyncFileIterator;
+
+/// The unit the synthetic data is generated in; the compressible part of
+/// each block is zeroed.
+const BENCH_BLOCK_SIZE: usize = 64 * 1024;
+
+const BENCH_FILE_NAME: &str = "xet-bench.dat";
+
+/// Measures clean, smudge, push, and fetch throughput on synthetic data and
+/// prints a JSON report.
+///
+/// Push uploads the generated data to a CAS in a temporary directory and
+/// fetch reads it back. With --remote they measure the configured CAS
+/// endpoint instead, without the local cache; the data is random on every
+/// run, so each such run stores up to --size-mb of new data in the CAS.
+#[derive(Args, Debug)]
+pub struct BenchArgs {
+    /// The size of the generated data in MB.
+    #[clap(long, default_value = "256")]
+    pub size_mb: u64,
+
+    /// The fraction of the data, between 0 and 1, that is zeros.
+    #[clap(long, default_value = "0")]
+    pub compressibility: f64,
+
+    /// The number of times each unique block of data is repeated. 1 produces
+    /// data that does not deduplicate.
+    #[clap(long, default_value = "1")]
+    pub dedup_factor: u64,
+
+    /// Push to and fetch from the configured CAS endpoint.
+    #[clap(long)]
+    pub remote: bool,
+
+    /// Writes the report to this file instead of stdout.
+    #[clap(long, short)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Serialize, Debug)]
+struct StageReport {
+    bytes: u64,
+    seconds: f64,
+    mb_per_sec: f64,
+}
+
+impl StageReport {
+    fn new(bytes: u64, start: Instant) -> Self {
+        let seconds = start.elapsed().as_secs_f64();
+        Self {
+            bytes,
+            seconds,
+            mb_per_sec: bytes as f64 / (1024. * 1024.) / seconds.max(f64::EPSILON),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct BenchReport {
+    version: String,
+    endpoint: String,
+    size_bytes: u64,
+    compressibility: f64,
+    dedup_factor: u64,
+    /// The bytes left to upload after deduplication.
+    stored_bytes: u64,
+    clean: StageReport,
+    smudge: StageReport,
+    push: StageReport,
+    fetch: StageReport,
+}
+
+/// Generates size bytes of random data where each unique block appears
+/// dedup_factor times and the given fraction of each block is zeros.
+fn generate_bench_data(size: usize, compressibility: f64, dedup_factor: u64) -> Vec<u8> {
+    let mut rng = rand::rngs::StdRng::from_entropy();
+    let zeros = (BENCH_BLOCK_SIZE as f64 * compressibility.clamp(0., 1.)) as usize;
+    let unique_size = size / dedup_factor.max(1) as usize;
+
+    let mut unique = vec![0u8; unique_size];
+    for block in unique.chunks_mut(BENCH_BLOCK_SIZE) {
+        let random_start = zeros.min(block.len());
+        rng.fill_bytes(&mut block[random_start..]);
+    }
+
+    let mut data = Vec::with_capacity(size);
+    while data.len() + unique.len() <= size && !unique.is_empty() {
+        data.extend_from_slice(&unique);
+    }
+    data.resize(size, 0);
+    data
+}
+
+/// Returns a copy of cfg that keeps all of the benchmark's state under dir,
+/// so that the benchmark neither reads nor changes any repository.
+fn bench_config(cfg: &XetConfig, dir: &Path, remote: bool) -> Result<XetConfig> {
+    let mut cfg = cfg.clone();
+    cfg.repo_path_if_present = None;
+    cfg.staging_path = Some(dir.join("staging"));
+    cfg.merkledb_v2_session = dir.join("session");
+    cfg.merkledb_v2_cache = PathBuf::default();
+    cfg.lazy_config = None;
+    // Fetch should measure the endpoint, not the local cache.
+    cfg.cache.enabled = false;
+    cfg.smudge_query_policy = SmudgeQueryPolicy::LocalOnly;
+    cfg.global_dedup_query_policy = GlobalDedupPolicy::Never;
+    if !remote {
+        std::fs::create_dir_all(dir.join("cas"))?;
+        cfg.cas.endpoint = format!("{LOCAL_CAS_SCHEME}{}", dir.join("cas").display());
+    }
+    std::fs::create_dir_all(dir.join("staging"))?;
+    std::fs::create_dir_all(dir.join("session"))?;
+    Ok(cfg)
+}
+
+pub async fn bench_command(cfg: XetConfig, args: &BenchArgs) -> Result<()> {
+    if !(0. ..=1.).contains(&args.compressibility) {
+        return Err(GitXetRepoError::InvalidOperation(
+            "--compressibility must be between 0 and 1".to_string(),
+        ));
+    }
+    if args.dedup_factor == 0 {
+        return Err(GitXetRepoError::InvalidOperation(
+            "--dedup-factor must be at least 1".to_string(),
+        ));
+    }
+
+    let dir = tempfile::TempDir::new()?;
+    let cfg = bench_config(&cfg, dir.path(), args.remote)?;
+    let path = PathBuf::from(BENCH_FILE_NAME);
+
+    let size = (args.size_mb * 1024 * 1024) as usize;
+    info!("Generating {} bytes of benchmark data.", size);
+    let data: Arc<[u8]> = generate_bench_data(size, args.compressibility, args.dedup_factor).into();
+
+    let translator = PointerFileTranslator::v2_from_config(&cfg, generate_repo_salt()?).await?;
+
+    info!("Benchmarking clean.");
+    let start = Instant::now();
+    let reader = AsyncFileIterator::new(Cursor::new(data.clone()), GIT_MAX_PACKET_SIZE);
+    let pointer = translator.clean_file(&path, reader).await?;
+    translator.finalize_cleaning().await?;
+    let clean = StageReport::new(size as u64, start);
+
+    let pointer =
+        PointerFile::init_from_string(&String::from_utf8_lossy(&pointer), BENCH_FILE_NAME);
+    if !pointer.is_valid() {
+        return Err(anyhow!("Benchmark data was not cleaned to a pointer file").into());
+    }
+
+    // Before the push the data is read back from the staging directory.
+    info!("Benchmarking smudge.");
+    let start = Instant::now();
+    translator
+        .smudge_file_from_pointer(&path, &pointer, &mut std::io::sink(), None)
+        .await?;
+    let smudge = StageReport::new(size as u64, start);
+
+    let pending = PendingUpload::load(
+        &cfg.merkledb_v2_session,
+        dir.path().join("staging").as_path(),
+    )?;
+
+    info!("Benchmarking push to {}.", cfg.cas.endpoint);
+    let start = Instant::now();
+    translator.upload_cas_staged(false).await?;
+    let push = StageReport::new(pending.xorb_bytes, start);
+
+    // The staged xorbs are removed once uploaded, so this reads from the
+    // endpoint.
+    info!("Benchmarking fetch from {}.", cfg.cas.endpoint);
+    let start = Instant::now();
+    translator
+        .smudge_file_from_pointer(&path, &pointer, &mut std::io::sink(), None)
+        .await?;
+    let fetch = StageReport::new(size as u64, start);
+
+    let report = BenchReport {
+        version: crate::constants::CURRENT_VERSION.to_string(),
+        endpoint: cfg.cas.endpoint.clone(),
+        size_bytes: size as u64,
+        compressibility: args.compressibility,
+        dedup_factor: args.dedup_factor,
+        stored_bytes: pending.xorb_bytes,
+        clean,
+        smudge,
+        push,
+        fetch,
+    };
+    let report = serde_json::to_string_pretty(&report)?;
+
+    match &args.output {
+        Some(output) => std::fs::write(output, report)?,
+        None => println!("{report}"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_bench_data() {
+        let size = 16 * BENCH_BLOCK_SIZE;
+        let data = generate_bench_data(size, 0.5, 4);
+        assert_eq!(data.len(), size);
+        let quarter = size / 4;
+        assert_eq!(data[..quarter], data[quarter..2 * quarter]);
+        assert!(data[..BENCH_BLOCK_SIZE / 2].iter().all(|b| *b == 0));
+        assert!(data[BENCH_BLOCK_SIZE / 2..BENCH_BLOCK_SIZE]
+            .iter()
+            .any(|b| *b != 0));
+
+        assert_eq!(generate_bench_data(100, 0., 1).len(), 100);
+        assert_eq!(generate_bench_data(100, 1., 1000).len(), 100);
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use clap::Args;
+use git2::Repository;
+use merklehash::MerkleHash;
+use pbr::{ProgressBar, Units};
+use tracing::{error, info, warn};
+
+use super::checkout::{
+    resolve_pathspec, smudged_pointer, tree_checkouts, update_index, write_checked_out,
+    PathspecRelativity,
+};
+use crate::config::XetConfig;
+use crate::data::preflight::{check_disk_space, materialized_bytes};
+use crate::data::{PointerFile, PointerFileTranslator};
+use crate::errors::Result;
+use crate::transfer_report;
+
+/// Checks out the files of a ref matching the paths into the working
+/// directory, fetching the data of all of them at once.
+///
+/// Unlike git checkout, which smudges the files one after the other, the
+/// reads of every file are planned up front: data shared by several files,
+/// or by copies of the same file, is fetched once, and each xorb is read in
+/// as few requests as possible, in order. This is much faster for dataset
+/// repositories with many files.
+///
+/// ```ignore
+/// git xet bulk-checkout --ref v2 data/train
+/// ```
+#[derive(Args, Debug)]
+pub struct BulkCheckoutArgs {
+    /// The ref to check out the files of.
+    #[clap(long = "ref", default_value = "HEAD")]
+    pub reference: String,
+
+    /// Only print what would be fetched, without checking anything out.
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// Paths or globs to check out, relative to the current directory.
+    /// Everything is checked out if none are given.
+    pub paths: Vec<PathBuf>,
+}
+
+pub async fn bulk_checkout_command(cfg: &XetConfig, args: &BulkCheckoutArgs) -> Result<()> {
+    let translator = PointerFileTranslator::from_config_in_repo(cfg).await?;
+
+    let curdir =
+        std::env::current_dir().map_err(|_| anyhow!("Unable to find current directory"))?;
+    let repo = Repository::discover(curdir)?;
+    let reporoot = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("Unable to find working directory"))?;
+    let gitpathspec = resolve_pathspec(
+        reporoot,
+        &args.paths,
+        PathspecRelativity::RelativeToCurrentDir,
+    )?;
+    std::env::set_current_dir(reporoot)
+        .map_err(|_| anyhow!("Unable to change current directory to working directory"))?;
+
+    let tree = repo
+        .revparse_single(&args.reference)
+        .and_then(|o| o.peel_to_tree())
+        .map_err(|_| anyhow!("Unable to find the tree of {}", args.reference))?;
+    let checkouts = tree_checkouts(&repo, &tree, &gitpathspec)?;
+
+    // Copies of a file are written from the same reads.
+    let mut files: Vec<(PointerFile, Vec<PathBuf>)> = Vec::new();
+    let mut file_index: HashMap<MerkleHash, usize> = HashMap::new();
+    let mut pointer_blobs: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut updatedpaths: Vec<String> = Vec::new();
+    for (name, oid) in checkouts {
+        let Ok(blob) = repo.find_blob(oid) else {
+            warn!("Unable to checkout {}. Unable to read blob.", name);
+            continue;
+        };
+        let Some(pointer) = smudged_pointer(&name, &blob, &translator) else {
+            if args.dry_run {
+                continue;
+            }
+            match write_checked_out(&name, blob.content()) {
+                Ok(()) => updatedpaths.push(name),
+                Err(e) => warn!("Unable to checkout {}: {:?}", name, e),
+            }
+            continue;
+        };
+        let i = *file_index.entry(pointer.hash()?).or_insert_with(|| {
+            files.push((pointer, Vec::new()));
+            files.len() - 1
+        });
+        files[i].1.push(PathBuf::from(&name));
+        pointer_blobs.push((name, blob.content().to_vec()));
+    }
+
+    let pointers: Vec<PointerFile> = files.iter().map(|(p, _)| p.clone()).collect();
+    let plan = translator.plan_bulk_materialize(&pointers).await?;
+    let bytes_fetched: u64 = plan
+        .iter()
+        .map(|p| (p.read.end - p.read.start) as u64)
+        .sum();
+    let bytes_written: u64 = files
+        .iter()
+        .map(|(p, paths)| p.filesize() * paths.len() as u64)
+        .sum();
+    info!(
+        "Planned {} reads of {} bytes for {} files of {} bytes",
+        plan.len(),
+        bytes_fetched,
+        pointer_blobs.len(),
+        bytes_written
+    );
+    if args.dry_run {
+        println!(
+            "Would check out {} files of {} bytes, fetching {} bytes in {} reads",
+            pointer_blobs.len(),
+            bytes_written,
+            bytes_fetched,
+            plan.len()
+        );
+        return Ok(());
+    }
+    check_disk_space(
+        cfg,
+        reporoot,
+        materialized_bytes(files.iter().flat_map(|(pointer, paths)| {
+            paths
+                .iter()
+                .map(|path| (path.as_path(), pointer.filesize()))
+        })),
+    )?;
+
+    let stage = transfer_report::stage("checkout");
+    let mut pb = ProgressBar::on(std::io::stderr(), bytes_fetched);
+    pb.set_units(Units::Bytes);
+    pb.tick(); // draw the bar immediately
+    let result = translator
+        .bulk_materialize(&files, plan, |len| {
+            pb.add(len);
+        })
+        .await;
+    pb.finish();
+    drop(stage);
+
+    match result {
+        Ok(()) => updatedpaths.extend(pointer_blobs.into_iter().map(|(name, _)| name)),
+        Err(e) => {
+            error!(
+                "Failed to hydrate {} files: {:?}. Writing the pointer files instead.",
+                pointer_blobs.len(),
+                e
+            );
+            for (name, raw) in pointer_blobs {
+                if let Err(e) = write_checked_out(&name, &raw) {
+                    warn!("Unable to checkout {}: {:?}", name, e);
+                }
+            }
+            update_index(updatedpaths)?;
+            return Err(e);
+        }
+    }
+
+    update_index(updatedpaths)
+}
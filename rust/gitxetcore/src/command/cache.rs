@@ -0,0 +1,69 @@
+use clap::{Args, Subcommand};
+use utils::output_bytes::output_bytes;
+
+use crate::config::XetConfig;
+use crate::environment::retention::prune;
+use crate::errors::Result;
+
+#[non_exhaustive]
+#[derive(Subcommand, Debug)]
+enum CacheCommand {
+    Prune(CachePruneArgs),
+}
+
+/// Deletes the cached blocks older than retention.cache_days, and the staged
+/// xorbs older than retention.staging_days that the remote CAS stores.
+///
+/// This also runs automatically, at most once an hour per repository, when
+/// either setting is configured.
+#[derive(Args, Debug)]
+struct CachePruneArgs {}
+
+/// Manages the data held on this machine.
+///
+/// ```ignore
+/// git xet config --global retention.cache_days 30
+/// git xet config --local retention.staging_days 7
+/// git xet cache prune
+/// ```
+// THIS "SHIM" STRUCT IS MANDATORY
+#[derive(Args, Debug)]
+pub struct CacheCommandShim {
+    #[clap(subcommand)]
+    subcommand: CacheCommand,
+}
+
+impl CacheCommandShim {
+    pub fn subcommand_name(&self) -> String {
+        match self.subcommand {
+            CacheCommand::Prune(_) => "prune".to_string(),
+        }
+    }
+}
+
+pub async fn cache_command(cfg: XetConfig, command: &CacheCommandShim) -> Result<()> {
+    match &command.subcommand {
+        CacheCommand::Prune(_) => cache_prune_command(&cfg).await,
+    }
+}
+
+async fn cache_prune_command(cfg: &XetConfig) -> Result<()> {
+    if !cfg.retention.enabled() {
+        println!(
+            "Neither retention.cache_days nor retention.staging_days is set; nothing to prune."
+        );
+        return Ok(());
+    }
+    let (cache, staging) = prune(cfg).await?;
+    println!(
+        "Pruned {} cached blocks ({})",
+        cache.files,
+        output_bytes(cache.bytes as usize)
+    );
+    println!(
+        "Pruned {} staged xorbs ({})",
+        staging.files,
+        output_bytes(staging.bytes as usize)
+    );
+    Ok(())
+}
@@ -0,0 +1,186 @@
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use cas_client::{CachingClient, Client, LocalClient, RemoteClient};
+use clap::Args;
+use tracing::info;
+
+use crate::cas_proxy::{run_cas_proxy, CasProxy, CountingClient, ProxyCertificate};
+use crate::config::XetConfig;
+use crate::constants::{GIT_XET_VERSION, LOCAL_CAS_SCHEME};
+use crate::errors::{self, GitXetRepoError};
+use crate::git_integration::git_hash_algorithm::read_hash_algorithm_from_config;
+use crate::git_integration::GitXetRepo;
+
+/// Runs a read-through caching CAS proxy for the machines on a local network.
+///
+/// Clients use the proxy by pointing their cas.server setting at it, after which
+/// data downloaded by one machine is served to the others from the proxy's
+/// disk cache. Blocks are evicted least recently used first once the cache
+/// exceeds --cache-size.
+///
+/// The proxy reads from the upstream CAS with the credentials of the user
+/// running it, so it only listens on localhost unless --bind says otherwise,
+/// and clients must present its token, set with --token or else generated
+/// once and kept in the cache directory. Clients give the token as the user
+/// of the proxy URL.
+///
+/// Data transfers use TLS on a second port, one above the bind port by
+/// default, with a certificate the proxy issues for --hostname on startup.
+/// Hit rates and cache statistics are served in Prometheus format at
+/// /metrics.
+///
+/// ```ignore
+/// git xet cas-proxy --bind 0.0.0.0:4040 --hostname cache.office.lan --cache-size 500000000000
+///
+/// # on each workstation, with the token the proxy printed
+/// git xet config --global cas.server http://<token>@cache.office.lan:4040
+/// ```
+#[derive(Args, Debug)]
+pub struct CasProxyArgs {
+    /// The address to listen on for CAS requests. Use 0.0.0.0:4040 to serve
+    /// other machines.
+    #[clap(long, default_value = "127.0.0.1:4040")]
+    pub bind: SocketAddr,
+
+    /// The port to serve TLS data transfers on. Defaults to the port after
+    /// the bind port.
+    #[clap(long)]
+    pub tls_port: Option<u16>,
+
+    /// The hostname or IP address clients reach this machine at. The TLS
+    /// certificate is issued for it. Defaults to the bind address, and must be
+    /// set when listening on all interfaces.
+    #[clap(long)]
+    pub hostname: Option<String>,
+
+    /// The CAS endpoint to cache. Defaults to the configured cas.server.
+    #[clap(long)]
+    pub upstream: Option<String>,
+
+    /// The directory to cache blocks in. Defaults to cas_proxy inside the
+    /// configured cache directory.
+    #[clap(long)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// The maximum size of the cache, in bytes.
+    #[clap(long, default_value = "107374182400")]
+    pub cache_size: u64,
+
+    /// The token clients must present. Defaults to a token generated on the
+    /// first run and kept in the cache directory.
+    #[clap(long)]
+    pub token: Option<String>,
+}
+
+/// The name of the file the generated token is kept in, in the cache
+/// directory.
+pub(crate) const TOKEN_FILE_NAME: &str = "token";
+
+/// Reads the token kept in dir, generating it if there is none.
+pub(crate) fn load_or_generate_token(dir: &Path) -> errors::Result<String> {
+    let path = dir.join(TOKEN_FILE_NAME);
+    if let Ok(token) = std::fs::read_to_string(&path) {
+        let token = token.trim();
+        if !token.is_empty() {
+            return Ok(token.to_string());
+        }
+    }
+    let token = hex::encode(rand::random::<[u8; 24]>());
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(&path)?.write_all(token.as_bytes())?;
+    Ok(token)
+}
+
+pub async fn cas_proxy_command(config: XetConfig, args: &CasProxyArgs) -> errors::Result<()> {
+    let upstream_endpoint = args
+        .upstream
+        .clone()
+        .unwrap_or_else(|| config.cas.endpoint.clone());
+    let hostname = match &args.hostname {
+        Some(hostname) => hostname.clone(),
+        None if !args.bind.ip().is_unspecified() => args.bind.ip().to_string(),
+        None => {
+            return Err(GitXetRepoError::InvalidOperation(
+                "Set --hostname to the name or address clients reach this machine at".into(),
+            ))
+        }
+    };
+    let tls_port = match args.tls_port {
+        Some(port) => port,
+        None => args.bind.port().checked_add(1).ok_or_else(|| {
+            GitXetRepoError::InvalidOperation(
+                "Unable to derive the TLS port, set --tls-port".into(),
+            )
+        })?,
+    };
+    let tls_addr = SocketAddr::new(args.bind.ip(), tls_port);
+
+    let cache_dir = args
+        .cache_dir
+        .clone()
+        .unwrap_or_else(|| config.cache.path.join("cas_proxy"));
+    std::fs::create_dir_all(&cache_dir)?;
+
+    let upstream: Arc<dyn Client + Send + Sync> = if let Some(path) =
+        upstream_endpoint.strip_prefix(LOCAL_CAS_SCHEME)
+    {
+        Arc::new(CachingClient::new(
+            CountingClient::new(
+                LocalClient::new(&PathBuf::from(path), false)
+                    .with_hash_algorithm(read_hash_algorithm_from_config(&config)?),
+            ),
+            &cache_dir,
+            args.cache_size,
+            config.cache.blocksize,
+        )?)
+    } else {
+        let (user_id, _) = &config.user.get_user_id();
+        let auth = &config.user.get_login_id();
+        let repo_paths = GitXetRepo::get_remote_urls(config.repo_path().ok().map(|x| x.as_path()))
+            .unwrap_or_else(|_| vec!["".to_string()]);
+        let remote = RemoteClient::from_config(
+            &upstream_endpoint,
+            user_id,
+            auth,
+            repo_paths,
+            GIT_XET_VERSION.clone(),
+        )
+        .await;
+        Arc::new(CachingClient::new(
+            CountingClient::new(remote),
+            &cache_dir,
+            args.cache_size,
+            config.cache.blocksize,
+        )?)
+    };
+    info!(
+        "Caching CAS {upstream_endpoint:?} at {cache_dir:?} with a quota of {} bytes",
+        args.cache_size
+    );
+
+    let token = match &args.token {
+        Some(token) => token.clone(),
+        None => {
+            let token = load_or_generate_token(&cache_dir)?;
+            eprintln!(
+                "The token clients present is kept in {:?}",
+                cache_dir.join(TOKEN_FILE_NAME)
+            );
+            token
+        }
+    };
+    eprintln!(
+        "Clients connect with cas.server set to http://<token>@{hostname}:{}",
+        args.bind.port()
+    );
+
+    let certificate = ProxyCertificate::generate(&hostname)?;
+    let proxy = CasProxy::new(upstream, &hostname, tls_port, &certificate, &token);
+    run_cas_proxy(proxy, certificate, args.bind, tls_addr).await
+}
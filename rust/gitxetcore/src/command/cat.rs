@@ -0,0 +1,232 @@
+use std::io::{stdin, stdout, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+use clap::Args;
+use git2::Repository;
+
+use crate::config::XetConfig;
+use crate::constants::POINTER_FILE_LIMIT;
+use crate::data::{PointerFile, PointerFileTranslator};
+use crate::errors::{self, GitXetRepoError};
+use crate::git_integration::GitXetRepo;
+
+use super::batch::batch_inputs;
+use super::smudge::RangeInput;
+
+/// Streams the contents of a file at any ref to stdout without checking it out.
+///
+/// Xet pointer files are resolved and their contents fetched; regular files are
+/// written out as stored in git.
+///
+/// ```ignore
+/// git xet cat HEAD~2:data/train.csv --range 0-1024
+/// ```
+///
+/// With --batch, the files are read from stdin, and each printed as git
+/// cat-file --batch does: a header line "<ref>:<path> <size>", the content
+/// and a newline, or "<ref>:<path> missing" if it can't be read.
+///
+/// ```ignore
+/// git ls-files data | sed 's/^/main:/' | git xet cat --batch
+/// ```
+#[derive(Args, Debug)]
+pub struct CatArgs {
+    /// The file to read, as <ref>:<path>. E.g. `main:data/train.csv`.
+    #[clap(required_unless_present = "batch")]
+    object: Option<String>,
+
+    /// Read the files from stdin, one per line.
+    #[clap(long, conflicts_with = "object")]
+    batch: bool,
+
+    /// With --batch, the files are separated by NUL bytes.
+    #[clap(short = 'z', requires = "batch")]
+    nul: bool,
+
+    /// Only output the bytes in the range start-end, or from start to the
+    /// end of the file with start-. Only the data of the range is fetched.
+    #[clap(long, short)]
+    range: Option<RangeInput>,
+}
+
+/// Resolves a <ref>:<path> spec to a blob, returning its contents.
+fn read_blob_at(repo: &Repository, spec: &str) -> errors::Result<Vec<u8>> {
+    if !spec.contains(':') {
+        return Err(GitXetRepoError::InvalidOperation(format!(
+            "{spec} is not of the form <ref>:<path>"
+        )));
+    }
+    let object = repo
+        .revparse_single(spec)
+        .map_err(|_| GitXetRepoError::FileNotFound(PathBuf::from(spec)))?;
+    let blob = object.peel_to_blob().map_err(|_| {
+        GitXetRepoError::InvalidOperation(format!("{spec} does not refer to a file"))
+    })?;
+    Ok(blob.content().to_vec())
+}
+
+/// Returns the pointer file if the blob contents are a valid pointer file.
+fn parse_pointer(content: &[u8], path: &str) -> Option<PointerFile> {
+    if content.len() > POINTER_FILE_LIMIT {
+        return None;
+    }
+    let pointer = PointerFile::init_from_string(std::str::from_utf8(content).ok()?, path);
+    pointer.is_valid().then_some(pointer)
+}
+
+/// Slices raw contents to the requested range, clamping to the end of the data.
+fn slice_range(content: &[u8], range: Option<(usize, usize)>) -> &[u8] {
+    match range {
+        Some((start, end)) => {
+            let end = end.min(content.len());
+            &content[start.min(end)..end]
+        }
+        None => content,
+    }
+}
+
+/// The size of the requested range of data of size bytes, as sliced by
+/// [slice_range].
+fn range_size(size: u64, range: Option<(usize, usize)>) -> u64 {
+    match range {
+        Some((start, end)) => {
+            let end = (end as u64).min(size);
+            end - (start as u64).min(end)
+        }
+        None => size,
+    }
+}
+
+pub async fn cat_command(config: XetConfig, args: &CatArgs) -> errors::Result<()> {
+    let repo = GitXetRepo::open(config.clone())?;
+    let range = args.range.as_ref().map(|r| (r.0, r.1));
+    let Some(object) = args.object.as_deref() else {
+        return cat_batch(&config, &repo, args.nul, range).await;
+    };
+    let content = read_blob_at(&repo.repo, object)?;
+    let path = object.split_once(':').map(|(_, p)| p).unwrap_or("");
+
+    let translator = PointerFileTranslator::from_config_in_repo(&config).await?;
+    let mut output = BufWriter::new(stdout());
+    write_file(&translator, &content, path, range, &mut output).await?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Writes the range of the file at path, relative to the repository root,
+/// whose blob has the given contents.
+async fn write_file(
+    translator: &PointerFileTranslator,
+    content: &[u8],
+    path: &str,
+    range: Option<(usize, usize)>,
+    output: &mut impl Write,
+) -> errors::Result<()> {
+    match parse_pointer(content, path) {
+        Some(pointer) => {
+            translator
+                .smudge_file_from_pointer(&PathBuf::from(path), &pointer, output, range)
+                .await
+        }
+        None => Ok(output.write_all(slice_range(content, range))?),
+    }
+}
+
+/// Prints each file read from stdin after a header line with its size.
+async fn cat_batch(
+    config: &XetConfig,
+    repo: &GitXetRepo,
+    nul: bool,
+    range: Option<(usize, usize)>,
+) -> errors::Result<()> {
+    let translator = PointerFileTranslator::from_config_in_repo(config).await?;
+    let mut output = BufWriter::new(stdout());
+    // Not the locked stdin, which can't be held across awaits on other
+    // threads.
+    for object in batch_inputs(BufReader::new(stdin()), nul) {
+        let object = object?;
+        let Ok(content) = read_blob_at(&repo.repo, &object) else {
+            writeln!(output, "{object} missing")?;
+            output.flush()?;
+            continue;
+        };
+        let path = object.split_once(':').map(|(_, p)| p).unwrap_or("");
+
+        let size = match parse_pointer(&content, path) {
+            Some(pointer) => range_size(pointer.filesize(), range),
+            None => slice_range(&content, range).len() as u64,
+        };
+        writeln!(output, "{object} {size}")?;
+        // A failure after the header leaves the output unreadable, so it
+        // ends the batch.
+        write_file(&translator, &content, path, range, &mut output).await?;
+        output.write_all(b"\n")?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use mdb_shard::shard_version::ShardVersion;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::data::access_policy::{AccessControl, AccessPolicy, TokenClaims};
+
+    #[test]
+    fn test_slice_range() {
+        let data = b"0123456789";
+        assert_eq!(slice_range(data, None), data);
+        assert_eq!(slice_range(data, Some((2, 5))), b"234");
+        assert_eq!(slice_range(data, Some((8, 100))), b"89");
+        assert_eq!(slice_range(data, Some((20, 30))), b"");
+
+        for range in [None, Some((2, 5)), Some((8, 100)), Some((20, 30))] {
+            assert_eq!(
+                range_size(data.len() as u64, range),
+                slice_range(data, range).len() as u64
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_pointer() {
+        let pointer = PointerFile::init_from_info("foo", "12345", 678);
+        assert!(parse_pointer(pointer.to_string().as_bytes(), "foo").is_some());
+        assert!(parse_pointer(b"not a pointer", "foo").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_restricted_file_denied() {
+        let stagedir = TempDir::new().unwrap();
+        let mut translator =
+            PointerFileTranslator::new_temporary(stagedir.path(), ShardVersion::V2)
+                .await
+                .unwrap();
+        let policy = AccessPolicy::parse(
+            "[[rule]]\npaths = [\"data/restricted\"]\nscopes = [\"restricted:read\"]",
+        )
+        .unwrap();
+        translator.set_access_control(Some(Arc::new(AccessControl::new(
+            Some(policy),
+            TokenClaims::default(),
+        ))));
+
+        let path = "data/restricted/a.csv";
+        let pointer = PointerFile::init_from_info(path, "12345", 678);
+        let mut output = Vec::new();
+        let result = write_file(
+            &translator,
+            pointer.to_string().as_bytes(),
+            path,
+            None,
+            &mut output,
+        )
+        .await;
+        assert!(matches!(result, Err(GitXetRepoError::AccessDenied(_))));
+        assert!(output.is_empty());
+    }
+}
@@ -1,22 +1,29 @@
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
 use anyhow::anyhow;
+use cas::fileio::write_all_at;
 use clap::Args;
 use git2::Repository;
+use parutils::shutdown_token;
 use pathdiff::diff_paths;
 use pbr::ProgressBar;
 use tracing::{error, info, info_span, warn};
 use tracing_futures::Instrument;
 
+use crate::data::preflight::{check_disk_space, materialized_bytes};
 use crate::data::PointerFile;
 
 use crate::config::XetConfig;
-use crate::constants::POINTER_FILE_LIMIT;
+use crate::constants::{PACK_SMUDGE_BATCH, POINTER_FILE_LIMIT, PREALLOCATE_MIN_FILE_SIZE};
 use crate::data::PointerFileTranslator;
 use crate::errors;
+use crate::errors::GitXetRepoError;
 use crate::git_integration::run_git_captured;
+use crate::git_integration::GitTreeEntryKind;
+use crate::interrupt::check_interrupted;
+use crate::transfer_report;
 
 /// Checkouts a collection of paths from the repository.
 /// If no arguments provided, will checkout everything.
@@ -42,10 +49,14 @@ pub enum PathspecRelativity {
     RelativeToRepoRoot,
     RelativeToCurrentDir,
 }
+/// Smudges the pointer file blob for repo_path, relative to the repository
+/// root, into filename.
 async fn checkout_pointer_blob<'a>(
     filename: &str,
+    repo_path: &Path,
     blob: &'a git2::Blob<'a>,
     gitxetrepo: &PointerFileTranslator,
+    preallocate: bool,
 ) -> anyhow::Result<()> {
     if blob.size() > POINTER_FILE_LIMIT {
         return Err(anyhow!("Invalid pointer file"));
@@ -54,16 +65,20 @@ async fn checkout_pointer_blob<'a>(
     if !pointer.is_valid() {
         return Err(anyhow!("Invalid pointer file"));
     }
+    if !gitxetrepo.allows(repo_path) {
+        info!("Not authorized to smudge {repo_path:?}; leaving the pointer file in place");
+        return Err(GitXetRepoError::AccessDenied(format!("{repo_path:?}")).into());
+    }
     let filepath = PathBuf::from(filename);
     if let Some(parent) = filepath.parent() {
         std::fs::create_dir_all(parent)?;
     }
-    let mut outfile = std::fs::File::create(&filepath)?;
-    if let Err(e) = gitxetrepo
-        .smudge_file_from_pointer(&filepath, &pointer, &mut outfile, None)
+    let preallocate = preallocate && pointer.filesize() >= PREALLOCATE_MIN_FILE_SIZE;
+    let result = gitxetrepo
+        .smudge_file_from_pointer_to_path(repo_path, &filepath, &pointer, preallocate)
         .instrument(info_span!("smudge_pointer_file"))
-        .await
-    {
+        .await;
+    if let Err(e) = result {
         error!(
             "Failed to hydrate file {:?}: {:?}. Writing the pointer file instead.",
             filepath, e
@@ -72,16 +87,93 @@ async fn checkout_pointer_blob<'a>(
     }
     Ok(())
 }
-fn checkout_raw_blob<'a>(filename: &str, blob: &'a git2::Blob<'a>) -> anyhow::Result<()> {
+/// The pointer file in the blob, if it's for a file small enough to be
+/// smudged in a batch with other packed files.
+fn packed_pointer(
+    filename: &str,
+    blob: &git2::Blob,
+    gitxetrepo: &PointerFileTranslator,
+) -> Option<PointerFile> {
+    if blob.size() > POINTER_FILE_LIMIT {
+        return None;
+    }
+    let pointer =
+        PointerFile::init_from_string(std::str::from_utf8(blob.content()).ok()?, filename);
+    (pointer.is_valid()
+        && gitxetrepo.packs(pointer.filesize())
+        && gitxetrepo.allows(Path::new(filename)))
+    .then_some(pointer)
+}
+
+/// Smudges a batch of packed pointer files together, returning the names of
+/// the files checked out. Files the batch fails for are left as pointer files.
+async fn checkout_packed_pointers(
+    batch: &[(String, PointerFile, Vec<u8>)],
+    gitxetrepo: &PointerFileTranslator,
+) -> Vec<String> {
+    let pointers: Vec<(PathBuf, PointerFile)> = batch
+        .iter()
+        .map(|(name, pointer, _)| (PathBuf::from(name), pointer.clone()))
+        .collect();
+    let contents = match gitxetrepo
+        .smudge_packed_files(&pointers)
+        .instrument(info_span!("smudge_packed_files"))
+        .await
+    {
+        Ok(contents) => contents,
+        Err(e) => {
+            error!(
+                "Failed to hydrate {} packed files: {:?}. Writing the pointer files instead.",
+                batch.len(),
+                e
+            );
+            return batch
+                .iter()
+                .filter_map(|(name, _, raw)| {
+                    write_checked_out(name, raw).ok().map(|_| name.clone())
+                })
+                .collect();
+        }
+    };
+
+    let mut updated = Vec::with_capacity(batch.len());
+    for (data, (name, _, _)) in contents.into_iter().zip(batch) {
+        match write_checked_out(name, &data) {
+            Ok(()) => updated.push(name.clone()),
+            Err(e) => warn!("Unable to checkout {}: {:?}", name, e),
+        }
+    }
+    updated
+}
+
+pub(crate) fn write_checked_out(filename: &str, data: &[u8]) -> anyhow::Result<()> {
     let filepath = PathBuf::from(filename);
     if let Some(parent) = filepath.parent() {
         std::fs::create_dir_all(parent)?;
     }
-    let mut outfile = std::fs::File::create(&filepath)?;
-    outfile.write_all(blob.content())?;
+    let outfile = std::fs::File::create(&filepath)?;
+    write_all_at(&outfile, data, 0)?;
     Ok(())
 }
 
+/// The pointer file in the blob, if it's one to materialize.
+pub(crate) fn smudged_pointer(
+    filename: &str,
+    blob: &git2::Blob,
+    gitxetrepo: &PointerFileTranslator,
+) -> Option<PointerFile> {
+    if blob.size() > POINTER_FILE_LIMIT {
+        return None;
+    }
+    let pointer =
+        PointerFile::init_from_string(std::str::from_utf8(blob.content()).ok()?, filename);
+    (pointer.is_valid() && gitxetrepo.allows(Path::new(filename))).then_some(pointer)
+}
+
+fn checkout_raw_blob<'a>(filename: &str, blob: &'a git2::Blob<'a>) -> anyhow::Result<()> {
+    write_checked_out(filename, blob.content())
+}
+
 /// Checkouts the contents of the the current repository HEAD into the
 /// working directory, smudging everything as needed.
 ///
@@ -104,6 +196,7 @@ pub async fn checkout(
     pathspec: &[PathBuf],
     pathspec_relativity: PathspecRelativity,
     gitxetrepo: &PointerFileTranslator,
+    cfg: &XetConfig,
 ) -> errors::Result<()> {
     let repopath = match repopath {
         Some(p) => p,
@@ -113,56 +206,12 @@ pub async fn checkout(
     let reporoot = repo
         .workdir()
         .ok_or_else(|| anyhow!("Unable to find working directory"))?;
-
-    let pathspec: Vec<PathBuf> = pathspec.into();
-
-    // search for all "." and convert to "*"
-    // This allows git xet checkout . to behave correctly.
-    let dotpath = PathBuf::from("/");
-    let pathspec: Vec<PathBuf> = pathspec
-        .into_iter()
-        .map(|x| if x == dotpath { PathBuf::from("*") } else { x })
-        .collect();
-
-    // resolve the PathspecRelativity
-    // If it is relative to repo root, we just convert to OSString
-    // Othewise, we compute a diff path before converting to OSString
-    let mut pathspec: Vec<std::ffi::OsString> = match pathspec_relativity {
-        PathspecRelativity::RelativeToRepoRoot => pathspec
-            .iter()
-            .map(|x| x.clone().into_os_string())
-            .collect(),
-        PathspecRelativity::RelativeToCurrentDir => {
-            let curdir =
-                std::env::current_dir().map_err(|_| anyhow!("Unable to find current directory"))?;
-            pathspec
-                .iter()
-                .filter_map(|x| match diff_paths(curdir.join(x), reporoot) {
-                    Some(x) => Some(x),
-                    None => {
-                        warn!("Unable to resolve path {:?}. Skipping.", x);
-                        None
-                    }
-                })
-                .map(|x| x.into_os_string())
-                .collect()
-        }
-    };
-    // if pathspec is empty we insert "*" to make it checkout
-    // everything in the repo. Note that it is important that this happens
-    // *after* we resolve pathspec relativity. (If we insert the "*" before,
-    // and the relativity rules have it relative to the current directory,
-    // we will only checkout that one directory).
-    if pathspec.is_empty() {
-        pathspec.push("*".into());
-    }
+    let gitpathspec = resolve_pathspec(reporoot, pathspec, pathspec_relativity)?;
 
     // change current directory to the repository root
     std::env::set_current_dir(reporoot)
         .map_err(|_| anyhow!("Unable to change current directory to working directory"))?;
 
-    let gitpathspec = git2::Pathspec::new(pathspec)?;
-
     // find the current worktree
     let headref = repo
         .head()
@@ -171,38 +220,34 @@ pub async fn checkout(
         .peel_to_tree()
         .map_err(|_| anyhow!("Unable to find tree in repository head"))?;
     // a list of filename, oid pairs to checkout
-    let mut checkouts: Vec<(String, git2::Oid)> = Vec::new();
+    let checkouts = tree_checkouts(&repo, &tree, &gitpathspec)?;
+
+    // fail before smudging anything if the files won't fit
+    let sizes: Vec<(&str, u64)> = checkouts
+        .iter()
+        .filter_map(|(name, oid)| {
+            let blob = repo.find_blob(*oid).ok()?;
+            let pointer = smudged_pointer(name, &blob, gitxetrepo)?;
+            Some((name.as_str(), pointer.filesize()))
+        })
+        .collect();
+    check_disk_space(
+        cfg,
+        reporoot,
+        materialized_bytes(sizes.iter().map(|(name, size)| (Path::new(*name), *size))),
+    )?;
 
-    // match the tree with the pathspec
-    let matches = gitpathspec.match_tree(&tree, git2::PathspecFlags::DEFAULT)?;
-    // This returns an iterator over entries
-    for entry in matches.entries() {
-        // test conversion to utf-8
-        if let Ok(mstr) = std::str::from_utf8(entry) {
-            let mpath = PathBuf::from(mstr);
-            // find it in the tree
-            if let Ok(ent) = tree.get_path(&mpath) {
-                // find the object
-                // nad insert into the checkouts list
-                info!("Adding {:?}", ent.name());
-                let maybe_oid = ent.to_object(&repo).map(|x| x.id());
-                if let Ok(oid) = maybe_oid {
-                    checkouts.push((mstr.to_string(), oid));
-                }
-            }
-        } else {
-            warn!(
-                "Unable to convert path {:?} to utf8",
-                String::from_utf8_lossy(entry)
-            );
-        }
-    }
     let mut pb = ProgressBar::on(std::io::stderr(), checkouts.len() as u64);
     pb.tick(); // draw the bar immediately
 
-    // smudge everything
+    // smudge everything, holding back small files to smudge them in batches
     let mut updatedpaths: Vec<String> = Vec::new();
+    let mut packed: Vec<(String, PointerFile, Vec<u8>)> = Vec::new();
     for entry in checkouts.iter() {
+        // once interrupted, the files not reached are left as they are
+        if shutdown_token().is_cancelled() {
+            break;
+        }
         let name = &entry.0;
         let oid = entry.1;
         info!("Checking out {:?}", name);
@@ -216,9 +261,14 @@ pub async fn checkout(
         pb.inc();
         let maybeblob = repo.find_blob(oid);
         if let Ok(blob) = maybeblob {
-            if let Err(e) = checkout_pointer_blob(name, &blob, gitxetrepo)
-                .await
-                .or_else(|_| checkout_raw_blob(name, &blob))
+            if let Some(pointer) = packed_pointer(name, &blob, gitxetrepo) {
+                packed.push((name.clone(), pointer, blob.content().to_vec()));
+                continue;
+            }
+            if let Err(e) =
+                checkout_pointer_blob(name, Path::new(name), &blob, gitxetrepo, cfg.io.preallocate)
+                    .await
+                    .or_else(|_| checkout_raw_blob(name, &blob))
             {
                 warn!("Unable to checkout {}: {:?}", name, e);
             } else {
@@ -228,8 +278,21 @@ pub async fn checkout(
             warn!("Unable to checkout {}. Unable to read blob.", name);
         }
     }
+    for batch in packed.chunks(PACK_SMUDGE_BATCH) {
+        if shutdown_token().is_cancelled() {
+            break;
+        }
+        updatedpaths.extend(checkout_packed_pointers(batch, gitxetrepo).await);
+    }
     pb.finish();
 
+    update_index(updatedpaths)?;
+    check_interrupted("run the checkout again")
+}
+
+/// Refreshes the index entries of the paths just checked out, so that git
+/// doesn't see them as modified.
+pub(crate) fn update_index(updatedpaths: Vec<String>) -> errors::Result<()> {
     // push all the updated files through git update-index
     // following the process from
     // https://github.com/git-lfs/git-lfs/blob/f61dbfa56b9be91411017e3cef0d4088a3988b70/commands/pull.go
@@ -266,6 +329,100 @@ pub async fn checkout(
     Ok(())
 }
 
+/// Resolves the pathspecs to a git pathspec relative to reporoot, matching
+/// everything if there are none.
+pub(crate) fn resolve_pathspec(
+    reporoot: &Path,
+    pathspec: &[PathBuf],
+    pathspec_relativity: PathspecRelativity,
+) -> errors::Result<git2::Pathspec> {
+    let pathspec: Vec<PathBuf> = pathspec.into();
+
+    // search for all "." and convert to "*"
+    // This allows git xet checkout . to behave correctly.
+    let dotpath = PathBuf::from("/");
+    let pathspec: Vec<PathBuf> = pathspec
+        .into_iter()
+        .map(|x| if x == dotpath { PathBuf::from("*") } else { x })
+        .collect();
+
+    // resolve the PathspecRelativity
+    // If it is relative to repo root, we just convert to OSString
+    // Othewise, we compute a diff path before converting to OSString
+    let mut pathspec: Vec<std::ffi::OsString> = match pathspec_relativity {
+        PathspecRelativity::RelativeToRepoRoot => pathspec
+            .iter()
+            .map(|x| x.clone().into_os_string())
+            .collect(),
+        PathspecRelativity::RelativeToCurrentDir => {
+            let curdir =
+                std::env::current_dir().map_err(|_| anyhow!("Unable to find current directory"))?;
+            pathspec
+                .iter()
+                .filter_map(|x| match diff_paths(curdir.join(x), reporoot) {
+                    Some(x) => Some(x),
+                    None => {
+                        warn!("Unable to resolve path {:?}. Skipping.", x);
+                        None
+                    }
+                })
+                .map(|x| x.into_os_string())
+                .collect()
+        }
+    };
+    // if pathspec is empty we insert "*" to make it checkout
+    // everything in the repo. Note that it is important that this happens
+    // *after* we resolve pathspec relativity. (If we insert the "*" before,
+    // and the relativity rules have it relative to the current directory,
+    // we will only checkout that one directory).
+    if pathspec.is_empty() {
+        pathspec.push("*".into());
+    }
+
+    Ok(git2::Pathspec::new(pathspec)?)
+}
+
+/// The names and objects of the entries of tree matching the pathspec.
+/// Symlinks and submodules are left out: their objects are a link target
+/// and a commit, not file contents to write out or smudge.
+pub(crate) fn tree_checkouts(
+    repo: &Repository,
+    tree: &git2::Tree,
+    gitpathspec: &git2::Pathspec,
+) -> errors::Result<Vec<(String, git2::Oid)>> {
+    let mut checkouts: Vec<(String, git2::Oid)> = Vec::new();
+
+    // match the tree with the pathspec
+    let matches = gitpathspec.match_tree(tree, git2::PathspecFlags::DEFAULT)?;
+    // This returns an iterator over entries
+    for entry in matches.entries() {
+        // test conversion to utf-8
+        if let Ok(mstr) = std::str::from_utf8(entry) {
+            let mpath = PathBuf::from(mstr);
+            // find it in the tree
+            if let Ok(ent) = tree.get_path(&mpath) {
+                if !GitTreeEntryKind::from_mode(ent.filemode() as u32).is_file() {
+                    info!("Skipping {:?}, which is not a file", ent.name());
+                    continue;
+                }
+                // find the object
+                // nad insert into the checkouts list
+                info!("Adding {:?}", ent.name());
+                let maybe_oid = ent.to_object(repo).map(|x| x.id());
+                if let Ok(oid) = maybe_oid {
+                    checkouts.push((mstr.to_string(), oid));
+                }
+            }
+        } else {
+            warn!(
+                "Unable to convert path {:?} to utf8",
+                String::from_utf8_lossy(entry)
+            );
+        }
+    }
+    Ok(checkouts)
+}
+
 fn get_stage(ours: bool, theirs: bool, base: bool) -> &'static str {
     // no more than 1 of ours, theirs and base is set
     assert!((ours as u8) + (theirs as u8) + (base as u8) <= 1);
@@ -287,6 +444,7 @@ pub async fn checkout_single(
     stage: &str,
     filepath: PathBuf,
     to: Option<PathBuf>,
+    cfg: &XetConfig,
 ) -> errors::Result<()> {
     if filepath.file_name().is_none() {
         return Err(anyhow!("We can only checkout a single file").into());
@@ -340,9 +498,27 @@ pub async fn checkout_single(
 
     let maybeblob = repo.find_blob(oid);
     if let Ok(blob) = maybeblob {
-        if let Err(e) = checkout_pointer_blob(&checkout_location, &blob, gitxetrepo)
-            .await
-            .or_else(|_| checkout_raw_blob(&checkout_location, &blob))
+        if let Some(pointer) = smudged_pointer(&checkout_location, &blob, gitxetrepo) {
+            let location = Path::new(&checkout_location);
+            let dest = location
+                .parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or_else(|| Path::new("."));
+            check_disk_space(
+                cfg,
+                dest,
+                materialized_bytes([(location, pointer.filesize())]),
+            )?;
+        }
+        if let Err(e) = checkout_pointer_blob(
+            &checkout_location,
+            &filepath,
+            &blob,
+            gitxetrepo,
+            cfg.io.preallocate,
+        )
+        .await
+        .or_else(|_| checkout_raw_blob(&checkout_location, &blob))
         {
             warn!("Unable to checkout {:?}: {:?}", &filepath, e);
         }
@@ -400,12 +576,14 @@ pub async fn checkout_command(cfg: &XetConfig, checkout_args: &CheckoutArgs) ->
     }
 
     let repo = PointerFileTranslator::from_config_in_repo(cfg).await?;
+    let _stage = transfer_report::stage("checkout");
     if single_checkout {
         checkout_single(
             &repo,
             get_stage(checkout_args.ours, checkout_args.theirs, checkout_args.base),
             checkout_args.paths[0].clone(),
             checkout_args.to.clone(),
+            cfg,
         )
         .await?;
     } else {
@@ -414,6 +592,7 @@ pub async fn checkout_command(cfg: &XetConfig, checkout_args: &CheckoutArgs) ->
             &checkout_args.paths[..],
             PathspecRelativity::RelativeToCurrentDir,
             &repo,
+            cfg,
         )
         .await?;
     }
@@ -1,7 +1,10 @@
 use crate::git_integration::clone_xet_repo;
+use crate::git_integration::git_url::is_remote_url;
 use clap::Args;
+use std::path::PathBuf;
+use xet_config::Level;
 
-use crate::config::XetConfig;
+use crate::config::{create_config_loader, ConfigError, ConfigGitPathOption, XetConfig};
 use crate::errors::Result;
 
 /// Clone an existing repo.  This command ensures that XET is properly configured, then calls git clone
@@ -15,6 +18,12 @@ pub struct CloneArgs {
     #[clap(long)]
     lazy: bool,
 
+    /// Fetch only the MerkleDB shards needed by the checked out files. Other
+    /// shards are fetched when a file that needs them is checked out. Sets
+    /// shard.partial in the repository's xet config.
+    #[clap(long)]
+    partial_merkledb: bool,
+
     /// All remaining arguments are passed to git clone.
     /// any arguments after '--' are unprocessed and passed through as is.
     /// This is useful for instance in:
@@ -29,7 +38,12 @@ pub async fn clone_command(config: XetConfig, args: &CloneArgs) -> Result<()> {
     // First check local config.
     eprintln!("Preparing to clone Xet repository.");
 
-    clone_xet_repo(
+    if args.partial_merkledb {
+        // The hooks and filter git runs during the clone read this.
+        std::env::set_var("XET_SHARD_PARTIAL", "true");
+    }
+
+    let (repo_name, _) = clone_xet_repo(
         Some(&config),
         &arg_v[..],
         args.no_smudge || args.lazy,
@@ -39,5 +53,53 @@ pub async fn clone_command(config: XetConfig, args: &CloneArgs) -> Result<()> {
         false,
     )?;
 
+    if args.partial_merkledb {
+        match clone_destination(&args.arguments, &repo_name) {
+            Some(dir) => {
+                create_config_loader(Some(ConfigGitPathOption::PathDiscover(dir)))?
+                    .override_value(Level::LOCAL, "shard.partial", true)
+                    .map_err(ConfigError::from)?;
+            }
+            None => eprintln!(
+                "Unable to determine the cloned directory; run \"git xet config --local shard.partial true\" in it to keep fetching MerkleDB shards on demand."
+            ),
+        }
+    }
+
     Ok(())
 }
+
+/// The directory git clone clones into: the argument after the remote if
+/// there is one, otherwise the name of the repository.
+fn clone_destination(arguments: &[String], repo_name: &str) -> Option<PathBuf> {
+    if let [.., prev, last] = arguments {
+        if !last.starts_with('-') && !prev.starts_with('-') && !is_remote_url(last) {
+            return Some(PathBuf::from(last));
+        }
+    }
+    (!repo_name.is_empty()).then(|| PathBuf::from(repo_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clone_destination() {
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let url = "https://xethub.com/user/repo";
+        assert_eq!(
+            clone_destination(&args(&[url, "dir"]), "repo"),
+            Some(PathBuf::from("dir"))
+        );
+        assert_eq!(
+            clone_destination(&args(&[url]), "repo"),
+            Some(PathBuf::from("repo"))
+        );
+        assert_eq!(
+            clone_destination(&args(&[url, "--depth", "1"]), "repo"),
+            Some(PathBuf::from("repo"))
+        );
+        assert_eq!(clone_destination(&args(&[url]), ""), None);
+    }
+}
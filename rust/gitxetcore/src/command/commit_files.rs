@@ -0,0 +1,176 @@
+use clap::Args;
+use parutils::tokio_par_for_each;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use crate::config::XetConfig;
+use crate::constants::{GIT_MAX_PACKET_SIZE, MAX_CONCURRENT_UPLOADS};
+use crate::data::PointerFileTranslator;
+use crate::errors::{convert_parallel_error, GitXetRepoError, Result};
+use crate::git_integration::git_commits::{atomic_commit_impl, ManifestEntry};
+use crate::git_integration::git_user_config::get_user_info_for_commit;
+use crate::git_integration::{open_libgit2_repo, GitXetRepo};
+use crate::import::ensure_not_checked_out;
+use crate::stream::data_iterators::AsyncFileIterator;
+
+/// Commits local files to a branch and pushes it, without a checkout.
+///
+/// The files are cleaned, and their pointer files written to a commit on
+/// top of the branch directly, so the working tree, if any, is never read or
+/// changed. Services producing data artifacts can append them to a dataset
+/// branch this way:
+///
+/// ```ignore
+/// git xet commit-files --branch data --message "Nightly export" \
+///     exports/2024-06-01.parquet=/tmp/out.parquet
+/// ```
+#[derive(Args, Debug)]
+pub struct CommitFilesArgs {
+    /// The files to commit, each as <path in the repository>=<local file>.
+    /// Files already at the path are replaced.
+    #[clap(required = true)]
+    files: Vec<String>,
+
+    /// The branch to commit to, created from HEAD if it doesn't exist. It
+    /// can't be the branch checked out.
+    #[clap(short, long)]
+    branch: String,
+
+    /// The commit message. Defaults to one listing the files.
+    #[clap(short, long)]
+    message: Option<String>,
+
+    /// The remote to push the branch to.
+    #[clap(long, default_value = "origin")]
+    remote: String,
+
+    /// Only commit, leaving the branch and its data to push later.
+    #[clap(long)]
+    no_push: bool,
+}
+
+pub async fn commit_files_command(cfg: XetConfig, args: &CommitFilesArgs) -> Result<()> {
+    let files = args
+        .files
+        .iter()
+        .map(|spec| parse_file_spec(spec))
+        .collect::<Result<Vec<_>>>()?;
+    let message = args
+        .message
+        .clone()
+        .unwrap_or_else(|| commit_message(&files));
+
+    let commit = commit_files(&cfg, &args.branch, &message, files).await?;
+    eprintln!(
+        "Committed {} file(s) to {}: {commit}",
+        args.files.len(),
+        args.branch
+    );
+
+    if !args.no_push {
+        push_branch(cfg, &args.remote, &args.branch).await?;
+        eprintln!("Pushed {} to {}.", args.branch, args.remote);
+    }
+    Ok(())
+}
+
+/// Cleans the local files and commits them to branch at their paths in the
+/// repository, without touching the working tree. Returns the id of the
+/// commit. The data of the files is staged, and uploaded by the next push.
+pub async fn commit_files(
+    cfg: &XetConfig,
+    branch: &str,
+    message: &str,
+    files: Vec<(String, PathBuf)>,
+) -> Result<String> {
+    let repo = open_libgit2_repo(cfg.repo_path_if_present.as_deref())?;
+    ensure_not_checked_out(&repo, branch)?;
+
+    let translator = PointerFileTranslator::from_config_in_repo(cfg).await?;
+    let translator = &translator;
+    let entries = tokio_par_for_each(
+        files,
+        MAX_CONCURRENT_UPLOADS,
+        |(path, local_path), _| async move {
+            let reader = BufReader::new(File::open(&local_path)?);
+            let reader = AsyncFileIterator::new(reader, GIT_MAX_PACKET_SIZE);
+            let pointer = translator.clean_file(Path::new(&path), reader).await?;
+            Ok(ManifestEntry::Upsert {
+                file: path.into(),
+                modeexec: false,
+                content: pointer,
+                githash_content: None,
+            })
+        },
+    )
+    .await
+    .map_err(convert_parallel_error)?;
+    translator.finalize_cleaning().await?;
+
+    let (name, email) = get_user_info_for_commit(Some(cfg), None, Some(repo.clone()));
+    let refname = format!("refs/heads/{branch}");
+    let (_, commit) = atomic_commit_impl(&repo, entries, &refname, message, &name, &email, true)?;
+    Ok(commit.id().to_string())
+}
+
+/// Uploads the staged data and pushes branch to remote, as the pre-push hook
+/// would, so that repositories without hooks installed push the same way.
+pub async fn push_branch(cfg: XetConfig, remote: &str, branch: &str) -> Result<()> {
+    let repo = GitXetRepo::open(cfg)?;
+    repo.pre_push_hook(remote).await?;
+    let refspec = format!("refs/heads/{branch}:refs/heads/{branch}");
+    repo.run_git_checked_in_repo("push", &["--no-verify", remote, &refspec])?;
+    Ok(())
+}
+
+/// Parses a file to commit given as <path in the repository>=<local file>.
+fn parse_file_spec(spec: &str) -> Result<(String, PathBuf)> {
+    let invalid = || {
+        GitXetRepoError::InvalidOperation(format!(
+            "{spec} is not of the form <path in the repository>=<local file>"
+        ))
+    };
+    let (path, local_path) = spec.split_once('=').ok_or_else(invalid)?;
+    let path = path.trim_matches('/');
+    if path.is_empty() || local_path.is_empty() {
+        return Err(invalid());
+    }
+    Ok((path.to_string(), PathBuf::from(local_path)))
+}
+
+/// The default commit message, naming the files committed.
+fn commit_message(files: &[(String, PathBuf)]) -> String {
+    let paths: Vec<&str> = files.iter().map(|(path, _)| path.as_str()).collect();
+    format!("Add {}", paths.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_file_spec() {
+        assert_eq!(
+            parse_file_spec("data/a.parquet=/tmp/out.parquet").unwrap(),
+            (
+                "data/a.parquet".to_string(),
+                PathBuf::from("/tmp/out.parquet")
+            )
+        );
+        // Only the first = separates the path from the local file.
+        assert_eq!(
+            parse_file_spec("/a.csv=x=1.csv").unwrap(),
+            ("a.csv".to_string(), PathBuf::from("x=1.csv"))
+        );
+        assert!(parse_file_spec("a.csv").is_err());
+        assert!(parse_file_spec("=a.csv").is_err());
+        assert!(parse_file_spec("a.csv=").is_err());
+
+        let files = vec![
+            ("a.csv".to_string(), PathBuf::from("x")),
+            ("b/c.bin".to_string(), PathBuf::from("y")),
+        ];
+        assert_eq!(commit_message(&files), "Add a.csv, b/c.bin");
+    }
+}
@@ -0,0 +1,141 @@
+//! Shell completions and a machine-readable description of the CLI, both
+//! generated from the clap definitions so they never drift from them.
+use clap::{Args, CommandFactory};
+use clap_complete::{generate, Shell};
+use serde::Serialize;
+
+use super::GitXetCommand;
+use crate::errors::Result;
+
+/// Prints the completions of git-xet for a shell.
+///
+/// ```ignore
+/// git xet completions bash > /etc/bash_completion.d/git-xet
+/// git xet completions zsh > "${fpath[1]}/_git-xet"
+/// ```
+#[derive(Args, Debug)]
+pub struct CompletionsArgs {
+    #[clap(arg_enum)]
+    pub shell: Shell,
+}
+
+pub fn completions_command(args: &CompletionsArgs) -> Result<()> {
+    let mut cmd = GitXetCommand::command();
+    let name = cmd.get_name().to_string();
+    generate(args.shell, &mut cmd, name, &mut std::io::stdout());
+    Ok(())
+}
+
+/// A command of the CLI, as printed by `git xet --dump-cli-json`.
+#[derive(Serialize, Debug)]
+pub struct CliCommand {
+    pub name: String,
+    pub about: Option<String>,
+    pub hidden: bool,
+    pub args: Vec<CliArg>,
+    pub subcommands: Vec<CliCommand>,
+}
+
+/// A flag or positional argument of a command.
+#[derive(Serialize, Debug)]
+pub struct CliArg {
+    pub id: String,
+    pub long: Option<String>,
+    pub short: Option<char>,
+    pub help: Option<String>,
+    pub required: bool,
+    pub positional: bool,
+    pub takes_value: bool,
+    /// Whether the flag also applies to the subcommands.
+    pub global: bool,
+    pub hidden: bool,
+    pub default_values: Vec<String>,
+    pub possible_values: Vec<String>,
+}
+
+impl CliCommand {
+    pub fn from_clap(cmd: &clap::Command) -> Self {
+        CliCommand {
+            name: cmd.get_name().to_string(),
+            about: cmd.get_about().map(String::from),
+            hidden: cmd.is_hide_set(),
+            args: cmd
+                .get_arguments()
+                .map(|arg| CliArg {
+                    id: arg.get_id().to_string(),
+                    long: arg.get_long().map(String::from),
+                    short: arg.get_short(),
+                    help: arg.get_help().map(String::from),
+                    required: arg.is_required_set(),
+                    positional: arg.is_positional(),
+                    takes_value: arg.is_takes_value_set(),
+                    global: arg.is_global_set(),
+                    hidden: arg.is_hide_set(),
+                    default_values: arg
+                        .get_default_values()
+                        .iter()
+                        .map(|v| v.to_string_lossy().to_string())
+                        .collect(),
+                    possible_values: arg
+                        .get_possible_values()
+                        .iter()
+                        .map(|v| v.get_name().to_string())
+                        .collect(),
+                })
+                .collect(),
+            subcommands: cmd.get_subcommands().map(CliCommand::from_clap).collect(),
+        }
+    }
+}
+
+/// Prints the whole tree of commands and flags of the CLI as JSON, for
+/// wrappers generating forms from it.
+pub fn print_cli_json() -> Result<()> {
+    let cli = CliCommand::from_clap(&GitXetCommand::command());
+    println!("{}", serde_json::to_string_pretty(&cli)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_json() {
+        GitXetCommand::command().debug_assert();
+
+        let cli = CliCommand::from_clap(&GitXetCommand::command());
+        assert_eq!(cli.name, "git-xet");
+        assert!(cli
+            .args
+            .iter()
+            .any(|a| a.id == "error-format" || a.long.as_deref() == Some("error-format")));
+
+        let fsck = cli.subcommands.iter().find(|c| c.name == "fsck").unwrap();
+        let json = fsck
+            .args
+            .iter()
+            .find(|a| a.long.as_deref() == Some("json"))
+            .unwrap();
+        assert!(!json.takes_value);
+        assert!(!json.required);
+
+        let completions = cli
+            .subcommands
+            .iter()
+            .find(|c| c.name == "completions")
+            .unwrap();
+        assert!(completions.args[0]
+            .possible_values
+            .contains(&"zsh".to_string()));
+    }
+
+    #[test]
+    fn test_completions() {
+        let mut cmd = GitXetCommand::command();
+        let mut out = Vec::new();
+        generate(Shell::Bash, &mut cmd, "git-xet", &mut out);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("checkout"));
+    }
+}
@@ -1,7 +1,7 @@
 use clap::Args;
 use itertools::Itertools;
 use lazy::lazy_pathlist_config::{check_or_create_lazy_config, LazyPathListConfigFile};
-use parutils::tokio_par_for_each;
+use parutils::{shutdown_token, tokio_par_for_each};
 use std::fs::File;
 use std::io::BufReader;
 use std::path::{Path, PathBuf};
@@ -12,6 +12,7 @@ use crate::constants::{GIT_MAX_PACKET_SIZE, MAX_CONCURRENT_UPLOADS};
 use crate::data::PointerFileTranslator;
 use crate::errors::Result;
 use crate::git_integration::{filter_files_from_index, walk_working_dir, GitXetRepo};
+use crate::interrupt::check_interrupted;
 use crate::stream::data_iterators::AsyncFileIterator;
 use crate::{config::XetConfig, constants::GIT_LAZY_CHECKOUT_CONFIG};
 
@@ -87,6 +88,10 @@ pub async fn dematerialize_command(cfg: XetConfig, args: &DematerializeArgs) ->
         absolute_path_list,
         MAX_CONCURRENT_UPLOADS,
         |path, _| async move {
+            // once interrupted, the files not started are left materialized
+            if shutdown_token().is_cancelled() {
+                return Ok(());
+            }
             let translator = translator_ref.clone();
             clean_file_to_itself(&translator, &path).await
         },
@@ -102,6 +107,7 @@ pub async fn dematerialize_command(cfg: XetConfig, args: &DematerializeArgs) ->
     // update index so dematerialized files don't show as "Changes not staged for commit"
     repo.run_git_checked_in_repo("add", &["-u"])?;
 
+    check_interrupted("run the command again")?;
     eprintln!("Done");
 
     Ok(())
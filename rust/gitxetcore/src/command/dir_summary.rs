@@ -1,17 +1,68 @@
+use crate::config::SummariesSettings;
 use crate::config::XetConfig;
+use crate::constants::{CONTENT_SUMMARIES_PATH_SUBDIR, POINTER_FILE_LIMIT, XET_IGNORE_FILE};
+use crate::data::{PointerFile, PointerFileTranslator};
 use crate::errors::{self, GitXetRepoError};
-use crate::git_integration::{GitTreeListing, GitXetRepo};
-use crate::summaries::analysis::FileSummary;
-use clap::Args;
-use libmagic::libmagic::summarize_libmagic;
+use crate::git_integration::git_file_tools::{
+    GitTreeEntryFilter, GitTreeEntryKind, GitTreeListingEntry,
+};
+use crate::git_integration::git_quota::resolve_remote_url;
+use crate::git_integration::{submodule_path, GitTreeListing, GitXetRepo, RepoPath};
+use crate::summaries::analysis::{AnalyzerLimits, FileSummary};
+use crate::summaries::content_summaries::{ContentSummary, ContentSummaryCache};
+use crate::summaries::languages::{
+    detect_language, summarize_language, LanguageStats, LANGUAGES_MAX_FILE_SIZE,
+    LANGUAGES_SUMMARY_VERSION,
+};
+use crate::xetblob::get_content_summaries;
+use clap::{ArgEnum, Args};
+use libmagic::libmagic::{summarize_file_type, LIBMAGIC_SUMMARY_VERSION};
+use libmagic::signatures::SIGNATURE_HEAD_LEN;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     path::{Path, PathBuf},
-    str::FromStr,
 };
+use utils::output_bytes::output_bytes;
+
+/// Version 2 caches the summaries in notes encoded with bincode rather than
+/// JSON; the JSON notes of version 1 are recomputed. Version 3 adds the
+/// bytes of each file type. Version 4 counts symlinks and submodules as
+/// file types of their own. Version 5 caches them as CBOR behind
+/// [CACHED_CBOR_TAG]; the bincode notes of versions 2 to 4 don't decode, and
+/// are recomputed.
+const DIR_SUMMARY_VERSION: i64 = 5;
+
+/// The prefix of the notes caching summaries as base64 encoded CBOR. Notes
+/// without it are the JSON of version 1.
+const CACHED_CBOR_TAG: &str = "cbor:";
+
+/// The file types symlinks and submodules are counted as, whatever their
+/// names.
+const SYMLINK_FILE_TYPE: &str = "symlink";
+const SUBMODULE_FILE_TYPE: &str = "submodule";
+
+/// The path the summaries are cleaned as when they are large enough to be
+/// stored in CAS.
+const DIR_SUMMARY_PAYLOAD_PATH: &str = "dir-summary.json";
+
+/// The analyzer recorded in the summaries that include the languages of the
+/// files stored as pointer files, computed with --remote-summaries. Cached
+/// summaries without it are recomputed when --remote-summaries is set.
+const POINTER_FILES_ANALYZER: &str = "pointer-files";
+
+/// The most hashes asked for in a single request for remote summaries.
+const CONTENT_SUMMARIES_BATCH_SIZE: usize = 1000;
 
-const DIR_SUMMARY_VERSION: i64 = 1;
+/// The analyzers the directory summaries are computed with, by name, and
+/// their versions. Cached summaries from other analyzer versions are
+/// recomputed.
+fn dir_summary_analyzers() -> BTreeMap<String, u32> {
+    BTreeMap::from([
+        ("libmagic".to_string(), LIBMAGIC_SUMMARY_VERSION),
+        ("languages".to_string(), LANGUAGES_SUMMARY_VERSION),
+    ])
+}
 
 #[derive(Args, Debug)]
 pub struct DirSummaryArgs {
@@ -28,11 +79,144 @@ pub struct DirSummaryArgs {
     /// subdirectories.  
     #[clap(long)]
     recursive: bool,
+
+    /// Also summarize the checked out submodules that are Xet repositories,
+    /// at the commits reference pins them to. Their directories are listed
+    /// under the submodule paths.
+    #[clap(long)]
+    recurse_submodules: bool,
+
+    /// Only summarize the files matching this git pathspec, relative to the
+    /// repository root, e.g. "data/imagenet/**". May be repeated. Summaries
+    /// restricted by a pathspec are not cached.
+    #[clap(long)]
+    pathspec: Vec<String>,
+
+    /// Leave out the paths matching this pattern, in addition to those
+    /// matching the patterns in the .xetignore file at reference. Patterns
+    /// follow .gitignore, so "node_modules" leaves out every node_modules
+    /// directory. May be repeated.
+    #[clap(long)]
+    exclude: Vec<String>,
+
+    /// Print the summary of each directory as a line of JSON as soon as it
+    /// is computed, holding only the directories being walked in memory,
+    /// for repositories with millions of directories. Directories come in
+    /// path order, each after its subdirectories with --recursive. Streamed
+    /// summaries are not cached.
+    #[clap(long)]
+    stream: bool,
+
+    /// Also count the source files stored as pointer files in the languages
+    /// of their directories, with the summaries of their contents the remote
+    /// has precomputed. Files the remote has no summary for are downloaded
+    /// and analyzed. Summaries are cached locally by content hash.
+    #[clap(long)]
+    remote_summaries: bool,
+
+    /// The remote to ask for summaries with --remote-summaries.
+    #[clap(long, default_value = "origin")]
+    remote: String,
 }
 
 pub async fn dir_summary_command(config: XetConfig, args: &DirSummaryArgs) -> errors::Result<()> {
-    let repo = GitXetRepo::open(config.clone())?;
+    let repo = GitXetRepo::open(config)?;
+    if args.stream {
+        if args.recurse_submodules || args.remote_summaries {
+            return Err(GitXetRepoError::InvalidOperation(
+                "--stream cannot be used with --recurse-submodules or --remote-summaries"
+                    .to_string(),
+            ));
+        }
+        return print_streamed_dir_summaries(&repo, args);
+    }
+    let mut summaries = load_or_compute_dir_summaries(&repo, &args.reference, "", args).await?;
+
+    if args.recurse_submodules {
+        let mut repos = vec![(String::new(), repo, args.reference.clone())];
+        while let Some((path, repo, reference)) = repos.pop() {
+            for (sub_path, sub_repo) in repo.xet_submodules()? {
+                let Some(commit) = repo.submodule_commit_at_ref(&reference, &sub_path)? else {
+                    continue;
+                };
+                let sub_path = submodule_path(&path, &sub_path);
+                let sub_summaries =
+                    load_or_compute_dir_summaries(&sub_repo, &commit, &sub_path, args).await?;
+                summaries.merge_submodule(&sub_path, sub_summaries, args.recursive);
+                repos.push((sub_path, sub_repo, commit));
+            }
+        }
+    }
+
+    let content_str = serde_json::to_string_pretty(&summaries).map_err(|_| {
+        GitXetRepoError::Other("Failed to serialize dir summaries to JSON".to_string())
+    })?;
+    println!("{content_str}");
+    Ok(())
+}
+
+/// The recursive directory summaries of repo at reference, cached in the
+/// git notes as by `git xet dir-summary --recursive`.
+pub async fn recursive_dir_summaries(
+    repo: &GitXetRepo,
+    reference: &str,
+) -> errors::Result<DirSummaries> {
+    let args = DirSummaryArgs {
+        reference: reference.to_string(),
+        no_cache: false,
+        recursive: true,
+        recurse_submodules: false,
+        pathspec: Vec::new(),
+        exclude: Vec::new(),
+        stream: false,
+        remote_summaries: false,
+        remote: "origin".to_string(),
+    };
+    load_or_compute_dir_summaries(repo, reference, "", &args).await
+}
+
+/// Prints the summary of each directory of the tree at the reference as a
+/// line of JSON, as stream_dir_summaries computes it.
+fn print_streamed_dir_summaries(repo: &GitXetRepo, args: &DirSummaryArgs) -> errors::Result<()> {
+    let ignore_patterns = exclude_patterns_at_ref(repo, &args.reference)?;
+    let filter = GitTreeEntryFilter::default()
+        .retain_pathspec(&args.pathspec, Path::new(""))?
+        .remove_excluded(&ignore_patterns, Path::new(""))?
+        .remove_excluded(&args.exclude, Path::new(""))?;
+    stream_dir_summaries(
+        repo,
+        &args.reference,
+        &filter,
+        args.recursive,
+        &mut |record| {
+            println!("{}", serde_json::to_string(record)?);
+            Ok(())
+        },
+    )
+}
+
+/// Returns the directory summaries of repo at reference, reading them from
+/// and writing them to the git notes unless --no-cache or --pathspec is set.
+/// path is the path of repo relative to the root repository, against which
+/// the pathspec and the --exclude patterns are matched.
+async fn load_or_compute_dir_summaries(
+    repo: &GitXetRepo,
+    reference: &str,
+    path: &str,
+    args: &DirSummaryArgs,
+) -> errors::Result<DirSummaries> {
     let gitrepo = &repo.repo;
+    // The --exclude patterns of a submodule depend on where it is checked
+    // out, which the cached note does not record.
+    let use_cache =
+        !args.no_cache && args.pathspec.is_empty() && (path.is_empty() || args.exclude.is_empty());
+
+    let ignore_patterns = exclude_patterns_at_ref(repo, reference)?;
+    let excludes: Vec<String> = ignore_patterns
+        .iter()
+        .chain(args.exclude.iter())
+        .cloned()
+        .collect();
 
     let notes_ref = if args.recursive {
         "refs/notes/xet/dir-summary-recursive"
@@ -41,65 +225,279 @@ pub async fn dir_summary_command(config: XetConfig, args: &DirSummaryArgs) -> er
     };
 
     let oid = gitrepo
-        .revparse_single(&args.reference)
-        .map_err(|_| anyhow::anyhow!("Unable to resolve reference {}", args.reference))?
+        .revparse_single(reference)
+        .map_err(|_| anyhow::anyhow!("Unable to resolve reference {}", reference))?
         .id();
 
-    let mut recompute = true;
-    let mut content_str = String::new();
     // if cached in git notes for the current commit, return that
-    if let (false, Ok(note)) = (args.no_cache, gitrepo.find_note(Some(notes_ref), oid)) {
+    let note_content = if use_cache {
+        match gitrepo.find_note(Some(notes_ref), oid) {
+            Ok(note) => Some(
+                note.message()
+                    .ok_or_else(|| {
+                        GitXetRepoError::Other("Failed to get message from git note".to_string())
+                    })?
+                    .to_string(),
+            ),
+            Err(_) => None,
+        }
+    } else {
+        None
+    };
+    if let Some(note_content) = note_content {
         tracing::info!("Fetching from note");
-        content_str = note
-            .message()
-            .ok_or_else(|| {
-                GitXetRepoError::Other("Failed to get message from git note".to_string())
-            })?
-            .to_string();
+        // Large summaries are stored in CAS; if they cannot be downloaded
+        // they are recomputed.
+        let content_str = repo
+            .payload_from_note_content(DIR_SUMMARY_PAYLOAD_PATH, &note_content)
+            .await
+            .map_err(|e| tracing::warn!("Unable to read the cached summaries: {e:?}"))
+            .unwrap_or_default();
 
         // make sure we can rehydrate into a summary object and
-        // that it is for the latest version
+        // that it is for the latest version, analyzers and exclusions
         // (otherwise, we still need to recompute)
-        if let Ok(d) = serde_json::from_str::<DirSummaries>(content_str.as_str()) {
-            if d.version == DIR_SUMMARY_VERSION {
-                recompute = false;
+        if let Some(d) = DirSummaries::decode_cached(&content_str) {
+            let stale = d.stale_analyzers();
+            let missing_pointer_files = args.remote_summaries
+                && d.analyzers.get(POINTER_FILES_ANALYZER) != Some(&LANGUAGES_SUMMARY_VERSION);
+            if d.version == DIR_SUMMARY_VERSION
+                && d.excludes == excludes
+                && stale.is_empty()
+                && !missing_pointer_files
+            {
+                return Ok(d);
+            }
+            if !stale.is_empty() {
+                tracing::info!("Analyzers {stale:?} changed since the summaries were cached");
             }
         }
     }
-    if recompute {
-        tracing::info!("Recomputing");
-        // recompute the dir summary
-        let summaries = compute_dir_summaries(&repo, &args.reference, args.recursive).await?;
 
-        content_str = serde_json::to_string_pretty(&summaries).map_err(|_| {
-            GitXetRepoError::Other("Failed to serialize dir summaries to JSON".to_string())
-        })?;
+    tracing::info!("Recomputing");
+    // The filter is not Send, so it's built again after awaiting.
+    let entries = || -> errors::Result<_> {
+        let filter = GitTreeEntryFilter::default()
+            .retain_pathspec(&args.pathspec, Path::new(path))?
+            .remove_excluded(&ignore_patterns, Path::new(""))?
+            .remove_excluded(&args.exclude, Path::new(path))?;
+        Ok(GitTreeListing::walk(&repo.repo_dir, Some(reference), true)?
+            .filter(move |e| e.as_ref().map_or(true, |e| filter.matches(e))))
+    };
+    let pointer_files = if args.remote_summaries {
+        let candidates = pointer_file_candidates(gitrepo, entries()?, &repo.xet_config.summaries)?;
+        Some(pointer_file_summaries(repo, candidates, &args.remote).await?)
+    } else {
+        None
+    };
+    // recompute the dir summary
+    let mut summaries =
+        compute_dir_summaries(repo, entries()?, args.recursive, pointer_files.as_ref())?;
+    summaries.excludes = excludes;
+    if pointer_files.is_some() {
+        summaries.analyzers.insert(
+            POINTER_FILES_ANALYZER.to_string(),
+            LANGUAGES_SUMMARY_VERSION,
+        );
+    }
 
-        if !args.no_cache {
-            let sig = repo.signature();
-            // use force: true to overwrite existing note (if any) since the format may have changed
-            gitrepo.note(&sig, &sig, Some(notes_ref), oid, &content_str, true)?;
-        }
+    if use_cache {
+        let content_str = repo
+            .note_content_for_payload(DIR_SUMMARY_PAYLOAD_PATH, summaries.encode_cached()?)
+            .await?;
+        let sig = repo.signature();
+        // use force: true to overwrite existing note (if any) since the format may have changed
+        gitrepo.note(&sig, &sig, Some(notes_ref), oid, &content_str, true)?;
     }
+    Ok(summaries)
+}
 
-    println!("{content_str}");
-    Ok(())
+/// Reads the exclude patterns from the .xetignore file in the tree at
+/// reference, if there is one.
+fn exclude_patterns_at_ref(repo: &GitXetRepo, reference: &str) -> errors::Result<Vec<String>> {
+    let tree = repo.repo.revparse_single(reference)?.peel_to_tree()?;
+    let Ok(entry) = tree.get_path(Path::new(XET_IGNORE_FILE)) else {
+        return Ok(Vec::new());
+    };
+    let blob = entry.to_object(&repo.repo)?.peel_to_blob()?;
+    Ok(parse_exclude_patterns(&String::from_utf8_lossy(
+        blob.content(),
+    )))
+}
+
+/// Parses the patterns of an ignore file: one per line, skipping blank
+/// lines and comments starting with #.
+fn parse_exclude_patterns(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(String::from)
+        .collect()
 }
 
 type FileExtension = String;
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct PerFileInfo {
     count: i64,
     display_name: String,
+    /// The total size of the files, smudged.
+    #[serde(default)]
+    bytes: u64,
 }
 type SummaryInfo = HashMap<FileExtension, PerFileInfo>;
 
+/// Counts a file of the type, with its size, in the summary of a directory.
+fn count_file_type(summaries: &mut SummaryInfo, file_type: &str, display_name: &str, bytes: u64) {
+    let info = summaries
+        .entry(file_type.to_string())
+        .or_insert(PerFileInfo {
+            count: 0,
+            display_name: display_name.to_string(),
+            bytes: 0,
+        });
+    info.count += 1;
+    info.bytes += bytes;
+}
+
+type LanguageName = String;
+type LanguageInfo = HashMap<LanguageName, LanguageStats>;
+
 type FolderPath = String;
 // hash map from dir (as String) to summaries for that dir (non-recursive)
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct DirSummaries {
     version: i64,
     summaries: HashMap<FolderPath, SummaryInfo>,
+    /// The patterns left out of the summaries, from .xetignore and
+    /// --exclude. Cached summaries are only used for the same patterns.
+    /// Missing in the JSON notes of version 1, which still decode, and are
+    /// then recomputed as their version differs.
+    #[serde(default)]
+    excludes: Vec<String>,
+    /// The versions of the analyzers the summaries were computed with.
+    /// Missing, like excludes, in the JSON notes of version 1.
+    #[serde(default)]
+    analyzers: BTreeMap<String, u32>,
+    /// The source files of each language in each directory and their lines.
+    /// Missing, like excludes, in the JSON notes of version 1.
+    #[serde(default)]
+    languages: HashMap<FolderPath, LanguageInfo>,
+}
+
+impl DirSummaries {
+    /// Encodes the summaries for the notes cache: CBOR, which is more
+    /// compact and faster to parse than JSON for large repositories, yet
+    /// names the fields so that new ones can be added, base64 encoded as
+    /// notes hold text, behind [CACHED_CBOR_TAG].
+    fn encode_cached(&self) -> errors::Result<String> {
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(self, &mut bytes)
+            .map_err(|e| anyhow::anyhow!("Failed to encode the dir summaries: {e}"))?;
+        Ok(format!("{CACHED_CBOR_TAG}{}", base64::encode(bytes)))
+    }
+
+    /// Decodes summaries from the notes cache. Returns None if the content
+    /// cannot be decoded.
+    fn decode_cached(content: &str) -> Option<Self> {
+        match content.trim().strip_prefix(CACHED_CBOR_TAG) {
+            Some(encoded) => {
+                let bytes = base64::decode(encoded).ok()?;
+                ciborium::de::from_reader(bytes.as_slice()).ok()
+            }
+            None => serde_json::from_str(content).ok(),
+        }
+    }
+
+    /// The analyzers whose current version differs from the one these
+    /// summaries were computed with.
+    fn stale_analyzers(&self) -> Vec<String> {
+        dir_summary_analyzers()
+            .into_iter()
+            .filter(|(name, version)| self.analyzers.get(name) != Some(version))
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    /// The number and total smudged size of the files at the repository
+    /// root, which in recursive summaries are those of the whole tree.
+    pub(crate) fn root_totals(&self) -> (i64, u64) {
+        self.summaries.get("").map_or((0, 0), |info| {
+            (
+                info.values().map(|i| i.count).sum(),
+                info.values().map(|i| i.bytes).sum(),
+            )
+        })
+    }
+
+    /// Adds the summaries of the submodule at path, with its directories
+    /// listed under path. If recursive, the totals of the submodule are also
+    /// added to each directory containing it.
+    fn merge_submodule(&mut self, path: &str, submodule: DirSummaries, recursive: bool) {
+        for (dir, info) in submodule.summaries {
+            if recursive && dir.is_empty() {
+                let parents = RepoPath::from_git_string(path).parent();
+                for p in parents.iter().flat_map(RepoPath::ancestors) {
+                    add_summary_counts(self.summaries.entry(p.to_git_string()).or_default(), &info);
+                }
+            }
+            let dir = if dir.is_empty() {
+                path.to_string()
+            } else {
+                submodule_path(path, &dir)
+            };
+            add_summary_counts(self.summaries.entry(dir).or_default(), &info);
+        }
+        for (dir, info) in submodule.languages {
+            if recursive && dir.is_empty() {
+                let parents = RepoPath::from_git_string(path).parent();
+                for p in parents.iter().flat_map(RepoPath::ancestors) {
+                    add_language_counts(
+                        self.languages.entry(p.to_git_string()).or_default(),
+                        &info,
+                    );
+                }
+            }
+            let dir = if dir.is_empty() {
+                path.to_string()
+            } else {
+                submodule_path(path, &dir)
+            };
+            add_language_counts(self.languages.entry(dir).or_default(), &info);
+        }
+    }
+
+    /// The source files of each language in dir and their lines, by
+    /// descending lines of code.
+    pub(crate) fn languages_in(&self, dir: &str) -> Vec<(&str, &LanguageStats)> {
+        let mut languages: Vec<(&str, &LanguageStats)> = self
+            .languages
+            .get(dir)
+            .into_iter()
+            .flatten()
+            .map(|(name, stats)| (name.as_str(), stats))
+            .collect();
+        languages.sort_by(|a, b| b.1.code.cmp(&a.1.code).then_with(|| a.0.cmp(b.0)));
+        languages
+    }
+}
+
+fn add_summary_counts(summaries: &mut SummaryInfo, other: &SummaryInfo) {
+    for (file_type, info) in other {
+        let entry = summaries.entry(file_type.clone()).or_insert(PerFileInfo {
+            count: 0,
+            display_name: info.display_name.clone(),
+            bytes: 0,
+        });
+        entry.count += info.count;
+        entry.bytes += info.bytes;
+    }
+}
+
+fn add_language_counts(languages: &mut LanguageInfo, other: &LanguageInfo) {
+    for (name, stats) in other {
+        languages.entry(name.clone()).or_default().add(stats);
+    }
 }
 
 impl Default for DirSummaries {
@@ -107,52 +505,428 @@ impl Default for DirSummaries {
         Self {
             version: DIR_SUMMARY_VERSION,
             summaries: Default::default(),
+            excludes: Default::default(),
+            analyzers: dir_summary_analyzers(),
+            languages: Default::default(),
         }
     }
 }
 
-fn compute_file_summary(path: &str) -> errors::Result<FileSummary> {
-    let mut ret = FileSummary::default();
-    ret.libmagic = Some(summarize_libmagic(Path::new(path))?);
-    Ok(ret)
+/// What `git xet summary top` ranks the directories by.
+#[derive(ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopOrder {
+    Count,
+    Bytes,
 }
 
-pub async fn compute_dir_summaries(
-    repo: &GitXetRepo,
+/// A directory ranked by `git xet summary top`, with its file types by
+/// descending file count.
+#[derive(Debug, PartialEq, Eq)]
+struct TopDirectory {
+    path: String,
+    count: i64,
+    bytes: u64,
+    types: Vec<(String, i64)>,
+}
+
+/// Ranks the directories of recursive summaries, leaving out the repository
+/// root and directories with fewer than min_count files.
+fn top_directories(
+    summaries: &DirSummaries,
+    order: TopOrder,
+    limit: usize,
+    min_count: i64,
+) -> Vec<TopDirectory> {
+    let mut dirs: Vec<TopDirectory> = summaries
+        .summaries
+        .iter()
+        .filter(|(path, _)| !path.is_empty())
+        .map(|(path, info)| {
+            let mut types: Vec<(String, i64)> = info
+                .values()
+                .map(|i| (i.display_name.clone(), i.count))
+                .collect();
+            types.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            TopDirectory {
+                path: path.clone(),
+                count: info.values().map(|i| i.count).sum(),
+                bytes: info.values().map(|i| i.bytes).sum(),
+                types,
+            }
+        })
+        .filter(|d| d.count >= min_count)
+        .collect();
+
+    dirs.sort_by(|a, b| {
+        let key = match order {
+            TopOrder::Count => b.count.cmp(&a.count),
+            TopOrder::Bytes => b.bytes.cmp(&a.bytes),
+        };
+        key.then_with(|| a.path.cmp(&b.path))
+    });
+    dirs.truncate(limit);
+    dirs
+}
+
+/// The number of file types listed for each directory by `git xet summary
+/// top`.
+const TOP_DOMINANT_TYPES: usize = 3;
+
+/// Prints the directories at reference with the most files, or bytes, and
+/// the file types dominating each.
+pub async fn print_top_directories(
+    config: XetConfig,
     reference: &str,
-    recursive: bool,
-) -> errors::Result<DirSummaries> {
-    let tree_listing = GitTreeListing::build(&repo.repo_dir, Some(reference), true, true, true)?;
+    order: TopOrder,
+    limit: usize,
+    min_count: i64,
+) -> errors::Result<()> {
+    let repo = GitXetRepo::open(config)?;
+    let summaries = recursive_dir_summaries(&repo, reference).await?;
 
-    let mut dir_summary = DirSummaries::default();
+    println!(
+        "{:>10}  {:>10}  {:<40}  DOMINANT TYPES",
+        "FILES", "SIZE", "DIRECTORY"
+    );
+    for dir in top_directories(&summaries, order, limit, min_count) {
+        let types = dir
+            .types
+            .iter()
+            .take(TOP_DOMINANT_TYPES)
+            .map(|(name, count)| format!("{name} ({count})"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!(
+            "{:>10}  {:>10}  {:<40}  {types}",
+            dir.count,
+            output_bytes(dir.bytes as usize),
+            dir.path
+        );
+    }
+    Ok(())
+}
+
+/// Prints the languages of the source files under dir at reference, with
+/// their share of the lines of code.
+pub async fn print_languages(config: XetConfig, reference: &str, dir: &str) -> errors::Result<()> {
+    let repo = GitXetRepo::open(config)?;
+    let summaries = recursive_dir_summaries(&repo, reference).await?;
+    let dir = dir.trim_matches('/');
+    let languages = summaries.languages_in(dir);
+    let total_code: u64 = languages.iter().map(|(_, s)| s.code).sum();
 
-    for blob_data in tree_listing.files {
-        // For each file, compute file summary from file path
-        let file_summary = compute_file_summary(&blob_data.path)?;
+    println!(
+        "{:<16}  {:>8}  {:>10}  {:>10}  {:>10}  {:>6}",
+        "LANGUAGE", "FILES", "CODE", "COMMENTS", "BLANKS", "SHARE"
+    );
+    for (name, stats) in languages {
+        let share = if total_code == 0 {
+            0.0
+        } else {
+            100.0 * stats.code as f64 / total_code as f64
+        };
+        println!(
+            "{:<16}  {:>8}  {:>10}  {:>10}  {:>10}  {:>5.1}%",
+            name, stats.files, stats.code, stats.comments, stats.blanks, share
+        );
+    }
+    Ok(())
+}
 
-        // Now, go through and increase the counts for these file types in this directory.
-        let entry_path = PathBuf::from_str(&blob_data.path).unwrap();
-        let entry_dir = entry_path.parent().unwrap_or_else(|| Path::new(""));
+/// Summarizes the file at path, of bytes bytes, whose content starts with
+/// head if it's in the repository.
+fn compute_file_summary(
+    path: &Path,
+    bytes: u64,
+    head: Option<&[u8]>,
+    settings: &SummariesSettings,
+) -> FileSummary {
+    let mut ret = FileSummary::default();
+    let mut limits = AnalyzerLimits::new("libmagic", &settings.libmagic);
+    ret.libmagic = limits.run(path, bytes, || {
+        summarize_file_type(path, head, settings.type_detector)
+    });
+    ret
+}
 
-        let summaries = dir_summary
-            .summaries
-            .entry(entry_dir.to_string_lossy().to_string())
-            .or_default();
+/// The start of the content of a blob of the tree listing, unless it's a
+/// pointer file, whose content is elsewhere.
+fn blob_head(repo: &git2::Repository, blob_data: &GitTreeListingEntry) -> Option<Vec<u8>> {
+    let blob = git2::Oid::from_str(&blob_data.object_id)
+        .and_then(|oid| repo.find_blob(oid))
+        .ok()?;
+    let content = blob.content();
+    if parse_pointer_file(content, blob_data).is_some() {
+        return None;
+    }
+    Some(content[..content.len().min(SIGNATURE_HEAD_LEN)].to_vec())
+}
+
+/// The size of the file stored in a blob of the tree listing, which for a
+/// pointer file is the size of the file it points to.
+fn smudged_file_size(repo: &git2::Repository, blob_data: &GitTreeListingEntry) -> u64 {
+    if blob_data.size > POINTER_FILE_LIMIT as u64 {
+        return blob_data.size;
+    }
+    git2::Oid::from_str(&blob_data.object_id)
+        .and_then(|oid| repo.find_blob(oid))
+        .ok()
+        .and_then(|blob| parse_pointer_file(blob.content(), blob_data))
+        .map_or(blob_data.size, |pointer_file| pointer_file.filesize())
+}
+
+/// The pointer file in the content of a blob of the tree listing, if it's
+/// one.
+fn parse_pointer_file(content: &[u8], blob_data: &GitTreeListingEntry) -> Option<PointerFile> {
+    if content.len() > POINTER_FILE_LIMIT {
+        return None;
+    }
+    let content = std::str::from_utf8(content).ok()?;
+    let pointer_file = PointerFile::init_from_string(content, &blob_data.path.to_string());
+    pointer_file.is_valid().then_some(pointer_file)
+}
+
+/// The summaries of the contents of files stored as pointer files, by the
+/// xet hash of the content.
+type PointerFileSummaries = HashMap<String, ContentSummary>;
 
-        if let Some(ref libmagic_summary) = file_summary.libmagic {
-            let extension = libmagic_summary.file_type.clone();
-            // exclude empty file extension from dir summaries
-            if !extension.is_empty() {
-                let file_type_simple_summary = summaries.entry(extension).or_insert(PerFileInfo {
-                    count: 0,
-                    display_name: libmagic_summary.file_type_simple.clone(),
-                });
+/// The language of the file stored in a blob of the tree listing and its
+/// line counts, if it's a source file. Pointer files aren't read; their
+/// languages are those of their summaries in pointer_files, if any.
+fn file_language(
+    repo: &git2::Repository,
+    blob_data: &GitTreeListingEntry,
+    pointer_files: Option<&PointerFileSummaries>,
+    settings: &SummariesSettings,
+) -> Option<(String, LanguageStats)> {
+    if blob_data.size > LANGUAGES_MAX_FILE_SIZE {
+        return None;
+    }
+    let blob = git2::Oid::from_str(&blob_data.object_id)
+        .and_then(|oid| repo.find_blob(oid))
+        .ok()?;
+    let content = blob.content();
+    let path = blob_data.path.to_path();
+    if let Some(pointer_file) = parse_pointer_file(content, blob_data) {
+        if !settings.languages.runs_on(&path, pointer_file.filesize()) {
+            return None;
+        }
+        let summary = pointer_files?.get(pointer_file.hash_string())?;
+        let language = summary.language.as_ref()?;
+        return Some((language.name.clone(), language.stats.clone()));
+    }
+    AnalyzerLimits::new("languages", &settings.languages)
+        .run(&path, blob_data.size, || summarize_language(&path, content))
+        .flatten()
+        .map(|(name, stats)| (name.to_string(), stats))
+}
+
+/// The pointer files among entries that may be source files, by the xet
+/// hash of their content, with the path of one of them.
+fn pointer_file_candidates(
+    repo: &git2::Repository,
+    entries: impl Iterator<Item = errors::Result<GitTreeListingEntry>>,
+    settings: &SummariesSettings,
+) -> errors::Result<BTreeMap<String, (RepoPath, PointerFile)>> {
+    let mut candidates = BTreeMap::new();
+    for blob_data in entries {
+        let blob_data = blob_data?;
+        let is_file = matches!(
+            blob_data.kind(),
+            GitTreeEntryKind::File | GitTreeEntryKind::Executable
+        );
+        if !is_file || blob_data.size > POINTER_FILE_LIMIT as u64 {
+            continue;
+        }
+        let blob = repo.find_blob(git2::Oid::from_str(&blob_data.object_id)?)?;
+        if let Some(pointer_file) = parse_pointer_file(blob.content(), &blob_data) {
+            if pointer_file.filesize() <= LANGUAGES_MAX_FILE_SIZE
+                && settings
+                    .languages
+                    .runs_on(&blob_data.path.to_path(), pointer_file.filesize())
+            {
+                candidates
+                    .entry(pointer_file.hash_string().clone())
+                    .or_insert((blob_data.path, pointer_file));
+            }
+        }
+    }
+    Ok(candidates)
+}
+
+/// Summarizes the contents of the pointer files: from the local cache, then
+/// from the summaries the remote has, and last by downloading and analyzing
+/// the files named like source files. Files that can't be summarized are
+/// left out, and counted in no language.
+async fn pointer_file_summaries(
+    repo: &GitXetRepo,
+    pointer_files: BTreeMap<String, (RepoPath, PointerFile)>,
+    remote: &str,
+) -> errors::Result<PointerFileSummaries> {
+    let mut cache = ContentSummaryCache::load(&repo.git_dir.join(CONTENT_SUMMARIES_PATH_SUBDIR));
+    let misses: Vec<String> = pointer_files
+        .keys()
+        .filter(|hash| cache.get(hash).is_none())
+        .cloned()
+        .collect();
 
-                file_type_simple_summary.count += 1;
+    let remote_url = resolve_remote_url(&repo.repo, remote);
+    let mut fetched = 0;
+    for batch in misses.chunks(CONTENT_SUMMARIES_BATCH_SIZE) {
+        match get_content_summaries(&repo.xet_config, &remote_url, batch).await {
+            Ok(summaries) => {
+                for (hash, summary) in summaries {
+                    if pointer_files.contains_key(&hash) {
+                        cache.insert(hash, summary);
+                        fetched += 1;
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Unable to get summaries from {remote}: {e}; analyzing the files locally."
+                );
+                break;
             }
         }
     }
 
+    // Only the files named like source files are worth downloading.
+    let to_download: Vec<(&String, PathBuf, &PointerFile)> = misses
+        .iter()
+        .filter(|hash| cache.get(hash).is_none())
+        .map(|hash| {
+            (
+                hash,
+                pointer_files[hash].0.to_path(),
+                &pointer_files[hash].1,
+            )
+        })
+        .filter(|(_, path, _)| detect_language(path, &[]).is_some())
+        .collect();
+    let mut analyzed = 0;
+    if !to_download.is_empty() {
+        let translator = PointerFileTranslator::from_config_in_repo(&repo.xet_config).await?;
+        for (hash, path, pointer_file) in to_download {
+            let mut content = Vec::new();
+            if let Err(e) = translator
+                .smudge_file_from_pointer(&path, pointer_file, &mut content, None)
+                .await
+            {
+                tracing::warn!("Unable to download {path:?} to summarize it: {e}");
+                continue;
+            }
+            // A file the analyzer gave up on is not cached, to try it again
+            // with other settings.
+            let mut limits = AnalyzerLimits::new("languages", &repo.xet_config.summaries.languages);
+            let Some(summary) = limits.run(&path, pointer_file.filesize(), || {
+                ContentSummary::from_content(&path, &content)
+            }) else {
+                continue;
+            };
+            cache.insert(hash.clone(), summary);
+            analyzed += 1;
+        }
+    }
+    tracing::info!(
+        "Summarized {} pointer files: {fetched} from {remote}, {analyzed} downloaded",
+        pointer_files.len()
+    );
+    if let Err(e) = cache.flush() {
+        tracing::warn!("Unable to write the content summary cache: {e}");
+    }
+
+    Ok(pointer_files
+        .keys()
+        .filter_map(|hash| Some((hash.clone(), cache.get(hash)?.clone())))
+        .collect())
+}
+
+/// Counts a file, symlink or submodule of a tree in the summary of its
+/// directory, and in the languages of the directory if it's a source file.
+fn summarize_entry(
+    repo: &git2::Repository,
+    blob_data: &GitTreeListingEntry,
+    summaries: &mut SummaryInfo,
+    languages: &mut LanguageInfo,
+    pointer_files: Option<&PointerFileSummaries>,
+    settings: &SummariesSettings,
+) -> errors::Result<()> {
+    match blob_data.kind() {
+        GitTreeEntryKind::Submodule => {
+            count_file_type(summaries, SUBMODULE_FILE_TYPE, "Git submodule", 0);
+            return Ok(());
+        }
+        // The blob of a symlink is the path it points to, which is neither a
+        // pointer file nor source, so it's only counted.
+        GitTreeEntryKind::Symlink => {
+            count_file_type(
+                summaries,
+                SYMLINK_FILE_TYPE,
+                "Symbolic link",
+                blob_data.size,
+            );
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    // For each file, compute file summary from file path
+    let bytes = smudged_file_size(repo, blob_data);
+    let head = blob_head(repo, blob_data);
+    let file_summary =
+        compute_file_summary(&blob_data.path.to_path(), bytes, head.as_deref(), settings);
+    let language = file_language(repo, blob_data, pointer_files, settings);
+
+    // Now, go through and increase the counts for these file types in this directory.
+    if let Some(ref libmagic_summary) = file_summary.libmagic {
+        // exclude empty file extension from dir summaries
+        if !libmagic_summary.file_type.is_empty() {
+            count_file_type(
+                summaries,
+                &libmagic_summary.file_type,
+                &libmagic_summary.file_type_simple,
+                bytes,
+            );
+        }
+    }
+
+    if let Some((name, stats)) = language {
+        languages.entry(name).or_default().add(&stats);
+    }
+    Ok(())
+}
+
+/// Summarizes the files and submodules of a tree, streamed by
+/// [GitTreeListing::walk], with the summaries of the pointer files if any.
+pub fn compute_dir_summaries(
+    repo: &GitXetRepo,
+    entries: impl Iterator<Item = errors::Result<GitTreeListingEntry>>,
+    recursive: bool,
+    pointer_files: Option<&PointerFileSummaries>,
+) -> errors::Result<DirSummaries> {
+    let mut dir_summary = DirSummaries::default();
+
+    for blob_data in entries {
+        let blob_data = blob_data?;
+        let entry_dir = blob_data.path.parent().unwrap_or_default().to_git_string();
+        let mut languages = LanguageInfo::new();
+        summarize_entry(
+            &repo.repo,
+            &blob_data,
+            dir_summary.summaries.entry(entry_dir.clone()).or_default(),
+            &mut languages,
+            pointer_files,
+            &repo.xet_config.summaries,
+        )?;
+        if !languages.is_empty() {
+            add_language_counts(
+                dir_summary.languages.entry(entry_dir).or_default(),
+                &languages,
+            );
+        }
+    }
+
     if recursive {
         // Now, go through and create a new dir summary that has aggregated all the entries back up
         // to their parent directories.
@@ -161,35 +935,417 @@ pub async fn compute_dir_summaries(
         for (path, st_hashmap) in dir_summary.summaries.into_iter() {
             for (file_type, info) in st_hashmap.into_iter() {
                 let count = info.count;
-                let mut entry_dir = PathBuf::from_str(&path).unwrap();
+                let bytes = info.bytes;
 
-                loop {
+                for entry_dir in RepoPath::from_git_string(&path).ancestors() {
                     let summaries = aggregated_ds
                         .summaries
-                        .entry(entry_dir.to_string_lossy().to_string())
+                        .entry(entry_dir.to_git_string())
                         .or_default();
 
                     let file_type_simple_summary =
                         summaries.entry(file_type.clone()).or_insert(PerFileInfo {
                             count: 0,
                             display_name: info.display_name.clone(),
+                            bytes: 0,
                         });
 
                     file_type_simple_summary.count += count;
-
-                    if entry_dir == PathBuf::from_str("").unwrap() {
-                        break;
-                    } else {
-                        entry_dir = entry_dir
-                            .parent()
-                            .unwrap_or_else(|| Path::new(""))
-                            .to_path_buf();
-                    }
+                    file_type_simple_summary.bytes += bytes;
                 }
             }
         }
+
+        for (path, info) in dir_summary.languages {
+            for dir in RepoPath::from_git_string(&path).ancestors() {
+                add_language_counts(
+                    aggregated_ds
+                        .languages
+                        .entry(dir.to_git_string())
+                        .or_default(),
+                    &info,
+                );
+            }
+        }
         Ok(aggregated_ds)
     } else {
         Ok(dir_summary)
     }
 }
+
+/// The summary of a directory, as printed by `git xet dir-summary --stream`.
+#[derive(Serialize, Debug, Default, PartialEq, Eq)]
+pub struct DirSummaryRecord {
+    path: FolderPath,
+    summary: SummaryInfo,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    languages: LanguageInfo,
+}
+
+impl DirSummaryRecord {
+    fn is_empty(&self) -> bool {
+        self.summary.is_empty() && self.languages.is_empty()
+    }
+}
+
+/// Summarizes the tree at reference one directory at a time, passing the
+/// summary of each directory with files to emit as soon as it is complete.
+/// The tree is walked in path order, depth first, so only the directories
+/// being walked are held in memory. A directory comes before its
+/// subdirectories, or, if recursive, after them, as it then includes them.
+pub fn stream_dir_summaries(
+    repo: &GitXetRepo,
+    reference: &str,
+    filter: &GitTreeEntryFilter,
+    recursive: bool,
+    emit: &mut impl FnMut(&DirSummaryRecord) -> errors::Result<()>,
+) -> errors::Result<()> {
+    let tree = repo.repo.revparse_single(reference)?.peel_to_tree()?;
+    let walk = DirSummaryStream {
+        repo: &repo.repo,
+        odb: repo.repo.odb()?,
+        filter,
+        recursive,
+        settings: &repo.xet_config.summaries,
+    };
+    walk.summarize_tree(&tree, RepoPath::root(), emit)?;
+    Ok(())
+}
+
+struct DirSummaryStream<'a> {
+    repo: &'a git2::Repository,
+    odb: git2::Odb<'a>,
+    filter: &'a GitTreeEntryFilter,
+    recursive: bool,
+    settings: &'a SummariesSettings,
+}
+
+impl DirSummaryStream<'_> {
+    /// Summarizes the tree of dir and, in turn, its subtrees; returns the
+    /// summary of dir.
+    fn summarize_tree(
+        &self,
+        tree: &git2::Tree,
+        dir: RepoPath,
+        emit: &mut impl FnMut(&DirSummaryRecord) -> errors::Result<()>,
+    ) -> errors::Result<DirSummaryRecord> {
+        let mut record = DirSummaryRecord {
+            path: dir.to_git_string(),
+            ..Default::default()
+        };
+        let mut subtrees = Vec::new();
+        for entry in tree.iter() {
+            let mut path = dir.clone();
+            path.push(entry.name_bytes());
+            if entry.kind() == Some(git2::ObjectType::Tree) {
+                subtrees.push((path, entry.id()));
+                continue;
+            }
+            let size = if entry.kind() == Some(git2::ObjectType::Blob) {
+                self.odb.read_header(entry.id())?.0 as u64
+            } else {
+                0
+            };
+            let blob_data = GitTreeListingEntry {
+                object_id: entry.id().to_string(),
+                path,
+                permissions: entry.filemode() as u32,
+                size,
+            };
+            if self.filter.matches(&blob_data) {
+                summarize_entry(
+                    self.repo,
+                    &blob_data,
+                    &mut record.summary,
+                    &mut record.languages,
+                    None,
+                    self.settings,
+                )?;
+            }
+        }
+
+        if !self.recursive && !record.is_empty() {
+            emit(&record)?;
+        }
+        for (path, id) in subtrees {
+            let subtree = self.repo.find_tree(id)?;
+            let sub_record = self.summarize_tree(&subtree, path, emit)?;
+            if self.recursive {
+                add_summary_counts(&mut record.summary, &sub_record.summary);
+                add_language_counts(&mut record.languages, &sub_record.languages);
+            }
+        }
+        if self.recursive && !record.is_empty() {
+            emit(&record)?;
+        }
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git_integration::git_repo_test_tools::TestRepo;
+
+    fn summary_info(file_type: &str, count: i64) -> SummaryInfo {
+        HashMap::from([(
+            file_type.to_string(),
+            PerFileInfo {
+                count,
+                display_name: file_type.to_string(),
+                bytes: count as u64 * 100,
+            },
+        )])
+    }
+
+    #[test]
+    fn test_merge_submodule() {
+        let submodule = || DirSummaries {
+            version: DIR_SUMMARY_VERSION,
+            summaries: HashMap::from([
+                ("".to_string(), summary_info("csv", 2)),
+                ("data".to_string(), summary_info("csv", 1)),
+            ]),
+            excludes: Vec::new(),
+            analyzers: dir_summary_analyzers(),
+            languages: HashMap::from([(
+                "".to_string(),
+                HashMap::from([(
+                    "Rust".to_string(),
+                    LanguageStats {
+                        files: 1,
+                        code: 10,
+                        ..Default::default()
+                    },
+                )]),
+            )]),
+        };
+
+        let mut summaries = DirSummaries::default();
+        summaries
+            .summaries
+            .insert("".to_string(), summary_info("csv", 1));
+        summaries.merge_submodule("deps/sub", submodule(), false);
+        assert_eq!(summaries.summaries.len(), 3);
+        assert_eq!(summaries.summaries[""]["csv"].count, 1);
+        assert_eq!(summaries.summaries["deps/sub"]["csv"].count, 2);
+        assert_eq!(summaries.summaries["deps/sub/data"]["csv"].count, 1);
+
+        let mut summaries = DirSummaries::default();
+        summaries
+            .summaries
+            .insert("".to_string(), summary_info("csv", 1));
+        summaries.merge_submodule("deps/sub", submodule(), true);
+        assert_eq!(summaries.summaries[""]["csv"].count, 3);
+        assert_eq!(summaries.summaries["deps"]["csv"].count, 2);
+        assert_eq!(summaries.summaries["deps/sub"]["csv"].count, 2);
+        assert_eq!(summaries.summaries["deps/sub/data"]["csv"].count, 1);
+        assert_eq!(summaries.languages[""]["Rust"].code, 10);
+        assert_eq!(summaries.languages["deps"]["Rust"].code, 10);
+        assert_eq!(summaries.languages_in("deps/sub")[0].0, "Rust");
+        assert!(summaries.languages_in("deps/sub/data").is_empty());
+    }
+
+    #[test]
+    fn test_parse_exclude_patterns() {
+        let content = "# vendored code\nnode_modules/\n\n  third_party  \n*.min.js\n";
+        assert_eq!(
+            parse_exclude_patterns(content),
+            vec!["node_modules/", "third_party", "*.min.js"]
+        );
+        assert!(parse_exclude_patterns("").is_empty());
+    }
+
+    #[test]
+    fn test_top_directories() {
+        let mut summaries = DirSummaries::default();
+        summaries
+            .summaries
+            .insert("".to_string(), summary_info("csv", 10));
+        summaries
+            .summaries
+            .insert("a".to_string(), summary_info("csv", 2));
+        summaries.summaries.insert("b".to_string(), {
+            let mut info = summary_info("csv", 1);
+            info.extend(summary_info("png", 3));
+            info.get_mut("png").unwrap().bytes = 1;
+            info
+        });
+        summaries
+            .summaries
+            .insert("c".to_string(), summary_info("txt", 1));
+
+        let top = top_directories(&summaries, TopOrder::Count, 10, 0);
+        let paths: Vec<_> = top.iter().map(|d| d.path.as_str()).collect();
+        assert_eq!(paths, vec!["b", "a", "c"]);
+        assert_eq!(top[0].count, 4);
+        assert_eq!(top[0].bytes, 101);
+        assert_eq!(
+            top[0].types,
+            vec![("png".to_string(), 3), ("csv".to_string(), 1)]
+        );
+
+        let top = top_directories(&summaries, TopOrder::Bytes, 10, 0);
+        let paths: Vec<_> = top.iter().map(|d| d.path.as_str()).collect();
+        assert_eq!(paths, vec!["a", "b", "c"]);
+
+        let top = top_directories(&summaries, TopOrder::Count, 1, 0);
+        assert_eq!(top.len(), 1);
+        let top = top_directories(&summaries, TopOrder::Count, 10, 2);
+        assert_eq!(top.len(), 2);
+    }
+
+    #[test]
+    fn test_cached_encoding() {
+        let mut summaries = DirSummaries::default();
+        summaries
+            .summaries
+            .insert("data".to_string(), summary_info("csv", 3));
+        summaries.excludes = vec!["node_modules".to_string()];
+
+        let encoded = summaries.encode_cached().unwrap();
+        assert!(encoded.starts_with(CACHED_CBOR_TAG));
+        assert_eq!(DirSummaries::decode_cached(&encoded), Some(summaries));
+
+        // Version 1 notes are JSON, and are recomputed as their version differs.
+        let d = DirSummaries::decode_cached(r#"{"version": 1, "summaries": {}}"#).unwrap();
+        assert_eq!(d.version, 1);
+        assert!(d.analyzers.is_empty());
+        assert!(DirSummaries::decode_cached("not a summary").is_none());
+        // Untagged bincode, from versions 2 to 4, doesn't decode.
+        let bincode = base64::encode(bincode::serialize(&d).unwrap());
+        assert!(DirSummaries::decode_cached(&bincode).is_none());
+        // Summaries missing fields added since still decode.
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(
+            &serde_json::json!({"version": 4, "summaries": {}}),
+            &mut bytes,
+        )
+        .unwrap();
+        let tagged = format!("{CACHED_CBOR_TAG}{}", base64::encode(bytes));
+        assert_eq!(DirSummaries::decode_cached(&tagged).unwrap().version, 4);
+    }
+
+    #[test]
+    fn test_output_schema() {
+        let mut summaries = DirSummaries::default();
+        summaries
+            .summaries
+            .insert("data".to_string(), summary_info("csv", 3));
+        summaries.excludes = vec!["node_modules".to_string()];
+        summaries.languages.insert(
+            "src".to_string(),
+            HashMap::from([("Rust".to_string(), LanguageStats::default())]),
+        );
+        crate::schemas::assert_matches_schema(
+            "dir-summaries",
+            &serde_json::to_value(&summaries).unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_excludes_serialization() {
+        // Notes written before excludes were recorded have none.
+        let d: DirSummaries = serde_json::from_str(r#"{"version": 1, "summaries": {}}"#).unwrap();
+        assert!(d.excludes.is_empty());
+    }
+
+    #[test]
+    fn test_stale_analyzers() {
+        let mut summaries = DirSummaries::default();
+        assert!(summaries.stale_analyzers().is_empty());
+
+        summaries
+            .analyzers
+            .insert("libmagic".to_string(), LIBMAGIC_SUMMARY_VERSION + 1);
+        assert_eq!(summaries.stale_analyzers(), vec!["libmagic"]);
+
+        // Analyzers the summaries no longer use do not matter.
+        let mut summaries = DirSummaries::default();
+        summaries.analyzers.insert("csv".to_string(), 1);
+        assert!(summaries.stale_analyzers().is_empty());
+
+        // Summaries cached before the versions were recorded are stale.
+        let d: DirSummaries = serde_json::from_str(r#"{"version": 1, "summaries": {}}"#).unwrap();
+        assert_eq!(d.stale_analyzers(), vec!["languages", "libmagic"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_stream_dir_summaries() -> errors::Result<()> {
+        let tr = TestRepo::new()?;
+        for path in ["a.txt", "data/b.csv", "data/sub/c.csv", "src/main.py"] {
+            tr.write_file(path, 0, 100)?;
+        }
+        tr.repo.run_git_checked_in_repo("add", &["."])?;
+        tr.repo
+            .run_git_checked_in_repo("commit", &["-m", "Add files."])?;
+
+        let stream = |filter: &GitTreeEntryFilter, recursive| {
+            let mut records = Vec::new();
+            stream_dir_summaries(&tr.repo, "HEAD", filter, recursive, &mut |record| {
+                records.push((record.path.clone(), record.summary.clone()));
+                Ok(())
+            })
+            .map(|_| records)
+        };
+
+        // Directories come in path order, with the summaries computed for
+        // the whole tree.
+        let filter = GitTreeEntryFilter::default();
+        let records = stream(&filter, false)?;
+        let paths: Vec<_> = records.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(paths, vec!["", "data", "data/sub", "src"]);
+        let walk = GitTreeListing::walk(&tr.repo.repo_dir, Some("HEAD"), true)?;
+        let summaries = compute_dir_summaries(&tr.repo, walk, false, None)?;
+        assert_eq!(HashMap::from_iter(records), summaries.summaries);
+
+        // Recursively, a directory comes after its subdirectories, which it
+        // includes.
+        let records = stream(&filter, true)?;
+        let paths: Vec<_> = records.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(paths, vec!["data/sub", "data", "src", ""]);
+        let count = |summary: &SummaryInfo| summary.values().map(|i| i.count).sum::<i64>();
+        assert_eq!(count(&records[1].1), 2);
+        assert_eq!(count(&records[3].1), 4);
+
+        // Directories with every file excluded are left out.
+        let filter = GitTreeEntryFilter::default().remove_excluded(&["data"], Path::new(""))?;
+        let records = stream(&filter, true)?;
+        let paths: Vec<_> = records.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(paths, vec!["src", ""]);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_pointer_file_languages() -> errors::Result<()> {
+        let tr = TestRepo::new()?;
+        let hash = "1".repeat(64);
+        let pointer = PointerFile::init_from_info("src/big.py", &hash, 10_000);
+        std::fs::create_dir_all(tr.repo.repo_dir.join("src"))?;
+        std::fs::write(tr.repo.repo_dir.join("src/big.py"), pointer.to_string())?;
+        std::fs::write(tr.repo.repo_dir.join("src/small.py"), "print(1)\n")?;
+        tr.repo.run_git_checked_in_repo("add", &["."])?;
+        tr.repo
+            .run_git_checked_in_repo("commit", &["-m", "Add files."])?;
+
+        let walk = || GitTreeListing::walk(&tr.repo.repo_dir, Some("HEAD"), true);
+        let candidates =
+            pointer_file_candidates(&tr.repo.repo, walk()?, &tr.repo.xet_config.summaries)?;
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[&hash].0, RepoPath::from("src/big.py"));
+
+        // Without summaries, pointer files are in no language.
+        let summaries = compute_dir_summaries(&tr.repo, walk()?, false, None)?;
+        assert_eq!(summaries.languages["src"]["Python"].files, 1);
+        assert_eq!(summaries.summaries["src"]["py"].bytes, 10_009);
+
+        let pointer_files = HashMap::from([(
+            hash.clone(),
+            ContentSummary::from_content(Path::new("big.py"), b"x = 1\ny = 2\n"),
+        )]);
+        let summaries = compute_dir_summaries(&tr.repo, walk()?, true, Some(&pointer_files))?;
+        assert_eq!(summaries.languages["src"]["Python"].files, 2);
+        assert_eq!(summaries.languages[""]["Python"].code, 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+use clap::Args;
+use std::io::stdout;
+use std::path::Path;
+
+use super::batch::{read_batch_inputs, write_batch_line, BatchError};
+
+use crate::config::XetConfig;
+use crate::errors::{GitXetRepoError, Result};
+use crate::git_integration::git_quota::resolve_remote_url;
+use crate::git_integration::GitXetRepo;
+use crate::xetblob::{get_presigned_urls, PresignedFile};
+
+/// Prints expiring download links to files in the remote repository, which
+/// can be shared with someone who has no access to the repository.
+///
+/// The remote serves each file at a single link, or, if it is too large to
+/// be assembled by the server, at a list of links to parts to be downloaded
+/// and concatenated in order.
+///
+/// ```ignore
+/// git xet url data/results.csv --expires 7d
+/// ```
+///
+/// With --batch, the files are read from stdin, and the links to each
+/// printed as a line of JSON, from a single request to the remote:
+///
+/// ```ignore
+/// git ls-files data | git xet url --batch
+/// ```
+#[derive(Args, Debug)]
+pub struct UrlArgs {
+    /// The files to link to, relative to the repository root.
+    #[clap(required_unless_present = "batch")]
+    paths: Vec<String>,
+
+    /// Read the files from stdin, one per line. A file that does not exist
+    /// is answered with an error rather than failing the batch.
+    #[clap(long, conflicts_with = "paths")]
+    batch: bool,
+
+    /// With --batch, the files are separated by NUL bytes.
+    #[clap(short = 'z', requires = "batch")]
+    nul: bool,
+
+    /// The branch or commit of the files, which must have been pushed.
+    /// Defaults to the branch checked out.
+    #[clap(long = "ref")]
+    reference: Option<String>,
+
+    /// The remote serving the files.
+    #[clap(long, default_value = "origin")]
+    remote: String,
+
+    /// How long the links work for, e.g. "1h" or "7d".
+    #[clap(long, default_value = "24h")]
+    expires: humantime::Duration,
+
+    /// Print the links as JSON.
+    #[clap(long)]
+    json: bool,
+}
+
+pub async fn url_command(cfg: XetConfig, args: &UrlArgs) -> Result<()> {
+    let repo = GitXetRepo::open(cfg.clone())?;
+    let reference = match &args.reference {
+        Some(reference) => reference.clone(),
+        None => {
+            let head = repo.repo.head()?;
+            match head.shorthand() {
+                Some(branch) if head.is_branch() => branch.to_string(),
+                _ => head.peel_to_commit()?.id().to_string(),
+            }
+        }
+    };
+
+    let mut paths = if args.batch {
+        read_batch_inputs(args.nul)?
+    } else {
+        args.paths.clone()
+    };
+
+    // Catch mistyped paths before asking the remote, if the reference is
+    // known locally.
+    let mut missing = Vec::new();
+    if let Ok(tree) = repo
+        .repo
+        .revparse_single(&reference)
+        .and_then(|o| o.peel_to_tree())
+    {
+        for path in &paths {
+            if tree.get_path(Path::new(path)).is_err() {
+                let error = format!("{path} does not exist at {reference}");
+                if !args.batch {
+                    return Err(GitXetRepoError::InvalidOperation(error));
+                }
+                missing.push((path.clone(), error));
+            }
+        }
+    }
+    paths.retain(|path| !missing.iter().any(|(p, _)| p == path));
+
+    let remote_url = resolve_remote_url(&repo.repo, &args.remote);
+    let files = if paths.is_empty() {
+        Vec::new()
+    } else {
+        get_presigned_urls(&cfg, &remote_url, &reference, &paths, *args.expires)
+            .await
+            .map_err(|e| {
+                GitXetRepoError::InvalidRemote(format!(
+                    "Unable to get links from {}: {e}",
+                    args.remote
+                ))
+            })?
+    };
+
+    if args.batch {
+        let mut out = stdout().lock();
+        for (input, error) in missing {
+            write_batch_line(
+                &mut out,
+                &BatchError {
+                    input: &input,
+                    error,
+                },
+            )?;
+        }
+        for file in &files {
+            write_batch_line(&mut out, file)?;
+        }
+    } else if args.json {
+        println!("{}", serde_json::to_string_pretty(&files)?);
+    } else {
+        for file in &files {
+            print!("{}", format_links(file));
+        }
+        eprintln!("The links expire in {}.", args.expires);
+    }
+    Ok(())
+}
+
+/// Lists the links to a file, one per line.
+fn format_links(file: &PresignedFile) -> String {
+    match &file.url {
+        Some(url) => format!("{}\t{url}\n", file.path),
+        None => {
+            let mut out = format!(
+                "{} ({} parts, expires {}):\n",
+                file.path,
+                file.parts.len(),
+                file.expires_at
+            );
+            for part in &file.parts {
+                out.push_str(&format!("  {}\n", part.url));
+            }
+            out
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xetblob::PresignedPart;
+
+    #[test]
+    fn test_format_links() {
+        let mut file = PresignedFile {
+            path: "data/a.bin".to_string(),
+            expires_at: "2024-01-02T00:00:00Z".to_string(),
+            url: Some("https://example.com/a".to_string()),
+            parts: Vec::new(),
+        };
+        assert_eq!(format_links(&file), "data/a.bin\thttps://example.com/a\n");
+
+        file.url = None;
+        file.parts = vec![
+            PresignedPart {
+                url: "https://example.com/0".to_string(),
+                length: 10,
+            },
+            PresignedPart {
+                url: "https://example.com/1".to_string(),
+                length: 5,
+            },
+        ];
+        assert_eq!(
+            format_links(&file),
+            "data/a.bin (2 parts, expires 2024-01-02T00:00:00Z):\n  \
+             https://example.com/0\n  \
+             https://example.com/1\n"
+        );
+    }
+}
@@ -2,6 +2,7 @@ use tracing::{info, info_span};
 use tracing_futures::Instrument;
 
 use crate::config::XetConfig;
+use crate::control::ControlServer;
 use crate::data::PointerFileTranslator;
 use crate::errors;
 use crate::git_integration::GitXetRepo;
@@ -17,6 +18,14 @@ pub async fn filter_command(config: XetConfig) -> errors::Result<()> {
     let repo = PointerFileTranslator::from_config_in_repo(&config).await?;
     let mut event_loop =
         GitStreamInterface::new_with_progress(std::io::stdin(), std::io::stdout(), repo);
+
+    let control = ControlServer::start(&config, "filter")?;
+    if let Some(control) = &control {
+        for reporter in event_loop.progress_reporters() {
+            control.state.add_progress(reporter.clone());
+        }
+    }
+
     event_loop
         .establish_git_handshake()
         .instrument(info_span!("git_handshake"))
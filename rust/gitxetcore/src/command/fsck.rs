@@ -0,0 +1,175 @@
+use clap::Args;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::config::XetConfig;
+use crate::constants::POINTER_FILE_LIMIT;
+use crate::data::signing::{verify_pointer, SignatureStatus};
+use crate::data::PointerFile;
+use crate::errors::{GitXetRepoError, Result};
+use crate::git_integration::{GitTreeListing, GitXetRepo};
+
+#[derive(Args, Debug)]
+pub struct FsckArgs {
+    /// The commit whose pointer files are checked.
+    #[clap(default_value = "HEAD")]
+    reference: String,
+
+    /// Also verify the signature of every pointer file against
+    /// signing.allowed_signers (ssh) or the GPG keyring, failing if any is
+    /// unsigned or does not verify.
+    #[clap(long)]
+    verify_signatures: bool,
+
+    /// Print the report as JSON, following `git xet schema fsck`.
+    #[clap(long)]
+    json: bool,
+}
+
+/// The pointer files checked by `git xet fsck` and the problems found, as
+/// printed with --json.
+#[derive(Serialize, Debug, Default)]
+pub struct FsckReport {
+    reference: String,
+    pointer_files: usize,
+    /// The pointer files whose hash does not parse.
+    malformed: Vec<String>,
+    unsigned: Vec<String>,
+    invalid_signatures: Vec<InvalidSignature>,
+    /// The number of pointer files signed by each signer.
+    signers: BTreeMap<String, usize>,
+}
+
+#[derive(Serialize, Debug)]
+struct InvalidSignature {
+    path: String,
+    reason: String,
+}
+
+impl FsckReport {
+    fn is_ok(&self) -> bool {
+        self.malformed.is_empty() && self.unsigned.is_empty() && self.invalid_signatures.is_empty()
+    }
+
+    fn print(&self, verify_signatures: bool) {
+        println!(
+            "Checked {} pointer files at {}.",
+            self.pointer_files, self.reference
+        );
+        for path in &self.malformed {
+            println!("  malformed hash: {path}");
+        }
+        if verify_signatures {
+            for (signer, count) in &self.signers {
+                println!("  {count} signed by {signer}");
+            }
+            for path in &self.unsigned {
+                println!("  unsigned: {path}");
+            }
+            for s in &self.invalid_signatures {
+                println!("  invalid signature: {} ({})", s.path, s.reason);
+            }
+        }
+    }
+}
+
+/// Checks the pointer files at a commit, optionally verifying their
+/// signatures.
+pub async fn fsck_command(cfg: XetConfig, args: &FsckArgs) -> Result<()> {
+    let repo = GitXetRepo::open(cfg.clone())?;
+    let entries = GitTreeListing::walk(&repo.repo_dir, Some(&args.reference), true)?;
+
+    let mut report = FsckReport {
+        reference: args.reference.clone(),
+        ..Default::default()
+    };
+
+    for entry in entries {
+        let entry = entry?;
+        if !entry.kind().is_file() || entry.size > POINTER_FILE_LIMIT as u64 {
+            continue;
+        }
+        let pointer = {
+            let blob = repo
+                .repo
+                .find_blob(git2::Oid::from_str(&entry.object_id)?)?;
+            let Ok(content) = std::str::from_utf8(blob.content()) else {
+                continue;
+            };
+            PointerFile::init_from_string(content, &entry.path.to_string())
+        };
+        if !pointer.is_valid() {
+            continue;
+        }
+        report.pointer_files += 1;
+        if pointer.hash().is_err() {
+            report.malformed.push(entry.path.to_string());
+            continue;
+        }
+
+        if args.verify_signatures {
+            match verify_pointer(&cfg.signing, &pointer).await? {
+                SignatureStatus::Valid(signer) => *report.signers.entry(signer).or_default() += 1,
+                SignatureStatus::Invalid(reason) => {
+                    report.invalid_signatures.push(InvalidSignature {
+                        path: entry.path.to_string(),
+                        reason,
+                    })
+                }
+                SignatureStatus::Unsigned => report.unsigned.push(entry.path.to_string()),
+            }
+        }
+    }
+    // The tree is walked in no particular order.
+    report.malformed.sort();
+    report.unsigned.sort();
+    report
+        .invalid_signatures
+        .sort_by(|a, b| a.path.cmp(&b.path));
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        report.print(args.verify_signatures);
+    }
+
+    if report.is_ok() {
+        Ok(())
+    } else {
+        Err(GitXetRepoError::IntegrityCheckFailed(format!(
+            "{} malformed, {} unsigned, and {} invalidly signed pointer files at {}",
+            report.malformed.len(),
+            report.unsigned.len(),
+            report.invalid_signatures.len(),
+            args.reference
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schemas::assert_matches_schema;
+
+    #[test]
+    fn test_report_schema() {
+        let mut report = FsckReport {
+            reference: "HEAD".to_string(),
+            pointer_files: 3,
+            malformed: vec!["a.bin".to_string()],
+            unsigned: vec!["b.bin".to_string()],
+            ..Default::default()
+        };
+        report.invalid_signatures.push(InvalidSignature {
+            path: "c.bin".to_string(),
+            reason: "unknown key".to_string(),
+        });
+        report.signers.insert("alice@example.com".to_string(), 1);
+        assert!(!report.is_ok());
+        assert_matches_schema("fsck", &serde_json::to_value(&report).unwrap());
+        assert_matches_schema(
+            "fsck",
+            &serde_json::to_value(FsckReport::default()).unwrap(),
+        );
+    }
+}
@@ -0,0 +1,36 @@
+use clap::Args;
+use utils::output_bytes::output_bytes;
+
+use crate::config::XetConfig;
+use crate::errors::Result;
+use crate::shared_store::gc;
+
+/// Collects the shared store set with store.path.
+///
+/// References held by repositories that no longer exist are dropped, then
+/// the xorbs that no repository on this machine references are deleted.
+///
+/// ```ignore
+/// git xet config --global store.path ~/.xet/store
+/// git xet gc
+/// ```
+#[derive(Args, Debug)]
+pub struct GcArgs {}
+
+pub async fn gc_command(cfg: XetConfig, _args: &GcArgs) -> Result<()> {
+    let Some(root) = cfg.store.path.as_ref() else {
+        println!("store.path is not set; there is no shared store to collect.");
+        return Ok(());
+    };
+    let summary = gc(root).await?;
+    println!(
+        "Dropped the references of {} removed repositories",
+        summary.repos
+    );
+    println!(
+        "Deleted {} unreferenced xorbs ({})",
+        summary.xorbs,
+        output_bytes(summary.bytes as usize)
+    );
+    Ok(())
+}
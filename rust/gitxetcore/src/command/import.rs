@@ -0,0 +1,133 @@
+use clap::Args;
+use std::path::Path;
+
+use crate::config::XetConfig;
+use crate::data::fallback::SourceRecord;
+use crate::data::PointerFileTranslator;
+use crate::errors::Result;
+use crate::git_integration::{create_commit, open_libgit2_repo};
+use crate::import::{clean_objects, ensure_not_checked_out, ImportSource, IMPORT_SOURCE_TRAILER};
+
+/// Imports objects from S3, GCS or HTTP into a new commit on a branch,
+/// without touching the working tree.
+///
+/// Objects are streamed through the clean pipeline, so only their pointer
+/// files are committed; the data is uploaded on the next push. Each source is
+/// recorded in a Xet-Import-Source trailer of the commit message.
+///
+/// ```ignore
+/// git xet import s3://bucket/raw/2024/ --branch import/raw --path data/raw
+/// git xet push origin import/raw
+/// ```
+#[derive(Args, Debug)]
+pub struct ImportArgs {
+    /// The sources to import: s3://bucket/prefix, gs://bucket/prefix, or the
+    /// http(s) URL of a single object.
+    #[clap(required = true)]
+    sources: Vec<String>,
+
+    /// The branch to commit to, created from HEAD if it doesn't exist. It
+    /// can't be the branch checked out.
+    #[clap(short, long)]
+    branch: String,
+
+    /// The directory of the repository to import into.
+    #[clap(long, default_value = "")]
+    path: String,
+
+    /// The commit message. Defaults to one listing the sources.
+    #[clap(short, long)]
+    message: Option<String>,
+}
+
+pub async fn import_command(cfg: XetConfig, args: &ImportArgs) -> Result<()> {
+    let sources = args
+        .sources
+        .iter()
+        .map(|s| ImportSource::parse(s))
+        .collect::<Result<Vec<_>>>()?;
+
+    let repo = open_libgit2_repo(cfg.repo_path_if_present.as_deref())?;
+    ensure_not_checked_out(&repo, &args.branch)?;
+
+    let http = reqwest::Client::new();
+    let translator = PointerFileTranslator::from_config_in_repo(&cfg).await?;
+
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    let record_path =
+        SourceRecord::repo_path(&format!("{}\n{}", args.sources.join("\n"), args.path));
+    let mut record = read_source_record(&repo, &args.branch, &record_path)?;
+    for (url, source) in args.sources.iter().zip(sources.iter()) {
+        let objects = source.list(&http).await?;
+        eprintln!("Importing {} object(s) from {url}", objects.len());
+        for file in clean_objects(source, &http, &translator, &args.path, objects).await? {
+            record.insert(&file.pointer, &file.url);
+            files.push((file.path, file.pointer));
+        }
+    }
+
+    if files.is_empty() {
+        eprintln!("Nothing to import.");
+        return Ok(());
+    }
+    translator.finalize_cleaning().await?;
+    let imported = files.len();
+    files.push((record_path, record.to_bytes()?));
+
+    let message = commit_message(args.message.as_deref(), &args.sources);
+    let files_ref: Vec<(&str, &[u8])> = files
+        .iter()
+        .map(|(path, pointer)| (path.as_str(), pointer.as_slice()))
+        .collect();
+    create_commit(&repo, Some(&args.branch), &message, &files_ref, None, None)?;
+
+    eprintln!("Imported {imported} file(s) into {}.", args.branch);
+    Ok(())
+}
+
+/// Reads the source record at record_path on the branch, or at HEAD if the
+/// branch doesn't exist yet, so that reimports add to it.
+pub(crate) fn read_source_record(
+    repo: &git2::Repository,
+    branch: &str,
+    record_path: &str,
+) -> Result<SourceRecord> {
+    let entry = repo
+        .find_reference(&format!("refs/heads/{branch}"))
+        .or_else(|_| repo.head())
+        .and_then(|r| r.peel_to_tree())
+        .and_then(|t| t.get_path(Path::new(record_path)));
+    match entry {
+        Ok(entry) => SourceRecord::parse(entry.to_object(repo)?.peel_to_blob()?.content()),
+        Err(_) => Ok(SourceRecord::default()),
+    }
+}
+
+/// The commit message of an import, with a trailer per source.
+pub(crate) fn commit_message(message: Option<&str>, sources: &[String]) -> String {
+    let subject = match message {
+        Some(message) => message.to_string(),
+        None => format!("Import from {}", sources.join(", ")),
+    };
+    let trailers: Vec<String> = sources
+        .iter()
+        .map(|source| format!("{IMPORT_SOURCE_TRAILER}: {source}"))
+        .collect();
+    format!("{subject}\n\n{}\n", trailers.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_message() {
+        let sources = vec!["s3://b/raw/".to_string(), "gs://c/x".to_string()];
+        assert_eq!(
+            commit_message(None, &sources),
+            "Import from s3://b/raw/, gs://c/x\n\n\
+             Xet-Import-Source: s3://b/raw/\n\
+             Xet-Import-Source: gs://c/x\n"
+        );
+    }
+}
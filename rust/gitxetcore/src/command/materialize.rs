@@ -1,18 +1,24 @@
+use crate::data::preflight::{check_disk_space, materialized_bytes};
 use crate::data::PointerFile;
 use clap::Args;
 use itertools::Itertools;
 use lazy::lazy_pathlist_config::{check_or_create_lazy_config, LazyPathListConfigFile};
-use parutils::tokio_par_for_each;
-use std::fs::File;
-use std::io::BufWriter;
+use parutils::{shutdown_token, tokio_par_for_each};
+use serde::Serialize;
+use std::io::stdout;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tracing::error;
 
-use crate::constants::{MAX_CONCURRENT_DOWNLOADS, POINTER_FILE_LIMIT};
+use super::batch::{read_batch_inputs, write_batch_line, BatchError};
+use crate::constants::{MAX_CONCURRENT_DOWNLOADS, POINTER_FILE_LIMIT, PREALLOCATE_MIN_FILE_SIZE};
 use crate::data::PointerFileTranslator;
 use crate::errors::Result;
-use crate::git_integration::{filter_files_from_index, walk_working_dir, GitXetRepo};
+use crate::git_integration::{
+    filter_files_from_index, walk_working_dir, GitTreeListing, GitXetRepo,
+};
+use crate::interrupt::check_interrupted;
+use crate::transfer_report;
 use crate::{config::XetConfig, constants::GIT_LAZY_CHECKOUT_CONFIG};
 
 #[derive(Args, Debug)]
@@ -20,7 +26,32 @@ pub struct MaterializeArgs {
     #[clap(short, long)]
     recursive: bool,
 
+    /// Also materialize the files at HEAD matching this git pathspec,
+    /// relative to the repository root, e.g. "data/imagenet/**". May be
+    /// repeated.
+    #[clap(long)]
+    pathspec: Vec<String>,
+
     paths: Vec<PathBuf>,
+
+    /// Read the paths from stdin, one per line, and print whether each file
+    /// was materialized as a line of JSON. A file failing to materialize
+    /// doesn't stop the others.
+    #[clap(long, conflicts_with = "paths")]
+    batch: bool,
+
+    /// With --batch, the paths are separated by NUL bytes.
+    #[clap(short = 'z', requires = "batch")]
+    nul: bool,
+}
+
+/// A file materialized by --batch.
+#[derive(Serialize, Debug)]
+struct BatchMaterialized<'a> {
+    input: &'a str,
+    /// False if the file was left a pointer file, e.g. as the access policy
+    /// doesn't authorize it.
+    materialized: bool,
 }
 
 pub async fn materialize_command(cfg: XetConfig, args: &MaterializeArgs) -> Result<()> {
@@ -40,9 +71,17 @@ pub async fn materialize_command(cfg: XetConfig, args: &MaterializeArgs) -> Resu
 
     let workdir_root = repo.repo_dir.clone();
 
+    let paths = if args.batch {
+        read_batch_inputs(args.nul)?
+            .into_iter()
+            .map(PathBuf::from)
+            .collect()
+    } else {
+        args.paths.clone()
+    };
+
     // now they are relative path to the working directory root
-    let path_list = args
-        .paths
+    let mut path_list = paths
         .iter()
         .map(|path| {
             let ret = walk_working_dir(&workdir_root, path, args.recursive);
@@ -55,12 +94,25 @@ pub async fn materialize_command(cfg: XetConfig, args: &MaterializeArgs) -> Resu
         .flatten()
         .collect_vec();
 
+    if !args.pathspec.is_empty() {
+        let mut listing = GitTreeListing::build(&workdir_root, None, true, true, false)?;
+        listing.retain_pathspec(&args.pathspec, Path::new(""))?;
+        path_list.extend(
+            listing
+                .files
+                .into_iter()
+                .filter(|e| e.kind().is_file())
+                .map(|e| e.path.to_path()),
+        );
+        path_list = path_list.into_iter().unique().collect();
+    }
+
     let path_list = filter_files_from_index(&path_list, repo.repo.clone())?;
 
     if path_list.is_empty() {
         eprintln!(
-            "Didn't find any checked in files under {:?}, skip materializing.",
-            &args.paths
+            "Didn't find any checked in files under {:?} or matching {:?}, skip materializing.",
+            &paths, &args.pathspec
         );
         return Ok(());
     }
@@ -78,17 +130,46 @@ pub async fn materialize_command(cfg: XetConfig, args: &MaterializeArgs) -> Resu
     drop(lazyconfig);
 
     // smudge all files
-    let absolute_path_list: Vec<_> = path_list.iter().map(|p| workdir_root.join(p)).collect();
+    let absolute_path_list: Vec<_> = path_list
+        .iter()
+        .map(|p| (p.clone(), workdir_root.join(p)))
+        .collect();
 
     let translator = Arc::new(PointerFileTranslator::from_config_in_repo(&cfg).await?);
+
+    // fail before smudging anything if the files won't fit
+    let sizes: Vec<(&Path, u64)> = absolute_path_list
+        .iter()
+        .filter_map(|(repo_path, path)| {
+            let pointer = materialized_pointer(path)?;
+            translator
+                .allows(repo_path)
+                .then(|| (path.as_path(), pointer.filesize()))
+        })
+        .collect();
+    check_disk_space(&cfg, &workdir_root, materialized_bytes(sizes))?;
+
     let translator_ref = &translator;
+    let preallocate = cfg.io.preallocate;
+    let batch = args.batch;
+    let stage = transfer_report::stage("materialize");
 
-    tokio_par_for_each(
+    let results = tokio_par_for_each(
         absolute_path_list,
         MAX_CONCURRENT_DOWNLOADS,
-        |path, _| async move {
+        |(repo_path, path), _| async move {
+            // once interrupted, the files not started are left as pointers
+            if shutdown_token().is_cancelled() {
+                return Ok(None);
+            }
             let translator = translator_ref.clone();
-            smudge_file_to_itself(&translator, &path).await
+            let result = smudge_file_to_itself(&translator, &repo_path, &path, preallocate).await;
+            let result = match result {
+                Ok(()) => Ok(materialized_pointer(&path).is_none()),
+                Err(e) if batch => Err(e.to_string()),
+                Err(e) => return Err(e),
+            };
+            Ok(Some((repo_path, result)))
         },
     )
     .await
@@ -98,41 +179,88 @@ pub async fn materialize_command(cfg: XetConfig, args: &MaterializeArgs) -> Resu
         }
         parutils::ParallelError::TaskError(e) => e,
     })?;
+    drop(stage);
+
+    if batch {
+        let mut out = stdout().lock();
+        for (repo_path, result) in results.iter().flatten() {
+            let input = repo_path.to_str().unwrap_or_default();
+            match result {
+                Ok(materialized) => write_batch_line(
+                    &mut out,
+                    &BatchMaterialized {
+                        input,
+                        materialized: *materialized,
+                    },
+                )?,
+                Err(error) => write_batch_line(
+                    &mut out,
+                    &BatchError {
+                        input,
+                        error: error.clone(),
+                    },
+                )?,
+            }
+        }
+    }
 
     // update index so materialized files don't show as "Changes not staged for commit"
     repo.run_git_checked_in_repo("add", &["-u"])?;
 
+    check_interrupted("run the command again")?;
     eprintln!("Done");
 
     Ok(())
 }
 
-/// Smudge a pointer file and overwrite itself
+/// Materializes every pointer file in the working tree.
+pub async fn materialize_all(cfg: XetConfig) -> Result<()> {
+    let args = MaterializeArgs {
+        recursive: true,
+        pathspec: Vec::new(),
+        paths: vec![cfg.repo_path()?.clone()],
+        batch: false,
+        nul: false,
+    };
+    materialize_command(cfg, &args).await
+}
+
+/// Smudge a pointer file and overwrite itself. Pointer files the access
+/// policy doesn't authorize are left in place.
 async fn smudge_file_to_itself(
     translator: &PointerFileTranslator,
+    repo_path: &Path,
     path: &Path,
+    preallocate: bool,
 ) -> anyhow::Result<()> {
-    let size = std::fs::metadata(path)?.len();
-
-    // quick check if likely a pointer file
-    if size > POINTER_FILE_LIMIT as u64 {
+    let Some(pointer_file) = materialized_pointer(path) else {
         return Ok(());
-    }
-
-    let pointer_file = PointerFile::init_from_path(path.to_str().unwrap_or_default());
-
-    // not a pointer file, leave it as it is.
-    if !pointer_file.is_valid() {
+    };
+    if !translator.allows(repo_path) {
+        eprintln!(
+            "Not authorized to materialize {repo_path:?}; leaving the pointer file in place."
+        );
         return Ok(());
     }
 
-    let file_hash = pointer_file.hash()?;
-
-    let mut writer = Box::new(BufWriter::new(File::create(path)?));
-
+    let preallocate = preallocate && pointer_file.filesize() >= PREALLOCATE_MIN_FILE_SIZE;
     translator
-        .smudge_file_from_hash(Some(path.to_owned()), &file_hash, &mut writer, None)
+        .smudge_file_from_pointer_to_path(repo_path, path, &pointer_file, preallocate)
         .await?;
 
     Ok(())
 }
+
+/// The pointer file at path, if it's one to materialize; files that aren't
+/// pointer files are left as they are.
+pub(crate) fn materialized_pointer(path: &Path) -> Option<PointerFile> {
+    let size = std::fs::metadata(path).ok()?.len();
+
+    // quick check if likely a pointer file
+    if size > POINTER_FILE_LIMIT as u64 {
+        return None;
+    }
+
+    let pointer_file = PointerFile::init_from_path(path.to_str().unwrap_or_default());
+    pointer_file.is_valid().then_some(pointer_file)
+}
@@ -3,7 +3,9 @@ use crate::constants::{GIT_NOTES_MERKLEDB_V1_REF_NAME, GIT_NOTES_MERKLEDB_V2_REF
 use crate::data::mdb::{self as mdbv2, force_sync_shard, get_mdb_version};
 use crate::data::mdbv1;
 use crate::errors::{self, GitXetRepoError};
+use crate::git_integration::content_moves::{find_content_moves, print_content_moves};
 use crate::git_integration::git_repo_salt::read_repo_salt_by_dir;
+use crate::git_integration::GitXetRepo;
 use crate::utils;
 
 use clap::{Args, Subcommand};
@@ -177,7 +179,9 @@ struct MerkleDBGitListArgs {
     notesref: String,
 }
 
-/// Prints out deduplication statistics about a particular commit.
+/// Prints out deduplication statistics about a particular commit. With
+/// MerkleDB v2, these are the files the commit renamed or copied, whose data
+/// is reused entirely.
 #[derive(Args, Debug)]
 struct MerkleDBGitStatArgs {
     /// A git commit reference to read statistics about.
@@ -380,7 +384,7 @@ pub async fn handle_merkledb_plumb_command(
                 args.similarity,
             )
             .map_err(GitXetRepoError::from),
-            ShardVersion::V2 => todo!(),
+            ShardVersion::V2 => stat_content_moves(cfg, args),
         },
         MerkleDBCommand::CASStat => match version {
             ShardVersion::Uninitialized => {
@@ -463,3 +467,33 @@ pub async fn handle_merkledb_plumb_command(
         },
     }
 }
+
+/// Prints the files the commit at args.reference renamed or copied since its
+/// first parent.
+fn stat_content_moves(cfg: XetConfig, args: &MerkleDBGitStatArgs) -> errors::Result<()> {
+    if args.change_stats || args.similarity {
+        return Err(GitXetRepoError::InvalidOperation(
+            "--change-stats and --similarity are only supported with MerkleDB v1".to_string(),
+        ));
+    }
+    let repo = GitXetRepo::open(cfg)?;
+    let new = repo
+        .pointer_files_by_path(&args.reference)?
+        .ok_or_else(|| {
+            GitXetRepoError::InvalidOperation(format!(
+                "Unable to resolve reference {}",
+                args.reference
+            ))
+        })?;
+    // An initial commit has no parent; its copies are still reported.
+    let old = repo
+        .pointer_files_by_path(&format!("{}^", args.reference))?
+        .unwrap_or_default();
+
+    let moves = find_content_moves(&old, &new);
+    if moves.is_empty() {
+        println!("No files renamed or copied.");
+    }
+    print_content_moves(&moves);
+    Ok(())
+}
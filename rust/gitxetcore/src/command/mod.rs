@@ -1,19 +1,34 @@
-use clap::{Args, Parser, Subcommand};
+use cas::fileio::set_io_backend;
+use cas_client::CAS_BANDWIDTH_LIMITER;
+use clap::{Args, CommandFactory, ErrorKind, Parser, Subcommand};
 use const_format::concatcp;
 use git_version::git_version;
 use opentelemetry::global::force_flush_tracer_provider;
 use std::path::PathBuf;
-use tracing::{debug, info, Instrument};
+use std::time::Instant;
+use tracing::{debug, info, warn, Instrument};
 
+use archive::{archive_command, ArchiveCommandShim};
+use bench::{bench_command, BenchArgs};
+use bulk_checkout::{bulk_checkout_command, BulkCheckoutArgs};
+use cache::{cache_command, CacheCommandShim};
 use cas_plumb::{handle_cas_plumb_command, CasSubCommandShim};
+use cas_proxy::{cas_proxy_command, CasProxyArgs};
+use cat::{cat_command, CatArgs};
 use checkout::{checkout_command, CheckoutArgs};
 use clone::{clone_command, CloneArgs};
+use commit_files::{commit_files_command, CommitFilesArgs};
+use completions::{completions_command, print_cli_json, CompletionsArgs};
 use config::{handle_config_command, ConfigArgs};
 use cp::{cp_command, CpArgs};
 use dematerialize::{dematerialize_command, DematerializeArgs};
 use diff::{diff_command, DiffArgs};
 use dir_summary::{dir_summary_command, DirSummaryArgs};
+use download_url::{url_command, UrlArgs};
 use filter::filter_command;
+use fsck::{fsck_command, FsckArgs};
+use gc::{gc_command, GcArgs};
+use import::{import_command, ImportArgs};
 use init::{init_command, InitArgs};
 use install::{install_command, InstallArgs};
 use lazy::{lazy_command, LazyCommandShim};
@@ -21,37 +36,66 @@ use login::{login_command, LoginArgs};
 use materialize::{materialize_command, MaterializeArgs};
 use merkledb::{handle_merkledb_plumb_command, MerkleDBSubCommandShim};
 use mount::{mount_command, mount_curdir_command, MountArgs, MountCurdirArgs};
+use p2p::{p2p_command, P2pCommandShim};
 use pointer::{pointer_command, PointerArgs};
-use push::push_command;
+use push::{push_command, PushArgs};
+use quota::{quota_command, QuotaArgs};
+use remote::{remote_command, RemoteCommandShim};
 use repo_size::{repo_size_command, RepoSizeArgs};
+use run::{run_command, RunArgs};
+use schema::{schema_command, SchemaArgs};
+use serve::{serve_command, ServeArgs};
 use smudge::{smudge_command, SmudgeArgs};
+use snapshot::{snapshot_command, SnapshotCommandShim};
 use summary::{summary_command, SummaryArgs};
+use sync::{sync_command, SyncArgs};
+use tier::{tier_command, TierArgs};
+use top::{top_command, TopArgs};
 use uninit::{uninit_command, UninitArgs};
 use uninstall::{uninstall_command, UninstallArgs};
+use verify_push::{verify_push_command, VerifyPushArgs};
 use visualization_dependencies::{
     visualization_dependencies_command, VisualizationDependenciesArgs,
 };
+use watch::{watch_command, WatchArgs};
 
 use crate::config::XetConfig;
 use crate::config::{get_sanitized_invocation_command, ConfigGitPathOption};
 use crate::constants::CURRENT_VERSION;
 use crate::data::remote_shard_interface::{GlobalDedupPolicy, SmudgeQueryPolicy};
-use crate::environment::axe::Axe;
 use crate::environment::log::{get_trace_span, initialize_tracing_subscriber};
+use crate::environment::retention::prune_if_due;
 use crate::environment::upgrade_checks::VersionCheckInfo;
+use crate::environment::usage_events::{self, UsageEvent};
 use crate::errors;
+use crate::errors::{set_error_format, ErrorFormat};
 use crate::git_integration::git_version_checks::perform_git_version_check;
 use crate::git_integration::hook_command_entry::{handle_hook_plumb_command, HookCommandShim};
-
+use crate::interrupt::install_interrupt_handler;
+use crate::transfer_report::{self, TransferReport};
+
+mod archive;
+mod batch;
+mod bench;
+mod bulk_checkout;
+mod cache;
 mod cas_plumb;
+mod cas_proxy;
+mod cat;
 mod checkout;
 mod clone;
+pub mod commit_files;
+mod completions;
 mod config;
 mod cp;
 mod dematerialize;
 mod diff;
 mod dir_summary;
+mod download_url;
 mod filter;
+mod fsck;
+mod gc;
+mod import;
 pub mod init;
 mod install;
 mod lazy;
@@ -59,14 +103,26 @@ pub mod login;
 mod materialize;
 mod merkledb;
 pub mod mount;
+mod p2p;
 mod pointer;
 mod push;
+mod quota;
+mod remote;
 mod repo_size;
+mod run;
+mod schema;
+mod serve;
 mod smudge;
+mod snapshot;
 mod summary;
+mod sync;
+mod tier;
+mod top;
 pub mod uninit;
 mod uninstall;
+mod verify_push;
 mod visualization_dependencies;
+mod watch;
 
 #[derive(Subcommand, Debug)]
 #[non_exhaustive]
@@ -74,6 +130,10 @@ pub enum Command {
     /// Hydrates all Xet objects converting Xet Pointer files to real files
     Checkout(CheckoutArgs),
 
+    /// Hydrates all Xet objects of a ref at once, planning the fetches of all
+    /// the files together instead of one file after the other.
+    BulkCheckout(BulkCheckoutArgs),
+
     /// Run the filter process.
     Filter,
 
@@ -81,8 +141,15 @@ pub enum Command {
 
     Smudge(SmudgeArgs),
 
+    /// Outputs the contents of a file at any ref, given as <ref>:<path>, without a checkout.
+    Cat(CatArgs),
+
     /// Manually push all staged cas information to a remote CAS.
-    Push,
+    Push(PushArgs),
+
+    /// Shows the remote's storage and bandwidth quota and whether the next
+    /// push would exceed it.
+    Quota(QuotaArgs),
 
     /// Plumbing commands for merkledb integration.
     Merkledb(MerkleDBSubCommandShim),
@@ -90,7 +157,8 @@ pub enum Command {
     /// Plumbing commands for cas.
     Cas(CasSubCommandShim),
 
-    /// Plumbing commands for the git integration hooks.
+    /// Install, uninstall, and check the status of the git integration hooks,
+    /// and their plumbing commands.
     Hooks(HookCommandShim),
 
     /// Clones an existing git xet repo, making sure the local configuration
@@ -123,11 +191,20 @@ pub enum Command {
     /// Mounts a repository on a local path
     Mount(MountArgs),
 
+    /// Serves the files of the repository at any ref over HTTP.
+    Serve(ServeArgs),
+
+    /// Runs a caching CAS proxy shared by the machines on a local network.
+    CasProxy(CasProxyArgs),
+
+    /// Shares downloaded xorbs with the other machines on a local network.
+    P2p(P2pCommandShim),
+
     /// Uninstall git config information.
     Uninstall(UninstallArgs),
 
-    /// Uninstall git xet hooks and components from the local repository.
-    #[clap(hide(true))]
+    /// Uninstall git xet hooks and components from the local repository; with
+    /// --restore-plain-git, convert it back to a plain git repository.
     Uninit(UninitArgs),
 
     #[clap(hide(true))]
@@ -151,6 +228,69 @@ pub enum Command {
     /// Copy files to/from a xet remote.  
     #[clap(hide(true))]
     Cp(CpArgs),
+
+    /// Measures clean, smudge, push, and fetch throughput on synthetic data
+    /// and prints a JSON report.
+    Bench(BenchArgs),
+
+    /// Checks the pointer files at a commit, optionally verifying their
+    /// signatures.
+    Fsck(FsckArgs),
+
+    /// Prints the JSON Schema of a JSON output, or lists the schemas.
+    Schema(SchemaArgs),
+
+    /// Prints the completions of git-xet for a shell.
+    Completions(CompletionsArgs),
+
+    /// Watches the working tree and serves a live summary of it to editors
+    /// over a local socket.
+    Watch(WatchArgs),
+
+    /// Prunes cached and staged data older than the configured retention.
+    Cache(CacheCommandShim),
+
+    /// Deletes the xorbs in the shared store that no repository references.
+    Gc(GcArgs),
+
+    /// Imports objects from S3, GCS or HTTP into a new commit on a branch.
+    Import(ImportArgs),
+
+    /// Mirrors an S3, GCS or HTTP source into a branch, committing only what
+    /// changed.
+    Sync(SyncArgs),
+
+    /// Commits local files to a branch and pushes it, without a checkout.
+    CommitFiles(CommitFilesArgs),
+
+    /// Runs a pipeline step, committing the outputs it changed with a
+    /// manifest of its command, inputs and outputs.
+    Run(RunArgs),
+
+    /// Creates and lists dataset snapshots: annotated tags recording the
+    /// directory summaries and total size of a commit.
+    Snapshot(SnapshotCommandShim),
+
+    /// Prints expiring download links to files in the remote repository.
+    Url(UrlArgs),
+
+    /// Checks that the remote stores every block of the files at the pushed
+    /// refs.
+    VerifyPush(VerifyPushArgs),
+
+    /// Manages the CAS remotes the repository's data is mirrored to.
+    Remote(RemoteCommandShim),
+
+    /// Moves the data no recent branch or tag references to cold storage.
+    Tier(TierArgs),
+
+    /// Shows the live transfers, cache hit rates, mount reads, pending
+    /// uploads and recent errors of the operations running in the repository.
+    Top(TopArgs),
+
+    /// Exports the files at a commit into an archive, optionally with
+    /// Reed–Solomon repair blocks, and verifies and repairs such archives.
+    Archive(ArchiveCommandShim),
 }
 
 const GIT_VERSION: &str = git_version!(
@@ -166,8 +306,26 @@ pub struct GitXetCommand {
     #[clap(flatten)]
     pub overrides: CliOverrides,
 
+    /// How errors are printed on stderr: "text", or "json" for a single
+    /// line with the error category, exit code, and message.
+    #[clap(long, global = true, default_value = "text")]
+    pub error_format: ErrorFormat,
+
+    /// Appends a JSON line reporting the bytes transferred, deduplication,
+    /// cache hits, retries and time per stage of push, fetch, checkout and
+    /// materialize to this file. In CI, where the CI environment variable is
+    /// set, they are appended to .git/xet/transfer-report.jsonl by default.
+    #[clap(long, global = true)]
+    pub report: Option<PathBuf>,
+
+    /// Prints all the commands and flags of the CLI as JSON, for tools
+    /// wrapping it, and exits.
+    #[clap(long, exclusive = true)]
+    pub dump_cli_json: bool,
+
+    /// Always set unless --dump-cli-json is.
     #[clap(subcommand)]
-    pub command: Command,
+    pub command: Option<Command>,
 }
 
 /// Overrides config settings with ones supplied on the CLI
@@ -184,6 +342,16 @@ pub struct CliOverrides {
     #[clap(long, short)]
     pub log: Option<PathBuf>,
 
+    /// Sets the level of the logs: error, warn, info, debug or trace.
+    /// Takes precedence over -v.
+    #[clap(long)]
+    pub log_level: Option<String>,
+
+    /// Sets the format of the logs: compact, or json for a JSON object per
+    /// line.
+    #[clap(long)]
+    pub log_format: Option<String>,
+
     /// Optionally override cas endpoint.
     #[clap(long, short)]
     pub cas: Option<String>,
@@ -243,14 +411,15 @@ pub struct CliOverrides {
 
 impl Command {
     pub async fn run(&self, cfg: XetConfig) -> errors::Result<()> {
-        let axe_cfg = cfg.clone();
-        let axe = Axe::command_start(&self.name(), &axe_cfg).await;
-        let ret = match self {
+        match self {
             Command::Checkout(args) => checkout_command(&cfg, args).await,
+            Command::BulkCheckout(args) => bulk_checkout_command(&cfg, args).await,
             Command::Filter => filter_command(cfg).await,
             Command::Pointer(args) => pointer_command(args),
             Command::Smudge(args) => smudge_command(&cfg, args).await,
-            Command::Push => push_command(cfg).await,
+            Command::Cat(args) => cat_command(cfg, args).await,
+            Command::Push(args) => push_command(cfg, args).await,
+            Command::Quota(args) => quota_command(cfg, args).await,
             Command::Merkledb(args) => handle_merkledb_plumb_command(cfg, args).await,
             Command::Cas(args) => handle_cas_plumb_command(&cfg, args).await,
             Command::Hooks(args) => handle_hook_plumb_command(cfg, args).await,
@@ -263,6 +432,9 @@ impl Command {
             Command::DirSummary(args) => dir_summary_command(cfg, args).await,
             Command::Diff(args) => diff_command(cfg, args).await,
             Command::Mount(args) => mount_command(&cfg, args).await,
+            Command::Serve(args) => serve_command(cfg, args).await,
+            Command::CasProxy(args) => cas_proxy_command(cfg, args).await,
+            Command::P2p(args) => p2p_command(cfg, args).await,
             Command::MountCurdir(args) => mount_curdir_command(cfg, args).await,
             Command::Uninstall(args) => uninstall_command(cfg, args).await,
             Command::Uninit(args) => uninit_command(cfg, args).await,
@@ -274,20 +446,37 @@ impl Command {
             Command::Materialize(args) => materialize_command(cfg, args).await,
             Command::Dematerialize(args) => dematerialize_command(cfg, args).await,
             Command::Cp(args) => cp_command(cfg, args).await,
-        };
-        if let Ok(mut axe) = axe {
-            axe.command_complete().await;
+            Command::Bench(args) => bench_command(cfg, args).await,
+            Command::Fsck(args) => fsck_command(cfg, args).await,
+            Command::Schema(args) => schema_command(args),
+            Command::Completions(args) => completions_command(args),
+            Command::Watch(args) => watch_command(cfg, args).await,
+            Command::Cache(args) => cache_command(cfg, args).await,
+            Command::Gc(args) => gc_command(cfg, args).await,
+            Command::Import(args) => import_command(cfg, args).await,
+            Command::Sync(args) => sync_command(cfg, args).await,
+            Command::CommitFiles(args) => commit_files_command(cfg, args).await,
+            Command::Run(args) => run_command(cfg, args).await,
+            Command::Snapshot(args) => snapshot_command(cfg, args).await,
+            Command::Url(args) => url_command(cfg, args).await,
+            Command::VerifyPush(args) => verify_push_command(cfg, args).await,
+            Command::Remote(args) => remote_command(cfg, args).await,
+            Command::Tier(args) => tier_command(cfg, args).await,
+            Command::Top(args) => top_command(cfg, args).await,
+            Command::Archive(args) => archive_command(cfg, args).await,
         }
-        ret
     }
 
     pub fn allow_version_check(&self) -> bool {
         match self {
             Command::Checkout(_) => true,
+            Command::BulkCheckout(_) => true,
             Command::Filter => true,
             Command::Pointer(_) => false,
             Command::Smudge(_) => false,
-            Command::Push => true,
+            Command::Cat(_) => false,
+            Command::Push(_) => true,
+            Command::Quota(_) => true,
             Command::Merkledb(_) => false,
             Command::Cas(_) => false,
             Command::Hooks(_) => false,
@@ -300,6 +489,9 @@ impl Command {
             Command::DirSummary(_) => false,
             Command::Diff(_) => false,
             Command::Mount(_) => true,
+            Command::Serve(_) => true,
+            Command::CasProxy(_) => true,
+            Command::P2p(_) => true,
             Command::MountCurdir(_) => true,
             Command::Uninstall(_) => false,
             Command::Uninit(_) => false,
@@ -309,16 +501,37 @@ impl Command {
             Command::Materialize(_) => true,
             Command::Dematerialize(_) => true,
             Command::Cp(_) => true,
+            Command::Bench(_) => false,
+            Command::Fsck(_) => true,
+            Command::Schema(_) => false,
+            Command::Completions(_) => false,
+            Command::Watch(_) => false,
+            Command::Cache(_) => false,
+            Command::Gc(_) => false,
+            Command::Import(_) => true,
+            Command::Sync(_) => false,
+            Command::CommitFiles(_) => true,
+            Command::Run(_) => false,
+            Command::Snapshot(_) => false,
+            Command::Url(_) => true,
+            Command::VerifyPush(_) => true,
+            Command::Remote(_) => false,
+            Command::Tier(_) => false,
+            Command::Top(_) => false,
+            Command::Archive(_) => false,
         }
     }
 
     pub fn name(&self) -> String {
         match self {
             Command::Checkout(_) => "checkout".to_string(),
+            Command::BulkCheckout(_) => "bulk-checkout".to_string(),
             Command::Filter => "filter".to_string(),
             Command::Pointer(_) => "pointer".to_string(),
             Command::Smudge(_) => "smudge".to_string(),
-            Command::Push => "push".to_string(),
+            Command::Cat(_) => "cat".to_string(),
+            Command::Push(_) => "push".to_string(),
+            Command::Quota(_) => "quota".to_string(),
             Command::Merkledb(args) => format!("merkledb.{}", args.subcommand_name()),
             Command::Cas(args) => format!("cas.{}", args.subcommand_name()),
             Command::Hooks(args) => format!("hooks.{}", args.subcommand_name()),
@@ -331,6 +544,9 @@ impl Command {
             Command::DirSummary(_) => "dir-summary".to_string(),
             Command::Diff(_) => "diff".to_string(),
             Command::Mount(_) => "mount".to_string(),
+            Command::Serve(_) => "serve".to_string(),
+            Command::CasProxy(_) => "cas-proxy".to_string(),
+            Command::P2p(args) => format!("p2p.{}", args.subcommand_name()),
             Command::MountCurdir(_) => "mount-curdir".to_string(),
             Command::Uninstall(_) => "uninstall".to_string(),
             Command::Uninit(_) => "uninit".to_string(),
@@ -340,10 +556,61 @@ impl Command {
             Command::Materialize(_) => "materialize".to_string(),
             Command::Dematerialize(_) => "dematerialize".to_string(),
             Command::Cp(_) => "cp".to_string(),
+            Command::Bench(_) => "bench".to_string(),
+            Command::Fsck(_) => "fsck".to_string(),
+            Command::Schema(_) => "schema".to_string(),
+            Command::Completions(_) => "completions".to_string(),
+            Command::Watch(_) => "watch".to_string(),
+            Command::Cache(args) => format!("cache.{}", args.subcommand_name()),
+            Command::Gc(_) => "gc".to_string(),
+            Command::Import(_) => "import".to_string(),
+            Command::Sync(_) => "sync".to_string(),
+            Command::CommitFiles(_) => "commit-files".to_string(),
+            Command::Run(_) => "run".to_string(),
+            Command::Snapshot(args) => format!("snapshot.{}", args.subcommand_name()),
+            Command::Url(_) => "url".to_string(),
+            Command::VerifyPush(_) => "verify-push".to_string(),
+            Command::Remote(args) => format!("remote.{}", args.subcommand_name()),
+            Command::Tier(_) => "tier".to_string(),
+            Command::Top(_) => "top".to_string(),
+            Command::Archive(args) => format!("archive.{}", args.subcommand_name()),
         }
     }
     pub fn long_running(&self) -> bool {
-        matches!(self, Command::Filter)
+        matches!(self, Command::Filter | Command::Watch(_))
+    }
+
+    /// Whether the command transfers data, and writes a transfer report; see
+    /// [crate::transfer_report]. Fetches transfer data through the filter
+    /// and hooks git runs.
+    pub fn reports_transfers(&self) -> bool {
+        matches!(
+            self,
+            Command::Checkout(_)
+                | Command::BulkCheckout(_)
+                | Command::Filter
+                | Command::Smudge(_)
+                | Command::Push(_)
+                | Command::Hooks(_)
+                | Command::Clone(_)
+                | Command::Materialize(_)
+                | Command::CommitFiles(_)
+        )
+    }
+
+    /// Whether the command stops cleanly, leaving its work resumable, when
+    /// interrupted; see [crate::interrupt].
+    pub fn cancellable(&self) -> bool {
+        matches!(
+            self,
+            Command::Checkout(_)
+                | Command::BulkCheckout(_)
+                | Command::Push(_)
+                | Command::Hooks(_)
+                | Command::Materialize(_)
+                | Command::Dematerialize(_)
+                | Command::VerifyPush(_)
+        )
     }
 }
 
@@ -352,6 +619,7 @@ impl Command {
 pub struct XetApp {
     command: Command,
     config: XetConfig,
+    report: Option<PathBuf>,
 }
 
 impl XetApp {
@@ -362,28 +630,55 @@ impl XetApp {
     /// * extracting the config from the environment and CLI args,
     /// * starting up logging/tracing
     pub fn init() -> errors::Result<XetApp> {
+        let mut cli = GitXetCommand::parse();
+        set_error_format(cli.error_format);
+
+        // --dump-cli-json stands in for a command, and exits like --version
+        if cli.dump_cli_json {
+            print_cli_json()?;
+            std::process::exit(0);
+        }
+        let Some(command) = cli.command.take() else {
+            GitXetCommand::command()
+                .error(ErrorKind::MissingSubcommand, "A subcommand is required")
+                .exit();
+        };
+
         // Make sure the version of git we're using is in fact correct.
         perform_git_version_check()?;
 
-        let mut cli = GitXetCommand::parse();
-
         // Disable the version check here if we need to.
-        if !cli.command.allow_version_check() {
+        if !command.allow_version_check() {
             cli.overrides.disable_version_check = true;
         }
         let cli = cli;
 
         // We don't validate the configuration for the `config` command
         // since, if the config is invalid, we want to allow fixing it.
-        let cfg = match &cli.command {
-            Command::Config(_) => XetConfig::empty(),
+        // Completions are generated anywhere, in or out of a repository.
+        let cfg = match &command {
+            Command::Config(_) | Command::Completions(_) => XetConfig::empty(),
             _ => XetConfig::new(
                 None,
                 Some(cli.overrides),
                 ConfigGitPathOption::CurdirDiscover,
             )?,
         };
-        initialize_tracing_subscriber(&cfg)?;
+        initialize_tracing_subscriber(&cfg, &command.name())?;
+
+        if cfg.control.bandwidth_limit.is_some() {
+            CAS_BANDWIDTH_LIMITER.set_limit(cfg.control.bandwidth_limit);
+        }
+        set_io_backend(cfg.io.backend);
+
+        let report = if command.reports_transfers() {
+            transfer_report::report_path(cli.report.as_deref(), &cfg)
+        } else {
+            None
+        };
+        if report.is_some() {
+            transfer_report::enable();
+        }
 
         // Log the command used to invoke this process.
         info!(
@@ -392,8 +687,9 @@ impl XetApp {
         );
 
         Ok(XetApp {
-            command: cli.command,
+            command,
             config: cfg,
+            report,
         })
     }
 
@@ -414,6 +710,18 @@ impl XetApp {
             )));
         }
 
+        // The cache prune command prunes in the foreground.
+        let prune_handle = match &self.command {
+            Command::Cache(_) => None,
+            _ => Some(tokio::spawn(prune_if_due(self.config.clone()))),
+        };
+
+        if self.command.cancellable() {
+            install_interrupt_handler();
+        }
+
+        let started_at = chrono::Utc::now();
+        let start = Instant::now();
         let span = get_trace_span(&self.command);
         let ret = if self.command.long_running() {
             self.command.run(self.config.clone()).await
@@ -421,6 +729,23 @@ impl XetApp {
             self.command.run(self.config.clone()).instrument(span).await
         };
 
+        if let Some(path) = &self.report {
+            let report = TransferReport::collect(
+                &self.command.name(),
+                ret.is_ok(),
+                started_at,
+                start.elapsed(),
+            );
+            if !report.is_empty() {
+                if let Err(e) = report.append_to(path) {
+                    warn!("Unable to write the transfer report to {path:?}: {e:?}");
+                }
+            }
+        }
+
+        let event = UsageEvent::collect(&self.command.name(), start.elapsed(), ret.as_ref().err());
+        usage_events::record(&self.config, &event).await;
+
         if let Some(jh) = version_check_handle {
             if let Ok(Some(mut vci)) = jh.await.map_err(|e| {
                 info!("Error occurred on joining of version check: {e:?}.");
@@ -430,6 +755,10 @@ impl XetApp {
             }
         }
 
+        if let Some(jh) = prune_handle {
+            let _ = jh.await;
+        }
+
         ret
     }
 }
@@ -1,7 +1,10 @@
 use crate::config::XetConfig;
 use crate::errors;
 use crate::git_integration::*;
-use crate::xetmnt::{check_for_mount_program, perform_mount_and_wait_for_ctrlc};
+use crate::xetmnt::webdav::{
+    check_for_webdav_mount_program, perform_webdav_mount_and_wait_for_ctrlc,
+};
+use crate::xetmnt::{check_for_mount_program, perform_mount_and_wait_for_ctrlc, MountProtocol};
 use clap::Args;
 use mdb_shard::shard_version::ShardVersion;
 use std::fmt::Debug;
@@ -67,6 +70,11 @@ pub struct MountArgs {
     /// If "localhost", then an unused local IP address will be used for mounting.
     pub ip: String,
 
+    /// The protocol to serve the mount over: nfs, or webdav for systems without
+    /// an NFS client. WebDAV mounts are read-only.
+    #[clap(long, default_value = "nfs")]
+    pub protocol: MountProtocol,
+
     /// The local path to clone the temporary repo directory into
     pub clonepath: Option<PathBuf>,
 
@@ -123,6 +131,10 @@ pub struct MountCurdirArgs {
     /// If "localhost", then an unused local IP address will be used for mounting.
     pub ip: String,
 
+    /// The protocol to serve the mount over: nfs or webdav.
+    #[clap(long, default_value = "nfs")]
+    pub protocol: MountProtocol,
+
     /// VERY Experimental writable mount feature.
     #[clap(short, long)]
     pub writable: bool,
@@ -161,16 +173,30 @@ pub async fn mount_command(cfg: &XetConfig, args: &MountArgs) -> errors::Result<
 
     let start_time = std::time::SystemTime::now();
 
-    if cfg!(windows) && is_windows_home_edition().unwrap_or(false) {
-        return Err(errors::GitXetRepoError::Other(
-            "Mount is not supported on Windows Home edition.".into(),
-        ));
-    }
+    if args.protocol == MountProtocol::WebDav {
+        if args.writable {
+            return Err(errors::GitXetRepoError::InvalidOperation(
+                "WebDAV mounts are read-only and cannot be used with --writable.".into(),
+            ));
+        }
+        if !check_for_webdav_mount_program() {
+            return Err(errors::GitXetRepoError::Other(
+                "Unable to locate suitable WebDAV mount command".into(),
+            ));
+        }
+    } else {
+        if cfg!(windows) && is_windows_home_edition().unwrap_or(false) {
+            return Err(errors::GitXetRepoError::Other(
+                "Mount is not supported on Windows Home edition. Use --protocol webdav instead."
+                    .into(),
+            ));
+        }
 
-    if !check_for_mount_program() {
-        return Err(errors::GitXetRepoError::Other(
-            "Unable to locate suitable mount command".into(),
-        ));
+        if !check_for_mount_program() {
+            return Err(errors::GitXetRepoError::Other(
+                "Unable to locate suitable mount command".into(),
+            ));
+        }
     }
 
     let path = {
@@ -367,6 +393,9 @@ If you use a git UI, point it to the raw path.
     if args.writable {
         command.arg("--writable");
     }
+    if args.protocol == MountProtocol::WebDav {
+        command.arg("--protocol").arg("webdav");
+    }
     command.arg("--reference");
     if let Some(br) = branch {
         command.arg(br);
@@ -473,26 +502,49 @@ pub async fn mount_curdir_command(cfg: XetConfig, args: &MountCurdirArgs) -> err
     if gitrepo.mdb_version == ShardVersion::V1 {
         gitrepo.sync_notes_to_dbs().await?;
     }
-    perform_mount_and_wait_for_ctrlc(
-        cfg,
-        &PathBuf::from("."),
-        &args.path,
-        &args.reference,
-        args.autostop,
-        args.prefetch,
-        args.writable,
-        args.ip.clone(),
-        || {
-            if let Some(_pid) = args.signal {
-                #[cfg(unix)]
-                // TODO: this should be implemented on windows as well, but the mechanisms for doing it are different.
-                unsafe {
-                    libc::kill(_pid, libc::SIGUSR1);
-                }
+    let mount_ready = || {
+        if let Some(_pid) = args.signal {
+            #[cfg(unix)]
+            // TODO: this should be implemented on windows as well, but the mechanisms for doing it are different.
+            unsafe {
+                libc::kill(_pid, libc::SIGUSR1);
+            }
+        }
+    };
+    let result = match args.protocol {
+        MountProtocol::Nfs => {
+            perform_mount_and_wait_for_ctrlc(
+                cfg,
+                &PathBuf::from("."),
+                &args.path,
+                &args.reference,
+                args.autostop,
+                args.prefetch,
+                args.writable,
+                args.ip.clone(),
+                mount_ready,
+                args.watch.map(|dur| dur.into()),
+            )
+            .await
+        }
+        MountProtocol::WebDav => {
+            if args.writable {
+                return Err(errors::GitXetRepoError::InvalidOperation(
+                    "WebDAV mounts are read-only and cannot be used with --writable.".into(),
+                ));
             }
-        },
-        args.watch.map(|dur| dur.into()),
-    )
-    .await
-    .map_err(|e| errors::GitXetRepoError::Other(format!("{e:?}")))
+            perform_webdav_mount_and_wait_for_ctrlc(
+                cfg,
+                &PathBuf::from("."),
+                &args.path,
+                &args.reference,
+                args.prefetch,
+                args.ip.clone(),
+                mount_ready,
+                args.watch.map(|dur| dur.into()),
+            )
+            .await
+        }
+    };
+    result.map_err(|e| errors::GitXetRepoError::Other(format!("{e:?}")))
 }
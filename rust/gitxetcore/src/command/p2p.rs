@@ -0,0 +1,132 @@
+use std::net::SocketAddr;
+
+use clap::{Args, Subcommand};
+
+use super::cas_proxy::{load_or_generate_token, TOKEN_FILE_NAME};
+use crate::config::XetConfig;
+use crate::errors::{GitXetRepoError, Result};
+use crate::p2p::{run_coordinator, run_peer_server, store_path, PeerStore};
+
+#[non_exhaustive]
+#[derive(Subcommand, Debug)]
+enum P2pCommand {
+    Coordinator(P2pCoordinatorArgs),
+    Serve(P2pServeArgs),
+}
+
+/// Runs the coordinator that tracks which machines hold which xorbs. Run
+/// one per network, e.g. on the head node of a cluster, and set
+/// p2p.coordinator on every machine to its URL and p2p.token to its token.
+#[derive(Args, Debug)]
+struct P2pCoordinatorArgs {
+    /// The address to listen on. Use 0.0.0.0:4050 to serve other machines.
+    #[clap(long, default_value = "127.0.0.1:4050")]
+    bind: SocketAddr,
+
+    /// The token peers must present. Defaults to p2p.token, or else to a
+    /// token generated on the first run and kept in the p2p store directory.
+    #[clap(long)]
+    token: Option<String>,
+}
+
+/// Serves the xorbs this machine has downloaded to its peers, announcing
+/// them to the coordinator as p2p.advertise.
+///
+/// Xorbs are only served to peers presenting p2p.token, which must be set.
+/// Anyone holding the token can read the xorbs downloaded by the user
+/// running it, so only share it within a trusted network.
+#[derive(Args, Debug)]
+struct P2pServeArgs {
+    /// The address to listen on. Defaults to localhost on the port of
+    /// p2p.advertise; use e.g. 0.0.0.0:4051 to serve other machines.
+    #[clap(long)]
+    bind: Option<SocketAddr>,
+}
+
+/// Shares downloaded xorbs between the machines of a local network.
+///
+/// With p2p.coordinator set, xorbs are fetched from peers that hold them
+/// before going to the CAS, and verified against their hash on receipt.
+///
+/// ```ignore
+/// # on the head node
+/// git xet p2p coordinator --bind 0.0.0.0:4050
+///
+/// # on each worker, with the token the coordinator uses
+/// git xet config --global p2p.coordinator http://head-node:4050
+/// git xet config --global p2p.token <token>
+/// git xet config --global p2p.advertise worker-3:4051
+/// git xet p2p serve --bind 0.0.0.0:4051 &
+/// ```
+// THIS "SHIM" STRUCT IS MANDATORY
+#[derive(Args, Debug)]
+pub struct P2pCommandShim {
+    #[clap(subcommand)]
+    subcommand: P2pCommand,
+}
+
+impl P2pCommandShim {
+    pub fn subcommand_name(&self) -> String {
+        match self.subcommand {
+            P2pCommand::Coordinator(_) => "coordinator".to_string(),
+            P2pCommand::Serve(_) => "serve".to_string(),
+        }
+    }
+}
+
+pub async fn p2p_command(cfg: XetConfig, command: &P2pCommandShim) -> Result<()> {
+    match &command.subcommand {
+        P2pCommand::Coordinator(args) => p2p_coordinator_command(&cfg, args).await,
+        P2pCommand::Serve(args) => p2p_serve_command(&cfg, args).await,
+    }
+}
+
+async fn p2p_coordinator_command(cfg: &XetConfig, args: &P2pCoordinatorArgs) -> Result<()> {
+    let token = match args.token.as_ref().or(cfg.p2p.token.as_ref()) {
+        Some(token) => token.clone(),
+        None => {
+            let dir = store_path(cfg);
+            std::fs::create_dir_all(&dir)?;
+            let token = load_or_generate_token(&dir)?;
+            eprintln!(
+                "Set p2p.token on every peer to the token kept in {:?}",
+                dir.join(TOKEN_FILE_NAME)
+            );
+            token
+        }
+    };
+    run_coordinator(args.bind, token).await
+}
+
+async fn p2p_serve_command(cfg: &XetConfig, args: &P2pServeArgs) -> Result<()> {
+    let (Some(coordinator), Some(advertise)) = (&cfg.p2p.coordinator, &cfg.p2p.advertise) else {
+        return Err(GitXetRepoError::InvalidOperation(
+            "Set p2p.coordinator and p2p.advertise to serve xorbs to peers".to_owned(),
+        ));
+    };
+    let Some(token) = &cfg.p2p.token else {
+        return Err(GitXetRepoError::InvalidOperation(
+            "Set p2p.token to the coordinator's token to serve xorbs to peers".to_owned(),
+        ));
+    };
+    let bind = match args.bind {
+        Some(bind) => bind,
+        None => {
+            // The advertised address is validated to end in a port.
+            let port = advertise
+                .rsplit_once(':')
+                .and_then(|(_, port)| port.parse::<u16>().ok())
+                .unwrap_or_default();
+            SocketAddr::from(([127, 0, 0, 1], port))
+        }
+    };
+    let store = PeerStore::new(&store_path(cfg), cfg.p2p.store_size)?;
+    run_peer_server(
+        store,
+        bind,
+        coordinator.clone(),
+        advertise.clone(),
+        token.clone(),
+    )
+    .await
+}
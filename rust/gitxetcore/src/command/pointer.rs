@@ -1,13 +1,15 @@
 use std::fs::File;
-use std::io::{stdin, BufReader, Read};
+use std::io::{stdin, stdout, BufReader, Read};
 use std::path::PathBuf;
 
 use clap::Args;
+use serde::Serialize;
 
 use crate::data::PointerFile;
 use merkledb::prelude_v2::*;
 use merkledb::{chunk_target_default, MerkleMemDB};
 
+use super::batch::{batch_inputs, write_batch_line, BatchError};
 use crate::errors;
 use crate::errors::GitXetRepoError::FileNotFound;
 
@@ -21,9 +23,33 @@ use crate::errors::GitXetRepoError::FileNotFound;
 /// # input and output should be the same file
 /// git xet pointer -f input | git xet --cas smudge > output
 /// ```
+///
+/// With --batch, the filenames are read from stdin and the pointer of each
+/// printed as a line of JSON:
+///
+/// ```ignore
+/// find data -type f -print0 | git xet pointer --batch -z
+/// ```
 pub struct PointerArgs {
     #[clap(long, short)]
     filename: Option<PathBuf>,
+
+    /// Read filenames from stdin, one per line, and print the hash and size
+    /// of each as a line of JSON.
+    #[clap(long, conflicts_with = "filename")]
+    batch: bool,
+
+    /// With --batch, the filenames are separated by NUL bytes.
+    #[clap(short = 'z', requires = "batch")]
+    nul: bool,
+}
+
+/// The pointer of a file, as printed by --batch.
+#[derive(Serialize, Debug)]
+struct BatchPointer<'a> {
+    input: &'a str,
+    hash: &'a str,
+    filesize: u64,
 }
 
 /// simply synchronously takes a file and chunks it,
@@ -47,6 +73,10 @@ pub fn file_to_pointer(filename_str: &str, reader: &mut impl Read) -> anyhow::Re
 /// If the filename is provided, we read the file.
 /// Otherwise we read from stdin
 pub fn pointer_command(args: &PointerArgs) -> errors::Result<()> {
+    if args.batch {
+        return pointer_batch(args.nul);
+    }
+
     // create the pointer from either fileanme or stdin
     let pointer = match &args.filename {
         Some(filename) => {
@@ -65,3 +95,32 @@ pub fn pointer_command(args: &PointerArgs) -> errors::Result<()> {
     println!("{}", pointer.to_string());
     Ok(())
 }
+
+/// Prints the pointer of each filename read from stdin.
+fn pointer_batch(nul: bool) -> errors::Result<()> {
+    let mut out = stdout().lock();
+    for filename in batch_inputs(stdin().lock(), nul) {
+        let filename = filename?;
+        let pointer = File::open(&filename)
+            .map_err(anyhow::Error::from)
+            .and_then(|f| file_to_pointer(&filename, &mut BufReader::new(f)));
+        match pointer {
+            Ok(pointer) => write_batch_line(
+                &mut out,
+                &BatchPointer {
+                    input: &filename,
+                    hash: pointer.hash_string(),
+                    filesize: pointer.filesize(),
+                },
+            )?,
+            Err(e) => write_batch_line(
+                &mut out,
+                &BatchError {
+                    input: &filename,
+                    error: e.to_string(),
+                },
+            )?,
+        }
+    }
+    Ok(())
+}
@@ -1,7 +1,139 @@
+use std::time::Duration;
+
+use clap::Args;
+use utils::output_bytes::output_bytes;
+
 use crate::config::XetConfig;
+use crate::data::PendingUpload;
 use crate::errors;
-use crate::git_integration::GitXetRepo;
+use crate::git_integration::content_moves::print_content_moves;
+use crate::git_integration::{submodule_path, GitXetRepo};
+
+/// What the branch is pushed to, which renames and copies are reported
+/// against.
+const UPSTREAM_REF: &str = "@{upstream}";
+
+#[derive(Args, Debug)]
+pub struct PushArgs {
+    /// Report which files would be pushed, how many bytes would be uploaded
+    /// after deduplication, and how long it would take, without uploading
+    /// anything or contacting the CAS.
+    ///
+    /// Use this rather than `git push --dry-run`: git still runs the pre-push
+    /// hook on a dry run, and the hook can't tell, so it uploads the data.
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// The upload bandwidth in MB/s used to estimate the upload time of a
+    /// dry run.
+    #[clap(long, default_value = "10")]
+    pub upload_rate: f64,
+
+    /// Also push the data of the checked out submodules that are Xet
+    /// repositories, each to its own remote.
+    #[clap(long)]
+    pub recurse_submodules: bool,
+}
+
+pub async fn push_command(cfg: XetConfig, args: &PushArgs) -> errors::Result<()> {
+    let repo = GitXetRepo::open(cfg)?;
+    let mut repos = vec![(String::new(), repo)];
+
+    while let Some((path, repo)) = repos.pop() {
+        if !path.is_empty() {
+            println!();
+            println!("Submodule {path}:");
+        }
+        if args.dry_run {
+            print_push_dry_run(&repo, args.upload_rate)?;
+        } else {
+            repo.record_pending_dedup()?;
+            repo.upload_all_staged().await?;
+            print_push_content_moves(&repo)?;
+        }
+
+        if args.recurse_submodules {
+            for (sub_path, sub_repo) in repo.xet_submodules()? {
+                repos.push((submodule_path(&path, &sub_path), sub_repo));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Prints what a push would upload. Everything is read from the staging and
+/// session directories, so nothing is sent over the network.
+fn print_push_dry_run(repo: &GitXetRepo, upload_rate: f64) -> errors::Result<()> {
+    let pending = PendingUpload::load(&repo.merkledb_v2_session_dir, &repo.cas_staging_path)?;
+    let paths = repo.pointer_files_at_ref("HEAD")?;
+
+    println!("Dry run: nothing will be uploaded.");
+    println!();
+    println!(
+        "Files cleaned since the last push: {} ({})",
+        pending.files.len(),
+        output_bytes(pending.file_bytes() as usize)
+    );
+    for (hash, size) in pending.files.iter() {
+        let path = paths
+            .get(hash)
+            .map(|p| p.as_str())
+            .unwrap_or("<not committed>");
+        println!("  {path}  {}  {hash}", output_bytes(*size as usize));
+    }
+    println!();
+    if print_push_content_moves(repo)? {
+        println!();
+    }
+    println!(
+        "New data to upload: {} blocks, {}",
+        pending.num_xorbs,
+        output_bytes(pending.xorb_bytes as usize)
+    );
+    println!(
+        "Shard metadata to upload: {} shards, {}",
+        pending.num_shards,
+        output_bytes(pending.shard_bytes as usize)
+    );
+    println!(
+        "Estimated upload time at {upload_rate} MB/s: {}",
+        humantime::format_duration(estimate_upload_time(pending.upload_bytes(), upload_rate))
+    );
+    Ok(())
+}
+
+/// Prints the files renamed or copied since the upstream of the branch, as
+/// their data is already there and is not uploaded again. Returns whether
+/// there were any.
+fn print_push_content_moves(repo: &GitXetRepo) -> errors::Result<bool> {
+    let moves = repo
+        .content_moves(UPSTREAM_REF, "HEAD")?
+        .unwrap_or_default();
+    print_content_moves(&moves);
+    Ok(!moves.is_empty())
+}
+
+fn estimate_upload_time(bytes: u64, upload_rate: f64) -> Duration {
+    if upload_rate <= 0.0 {
+        return Duration::ZERO;
+    }
+    // Whole seconds read better than the nanosecond precision humantime
+    // would otherwise print.
+    Duration::from_secs((bytes as f64 / (upload_rate * 1_000_000.0)).ceil() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-pub async fn push_command(cfg: XetConfig) -> errors::Result<()> {
-    GitXetRepo::open(cfg)?.upload_all_staged().await
+    #[test]
+    fn test_estimate_upload_time() {
+        assert_eq!(
+            estimate_upload_time(300_000_000_000, 10.0),
+            Duration::from_secs(30_000)
+        );
+        assert_eq!(estimate_upload_time(1, 10.0), Duration::from_secs(1));
+        assert_eq!(estimate_upload_time(0, 10.0), Duration::ZERO);
+        assert_eq!(estimate_upload_time(1000, 0.0), Duration::ZERO);
+    }
 }
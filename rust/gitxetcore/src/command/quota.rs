@@ -0,0 +1,54 @@
+use clap::Args;
+use utils::output_bytes::output_bytes;
+
+use crate::config::XetConfig;
+use crate::data::PendingUpload;
+use crate::errors::{GitXetRepoError, Result};
+use crate::git_integration::git_quota::{quota_violations, resolve_remote_url};
+use crate::git_integration::GitXetRepo;
+use crate::xetblob::get_repo_quota;
+
+/// Shows the storage and bandwidth quota of the remote repository, and
+/// whether pushing the data waiting to be uploaded would exceed it.
+///
+/// The pre-push hook performs the same check; set quota.check to "error" to
+/// refuse pushes over the quota, or to "off" to skip the check.
+#[derive(Args, Debug)]
+pub struct QuotaArgs {
+    /// The remote to query.
+    #[clap(long, default_value = "origin")]
+    pub remote: String,
+}
+
+pub async fn quota_command(cfg: XetConfig, args: &QuotaArgs) -> Result<()> {
+    let repo = GitXetRepo::open(cfg.clone())?;
+    let remote_url = resolve_remote_url(&repo.repo, &args.remote);
+    let quota = get_repo_quota(&cfg, &remote_url).await.map_err(|e| {
+        GitXetRepoError::InvalidRemote(format!("Unable to query the quota of {}: {e}", args.remote))
+    })?;
+    let pending = PendingUpload::load(&repo.merkledb_v2_session_dir, &repo.cas_staging_path)?;
+
+    let limit = |limit: Option<u64>| {
+        limit
+            .map(|l| output_bytes(l as usize))
+            .unwrap_or_else(|| "unlimited".to_string())
+    };
+    println!(
+        "Storage:   {} used of {}",
+        output_bytes(quota.storage_used as usize),
+        limit(quota.storage_limit)
+    );
+    println!(
+        "Bandwidth: {} used of {}",
+        output_bytes(quota.bandwidth_used as usize),
+        limit(quota.bandwidth_limit)
+    );
+    println!(
+        "Pending upload: {}",
+        output_bytes(pending.upload_bytes() as usize)
+    );
+    for violation in quota_violations(&quota, pending.upload_bytes()) {
+        println!("Warning: {violation}.");
+    }
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use clap::{Args, Subcommand};
+use std::path::Path;
+use xet_config::{Cfg, Mirror};
+
+use crate::config::{get_local_config, CasRemote, MirrorSettings, XetConfig, PRIMARY_CAS_REMOTE};
+use crate::errors::{GitXetRepoError, Result};
+
+#[non_exhaustive]
+#[derive(Subcommand, Debug)]
+enum RemoteCommand {
+    AddCas(AddCasArgs),
+    RemoveCas(RemoveCasArgs),
+    ListCas(ListCasArgs),
+}
+
+/// Adds a CAS remote the repository's data is replicated to.
+///
+/// Blocks are written to every CAS remote before a push completes, or with
+/// mirror.replication set to async, to cas.server and then copied to the
+/// others in the background.
+#[derive(Args, Debug)]
+struct AddCasArgs {
+    /// The name of the remote.
+    name: String,
+
+    /// The endpoint of the CAS, e.g. https://cas.internal:5000, or
+    /// local:///path for a CAS on a mounted filesystem.
+    endpoint: String,
+
+    /// Read blocks from this remote first, falling back to the others when
+    /// it doesn't have them.
+    #[clap(long)]
+    fetch: bool,
+}
+
+/// Removes a CAS remote. Blocks already copied to it are left there.
+#[derive(Args, Debug)]
+struct RemoveCasArgs {
+    /// The name of the remote.
+    name: String,
+}
+
+/// Lists the CAS remotes, in the order blocks are read from them.
+#[derive(Args, Debug)]
+struct ListCasArgs {}
+
+/// Manages the CAS remotes the repository's data is mirrored to, besides
+/// cas.server.
+///
+/// ```ignore
+/// git xet remote add-cas onprem https://cas.internal:5000 --fetch
+/// git xet config mirror.replication async
+/// git xet remote list-cas
+/// ```
+// THIS "SHIM" STRUCT IS MANDATORY
+#[derive(Args, Debug)]
+pub struct RemoteCommandShim {
+    #[clap(subcommand)]
+    subcommand: RemoteCommand,
+}
+
+impl RemoteCommandShim {
+    pub fn subcommand_name(&self) -> String {
+        match self.subcommand {
+            RemoteCommand::AddCas(_) => "add-cas".to_string(),
+            RemoteCommand::RemoveCas(_) => "remove-cas".to_string(),
+            RemoteCommand::ListCas(_) => "list-cas".to_string(),
+        }
+    }
+}
+
+pub async fn remote_command(cfg: XetConfig, command: &RemoteCommandShim) -> Result<()> {
+    match &command.subcommand {
+        RemoteCommand::AddCas(args) => update_mirror(|mirror| {
+            let remote = CasRemote::parse(&format!("{}={}", args.name, args.endpoint))?;
+            mirror
+                .remotes
+                .get_or_insert_with(Vec::new)
+                .push(remote.to_string());
+            if args.fetch {
+                mirror.fetch = Some(remote.name);
+            }
+            Ok(())
+        }),
+        RemoteCommand::RemoveCas(args) => update_mirror(|mirror| {
+            let remotes = mirror.remotes.get_or_insert_with(Vec::new);
+            let before = remotes.len();
+            remotes.retain(|r| r.split_once('=').map(|(name, _)| name) != Some(args.name.as_str()));
+            if remotes.len() == before {
+                return Err(GitXetRepoError::InvalidOperation(format!(
+                    "There is no CAS remote {} in this repository's config",
+                    args.name
+                )));
+            }
+            if mirror.fetch.as_deref() == Some(args.name.as_str()) {
+                mirror.fetch = None;
+            }
+            Ok(())
+        }),
+        RemoteCommand::ListCas(_) => {
+            list_cas_remotes(&cfg);
+            Ok(())
+        }
+    }
+}
+
+fn list_cas_remotes(cfg: &XetConfig) {
+    let fetch = cfg.mirror.fetch.as_deref().unwrap_or(PRIMARY_CAS_REMOTE);
+    let remotes = std::iter::once((PRIMARY_CAS_REMOTE, cfg.cas.endpoint.as_str())).chain(
+        cfg.mirror
+            .remotes
+            .iter()
+            .map(|r| (r.name.as_str(), r.endpoint.as_str())),
+    );
+    let (first, rest): (Vec<_>, Vec<_>) = remotes.partition(|(name, _)| *name == fetch);
+    for (name, endpoint) in first.into_iter().chain(rest) {
+        println!("{name}\t{endpoint}");
+    }
+    if cfg.mirror.enabled() {
+        println!("replication: {:?}", cfg.mirror.replication);
+    }
+}
+
+/// Applies update to the mirror section of the repository's config file,
+/// which is written back only if the result is valid.
+fn update_mirror(update: impl FnOnce(&mut Mirror) -> Result<()>) -> Result<()> {
+    let path = get_local_config(None)?;
+    if path == Path::new("") {
+        return Err(GitXetRepoError::InvalidOperation(
+            "CAS remotes can only be configured in a repository".to_string(),
+        ));
+    }
+    let mut cfg = if path.exists() {
+        Cfg::from_file(&path).map_err(|e| GitXetRepoError::ConfigError(e.into()))?
+    } else {
+        Cfg::default()
+    };
+
+    let mut mirror = cfg.mirror.take().unwrap_or_default();
+    update(&mut mirror)?;
+    MirrorSettings::try_from(Some(&mirror))?;
+    cfg.mirror = Some(mirror);
+
+    cfg.to_file(&path)
+        .map_err(|e| GitXetRepoError::ConfigError(e.into()))
+}
@@ -0,0 +1,195 @@
+use chrono::{SecondsFormat, Utc};
+use clap::Args;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::time::Instant;
+
+use crate::config::XetConfig;
+use crate::errors::{GitXetRepoError, Result};
+use crate::git_integration::{walk_working_dir, GitXetRepo};
+use crate::lineage::{FileRecord, OutputSnapshot, RunManifest};
+
+/// Runs a pipeline step and commits its outputs with a record of how they
+/// were produced.
+///
+/// The declared outputs created or modified by the command are added
+/// through the clean filter, so large files are committed as pointer files.
+/// The command line and the hashes of the inputs and changed outputs are
+/// written to a manifest under .xet/runs, committed alongside them:
+///
+/// ```ignore
+/// git xet run -i data/raw -o data/features -o models/model.bin -- python train.py
+/// ```
+///
+/// Nothing is recorded if the command fails.
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    /// A file or directory the command reads.
+    #[clap(short, long = "input")]
+    inputs: Vec<String>,
+
+    /// A file or directory the command writes.
+    #[clap(short, long = "output", required = true)]
+    outputs: Vec<String>,
+
+    /// The name of the manifest in .xet/runs. Defaults to one derived from
+    /// the outputs, so rerunning a step replaces its manifest.
+    #[clap(long)]
+    name: Option<String>,
+
+    /// Stage the outputs and manifest without committing them.
+    #[clap(long)]
+    no_commit: bool,
+
+    /// The commit message. Defaults to one naming the command.
+    #[clap(short, long)]
+    message: Option<String>,
+
+    /// The command to run, after "--".
+    #[clap(required = true, last = true)]
+    command: Vec<String>,
+}
+
+/// Resolves a path given relative to cwd into one relative to the root of
+/// the repository, without requiring it to exist.
+fn repo_relative(root: &Path, cwd: &Path, path: &str) -> Result<String> {
+    let mut resolved = PathBuf::new();
+    for component in cwd.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            c => resolved.push(c),
+        }
+    }
+    let relative = resolved.strip_prefix(root).map_err(|_| {
+        GitXetRepoError::InvalidOperation(format!("{path} is outside the repository"))
+    })?;
+    Ok(relative.to_string_lossy().replace('\\', "/"))
+}
+
+pub async fn run_command(cfg: XetConfig, args: &RunArgs) -> Result<()> {
+    let repo = GitXetRepo::open(cfg)?;
+    let root = repo.repo_dir.canonicalize()?;
+    let cwd = std::env::current_dir()?.canonicalize()?;
+    let resolve = |paths: &[String]| -> Result<Vec<String>> {
+        paths
+            .iter()
+            .map(|p| repo_relative(&root, &cwd, p))
+            .collect()
+    };
+    let inputs = resolve(&args.inputs)?;
+    let outputs = resolve(&args.outputs)?;
+
+    // The commit must only contain what the run produced.
+    if !args.no_commit {
+        let head = repo.repo.head().and_then(|h| h.peel_to_tree()).ok();
+        let staged = repo.repo.diff_tree_to_index(head.as_ref(), None, None)?;
+        if staged.deltas().len() > 0 {
+            return Err(GitXetRepoError::InvalidOperation(
+                "There are staged changes; commit or unstage them, or use --no-commit".to_string(),
+            ));
+        }
+    }
+
+    let mut input_records = Vec::new();
+    for input in &inputs {
+        let files = walk_working_dir(&root, root.join(input), true)
+            .map_err(|e| GitXetRepoError::InvalidOperation(format!("{input}: {e}")))?;
+        for file in files {
+            let path = file.to_string_lossy().replace('\\', "/");
+            input_records.push(FileRecord::from_working_file(&root, &path)?);
+        }
+    }
+
+    let before = OutputSnapshot::take(&root, &outputs)?;
+    let started = Utc::now();
+    let timer = Instant::now();
+    let status = Command::new(&args.command[0])
+        .args(&args.command[1..])
+        .status()
+        .map_err(|e| GitXetRepoError::InvalidOperation(format!("{}: {e}", args.command[0])))?;
+    let duration = timer.elapsed();
+    if !status.success() {
+        return Err(GitXetRepoError::InvalidOperation(format!(
+            "{} failed with {status}; nothing was recorded",
+            args.command.join(" ")
+        )));
+    }
+
+    let changed = OutputSnapshot::take(&root, &outputs)?.changed_since(&before);
+    if changed.is_empty() {
+        eprintln!("Warning: the command didn't create or modify any of the declared outputs.");
+    } else {
+        let mut add_args = vec!["--"];
+        add_args.extend(changed.iter().map(String::as_str));
+        repo.run_git_checked_in_repo("add", &add_args)?;
+    }
+
+    // Outputs are identified by what the clean filter staged for them.
+    let mut index = repo.repo.index()?;
+    index.read(true)?;
+    let mut output_records = Vec::new();
+    for path in &changed {
+        let entry = index.get_path(Path::new(path), 0).ok_or_else(|| {
+            GitXetRepoError::InvalidOperation(format!("{path} was not staged; is it ignored?"))
+        })?;
+        let blob = repo.repo.find_blob(entry.id)?;
+        output_records.push(FileRecord::from_cleaned(path, blob.content()));
+    }
+
+    let manifest = RunManifest {
+        command: args.command.clone(),
+        started: started.to_rfc3339_opts(SecondsFormat::Secs, true),
+        duration_secs: duration.as_secs_f64(),
+        inputs: input_records,
+        outputs: output_records,
+    };
+    let name = args
+        .name
+        .clone()
+        .unwrap_or_else(|| RunManifest::default_name(&outputs));
+    let manifest_path = RunManifest::repo_path(&name);
+    let manifest_file = root.join(&manifest_path);
+    if let Some(parent) = manifest_file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&manifest_file, manifest.to_bytes()?)?;
+    repo.run_git_checked_in_repo("add", &["--", &manifest_path])?;
+
+    if args.no_commit {
+        eprintln!(
+            "Staged {} output(s) and {manifest_path}.",
+            manifest.outputs.len()
+        );
+        return Ok(());
+    }
+    let message = args
+        .message
+        .clone()
+        .unwrap_or_else(|| format!("Run {}", args.command.join(" ")));
+    repo.run_git_checked_in_repo("commit", &["-m", &message])?;
+    eprintln!(
+        "Committed {} output(s) and {manifest_path}.",
+        manifest.outputs.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repo_relative() {
+        let root = Path::new("/repo");
+        let cwd = Path::new("/repo/src");
+        assert_eq!(repo_relative(root, cwd, "a.bin").unwrap(), "src/a.bin");
+        assert_eq!(
+            repo_relative(root, cwd, "../data/./raw").unwrap(),
+            "data/raw"
+        );
+        assert!(repo_relative(root, cwd, "../../etc").is_err());
+    }
+}
@@ -0,0 +1,34 @@
+use clap::Args;
+
+use crate::errors::{GitXetRepoError, Result};
+use crate::schemas::{output_schema, OUTPUT_SCHEMAS};
+
+/// Prints the JSON Schema of a JSON output, or lists the schemas.
+///
+/// ```ignore
+/// git xet schema dir-summaries
+/// ```
+#[derive(Args, Debug)]
+pub struct SchemaArgs {
+    /// The schema to print; the schemas are listed if not set.
+    pub name: Option<String>,
+}
+
+pub fn schema_command(args: &SchemaArgs) -> Result<()> {
+    let Some(name) = &args.name else {
+        for schema in OUTPUT_SCHEMAS {
+            println!(
+                "{:<16}  v{:<3}  {}",
+                schema.name, schema.version, schema.description
+            );
+        }
+        return Ok(());
+    };
+    let schema = output_schema(name).ok_or_else(|| {
+        GitXetRepoError::InvalidOperation(format!(
+            "No schema named {name}; run git xet schema to list them"
+        ))
+    })?;
+    print!("{}", schema.schema);
+    Ok(())
+}
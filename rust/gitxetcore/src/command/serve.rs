@@ -0,0 +1,100 @@
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use clap::Args;
+
+use crate::config::XetConfig;
+use crate::errors;
+use crate::xetserve::raw::handle_raw_request;
+use crate::xetserve::s3::{handle_s3_request, S3Gateway};
+use crate::xetserve::{run_http_server, RepoFileReader};
+
+/// The protocol files are served with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServeProtocol {
+    /// Plain HTTP at /raw/<ref>/<path>.
+    Raw,
+
+    /// A read-only S3-compatible API, with buckets as refs and keys as paths.
+    S3,
+}
+
+impl FromStr for ServeProtocol {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "raw" | "http" => Ok(ServeProtocol::Raw),
+            "s3" => Ok(ServeProtocol::S3),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid protocol, should be one of raw, s3: {s}"),
+            )),
+        }
+    }
+}
+
+/// Serves the files of the repository over HTTP, without a checkout.
+///
+/// With the raw protocol, files at any ref are available at
+/// `/raw/<ref>/<path>`, with support for HTTP Range requests.
+///
+/// With the s3 protocol, the repository is exposed as a read-only S3 endpoint
+/// with one bucket per ref, so S3 clients and data loaders can read it
+/// unmodified. Requests must use path-style addressing.
+///
+/// ```ignore
+/// git xet serve --bind 0.0.0.0:8080 &
+/// curl -r 0-1023 http://localhost:8080/raw/main/data/train.csv
+///
+/// git xet serve --protocol s3 &
+/// aws --endpoint-url http://localhost:8080 s3 ls s3://main/data/
+/// ```
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// The address to listen on.
+    #[clap(long, default_value = "127.0.0.1:8080")]
+    pub bind: SocketAddr,
+
+    /// The protocol to serve: raw or s3.
+    #[clap(long, default_value = "raw")]
+    pub protocol: ServeProtocol,
+
+    /// Prefetch aggressiveness for the s3 protocol. the number of 32MB blocks
+    /// to prefetch after a read. Set to 0 if mostly random access patterns are
+    /// expected.
+    #[clap(short, long, default_value = "16")]
+    pub prefetch: usize,
+}
+
+pub async fn serve_command(config: XetConfig, args: &ServeArgs) -> errors::Result<()> {
+    match args.protocol {
+        ServeProtocol::Raw => {
+            let reader = Arc::new(RepoFileReader::new(&config).await?);
+            run_http_server(args.bind, move |req| {
+                handle_raw_request(reader.clone(), req)
+            })
+            .await
+        }
+        ServeProtocol::S3 => {
+            let gateway = Arc::new(S3Gateway::new(&config, args.prefetch)?);
+            run_http_server(args.bind, move |req| {
+                handle_s3_request(gateway.clone(), req)
+            })
+            .await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_protocol() {
+        assert_eq!("raw".parse::<ServeProtocol>().unwrap(), ServeProtocol::Raw);
+        assert_eq!("S3".parse::<ServeProtocol>().unwrap(), ServeProtocol::S3);
+        assert!("webdav".parse::<ServeProtocol>().is_err());
+    }
+}
@@ -3,6 +3,7 @@ use std::io::{stdin, stdout, BufReader, BufWriter, Read, Write};
 use std::path::PathBuf;
 use std::str::FromStr;
 
+use cas::fileio::PositionedWriter;
 use clap::Args;
 use mdb_shard::shard_version::ShardVersion;
 use merklehash::MerkleHash;
@@ -45,6 +46,8 @@ pub struct SmudgeArgs {
     #[clap(long, short)]
     output: Option<PathBuf>,
 
+    /// Only output the bytes in the range start-end, or from start to the
+    /// end of the file with start-. Only the data of the range is fetched.
     #[clap(long, short)]
     range: Option<RangeInput>,
 }
@@ -60,7 +63,7 @@ pub async fn smudge_command_impl(
     let mut output: Box<dyn Write + Send + Sync> = match &args.output {
         Some(filename) => {
             let f = File::create(filename)?;
-            Box::new(BufWriter::new(f))
+            Box::new(BufWriter::new(PositionedWriter::new(f)))
         }
         None => Box::new(stdout()),
     };
@@ -147,22 +150,26 @@ pub enum RangeInputError {
     InvalidRange(usize, usize),
 }
 
-/// A custom type for our range input, takes a comma-delimited string and parses out
-/// a start and end of the range. The range is inclusive.
+/// A custom type for our range input, takes a comma or dash delimited string and
+/// parses out a start and end of the range, the end excluded. Without an end,
+/// e.g. "100-", the range runs to the end of the file.
 #[derive(Debug, PartialEq, Eq)]
-struct RangeInput(usize, usize);
+pub(crate) struct RangeInput(pub usize, pub usize);
 
 impl FromStr for RangeInput {
     type Err = RangeInputError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let vals: Vec<&str> = s.trim().split(',').collect();
+        let vals: Vec<&str> = s.trim().split(|c| c == ',' || c == '-').collect();
 
         if vals.len() != 2 {
             return Err(RangeInputError::InvalidArgumentCount(vals.len()));
         }
         let val1 = vals[0].trim().parse::<usize>()?;
-        let val2 = vals[1].trim().parse::<usize>()?;
+        let val2 = match vals[1].trim() {
+            "" => usize::MAX,
+            val2 => val2.parse::<usize>()?,
+        };
 
         if val2 < val1 {
             return Err(RangeInputError::InvalidRange(val1, val2));
@@ -185,6 +192,11 @@ mod tests {
             RangeInput::from_str(" 100, 200   "),
             Ok(RangeInput(100, 200))
         );
+        assert_eq!(RangeInput::from_str("100-200"), Ok(RangeInput(100, 200)));
+        assert_eq!(
+            RangeInput::from_str("100-"),
+            Ok(RangeInput(100, usize::MAX))
+        );
 
         // error conditions
         assert!(RangeInput::from_str("-1, 2").is_err());
@@ -194,5 +206,6 @@ mod tests {
         assert!(RangeInput::from_str("trash,200").is_err());
         assert!(RangeInput::from_str("trash,garbage").is_err());
         assert!(RangeInput::from_str("200,100").is_err());
+        assert!(RangeInput::from_str("100-200,300").is_err());
     }
 }
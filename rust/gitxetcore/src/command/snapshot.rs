@@ -0,0 +1,189 @@
+use chrono::{TimeZone, Utc};
+use clap::{Args, Subcommand};
+use utils::output_bytes::output_bytes;
+
+use super::dir_summary::recursive_dir_summaries;
+use crate::config::XetConfig;
+use crate::errors::Result;
+use crate::git_integration::GitXetRepo;
+
+/// The trailer of a snapshot tag holding the number of files snapshotted.
+const SNAPSHOT_FILES_TRAILER: &str = "Xet-Snapshot-Files";
+/// The trailer of a snapshot tag holding the total size of the files,
+/// smudged.
+const SNAPSHOT_BYTES_TRAILER: &str = "Xet-Snapshot-Bytes";
+/// The trailer of a snapshot tag holding the recursive directory summaries
+/// of the tagged commit, as JSON.
+const SNAPSHOT_SUMMARY_TRAILER: &str = "Xet-Snapshot-Summary";
+
+#[non_exhaustive]
+#[derive(Subcommand, Debug)]
+enum SnapshotCommand {
+    Create(SnapshotCreateArgs),
+    List(SnapshotListArgs),
+}
+
+/// Tags a commit as a snapshot, recording its directory summaries and total
+/// size in the tag message.
+#[derive(Args, Debug)]
+struct SnapshotCreateArgs {
+    /// The name of the tag to create.
+    name: String,
+
+    /// The commit to snapshot.
+    #[clap(default_value = "HEAD")]
+    reference: String,
+
+    /// A description of the snapshot.
+    #[clap(short, long)]
+    message: Option<String>,
+
+    /// Replace an existing tag of the same name.
+    #[clap(short, long)]
+    force: bool,
+}
+
+/// Lists the snapshots, newest first, with their sizes.
+#[derive(Args, Debug)]
+struct SnapshotListArgs {}
+
+/// Dataset releases: annotated tags carrying the directory summaries and
+/// total size of the tagged commit.
+///
+/// ```ignore
+/// git xet snapshot create v1.0 -m "Cleaned labels for the 2024 release"
+/// git push origin v1.0
+/// git xet snapshot list
+/// ```
+// THIS "SHIM" STRUCT IS MANDATORY
+#[derive(Args, Debug)]
+pub struct SnapshotCommandShim {
+    #[clap(subcommand)]
+    subcommand: SnapshotCommand,
+}
+
+impl SnapshotCommandShim {
+    pub fn subcommand_name(&self) -> String {
+        match self.subcommand {
+            SnapshotCommand::Create(_) => "create".to_string(),
+            SnapshotCommand::List(_) => "list".to_string(),
+        }
+    }
+}
+
+pub async fn snapshot_command(cfg: XetConfig, command: &SnapshotCommandShim) -> Result<()> {
+    let repo = GitXetRepo::open(cfg)?;
+    match &command.subcommand {
+        SnapshotCommand::Create(args) => snapshot_create_command(&repo, args).await,
+        SnapshotCommand::List(_) => snapshot_list_command(&repo),
+    }
+}
+
+/// The message of a snapshot tag: the description, followed by the
+/// trailers.
+fn snapshot_message(message: Option<&str>, files: i64, bytes: u64, summary: &str) -> String {
+    let subject = message.map(str::trim).unwrap_or("Snapshot");
+    format!(
+        "{subject}\n\n\
+         {SNAPSHOT_FILES_TRAILER}: {files}\n\
+         {SNAPSHOT_BYTES_TRAILER}: {bytes}\n\
+         {SNAPSHOT_SUMMARY_TRAILER}: {summary}\n"
+    )
+}
+
+/// The value of a trailer in a tag message, if present.
+fn trailer<'a>(message: &'a str, key: &str) -> Option<&'a str> {
+    message
+        .lines()
+        .rev()
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix(": "))
+}
+
+async fn snapshot_create_command(repo: &GitXetRepo, args: &SnapshotCreateArgs) -> Result<()> {
+    let target = repo
+        .repo
+        .revparse_single(&args.reference)?
+        .peel_to_commit()?;
+    let summaries = recursive_dir_summaries(repo, &target.id().to_string()).await?;
+    let (files, bytes) = summaries.root_totals();
+    let summary = serde_json::to_string(&summaries)?;
+
+    let message = snapshot_message(args.message.as_deref(), files, bytes, &summary);
+    repo.repo.tag(
+        &args.name,
+        target.as_object(),
+        &repo.signature(),
+        &message,
+        args.force,
+    )?;
+    println!(
+        "Tagged {} as {}: {files} files, {}",
+        args.reference,
+        args.name,
+        output_bytes(bytes as usize)
+    );
+    Ok(())
+}
+
+fn snapshot_list_command(repo: &GitXetRepo) -> Result<()> {
+    let mut snapshots = Vec::new();
+    for name in repo.repo.tag_names(None)?.iter().flatten() {
+        let Ok(tag) = repo
+            .repo
+            .revparse_single(&format!("refs/tags/{name}"))
+            .and_then(|o| o.peel_to_tag())
+        else {
+            continue;
+        };
+        let message = tag.message().unwrap_or_default();
+        let Some(bytes) =
+            trailer(message, SNAPSHOT_BYTES_TRAILER).and_then(|b| b.parse::<u64>().ok())
+        else {
+            continue;
+        };
+        let files: i64 = trailer(message, SNAPSHOT_FILES_TRAILER)
+            .and_then(|f| f.parse().ok())
+            .unwrap_or_default();
+        let time = tag.tagger().map_or(0, |t| t.when().seconds());
+        let subject = message.lines().next().unwrap_or_default().to_string();
+        snapshots.push((time, name.to_string(), files, bytes, subject));
+    }
+    if snapshots.is_empty() {
+        eprintln!("No snapshots; create one with git xet snapshot create.");
+        return Ok(());
+    }
+
+    snapshots.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    for (time, name, files, bytes, subject) in snapshots {
+        let date = Utc
+            .timestamp_opt(time, 0)
+            .single()
+            .map(|t| t.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        println!(
+            "{name}\t{date}\t{files} files\t{}\t{subject}",
+            output_bytes(bytes as usize)
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_message() {
+        let message = snapshot_message(Some("Release 1\n"), 3, 4096, "{\"version\":3}");
+        assert_eq!(
+            message,
+            "Release 1\n\n\
+             Xet-Snapshot-Files: 3\n\
+             Xet-Snapshot-Bytes: 4096\n\
+             Xet-Snapshot-Summary: {\"version\":3}\n"
+        );
+        assert_eq!(trailer(&message, SNAPSHOT_BYTES_TRAILER), Some("4096"));
+        assert_eq!(trailer(&message, SNAPSHOT_FILES_TRAILER), Some("3"));
+        assert_eq!(trailer("Release 1\n", SNAPSHOT_BYTES_TRAILER), None);
+    }
+}
@@ -8,6 +8,7 @@ use std::{
 };
 use tracing::warn;
 
+use crate::command::dir_summary::{print_languages, print_top_directories, TopOrder};
 use crate::{config::XetConfig, errors::GitXetRepoError, utils};
 use crate::{
     constants::{GIT_NOTES_SUMMARIES_REF_NAME, POINTER_FILE_LIMIT},
@@ -60,6 +61,40 @@ pub enum SummarySubCommand {
 
     /// Writes out all summary types for all files (from git notes) as JSON.
     Dump,
+
+    /// Lists the directories with the most files, including those in
+    /// subdirectories, and the file types dominating each.
+    Top {
+        /// A git commit reference to rank the directories at.
+        #[clap(default_value = "HEAD")]
+        reference: String,
+
+        /// The number of directories to list.
+        #[clap(long, default_value = "20")]
+        limit: usize,
+
+        /// Leave out directories with fewer files than this.
+        #[clap(long, default_value = "0")]
+        min_count: i64,
+
+        /// Rank the directories by file count or by the size of their files.
+        #[clap(long, arg_enum, default_value = "count")]
+        by: TopOrder,
+    },
+
+    /// Lists the programming languages of the source files in a directory,
+    /// including those in subdirectories, with their lines of code, comments
+    /// and blanks.
+    Languages {
+        /// A git commit reference to list the languages at.
+        #[clap(default_value = "HEAD")]
+        reference: String,
+
+        /// The directory to list the languages of, relative to the
+        /// repository root; the whole repository if not set.
+        #[clap(long, default_value = "")]
+        dir: String,
+    },
 }
 
 fn print_stored_summary_impl<T: Serialize>(t: &Option<T>) -> errors::Result<()>
@@ -171,5 +206,14 @@ pub async fn summary_command(config: XetConfig, args: &SummaryArgs) -> errors::R
         }
         SummarySubCommand::Query { merklehash } => summaries_query(config, merklehash).await,
         SummarySubCommand::Dump => summaries_dump(config).await,
+        SummarySubCommand::Top {
+            reference,
+            limit,
+            min_count,
+            by,
+        } => print_top_directories(config, reference, *by, *limit, *min_count).await,
+        SummarySubCommand::Languages { reference, dir } => {
+            print_languages(config, reference, dir).await
+        }
     }
 }
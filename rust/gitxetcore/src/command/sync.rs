@@ -0,0 +1,155 @@
+use clap::Args;
+use std::path::Path;
+
+use super::import::{commit_message, read_source_record};
+use crate::config::XetConfig;
+use crate::data::fallback::SourceRecord;
+use crate::data::PointerFileTranslator;
+use crate::errors::Result;
+use crate::git_integration::git_commits::{atomic_commit_impl, ManifestEntry};
+use crate::git_integration::git_user_config::get_user_info_for_commit;
+use crate::git_integration::open_libgit2_repo;
+use crate::import::sync::{SyncManifest, SyncPlan};
+use crate::import::{clean_objects, ensure_not_checked_out, repo_path, ImportSource};
+
+/// Mirrors a source into a branch, committing only the objects that changed
+/// since the last sync.
+///
+/// Objects are compared by the ETag, size and modification time the source
+/// reports, against a manifest the sync commits under .xet/sync. Run from
+/// cron to keep a versioned copy of a bucket:
+///
+/// ```ignore
+/// git xet sync s3://bucket/raw/ --branch mirror/raw --path data/raw --delete
+/// git xet push origin mirror/raw
+/// ```
+#[derive(Args, Debug)]
+pub struct SyncArgs {
+    /// The source to mirror: s3://bucket/prefix, gs://bucket/prefix, or the
+    /// http(s) URL of a single object.
+    source: String,
+
+    /// The branch to commit to, created from HEAD if it doesn't exist. It
+    /// can't be the branch checked out.
+    #[clap(short, long)]
+    branch: String,
+
+    /// The directory of the repository to mirror into.
+    #[clap(long, default_value = "")]
+    path: String,
+
+    /// Delete the files whose objects were removed from the source.
+    #[clap(long)]
+    delete: bool,
+
+    /// Report the changes without importing or committing them.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// The commit message. Defaults to one naming the source.
+    #[clap(short, long)]
+    message: Option<String>,
+}
+
+pub async fn sync_command(cfg: XetConfig, args: &SyncArgs) -> Result<()> {
+    let source = ImportSource::parse(&args.source)?;
+    let repo = open_libgit2_repo(cfg.repo_path_if_present.as_deref())?;
+    if !args.dry_run {
+        ensure_not_checked_out(&repo, &args.branch)?;
+    }
+
+    // A new branch starts from HEAD, as the commit will.
+    let refname = format!("refs/heads/{}", args.branch);
+    let tree = repo
+        .find_reference(&refname)
+        .or_else(|_| repo.head())
+        .and_then(|r| r.peel_to_tree())
+        .ok();
+    let exists = |path: &str| {
+        tree.as_ref()
+            .map_or(false, |t| t.get_path(Path::new(path)).is_ok())
+    };
+
+    let manifest_path = SyncManifest::repo_path(&args.source, &args.path);
+    let mut manifest = match tree
+        .as_ref()
+        .and_then(|t| t.get_path(Path::new(&manifest_path)).ok())
+    {
+        Some(entry) => SyncManifest::parse(entry.to_object(&repo)?.peel_to_blob()?.content())?,
+        None => SyncManifest::new(&args.source, &args.path),
+    };
+
+    let http = reqwest::Client::new();
+    let objects = source.list(&http).await?;
+    let plan = SyncPlan::new(&manifest, objects, args.delete, &exists);
+    if plan.is_empty() {
+        eprintln!("{} is up to date with {}.", args.branch, args.source);
+        return Ok(());
+    }
+    println!("{}", plan.report(&args.path));
+    let summary = format!(
+        "{} added, {} modified, {} deleted",
+        plan.added.len(),
+        plan.modified.len(),
+        plan.deleted.len()
+    );
+    if args.dry_run {
+        eprintln!("Dry run: {summary}.");
+        return Ok(());
+    }
+
+    let translator = PointerFileTranslator::from_config_in_repo(&cfg).await?;
+    let changed = plan
+        .added
+        .iter()
+        .chain(plan.modified.iter())
+        .cloned()
+        .collect();
+    let files = clean_objects(&source, &http, &translator, &args.path, changed).await?;
+    translator.finalize_cleaning().await?;
+    plan.apply(&mut manifest);
+
+    let record_path =
+        SourceRecord::repo_path(&format!("{}\n{}", args.source, args.path.trim_matches('/')));
+    let mut record = read_source_record(&repo, &args.branch, &record_path)?;
+    let mut entries: Vec<ManifestEntry> = Vec::new();
+    for file in files {
+        record.insert(&file.pointer, &file.url);
+        entries.push(ManifestEntry::Upsert {
+            file: file.path.into(),
+            modeexec: false,
+            content: file.pointer,
+            githash_content: None,
+        });
+    }
+    entries.extend(
+        plan.deleted
+            .iter()
+            .map(|path| repo_path(&args.path, path))
+            .filter(|path| exists(path))
+            .map(|path| ManifestEntry::Delete { file: path.into() }),
+    );
+    entries.push(ManifestEntry::Upsert {
+        file: manifest_path.into(),
+        modeexec: false,
+        content: manifest.to_bytes()?,
+        githash_content: None,
+    });
+    entries.push(ManifestEntry::Upsert {
+        file: record_path.into(),
+        modeexec: false,
+        content: record.to_bytes()?,
+        githash_content: None,
+    });
+
+    let subject = args
+        .message
+        .clone()
+        .unwrap_or_else(|| format!("Sync from {}", args.source));
+    let message = commit_message(Some(&subject), &[args.source.clone()]);
+    let (name, email) = get_user_info_for_commit(Some(&cfg), None, Some(repo.clone()));
+    atomic_commit_impl(&repo, entries, &refname, &message, &name, &email, true)?;
+
+    eprintln!("Synced {} into {}: {summary}.", args.source, args.branch);
+    Ok(())
+}
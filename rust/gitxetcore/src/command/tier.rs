@@ -0,0 +1,256 @@
+use clap::Args;
+use git2::{ReferenceType, Repository};
+use mdb_shard::shard_version::ShardVersion;
+use mdb_shard::MDBShardFile;
+use merklehash::MerkleHash;
+use parutils::tokio_par_for_each;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::error;
+use utils::output_bytes::output_bytes;
+
+use crate::config::XetConfig;
+use crate::constants::{GIT_NOTES_MERKLEDB_V2_REF_NAME, MAX_CONCURRENT_UPLOADS};
+use crate::data::mdb::sync_mdb_shards_from_git;
+use crate::data::PointerFileTranslator;
+use crate::errors::{convert_parallel_error, GitXetRepoError, Result};
+use crate::git_integration::git_quota::resolve_remote_url;
+use crate::git_integration::GitXetRepo;
+use crate::xetblob::{request_tiering, TierResponse};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// The xorbs asked to move in one request to the remote.
+const TIER_REQUEST_SIZE: usize = 1000;
+
+/// Moves the data no recent branch or tag references to cold storage.
+///
+/// The data of the files at HEAD, and at each branch, remote-tracking
+/// branch and tag last committed to within tier.hotdays days, stays in the
+/// hot tier, as does the data of the large payloads the git-xet notes hold
+/// pointer files to, e.g. cached dir summaries. The remote is asked to move every other xorb of the repository
+/// to tier.target, the cold storage class of its CAS by default. Nothing is
+/// deleted: smudging a file whose data is cold restores it first, which
+/// takes longer, so only data rarely read should be moved.
+///
+/// ```ignore
+/// git xet tier --dry-run
+/// git xet tier --hot-days 30
+/// ```
+#[derive(Args, Debug)]
+pub struct TierArgs {
+    /// The remote whose data is moved.
+    #[clap(long, default_value = "origin")]
+    remote: String,
+
+    /// Keeps the data of the refs committed to within this many days hot,
+    /// instead of tier.hotdays.
+    #[clap(long)]
+    hot_days: Option<u64>,
+
+    /// Only list the xorbs that would be moved, with their sizes.
+    #[clap(long)]
+    dry_run: bool,
+}
+
+pub async fn tier_command(cfg: XetConfig, args: &TierArgs) -> Result<()> {
+    let hot_age = match args.hot_days {
+        Some(0) => {
+            return Err(GitXetRepoError::InvalidOperation(
+                "--hot-days must be at least 1".to_string(),
+            ))
+        }
+        Some(days) => Duration::from_secs(days * SECONDS_PER_DAY),
+        None => cfg.tier.hot_age,
+    };
+    let repo = GitXetRepo::open(cfg.clone())?;
+    if repo.mdb_version != ShardVersion::V2 {
+        return Err(GitXetRepoError::InvalidOperation(
+            "Only repositories with a MerkleDB v2 can be tiered".to_string(),
+        ));
+    }
+
+    // Every shard is needed to list every xorb of the repository, and the
+    // files at the refs kept hot.
+    repo.sync_remote_to_notes(&args.remote)?;
+    sync_mdb_shards_from_git(
+        &cfg,
+        &repo.merkledb_v2_cache_dir,
+        GIT_NOTES_MERKLEDB_V2_REF_NAME,
+        true,
+    )
+    .await?;
+
+    let hot_refs = recent_refs(&repo.repo, hot_age, SystemTime::now())?;
+    let mut files = HashMap::new();
+    for reference in &hot_refs {
+        files.extend(repo.pointer_files_at_ref(reference)?);
+    }
+    let note_payloads = repo.pointer_files_in_notes()?;
+    let num_note_payloads = note_payloads.len();
+    files.extend(note_payloads);
+    let translator = PointerFileTranslator::from_config_in_repo(&cfg).await?;
+    let translator = &translator;
+    let file_blocks = tokio_par_for_each(
+        files.into_iter().collect(),
+        MAX_CONCURRENT_UPLOADS,
+        |(hash, path): (MerkleHash, String), _| async move {
+            translator.derive_blocks(&hash).await.map_err(|e| {
+                error!("Unable to read {path}: {e}");
+                e
+            })
+        },
+    )
+    .await
+    .map_err(convert_parallel_error)?;
+    let hot: HashSet<MerkleHash> = file_blocks
+        .into_iter()
+        .flatten()
+        .map(|range| range.hash)
+        .collect();
+    eprintln!(
+        "Keeping the {} xorbs of {} and of {num_note_payloads} note payloads hot",
+        hot.len(),
+        hot_refs.join(", ")
+    );
+
+    let cold = cold_xorbs(repo_xorbs(&repo.merkledb_v2_cache_dir)?, &hot);
+    let cold_bytes: u64 = cold.iter().map(|(_, size)| size).sum();
+    println!(
+        "{} xorbs ({}) are not referenced by a recent ref",
+        cold.len(),
+        output_bytes(cold_bytes as usize)
+    );
+    if args.dry_run {
+        for (hash, size) in &cold {
+            println!("{hash} {size}");
+        }
+        return Ok(());
+    }
+    if cold.is_empty() {
+        return Ok(());
+    }
+
+    let remote_url = resolve_remote_url(&repo.repo, &args.remote);
+    let hashes: Vec<String> = cold.iter().map(|(hash, _)| hash.hex()).collect();
+    let mut moved = TierResponse::default();
+    for request in hashes.chunks(TIER_REQUEST_SIZE) {
+        let response = request_tiering(&cfg, &remote_url, &cfg.tier.target, request)
+            .await
+            .map_err(|e| {
+                GitXetRepoError::InvalidRemote(format!(
+                    "Unable to move data to {} on {}: {e}",
+                    cfg.tier.target, args.remote
+                ))
+            })?;
+        moved.queued += response.queued;
+        moved.already_moved += response.already_moved;
+    }
+    println!(
+        "Queued {} xorbs to move to {}; {} were already there.",
+        moved.queued, cfg.tier.target, moved.already_moved
+    );
+    Ok(())
+}
+
+/// The refs whose data stays hot: HEAD, and the branches, remote-tracking
+/// branches and tags whose commit is younger than hot_age at now.
+fn recent_refs(repo: &Repository, hot_age: Duration, now: SystemTime) -> Result<Vec<String>> {
+    let cutoff = now
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .saturating_sub(hot_age)
+        .as_secs() as i64;
+    let mut refs = Vec::new();
+    if repo.head().is_ok() {
+        refs.push("HEAD".to_string());
+    }
+    for reference in repo.references()? {
+        let reference = reference?;
+        // Symbolic refs, e.g. refs/remotes/origin/HEAD, name refs listed on
+        // their own.
+        if reference.kind() != Some(ReferenceType::Direct)
+            || !(reference.is_branch() || reference.is_remote() || reference.is_tag())
+        {
+            continue;
+        }
+        let (Some(name), Ok(commit)) = (reference.name(), reference.peel_to_commit()) else {
+            continue;
+        };
+        if commit.time().seconds() >= cutoff {
+            refs.push(name.to_string());
+        }
+    }
+    Ok(refs)
+}
+
+/// Every xorb of the repository, with its size, from its shards in dir.
+fn repo_xorbs(dir: &Path) -> Result<Vec<(MerkleHash, u64)>> {
+    let mut xorbs = Vec::new();
+    for shard in MDBShardFile::load_all(dir)? {
+        for (cas, _) in shard.read_all_cas_blocks()? {
+            xorbs.push((cas.cas_hash, cas.num_bytes_in_cas as u64));
+        }
+    }
+    Ok(xorbs)
+}
+
+/// The xorbs not in hot, once each, in hash order.
+fn cold_xorbs(xorbs: Vec<(MerkleHash, u64)>, hot: &HashSet<MerkleHash>) -> Vec<(MerkleHash, u64)> {
+    xorbs
+        .into_iter()
+        .filter(|(hash, _)| !hot.contains(hash))
+        .collect::<BTreeMap<_, _>>()
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::{Oid, Signature, Time};
+    use tempfile::TempDir;
+
+    fn commit_at(repo: &Repository, refname: &str, secs: i64) -> Oid {
+        let sig = Signature::new("test", "test@xethub.com", &Time::new(secs, 0)).unwrap();
+        let tree_id = repo.treebuilder(None).unwrap().write().unwrap();
+        let tree = repo.find_tree(tree_id).unwrap();
+        repo.commit(Some(refname), &sig, &sig, refname, &tree, &[])
+            .unwrap()
+    }
+
+    #[test]
+    fn test_recent_refs() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let now = UNIX_EPOCH + Duration::from_secs(1000 * SECONDS_PER_DAY);
+        let day = |d: u64| (d * SECONDS_PER_DAY) as i64;
+
+        commit_at(&repo, "refs/heads/master", day(1000));
+        repo.set_head("refs/heads/master").unwrap();
+        let old = commit_at(&repo, "refs/heads/old", day(800));
+        commit_at(&repo, "refs/remotes/origin/wip", day(990));
+        repo.reference("refs/tags/v1", old, false, "").unwrap();
+        repo.reference_symbolic("refs/remotes/origin/HEAD", "refs/heads/master", false, "")
+            .unwrap();
+
+        let mut refs = recent_refs(&repo, Duration::from_secs(30 * SECONDS_PER_DAY), now).unwrap();
+        refs.sort();
+        assert_eq!(
+            refs,
+            ["HEAD", "refs/heads/master", "refs/remotes/origin/wip"]
+        );
+
+        let refs = recent_refs(&repo, Duration::from_secs(365 * SECONDS_PER_DAY), now).unwrap();
+        assert!(refs.contains(&"refs/tags/v1".to_string()));
+    }
+
+    #[test]
+    fn test_cold_xorbs() {
+        let hash = |i: u64| MerkleHash::from([i, 0, 0, 0]);
+        let xorbs = vec![(hash(3), 30), (hash(1), 10), (hash(2), 20), (hash(3), 30)];
+        let hot = HashSet::from([hash(2)]);
+        assert_eq!(cold_xorbs(xorbs, &hot), vec![(hash(1), 10), (hash(3), 30)]);
+    }
+}
@@ -0,0 +1,439 @@
+use std::collections::HashMap;
+use std::io::{stdout, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use clap::Args;
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute, queue};
+use serde_json::Value;
+use tracing::debug;
+use utils::output_bytes::output_bytes;
+
+use crate::config::XetConfig;
+use crate::constants::CONTROL_SOCKET_SUBDIR;
+use crate::control::{ProgressReport, MOUNT_READ_BYTES};
+use crate::data::PendingUpload;
+use crate::errors::{GitXetRepoError, Result};
+use crate::git_integration::GitXetRepo;
+use crate::jsonrpc;
+
+/// How long a control socket has to answer before the operation is
+/// considered gone.
+const CALL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The recent errors shown, across all operations.
+const MAX_ERRORS_SHOWN: usize = 10;
+
+/// Shows the mounts and filter processes running in the repository, with
+/// their live transfers, cache hit rates, mount read throughput, the tasks
+/// in progress, the data pending upload, and their recent errors.
+///
+/// The operations are read from their control sockets, so they must run
+/// with control.enabled set. Press q to quit.
+///
+/// ```ignore
+/// git xet top
+/// git xet top --once
+/// ```
+#[derive(Args, Debug)]
+pub struct TopArgs {
+    /// Seconds between refreshes.
+    #[clap(long, default_value = "1")]
+    interval: u64,
+
+    /// Prints the dashboard once, after one interval to measure the rates,
+    /// and exits. The default when stdout is not a terminal.
+    #[clap(long)]
+    once: bool,
+}
+
+pub async fn top_command(cfg: XetConfig, args: &TopArgs) -> Result<()> {
+    if args.interval == 0 {
+        return Err(GitXetRepoError::InvalidOperation(
+            "--interval must be at least 1".to_string(),
+        ));
+    }
+    let repo = GitXetRepo::open(cfg)?;
+    let interval = Duration::from_secs(args.interval);
+    let mut dashboard = Dashboard::default();
+
+    if args.once || !atty::is(atty::Stream::Stdout) {
+        // The rates are measured over one interval.
+        dashboard.refresh(&repo).await;
+        tokio::time::sleep(interval).await;
+        dashboard.refresh(&repo).await;
+        for line in dashboard.render() {
+            println!("{line}");
+        }
+        return Ok(());
+    }
+
+    let screen = Screen::enter()?;
+    loop {
+        dashboard.refresh(&repo).await;
+        let mut lines = vec![format!(
+            "git xet top: refreshing every {}s, press q to quit",
+            args.interval
+        )];
+        lines.extend(dashboard.render());
+        screen.draw(&lines)?;
+        if tokio::task::spawn_blocking(move || quit_pressed(interval)).await?? {
+            return Ok(());
+        }
+    }
+}
+
+/// The rates of an operation between two refreshes, in bytes per second.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Rates {
+    download: f64,
+    upload: f64,
+    /// None unless the operation is a mount.
+    mount_read: Option<f64>,
+}
+
+impl Rates {
+    /// The rates from the previous report of an operation to the current
+    /// one, timed by the uptime of the operation.
+    fn between(previous: &ProgressReport, current: &ProgressReport) -> Self {
+        let secs = current.uptime_secs - previous.uptime_secs;
+        let rate = |before: u64, after: u64| {
+            if secs > 0. {
+                after.saturating_sub(before) as f64 / secs
+            } else {
+                0.
+            }
+        };
+        Rates {
+            download: rate(
+                previous.transfer.downloaded_bytes,
+                current.transfer.downloaded_bytes,
+            ),
+            upload: rate(
+                previous.transfer.uploaded_bytes,
+                current.transfer.uploaded_bytes,
+            ),
+            mount_read: current.counters.get(MOUNT_READ_BYTES).map(|&after| {
+                let before = previous.counters.get(MOUNT_READ_BYTES).copied();
+                rate(before.unwrap_or(after), after)
+            }),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Dashboard {
+    /// The last report of each operation, and its rates since the report
+    /// before.
+    operations: Vec<(ProgressReport, Rates)>,
+    /// The sockets that did not answer, e.g. left by a killed process.
+    unreachable: usize,
+    pending: Option<PendingUpload>,
+}
+
+impl Dashboard {
+    async fn refresh(&mut self, repo: &GitXetRepo) {
+        let (reports, unreachable) = poll(&repo.git_dir.join(CONTROL_SOCKET_SUBDIR)).await;
+        self.update(reports);
+        self.unreachable = unreachable;
+        self.pending =
+            PendingUpload::load(&repo.merkledb_v2_session_dir, &repo.cas_staging_path).ok();
+    }
+
+    fn update(&mut self, mut reports: Vec<ProgressReport>) {
+        reports.sort_by_key(|report| report.pid);
+        let previous: HashMap<u32, ProgressReport> = self
+            .operations
+            .drain(..)
+            .map(|(report, _)| (report.pid, report))
+            .collect();
+        self.operations = reports
+            .into_iter()
+            .map(|report| {
+                let rates = previous
+                    .get(&report.pid)
+                    .map(|previous| Rates::between(previous, &report))
+                    .unwrap_or_default();
+                (report, rates)
+            })
+            .collect();
+    }
+
+    fn render(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if self.operations.is_empty() {
+            lines.push(
+                "No operation is serving a control socket; mounts and filter processes serve one with control.enabled set."
+                    .to_string(),
+            );
+        } else {
+            lines.push(format!(
+                "{:>7}  {:<8} {:>12} {:>12} {:>12} {:>13} {:>9} {:>8} {:>12}",
+                "PID",
+                "COMMAND",
+                "UPTIME",
+                "DOWN/s",
+                "UP/s",
+                "MOUNT READ/s",
+                "CACHE HIT",
+                "RETRIES",
+                "LIMIT/s"
+            ));
+            for (report, rates) in &self.operations {
+                let transfer = &report.transfer;
+                let cache_reads = transfer.cache_hit_bytes + transfer.cache_miss_bytes;
+                lines.push(format!(
+                    "{:>7}  {:<8} {:>12} {:>12} {:>12} {:>13} {:>9} {:>8} {:>12}",
+                    report.pid,
+                    report.operation,
+                    humantime::format_duration(Duration::from_secs(report.uptime_secs as u64))
+                        .to_string(),
+                    output_bytes(rates.download as usize),
+                    output_bytes(rates.upload as usize),
+                    rates
+                        .mount_read
+                        .map(|rate| output_bytes(rate as usize))
+                        .unwrap_or_else(|| "-".to_string()),
+                    match cache_reads {
+                        0 => "-".to_string(),
+                        _ => format!(
+                            "{:.1}%",
+                            100. * transfer.cache_hit_bytes as f64 / cache_reads as f64
+                        ),
+                    },
+                    transfer.retries,
+                    report
+                        .bandwidth_limit
+                        .map(|limit| output_bytes(limit as usize))
+                        .unwrap_or_else(|| "-".to_string()),
+                ));
+            }
+        }
+        if self.unreachable > 0 {
+            lines.push(format!(
+                "{} control sockets did not answer",
+                self.unreachable
+            ));
+        }
+
+        let tasks: Vec<String> = self
+            .operations
+            .iter()
+            .flat_map(|(report, _)| {
+                report.tasks.iter().filter(|task| task.active).map(|task| {
+                    format!(
+                        "{:>7}  {}: {}/{} files, {}/{}",
+                        report.pid,
+                        task.message,
+                        task.current_count,
+                        task.total_count,
+                        output_bytes(task.current_bytes),
+                        output_bytes(task.total_bytes)
+                    )
+                })
+            })
+            .collect();
+        if !tasks.is_empty() {
+            lines.push(String::new());
+            lines.push("Tasks".to_string());
+            lines.extend(tasks);
+        }
+
+        if let Some(pending) = &self.pending {
+            lines.push(String::new());
+            lines.push(format!(
+                "Pending upload: {} files ({}), {} xorbs and {} shards to send ({})",
+                pending.files.len(),
+                output_bytes(pending.file_bytes() as usize),
+                pending.num_xorbs,
+                pending.num_shards,
+                output_bytes(pending.upload_bytes() as usize)
+            ));
+        }
+
+        let mut errors: Vec<_> = self
+            .operations
+            .iter()
+            .flat_map(|(report, _)| report.recent_errors.iter().map(|e| (report, e)))
+            .collect();
+        errors.sort_by_key(|(_, error)| error.time);
+        if !errors.is_empty() {
+            lines.push(String::new());
+            lines.push("Recent errors".to_string());
+            for (report, error) in &errors[errors.len().saturating_sub(MAX_ERRORS_SHOWN)..] {
+                lines.push(format!(
+                    "{} {:>7} {:<5} {}",
+                    error.time.format("%H:%M:%S"),
+                    report.pid,
+                    error.level,
+                    error.message
+                ));
+            }
+        }
+        lines
+    }
+}
+
+/// The progress reports of the operations serving a control socket in
+/// socket_dir, and the number of sockets that did not answer.
+async fn poll(socket_dir: &Path) -> (Vec<ProgressReport>, usize) {
+    let Ok(entries) = std::fs::read_dir(socket_dir) else {
+        return (Vec::new(), 0);
+    };
+    let mut reports = Vec::new();
+    let mut unreachable = 0;
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.extension().and_then(|e| e.to_str()) != Some("sock") {
+            continue;
+        }
+        let call = jsonrpc::call(&path, "progress", Value::Null);
+        let report = match tokio::time::timeout(CALL_TIMEOUT, call).await {
+            Ok(Ok(result)) => serde_json::from_value(result).map_err(|e| e.to_string()),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err("timed out".to_string()),
+        };
+        match report {
+            Ok(report) => reports.push(report),
+            Err(e) => {
+                debug!("XET top: no progress from {path:?}: {e}");
+                unreachable += 1;
+            }
+        }
+    }
+    (reports, unreachable)
+}
+
+/// The terminal in the alternate screen and raw mode, restored when dropped.
+struct Screen;
+
+impl Screen {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(stdout(), EnterAlternateScreen, cursor::Hide)?;
+        Ok(Screen)
+    }
+
+    /// Replaces the screen with the lines, cut to its size.
+    fn draw(&self, lines: &[String]) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        let mut out = stdout();
+        queue!(out, Clear(ClearType::All))?;
+        for (row, line) in lines.iter().take(height as usize).enumerate() {
+            let line: String = line.chars().take(width as usize).collect();
+            queue!(out, cursor::MoveTo(0, row as u16), Print(line))?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(stdout(), cursor::Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Waits up to timeout for a key press, returning whether it quits. Any
+/// other event, e.g. a resize, ends the wait so that the screen is redrawn.
+fn quit_pressed(timeout: Duration) -> Result<bool> {
+    let deadline = Instant::now() + timeout;
+    while event::poll(deadline.saturating_duration_since(Instant::now()))? {
+        match event::read()? {
+            Event::Key(key) => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(true),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(true)
+                }
+                _ => {}
+            },
+            _ => return Ok(false),
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::{TaskProgress, TransferTotals};
+    use crate::environment::recent_errors::RecentError;
+    use chrono::{TimeZone, Utc};
+    use std::collections::BTreeMap;
+
+    fn report(
+        pid: u32,
+        uptime_secs: f64,
+        downloaded_bytes: u64,
+        mount_read: u64,
+    ) -> ProgressReport {
+        ProgressReport {
+            operation: "mount".to_string(),
+            pid,
+            uptime_secs,
+            transferred_bytes: downloaded_bytes,
+            bandwidth_limit: None,
+            tasks: vec![TaskProgress {
+                message: "Prefetching".to_string(),
+                active: true,
+                current_count: 1,
+                total_count: 4,
+                current_bytes: 1024,
+                total_bytes: 4096,
+                elapsed_secs: uptime_secs,
+            }],
+            transfer: TransferTotals {
+                downloaded_bytes,
+                cache_hit_bytes: 300,
+                cache_miss_bytes: 100,
+                ..Default::default()
+            },
+            counters: BTreeMap::from([(MOUNT_READ_BYTES.to_string(), mount_read)]),
+            recent_errors: vec![RecentError {
+                time: Utc.timestamp_opt(uptime_secs as i64, 0).unwrap(),
+                level: "WARN".to_string(),
+                message: format!("slow read {uptime_secs}"),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_rates() {
+        let mut dashboard = Dashboard::default();
+        dashboard.update(vec![report(7, 10., 1000, 0)]);
+        assert_eq!(dashboard.operations[0].1, Rates::default());
+
+        dashboard.update(vec![report(7, 12., 5000, 2000), report(3, 1., 0, 0)]);
+        assert_eq!(dashboard.operations[0].0.pid, 3);
+        let rates = dashboard.operations[1].1;
+        assert_eq!(rates.download, 2000.);
+        assert_eq!(rates.upload, 0.);
+        assert_eq!(rates.mount_read, Some(1000.));
+
+        // A restarted operation with a new pid starts over.
+        dashboard.update(vec![report(8, 1., 100, 0)]);
+        assert_eq!(dashboard.operations[0].1, Rates::default());
+    }
+
+    #[test]
+    fn test_render() {
+        let mut dashboard = Dashboard::default();
+        assert!(dashboard.render()[0].starts_with("No operation"));
+
+        dashboard.update(vec![report(7, 10., 1000, 0)]);
+        dashboard.update(vec![report(7, 12., 5000, 2000)]);
+        dashboard.pending = Some(PendingUpload::default());
+        let lines = dashboard.render();
+        assert!(lines[0].contains("CACHE HIT"));
+        assert!(lines[1].contains("mount"));
+        assert!(lines[1].contains("75.0%"));
+        assert!(lines.iter().any(|l| l.contains("Prefetching: 1/4 files")));
+        assert!(lines
+            .iter()
+            .any(|l| l.starts_with("Pending upload: 0 files")));
+        assert!(lines.last().unwrap().ends_with("slow read 12"));
+    }
+}
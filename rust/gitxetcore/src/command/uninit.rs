@@ -1,7 +1,9 @@
 use clap::Args;
 
+use crate::command::materialize::{materialize_all, materialized_pointer};
 use crate::config::XetConfig;
-use crate::errors::Result;
+use crate::errors::{GitXetRepoError, Result};
+use crate::git_integration::git_hooks::HookState;
 use crate::git_integration::GitXetRepo;
 
 #[derive(Args, Debug, Clone)]
@@ -31,6 +33,12 @@ pub struct UninitArgs {
     #[clap(long)]
     pub purge_fetch_config: bool,
 
+    /// Delete the git-xet notes, local and fetched from the remotes. The
+    /// notes of commits not pushed yet can't be recovered. Not included in
+    /// --full.
+    #[clap(long)]
+    pub purge_notes: bool,
+
     /// Remove all the hooks from the local repository.  This defaults to true if no flags are given.
     #[clap(long)]
     pub remove_hooks: bool,
@@ -38,9 +46,21 @@ pub struct UninitArgs {
     /// Override any hooks or files locked by a "# XET LOCK" comment, removing them as well.
     #[clap(long)]
     pub ignore_locks: bool,
+
+    /// Convert the repository back to plain git: materialize every pointer
+    /// file and commit the content of the files in place of the pointers,
+    /// then do a full uninit and check that git works without git-xet.
+    /// Earlier commits keep their pointer files, and the git-xet notes
+    /// needed to smudge them are kept unless --purge-notes is given.
+    #[clap(long)]
+    pub restore_plain_git: bool,
 }
 
 pub async fn uninit_command(config: XetConfig, args: &UninitArgs) -> Result<()> {
+    if args.restore_plain_git {
+        return restore_plain_git(config, args).await;
+    }
+
     let repo = GitXetRepo::open(config)?;
 
     // If the user has specified one of these flags, just do that.  Otherwise, do the default.
@@ -51,7 +71,8 @@ pub async fn uninit_command(config: XetConfig, args: &UninitArgs) -> Result<()>
         || args.purge_xet_data_dir
         || args.purge_xet_config
         || args.purge_filter_config
-        || args.purge_fetch_config)
+        || args.purge_fetch_config
+        || args.purge_notes)
     {
         args.remove_hooks = true;
         args.purge_fetch_config = true;
@@ -61,3 +82,145 @@ pub async fn uninit_command(config: XetConfig, args: &UninitArgs) -> Result<()>
 
     Ok(())
 }
+
+async fn restore_plain_git(config: XetConfig, args: &UninitArgs) -> Result<()> {
+    let repo = GitXetRepo::open(config.clone())?;
+    if !repo.repo_is_clean()? {
+        return Err(GitXetRepoError::InvalidOperation(
+            "Repository must be clean to convert it to plain git; commit or stash any changes and rerun.".to_string(),
+        ));
+    }
+
+    // Everything is materialized before anything is removed, as the
+    // MerkleDB and the CAS config are needed to do so.
+    materialize_all(config).await?;
+    let remaining = pointer_files_in_working_tree(&repo)?;
+    if let Some(path) = remaining.first() {
+        return Err(GitXetRepoError::InvalidOperation(format!(
+            "{} pointer files could not be materialized, e.g. {path}; nothing was removed. Rerun once they can be.",
+            remaining.len()
+        )));
+    }
+
+    let args = UninitArgs {
+        full: true,
+        ..args.clone()
+    };
+    repo.uninstall_xet_from_local_repo(&args)?;
+
+    // Without the xet filter, adding the files again stores their content.
+    repo.run_git_checked_in_repo("add", &["--renormalize", "--", "."])?;
+    if !repo.repo_is_clean()? {
+        repo.run_git_checked_in_repo(
+            "commit",
+            &[
+                "-m",
+                "Converted git-xet pointer files to the content of the files.",
+            ],
+        )?;
+    }
+
+    let problems = plain_git_problems(&repo)?;
+    if !problems.is_empty() {
+        for problem in &problems {
+            eprintln!("  {problem}");
+        }
+        return Err(GitXetRepoError::Other(
+            "The repository still depends on git-xet; see the problems above.".to_string(),
+        ));
+    }
+    eprintln!("The repository is now a plain git repository.");
+    Ok(())
+}
+
+/// The files of the index still pointer files in the working tree.
+fn pointer_files_in_working_tree(repo: &GitXetRepo) -> Result<Vec<String>> {
+    let index = repo.repo.index()?;
+    Ok(index
+        .iter()
+        .map(|entry| String::from_utf8_lossy(&entry.path).into_owned())
+        .filter(|path| materialized_pointer(&repo.repo_dir.join(path)).is_some())
+        .collect())
+}
+
+/// What is left of git-xet in a repository converted to plain git.
+fn plain_git_problems(repo: &GitXetRepo) -> Result<Vec<String>> {
+    let mut problems = Vec::new();
+
+    let pointer_files = repo.pointer_files_by_path("HEAD")?.unwrap_or_default();
+    if !pointer_files.is_empty() {
+        problems.push(format!("{} pointer files at HEAD", pointer_files.len()));
+    }
+
+    let (_, attributes, _) = repo.run_git_in_repo(
+        "grep",
+        &[
+            "-l",
+            "filter=xet",
+            "HEAD",
+            "--",
+            ".gitattributes",
+            "*/.gitattributes",
+        ],
+    )?;
+    for path in attributes.lines() {
+        problems.push(format!("the xet filter is still set in {path}"));
+    }
+
+    for hook in repo.hooks_status()? {
+        if hook.state != HookState::NotInstalled {
+            problems.push(format!("the {} hook still calls git-xet", hook.name));
+        }
+    }
+
+    let (_, status, _) = repo.run_git_in_repo("status", &["--porcelain"])?;
+    if !status.trim().is_empty() {
+        problems.push("git status shows changes after the conversion".to_string());
+    }
+
+    if let Err(e) = repo.run_git_checked_in_repo("fsck", &["--connectivity-only", "--no-progress"])
+    {
+        problems.push(format!("git fsck failed: {e}"));
+    }
+
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git_integration::git_repo_test_tools::TestRepo;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_plain_git_problems() -> Result<()> {
+        let tr = TestRepo::new()?;
+        tr.write_file("data.bin", 0, 100)?;
+        tr.repo.run_git_checked_in_repo("add", &["data.bin"])?;
+        tr.repo
+            .run_git_checked_in_repo("commit", &["-m", "Add data."])?;
+        assert!(plain_git_problems(&tr.repo)?.is_empty());
+
+        // Hooks and filter attributes left are found; notes are kept for
+        // the earlier commits.
+        tr.repo.write_prepush_hook()?;
+        std::fs::write(
+            tr.repo.repo_dir.join(".gitattributes"),
+            "* filter=xet -text\n",
+        )?;
+        tr.repo
+            .run_git_checked_in_repo("add", &[".gitattributes"])?;
+        tr.repo
+            .run_git_checked_in_repo("commit", &["-m", "Add attributes."])?;
+        tr.repo.run_git_checked_in_repo(
+            "notes",
+            &["--ref=refs/notes/xet/merkledb", "add", "-m", "x", "HEAD"],
+        )?;
+        let problems = plain_git_problems(&tr.repo)?;
+        assert_eq!(problems.len(), 2, "{problems:?}");
+
+        tr.repo.uninstall_gitxet_hooks(false)?;
+        tr.repo.purge_xet_notes()?;
+        assert!(tr.repo.xet_notes_refs()?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+use cas_client::{CasClientError, Staging};
+use clap::Args;
+use merklehash::MerkleHash;
+use parutils::tokio_par_for_each;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use crate::config::XetConfig;
+use crate::constants::MAX_CONCURRENT_UPLOADS;
+use crate::data::PointerFileTranslator;
+use crate::errors::{convert_parallel_error, GitXetRepoError, Result};
+use crate::git_integration::GitXetRepo;
+
+/// Checks that the remote CAS stores every block of the files at the pushed
+/// refs, so that collaborators can smudge them.
+///
+/// Run after pushing. Blocks still staged locally are uploaded again with
+/// --repair:
+///
+/// ```ignore
+/// git push origin main
+/// git xet verify-push --repair
+/// ```
+#[derive(Args, Debug)]
+pub struct VerifyPushArgs {
+    /// The refs to check. Defaults to the remote-tracking branch of the
+    /// branch checked out.
+    refs: Vec<String>,
+
+    /// The remote whose tracking branch is checked by default.
+    #[clap(long, default_value = "origin")]
+    remote: String,
+
+    /// Upload the missing blocks that are still staged locally, then check
+    /// again.
+    #[clap(long)]
+    repair: bool,
+}
+
+pub async fn verify_push_command(cfg: XetConfig, args: &VerifyPushArgs) -> Result<()> {
+    let repo = GitXetRepo::open(cfg.clone())?;
+    let refs = if args.refs.is_empty() {
+        let head = repo.repo.head()?;
+        let branch = head
+            .shorthand()
+            .filter(|_| head.is_branch())
+            .ok_or_else(|| {
+                GitXetRepoError::InvalidOperation("HEAD is detached; name the refs to check".into())
+            })?;
+        vec![format!("refs/remotes/{}/{branch}", args.remote)]
+    } else {
+        args.refs.clone()
+    };
+
+    let mut files = HashMap::new();
+    for reference in &refs {
+        if repo.repo.revparse_single(reference).is_err() {
+            return Err(GitXetRepoError::InvalidOperation(format!(
+                "{reference} does not exist; has it been pushed?"
+            )));
+        }
+        files.extend(repo.pointer_files_at_ref(reference)?);
+    }
+
+    // The files referencing each block, to report what a missing one breaks.
+    let translator = PointerFileTranslator::from_config_in_repo(&cfg).await?;
+    let translator = &translator;
+    let file_blocks = tokio_par_for_each(
+        files.into_iter().collect(),
+        MAX_CONCURRENT_UPLOADS,
+        |(hash, path): (MerkleHash, String), _| async move {
+            let blocks = translator
+                .derive_blocks(&hash)
+                .await
+                .map_err(|e| GitXetRepoError::Other(format!("Unable to read {path}: {e}")))?;
+            Ok::<_, GitXetRepoError>((path, blocks))
+        },
+    )
+    .await
+    .map_err(convert_parallel_error)?;
+    let mut blocks = BTreeMap::<MerkleHash, Vec<String>>::new();
+    for (path, ranges) in file_blocks {
+        for range in ranges {
+            let paths = blocks.entry(range.hash).or_default();
+            if paths.last() != Some(&path) {
+                paths.push(path.clone());
+            }
+        }
+    }
+    eprintln!(
+        "Checking {} blocks at {} on the remote CAS",
+        blocks.len(),
+        refs.join(", ")
+    );
+
+    let cas = repo.get_staging_cas().await?;
+    let prefix = translator.get_prefix();
+    let mut missing = missing_blocks(&cas, &prefix, blocks.keys().copied().collect()).await?;
+    if !missing.is_empty() && args.repair {
+        eprintln!(
+            "{} blocks are missing; uploading the staged blocks",
+            missing.len()
+        );
+        repo.upload_all_staged().await?;
+        missing = missing_blocks(&cas, &prefix, missing).await?;
+    }
+
+    if missing.is_empty() {
+        println!("All {} blocks are stored on the remote.", blocks.len());
+        return Ok(());
+    }
+    let mut affected = BTreeMap::<&str, usize>::new();
+    for hash in &missing {
+        for path in &blocks[hash] {
+            *affected.entry(path).or_default() += 1;
+        }
+    }
+    println!("{} blocks are missing from the remote:", missing.len());
+    for (path, count) in &affected {
+        println!("  {path} ({count} blocks)");
+    }
+    let retry = if args.repair {
+        "They are not staged on this machine; push them from the machine the files were added on, \
+         with git xet verify-push --repair"
+    } else {
+        "Run git xet verify-push --repair to upload the ones staged on this machine"
+    };
+    Err(GitXetRepoError::IntegrityCheckFailed(format!(
+        "{} files at {} can't be smudged from the remote. {retry}.",
+        affected.len(),
+        refs.join(", ")
+    )))
+}
+
+/// The blocks the remote CAS doesn't store, queried concurrently.
+async fn missing_blocks(
+    cas: &Arc<dyn Staging + Send + Sync>,
+    prefix: &str,
+    hashes: Vec<MerkleHash>,
+) -> Result<Vec<MerkleHash>> {
+    let present = tokio_par_for_each(hashes, MAX_CONCURRENT_UPLOADS, |hash, _| async move {
+        match cas.get_length_remote(prefix, &hash).await {
+            Ok(n) => Ok((hash, n > 0)),
+            Err(CasClientError::XORBNotFound(_)) => Ok((hash, false)),
+            Err(e) => Err(GitXetRepoError::from(e)),
+        }
+    })
+    .await
+    .map_err(convert_parallel_error)?;
+    Ok(present
+        .into_iter()
+        .filter(|(_, stored)| !stored)
+        .map(|(hash, _)| hash)
+        .collect())
+}
@@ -0,0 +1,103 @@
+use clap::Args;
+use notify::{RecursiveMode, Watcher};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, RwLock};
+use tracing::{info, warn};
+
+use crate::config::XetConfig;
+use crate::constants::WATCH_SOCKET_SUBDIR;
+use crate::errors::{GitXetRepoError, Result};
+use crate::git_integration::GitXetRepo;
+use crate::watch::{rpc, WatchModel};
+
+#[derive(Args, Debug)]
+pub struct WatchArgs {
+    /// The Unix socket to serve JSON-RPC requests on. Defaults to
+    /// .git/xet/watch.sock.
+    #[clap(long)]
+    socket: Option<PathBuf>,
+
+    /// Milliseconds to wait for a burst of filesystem changes to settle
+    /// before updating the summary.
+    #[clap(long, default_value = "200")]
+    debounce_ms: u64,
+}
+
+pub async fn watch_command(cfg: XetConfig, args: &WatchArgs) -> Result<()> {
+    let repo = GitXetRepo::open(cfg)?;
+    let root = repo.repo_dir.canonicalize()?;
+    let socket_path = args
+        .socket
+        .clone()
+        .unwrap_or_else(|| repo.git_dir.join(WATCH_SOCKET_SUBDIR));
+
+    let model = Arc::new(RwLock::new(WatchModel::scan(&root)?));
+    eprintln!(
+        "Watching {root:?} ({} files); serving on {socket_path:?}",
+        model.read().await.status().files
+    );
+
+    // The notify callback runs on its own thread, so hand events to the
+    // async side over a channel.
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let _ = tx.send(event);
+    })
+    .map_err(|e| GitXetRepoError::Other(format!("Error starting file watcher: {e}")))?;
+    watcher
+        .watch(&root, RecursiveMode::Recursive)
+        .map_err(|e| GitXetRepoError::Other(format!("Error watching {root:?}: {e}")))?;
+
+    let mut server = tokio::spawn({
+        let socket_path = socket_path.clone();
+        let model = model.clone();
+        async move { rpc::serve(&socket_path, model).await }
+    });
+
+    let debounce = Duration::from_millis(args.debounce_ms);
+    let ret = loop {
+        tokio::select! {
+            event = rx.recv() => {
+                let Some(event) = event else {
+                    break Err(GitXetRepoError::Other("File watcher stopped".to_string()));
+                };
+                // Collect the rest of the burst so each path is rescanned once.
+                tokio::time::sleep(debounce).await;
+                let mut events = vec![event];
+                while let Ok(event) = rx.try_recv() {
+                    events.push(event);
+                }
+
+                let mut paths: Vec<PathBuf> = Vec::new();
+                for event in events {
+                    match event {
+                        Ok(event) => paths.extend(event.paths),
+                        Err(e) => warn!("XET watch: {e}"),
+                    }
+                }
+                paths.sort();
+                paths.dedup();
+
+                let mut model = model.write().await;
+                for path in paths {
+                    model.update_path(&path);
+                }
+                info!("XET watch: updated {} files", model.status().files);
+            }
+            res = &mut server => {
+                break res
+                    .map_err(|e| GitXetRepoError::Other(format!("Watch server failed: {e}")))
+                    .and_then(|r| r);
+            }
+            res = tokio::signal::ctrl_c() => {
+                break res.map_err(GitXetRepoError::from);
+            }
+        }
+    };
+
+    server.abort();
+    let _ = std::fs::remove_file(&socket_path);
+    ret
+}
@@ -0,0 +1,47 @@
+use crate::config::ConfigError;
+use std::path::PathBuf;
+use xet_config::Audit;
+
+/// The size at which the audit log is rotated if not configured.
+const DEFAULT_AUDIT_MAX_SIZE: u64 = 100 * 1024 * 1024;
+/// The number of rotated audit logs kept if not configured.
+const DEFAULT_AUDIT_MAX_FILES: usize = 5;
+
+#[derive(Debug, Clone)]
+pub struct AuditSettings {
+    /// The file audit records are appended to. Auditing is off if unset.
+    pub path: Option<PathBuf>,
+    pub max_size: u64,
+    pub max_files: usize,
+}
+
+impl Default for AuditSettings {
+    fn default() -> Self {
+        Self {
+            path: None,
+            max_size: DEFAULT_AUDIT_MAX_SIZE,
+            max_files: DEFAULT_AUDIT_MAX_FILES,
+        }
+    }
+}
+
+impl AuditSettings {
+    pub fn enabled(&self) -> bool {
+        self.path.is_some()
+    }
+}
+
+impl TryFrom<Option<&Audit>> for AuditSettings {
+    type Error = ConfigError;
+
+    fn try_from(audit: Option<&Audit>) -> Result<Self, Self::Error> {
+        let Some(audit) = audit else {
+            return Ok(AuditSettings::default());
+        };
+        Ok(AuditSettings {
+            path: audit.path.clone().filter(|p| !p.as_os_str().is_empty()),
+            max_size: audit.max_size.unwrap_or(DEFAULT_AUDIT_MAX_SIZE),
+            max_files: audit.max_files.unwrap_or(DEFAULT_AUDIT_MAX_FILES),
+        })
+    }
+}
@@ -1,6 +1,8 @@
 use crate::config;
 use crate::config::ConfigError;
-use crate::config::ConfigError::{CachePathNotDir, CachePathReadOnly, InvalidCachePath};
+use crate::config::ConfigError::{
+    CachePathNotDir, CachePathReadOnly, InvalidCacheMemory, InvalidCachePath,
+};
 use config::util::{can_write, is_empty};
 use std::fs;
 use std::path::PathBuf;
@@ -11,6 +13,8 @@ pub struct CacheSettings {
     pub path: PathBuf,
     pub size: u64,
     pub enabled: bool,
+    /// Size in bytes of the blocks held in memory; 0 if none are.
+    pub memory: u64,
     // TODO: This should not be optional and the default should be applied at config parsing time,
     //       not inferrred by the underlying CacheClient library.
     pub blocksize: Option<u64>,
@@ -55,6 +59,12 @@ impl TryFrom<Option<&Cache>> for CacheSettings {
                     path,
                     size,
                     enabled,
+                    memory: cache
+                        .memory
+                        .as_deref()
+                        .map(parse_size)
+                        .transpose()?
+                        .unwrap_or_default(),
                     blocksize: cache.blocksize,
                 }
             }
@@ -63,6 +73,31 @@ impl TryFrom<Option<&Cache>> for CacheSettings {
     }
 }
 
+/// Parses a size in bytes with an optional unit, e.g. 2GB or 512MiB.
+pub(crate) fn parse_size(size: &str) -> Result<u64, ConfigError> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "tb" => 1_000_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        _ => return Err(InvalidCacheMemory(size.to_string())),
+    };
+    let number: f64 = number
+        .parse()
+        .map_err(|_| InvalidCacheMemory(size.to_string()))?;
+    Ok((number * multiplier as f64) as u64)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -76,6 +111,7 @@ mod tests {
         let cache_cfg = Cache {
             path: Some(path.clone()),
             size: Some(1024000),
+            memory: None,
             blocksize: Some(1024),
         };
 
@@ -99,6 +135,7 @@ mod tests {
         let cache_cfg = Cache {
             path: Some(path),
             size: Some(0),
+            memory: None,
             blocksize: Some(1024),
         };
 
@@ -111,6 +148,7 @@ mod tests {
         let cache_cfg = Cache {
             path: Some(PathBuf::default()),
             size: Some(1024000),
+            memory: None,
             blocksize: Some(1024),
         };
 
@@ -125,9 +163,30 @@ mod tests {
         let cache_cfg = Cache {
             path: Some(path),
             size: Some(1024000),
+            memory: None,
             blocksize: Some(1024),
         };
 
         assert_err!(CacheSettings::try_from(Some(&cache_cfg)));
     }
+
+    #[test]
+    fn test_parse_cache_memory() {
+        let tmpdir = TempDir::new().unwrap();
+        let cache_cfg = Cache {
+            path: Some(tmpdir.path().to_path_buf()),
+            size: Some(1024000),
+            memory: Some("2GB".to_string()),
+            blocksize: Some(1024),
+        };
+        let cache_settings = CacheSettings::try_from(Some(&cache_cfg)).unwrap();
+        assert_eq!(2_000_000_000, cache_settings.memory);
+
+        assert_eq!(512 << 20, parse_size("512MiB").unwrap());
+        assert_eq!(1_500_000, parse_size("1.5 mb").unwrap());
+        assert_eq!(4096, parse_size("4096").unwrap());
+        assert_eq!(0, parse_size("0").unwrap());
+        assert_err!(parse_size("2 gigs"));
+        assert_err!(parse_size("GB"));
+    }
 }
@@ -94,7 +94,7 @@ impl TryFrom<Option<&Cas>> for CasSettings {
     }
 }
 
-fn check_uri(endpoint: &str) -> Result<(), ConfigError> {
+pub(crate) fn check_uri(endpoint: &str) -> Result<(), ConfigError> {
     match endpoint.strip_prefix(LOCAL_CAS_SCHEME) {
         Some(path) => {
             PathBuf::from_str(path)
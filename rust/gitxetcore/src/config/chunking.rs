@@ -0,0 +1,19 @@
+use crate::config::ConfigError;
+use xet_config::Chunking;
+
+/// How files are chunked when cleaned.
+#[derive(Debug, Clone, Default)]
+pub struct ChunkingSettings {
+    /// Whether archives are chunked along the entries in them.
+    pub format_aware: bool,
+}
+
+impl TryFrom<Option<&Chunking>> for ChunkingSettings {
+    type Error = ConfigError;
+
+    fn try_from(chunking: Option<&Chunking>) -> Result<Self, Self::Error> {
+        Ok(ChunkingSettings {
+            format_aware: chunking.and_then(|c| c.formataware).unwrap_or(false),
+        })
+    }
+}
@@ -0,0 +1,26 @@
+use crate::config::ConfigError;
+use xet_config::Control;
+
+#[derive(Debug, Clone, Default)]
+pub struct ControlSettings {
+    /// Whether mounts and the filter process serve the JSON-RPC control
+    /// socket.
+    pub enabled: bool,
+    /// The initial limit in bytes per second on data transferred to and
+    /// from the CAS, or None if unlimited.
+    pub bandwidth_limit: Option<u64>,
+}
+
+impl TryFrom<Option<&Control>> for ControlSettings {
+    type Error = ConfigError;
+
+    fn try_from(control: Option<&Control>) -> Result<Self, Self::Error> {
+        Ok(match control {
+            Some(control) => ControlSettings {
+                enabled: control.enabled.unwrap_or(false),
+                bandwidth_limit: control.bandwidth_limit.filter(|&l| l > 0),
+            },
+            None => ControlSettings::default(),
+        })
+    }
+}
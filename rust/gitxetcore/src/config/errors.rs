@@ -52,9 +52,69 @@ pub enum ConfigError {
     #[error("cache.path: {0} is not writeable")]
     CachePathReadOnly(PathBuf),
 
+    #[error("cache.memory: {0} is not a size, e.g. 2GB or 512MiB")]
+    InvalidCacheMemory(String),
+
+    #[error("staging.path: {0} couldn't be created: {1}")]
+    InvalidStagingPath(PathBuf, io::Error),
+
+    #[error("staging.path: {0} is not a directory")]
+    StagingPathNotDirectory(PathBuf),
+
+    #[error("staging.path: {0} is not writeable")]
+    StagingPathReadOnly(PathBuf),
+
+    #[error("staging.minfree: {0} is not a size, e.g. 10GB or 512MiB")]
+    InvalidStagingMinFree(String),
+
+    #[error("summaries: {0} is not a pathspec optionally followed by >size, e.g. *.bin>1GB")]
+    InvalidSummariesExclude(String),
+
+    #[error("summaries.typedetector: {0} is not one of {{'extension'|'signature'}}")]
+    InvalidTypeDetector(String),
+
     #[error("log.level: {0} is not one of {{'error'|'warn'|'info'|'debug'|'trace'}}")]
     InvalidLogLevel(String),
 
+    #[error("io.backend: {0} is not one of {{'auto'|'std'|'io_uring'}}")]
+    InvalidIoBackend(String),
+
+    #[error("integrity.verify: {0} is not one of {{'always'|'never'|'on_push'}}")]
+    InvalidIntegrityVerify(String),
+
+    #[error("p2p.advertise: {0} is not of the form host:port")]
+    InvalidP2pAdvertise(String),
+
+    #[error("fallback.mirrors: {0} is not of the form <prefix>=<mirror>")]
+    InvalidFallbackMirror(String),
+
+    #[error("mirror.remotes: {0} is not of the form <name>=<endpoint>")]
+    InvalidCasRemote(String),
+
+    #[error("mirror.remotes: the CAS remote {0} already exists")]
+    DuplicateCasRemote(String),
+
+    #[error("mirror.replication: {0} is not one of {{'all'|'async'}}")]
+    InvalidMirrorReplication(String),
+
+    #[error("mirror.fetch: {0} is neither primary nor a remote in mirror.remotes")]
+    UnknownCasRemote(String),
+
+    #[error("pack.readsize: {0} is not a positive number of bytes")]
+    InvalidPackReadSize(u64),
+
+    #[error("shard.hash_algorithm: {0} is not one of {{'xet'|'blake3'|'sha256'}}")]
+    InvalidHashAlgorithm(String),
+
+    #[error("quota.check: {0} is not one of {{'off'|'warn'|'error'}}")]
+    InvalidQuotaCheck(String),
+
+    #[error("signing.format: {0} is not one of {{'ssh'|'gpg'}}")]
+    InvalidSignatureFormat(String),
+
+    #[error("upload.{0} must be at least 1")]
+    InvalidUploadConcurrency(String),
+
     #[error("log.path: {0} is not a file")]
     LogPathNotFile(PathBuf),
 
@@ -106,6 +166,12 @@ pub enum ConfigError {
     #[error("axe.enabled: {0} invalid. Valid inputs are true / false")]
     InvalidAxeEnabled(String),
 
+    #[error("usage: {0}")]
+    InvalidUsageSetting(String),
+
+    #[error("tier: {0}")]
+    InvalidTierSetting(String),
+
     #[error("Could not find profile: {0} in config")]
     ProfileNotFound(String),
 
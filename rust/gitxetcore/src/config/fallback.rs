@@ -0,0 +1,80 @@
+use crate::config::ConfigError;
+use crate::config::ConfigError::InvalidFallbackMirror;
+use xet_config::Fallback;
+
+#[derive(Debug, Clone, Default)]
+pub struct FallbackSettings {
+    /// Whether blocks missing from CAS are rebuilt from the recorded sources
+    /// of the files.
+    pub enabled: bool,
+    /// The source URL prefixes rewritten to mirrors, in the order given.
+    pub mirrors: Vec<(String, String)>,
+}
+
+impl FallbackSettings {
+    /// The URL to download a recorded source from: the source rewritten by
+    /// the first mirror whose prefix it starts with.
+    pub fn resolve(&self, source: &str) -> String {
+        self.mirrors
+            .iter()
+            .find_map(|(prefix, mirror)| {
+                source
+                    .strip_prefix(prefix.as_str())
+                    .map(|rest| format!("{mirror}{rest}"))
+            })
+            .unwrap_or_else(|| source.to_string())
+    }
+}
+
+impl TryFrom<Option<&Fallback>> for FallbackSettings {
+    type Error = ConfigError;
+
+    fn try_from(fallback: Option<&Fallback>) -> Result<Self, Self::Error> {
+        let Some(fallback) = fallback else {
+            return Ok(FallbackSettings::default());
+        };
+        let mirrors = fallback
+            .mirrors
+            .iter()
+            .flatten()
+            .map(|m| {
+                m.split_once('=')
+                    .filter(|(prefix, _)| !prefix.is_empty())
+                    .map(|(prefix, mirror)| (prefix.to_string(), mirror.to_string()))
+                    .ok_or_else(|| InvalidFallbackMirror(m.clone()))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(FallbackSettings {
+            enabled: fallback.enabled.unwrap_or(false),
+            mirrors,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_mirror() {
+        let settings = FallbackSettings::try_from(Some(&Fallback {
+            enabled: Some(true),
+            mirrors: Some(vec![
+                "s3://datasets/=https://mirror.example.com/datasets/".to_string()
+            ]),
+        }))
+        .unwrap();
+        assert!(settings.enabled);
+        assert_eq!(
+            settings.resolve("s3://datasets/raw/a.csv"),
+            "https://mirror.example.com/datasets/raw/a.csv"
+        );
+        assert_eq!(settings.resolve("gs://other/a.csv"), "gs://other/a.csv");
+
+        assert!(FallbackSettings::try_from(Some(&Fallback {
+            enabled: None,
+            mirrors: Some(vec!["no-mirror".to_string()]),
+        }))
+        .is_err());
+    }
+}
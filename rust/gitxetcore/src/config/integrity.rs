@@ -0,0 +1,90 @@
+use crate::config::ConfigError;
+use crate::config::ConfigError::InvalidIntegrityVerify;
+use std::str::FromStr;
+use xet_config::Integrity;
+
+/// When the whole-file hash recorded in a pointer file is checked against
+/// the reconstructed contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntegrityVerify {
+    /// No hash is recorded at clean time and nothing is verified.
+    #[default]
+    Never,
+    /// The hash is recorded at clean time and every smudge verifies it.
+    Always,
+    /// The hash is recorded at clean time, and the files cleaned since the
+    /// last push are reconstructed and verified before the push proceeds.
+    OnPush,
+}
+
+impl IntegrityVerify {
+    /// Whether the whole-file hash should be computed and stored when cleaning.
+    pub fn records_hash(&self) -> bool {
+        !matches!(self, IntegrityVerify::Never)
+    }
+}
+
+impl FromStr for IntegrityVerify {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "always" => Ok(IntegrityVerify::Always),
+            "never" | "" => Ok(IntegrityVerify::Never),
+            "on_push" | "onpush" => Ok(IntegrityVerify::OnPush),
+            _ => Err(InvalidIntegrityVerify(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IntegritySettings {
+    pub verify: IntegrityVerify,
+}
+
+impl TryFrom<Option<&Integrity>> for IntegritySettings {
+    type Error = ConfigError;
+
+    fn try_from(integrity: Option<&Integrity>) -> Result<Self, Self::Error> {
+        Ok(match integrity.and_then(|i| i.verify.as_ref()) {
+            Some(verify) => IntegritySettings {
+                verify: verify.parse()?,
+            },
+            None => IntegritySettings::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_verify_mode() {
+        assert_eq!(
+            "always".parse::<IntegrityVerify>().unwrap(),
+            IntegrityVerify::Always
+        );
+        assert_eq!(
+            "NEVER".parse::<IntegrityVerify>().unwrap(),
+            IntegrityVerify::Never
+        );
+        assert_eq!(
+            "on_push".parse::<IntegrityVerify>().unwrap(),
+            IntegrityVerify::OnPush
+        );
+        assert!("sometimes".parse::<IntegrityVerify>().is_err());
+    }
+
+    #[test]
+    fn test_settings_default_to_never() {
+        let settings = IntegritySettings::try_from(None).unwrap();
+        assert_eq!(settings.verify, IntegrityVerify::Never);
+
+        let cfg = Integrity {
+            verify: Some("always".to_string()),
+        };
+        let settings = IntegritySettings::try_from(Some(&cfg)).unwrap();
+        assert_eq!(settings.verify, IntegrityVerify::Always);
+    }
+}
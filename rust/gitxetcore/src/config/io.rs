@@ -0,0 +1,54 @@
+use crate::config::ConfigError;
+use crate::config::ConfigError::InvalidIoBackend;
+use cas::fileio::IoBackend;
+use xet_config::Io;
+
+#[derive(Debug, Clone, Default)]
+pub struct IoSettings {
+    /// Whether large files are materialized with preallocation and positioned
+    /// writes rather than sequentially through a buffered writer.
+    pub preallocate: bool,
+    /// The backend requested for file reads and writes.
+    pub backend: IoBackend,
+}
+
+impl TryFrom<Option<&Io>> for IoSettings {
+    type Error = ConfigError;
+
+    fn try_from(io: Option<&Io>) -> Result<Self, Self::Error> {
+        Ok(match io {
+            Some(io) => IoSettings {
+                preallocate: io.preallocate.unwrap_or(false),
+                backend: match io.backend.as_deref() {
+                    Some(backend) => backend.parse().map_err(InvalidIoBackend)?,
+                    None => IoBackend::default(),
+                },
+            },
+            None => IoSettings::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_io_backend() {
+        let io = Io {
+            backend: Some("std".to_string()),
+            ..Default::default()
+        };
+        let settings = IoSettings::try_from(Some(&io)).unwrap();
+        assert_eq!(settings.backend, IoBackend::Std);
+
+        let settings = IoSettings::try_from(None).unwrap();
+        assert_eq!(settings.backend, IoBackend::Auto);
+
+        let io = Io {
+            backend: Some("aio".to_string()),
+            ..Default::default()
+        };
+        assert!(IoSettings::try_from(Some(&io)).is_err());
+    }
+}
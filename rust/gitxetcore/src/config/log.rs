@@ -2,7 +2,7 @@ use crate::config;
 use crate::config::util::get_sanitized_invocation_command;
 use crate::config::ConfigError;
 use crate::config::ConfigError::{LogPathNotFile, LogPathReadOnly};
-use crate::constants::XET_PROGRAM_NAME;
+use crate::constants::{LOG_DIR_SUBDIR, XET_PROGRAM_NAME};
 use atty::Stream;
 use chrono::Utc;
 use std::path::{Path, PathBuf};
@@ -36,6 +36,31 @@ impl From<Option<&str>> for LogFormat {
     }
 }
 
+/// Where each command writes its own log file.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum LogDir {
+    /// .git/xet/logs, when in a repository.
+    #[default]
+    Repo,
+    Path(PathBuf),
+    Disabled,
+}
+
+impl LogDir {
+    /// The directory of the log files, given the .git directory of the
+    /// repository if in one.
+    pub fn resolve(&self, git_path: Option<&Path>) -> Option<PathBuf> {
+        match self {
+            LogDir::Repo => git_path.map(|p| p.join(LOG_DIR_SUBDIR)),
+            LogDir::Path(path) => Some(path.clone()),
+            LogDir::Disabled => None,
+        }
+    }
+}
+
+/// The number of log files kept in the log directory by default.
+const DEFAULT_LOG_MAX_FILES: usize = 50;
+
 #[derive(Debug, Clone)]
 pub struct LogSettings {
     pub level: Level,
@@ -44,6 +69,8 @@ pub struct LogSettings {
     pub with_tracer: bool,
     pub silent_summary: bool,
     pub exceptions: bool,
+    pub dir: LogDir,
+    pub max_files: usize,
 }
 
 impl Default for LogSettings {
@@ -55,6 +82,8 @@ impl Default for LogSettings {
             with_tracer: false,
             silent_summary: false,
             exceptions: false,
+            dir: LogDir::Repo,
+            max_files: DEFAULT_LOG_MAX_FILES,
         }
     }
 }
@@ -117,6 +146,12 @@ impl TryFrom<Option<&Log>> for LogSettings {
                 let with_tracer = log.tracing.unwrap_or(false);
                 let silent_summary = log.silentsummary.unwrap_or(false);
                 let exceptions = log.exceptions.unwrap_or(false);
+                let dir = match log.dir.as_ref() {
+                    Some(dir) if config::util::is_empty(dir) => LogDir::Disabled,
+                    Some(dir) => LogDir::Path(dir.clone()),
+                    None => LogDir::Repo,
+                };
+                let max_files = log.maxfiles.unwrap_or(DEFAULT_LOG_MAX_FILES);
                 LogSettings {
                     level,
                     path,
@@ -124,6 +159,8 @@ impl TryFrom<Option<&Log>> for LogSettings {
                     with_tracer,
                     silent_summary,
                     exceptions,
+                    dir,
+                    max_files,
                 }
             }
             None => LogSettings::default(),
@@ -205,6 +242,8 @@ mod tests {
             tracing: None,
             silentsummary: None,
             exceptions: None,
+            dir: None,
+            maxfiles: None,
         };
 
         let log_settings = LogSettings::try_from(Some(&log_cfg)).unwrap();
@@ -239,6 +278,36 @@ mod tests {
         assert_eq!(Level::WARN, log_settings.level);
         assert_eq!(LogFormat::Compact, log_settings.format);
         assert!(!log_settings.with_tracer);
+        assert_eq!(LogDir::Repo, log_settings.dir);
+    }
+
+    #[test]
+    fn test_parse_dir() {
+        let git_path = Path::new("/repo/.git");
+        let log_settings = LogSettings::try_from(Some(&Log::default())).unwrap();
+        assert_eq!(
+            log_settings.dir.resolve(Some(git_path)),
+            Some(git_path.join("xet").join("logs"))
+        );
+        assert_eq!(log_settings.dir.resolve(None), None);
+        assert_eq!(log_settings.max_files, DEFAULT_LOG_MAX_FILES);
+
+        let log_cfg = Log {
+            dir: Some("/tmp/xet-logs".into()),
+            maxfiles: Some(3),
+            ..Default::default()
+        };
+        let log_settings = LogSettings::try_from(Some(&log_cfg)).unwrap();
+        assert_eq!(log_settings.dir.resolve(None), Some("/tmp/xet-logs".into()));
+        assert_eq!(log_settings.max_files, 3);
+
+        let log_cfg = Log {
+            dir: Some("".into()),
+            ..Default::default()
+        };
+        let log_settings = LogSettings::try_from(Some(&log_cfg)).unwrap();
+        assert_eq!(log_settings.dir, LogDir::Disabled);
+        assert_eq!(log_settings.dir.resolve(Some(git_path)), None);
     }
 
     #[test]
@@ -252,6 +321,8 @@ mod tests {
             tracing: None,
             silentsummary: None,
             exceptions: None,
+            dir: None,
+            maxfiles: None,
         };
 
         assert_err!(LogSettings::try_from(Some(&log_cfg)));
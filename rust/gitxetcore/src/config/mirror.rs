@@ -0,0 +1,161 @@
+use crate::config::cas::check_uri;
+use crate::config::ConfigError;
+use crate::config::ConfigError::{
+    DuplicateCasRemote, InvalidCasRemote, InvalidMirrorReplication, UnknownCasRemote,
+};
+use cas_client::Replication;
+use xet_config::Mirror;
+
+/// The name of the remote at cas.server among the CAS remotes.
+pub const PRIMARY_CAS_REMOTE: &str = "primary";
+
+const DEFAULT_HEDGE_INFLIGHT: usize = 16;
+
+/// A CAS remote the data is replicated to, besides cas.server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CasRemote {
+    pub name: String,
+    pub endpoint: String,
+}
+
+impl CasRemote {
+    /// Parses a remote of mirror.remotes, of the form <name>=<endpoint>.
+    pub fn parse(remote: &str) -> Result<Self, ConfigError> {
+        let (name, endpoint) = remote
+            .split_once('=')
+            .filter(|(name, endpoint)| !name.is_empty() && !endpoint.is_empty())
+            .ok_or_else(|| InvalidCasRemote(remote.to_string()))?;
+        check_uri(endpoint)?;
+        Ok(CasRemote {
+            name: name.to_string(),
+            endpoint: endpoint.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for CasRemote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.name, self.endpoint)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MirrorSettings {
+    pub remotes: Vec<CasRemote>,
+    pub replication: Replication,
+    /// The remote reads start from; None for the primary.
+    pub fetch: Option<String>,
+    /// The most hedged reads in flight at once; 0 if reads aren't hedged.
+    pub hedge_inflight: usize,
+}
+
+impl Default for MirrorSettings {
+    fn default() -> Self {
+        MirrorSettings {
+            remotes: Vec::new(),
+            replication: Replication::default(),
+            fetch: None,
+            hedge_inflight: DEFAULT_HEDGE_INFLIGHT,
+        }
+    }
+}
+
+impl MirrorSettings {
+    pub fn enabled(&self) -> bool {
+        !self.remotes.is_empty()
+    }
+}
+
+fn parse_replication(replication: &str) -> Result<Replication, ConfigError> {
+    match replication.to_lowercase().as_str() {
+        "all" | "" => Ok(Replication::All),
+        "async" => Ok(Replication::Async),
+        _ => Err(InvalidMirrorReplication(replication.to_string())),
+    }
+}
+
+impl TryFrom<Option<&Mirror>> for MirrorSettings {
+    type Error = ConfigError;
+
+    fn try_from(mirror: Option<&Mirror>) -> Result<Self, Self::Error> {
+        let Some(mirror) = mirror else {
+            return Ok(MirrorSettings::default());
+        };
+
+        let mut remotes: Vec<CasRemote> = Vec::new();
+        for remote in mirror.remotes.iter().flatten() {
+            let remote = CasRemote::parse(remote)?;
+            if remote.name == PRIMARY_CAS_REMOTE || remotes.iter().any(|r| r.name == remote.name) {
+                return Err(DuplicateCasRemote(remote.name));
+            }
+            remotes.push(remote);
+        }
+
+        let fetch = match mirror.fetch.as_deref() {
+            None | Some(PRIMARY_CAS_REMOTE) => None,
+            Some(name) if remotes.iter().any(|r| r.name == name) => Some(name.to_string()),
+            Some(name) => return Err(UnknownCasRemote(name.to_string())),
+        };
+
+        Ok(MirrorSettings {
+            remotes,
+            replication: mirror
+                .replication
+                .as_deref()
+                .map(parse_replication)
+                .transpose()?
+                .unwrap_or_default(),
+            fetch,
+            hedge_inflight: mirror.hedge_inflight.unwrap_or(DEFAULT_HEDGE_INFLIGHT),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mirror_settings() {
+        let mirror = Mirror {
+            remotes: Some(vec!["onprem=https://cas.internal:5000".to_string()]),
+            replication: Some("async".to_string()),
+            fetch: Some("onprem".to_string()),
+            hedge_inflight: Some(0),
+        };
+        let settings = MirrorSettings::try_from(Some(&mirror)).unwrap();
+        assert!(settings.enabled());
+        assert_eq!(
+            settings.remotes,
+            vec![CasRemote {
+                name: "onprem".to_string(),
+                endpoint: "https://cas.internal:5000".to_string(),
+            }]
+        );
+        assert_eq!(settings.replication, Replication::Async);
+        assert_eq!(settings.fetch.as_deref(), Some("onprem"));
+        assert_eq!(settings.hedge_inflight, 0);
+
+        let settings = MirrorSettings::try_from(None).unwrap();
+        assert!(!settings.enabled());
+        assert_eq!(settings.hedge_inflight, DEFAULT_HEDGE_INFLIGHT);
+
+        let invalid = |m: Mirror| MirrorSettings::try_from(Some(&m)).is_err();
+        assert!(invalid(Mirror {
+            remotes: Some(vec!["https://cas.internal:5000".to_string()]),
+            ..Default::default()
+        }));
+        assert!(invalid(Mirror {
+            remotes: Some(vec!["primary=https://cas.internal:5000".to_string()]),
+            ..Default::default()
+        }));
+        assert!(invalid(Mirror {
+            replication: Some("sometimes".to_string()),
+            ..Default::default()
+        }));
+        assert!(invalid(Mirror {
+            fetch: Some("onprem".to_string()),
+            ..Default::default()
+        }));
+    }
+}
@@ -1,26 +1,64 @@
 pub use self::cas::CasSettings;
+pub use audit::AuditSettings;
 pub use axe::AxeSettings;
 pub use cache::CacheSettings;
+pub use chunking::ChunkingSettings;
+pub use control::ControlSettings;
 pub use env::PROD_XETEA_DOMAIN;
 pub use errors::ConfigError;
+pub use fallback::FallbackSettings;
 pub use git_path::{remote_to_repo_info, ConfigGitPathOption, RepoInfo};
-pub use log::{LogFormat, LogSettings};
+pub use integrity::{IntegritySettings, IntegrityVerify};
+pub use io::IoSettings;
+pub use log::{LogDir, LogFormat, LogSettings};
+pub use mirror::{CasRemote, MirrorSettings, PRIMARY_CAS_REMOTE};
+pub use p2p::P2pSettings;
+pub use pack::PackSettings;
+pub use quota::{QuotaCheck, QuotaSettings};
+pub use retention::RetentionSettings;
+pub use shard::ShardSettings;
+pub use signing::{SignatureFormat, SigningSettings};
+pub use staging::StagingSettings;
+pub use store::StoreSettings;
+pub use summaries::{AnalyzerSettings, ExcludeRule, SummariesSettings};
+pub use tier::TierSettings;
+pub use upload::UploadSettings;
 pub use upstream_config::*;
+pub use usage::UsageSettings;
 pub use user::{UserIdType, UserSettings};
 pub use util::get_sanitized_invocation_command;
 pub use util::{get_global_config, get_local_config};
 pub use xet::{create_config_loader, XetConfig};
 
+pub mod audit;
 pub mod authentication;
 pub mod axe;
 pub mod cache;
 pub mod cas;
+pub mod chunking;
+pub mod control;
 pub mod env;
 pub mod errors;
+pub mod fallback;
 pub mod git_path;
+pub mod integrity;
+pub mod io;
 pub mod log;
+pub mod mirror;
+pub mod p2p;
+pub mod pack;
 pub mod permission;
+pub mod quota;
+pub mod retention;
+pub mod shard;
+pub mod signing;
+pub mod staging;
+pub mod store;
+pub mod summaries;
+pub mod tier;
+pub mod upload;
 pub mod upstream_config;
+pub mod usage;
 pub mod user;
 mod util;
 mod xet;
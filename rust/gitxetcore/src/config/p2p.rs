@@ -0,0 +1,99 @@
+use crate::config::ConfigError;
+use crate::config::ConfigError::InvalidP2pAdvertise;
+use xet_config::P2p;
+
+/// The default maximum total size of the xorbs kept for peers: 50GiB.
+pub const DEFAULT_P2P_STORE_SIZE: u64 = 50 * 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct P2pSettings {
+    /// The coordinator to look up and announce xorbs at. Peer fetching is
+    /// disabled when unset.
+    pub coordinator: Option<String>,
+    /// The "host:port" this machine serves xorbs to peers on, if any.
+    pub advertise: Option<String>,
+    pub store_size: u64,
+    /// The token presented to the coordinator and to peers.
+    pub token: Option<String>,
+}
+
+impl Default for P2pSettings {
+    fn default() -> Self {
+        Self {
+            coordinator: None,
+            advertise: None,
+            store_size: DEFAULT_P2P_STORE_SIZE,
+            token: None,
+        }
+    }
+}
+
+impl P2pSettings {
+    pub fn enabled(&self) -> bool {
+        self.coordinator.is_some()
+    }
+}
+
+impl TryFrom<Option<&P2p>> for P2pSettings {
+    type Error = ConfigError;
+
+    fn try_from(p2p: Option<&P2p>) -> Result<Self, Self::Error> {
+        let Some(p2p) = p2p else {
+            return Ok(P2pSettings::default());
+        };
+        if let Some(advertise) = p2p.advertise.as_ref() {
+            // Only the syntax is checked, the name may not resolve from here.
+            let valid = advertise
+                .rsplit_once(':')
+                .map(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+                .unwrap_or(false);
+            if !valid {
+                return Err(InvalidP2pAdvertise(advertise.clone()));
+            }
+        }
+        Ok(P2pSettings {
+            coordinator: p2p
+                .coordinator
+                .as_ref()
+                .map(|c| c.trim_end_matches('/').to_string()),
+            advertise: p2p.advertise.clone(),
+            store_size: p2p.store_size.unwrap_or(DEFAULT_P2P_STORE_SIZE),
+            token: p2p.token.clone().filter(|t| !t.is_empty()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_default_to_disabled() {
+        let settings = P2pSettings::try_from(None).unwrap();
+        assert!(!settings.enabled());
+        assert_eq!(settings.store_size, DEFAULT_P2P_STORE_SIZE);
+
+        let cfg = P2p {
+            coordinator: Some("http://head-node:4050/".to_string()),
+            advertise: Some("worker-3:4051".to_string()),
+            store_size: Some(1000),
+            token: Some("secret".to_string()),
+        };
+        let settings = P2pSettings::try_from(Some(&cfg)).unwrap();
+        assert!(settings.enabled());
+        assert_eq!(settings.coordinator.unwrap(), "http://head-node:4050");
+        assert_eq!(settings.store_size, 1000);
+        assert_eq!(settings.token.unwrap(), "secret");
+    }
+
+    #[test]
+    fn test_invalid_advertise() {
+        for advertise in ["worker-3", ":4051", "worker-3:port"] {
+            let cfg = P2p {
+                advertise: Some(advertise.to_string()),
+                ..Default::default()
+            };
+            assert!(P2pSettings::try_from(Some(&cfg)).is_err());
+        }
+    }
+}
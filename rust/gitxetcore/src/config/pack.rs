@@ -0,0 +1,89 @@
+use crate::config::ConfigError;
+use crate::config::ConfigError::InvalidPackReadSize;
+use crate::constants::{DEFAULT_PACK_READ_SIZE, DEFAULT_PACK_THRESHOLD};
+use xet_config::Pack;
+
+/// How small files are packed together.
+#[derive(Debug, Clone)]
+pub struct PackSettings {
+    /// Files smaller than this are packed; 0 if none are.
+    pub threshold: u64,
+    /// The most bytes fetched in one request when smudging packed files.
+    pub read_size: u64,
+}
+
+impl Default for PackSettings {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_PACK_THRESHOLD,
+            read_size: DEFAULT_PACK_READ_SIZE,
+        }
+    }
+}
+
+impl PackSettings {
+    pub fn enabled(&self) -> bool {
+        self.threshold > 0
+    }
+
+    /// Whether a file of this size is packed.
+    pub fn packs(&self, size: u64) -> bool {
+        size < self.threshold
+    }
+}
+
+impl TryFrom<Option<&Pack>> for PackSettings {
+    type Error = ConfigError;
+
+    fn try_from(pack: Option<&Pack>) -> Result<Self, Self::Error> {
+        let Some(pack) = pack else {
+            return Ok(PackSettings::default());
+        };
+        let read_size = match pack.readsize {
+            Some(0) => return Err(InvalidPackReadSize(0)),
+            Some(n) => n,
+            None => DEFAULT_PACK_READ_SIZE,
+        };
+        Ok(PackSettings {
+            threshold: pack.threshold.unwrap_or(DEFAULT_PACK_THRESHOLD),
+            read_size,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_settings() {
+        let settings = PackSettings::try_from(None).unwrap();
+        assert!(!settings.enabled());
+        assert!(!settings.packs(1000));
+
+        let pack = Pack {
+            threshold: Some(256 * 1024),
+            ..Default::default()
+        };
+        let settings = PackSettings::try_from(Some(&pack)).unwrap();
+        assert!(settings.enabled());
+        assert!(settings.packs(1000));
+        assert!(!settings.packs(256 * 1024));
+        assert_eq!(settings.read_size, DEFAULT_PACK_READ_SIZE);
+
+        let pack = Pack {
+            threshold: Some(0),
+            readsize: Some(1 << 20),
+        };
+        let settings = PackSettings::try_from(Some(&pack)).unwrap();
+        assert!(!settings.enabled());
+        assert!(!settings.packs(0));
+        assert_eq!(settings.read_size, 1 << 20);
+
+        let pack = Pack {
+            readsize: Some(0),
+            ..Default::default()
+        };
+        assert!(PackSettings::try_from(Some(&pack)).is_err());
+    }
+}
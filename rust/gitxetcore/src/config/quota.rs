@@ -0,0 +1,65 @@
+use crate::config::ConfigError;
+use crate::config::ConfigError::InvalidQuotaCheck;
+use std::str::FromStr;
+use xet_config::Quota;
+
+/// What the pre-push hook does when a push would exceed the remote's storage
+/// quota.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QuotaCheck {
+    /// The quota is not queried.
+    Off,
+    /// A warning is printed and the push proceeds.
+    #[default]
+    Warn,
+    /// The push is refused.
+    Error,
+}
+
+impl FromStr for QuotaCheck {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" | "never" => Ok(QuotaCheck::Off),
+            "warn" | "" => Ok(QuotaCheck::Warn),
+            "error" => Ok(QuotaCheck::Error),
+            _ => Err(InvalidQuotaCheck(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct QuotaSettings {
+    pub check: QuotaCheck,
+}
+
+impl TryFrom<Option<&Quota>> for QuotaSettings {
+    type Error = ConfigError;
+
+    fn try_from(quota: Option<&Quota>) -> Result<Self, Self::Error> {
+        Ok(match quota.and_then(|q| q.check.as_ref()) {
+            Some(check) => QuotaSettings {
+                check: check.parse()?,
+            },
+            None => QuotaSettings::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_quota_check() {
+        assert_eq!("off".parse::<QuotaCheck>().unwrap(), QuotaCheck::Off);
+        assert_eq!("WARN".parse::<QuotaCheck>().unwrap(), QuotaCheck::Warn);
+        assert_eq!("error".parse::<QuotaCheck>().unwrap(), QuotaCheck::Error);
+        assert!("fatal".parse::<QuotaCheck>().is_err());
+        assert_eq!(
+            QuotaSettings::try_from(None).unwrap().check,
+            QuotaCheck::Warn
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use crate::config::ConfigError;
+use std::time::Duration;
+use xet_config::Retention;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Default)]
+pub struct RetentionSettings {
+    /// How long cached blocks are kept, if they expire.
+    pub cache_ttl: Option<Duration>,
+    /// How long pushed xorbs are kept in the staging directory, if they
+    /// expire.
+    pub staging_ttl: Option<Duration>,
+}
+
+impl RetentionSettings {
+    pub fn enabled(&self) -> bool {
+        self.cache_ttl.is_some() || self.staging_ttl.is_some()
+    }
+}
+
+impl TryFrom<Option<&Retention>> for RetentionSettings {
+    type Error = ConfigError;
+
+    fn try_from(retention: Option<&Retention>) -> Result<Self, Self::Error> {
+        let Some(retention) = retention else {
+            return Ok(RetentionSettings::default());
+        };
+        // A TTL of 0 days disables expiry, as an unset one does.
+        let ttl = |days: Option<u64>| {
+            days.filter(|d| *d > 0)
+                .map(|d| Duration::from_secs(d * SECONDS_PER_DAY))
+        };
+        Ok(RetentionSettings {
+            cache_ttl: ttl(retention.cache_days),
+            staging_ttl: ttl(retention.staging_days),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retention_settings() {
+        let settings = RetentionSettings::try_from(None).unwrap();
+        assert!(!settings.enabled());
+
+        let settings = RetentionSettings::try_from(Some(&Retention {
+            cache_days: Some(30),
+            staging_days: Some(0),
+        }))
+        .unwrap();
+        assert!(settings.enabled());
+        assert_eq!(
+            settings.cache_ttl,
+            Some(Duration::from_secs(30 * SECONDS_PER_DAY))
+        );
+        assert_eq!(settings.staging_ttl, None);
+    }
+}
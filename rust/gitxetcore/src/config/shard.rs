@@ -0,0 +1,35 @@
+use crate::config::ConfigError;
+use merklehash::HashAlgorithm;
+use xet_config::Shard;
+
+#[derive(Debug, Clone, Default)]
+pub struct ShardSettings {
+    /// Whether only the MerkleDB shards needed by the checked out files are
+    /// fetched, with the rest fetched when a file that needs them is smudged.
+    /// Only applies to repositories using MerkleDB v2.
+    pub partial_fetch: bool,
+    /// The hash algorithm requested for repositories initialized with this
+    /// configuration; the remote's preference is used if unset.
+    pub hash_algorithm: Option<HashAlgorithm>,
+}
+
+impl TryFrom<Option<&Shard>> for ShardSettings {
+    type Error = ConfigError;
+
+    fn try_from(shard: Option<&Shard>) -> Result<Self, Self::Error> {
+        Ok(match shard {
+            Some(shard) => ShardSettings {
+                partial_fetch: shard.partial.unwrap_or(false),
+                hash_algorithm: shard
+                    .hash_algorithm
+                    .as_deref()
+                    .map(|a| {
+                        a.parse()
+                            .map_err(|_| ConfigError::InvalidHashAlgorithm(a.to_string()))
+                    })
+                    .transpose()?,
+            },
+            None => ShardSettings::default(),
+        })
+    }
+}
@@ -0,0 +1,87 @@
+use crate::config::ConfigError;
+use crate::config::ConfigError::InvalidSignatureFormat;
+use std::path::PathBuf;
+use std::str::FromStr;
+use xet_config::Signing;
+
+/// The kind of key pointer files are signed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SignatureFormat {
+    /// Signed with `ssh-keygen -Y sign`.
+    #[default]
+    Ssh,
+    /// Signed with `gpg --detach-sign`.
+    Gpg,
+}
+
+impl FromStr for SignatureFormat {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ssh" | "" => Ok(SignatureFormat::Ssh),
+            "gpg" | "openpgp" => Ok(SignatureFormat::Gpg),
+            _ => Err(InvalidSignatureFormat(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SigningSettings {
+    /// The key cleaned pointer files are signed with, if any.
+    pub key: Option<String>,
+    pub format: SignatureFormat,
+    /// The ssh allowed signers file used to verify ssh signatures.
+    pub allowed_signers: Option<PathBuf>,
+}
+
+impl SigningSettings {
+    /// Whether cleaned pointer files are signed.
+    pub fn signs(&self) -> bool {
+        self.key.is_some()
+    }
+}
+
+impl TryFrom<Option<&Signing>> for SigningSettings {
+    type Error = ConfigError;
+
+    fn try_from(signing: Option<&Signing>) -> Result<Self, Self::Error> {
+        let Some(signing) = signing else {
+            return Ok(SigningSettings::default());
+        };
+        Ok(SigningSettings {
+            key: signing.key.clone().filter(|k| !k.is_empty()),
+            format: match &signing.format {
+                Some(format) => format.parse()?,
+                None => SignatureFormat::default(),
+            },
+            allowed_signers: signing.allowed_signers.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signing_settings() {
+        let settings = SigningSettings::try_from(None).unwrap();
+        assert!(!settings.signs());
+
+        let settings = SigningSettings::try_from(Some(&Signing {
+            key: Some("ABCD1234".to_string()),
+            format: Some("GPG".to_string()),
+            ..Default::default()
+        }))
+        .unwrap();
+        assert!(settings.signs());
+        assert_eq!(settings.format, SignatureFormat::Gpg);
+
+        let invalid = Signing {
+            format: Some("x509".to_string()),
+            ..Default::default()
+        };
+        assert!(SigningSettings::try_from(Some(&invalid)).is_err());
+    }
+}
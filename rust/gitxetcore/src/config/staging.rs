@@ -0,0 +1,117 @@
+use crate::config::cache::parse_size;
+use crate::config::util::can_write;
+use crate::config::ConfigError;
+use crate::config::ConfigError::{
+    InvalidStagingMinFree, InvalidStagingPath, StagingPathNotDirectory, StagingPathReadOnly,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+use xet_config::Staging;
+
+const STAGING_CACHE_SUBDIR: &str = "cache";
+
+/// Where data is staged, if not in the repository's .git.
+#[derive(Debug, Clone, Default)]
+pub struct StagingSettings {
+    /// The directory data is staged under; None to stage in .git.
+    pub path: Option<PathBuf>,
+    /// The bytes to leave free on the staging volume.
+    pub min_free: u64,
+}
+
+impl StagingSettings {
+    /// The directory the chunks of the repository at git_path are staged
+    /// in, under path: one per repository, so repositories sharing
+    /// staging.path don't push each other's chunks.
+    pub fn repo_staging_path(&self, git_path: &Path) -> Option<PathBuf> {
+        let path = self.path.as_ref()?;
+        let git_path = git_path
+            .canonicalize()
+            .unwrap_or_else(|_| git_path.to_path_buf());
+        let key = blake3::hash(git_path.as_os_str().to_string_lossy().as_bytes()).to_hex();
+        Some(path.join(&key.as_str()[..16]))
+    }
+
+    /// The directory the block cache is kept in, if it isn't set with
+    /// cache.path.
+    pub fn cache_path(&self) -> Option<PathBuf> {
+        self.path.as_ref().map(|p| p.join(STAGING_CACHE_SUBDIR))
+    }
+}
+
+impl TryFrom<Option<&Staging>> for StagingSettings {
+    type Error = ConfigError;
+
+    fn try_from(staging: Option<&Staging>) -> Result<Self, Self::Error> {
+        let Some(staging) = staging else {
+            return Ok(StagingSettings::default());
+        };
+
+        let path = match staging.path.as_ref().filter(|p| !p.as_os_str().is_empty()) {
+            Some(path) => {
+                if !path.exists() {
+                    fs::create_dir_all(path).map_err(|e| InvalidStagingPath(path.clone(), e))?;
+                } else if !path.is_dir() {
+                    return Err(StagingPathNotDirectory(path.clone()));
+                } else if !can_write(path) {
+                    return Err(StagingPathReadOnly(path.clone()));
+                }
+                Some(path.clone())
+            }
+            None => None,
+        };
+
+        let min_free = match staging.minfree.as_deref() {
+            Some(size) => parse_size(size).map_err(|_| InvalidStagingMinFree(size.to_string()))?,
+            None => 0,
+        };
+
+        Ok(StagingSettings { path, min_free })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_staging_settings() {
+        let settings = StagingSettings::try_from(None).unwrap();
+        assert!(settings.path.is_none());
+        assert!(settings.repo_staging_path(Path::new(".git")).is_none());
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("scratch");
+        let staging = Staging {
+            path: Some(path.clone()),
+            minfree: Some("10GB".to_string()),
+        };
+        let settings = StagingSettings::try_from(Some(&staging)).unwrap();
+        assert!(path.is_dir());
+        assert_eq!(settings.min_free, 10_000_000_000);
+        assert_eq!(settings.cache_path(), Some(path.join("cache")));
+
+        // Each repository stages in its own directory.
+        let repo_a = settings
+            .repo_staging_path(Path::new("/repos/a/.git"))
+            .unwrap();
+        let repo_b = settings
+            .repo_staging_path(Path::new("/repos/b/.git"))
+            .unwrap();
+        assert_eq!(repo_a.parent(), Some(path.as_path()));
+        assert_ne!(repo_a, repo_b);
+
+        let invalid = |s: Staging| StagingSettings::try_from(Some(&s)).is_err();
+        assert!(invalid(Staging {
+            minfree: Some("lots".to_string()),
+            ..Default::default()
+        }));
+        let file = dir.path().join("file");
+        fs::write(&file, "").unwrap();
+        assert!(invalid(Staging {
+            path: Some(file),
+            ..Default::default()
+        }));
+    }
+}
@@ -0,0 +1,29 @@
+use crate::config::ConfigError;
+use std::path::PathBuf;
+use xet_config::Store;
+
+#[derive(Debug, Clone, Default)]
+pub struct StoreSettings {
+    /// The machine-global directory xorbs are shared across repositories
+    /// in. The shared store is off if unset.
+    pub path: Option<PathBuf>,
+}
+
+impl StoreSettings {
+    pub fn enabled(&self) -> bool {
+        self.path.is_some()
+    }
+}
+
+impl TryFrom<Option<&Store>> for StoreSettings {
+    type Error = ConfigError;
+
+    fn try_from(store: Option<&Store>) -> Result<Self, Self::Error> {
+        let Some(store) = store else {
+            return Ok(StoreSettings::default());
+        };
+        Ok(StoreSettings {
+            path: store.path.clone().filter(|p| !p.as_os_str().is_empty()),
+        })
+    }
+}
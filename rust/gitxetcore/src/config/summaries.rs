@@ -0,0 +1,186 @@
+use crate::config::cache::parse_size;
+use crate::config::ConfigError;
+use crate::config::ConfigError::{InvalidSummariesExclude, InvalidTypeDetector};
+use libmagic::libmagic::TypeDetector;
+use std::path::Path;
+use std::time::Duration;
+use xet_config::{Summaries, SummaryAnalyzer};
+
+/// Which analyzers summarize which files, and for how long.
+#[derive(Debug, Clone, Default)]
+pub struct SummariesSettings {
+    /// Summarizes csv and tsv files as they are cleaned.
+    pub csv: AnalyzerSettings,
+    /// Names the type of each file in the directory summaries.
+    pub libmagic: AnalyzerSettings,
+    /// Counts the lines of source files in the directory summaries.
+    pub languages: AnalyzerSettings,
+    /// How libmagic names the type of a file.
+    pub type_detector: TypeDetector,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnalyzerSettings {
+    pub enabled: bool,
+    pub exclude: Vec<ExcludeRule>,
+    /// How long the analyzer may spend on a file, if limited.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for AnalyzerSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            exclude: Vec::new(),
+            timeout: None,
+        }
+    }
+}
+
+impl AnalyzerSettings {
+    /// Whether the analyzer summarizes the file at path, a path relative to
+    /// the repository, having size bytes. Files that are streamed are
+    /// checked again as they grow.
+    pub fn runs_on(&self, path: &Path, size: u64) -> bool {
+        self.enabled && !self.exclude.iter().any(|rule| rule.matches(path, size))
+    }
+}
+
+/// Files excluded from an analyzer: those matching a git pathspec, and of at
+/// least min_size bytes if set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExcludeRule {
+    pub pattern: String,
+    pub min_size: Option<u64>,
+}
+
+impl ExcludeRule {
+    /// Parses a rule of the form "<pathspec>[><size>]", e.g. "*.bin>1GB".
+    pub fn parse(rule: &str) -> Result<Self, ConfigError> {
+        let invalid = || InvalidSummariesExclude(rule.to_string());
+        let (pattern, min_size) = match rule.rsplit_once('>') {
+            Some((pattern, size)) => (pattern, Some(parse_size(size).map_err(|_| invalid())?)),
+            None => (rule, None),
+        };
+        let pattern = pattern.trim();
+        if pattern.is_empty() || git2::Pathspec::new([pattern]).is_err() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            pattern: pattern.to_string(),
+            min_size,
+        })
+    }
+
+    pub fn matches(&self, path: &Path, size: u64) -> bool {
+        if self.min_size.is_some_and(|min| size < min) {
+            return false;
+        }
+        git2::Pathspec::new([self.pattern.as_str()])
+            .map(|spec| spec.matches_path(path, git2::PathspecFlags::DEFAULT))
+            .unwrap_or(false)
+    }
+}
+
+impl TryFrom<Option<&Summaries>> for SummariesSettings {
+    type Error = ConfigError;
+
+    fn try_from(summaries: Option<&Summaries>) -> Result<Self, Self::Error> {
+        let Some(summaries) = summaries else {
+            return Ok(SummariesSettings::default());
+        };
+        Ok(SummariesSettings {
+            csv: analyzer_settings(summaries, summaries.csv.as_ref())?,
+            libmagic: analyzer_settings(summaries, summaries.libmagic.as_ref())?,
+            languages: analyzer_settings(summaries, summaries.languages.as_ref())?,
+            type_detector: match summaries.typedetector.as_deref() {
+                Some(detector) => detector
+                    .parse()
+                    .map_err(|_| InvalidTypeDetector(detector.to_string()))?,
+                None => TypeDetector::default(),
+            },
+        })
+    }
+}
+
+/// The settings of an analyzer, with those of every analyzer applied.
+fn analyzer_settings(
+    summaries: &Summaries,
+    analyzer: Option<&SummaryAnalyzer>,
+) -> Result<AnalyzerSettings, ConfigError> {
+    let exclude = summaries
+        .exclude
+        .iter()
+        .chain(analyzer.and_then(|a| a.exclude.as_ref()))
+        .flatten()
+        .map(|rule| ExcludeRule::parse(rule))
+        .collect::<Result<_, _>>()?;
+    // A timeout of 0 disables it, as an unset one does.
+    let timeout = analyzer
+        .and_then(|a| a.timeout)
+        .or(summaries.timeout)
+        .filter(|t| *t > 0)
+        .map(Duration::from_secs);
+    Ok(AnalyzerSettings {
+        enabled: analyzer.and_then(|a| a.enabled).unwrap_or(true),
+        exclude,
+        timeout,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summaries_settings() {
+        let settings = SummariesSettings::try_from(None).unwrap();
+        assert!(settings.csv.runs_on(Path::new("data/a.csv"), 1 << 40));
+        assert_eq!(settings.libmagic.timeout, None);
+        assert_eq!(settings.type_detector, TypeDetector::Extension);
+
+        let settings = SummariesSettings::try_from(Some(&Summaries {
+            exclude: Some(vec!["scratch/*".to_string()]),
+            timeout: Some(30),
+            typedetector: Some("signature".to_string()),
+            csv: Some(SummaryAnalyzer {
+                enabled: Some(false),
+                ..Default::default()
+            }),
+            libmagic: Some(SummaryAnalyzer {
+                exclude: Some(vec!["*.bin>1GB".to_string()]),
+                timeout: Some(0),
+                ..Default::default()
+            }),
+            languages: None,
+        }))
+        .unwrap();
+
+        assert!(!settings.csv.runs_on(Path::new("a.csv"), 0));
+        assert!(settings
+            .libmagic
+            .runs_on(Path::new("data/model.bin"), 1_000));
+        assert!(!settings
+            .libmagic
+            .runs_on(Path::new("data/model.bin"), 2_000_000_000));
+        assert!(!settings.libmagic.runs_on(Path::new("scratch/a.txt"), 0));
+        assert!(settings
+            .languages
+            .runs_on(Path::new("data/model.bin"), 2_000_000_000));
+        assert!(!settings.languages.runs_on(Path::new("scratch/main.py"), 0));
+        assert_eq!(settings.libmagic.timeout, None);
+        assert_eq!(settings.languages.timeout, Some(Duration::from_secs(30)));
+        assert_eq!(settings.type_detector, TypeDetector::Signature);
+
+        let invalid = Summaries {
+            exclude: Some(vec!["*.bin>lots".to_string()]),
+            ..Default::default()
+        };
+        assert!(SummariesSettings::try_from(Some(&invalid)).is_err());
+        let invalid = Summaries {
+            typedetector: Some("magic".to_string()),
+            ..Default::default()
+        };
+        assert!(SummariesSettings::try_from(Some(&invalid)).is_err());
+    }
+}
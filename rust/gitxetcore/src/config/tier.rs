@@ -0,0 +1,96 @@
+use crate::config::ConfigError;
+use crate::config::ConfigError::InvalidTierSetting;
+use std::time::Duration;
+use xet_config::Tier;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+const DEFAULT_HOT_DAYS: u64 = 90;
+const DEFAULT_RESTORE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// The target of `git xet tier` for the cold storage class of the remote CAS.
+pub const COLD_TIER_TARGET: &str = "cold";
+
+#[derive(Debug, Clone)]
+pub struct TierSettings {
+    /// How recently a branch or tag must have been committed to for the data
+    /// it references to stay in the hot tier.
+    pub hot_age: Duration,
+    pub target: String,
+    /// How long a read waits for data to be restored from cold storage.
+    pub restore_timeout: Duration,
+}
+
+impl Default for TierSettings {
+    fn default() -> Self {
+        Self {
+            hot_age: Duration::from_secs(DEFAULT_HOT_DAYS * SECONDS_PER_DAY),
+            target: COLD_TIER_TARGET.to_string(),
+            restore_timeout: DEFAULT_RESTORE_TIMEOUT,
+        }
+    }
+}
+
+impl TryFrom<Option<&Tier>> for TierSettings {
+    type Error = ConfigError;
+
+    fn try_from(tier: Option<&Tier>) -> Result<Self, Self::Error> {
+        let Some(tier) = tier else {
+            return Ok(TierSettings::default());
+        };
+        let default = TierSettings::default();
+        // 0 days would move the data of every branch to the cold tier.
+        let hot_age = match tier.hotdays {
+            Some(0) => return Err(InvalidTierSetting("hotdays must be at least 1".to_string())),
+            Some(days) => Duration::from_secs(days * SECONDS_PER_DAY),
+            None => default.hot_age,
+        };
+        let target = match tier.target.as_deref().map(str::trim) {
+            Some("") => return Err(InvalidTierSetting("target is empty".to_string())),
+            Some(target) => target.to_string(),
+            None => default.target,
+        };
+        Ok(TierSettings {
+            hot_age,
+            target,
+            restore_timeout: tier
+                .restoretimeout
+                .map(Duration::from_secs)
+                .unwrap_or(default.restore_timeout),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tier_settings() {
+        let settings = TierSettings::try_from(None).unwrap();
+        assert_eq!(
+            settings.hot_age,
+            Duration::from_secs(DEFAULT_HOT_DAYS * SECONDS_PER_DAY)
+        );
+        assert_eq!(settings.target, COLD_TIER_TARGET);
+
+        let settings = TierSettings::try_from(Some(&Tier {
+            hotdays: Some(30),
+            target: Some("glacier".to_string()),
+            restoretimeout: Some(600),
+        }))
+        .unwrap();
+        assert_eq!(settings.hot_age, Duration::from_secs(30 * SECONDS_PER_DAY));
+        assert_eq!(settings.target, "glacier");
+        assert_eq!(settings.restore_timeout, Duration::from_secs(600));
+
+        let invalid = |tier: Tier| TierSettings::try_from(Some(&tier)).is_err();
+        assert!(invalid(Tier {
+            hotdays: Some(0),
+            ..Default::default()
+        }));
+        assert!(invalid(Tier {
+            target: Some(" ".to_string()),
+            ..Default::default()
+        }));
+    }
+}
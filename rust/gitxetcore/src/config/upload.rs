@@ -0,0 +1,65 @@
+use crate::config::ConfigError;
+use crate::config::ConfigError::InvalidUploadConcurrency;
+use crate::constants::MAX_CONCURRENT_UPLOADS;
+use cas_client::UploadConcurrency;
+use xet_config::Upload;
+
+/// The concurrency of each stage of uploading staged xorbs to the CAS. With
+/// -v, the time spent in each stage is logged after every upload, to help
+/// tune these.
+#[derive(Debug, Clone)]
+pub struct UploadSettings {
+    pub concurrency: UploadConcurrency,
+}
+
+impl Default for UploadSettings {
+    fn default() -> Self {
+        Self {
+            concurrency: UploadConcurrency::uniform(MAX_CONCURRENT_UPLOADS),
+        }
+    }
+}
+
+impl TryFrom<Option<&Upload>> for UploadSettings {
+    type Error = ConfigError;
+
+    fn try_from(upload: Option<&Upload>) -> Result<Self, Self::Error> {
+        let Some(upload) = upload else {
+            return Ok(UploadSettings::default());
+        };
+        let stage = |name: &str, value: Option<usize>| match value {
+            Some(0) => Err(InvalidUploadConcurrency(name.to_string())),
+            Some(n) => Ok(n),
+            None => Ok(MAX_CONCURRENT_UPLOADS),
+        };
+        Ok(UploadSettings {
+            concurrency: UploadConcurrency {
+                dedup_check: stage("dedup_check_concurrency", upload.dedup_check_concurrency)?,
+                read: stage("read_concurrency", upload.read_concurrency)?,
+                upload: stage("concurrency", upload.concurrency)?,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upload_concurrency() {
+        let settings = UploadSettings::try_from(Some(&Upload {
+            dedup_check_concurrency: Some(64),
+            ..Default::default()
+        }))
+        .unwrap();
+        assert_eq!(settings.concurrency.dedup_check, 64);
+        assert_eq!(settings.concurrency.upload, MAX_CONCURRENT_UPLOADS);
+
+        let zero = Upload {
+            read_concurrency: Some(0),
+            ..Default::default()
+        };
+        assert!(UploadSettings::try_from(Some(&zero)).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use crate::config::axe::AxeSettings;
+use crate::config::ConfigError;
+use crate::config::ConfigError::InvalidUsageSetting;
+use xet_config::{Axe, Usage};
+
+pub const DEFAULT_USAGE_ENDPOINT: &str = "https://app.posthog.com/batch/";
+const DEFAULT_BATCH_SIZE: usize = 20;
+const DEFAULT_SPOOL_MAX: usize = 1000;
+
+/// Whether and where the anonymous usage events are sent. See
+/// [crate::environment::usage_events] for what an event contains.
+#[derive(Debug, Clone)]
+pub struct UsageSettings {
+    /// Off unless opted in with usage.enabled, or the older axe.enabled.
+    pub enabled: bool,
+    pub endpoint: String,
+    pub api_key: String,
+    pub batch_size: usize,
+    pub spool_max: usize,
+}
+
+impl Default for UsageSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: DEFAULT_USAGE_ENDPOINT.to_string(),
+            api_key: AxeSettings::default().axe_code,
+            batch_size: DEFAULT_BATCH_SIZE,
+            spool_max: DEFAULT_SPOOL_MAX,
+        }
+    }
+}
+
+impl TryFrom<(Option<&Usage>, Option<&Axe>)> for UsageSettings {
+    type Error = ConfigError;
+
+    fn try_from((usage, axe): (Option<&Usage>, Option<&Axe>)) -> Result<Self, Self::Error> {
+        let axe = AxeSettings::try_from(axe)?;
+        let default = UsageSettings::default();
+        let usage = usage.cloned().unwrap_or_default();
+        let batch_size = usage.batchsize.unwrap_or(default.batch_size);
+        if batch_size == 0 {
+            return Err(InvalidUsageSetting(
+                "batchsize must be at least 1".to_string(),
+            ));
+        }
+        let spool_max = usage.spoolmax.unwrap_or(default.spool_max);
+        if spool_max < batch_size {
+            return Err(InvalidUsageSetting(format!(
+                "spoolmax {spool_max} is less than batchsize {batch_size}"
+            )));
+        }
+        Ok(UsageSettings {
+            enabled: usage.enabled.unwrap_or(axe.enabled),
+            endpoint: usage.endpoint.unwrap_or(default.endpoint),
+            api_key: axe.axe_code,
+            batch_size,
+            spool_max,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_settings() {
+        let settings = UsageSettings::try_from((None, None)).unwrap();
+        assert!(!settings.enabled);
+        assert_eq!(settings.endpoint, DEFAULT_USAGE_ENDPOINT);
+        assert_eq!(settings.batch_size, DEFAULT_BATCH_SIZE);
+
+        // axe.enabled opts in while usage.enabled is unset, and is overridden
+        // by it otherwise.
+        let axe = Axe {
+            enabled: Some("true".to_string()),
+            axe_code: Some("6767".to_string()),
+        };
+        let settings = UsageSettings::try_from((None, Some(&axe))).unwrap();
+        assert!(settings.enabled);
+        assert_eq!(settings.api_key, "6767");
+        let usage = Usage {
+            enabled: Some(false),
+            batchsize: Some(5),
+            ..Default::default()
+        };
+        let settings = UsageSettings::try_from((Some(&usage), Some(&axe))).unwrap();
+        assert!(!settings.enabled);
+        assert_eq!(settings.batch_size, 5);
+
+        let invalid = Usage {
+            batchsize: Some(0),
+            ..Default::default()
+        };
+        assert!(UsageSettings::try_from((Some(&invalid), None)).is_err());
+        let invalid = Usage {
+            batchsize: Some(50),
+            spoolmax: Some(10),
+            ..Default::default()
+        };
+        assert!(UsageSettings::try_from((Some(&invalid), None)).is_err());
+    }
+}
@@ -49,15 +49,25 @@ pub fn get_local_config(gitpath: Option<PathBuf>) -> Result<PathBuf, GitXetRepoE
 /// Overrides from the CLI
 pub fn get_override_cfg(overrides: &CliOverrides) -> Cfg {
     let mut log_overrides = None;
-    if overrides.verbose > 0 || overrides.log.is_some() {
+    if overrides.verbose > 0
+        || overrides.log.is_some()
+        || overrides.log_level.is_some()
+        || overrides.log_format.is_some()
+    {
         let path = overrides.log.as_ref().cloned();
+        let level = overrides
+            .log_level
+            .clone()
+            .or_else(|| verbosity_to_level(overrides.verbose));
         log_overrides = Some(Log {
             path,
-            level: verbosity_to_level(overrides.verbose),
-            format: None,
+            level,
+            format: overrides.log_format.clone(),
             tracing: None,
             silentsummary: None,
             exceptions: None,
+            dir: None,
+            maxfiles: None,
         })
     }
     let mut cas_overrides = None;
@@ -204,6 +214,8 @@ mod tests {
         let overrides = CliOverrides {
             verbose: 2,
             log: Some(path.clone()),
+            log_level: None,
+            log_format: Some("json".to_string()),
             smudge_query_policy: Default::default(),
             global_dedup_query_policy: Default::default(),
             cas: Some(expected_cas_server.clone()),
@@ -220,6 +232,7 @@ mod tests {
         let cfg = get_override_cfg(&overrides);
         assert_eq!("debug", cfg.log.as_ref().unwrap().level.as_ref().unwrap());
         assert_eq!(&path, cfg.log.as_ref().unwrap().path.as_ref().unwrap());
+        assert_eq!("json", cfg.log.as_ref().unwrap().format.as_ref().unwrap());
         assert_eq!(
             &expected_cas_server,
             cfg.cas.as_ref().unwrap().server.as_ref().unwrap()
@@ -232,6 +245,8 @@ mod tests {
         let overrides = CliOverrides {
             verbose: 2,
             log: None,
+            log_level: None,
+            log_format: None,
             cas: Some(expected_cas_server.clone()),
             smudge_query_policy: Default::default(),
             global_dedup_query_policy: Default::default(),
@@ -257,6 +272,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_cfg_override_log_level() {
+        let overrides = CliOverrides {
+            verbose: 1,
+            log_level: Some("trace".to_string()),
+            ..Default::default()
+        };
+        let cfg = get_override_cfg(&overrides);
+        let log = cfg.log.unwrap();
+        assert_eq!("trace", log.level.unwrap());
+        assert!(log.path.is_none());
+        assert!(log.format.is_none());
+    }
+
     #[test]
     fn test_option_ok_or_result() {
         fn panic_fn() -> Result<i32, ()> {
@@ -1,11 +1,30 @@
 use crate::command::CliOverrides;
+use crate::config::audit::AuditSettings;
 use crate::config::axe::AxeSettings;
 use crate::config::cache::CacheSettings;
 use crate::config::cas::CasSettings;
+use crate::config::chunking::ChunkingSettings;
+use crate::config::control::ControlSettings;
 use crate::config::env::XetEnv;
+use crate::config::fallback::FallbackSettings;
 use crate::config::git_path::{ConfigGitPathOption, RepoInfo};
+use crate::config::integrity::IntegritySettings;
+use crate::config::io::IoSettings;
 use crate::config::log::LogSettings;
+use crate::config::mirror::MirrorSettings;
+use crate::config::p2p::P2pSettings;
+use crate::config::pack::PackSettings;
 use crate::config::permission::Permission;
+use crate::config::quota::QuotaSettings;
+use crate::config::retention::RetentionSettings;
+use crate::config::shard::ShardSettings;
+use crate::config::signing::SigningSettings;
+use crate::config::staging::StagingSettings;
+use crate::config::store::StoreSettings;
+use crate::config::summaries::SummariesSettings;
+use crate::config::tier::TierSettings;
+use crate::config::upload::UploadSettings;
+use crate::config::usage::UsageSettings;
 use crate::config::user::UserSettings;
 use crate::config::util;
 use crate::config::util::OptionHelpers;
@@ -63,6 +82,24 @@ pub struct XetConfig {
     pub cas: CasSettings,
     pub cache: CacheSettings,
     pub log: LogSettings,
+    pub io: IoSettings,
+    pub integrity: IntegritySettings,
+    pub p2p: P2pSettings,
+    pub quota: QuotaSettings,
+    pub upload: UploadSettings,
+    pub shard: ShardSettings,
+    pub control: ControlSettings,
+    pub signing: SigningSettings,
+    pub audit: AuditSettings,
+    pub retention: RetentionSettings,
+    pub store: StoreSettings,
+    pub fallback: FallbackSettings,
+    pub mirror: MirrorSettings,
+    pub pack: PackSettings,
+    pub staging: StagingSettings,
+    pub chunking: ChunkingSettings,
+    pub summaries: SummariesSettings,
+    pub tier: TierSettings,
     pub repo_path_if_present: Option<PathBuf>,
     pub merkledb: PathBuf,
     // The directory to cache MDB shards pulled from CAS.
@@ -77,6 +114,7 @@ pub struct XetConfig {
     pub staging_path: Option<PathBuf>,
     pub user: UserSettings,
     pub axe: AxeSettings,
+    pub usage: UsageSettings,
     pub force_no_smudge: bool,
     pub disable_version_check: bool,
     pub lazy_config: Option<PathBuf>,
@@ -96,8 +134,27 @@ impl XetConfig {
             cas: Default::default(),
             cache: Default::default(),
             log: Default::default(),
+            io: Default::default(),
+            integrity: Default::default(),
+            p2p: Default::default(),
+            quota: Default::default(),
+            upload: Default::default(),
+            shard: Default::default(),
+            control: Default::default(),
+            signing: Default::default(),
+            audit: Default::default(),
+            retention: Default::default(),
+            store: Default::default(),
+            fallback: Default::default(),
+            mirror: Default::default(),
+            pack: Default::default(),
+            staging: Default::default(),
+            chunking: Default::default(),
+            summaries: Default::default(),
+            tier: Default::default(),
             user: Default::default(),
             axe: Default::default(),
+            usage: Default::default(),
             repo_path_if_present: None,
             merkledb: Default::default(),
             merkledb_v2_cache: Default::default(),
@@ -276,8 +333,18 @@ impl XetConfig {
         // create xet home with correct permission
         permission.create_dir_all(&xet_home)?;
 
+        let staging: StagingSettings = active_cfg.staging.as_ref().try_into()?;
+
+        // The default cache moves to staging.path along with the staged data.
+        let mut cache = active_cfg.cache.clone();
+        if let (Some(cache), Some(path)) = (cache.as_mut(), staging.cache_path()) {
+            if cache.path == Cfg::with_default_values().cache.and_then(|c| c.path) {
+                cache.path = Some(path);
+            }
+        }
+
         // create cache directory with correct permission
-        if let Some(cache) = active_cfg.cache.as_ref() {
+        if let Some(cache) = cache.as_ref() {
             if let Some(cache_path) = cache.path.as_ref() {
                 permission.create_dir_all(cache_path)?;
             }
@@ -285,10 +352,29 @@ impl XetConfig {
 
         Ok(Self {
             cas: active_cfg.cas.as_ref().try_into()?,
-            cache: active_cfg.cache.as_ref().try_into()?,
+            cache: cache.as_ref().try_into()?,
             log: active_cfg.log.as_ref().try_into()?,
+            io: active_cfg.io.as_ref().try_into()?,
+            integrity: active_cfg.integrity.as_ref().try_into()?,
+            p2p: active_cfg.p2p.as_ref().try_into()?,
+            quota: active_cfg.quota.as_ref().try_into()?,
+            upload: active_cfg.upload.as_ref().try_into()?,
+            shard: active_cfg.shard.as_ref().try_into()?,
+            control: active_cfg.control.as_ref().try_into()?,
+            signing: active_cfg.signing.as_ref().try_into()?,
+            audit: active_cfg.audit.as_ref().try_into()?,
+            retention: active_cfg.retention.as_ref().try_into()?,
+            store: active_cfg.store.as_ref().try_into()?,
+            fallback: active_cfg.fallback.as_ref().try_into()?,
+            mirror: active_cfg.mirror.as_ref().try_into()?,
+            pack: active_cfg.pack.as_ref().try_into()?,
+            staging,
+            chunking: active_cfg.chunking.as_ref().try_into()?,
+            summaries: active_cfg.summaries.as_ref().try_into()?,
+            tier: active_cfg.tier.as_ref().try_into()?,
             user: (active_cfg.user.as_ref(), &repo_info.remote_urls).try_into()?,
             axe: active_cfg.axe.as_ref().try_into()?,
+            usage: (active_cfg.usage.as_ref(), active_cfg.axe.as_ref()).try_into()?,
             repo_path_if_present: repo_info.maybe_git_path.as_ref().cloned(),
             merkledb: Default::default(),
             merkledb_v2_cache: Default::default(),
@@ -353,7 +439,14 @@ impl XetConfig {
                 };
 
                 let summarydb = git_path.join(SUMMARIES_PATH_SUBDIR);
-                let staging_path = git_path.join(CAS_STAGING_SUBDIR);
+                let default_staging_path = git_path.join(CAS_STAGING_SUBDIR);
+                let staging_path = match s.staging.repo_staging_path(&git_path) {
+                    Some(staging_path) => {
+                        move_staged_files(&default_staging_path, &staging_path)?;
+                        staging_path
+                    }
+                    None => default_staging_path,
+                };
                 let lazy_config = git_path.join(GIT_LAZY_CHECKOUT_CONFIG);
 
                 s.try_with_merkledb(merkledb)?
@@ -544,6 +637,24 @@ impl XetConfig {
     }
 }
 
+/// Moves the chunks staged in from, in the repository's .git, to the
+/// staging.path directory to, so they're pushed from there. The two may be on
+/// different volumes, in which case they are copied.
+fn move_staged_files(from: &Path, to: &Path) -> Result<(), ConfigError> {
+    let Ok(entries) = fs::read_dir(from) else {
+        return Ok(());
+    };
+    fs::create_dir_all(to).map_err(|e| StagingDirNotCreated(to.to_path_buf(), e))?;
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        cas::safeio::move_file(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
 fn no_version_check_from_env() -> bool {
     match std::env::var_os(XET_DISABLE_VERSION_CHECK) {
         Some(v) => v != "0",
@@ -990,6 +1101,8 @@ mod config_create_tests {
         let overrides = CliOverrides {
             verbose: 2,
             log: None,
+            log_level: None,
+            log_format: None,
             cas: None,
             smudge_query_policy: Default::default(),
             global_dedup_query_policy: Default::default(),
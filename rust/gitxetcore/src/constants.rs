@@ -15,6 +15,7 @@ pub const GIT_NOTES_SUMMARIES_REF_SUFFIX: &str = "xet/summaries";
 pub const GIT_NOTES_SUMMARIES_REF_NAME: &str = "refs/notes/xet/summaries";
 pub const MERKLEDBV1_PATH_SUBDIR: &str = "xet/merkledb.db";
 pub const SUMMARIES_PATH_SUBDIR: &str = "xet/summaries.db";
+pub const CONTENT_SUMMARIES_PATH_SUBDIR: &str = "xet/content-summaries.db";
 
 pub const GIT_NOTES_MERKLEDB_V2_REF_SUFFIX: &str = "xet/merkledbv2";
 pub const GIT_NOTES_MERKLEDB_V2_REF_NAME: &str = "refs/notes/xet/merkledbv2";
@@ -24,11 +25,56 @@ pub const MERKLEDB_V2_SESSION_PATH_SUBDIR: &str = "xet/merkledbv2-session";
 pub const GIT_NOTES_REPO_SALT_REF_SUFFIX: &str = "xet/reposalt";
 pub const GIT_NOTES_REPO_SALT_REF_NAME: &str = "refs/notes/xet/reposalt";
 
+pub const GIT_NOTES_HASH_ALGORITHM_REF_SUFFIX: &str = "xet/hashalgorithm";
+pub const GIT_NOTES_HASH_ALGORITHM_REF_NAME: &str = "refs/notes/xet/hashalgorithm";
+
 pub const GIT_LAZY_CHECKOUT_CONFIG: &str = "xet/lazyconfig";
 
+/// Files cleaned since the last push whose reconstruction is verified by the
+/// pre-push hook when integrity.verify = on_push.
+pub const INTEGRITY_PENDING_SUBDIR: &str = "xet/integrity-pending";
+
+/// Default Unix socket on which git xet watch serves the working tree summary.
+pub const WATCH_SOCKET_SUBDIR: &str = "xet/watch.sock";
+
+/// Directory holding the control sockets of running mounts and filter
+/// processes, named <operation>-<pid>.sock.
+pub const CONTROL_SOCKET_SUBDIR: &str = "xet/control";
+
+/// Marker touched whenever expired cached blocks and staged xorbs are
+/// pruned, to prune at most once per RETENTION_PRUNE_INTERVAL_SECS.
+pub const RETENTION_MARKER_SUBDIR: &str = "xet/retention-pruned";
+pub const RETENTION_PRUNE_INTERVAL_SECS: u64 = 60 * 60;
+
+/// Directory files in the working tree are smudged into before they replace
+/// them, once complete; see [crate::data::partial_smudge].
+pub const PARTIAL_SMUDGE_SUBDIR: &str = "xet/partial-smudges";
+/// Partial smudges not written to for this long are removed.
+pub const PARTIAL_SMUDGE_MAX_AGE_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Directory each command writes its own log file in, by default.
+pub const LOG_DIR_SUBDIR: &str = "xet/logs";
+
+/// The transfer report appended to by default in CI; see
+/// [crate::transfer_report].
+pub const TRANSFER_REPORT_SUBDIR: &str = "xet/transfer-report.jsonl";
+
 // This file is checked into the repo.  Path is relative to the repo root.
 pub const GIT_REPO_SPECIFIC_CONFIG: &str = ".xet/config.toml";
 
+/// Patterns excluded from the directory summaries, one per line in the style
+/// of .gitignore. This file is checked into the repo at the repo root.
+pub const XET_IGNORE_FILE: &str = ".xetignore";
+
+/// The policy mapping path patterns to the token scopes or roles needed to
+/// smudge them. This file is checked into the repo.  Path is relative to the
+/// repo root.
+pub const ACCESS_POLICY_FILE: &str = ".xet/access.toml";
+
+/// Git note payloads at least this large are stored in CAS, with the note
+/// holding only the pointer file.
+pub const NOTE_PAYLOAD_CAS_THRESHOLD: usize = 1024 * 1024;
+
 /// The maximum git filter protocol packet size
 pub const GIT_MAX_PACKET_SIZE: usize = 65516;
 
@@ -48,6 +94,41 @@ pub const POINTER_FILE_LIMIT: usize = GIT_MAX_PACKET_SIZE - 1;
 /// is a small file.
 pub const SMALL_FILE_THRESHOLD: usize = 4 * GIT_MAX_PACKET_SIZE - 1;
 
+/// Files at least this large are materialized with preallocation and positioned
+/// writes when `io.preallocate` is enabled.  Below this, the sequential writer is
+/// already fast enough that the extra syscalls don't pay off.
+pub const PREALLOCATE_MIN_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Files smaller than this are packed unless `pack.threshold` says otherwise.
+/// Packing is off by default: packed files skip global dedup queries, so
+/// small files shared with other repositories would be uploaded again.
+pub const DEFAULT_PACK_THRESHOLD: u64 = 0;
+
+/// The most bytes of a xorb fetched in one request when smudging packed files,
+/// unless `pack.readsize` says otherwise.
+pub const DEFAULT_PACK_READ_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Packed files whose data is at most this far apart in a xorb are fetched in
+/// one request, reading through the bytes between them.
+pub const PACK_READ_GAP: usize = 64 * 1024;
+
+/// How archives are split for their Reed–Solomon repair blocks: every
+/// ARCHIVE_PARITY_DATA_BLOCKS blocks of ARCHIVE_PARITY_BLOCK_SIZE bytes get
+/// DEFAULT_ARCHIVE_PARITY_BLOCKS repair blocks, 12.5% more data, of which
+/// that many blocks can be rebuilt. See [crate::archive].
+pub const ARCHIVE_PARITY_BLOCK_SIZE: usize = 1024 * 1024;
+pub const ARCHIVE_PARITY_DATA_BLOCKS: usize = 32;
+pub const DEFAULT_ARCHIVE_PARITY_BLOCKS: usize = 4;
+/// The parity file of an archive is named after it, with this suffix.
+pub const ARCHIVE_PARITY_SUFFIX: &str = ".parity";
+
+/// How many packed files checkout smudges together.
+pub const PACK_SMUDGE_BATCH: usize = 1024;
+
+/// How many bytes of a partial smudge are written between syncs of its
+/// journal. An interrupted smudge refetches at most this much.
+pub const PARTIAL_SMUDGE_SYNC_BYTES: u64 = 64 * 1024 * 1024;
+
 // Salt is 256-bit in length.
 pub const REPO_SALT_LEN: usize = 32;
 
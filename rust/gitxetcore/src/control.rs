@@ -0,0 +1,320 @@
+//! The control socket of long-running operations: mounts and the filter
+//! process. With control.enabled set, each serves JSON-RPC ([crate::jsonrpc])
+//! on .git/xet/control/<operation>-<pid>.sock, so that GUI front-ends can
+//! follow and steer it without parsing logs.
+//!
+//! Methods:
+//! - `progress`: the operation, its uptime, the bytes transferred to and
+//!   from the CAS, the reads the cache served, the bandwidth limit, the
+//!   progress of each task, the counters the operation registered (e.g. the
+//!   bytes read through a mount), and the last warnings and errors logged.
+//! - `flush_caches`: clears the in-memory caches of the operation, returning
+//!   the number of entries dropped.
+//! - `set_bandwidth_limit`: limits the transfers to and from the CAS to
+//!   `params.bytes_per_sec`, null or 0 removing the limit.
+//! - `shutdown`: stops the operation gracefully. Mounts unmount and exit; the
+//!   filter process is driven by git, exits when git closes its input, and
+//!   rejects this.
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use cas_client::{CAS_BANDWIDTH_LIMITER, CAS_TRANSFER_STATS};
+use progress_reporting::DataProgressReporter;
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::config::XetConfig;
+use crate::constants::CONTROL_SOCKET_SUBDIR;
+use crate::environment::recent_errors::{recent_errors, RecentError};
+use crate::errors::Result;
+use crate::jsonrpc::{self, Request, Response, INVALID_PARAMS};
+
+/// The counter of the bytes read through a mount.
+pub const MOUNT_READ_BYTES: &str = "mount_read_bytes";
+
+type FlushHook = Box<dyn Fn() -> usize + Send + Sync>;
+type CounterHook = Box<dyn Fn() -> u64 + Send + Sync>;
+
+/// What an operation exposes through its control socket. Operations
+/// register their progress reporters, caches, and shutdown handling here.
+pub struct ControlState {
+    operation: String,
+    started: Instant,
+    progress: Mutex<Vec<Arc<DataProgressReporter>>>,
+    flush_hooks: Mutex<Vec<FlushHook>>,
+    counters: Mutex<Vec<(String, CounterHook)>>,
+    shutdown: Mutex<Option<oneshot::Sender<()>>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaskProgress {
+    pub message: String,
+    pub active: bool,
+    pub current_count: usize,
+    pub total_count: usize,
+    pub current_bytes: usize,
+    pub total_bytes: usize,
+    pub elapsed_secs: f64,
+}
+
+/// The transfers of the operation to and from the CAS since it started.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TransferTotals {
+    pub uploaded_bytes: u64,
+    pub downloaded_bytes: u64,
+    pub retries: u64,
+    pub throttles: u64,
+    pub cache_hit_bytes: u64,
+    pub cache_miss_bytes: u64,
+}
+
+/// The result of the `progress` method.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProgressReport {
+    pub operation: String,
+    pub pid: u32,
+    pub uptime_secs: f64,
+    pub transferred_bytes: u64,
+    pub bandwidth_limit: Option<u64>,
+    pub tasks: Vec<TaskProgress>,
+    #[serde(default)]
+    pub transfer: TransferTotals,
+    #[serde(default)]
+    pub counters: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub recent_errors: Vec<RecentError>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+struct BandwidthParams {
+    #[serde(default)]
+    bytes_per_sec: Option<u64>,
+}
+
+impl ControlState {
+    pub fn new(operation: &str) -> Arc<Self> {
+        Arc::new(Self {
+            operation: operation.to_string(),
+            started: Instant::now(),
+            progress: Mutex::new(Vec::new()),
+            flush_hooks: Mutex::new(Vec::new()),
+            counters: Mutex::new(Vec::new()),
+            shutdown: Mutex::new(None),
+        })
+    }
+
+    /// Reports the progress of reporter through the `progress` method.
+    pub fn add_progress(&self, reporter: Arc<DataProgressReporter>) {
+        self.progress.lock().unwrap().push(reporter);
+    }
+
+    /// Runs hook on `flush_caches`. The hook clears a cache and returns the
+    /// number of entries dropped.
+    pub fn add_flush_hook(&self, hook: impl Fn() -> usize + Send + Sync + 'static) {
+        self.flush_hooks.lock().unwrap().push(Box::new(hook));
+    }
+
+    /// Reports the value of hook as the counter name through the `progress`
+    /// method.
+    pub fn add_counter(&self, name: &str, hook: impl Fn() -> u64 + Send + Sync + 'static) {
+        self.counters
+            .lock()
+            .unwrap()
+            .push((name.to_string(), Box::new(hook)));
+    }
+
+    /// Returns a receiver completing when a client requests shutdown. The
+    /// `shutdown` method is rejected unless the operation called this.
+    pub fn shutdown_requested(&self) -> oneshot::Receiver<()> {
+        let (tx, rx) = oneshot::channel();
+        *self.shutdown.lock().unwrap() = Some(tx);
+        rx
+    }
+
+    fn progress_report(&self) -> ProgressReport {
+        let tasks = self
+            .progress
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|reporter| {
+                let snapshot = reporter.snapshot();
+                TaskProgress {
+                    message: snapshot.message,
+                    active: snapshot.is_active,
+                    current_count: snapshot.current_count,
+                    total_count: snapshot.total_count,
+                    current_bytes: snapshot.current_bytes,
+                    total_bytes: snapshot.total_bytes,
+                    elapsed_secs: snapshot.elapsed.as_secs_f64(),
+                }
+            })
+            .collect();
+        let counts = CAS_TRANSFER_STATS.counts();
+        let counters = self
+            .counters
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, hook)| (name.clone(), hook()))
+            .collect();
+        ProgressReport {
+            operation: self.operation.clone(),
+            pid: std::process::id(),
+            uptime_secs: self.started.elapsed().as_secs_f64(),
+            transferred_bytes: CAS_BANDWIDTH_LIMITER.total_bytes(),
+            bandwidth_limit: CAS_BANDWIDTH_LIMITER.limit(),
+            tasks,
+            transfer: TransferTotals {
+                uploaded_bytes: counts.uploaded_bytes,
+                downloaded_bytes: counts.downloaded_bytes,
+                retries: counts.retries,
+                throttles: counts.throttles,
+                cache_hit_bytes: counts.cache_hit_bytes,
+                cache_miss_bytes: counts.cache_miss_bytes,
+            },
+            counters,
+            recent_errors: recent_errors(),
+        }
+    }
+
+    fn flush_caches(&self) -> usize {
+        let flushed = self.flush_hooks.lock().unwrap().iter().map(|h| h()).sum();
+        info!("XET control: flushed {flushed} cache entries");
+        flushed
+    }
+
+    /// Answers one request line.
+    pub fn handle_request(&self, line: &str) -> Response {
+        let request = match Request::parse(line) {
+            Ok(r) => r,
+            Err(response) => return response,
+        };
+        debug!("XET control: {} request", request.method);
+
+        match request.method.as_str() {
+            "progress" => request.result(self.progress_report()),
+            "flush_caches" => request.result(serde_json::json!({
+                "flushed": self.flush_caches()
+            })),
+            "set_bandwidth_limit" => {
+                let params: BandwidthParams = match request.params() {
+                    Ok(p) => p,
+                    Err(response) => return response,
+                };
+                CAS_BANDWIDTH_LIMITER.set_limit(params.bytes_per_sec);
+                request.result(serde_json::json!({
+                    "bandwidth_limit": CAS_BANDWIDTH_LIMITER.limit()
+                }))
+            }
+            "shutdown" => match self.shutdown.lock().unwrap().take() {
+                Some(tx) => {
+                    info!("XET control: shutdown requested");
+                    let _ = tx.send(());
+                    request.result(serde_json::json!({ "shutting_down": true }))
+                }
+                None => request.error(
+                    INVALID_PARAMS,
+                    format!("{} does not accept shutdown requests", self.operation),
+                ),
+            },
+            _ => request.method_not_found(),
+        }
+    }
+}
+
+/// A running control socket, removed when dropped.
+pub struct ControlServer {
+    pub state: Arc<ControlState>,
+    socket_path: PathBuf,
+    task: JoinHandle<()>,
+}
+
+impl ControlServer {
+    /// Serves the control socket of operation if control.enabled is set in
+    /// the config and the operation runs in a repository.
+    pub fn start(cfg: &XetConfig, operation: &str) -> Result<Option<Self>> {
+        if !cfg.control.enabled {
+            return Ok(None);
+        }
+        let Ok(git_path) = cfg.repo_path() else {
+            return Ok(None);
+        };
+        let socket_dir = git_path.join(CONTROL_SOCKET_SUBDIR);
+        std::fs::create_dir_all(&socket_dir)?;
+        let socket_path = socket_dir.join(format!("{operation}-{}.sock", std::process::id()));
+
+        let state = ControlState::new(operation);
+        let task = tokio::spawn({
+            let state = state.clone();
+            let socket_path = socket_path.clone();
+            async move {
+                let served = jsonrpc::serve(&socket_path, move |line| {
+                    let state = state.clone();
+                    async move { state.handle_request(&line) }
+                })
+                .await;
+                if let Err(e) = served {
+                    warn!("XET control: unable to serve on {socket_path:?}: {e}");
+                }
+            }
+        });
+
+        Ok(Some(Self {
+            state,
+            socket_path,
+            task,
+        }))
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        self.task.abort();
+        let _ = std::fs::remove_file(&self.socket_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jsonrpc::METHOD_NOT_FOUND;
+
+    #[test]
+    fn test_control_requests() {
+        let state = ControlState::new("filter");
+        let reporter = DataProgressReporter::new_inactive("Processing data", None, None);
+        reporter.update_target(Some(4), Some(4096));
+        reporter.register_progress(Some(1), Some(1024));
+        state.add_progress(reporter);
+        state.add_flush_hook(|| 3);
+        state.add_counter(MOUNT_READ_BYTES, || 42);
+
+        let response = state.handle_request(r#"{"id": 1, "method": "progress"}"#);
+        let result = response.result.unwrap();
+        assert_eq!(result["operation"], "filter");
+        assert_eq!(result["tasks"][0]["current_count"], 1);
+        assert_eq!(result["tasks"][0]["total_bytes"], 4096);
+        assert_eq!(result["counters"][MOUNT_READ_BYTES], 42);
+        let report: ProgressReport = serde_json::from_value(result).unwrap();
+        assert_eq!(report.counters[MOUNT_READ_BYTES], 42);
+
+        let response = state.handle_request(r#"{"id": 2, "method": "flush_caches"}"#);
+        assert_eq!(response.result.unwrap()["flushed"], 3);
+
+        // Shutdown is only accepted once the operation handles it.
+        let response = state.handle_request(r#"{"id": 3, "method": "shutdown"}"#);
+        assert_eq!(response.error.unwrap().code, INVALID_PARAMS);
+        let mut rx = state.shutdown_requested();
+        let response = state.handle_request(r#"{"id": 4, "method": "shutdown"}"#);
+        assert_eq!(response.result.unwrap()["shutting_down"], true);
+        assert!(rx.try_recv().is_ok());
+
+        let response = state.handle_request(r#"{"id": 5, "method": "pause"}"#);
+        assert_eq!(response.error.unwrap().code, METHOD_NOT_FOUND);
+    }
+}
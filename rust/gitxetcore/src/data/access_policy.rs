@@ -0,0 +1,220 @@
+//! Per-path access control on smudge.
+//!
+//! A repository may check in a policy at `.xet/access.toml` listing the
+//! token scopes or roles needed to materialize the files matching a set of
+//! git pathspecs:
+//!
+//! ```toml
+//! [[rule]]
+//! paths = ["data/restricted"]
+//! scopes = ["restricted:read"]
+//! roles = ["data-team"]
+//! ```
+//!
+//! A file is authorized if, for every rule matching it, the current token
+//! carries at least one of the rule's scopes or roles. Files that are not
+//! authorized are left as pointer files rather than failing the checkout.
+//!
+//! This check keeps checkouts of mixed public and restricted repos usable;
+//! the server remains the authority on what a token may download.
+use crate::config::XetConfig;
+use crate::constants::ACCESS_POLICY_FILE;
+use crate::errors::{GitXetRepoError, Result};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{error, info};
+
+#[derive(Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct AccessPolicy {
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<AccessRule>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AccessRule {
+    /// Git pathspecs relative to the repository root.
+    pub paths: Vec<String>,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+impl AccessPolicy {
+    pub fn parse(contents: &str) -> Result<Self> {
+        toml::from_str(contents).map_err(|e| {
+            GitXetRepoError::Other(format!("Unable to parse {ACCESS_POLICY_FILE}: {e}"))
+        })
+    }
+
+    /// Reads the policy in the tree at HEAD, if there is one.
+    fn read_from_head(repo_path: &Path) -> Result<Option<Self>> {
+        let repo = git2::Repository::discover(repo_path)?;
+        let Ok(head) = repo.head().and_then(|h| h.peel_to_tree()) else {
+            return Ok(None);
+        };
+        let Ok(entry) = head.get_path(Path::new(ACCESS_POLICY_FILE)) else {
+            return Ok(None);
+        };
+        let blob = entry.to_object(&repo)?.peel_to_blob()?;
+        Ok(Some(Self::parse(&String::from_utf8_lossy(blob.content()))?))
+    }
+}
+
+impl AccessRule {
+    fn matches(&self, path: &Path) -> bool {
+        git2::Pathspec::new(self.paths.iter().map(String::as_str))
+            .map(|spec| spec.matches_path(path, git2::PathspecFlags::DEFAULT))
+            .unwrap_or(true)
+    }
+
+    fn satisfied_by(&self, claims: &TokenClaims) -> bool {
+        self.scopes.iter().any(|s| claims.scopes.contains(s))
+            || self.roles.iter().any(|r| claims.roles.contains(r))
+    }
+}
+
+/// The scopes and roles carried by a token.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TokenClaims {
+    pub scopes: HashSet<String>,
+    pub roles: HashSet<String>,
+}
+
+impl TokenClaims {
+    /// Reads the claims from the payload of a JWT. The signature is not
+    /// verified. A token that isn't a JWT carries no claims.
+    ///
+    /// Scopes are read from "scope" (space separated, as in OAuth) or
+    /// "scopes", and roles from "roles".
+    pub fn from_token(token: &str) -> Self {
+        let mut claims = TokenClaims::default();
+        let Some(payload) = token.split('.').nth(1) else {
+            return claims;
+        };
+        let Ok(payload) = base64::decode_config(payload, base64::URL_SAFE_NO_PAD) else {
+            return claims;
+        };
+        let Ok(payload) = serde_json::from_slice::<serde_json::Value>(&payload) else {
+            return claims;
+        };
+
+        let strings = |value: Option<&serde_json::Value>| -> Vec<String> {
+            match value {
+                Some(serde_json::Value::String(s)) => {
+                    s.split_whitespace().map(String::from).collect()
+                }
+                Some(serde_json::Value::Array(values)) => values
+                    .iter()
+                    .filter_map(|v| v.as_str().map(String::from))
+                    .collect(),
+                _ => Vec::new(),
+            }
+        };
+        claims.scopes.extend(strings(payload.get("scope")));
+        claims.scopes.extend(strings(payload.get("scopes")));
+        claims.roles.extend(strings(payload.get("roles")));
+        claims
+    }
+}
+
+pub struct AccessControl {
+    /// None if the policy could not be read, in which case no pointer file
+    /// is authorized.
+    policy: Option<AccessPolicy>,
+    claims: TokenClaims,
+}
+
+impl AccessControl {
+    pub fn new(policy: Option<AccessPolicy>, claims: TokenClaims) -> Self {
+        Self { policy, claims }
+    }
+
+    /// Returns the access control for the repository, if it has a policy.
+    pub fn from_config(config: &XetConfig) -> Option<Arc<Self>> {
+        let repo_path = config.repo_path_if_present.as_ref()?;
+        let policy = match AccessPolicy::read_from_head(repo_path) {
+            Ok(Some(policy)) => Some(policy),
+            Ok(None) => return None,
+            Err(e) => {
+                error!("{e}; leaving all files as pointer files.");
+                None
+            }
+        };
+        let claims = config
+            .user
+            .token
+            .as_deref()
+            .map(TokenClaims::from_token)
+            .unwrap_or_default();
+        info!("Enforcing {ACCESS_POLICY_FILE} with token claims {claims:?}");
+        Some(Arc::new(Self::new(policy, claims)))
+    }
+
+    /// Whether the file at path, relative to the repository root, may be
+    /// materialized.
+    pub fn allows(&self, path: &Path) -> bool {
+        let Some(policy) = &self.policy else {
+            return false;
+        };
+        policy
+            .rules
+            .iter()
+            .filter(|rule| rule.matches(path))
+            .all(|rule| rule.satisfied_by(&self.claims))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(payload: &str) -> String {
+        format!(
+            "e30.{}.sig",
+            base64::encode_config(payload, base64::URL_SAFE_NO_PAD)
+        )
+    }
+
+    #[test]
+    fn test_token_claims() {
+        let claims = TokenClaims::from_token(&token(
+            r#"{"scope": "read restricted:read", "roles": ["data-team"]}"#,
+        ));
+        assert!(claims.scopes.contains("restricted:read"));
+        assert!(claims.roles.contains("data-team"));
+
+        assert_eq!(TokenClaims::from_token("opaque"), TokenClaims::default());
+    }
+
+    #[test]
+    fn test_access_control() {
+        let policy = AccessPolicy::parse(
+            r#"
+[[rule]]
+paths = ["data/restricted"]
+scopes = ["restricted:read"]
+
+[[rule]]
+paths = ["data/hr/*.csv"]
+roles = ["hr"]
+"#,
+        )
+        .unwrap();
+
+        let none = AccessControl::new(Some(policy.clone()), TokenClaims::default());
+        assert!(none.allows(Path::new("src/main.rs")));
+        assert!(!none.allows(Path::new("data/restricted/a.bin")));
+        assert!(!none.allows(Path::new("data/hr/people.csv")));
+
+        let claims = TokenClaims::from_token(&token(r#"{"scope": "restricted:read"}"#));
+        let scoped = AccessControl::new(Some(policy), claims);
+        assert!(scoped.allows(Path::new("data/restricted/a.bin")));
+        assert!(!scoped.allows(Path::new("data/hr/people.csv")));
+
+        let invalid = AccessControl::new(None, TokenClaims::default());
+        assert!(!invalid.allows(Path::new("src/main.rs")));
+    }
+}
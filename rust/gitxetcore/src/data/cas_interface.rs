@@ -1,21 +1,50 @@
-use crate::config::XetConfig;
-use crate::constants::{GIT_XET_VERSION, LOCAL_CAS_SCHEME, MAX_CONCURRENT_DOWNLOADS};
+use crate::config::{XetConfig, PRIMARY_CAS_REMOTE};
+use crate::constants::{
+    GIT_XET_VERSION, LOCAL_CAS_SCHEME, MAX_CONCURRENT_DOWNLOADS, PACK_READ_GAP,
+};
+use crate::data::partial_smudge::PartialSmudge;
 pub use crate::data::{FILTER_BYTES_CLEANED, FILTER_BYTES_SMUDGED, FILTER_CAS_BYTES_PRODUCED};
 use crate::errors::{GitXetRepoError, Result};
+use crate::git_integration::git_hash_algorithm::read_hash_algorithm_from_config;
 use crate::git_integration::GitXetRepo;
+use crate::interrupt::check_interrupted;
+use crate::p2p::{PeerClient, XorbBoundaries};
+use crate::shared_store::{SharedStore, SharedStoreClient};
+use cas::fileio::write_all_at;
 use cas_client::{
-    new_staging_client, new_staging_client_with_progressbar, CachingClient, LocalClient,
-    RemoteClient, Staging,
+    new_staging_client, new_staging_client_with_progressbar, set_restore_progress,
+    set_restore_timeout, set_staging_min_free, CachingClient, Client, LocalClient, MirrorClient,
+    RemoteClient, RestoreProgress, Staging, CAS_MEMORY_CACHE,
 };
 use futures::prelude::stream::*;
 use merkledb::ObjectRange;
-use merklehash::MerkleHash;
+use merklehash::{HashAlgorithm, MerkleHash};
+use std::collections::HashMap;
 use std::env::current_dir;
+use std::fs::OpenOptions;
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
 use tracing::{error, info, info_span};
 
+/// Tells the user on stderr when reads wait on data restored from cold
+/// storage, so that a smudge waiting on it does not look stuck.
+fn print_restore_progress(progress: RestoreProgress) {
+    match progress {
+        RestoreProgress::Restoring(hash) => {
+            eprintln!(
+                "Data {hash} is in cold storage; restoring it, which may take a few minutes..."
+            )
+        }
+        RestoreProgress::Restored(hash, waited) => {
+            eprintln!(
+                "Restored {hash} from cold storage in {}s.",
+                waited.as_secs()
+            )
+        }
+    }
+}
+
 pub async fn create_cas_client(config: &XetConfig) -> Result<Arc<dyn Staging + Send + Sync>> {
     info!(
         "CAS staging directory located at: {:?}.",
@@ -27,18 +56,112 @@ pub async fn create_cas_client(config: &XetConfig) -> Result<Arc<dyn Staging + S
     let auth = &config.user.get_login_id();
     let repo_paths = GitXetRepo::get_remote_urls(config.repo_path().ok().map(|x| x.as_path()))
         .unwrap_or_else(|_| vec!["".to_string()]);
+    let hash_algorithm = read_hash_algorithm_from_config(config)?;
 
-    if let Some(fs_path) = endpoint.strip_prefix(LOCAL_CAS_SCHEME) {
+    if config.cache.enabled {
+        CAS_MEMORY_CACHE.set_capacity(config.cache.memory);
+    }
+    set_staging_min_free(config.staging.min_free);
+    set_restore_timeout(config.tier.restore_timeout);
+    set_restore_progress(print_restore_progress);
+
+    if config.mirror.enabled() {
+        // Blocks are read from whichever remote has them, so the peer and
+        // shared stores, which stand in for a single remote, are not used.
+        info!(
+            "Using CAS endpoint {:?} mirrored to {:?}, with {:?} replication.",
+            &endpoint, &config.mirror.remotes, &config.mirror.replication
+        );
+        let client = mirror_client(config, &repo_paths, hash_algorithm).await?;
+        if config.cache.enabled {
+            match CachingClient::new(
+                client,
+                &config.cache.path,
+                config.cache.size,
+                config.cache.blocksize,
+            ) {
+                Ok(cacheclient) => {
+                    return Ok(new_staging_client_with_progressbar(
+                        cacheclient,
+                        config.staging_path.as_deref(),
+                        hash_algorithm,
+                    ))
+                }
+                Err(e) => {
+                    error!(
+                        "Unable to use caching CAS due to: {:?}; Falling back to non-caching CAS.",
+                        &e
+                    );
+                    return Ok(new_staging_client_with_progressbar(
+                        mirror_client(config, &repo_paths, hash_algorithm).await?,
+                        config.staging_path.as_deref(),
+                        hash_algorithm,
+                    ));
+                }
+            }
+        }
+        Ok(new_staging_client_with_progressbar(
+            client,
+            config.staging_path.as_deref(),
+            hash_algorithm,
+        ))
+    } else if let Some(fs_path) = endpoint.strip_prefix(LOCAL_CAS_SCHEME) {
         info!("Using local CAS with path: {:?}.", endpoint);
         let mut path = PathBuf::from_str(fs_path)
             .map_err(|_| GitXetRepoError::InvalidLocalCasPath(fs_path.to_string()))?;
         if !path.is_absolute() {
             path = current_dir()?.join(path);
         }
-        let client = LocalClient::new(&path, false);
+        let client = LocalClient::new(&path, false).with_hash_algorithm(hash_algorithm);
         Ok(new_staging_client_with_progressbar(
             client,
             config.staging_path.as_deref(),
+            hash_algorithm,
+        ))
+    } else if config.p2p.enabled() {
+        // The peer store keeps whole xorbs on disk, so it takes the place of
+        // the block cache.
+        let remote_client = RemoteClient::from_config(
+            endpoint,
+            user_id,
+            auth,
+            repo_paths.clone(),
+            GIT_XET_VERSION.clone(),
+        )
+        .await;
+        let peer_client = PeerClient::from_config(config, remote_client, hash_algorithm)?;
+        info!(
+            "Using peer-to-peer CAS with coordinator {:?}, falling back to endpoint {:?}.",
+            &config.p2p.coordinator, &endpoint
+        );
+        Ok(new_staging_client_with_progressbar(
+            peer_client,
+            config.staging_path.as_deref(),
+            hash_algorithm,
+        ))
+    } else if let Some(store) = SharedStore::from_config(config, hash_algorithm)? {
+        // The shared store keeps whole xorbs on disk, so it takes the place
+        // of the block cache.
+        let remote_client = RemoteClient::from_config(
+            endpoint,
+            user_id,
+            auth,
+            repo_paths.clone(),
+            GIT_XET_VERSION.clone(),
+        )
+        .await;
+        let boundaries = XorbBoundaries::new(vec![
+            config.merkledb_v2_cache.clone(),
+            config.merkledb_v2_session.clone(),
+        ]);
+        info!(
+            "Using shared store at {:?}, falling back to endpoint {:?}.",
+            &config.store.path, &endpoint
+        );
+        Ok(new_staging_client_with_progressbar(
+            SharedStoreClient::new(remote_client, store, boundaries),
+            config.staging_path.as_deref(),
+            hash_algorithm,
         ))
     } else if config.cache.enabled {
         let cacheclient_result = CachingClient::new(
@@ -63,6 +186,7 @@ pub async fn create_cas_client(config: &XetConfig) -> Result<Arc<dyn Staging + S
                 Ok(new_staging_client_with_progressbar(
                     cacheclient,
                     config.staging_path.as_deref(),
+                    hash_algorithm,
                 ))
             }
             Err(e) => {
@@ -81,6 +205,7 @@ pub async fn create_cas_client(config: &XetConfig) -> Result<Arc<dyn Staging + S
                 Ok(new_staging_client_with_progressbar(
                     remote_client,
                     config.staging_path.as_deref(),
+                    hash_algorithm,
                 ))
             }
         }
@@ -97,10 +222,66 @@ pub async fn create_cas_client(config: &XetConfig) -> Result<Arc<dyn Staging + S
         Ok(new_staging_client(
             remote_client,
             config.staging_path.as_deref(),
+            hash_algorithm,
         ))
     }
 }
 
+/// A client over cas.server and the remotes of mirror.remotes.
+async fn mirror_client(
+    config: &XetConfig,
+    repo_paths: &[String],
+    hash_algorithm: HashAlgorithm,
+) -> Result<MirrorClient> {
+    let mut remotes = vec![(
+        PRIMARY_CAS_REMOTE.to_string(),
+        endpoint_client(config, &config.cas.endpoint, repo_paths, hash_algorithm).await?,
+    )];
+    for remote in &config.mirror.remotes {
+        remotes.push((
+            remote.name.clone(),
+            endpoint_client(config, &remote.endpoint, repo_paths, hash_algorithm).await?,
+        ));
+    }
+    Ok(MirrorClient::new(
+        remotes,
+        config.mirror.fetch.as_deref(),
+        config.mirror.replication,
+    )?
+    .with_hedged_reads(config.mirror.hedge_inflight))
+}
+
+/// A client to the CAS at endpoint, either local or remote.
+async fn endpoint_client(
+    config: &XetConfig,
+    endpoint: &str,
+    repo_paths: &[String],
+    hash_algorithm: HashAlgorithm,
+) -> Result<Arc<dyn Client + Send + Sync>> {
+    if let Some(fs_path) = endpoint.strip_prefix(LOCAL_CAS_SCHEME) {
+        let mut path = PathBuf::from_str(fs_path)
+            .map_err(|_| GitXetRepoError::InvalidLocalCasPath(fs_path.to_string()))?;
+        if !path.is_absolute() {
+            path = current_dir()?.join(path);
+        }
+        return Ok(Arc::new(
+            LocalClient::new(&path, false).with_hash_algorithm(hash_algorithm),
+        ));
+    }
+    let (user_id, _) = &config.user.get_user_id();
+    let auth = &config.user.get_login_id();
+    Ok(Arc::new(
+        RemoteClient::from_config(
+            endpoint,
+            user_id,
+            auth,
+            repo_paths.to_vec(),
+            GIT_XET_VERSION.clone(),
+        )
+        .await,
+    ))
+}
+
 /**  Wrapper to consolidate the logic for retrieving from CAS.   
  */
 pub async fn get_from_cas(
@@ -115,7 +296,10 @@ pub async fn get_from_cas(
     let mut query_result = cas
         .get_object_range(&prefix, &hash, vec![ranges])
         .await
-        .map_err(|e| GitXetRepoError::Other(format!("Error fetching Xorb {hash:?}: {e:?}.")))?;
+        .map_err(|e| {
+            error!("Error fetching Xorb {hash:?}: {e:?}.");
+            GitXetRepoError::from(e)
+        })?;
     Ok(std::mem::take(&mut query_result[0]))
 }
 
@@ -179,3 +363,316 @@ pub async fn data_from_chunks_to_writer(
 
     Ok(())
 }
+
+/// Writes a collection of chunks from a Vec<ObjectRange> directly into the
+/// preallocated file of a partial smudge.
+///
+/// Each block is written at its final offset as soon as it arrives, so
+/// downloads complete in any order and no intermediate sequential buffer is
+/// needed.  Blocks the partial smudge already has are skipped, and the
+/// others are recorded in it as they are written.  Intended for
+/// materializing large files.
+pub async fn data_from_chunks_to_file(
+    cas: &Arc<dyn Staging + Send + Sync>,
+    prefix: String,
+    chunks: Vec<ObjectRange>,
+    partial: &mut PartialSmudge,
+) -> Result<()> {
+    let mut offset: u64 = 0;
+    let mut offset_chunks = Vec::with_capacity(chunks.len());
+    for objr in chunks.into_iter() {
+        let len = (objr.end - objr.start) as u64;
+        // Chunks written before an interruption aren't fetched again.
+        if !partial.is_written(offset, len) {
+            offset_chunks.push((offset, objr));
+        }
+        offset += len;
+    }
+
+    let mut strm = iter(offset_chunks.into_iter().map(|(offset, objr)| {
+        let prefix = prefix.clone();
+        async move {
+            get_from_cas(cas, prefix, objr.hash, (objr.start as u64, objr.end as u64))
+                .await
+                .map(|buf| (offset, buf))
+        }
+    }))
+    .buffer_unordered(MAX_CONCURRENT_DOWNLOADS);
+
+    let mut bytes_smudged: u64 = 0;
+    while let Some(res) = strm.next().await {
+        let (offset, buf) = res?;
+        bytes_smudged += buf.len() as u64;
+        let s = info_span!("write_chunk_at");
+        let _ = s.enter();
+        write_all_at(partial.file(), &buf, offset)?;
+        partial.record(offset, buf.len() as u64)?;
+    }
+
+    FILTER_BYTES_SMUDGED.inc_by(bytes_smudged);
+
+    Ok(())
+}
+
+/// Fetches the data of files packed into shared xorbs, reading the parts of a
+/// xorb they share with one request rather than one per file. Returns the
+/// contents of each file, in order.
+pub async fn data_from_packed_chunks(
+    cas: &Arc<dyn Staging + Send + Sync>,
+    prefix: String,
+    files: Vec<Vec<ObjectRange>>,
+    read_size: u64,
+) -> Result<Vec<Vec<u8>>> {
+    let reads = plan_packed_reads(files.iter().flatten(), PACK_READ_GAP, read_size as usize);
+
+    let fetched: Vec<(ObjectRange, Vec<u8>)> = iter(reads.into_iter().map(|objr| {
+        let prefix = prefix.clone();
+        async move {
+            get_from_cas(cas, prefix, objr.hash, (objr.start as u64, objr.end as u64))
+                .await
+                .map(|buf| (objr, buf))
+        }
+    }))
+    .buffer_unordered(MAX_CONCURRENT_DOWNLOADS)
+    .try_collect()
+    .await?;
+
+    let mut reads_by_xorb = HashMap::<MerkleHash, Vec<(ObjectRange, Vec<u8>)>>::new();
+    for (objr, buf) in fetched {
+        reads_by_xorb
+            .entry(objr.hash)
+            .or_default()
+            .push((objr, buf));
+    }
+
+    let mut bytes_smudged: u64 = 0;
+    let contents = files
+        .into_iter()
+        .map(|ranges| {
+            let mut data = Vec::with_capacity(ranges.iter().map(|r| r.end - r.start).sum());
+            for r in ranges {
+                let (read, buf) = reads_by_xorb
+                    .get(&r.hash)
+                    .and_then(|reads| {
+                        reads
+                            .iter()
+                            .find(|(read, _)| read.start <= r.start && r.end <= read.end)
+                    })
+                    .ok_or_else(|| {
+                        GitXetRepoError::Other(format!(
+                            "No read of xorb {} covers {}..{}",
+                            r.hash, r.start, r.end
+                        ))
+                    })?;
+                data.extend_from_slice(&buf[r.start - read.start..r.end - read.start]);
+            }
+            bytes_smudged += data.len() as u64;
+            Ok(data)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    FILTER_BYTES_SMUDGED.inc_by(bytes_smudged);
+
+    Ok(contents)
+}
+
+/// Plans the reads covering the ranges: ranges of the same xorb at most gap
+/// bytes apart are merged into one read of up to max_read bytes. A range
+/// longer than max_read is read on its own.
+pub fn plan_packed_reads<'a>(
+    ranges: impl Iterator<Item = &'a ObjectRange>,
+    gap: usize,
+    max_read: usize,
+) -> Vec<ObjectRange> {
+    let mut ranges: Vec<&ObjectRange> = ranges.filter(|r| r.end > r.start).collect();
+    ranges.sort_by_key(|r| (r.hash, r.start));
+
+    let mut reads: Vec<ObjectRange> = Vec::new();
+    for r in ranges {
+        match reads.last_mut() {
+            Some(read)
+                if read.hash == r.hash
+                    && r.start <= read.end + gap
+                    && r.end.max(read.end) - read.start <= max_read =>
+            {
+                read.end = read.end.max(r.end);
+            }
+            _ => reads.push(r.clone()),
+        }
+    }
+    reads
+}
+
+/// A read of a bulk materialization, and the parts of the files it covers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedRead {
+    pub read: ObjectRange,
+    /// The parts of files the read covers, as the index of the file, the
+    /// offset in the file, and the start and end in the read.
+    pub segments: Vec<(usize, u64, usize, usize)>,
+}
+
+/// Plans the reads materializing all of files, the ranges of each of which
+/// are given in order. The reads are planned across the files, as by
+/// [plan_packed_reads], so that data the files share is read once, and
+/// are sorted by xorb.
+pub fn plan_bulk_reads(
+    files: &[Vec<ObjectRange>],
+    gap: usize,
+    max_read: usize,
+) -> Result<Vec<PlannedRead>> {
+    let mut reads: Vec<PlannedRead> = plan_packed_reads(files.iter().flatten(), gap, max_read)
+        .into_iter()
+        .map(|read| PlannedRead {
+            read,
+            segments: Vec::new(),
+        })
+        .collect();
+
+    for (file, ranges) in files.iter().enumerate() {
+        let mut offset: u64 = 0;
+        for r in ranges.iter().filter(|r| r.end > r.start) {
+            // Reads of a xorb are in order of their starts, and a range is
+            // covered by the last read starting at or before it, or one
+            // just before that where reads were split by max_read.
+            let candidates =
+                reads.partition_point(|p| (p.read.hash, p.read.start) <= (r.hash, r.start));
+            let planned = reads[..candidates]
+                .iter_mut()
+                .rev()
+                .find(|p| p.read.hash == r.hash && p.read.start <= r.start && r.end <= p.read.end)
+                .ok_or_else(|| {
+                    GitXetRepoError::Other(format!(
+                        "No read of xorb {} covers {}..{}",
+                        r.hash, r.start, r.end
+                    ))
+                })?;
+            planned.segments.push((
+                file,
+                offset,
+                r.start - planned.read.start,
+                r.end - planned.read.start,
+            ));
+            offset += (r.end - r.start) as u64;
+        }
+    }
+    Ok(reads)
+}
+
+/// Carries out the planned reads, writing the data of each file at its
+/// offsets in every one of its paths as reads complete. The files must
+/// already exist. on_read is called with the length of each read done.
+pub async fn data_from_planned_reads_to_files(
+    cas: &Arc<dyn Staging + Send + Sync>,
+    prefix: String,
+    plan: Vec<PlannedRead>,
+    paths: &[Vec<PathBuf>],
+    mut on_read: impl FnMut(u64),
+) -> Result<()> {
+    let mut strm = iter(plan.into_iter().map(|planned| {
+        let prefix = prefix.clone();
+        let objr = planned.read.clone();
+        async move {
+            get_from_cas(cas, prefix, objr.hash, (objr.start as u64, objr.end as u64))
+                .await
+                .map(|buf| (planned.segments, buf))
+        }
+    }))
+    .buffer_unordered(MAX_CONCURRENT_DOWNLOADS);
+
+    let mut bytes_smudged: u64 = 0;
+    while let Some(res) = strm.next().await {
+        // an interrupt leaves the files partially written, which the caller
+        // replaces with the pointer files
+        check_interrupted("run the command again")?;
+        let (segments, buf) = res?;
+        let s = info_span!("write_planned_read");
+        let _ = s.enter();
+        // Segments are grouped by file, so each file is opened once a read.
+        let mut rest = &segments[..];
+        while let Some(&(file, _, _, _)) = rest.first() {
+            let (same, others) = rest.split_at(rest.iter().take_while(|s| s.0 == file).count());
+            for path in &paths[file] {
+                let f = OpenOptions::new().write(true).open(path)?;
+                for (_, offset, start, end) in same {
+                    write_all_at(&f, &buf[*start..*end], *offset)?;
+                    bytes_smudged += (end - start) as u64;
+                }
+            }
+            rest = others;
+        }
+        on_read(buf.len() as u64);
+    }
+
+    FILTER_BYTES_SMUDGED.inc_by(bytes_smudged);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_packed_reads() {
+        let xorb_a = MerkleHash::from([1u64, 0, 0, 0]);
+        let xorb_b = MerkleHash::from([2u64, 0, 0, 0]);
+        let range = |hash, start, end| ObjectRange { hash, start, end };
+
+        let ranges = vec![
+            range(xorb_a, 100, 200),
+            range(xorb_b, 0, 50),
+            range(xorb_a, 0, 100),
+            // Within the gap of the previous range of xorb_a.
+            range(xorb_a, 250, 300),
+            // Too far from the others.
+            range(xorb_a, 1000, 1100),
+            // Would make the read longer than max_read.
+            range(xorb_a, 1150, 1600),
+            range(xorb_b, 10, 20),
+            range(xorb_b, 60, 60),
+        ];
+        let reads = plan_packed_reads(ranges.iter(), 100, 500);
+        assert_eq!(
+            reads,
+            vec![
+                range(xorb_a, 0, 300),
+                range(xorb_a, 1000, 1100),
+                range(xorb_a, 1150, 1600),
+                range(xorb_b, 0, 50),
+            ]
+        );
+    }
+    #[test]
+    fn test_plan_bulk_reads() {
+        let xorb_a = MerkleHash::from([1u64, 0, 0, 0]);
+        let xorb_b = MerkleHash::from([2u64, 0, 0, 0]);
+        let range = |hash, start, end| ObjectRange { hash, start, end };
+
+        // The second file shares its first chunk with the first, and the
+        // third is too far from the others to be read with them.
+        let files = vec![
+            vec![range(xorb_b, 0, 100), range(xorb_a, 0, 100)],
+            vec![range(xorb_a, 0, 100), range(xorb_a, 150, 200)],
+            vec![range(xorb_a, 1000, 1100)],
+        ];
+        let reads = plan_bulk_reads(&files, 100, 500).unwrap();
+        assert_eq!(
+            reads,
+            vec![
+                PlannedRead {
+                    read: range(xorb_a, 0, 200),
+                    segments: vec![(0, 100, 0, 100), (1, 0, 0, 100), (1, 100, 150, 200)],
+                },
+                PlannedRead {
+                    read: range(xorb_a, 1000, 1100),
+                    segments: vec![(2, 0, 0, 100)],
+                },
+                PlannedRead {
+                    read: range(xorb_b, 0, 100),
+                    segments: vec![(0, 0, 0, 100)],
+                },
+            ]
+        );
+    }
+}
@@ -1,25 +1,44 @@
-use super::cas_interface::create_cas_client;
+use super::access_policy::AccessControl;
+use super::cas_interface::{
+    create_cas_client, data_from_chunks_to_file, data_from_packed_chunks,
+    data_from_planned_reads_to_files, plan_bulk_reads, PlannedRead,
+};
 use super::data_processing_v1::PointerFileTranslatorV1;
 use super::data_processing_v2::PointerFileTranslatorV2;
+use super::fallback::FallbackSources;
+use super::integrity::{
+    check_blake3, check_file_blake3, forward_and_verify, record_pending_integrity,
+    HashingDataIterator, HashingWriter, ReplayDataIterator, INTEGRITY_MPSC_CHANNEL_SIZE,
+};
 use super::mdb::get_mdb_version;
 use super::mini_smudger::MiniPointerFileSmudger;
-use super::PointerFile;
-use crate::config::XetConfig;
-use crate::errors::Result;
+use super::partial_smudge::{sweep_stale, PartialSmudge};
+use super::signing::sign_pointer;
+use super::{pointer_file_from_reader, PointerFile};
+use crate::audit::{AuditLog, AUDIT_EVENT_READ, AUDIT_EVENT_SMUDGE};
+use crate::config::{IntegrityVerify, PackSettings, SigningSettings, XetConfig};
+use crate::constants::{
+    INTEGRITY_PENDING_SUBDIR, PACK_READ_GAP, PARTIAL_SMUDGE_MAX_AGE_SECS, PARTIAL_SMUDGE_SUBDIR,
+};
+use crate::errors::{GitXetRepoError, Result};
 use crate::git_integration::git_repo_salt::{read_repo_salt_by_dir, RepoSalt};
 use crate::stream::data_iterators::AsyncDataIterator;
 use crate::summaries::WholeRepoSummary;
+use cas::fileio::PositionedWriter;
 use cas_client::Staging;
 use mdb_shard::shard_version::ShardVersion;
 use merkledb::ObjectRange;
 use merklehash::MerkleHash;
 use progress_reporting::DataProgressReporter;
+use std::collections::HashSet;
+use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::mpsc::Sender;
+use std::time::Duration;
+use tokio::sync::mpsc::{channel, Sender};
 use tokio::sync::watch;
 use tokio::sync::Mutex;
-use tracing::info;
+use tracing::{error, info};
 
 use lazy_static::lazy_static;
 use prometheus::{register_int_counter, IntCounter};
@@ -44,59 +63,133 @@ pub enum PFTRouter {
 
 pub struct PointerFileTranslator {
     pub pft: PFTRouter,
+
+    /// When to record and verify whole-file hashes.
+    integrity: IntegrityVerify,
+
+    /// Where files pending verification on push are recorded, if in a repo.
+    integrity_pending: Option<PathBuf>,
+
+    /// Where files in the working tree are smudged into before replacing
+    /// them, if in a repo; otherwise next to the file.
+    partial_smudges: Option<PathBuf>,
+
+    /// Whether stale partial smudges have been swept.
+    partial_smudges_swept: std::sync::Once,
+
+    /// The key cleaned pointer files are signed with, if any.
+    signing: SigningSettings,
+
+    /// Where smudged files are recorded, if auditing is enabled.
+    audit: Option<Arc<AuditLog>>,
+
+    /// The paths and hashes of the files whose range reads have been
+    /// recorded in the audit log.
+    audited_ranges: std::sync::Mutex<HashSet<(PathBuf, String)>>,
+
+    /// The repository's access policy, if it has one.
+    access: Option<Arc<AccessControl>>,
+
+    /// The sources blocks missing from CAS are recovered from, if fallback
+    /// is enabled.
+    fallback: Option<Arc<FallbackSources>>,
+
+    /// How small files are packed together.
+    pack: PackSettings,
+}
+
+/// Parses the cleaned output as the pointer file for the data that was
+/// hashed. Returns None for small files passed through as-is.
+fn cleaned_pointer_file(
+    path: &Path,
+    cleaned: &[u8],
+    hasher: &blake3::Hasher,
+) -> Option<PointerFile> {
+    let contents = std::str::from_utf8(cleaned).ok()?;
+    let pointer = PointerFile::init_from_string(contents, path.to_str().unwrap_or(""));
+
+    // A file that happens to be a pointer file itself passes through
+    // unchanged; its filesize won't match the amount of data hashed.
+    let passthrough = pointer.blake3().is_some() || pointer.signature().is_some();
+    (pointer.is_valid() && !passthrough && pointer.filesize() == hasher.count()).then_some(pointer)
 }
 
 impl PointerFileTranslator {
+    fn new(pft: PFTRouter, config: &XetConfig) -> Self {
+        Self {
+            pft,
+            integrity: config.integrity.verify,
+            integrity_pending: config
+                .repo_path_if_present
+                .as_ref()
+                .map(|p| p.join(INTEGRITY_PENDING_SUBDIR)),
+            partial_smudges: config
+                .repo_path_if_present
+                .as_ref()
+                .map(|p| p.join(PARTIAL_SMUDGE_SUBDIR)),
+            partial_smudges_swept: std::sync::Once::new(),
+            signing: config.signing.clone(),
+            audit: AuditLog::from_config(config),
+            audited_ranges: std::sync::Mutex::new(HashSet::new()),
+            access: AccessControl::from_config(config),
+            fallback: FallbackSources::from_config(config),
+            pack: config.pack.clone(),
+        }
+    }
+
     pub async fn v1_from_config(config: &XetConfig) -> Result<Self> {
-        Ok(Self {
-            pft: PFTRouter::V1(PointerFileTranslatorV1::from_config(config).await?),
-        })
+        Ok(Self::new(
+            PFTRouter::V1(PointerFileTranslatorV1::from_config(config).await?),
+            config,
+        ))
     }
 
     pub async fn v2_from_config(config: &XetConfig, repo_salt: RepoSalt) -> Result<Self> {
-        Ok(Self {
-            pft: PFTRouter::V2(PointerFileTranslatorV2::from_config(config, repo_salt).await?),
-        })
+        Ok(Self::new(
+            PFTRouter::V2(PointerFileTranslatorV2::from_config(config, repo_salt).await?),
+            config,
+        ))
     }
 
     pub async fn v2_from_config_smudge_only(config: &XetConfig) -> Result<Self> {
-        Ok(Self {
-            pft: PFTRouter::V2(PointerFileTranslatorV2::from_config_smudge_only(config).await?),
-        })
+        Ok(Self::new(
+            PFTRouter::V2(PointerFileTranslatorV2::from_config_smudge_only(config).await?),
+            config,
+        ))
     }
 
     pub async fn from_config_in_repo(config: &XetConfig) -> Result<Self> {
         let version = get_mdb_version(config.repo_path()?, config)?;
 
         match version {
-            ShardVersion::V1 => Ok(Self {
-                pft: PFTRouter::V1(PointerFileTranslatorV1::from_config(config).await?),
-            }),
+            ShardVersion::V1 => Ok(Self::new(
+                PFTRouter::V1(PointerFileTranslatorV1::from_config(config).await?),
+                config,
+            )),
             ShardVersion::V2 => {
                 let maybe_salt = read_repo_salt_by_dir(config.repo_path()?, config)?;
 
                 if let Some(salt) = maybe_salt {
-                    Ok(Self {
-                        pft: PFTRouter::V2(
-                            PointerFileTranslatorV2::from_config(config, salt).await?,
-                        ),
-                    })
+                    Ok(Self::new(
+                        PFTRouter::V2(PointerFileTranslatorV2::from_config(config, salt).await?),
+                        config,
+                    ))
                 } else {
                     info!("Note: Repository salt unavailable; PointerFileTranslator created in smudge-only mode.");
-                    Ok(Self {
-                        pft: PFTRouter::V2(
+                    Ok(Self::new(
+                        PFTRouter::V2(
                             PointerFileTranslatorV2::from_config_smudge_only(config).await?,
                         ),
-                    })
+                        config,
+                    ))
                 }
             }
             ShardVersion::Uninitialized => {
                 info!("Note: Repository uninitialized; PointerFileTranslator created in smudge-only mode.");
-                Ok(Self {
-                    pft: PFTRouter::V2(
-                        PointerFileTranslatorV2::from_config_smudge_only(config).await?,
-                    ),
-                })
+                Ok(Self::new(
+                    PFTRouter::V2(PointerFileTranslatorV2::from_config_smudge_only(config).await?),
+                    config,
+                ))
             }
         }
     }
@@ -104,15 +197,30 @@ impl PointerFileTranslator {
     #[cfg(test)] // Only for testing.
     pub async fn new_temporary(temp_dir: &Path, version: ShardVersion) -> Result<Self> {
         match version {
-            ShardVersion::V1 => Ok(Self {
-                pft: PFTRouter::V1(PointerFileTranslatorV1::new_temporary(temp_dir)),
-            }),
-            ShardVersion::Uninitialized | ShardVersion::V2 => Ok(Self {
-                pft: PFTRouter::V2(PointerFileTranslatorV2::new_temporary(temp_dir).await?),
-            }),
+            ShardVersion::V1 => Ok(Self::new(
+                PFTRouter::V1(PointerFileTranslatorV1::new_temporary(temp_dir)),
+                &XetConfig::empty(),
+            )),
+            ShardVersion::Uninitialized | ShardVersion::V2 => Ok(Self::new(
+                PFTRouter::V2(PointerFileTranslatorV2::new_temporary(temp_dir).await?),
+                &XetConfig::empty(),
+            )),
         }
     }
 
+    /// Overrides when whole-file hashes are recorded and verified.
+    pub fn set_integrity_verify(&mut self, integrity: IntegrityVerify) {
+        self.integrity = integrity;
+    }
+
+    pub fn set_access_control(&mut self, access: Option<Arc<AccessControl>>) {
+        self.access = access;
+    }
+
+    pub fn set_audit_log(&mut self, audit: Option<Arc<AuditLog>>) {
+        self.audit = audit;
+    }
+
     pub fn set_enable_global_dedup_queries(&mut self, enable: bool) {
         if let PFTRouter::V2(ref mut p) = &mut self.pft {
             p.set_enable_global_dedup_queries(enable);
@@ -179,17 +287,65 @@ impl PointerFileTranslator {
         path: &Path,
         reader: impl AsyncDataIterator + 'static,
     ) -> Result<Vec<u8>> {
-        match &self.pft {
-            PFTRouter::V1(ref p) => p.clean_file(path, reader).await,
-            PFTRouter::V2(ref p) => p.clean_file(path, reader).await,
-        }
+        self.clean_file_and_report_progress(path, reader, &None)
+            .await
     }
 
+    /// Cleans the file. If integrity verification is enabled, the whole file
+    /// is hashed on the way through and the hash recorded in the pointer file.
+    /// If signing is configured, the pointer file is signed.
     pub async fn clean_file_and_report_progress(
         &self,
         path: &Path,
         reader: impl AsyncDataIterator + 'static,
         progress_indicator: &Option<Arc<DataProgressReporter>>,
+    ) -> Result<Vec<u8>> {
+        if !self.integrity.records_hash() && !self.signing.signs() {
+            return self
+                .clean_file_unverified(path, reader, progress_indicator)
+                .await;
+        }
+
+        let (reader, hasher) = HashingDataIterator::new(reader);
+        let cleaned = self
+            .clean_file_unverified(path, reader, progress_indicator)
+            .await?;
+        let hasher = hasher.lock().unwrap().clone();
+        let Some(mut pointer) = cleaned_pointer_file(path, &cleaned, &hasher) else {
+            return Ok(cleaned);
+        };
+
+        if self.integrity.records_hash() {
+            self.record_integrity_hash(&mut pointer, &hasher)?;
+        }
+        if self.signing.signs() {
+            sign_pointer(&self.signing, path, &mut pointer).await?;
+        }
+        Ok(pointer.to_string().into_bytes())
+    }
+
+    /// Adds the whole-file hash to the pointer file.
+    fn record_integrity_hash(
+        &self,
+        pointer: &mut PointerFile,
+        hasher: &blake3::Hasher,
+    ) -> Result<()> {
+        let blake3 = hasher.finalize().to_hex();
+        pointer.set_blake3(blake3.as_str());
+
+        if self.integrity == IntegrityVerify::OnPush {
+            if let Some(pending) = &self.integrity_pending {
+                record_pending_integrity(pending, &pointer.hash()?, blake3.as_str())?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn clean_file_unverified(
+        &self,
+        path: &Path,
+        reader: impl AsyncDataIterator + 'static,
+        progress_indicator: &Option<Arc<DataProgressReporter>>,
     ) -> Result<Vec<u8>> {
         match &self.pft {
             PFTRouter::V1(ref p) => {
@@ -220,6 +376,56 @@ impl PointerFileTranslator {
     /// If passthrough is set, a failed parse of the pointer file will pass
     /// through all the contents directly to the writer.
     pub async fn smudge_file(
+        &self,
+        path: &PathBuf,
+        mut reader: impl AsyncDataIterator,
+        writer: &mut impl std::io::Write,
+        passthrough: bool,
+        range: Option<(usize, usize)>,
+    ) -> Result<()> {
+        let verify = self.integrity == IntegrityVerify::Always && range.is_none();
+        if !verify && self.audit.is_none() && self.access.is_none() && self.fallback.is_none() {
+            return self
+                .smudge_file_unverified(path, reader, writer, passthrough, range)
+                .await;
+        }
+
+        let (pointer, data) = pointer_file_from_reader(path, &mut reader, false).await?;
+        if pointer.is_some() && !self.allows(path) {
+            if range.is_some() {
+                return Err(GitXetRepoError::AccessDenied(format!("{path:?}")));
+            }
+            info!("Not authorized to smudge {path:?}; leaving the pointer file in place");
+            writer.write_all(&data)?;
+            return Ok(());
+        }
+        let passthrough_hash = blake3::hash(&data);
+        let reader = ReplayDataIterator::new(data, reader);
+
+        if let Some(pointer) = &pointer {
+            self.record_access(AUDIT_EVENT_SMUDGE, None, path, pointer)?;
+            self.recover_missing_blocks(path, pointer).await?;
+        }
+
+        let Some(expected) = pointer.as_ref().and_then(|p| p.blake3()).filter(|_| verify) else {
+            return self
+                .smudge_file_unverified(path, reader, writer, passthrough, range)
+                .await;
+        };
+
+        let mut hashing_writer = HashingWriter::new(writer);
+        self.smudge_file_unverified(path, reader, &mut hashing_writer, passthrough, range)
+            .await?;
+
+        let computed = hashing_writer.finalize();
+        if computed == passthrough_hash {
+            // The pointer file itself was passed through.
+            return Ok(());
+        }
+        check_blake3(path, expected, &computed)
+    }
+
+    async fn smudge_file_unverified(
         &self,
         path: &PathBuf,
         reader: impl AsyncDataIterator,
@@ -238,12 +444,82 @@ impl PointerFileTranslator {
             }
         }
     }
+
     /// Smudges a file reading a pointer file from reader, and writing
     /// all results including errors to the writer MPSC channel
     ///
     /// If the reader is not a pointer file, we passthrough the contents
     /// to the writer.
     pub async fn smudge_file_to_mpsc(
+        &self,
+        path: &Path,
+        mut reader: impl AsyncDataIterator,
+        writer: &Sender<Result<Vec<u8>>>,
+        ready: &Option<watch::Sender<bool>>,
+        progress_indicator: &Option<Arc<DataProgressReporter>>,
+    ) -> usize {
+        let verify = self.integrity == IntegrityVerify::Always;
+        if !verify && self.audit.is_none() && self.access.is_none() && self.fallback.is_none() {
+            return self
+                .smudge_file_to_mpsc_unverified(path, reader, writer, ready, progress_indicator)
+                .await;
+        }
+
+        let (pointer, data) = match pointer_file_from_reader(path, &mut reader, false).await {
+            Ok(r) => r,
+            Err(e) => {
+                if let Err(e) = writer.send(Err(e)).await {
+                    error!("Unable to send smudge error {:?} as channel has closed", e);
+                }
+                return 0;
+            }
+        };
+        if pointer.is_some() && !self.allows(path) {
+            info!("Not authorized to smudge {path:?}; leaving the pointer file in place");
+            if let Some(ready_signal) = ready {
+                let _ = ready_signal.send(true);
+            }
+            if let Err(e) = writer.send(Ok(data)).await {
+                error!("Unable to send pointer file {:?} as channel has closed", e);
+            }
+            return 0;
+        }
+        let passthrough_hash = blake3::hash(&data);
+        let reader = ReplayDataIterator::new(data, reader);
+
+        if let Some(pointer) = &pointer {
+            let recorded = self.record_access(AUDIT_EVENT_SMUDGE, None, path, pointer);
+            let recovered = match recorded {
+                Ok(()) => self.recover_missing_blocks(path, pointer).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = recovered {
+                if let Err(e) = writer.send(Err(e)).await {
+                    error!("Unable to send smudge error {:?} as channel has closed", e);
+                }
+                return 0;
+            }
+        }
+
+        let Some(expected) = pointer.as_ref().and_then(|p| p.blake3()).filter(|_| verify) else {
+            return self
+                .smudge_file_to_mpsc_unverified(path, reader, writer, ready, progress_indicator)
+                .await;
+        };
+
+        let (tx, rx) = channel(INTEGRITY_MPSC_CHANNEL_SIZE);
+        let smudge = async move {
+            let ret = self
+                .smudge_file_to_mpsc_unverified(path, reader, &tx, ready, progress_indicator)
+                .await;
+            drop(tx);
+            ret
+        };
+        let verify = forward_and_verify(path, expected, Some(passthrough_hash), rx, writer);
+        tokio::join!(smudge, verify).0
+    }
+
+    async fn smudge_file_to_mpsc_unverified(
         &self,
         path: &Path,
         reader: impl AsyncDataIterator,
@@ -263,12 +539,185 @@ impl PointerFileTranslator {
         }
     }
 
+    /// Whether the repository's access policy allows the file at path to be
+    /// materialized with the current token.
+    pub fn allows(&self, path: &Path) -> bool {
+        self.access
+            .as_ref()
+            .map_or(true, |access| access.allows(path))
+    }
+
+    /// Fails with [GitXetRepoError::AccessDenied] unless the access policy
+    /// allows the file at path, relative to the repository root, to be read.
+    fn check_allowed(&self, path: &Path) -> Result<()> {
+        if self.allows(path) {
+            Ok(())
+        } else {
+            Err(GitXetRepoError::AccessDenied(format!("{path:?}")))
+        }
+    }
+
+    /// Checks the access policy allows the file of pointer at path to be
+    /// read, then records the read in the audit log.
+    fn authorize_read(
+        &self,
+        event: &str,
+        reference: Option<&str>,
+        path: &Path,
+        pointer: &PointerFile,
+    ) -> Result<()> {
+        self.check_allowed(path)?;
+        self.record_access(event, reference, path, pointer)
+    }
+
+    /// Records the first range read of the file of pointer at path in the
+    /// audit log; the many reads after it of the same file aren't.
+    fn record_range_read(
+        &self,
+        event: &str,
+        reference: Option<&str>,
+        path: &Path,
+        pointer: &PointerFile,
+    ) -> Result<()> {
+        if self.audit.is_none() {
+            return Ok(());
+        }
+        let key = (path.to_path_buf(), pointer.hash_string().clone());
+        if !self.audited_ranges.lock().unwrap().insert(key.clone()) {
+            return Ok(());
+        }
+        let recorded = self.record_access(event, reference, path, pointer);
+        if recorded.is_err() {
+            self.audited_ranges.lock().unwrap().remove(&key);
+        }
+        recorded
+    }
+
+    /// Whether accesses are recorded in an audit log.
+    pub fn audits(&self) -> bool {
+        self.audit.is_some()
+    }
+
+    /// Records an access to the contents of the pointer file at path in the
+    /// audit log, if auditing is enabled.
+    pub fn record_access(
+        &self,
+        event: &str,
+        reference: Option<&str>,
+        path: &Path,
+        pointer: &PointerFile,
+    ) -> Result<()> {
+        match &self.audit {
+            Some(audit) => audit.record(event, reference, path, pointer),
+            None => Ok(()),
+        }
+    }
+
+    /// Rebuilds the blocks of the file of pointer that are missing from CAS
+    /// from its recorded source, if fallback is enabled.
+    async fn recover_missing_blocks(&self, path: &Path, pointer: &PointerFile) -> Result<()> {
+        let Some(fallback) = &self.fallback else {
+            return Ok(());
+        };
+        if fallback.source(pointer.hash_string()).is_none() {
+            return Ok(());
+        }
+        let blocks = self.derive_blocks(&pointer.hash()?).await?;
+        fallback
+            .recover(&self.get_cas(), &self.get_prefix(), path, pointer, &blocks)
+            .await
+    }
+
+    /// The recorded whole-file hash to verify a full smudge of this pointer
+    /// against, if verification is enabled.
+    fn expected_blake3<'a>(&self, pointer: &'a PointerFile) -> Option<&'a str> {
+        if self.integrity == IntegrityVerify::Always {
+            pointer.blake3()
+        } else {
+            None
+        }
+    }
+
+    /// Writes the contents of the file of pointer, or the range of it, to
+    /// writer. path is the path of the file relative to the repository
+    /// root, which the access policy must allow. The read is recorded in the
+    /// audit log.
     pub async fn smudge_file_from_pointer(
         &self,
         path: &Path,
         pointer: &PointerFile,
         writer: &mut impl std::io::Write,
         range: Option<(usize, usize)>,
+    ) -> Result<()> {
+        self.authorize_read(AUDIT_EVENT_READ, None, path, pointer)?;
+        self.smudge_file_from_pointer_allowed(path, pointer, writer, range)
+            .await
+    }
+
+    async fn smudge_file_from_pointer_allowed(
+        &self,
+        path: &Path,
+        pointer: &PointerFile,
+        writer: &mut impl std::io::Write,
+        range: Option<(usize, usize)>,
+    ) -> Result<()> {
+        self.recover_missing_blocks(path, pointer).await?;
+        let expected = match range {
+            Some(_) => None,
+            None => self.expected_blake3(pointer),
+        };
+
+        let Some(expected) = expected else {
+            return self
+                .smudge_file_from_pointer_unverified(path, pointer, writer, range)
+                .await;
+        };
+
+        let mut hashing_writer = HashingWriter::new(writer);
+        self.smudge_file_from_pointer_unverified(path, pointer, &mut hashing_writer, range)
+            .await?;
+        check_blake3(path, expected, &hashing_writer.finalize())
+    }
+
+    /// Reads len bytes of the file of pointer from offset, fewer if the file
+    /// ends first. Only the blocks holding the range are fetched, so reading
+    /// part of a large file never reconstructs all of it. path is the path
+    /// of the file relative to the repository root, which the access policy
+    /// must allow. The first read of the file is recorded in the audit log
+    /// as event, at reference if given.
+    pub async fn read_range(
+        &self,
+        event: &str,
+        reference: Option<&str>,
+        path: &Path,
+        pointer: &PointerFile,
+        offset: u64,
+        len: u64,
+    ) -> Result<Vec<u8>> {
+        self.check_allowed(path)?;
+        self.record_range_read(event, reference, path, pointer)?;
+        let size = pointer.filesize();
+        let start = offset.min(size);
+        let end = offset.saturating_add(len).min(size);
+        let mut output = Vec::with_capacity((end - start) as usize);
+        if start < end {
+            self.smudge_file_from_pointer_allowed(
+                path,
+                pointer,
+                &mut output,
+                Some((start as usize, end as usize)),
+            )
+            .await?;
+        }
+        Ok(output)
+    }
+
+    async fn smudge_file_from_pointer_unverified(
+        &self,
+        path: &Path,
+        pointer: &PointerFile,
+        writer: &mut impl std::io::Write,
+        range: Option<(usize, usize)>,
     ) -> Result<()> {
         match &self.pft {
             PFTRouter::V1(ref p) => {
@@ -282,6 +731,161 @@ impl PointerFileTranslator {
         }
     }
 
+    /// Smudges a pointer file into the file at path. The data is written to
+    /// a partial file under the repository's xet directory, which replaces
+    /// the file once complete; a smudge interrupted part way is resumed the
+    /// next time the file is smudged. See
+    /// [partial_smudge](super::partial_smudge).
+    ///
+    /// With preallocate, the partial file is preallocated and the blocks are
+    /// written at their final offsets as they arrive.
+    ///
+    /// repo_path is the path of the file relative to the repository root,
+    /// which the access policy must allow. The smudge is recorded in the
+    /// audit log.
+    pub async fn smudge_file_from_pointer_to_path(
+        &self,
+        repo_path: &Path,
+        path: &Path,
+        pointer: &PointerFile,
+        preallocate: bool,
+    ) -> Result<()> {
+        self.authorize_read(AUDIT_EVENT_SMUDGE, None, repo_path, pointer)?;
+        let hash = pointer.hash()?;
+        let size = pointer.filesize();
+        let partial_dir = match &self.partial_smudges {
+            Some(dir) => {
+                self.partial_smudges_swept.call_once(|| {
+                    sweep_stale(dir, Duration::from_secs(PARTIAL_SMUDGE_MAX_AGE_SECS));
+                });
+                dir.as_path()
+            }
+            None => path.parent().unwrap_or(Path::new("")),
+        };
+        let mut partial = PartialSmudge::open(partial_dir, path, &hash, size, preallocate)?;
+
+        if preallocate {
+            info!("Smudging file {:?} with positioned writes", &path);
+            self.recover_missing_blocks(path, pointer).await?;
+            let blocks = self.derive_blocks(&hash).await?;
+            data_from_chunks_to_file(&self.get_cas(), self.get_prefix(), blocks, &mut partial)
+                .await?;
+        } else if partial.resume_offset() < size {
+            let offset = partial.resume_offset();
+            let file = partial.file().try_clone()?;
+            let mut writer = BufWriter::new(PositionedWriter::at(file, offset));
+            let range = (offset > 0).then_some((offset as usize, size as usize));
+            self.recover_missing_blocks(path, pointer).await?;
+            self.smudge_file_from_pointer_unverified(path, pointer, &mut writer, range)
+                .await?;
+            writer.flush()?;
+        }
+
+        if let Some(expected) = self.expected_blake3(pointer) {
+            // Blocks land out of order, or across smudges, so the file is
+            // read back to be hashed.
+            partial.file().sync_data()?;
+            if let Err(e) = check_file_blake3(partial.partial_path(), expected) {
+                partial.discard();
+                return Err(e);
+            }
+        }
+        partial.finish()
+    }
+
+    /// Whether a file of this size is packed with other small files, and so
+    /// is best smudged with them by
+    /// [smudge_packed_files](Self::smudge_packed_files).
+    pub fn packs(&self, size: u64) -> bool {
+        self.pack.packs(size)
+    }
+
+    /// Smudges small files packed into shared xorbs together, fetching the
+    /// parts of each xorb they're in with as few requests as possible.
+    /// Returns the contents of each file, in order. The paths are relative
+    /// to the repository root, and the access policy must allow each. Each
+    /// smudge is recorded in the audit log.
+    pub async fn smudge_packed_files(
+        &self,
+        pointers: &[(PathBuf, PointerFile)],
+    ) -> Result<Vec<Vec<u8>>> {
+        let mut files = Vec::with_capacity(pointers.len());
+        for (path, pointer) in pointers {
+            self.authorize_read(AUDIT_EVENT_SMUDGE, None, path, pointer)?;
+            self.recover_missing_blocks(path, pointer).await?;
+            files.push(self.derive_blocks(&pointer.hash()?).await?);
+        }
+        let contents = data_from_packed_chunks(
+            &self.get_cas(),
+            self.get_prefix(),
+            files,
+            self.pack.read_size,
+        )
+        .await?;
+
+        for ((path, pointer), data) in pointers.iter().zip(contents.iter()) {
+            if let Some(expected) = self.expected_blake3(pointer) {
+                check_blake3(path, expected, &blake3::hash(data))?;
+            }
+        }
+        Ok(contents)
+    }
+
+    /// Plans the reads materializing the files of the pointer files all at
+    /// once: the data the files share is read once, and the reads are sorted
+    /// by xorb rather than in the order of the files.
+    pub async fn plan_bulk_materialize(
+        &self,
+        pointers: &[PointerFile],
+    ) -> Result<Vec<PlannedRead>> {
+        let mut files = Vec::with_capacity(pointers.len());
+        for pointer in pointers {
+            files.push(self.derive_blocks(&pointer.hash()?).await?);
+        }
+        plan_bulk_reads(&files, PACK_READ_GAP, self.pack.read_size as usize)
+    }
+
+    /// Materializes the files of the pointer files to each of their paths
+    /// with the reads planned by
+    /// [plan_bulk_materialize](Self::plan_bulk_materialize). on_read is
+    /// called with the length of each read done. The paths are relative to
+    /// the repository root, and the access policy must allow each.
+    pub async fn bulk_materialize(
+        &self,
+        files: &[(PointerFile, Vec<PathBuf>)],
+        plan: Vec<PlannedRead>,
+        on_read: impl FnMut(u64),
+    ) -> Result<()> {
+        for (pointer, paths) in files {
+            for path in paths {
+                self.authorize_read(AUDIT_EVENT_SMUDGE, None, path, pointer)?;
+            }
+            if let Some(path) = paths.first() {
+                self.recover_missing_blocks(path, pointer).await?;
+            }
+            for path in paths {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                // Preallocate so the filesystem can lay out the file contiguously.
+                std::fs::File::create(path)?.set_len(pointer.filesize())?;
+            }
+        }
+
+        let paths: Vec<Vec<PathBuf>> = files.iter().map(|(_, paths)| paths.clone()).collect();
+        data_from_planned_reads_to_files(&self.get_cas(), self.get_prefix(), plan, &paths, on_read)
+            .await?;
+
+        for (pointer, paths) in files {
+            if let Some(expected) = self.expected_blake3(pointer) {
+                for path in paths {
+                    check_file_blake3(path, expected)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub async fn smudge_file_from_hash(
         &self,
         path: Option<PathBuf>,
@@ -296,7 +900,9 @@ impl PointerFileTranslator {
     }
 
     /// This function does not return, but any results are sent
-    /// through the mpsc channel
+    /// through the mpsc channel. path is the path of the file relative to
+    /// the repository root, which the access policy must allow. The smudge
+    /// is recorded in the audit log.
     pub async fn smudge_file_from_pointer_to_mpsc(
         &self,
         path: &Path,
@@ -304,6 +910,54 @@ impl PointerFileTranslator {
         writer: &Sender<Result<Vec<u8>>>,
         ready: &Option<watch::Sender<bool>>,
         progress_indicator: &Option<Arc<DataProgressReporter>>,
+    ) -> usize {
+        let recovered = match self.authorize_read(AUDIT_EVENT_SMUDGE, None, path, pointer) {
+            Ok(()) => self.recover_missing_blocks(path, pointer).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = recovered {
+            if let Err(e) = writer.send(Err(e)).await {
+                error!("Unable to send smudge error {:?} as channel has closed", e);
+            }
+            return 0;
+        }
+        let Some(expected) = self.expected_blake3(pointer) else {
+            return self
+                .smudge_file_from_pointer_to_mpsc_unverified(
+                    path,
+                    pointer,
+                    writer,
+                    ready,
+                    progress_indicator,
+                )
+                .await;
+        };
+
+        let (tx, rx) = channel(INTEGRITY_MPSC_CHANNEL_SIZE);
+        let smudge = async move {
+            let ret = self
+                .smudge_file_from_pointer_to_mpsc_unverified(
+                    path,
+                    pointer,
+                    &tx,
+                    ready,
+                    progress_indicator,
+                )
+                .await;
+            drop(tx);
+            ret
+        };
+        let verify = forward_and_verify(path, expected, None, rx, writer);
+        tokio::join!(smudge, verify).0
+    }
+
+    async fn smudge_file_from_pointer_to_mpsc_unverified(
+        &self,
+        path: &Path,
+        pointer: &PointerFile,
+        writer: &Sender<Result<Vec<u8>>>,
+        ready: &Option<watch::Sender<bool>>,
+        progress_indicator: &Option<Arc<DataProgressReporter>>,
     ) -> usize {
         match &self.pft {
             PFTRouter::V1(ref p) => {
@@ -400,3 +1054,317 @@ impl PointerFileTranslator {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::AUDIT_EVENT_MOUNT_READ;
+    use crate::config::AuditSettings;
+    use crate::constants::GIT_MAX_PACKET_SIZE;
+    use crate::data::access_policy::{AccessPolicy, TokenClaims};
+    use crate::stream::data_iterators::AsyncFileIterator;
+    use rand::{Rng, SeedableRng};
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_smudge_to_file_with_positioned_writes() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let input_bytes: Vec<u8> = (0..4 * 1024 * 1024).map(|_| rng.gen()).collect();
+
+        let stagedir = TempDir::new().unwrap();
+        let translator = PointerFileTranslator::new_temporary(stagedir.path(), ShardVersion::V2)
+            .await
+            .unwrap();
+
+        let input = std::io::Cursor::new(input_bytes.clone());
+        let async_input = AsyncFileIterator::new(input, GIT_MAX_PACKET_SIZE);
+        let cleaned = translator
+            .clean_file(&PathBuf::new(), async_input)
+            .await
+            .unwrap();
+        translator.finalize_cleaning().await.unwrap();
+
+        let pointer = PointerFile::init_from_string(std::str::from_utf8(&cleaned).unwrap(), "");
+        assert!(pointer.is_valid());
+
+        let dest = stagedir.path().join("smudged");
+        translator
+            .smudge_file_from_pointer_to_path(Path::new(""), &dest, &pointer, true)
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read(&dest).unwrap(), input_bytes);
+
+        // An interrupted sequential smudge is resumed.
+        let dest = stagedir.path().join("resumed");
+        let partial = PartialSmudge::open(
+            stagedir.path(),
+            &dest,
+            &pointer.hash().unwrap(),
+            input_bytes.len() as u64,
+            false,
+        )
+        .unwrap();
+        cas::fileio::write_all_at(partial.file(), &input_bytes[..1000], 0).unwrap();
+        drop(partial);
+        translator
+            .smudge_file_from_pointer_to_path(Path::new(""), &dest, &pointer, false)
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read(&dest).unwrap(), input_bytes);
+    }
+
+    #[tokio::test]
+    async fn test_read_range() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(2);
+        let input_bytes: Vec<u8> = (0..3 * 1024 * 1024).map(|_| rng.gen()).collect();
+
+        let stagedir = TempDir::new().unwrap();
+        let mut translator =
+            PointerFileTranslator::new_temporary(stagedir.path(), ShardVersion::V2)
+                .await
+                .unwrap();
+        // Ranges aren't verified against the hash of the whole file.
+        translator.set_integrity_verify(IntegrityVerify::Always);
+
+        let input = std::io::Cursor::new(input_bytes.clone());
+        let async_input = AsyncFileIterator::new(input, GIT_MAX_PACKET_SIZE);
+        let cleaned = translator
+            .clean_file(&PathBuf::new(), async_input)
+            .await
+            .unwrap();
+        translator.finalize_cleaning().await.unwrap();
+        let pointer = PointerFile::init_from_string(std::str::from_utf8(&cleaned).unwrap(), "");
+
+        let size = input_bytes.len() as u64;
+        for (offset, len) in [
+            (0, 10),
+            (1024 * 1024 - 5, 100_000),
+            (size - 3, 10),
+            (0, size),
+        ] {
+            let data = translator
+                .read_range(AUDIT_EVENT_READ, None, Path::new(""), &pointer, offset, len)
+                .await
+                .unwrap();
+            let end = (offset + len).min(size);
+            assert_eq!(data, input_bytes[offset as usize..end as usize]);
+        }
+        assert!(translator
+            .read_range(
+                AUDIT_EVENT_READ,
+                None,
+                Path::new(""),
+                &pointer,
+                size + 1,
+                10
+            )
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(translator
+            .read_range(AUDIT_EVENT_READ, None, Path::new(""), &pointer, 10, 0)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_access_policy_on_reads() {
+        let input_bytes = b"restricted contents".to_vec();
+
+        let stagedir = TempDir::new().unwrap();
+        let mut translator =
+            PointerFileTranslator::new_temporary(stagedir.path(), ShardVersion::V2)
+                .await
+                .unwrap();
+        let policy = AccessPolicy::parse(
+            r#"
+[[rule]]
+paths = ["data/restricted"]
+scopes = ["restricted:read"]
+"#,
+        )
+        .unwrap();
+        translator.set_access_control(Some(Arc::new(AccessControl::new(
+            Some(policy),
+            TokenClaims::default(),
+        ))));
+
+        let input = std::io::Cursor::new(input_bytes.clone());
+        let async_input = AsyncFileIterator::new(input, GIT_MAX_PACKET_SIZE);
+        let cleaned = translator
+            .clean_file(&PathBuf::new(), async_input)
+            .await
+            .unwrap();
+        translator.finalize_cleaning().await.unwrap();
+        let pointer = PointerFile::init_from_string(std::str::from_utf8(&cleaned).unwrap(), "");
+
+        let restricted = Path::new("data/restricted/a.csv");
+        let public = Path::new("data/public/a.csv");
+        let denied = |r: Result<_>| matches!(r, Err(GitXetRepoError::AccessDenied(_)));
+
+        let mut output = Vec::new();
+        assert!(denied(
+            translator
+                .smudge_file_from_pointer(restricted, &pointer, &mut output, None)
+                .await
+        ));
+        assert!(output.is_empty());
+        translator
+            .smudge_file_from_pointer(public, &pointer, &mut output, None)
+            .await
+            .unwrap();
+        assert_eq!(output, input_bytes);
+
+        assert!(denied(
+            translator
+                .read_range(AUDIT_EVENT_READ, None, restricted, &pointer, 0, 5)
+                .await
+        ));
+        assert_eq!(
+            translator
+                .read_range(AUDIT_EVENT_READ, None, public, &pointer, 0, 5)
+                .await
+                .unwrap(),
+            input_bytes[..5]
+        );
+
+        let dest = stagedir.path().join("smudged");
+        assert!(denied(
+            translator
+                .smudge_file_from_pointer_to_path(restricted, &dest, &pointer, false)
+                .await
+        ));
+        assert!(!dest.exists());
+        assert!(denied(
+            translator
+                .smudge_packed_files(&[(restricted.to_path_buf(), pointer.clone())])
+                .await
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reads_audited() {
+        let input_bytes = b"audited contents".to_vec();
+
+        let stagedir = TempDir::new().unwrap();
+        let log_path = stagedir.path().join("audit.jsonl");
+        let mut translator =
+            PointerFileTranslator::new_temporary(stagedir.path(), ShardVersion::V2)
+                .await
+                .unwrap();
+        let settings = AuditSettings {
+            path: Some(log_path.clone()),
+            ..Default::default()
+        };
+        translator.set_audit_log(AuditLog::new(&settings, "alice".to_string()).map(Arc::new));
+
+        let input = std::io::Cursor::new(input_bytes.clone());
+        let async_input = AsyncFileIterator::new(input, GIT_MAX_PACKET_SIZE);
+        let cleaned = translator
+            .clean_file(&PathBuf::new(), async_input)
+            .await
+            .unwrap();
+        translator.finalize_cleaning().await.unwrap();
+        let pointer = PointerFile::init_from_string(std::str::from_utf8(&cleaned).unwrap(), "");
+
+        let mut output = Vec::new();
+        translator
+            .smudge_file_from_pointer(Path::new("cat.csv"), &pointer, &mut output, None)
+            .await
+            .unwrap();
+        // Only the first range read of a file is recorded.
+        for offset in 0..3 {
+            translator
+                .read_range(
+                    AUDIT_EVENT_MOUNT_READ,
+                    Some("main"),
+                    Path::new("mount.csv"),
+                    &pointer,
+                    offset,
+                    5,
+                )
+                .await
+                .unwrap();
+        }
+        let dest = stagedir.path().join("smudged");
+        translator
+            .smudge_file_from_pointer_to_path(Path::new("checkout.csv"), &dest, &pointer, false)
+            .await
+            .unwrap();
+
+        let records: Vec<serde_json::Value> = std::fs::read_to_string(&log_path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let events: Vec<(&str, &str)> = records
+            .iter()
+            .map(|r| (r["event"].as_str().unwrap(), r["path"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                (AUDIT_EVENT_READ, "cat.csv"),
+                (AUDIT_EVENT_MOUNT_READ, "mount.csv"),
+                (AUDIT_EVENT_SMUDGE, "checkout.csv"),
+            ]
+        );
+        assert_eq!(records[1]["ref"], "main");
+    }
+
+    #[tokio::test]
+    async fn test_integrity_hash_recorded_and_verified() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let input_bytes: Vec<u8> = (0..1024 * 1024).map(|_| rng.gen()).collect();
+
+        let stagedir = TempDir::new().unwrap();
+        let mut translator =
+            PointerFileTranslator::new_temporary(stagedir.path(), ShardVersion::V2)
+                .await
+                .unwrap();
+        translator.set_integrity_verify(IntegrityVerify::Always);
+
+        let input = std::io::Cursor::new(input_bytes.clone());
+        let async_input = AsyncFileIterator::new(input, GIT_MAX_PACKET_SIZE);
+        let cleaned = translator
+            .clean_file(&PathBuf::new(), async_input)
+            .await
+            .unwrap();
+        translator.finalize_cleaning().await.unwrap();
+
+        let pointer = PointerFile::init_from_string(std::str::from_utf8(&cleaned).unwrap(), "");
+        assert!(pointer.is_valid());
+        let expected = blake3::hash(&input_bytes).to_hex();
+        assert_eq!(pointer.blake3(), Some(expected.as_str()));
+
+        let mut output = Vec::new();
+        translator
+            .smudge_file_from_pointer(&PathBuf::new(), &pointer, &mut output, None)
+            .await
+            .unwrap();
+        assert_eq!(output, input_bytes);
+
+        // A pointer with the wrong hash fails loudly.
+        let mut bad_pointer = pointer.clone();
+        bad_pointer.set_blake3(blake3::hash(b"not the data").to_hex().as_str());
+        let mut output = Vec::new();
+        let result = translator
+            .smudge_file_from_pointer(&PathBuf::new(), &bad_pointer, &mut output, None)
+            .await;
+        assert!(matches!(
+            result,
+            Err(crate::errors::GitXetRepoError::IntegrityCheckFailed(_))
+        ));
+
+        // Ranged reads cannot be checked against the whole-file hash.
+        let mut output = Vec::new();
+        translator
+            .smudge_file_from_pointer(&PathBuf::new(), &bad_pointer, &mut output, Some((0, 10)))
+            .await
+            .unwrap();
+        assert_eq!(output, &input_bytes[..10]);
+    }
+}
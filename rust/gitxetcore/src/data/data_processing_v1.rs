@@ -1,6 +1,5 @@
 use std::clone::Clone;
 use std::collections::HashMap;
-use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
@@ -32,13 +31,12 @@ use super::small_file_determination::PassThroughFileStatus;
 use crate::config::XetConfig;
 use crate::constants::{
     DERIVE_BLOCKS_CACHE_COUNT, GIT_NOTES_SUMMARIES_REF_NAME, MAX_CONCURRENT_DOWNLOADS,
-    MAX_CONCURRENT_PREFETCHES, MAX_CONCURRENT_PREFETCH_DOWNLOADS, MAX_CONCURRENT_UPLOADS,
-    PREFETCH_TRACK_COUNT, PREFETCH_WINDOW_SIZE_BYTES,
+    MAX_CONCURRENT_PREFETCHES, MAX_CONCURRENT_PREFETCH_DOWNLOADS, PREFETCH_TRACK_COUNT,
+    PREFETCH_WINDOW_SIZE_BYTES,
 };
 use crate::errors::{convert_cas_error, GitXetRepoError, Result};
 use crate::stream::data_iterators::AsyncDataIterator;
 use crate::summaries::analysis::FileAnalyzers;
-use crate::summaries::csv::CSVAnalyzer;
 use crate::summaries::WholeRepoSummary;
 
 #[derive(Default)]
@@ -209,7 +207,7 @@ impl PointerFileTranslatorV1 {
 
     pub async fn upload_cas_staged(&self, retain: bool) -> Result<()> {
         self.cas
-            .upload_all_staged(MAX_CONCURRENT_UPLOADS, retain)
+            .upload_all_staged(self.cfg.upload.concurrency, retain)
             .await
             .or_else(convert_cas_error)
     }
@@ -232,18 +230,8 @@ impl PointerFileTranslatorV1 {
         progress_indicator: &Option<Arc<DataProgressReporter>>,
     ) -> Result<Vec<u8>> {
         // First initialize any analyzers needed.
-        let mut analyzers = FileAnalyzers::default();
-
-        debug!("Including analyzers for path {:?}", &path);
-
-        let ext = path.extension();
-        if ext == Some(OsStr::new("csv")) {
-            info!("Including CSV analyzer (file extension .csv)");
-            analyzers.csv = Some(CSVAnalyzer::new(self.cfg.log.silent_summary, b','));
-        } else if ext == Some(OsStr::new("tsv")) {
-            info!("Including CSV analyzer (file extension .tsv)");
-            analyzers.csv = Some(CSVAnalyzer::new(self.cfg.log.silent_summary, b'\t'));
-        }
+        let mut analyzers =
+            FileAnalyzers::for_path(path, &self.cfg.summaries, self.cfg.log.silent_summary);
 
         // Now, test whether to pass this file through or not.
         let starting_data = {
@@ -289,7 +277,9 @@ impl PointerFileTranslatorV1 {
                 cas_bytes_produced += self.try_flush_accumulator(&mut localacc, false).await?;
             }
             // Run through any analyzers, if appropriate
-            analyzers.process_chunk(&bytes[..], path, bytes_cleaned);
+            if let Some(analyzers) = &mut analyzers {
+                analyzers.process_chunk(&bytes[..], path, bytes_cleaned);
+            }
 
             if let Some(pi) = progress_indicator {
                 pi.set_active(true);
@@ -320,7 +310,7 @@ impl PointerFileTranslatorV1 {
             MerkleNode::default()
         };
         let pointer_file: PointerFile = PointerFile::init_from_info(
-            path.to_str().unwrap(),
+            &path.to_string_lossy(),
             &filenode.hash().hex(),
             filenode.len() as u64,
         );
@@ -329,7 +319,7 @@ impl PointerFileTranslatorV1 {
         let key = filenode.hash().hex();
         let mut summarydb = self.summarydb.lock().await;
         let existing_file_summary = summarydb.entry(key.clone()).or_default();
-        if let Some(new_file_summary) = analyzers.finalize(path) {
+        if let Some(new_file_summary) = analyzers.as_mut().and_then(|a| a.finalize(path)) {
             existing_file_summary.merge_in(new_file_summary, &key);
         }
 
@@ -809,7 +799,11 @@ impl PointerFileTranslatorV1 {
         let summarydb = Arc::new(Mutex::new(WholeRepoSummary::empty(&PathBuf::default())));
 
         let localclient = cas_client::LocalClient::default();
-        let cas = cas_client::new_staging_client(localclient, Some(stage_path));
+        let cas = cas_client::new_staging_client(
+            localclient,
+            Some(stage_path),
+            merklehash::HashAlgorithm::default(),
+        );
 
         Self {
             initial_mdb_sequence_number: 0,
@@ -11,15 +11,14 @@ use mdb_shard::intershard_reference_structs::IntershardReferenceSequence;
 use mdb_shard::shard_file_handle::MDBShardFile;
 use mdb_shard::shard_file_manager::ShardFileManager;
 use mdb_shard::shard_file_reconstructor::FileReconstructor;
-use merkledb::aggregate_hashes::{cas_node_hash, file_node_hash};
+use merkledb::aggregate_hashes::{cas_node_hash_with_algorithm, file_node_hash_with_algorithm};
 use merkledb::constants::TARGET_CAS_BLOCK_SIZE;
 use merkledb::*;
-use merklehash::MerkleHash;
+use merklehash::{HashAlgorithm, MerkleHash};
 use parutils::{BatchedAsyncIterator, BufferedAsyncIterator};
 use progress_reporting::DataProgressReporter;
 use std::clone::Clone;
 use std::collections::HashMap;
-use std::ffi::OsStr;
 use std::mem::take;
 use std::ops::DerefMut;
 use std::path::{Path, PathBuf};
@@ -38,11 +37,14 @@ use super::mdb::download_shard;
 use super::remote_shard_interface::{
     shard_manager_from_config, RemoteShardInterface, SmudgeQueryPolicy,
 };
-use super::small_file_determination::{check_passthrough_status, PassThroughFileStatus};
+use super::small_file_determination::{
+    check_pack_status, check_passthrough_status, PassThroughFileStatus,
+};
 use super::*;
 use crate::config::XetConfig;
 use crate::constants::*;
 use crate::errors::{convert_cas_error, GitXetRepoError, Result};
+use crate::git_integration::git_hash_algorithm::read_hash_algorithm_from_config;
 use crate::git_integration::git_repo_salt::RepoSalt;
 use crate::stream::data_iterators::AsyncDataIterator;
 use crate::summaries::*;
@@ -79,6 +81,9 @@ pub struct PointerFileTranslatorV2 {
 
     repo_salt: Option<RepoSalt>,
 
+    // The hash algorithm of chunks, CAS blocks and files in this repository.
+    hash_algorithm: HashAlgorithm,
+
     cfg: XetConfig,
 
     lazyconfig: Option<LazyPathListConfigFile>,
@@ -115,7 +120,10 @@ impl PointerFileTranslatorV2 {
             Arc::new(Mutex::new(WholeRepoSummary::empty(&PathBuf::default())))
         };
 
+        let hash_algorithm = read_hash_algorithm_from_config(config)?;
+
         let shard_manager = Arc::new(shard_manager_from_config(config).await?);
+        shard_manager.set_hash_algorithm(hash_algorithm).await;
 
         let remote_shards = {
             if let Some(salt) = repo_salt {
@@ -148,6 +156,7 @@ impl PointerFileTranslatorV2 {
             small_file_threshold: config.cas.size_threshold,
             cas_data: Arc::new(Default::default()),
             repo_salt,
+            hash_algorithm,
             cfg: config.clone(),
             lazyconfig,
 
@@ -222,6 +231,7 @@ impl PointerFileTranslatorV2 {
             small_file_threshold: SMALL_FILE_THRESHOLD,
             cas_data: Arc::new(Default::default()),
             repo_salt: Some(repo_salt),
+            hash_algorithm: HashAlgorithm::default(),
             cfg: config, 
             lazyconfig: None,
             enable_global_dedup_queries: false,
@@ -250,7 +260,7 @@ impl PointerFileTranslatorV2 {
 
     pub async fn upload_cas_staged(&self, retain: bool) -> Result<()> {
         self.cas
-            .upload_all_staged(MAX_CONCURRENT_UPLOADS, retain)
+            .upload_all_staged(self.cfg.upload.concurrency, retain)
             .await
             .or_else(convert_cas_error)
     }
@@ -331,12 +341,37 @@ impl PointerFileTranslatorV2 {
             }
         };
 
+        // With pack.threshold set, small files are packed: their data goes
+        // into the shared xorbs along with other files', without a dedup
+        // query to the server for each, so they are only deduplicated within
+        // the repository.
+        let (starting_data, packed) =
+            check_pack_status(&mut reader, starting_data, self.cfg.pack.threshold).await?;
+
+        // Archives are chunked along their entries if enabled; the format is
+        // detected from the first bytes, and recorded with the file.
+        let chunker = if self.cfg.chunking.format_aware {
+            let head: Vec<u8> = starting_data
+                .iter()
+                .flatten()
+                .take(ChunkerId::DETECT_LEN)
+                .copied()
+                .collect();
+            ChunkerId::detect(&head)
+        } else {
+            ChunkerId::Cdc
+        };
+        debug!("Chunking {path:?} with the {chunker} chunker");
+
         // Now, start chunking.
         let raw_data_iter =
             BufferedAsyncIterator::new_with_starting_data(starting_data, reader, None);
 
         let mut generator =
-            BufferedAsyncIterator::new(async_chunk_target_default(raw_data_iter), Some(4096));
+            BufferedAsyncIterator::new(
+                async_chunk_target_with_format(raw_data_iter, self.hash_algorithm, chunker),
+                Some(4096),
+            );
         let mut bytes_cleaned: usize = 0;
 
         // TODO: This span isn't quite accurate as we hold it across `await` calls.
@@ -354,35 +389,17 @@ impl PointerFileTranslatorV2 {
 
         let mut shard_dedup_tracker = HashMap::<MerkleHash, usize>::new();
 
-        // Now get started on whatever analyzers are needed.
-        let mut analyzers = FileAnalyzers::default();
-
-        debug!("Including analyzers for path {:?}", &path);
-        let mut analyzers_active = false;
-        let ext = path.extension();
-        if ext == Some(OsStr::new("csv")) {
-            debug!("Including CSV analyzer (file extension .csv) for {path:?}");
-            analyzers.csv = Some(CSVAnalyzer::new(self.cfg.log.silent_summary, b','));
-            analyzers_active = true;
-        } else if ext == Some(OsStr::new("tsv")) {
-            debug!("Including CSV analyzer (file extension .tsv) for {path:?}");
-            analyzers.csv = Some(CSVAnalyzer::new(self.cfg.log.silent_summary, b'\t'));
-            analyzers_active = true;
-        }
-
-        // Create a container for the analyzers so we can give it to the background thread and get it back.
-        let mut analyzer_holder = if analyzers_active {
-            Some(analyzers)
-        } else {
-            None
-        };
+        // Now get started on whatever analyzers are needed, in a container so we can give it
+        // to the background thread and get it back.
+        let mut analyzer_holder =
+            FileAnalyzers::for_path(path, &self.cfg.summaries, self.cfg.log.silent_summary);
 
         let enable_global_dedup;
         let salt;
 
         if let Some(salt_) = self.repo_salt {
             salt = salt_;
-            enable_global_dedup = self.enable_global_dedup_queries;
+            enable_global_dedup = self.enable_global_dedup_queries && !packed;
             debug!("clean_file_and_report_progress: global dedup status = {enable_global_dedup}.");
         } else {
             salt = Default::default();
@@ -656,7 +673,8 @@ impl PointerFileTranslatorV2 {
             }
         }
 
-        let file_hash = file_node_hash(&file_hashes, &self.repo_salt()?)?;
+        let file_hash =
+            file_node_hash_with_algorithm(&file_hashes, &self.repo_salt()?, self.hash_algorithm)?;
 
         // Is the file registered already?  If so, nothing needs to be added now.   
         let file_already_registered = match self.remote_shards.smudge_query_policy {
@@ -676,15 +694,26 @@ impl PointerFileTranslatorV2 {
             super::remote_shard_interface::SmudgeQueryPolicy::ServerOnly => false,
         };
 
-        if !file_already_registered {
-            // Put an accumulated data into the struct-wide cas block for building a future chunk.
-            let mut cas_data_accumulator = self.cas_data.lock().await;
+        // A copy of a file cleaned earlier in this session is not registered
+        // until its cas block is, but its data must not be added again either.
+        let mut cas_data_accumulator = self.cas_data.lock().await;
+        let file_already_pending = cas_data_accumulator
+            .pending_file_info
+            .iter()
+            .any(|(fi, _, _)| fi.metadata.file_hash == file_hash);
 
+        if file_already_registered || file_already_pending {
+            debug!("clean_file ({path:?}): content already known as {file_hash}; adding no data.");
+            drop(cas_data_accumulator);
+        } else {
+            // Put an accumulated data into the struct-wide cas block for building a future chunk.
             let shift = cas_data_accumulator.data.len() as u32;
             cas_data_accumulator.data.append(&mut cas_data.data);
             cas_data_accumulator.chunks.append(&mut cas_data.chunks);
+            let mut metadata = FileDataSequenceHeader::new(file_hash, file_info.len());
+            metadata.set_chunker_id(chunker.id());
             let new_file_info = MDBFileInfo {
-                metadata: FileDataSequenceHeader::new(file_hash, file_info.len()),
+                metadata,
                 segments: file_info
                     .into_iter()
                     .map(|fi| {
@@ -726,7 +755,7 @@ impl PointerFileTranslatorV2 {
         let _scope = span.enter();
 
         let pointer_file: PointerFile =
-            PointerFile::init_from_info(path.to_str().unwrap(), &file_hash.hex(), file_size as u64);
+            PointerFile::init_from_info(&path.to_string_lossy(), &file_hash.hex(), file_size as u64);
 
         // For each of the analyzers, add data to the notes as appropriate.
         let key = file_hash.hex();
@@ -743,7 +772,7 @@ impl PointerFileTranslatorV2 {
     }
 
     async fn register_new_cas_block(&self, cas_data: &mut CASDataAggregator) -> Result<MerkleHash> {
-        let cas_hash = cas_node_hash(&cas_data.chunks[..])?;
+        let cas_hash = cas_node_hash_with_algorithm(&cas_data.chunks[..], self.hash_algorithm)?;
         let metadata =
             CASChunkSequenceHeader::new(cas_hash, cas_data.chunks.len(), cas_data.data.len());
 
@@ -1694,4 +1723,43 @@ mod tests {
         repo.finalize_cleaning().await.unwrap();
         assert_eq!(cleaned, input_bytes);
     }
+
+    #[tokio::test]
+    async fn test_clean_copy_adds_no_data() {
+        use rand::{RngCore, SeedableRng};
+        let mut input_bytes = vec![0u8; 4 * TARGET_CDC_CHUNK_SIZE];
+        rand::rngs::StdRng::seed_from_u64(0).fill_bytes(&mut input_bytes);
+
+        let stagedir = TempDir::new().unwrap();
+        let mut repo = PointerFileTranslatorV2::new_temporary(stagedir.path())
+            .await
+            .unwrap();
+        repo.small_file_threshold = 0;
+
+        // The same content cleaned under two names, before its cas block is
+        // registered, is only added once.
+        let mut cleaned = Vec::new();
+        for name in ["a.bin", "renamed/a.bin"] {
+            let input = std::io::Cursor::new(input_bytes.clone());
+            let async_input = AsyncFileIterator::new(input, GIT_MAX_PACKET_SIZE);
+            cleaned.push(repo.clean_file(Path::new(name), async_input).await.unwrap());
+        }
+        assert_eq!(cleaned[0], cleaned[1]);
+        {
+            let cas_data = repo.cas_data.lock().await;
+            assert_eq!(cas_data.data.len(), input_bytes.len());
+            assert_eq!(cas_data.pending_file_info.len(), 1);
+        }
+        repo.finalize_cleaning().await.unwrap();
+
+        // Once it is, neither is the content cleaned again.
+        let input = std::io::Cursor::new(input_bytes.clone());
+        let async_input = AsyncFileIterator::new(input, GIT_MAX_PACKET_SIZE);
+        let again = repo
+            .clean_file(Path::new("copy.bin"), async_input)
+            .await
+            .unwrap();
+        assert_eq!(again, cleaned[0]);
+        assert!(repo.cas_data.lock().await.data.is_empty());
+    }
 }
@@ -0,0 +1,266 @@
+//! Recovery of blocks missing from CAS from the sources files were imported
+//! from.
+//!
+//! `git xet import` and `git xet sync` commit records under `.xet/sources`
+//! of the URL each imported file was read from, keyed by its xet hash. With
+//! fallback.enabled set, the blocks of such a file that the CAS no longer
+//! has (e.g. because they were pruned remotely) are rebuilt from its source,
+//! or a mirror of it configured in fallback.mirrors, before it is smudged,
+//! and uploaded again. The CAS verifies each rebuilt block against its hash,
+//! so a source that changed since the import can't corrupt a checkout.
+//!
+//! A block can only be rebuilt from a file if the file covers all of it;
+//! blocks holding data of other files too are reported as unrecoverable.
+use cas_client::{CasClientError, Staging};
+use merkledb::{chunk_target_default, ObjectRange};
+use merklehash::MerkleHash;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+use super::PointerFile;
+use crate::config::{FallbackSettings, XetConfig};
+use crate::errors::{GitXetRepoError, Result};
+use crate::import::open_url;
+
+/// The directory source records are committed in.
+pub const SOURCES_DIR: &str = ".xet/sources";
+
+/// The URLs files were imported from, keyed by their xet hash.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct SourceRecord {
+    pub sources: BTreeMap<String, String>,
+}
+
+impl SourceRecord {
+    /// The path in the repository of the record of the imports identified
+    /// by id, one per id.
+    pub fn repo_path(id: &str) -> String {
+        format!(
+            "{SOURCES_DIR}/{}.json",
+            merklehash::compute_data_hash(id.as_bytes()).hex()
+        )
+    }
+
+    pub fn parse(contents: &[u8]) -> Result<Self> {
+        serde_json::from_slice(contents).map_err(|e| {
+            GitXetRepoError::DataParsingError(format!("Unable to parse source record: {e}"))
+        })
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = serde_json::to_vec_pretty(self)?;
+        bytes.push(b'\n');
+        Ok(bytes)
+    }
+
+    /// Records url as the source of the file cleaned into pointer. Small
+    /// files passed through as they are need no source.
+    pub fn insert(&mut self, pointer: &[u8], url: &str) {
+        let pointer = PointerFile::init_from_string(&String::from_utf8_lossy(pointer), "");
+        if pointer.is_valid() {
+            self.sources
+                .insert(pointer.hash_string().clone(), url.to_string());
+        }
+    }
+
+    /// Reads the records in the tree at HEAD and in the index, which during
+    /// a checkout may still be that of the previous commit.
+    fn read_all(repo_path: &Path) -> Result<HashMap<String, String>> {
+        let repo = git2::Repository::discover(repo_path)?;
+        let mut blobs = Vec::new();
+        if let Ok(dir) = repo
+            .head()
+            .and_then(|h| h.peel_to_tree())
+            .and_then(|t| t.get_path(Path::new(SOURCES_DIR)))
+        {
+            if let Ok(tree) = dir.to_object(&repo).and_then(|o| o.peel_to_tree()) {
+                blobs.extend(tree.iter().map(|e| e.id()));
+            }
+        }
+        if let Ok(index) = repo.index() {
+            let prefix = format!("{SOURCES_DIR}/");
+            blobs.extend(
+                index
+                    .iter()
+                    .filter(|e| e.path.starts_with(prefix.as_bytes()))
+                    .map(|e| e.id),
+            );
+        }
+
+        let mut sources = HashMap::new();
+        for id in blobs {
+            let Ok(blob) = repo.find_blob(id) else {
+                continue;
+            };
+            sources.extend(Self::parse(blob.content())?.sources);
+        }
+        Ok(sources)
+    }
+}
+
+pub struct FallbackSources {
+    settings: FallbackSettings,
+    sources: HashMap<String, String>,
+    http: reqwest::Client,
+}
+
+impl FallbackSources {
+    pub fn new(settings: FallbackSettings, sources: HashMap<String, String>) -> Self {
+        Self {
+            settings,
+            sources,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Returns the fallback sources of the repository, if fallback is
+    /// enabled and any files have a recorded source.
+    pub fn from_config(config: &XetConfig) -> Option<Arc<Self>> {
+        if !config.fallback.enabled {
+            return None;
+        }
+        let repo_path = config.repo_path_if_present.as_ref()?;
+        let sources = match SourceRecord::read_all(repo_path) {
+            Ok(sources) if !sources.is_empty() => sources,
+            Ok(_) => return None,
+            Err(e) => {
+                error!("{e}; missing blocks won't be recovered.");
+                return None;
+            }
+        };
+        Some(Arc::new(Self::new(config.fallback.clone(), sources)))
+    }
+
+    /// The recorded source of the file with the given xet hash.
+    pub fn source(&self, hash: &str) -> Option<&str> {
+        self.sources.get(hash).map(String::as_str)
+    }
+
+    /// Rebuilds and uploads the blocks of the file of pointer that the CAS
+    /// is missing, from the file's recorded source. blocks are the ranges of
+    /// blocks the file is reconstructed from, in order.
+    pub async fn recover(
+        &self,
+        cas: &Arc<dyn Staging + Send + Sync>,
+        prefix: &str,
+        path: &Path,
+        pointer: &PointerFile,
+        blocks: &[ObjectRange],
+    ) -> Result<()> {
+        let Some(source) = self.source(pointer.hash_string()) else {
+            return Ok(());
+        };
+
+        let mut missing: HashMap<MerkleHash, Vec<u8>> = HashMap::new();
+        for block in blocks {
+            if missing.contains_key(&block.hash) {
+                continue;
+            }
+            if let Err(CasClientError::XORBNotFound(_)) = cas.get_length(prefix, &block.hash).await
+            {
+                missing.insert(block.hash, Vec::new());
+            }
+        }
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let url = self.settings.resolve(source);
+        info!(
+            "{} blocks of {path:?} are missing from CAS; recovering them from {url}",
+            missing.len()
+        );
+        let mut segments = Vec::new();
+        let mut offset = 0;
+        for block in blocks {
+            let len = block.end - block.start;
+            if let Some(data) = missing.get_mut(&block.hash) {
+                data.resize(data.len().max(block.end), 0);
+                segments.push((offset, block.clone()));
+            }
+            offset += len;
+        }
+
+        let mut reader = open_url(&self.http, &url).await?;
+        let mut read = 0;
+        while let Some(chunk) = parutils::AsyncIterator::next(&mut reader).await? {
+            for (start, block) in &segments {
+                let end = start + block.end - block.start;
+                let (lo, hi) = (read.max(*start), (read + chunk.len()).min(end));
+                if let Some(data) = missing.get_mut(&block.hash).filter(|_| lo < hi) {
+                    let at = block.start + lo - start;
+                    data[at..at + hi - lo].copy_from_slice(&chunk[lo - read..hi - read]);
+                }
+            }
+            read += chunk.len();
+        }
+        if read as u64 != pointer.filesize() {
+            return Err(GitXetRepoError::Other(format!(
+                "{url} has {read} bytes, but {path:?} has {}; it changed since it was imported",
+                pointer.filesize()
+            )));
+        }
+
+        for (hash, data) in missing {
+            let covered: usize = segments
+                .iter()
+                .filter(|(_, b)| b.hash == hash)
+                .map(|(_, b)| b.end - b.start)
+                .sum();
+            if covered != data.len() {
+                return Err(GitXetRepoError::Other(format!(
+                    "Block {hash} of {path:?} holds data of other files and can't be recovered"
+                )));
+            }
+            let mut boundaries = Vec::new();
+            let mut end = 0;
+            for chunk in chunk_target_default(&mut Cursor::new(&data)) {
+                end += chunk.length as u64;
+                boundaries.push(end);
+            }
+            match cas
+                .put_bypass_stage(prefix, &hash, data.clone(), boundaries.clone())
+                .await
+            {
+                Ok(()) => info!("Recovered block {hash} of {path:?}"),
+                Err(CasClientError::HashMismatch) => {
+                    return Err(GitXetRepoError::Other(format!(
+                        "Block {hash} of {path:?} can't be recovered from {url}; \
+                         it holds data of other files or the source changed since it was imported"
+                    )));
+                }
+                Err(e) => {
+                    // Keep the block locally to be uploaded on the next push.
+                    warn!("Unable to upload recovered block {hash}: {e:?}; staging it");
+                    cas.put(prefix, &hash, data, boundaries).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_record() {
+        let pointer = PointerFile::init_from_info("a.bin", &"ab".repeat(32), 100);
+        let mut record = SourceRecord::default();
+        record.insert(pointer.to_string().as_bytes(), "s3://bucket/a.bin");
+        record.insert(b"a,b\n1,2\n", "s3://bucket/small.csv");
+        assert_eq!(
+            record.sources,
+            BTreeMap::from([("ab".repeat(32), "s3://bucket/a.bin".to_string())])
+        );
+        assert_eq!(
+            SourceRecord::parse(&record.to_bytes().unwrap()).unwrap(),
+            record
+        );
+    }
+}
@@ -0,0 +1,320 @@
+//! End-to-end integrity verification of reconstructed files.
+//!
+//! When enabled, a blake3 hash of the whole file contents is computed while
+//! the file is cleaned and recorded in the pointer file. Smudging then hashes
+//! the reconstructed bytes and fails loudly if the two do not agree, catching
+//! corruption anywhere between the chunker and the final writer.
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use merklehash::MerkleHash;
+use parutils::AsyncIterator;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tracing::{error, info};
+
+use crate::errors::{GitXetRepoError, Result};
+use crate::stream::data_iterators::AsyncDataIterator;
+
+/// The channel size used when interposing a verifier between a smudge and
+/// its output channel.
+pub const INTEGRITY_MPSC_CHANNEL_SIZE: usize = 16;
+
+/// Wraps an AsyncDataIterator, hashing all the data passing through it.
+/// The hasher is shared so the hash can be read once the iterator has been
+/// consumed by a task that takes ownership of it.
+pub struct HashingDataIterator<R: AsyncDataIterator> {
+    inner: R,
+    hasher: Arc<Mutex<blake3::Hasher>>,
+}
+
+impl<R: AsyncDataIterator> HashingDataIterator<R> {
+    pub fn new(inner: R) -> (Self, Arc<Mutex<blake3::Hasher>>) {
+        let hasher = Arc::new(Mutex::new(blake3::Hasher::new()));
+        (
+            Self {
+                inner,
+                hasher: hasher.clone(),
+            },
+            hasher,
+        )
+    }
+}
+
+#[async_trait]
+impl<R: AsyncDataIterator> AsyncIterator<GitXetRepoError> for HashingDataIterator<R> {
+    type Item = Vec<u8>;
+
+    async fn next(&mut self) -> Result<Option<Self::Item>> {
+        let ret = self.inner.next().await?;
+        if let Some(data) = &ret {
+            self.hasher.lock().unwrap().update(data);
+        }
+        Ok(ret)
+    }
+}
+
+impl<R: AsyncDataIterator> AsyncDataIterator for HashingDataIterator<R> {}
+
+/// Replays data already pulled out of an iterator before continuing with
+/// the rest of it.
+pub struct ReplayDataIterator<R: AsyncDataIterator> {
+    head: Option<Vec<u8>>,
+    inner: R,
+}
+
+impl<R: AsyncDataIterator> ReplayDataIterator<R> {
+    pub fn new(head: Vec<u8>, inner: R) -> Self {
+        Self {
+            head: Some(head),
+            inner,
+        }
+    }
+}
+
+#[async_trait]
+impl<R: AsyncDataIterator> AsyncIterator<GitXetRepoError> for ReplayDataIterator<R> {
+    type Item = Vec<u8>;
+
+    async fn next(&mut self) -> Result<Option<Self::Item>> {
+        match self.head.take() {
+            Some(head) if !head.is_empty() => Ok(Some(head)),
+            _ => self.inner.next().await,
+        }
+    }
+}
+
+impl<R: AsyncDataIterator> AsyncDataIterator for ReplayDataIterator<R> {}
+
+/// A writer that hashes everything written through it.
+pub struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: blake3::Hasher,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: blake3::Hasher::new(),
+        }
+    }
+
+    pub fn finalize(&self) -> blake3::Hash {
+        self.hasher.finalize()
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Checks a computed hash against the expected hex encoded hash, returning
+/// an IntegrityCheckFailed error on mismatch.
+pub fn check_blake3(path: &Path, expected: &str, computed: &blake3::Hash) -> Result<()> {
+    if computed.to_hex().as_str() == expected {
+        Ok(())
+    } else {
+        let msg = format!(
+            "Reconstructed contents of {path:?} do not match the recorded hash (expected blake3 {expected}, got {})",
+            computed.to_hex()
+        );
+        error!("{msg}");
+        Err(GitXetRepoError::IntegrityCheckFailed(msg))
+    }
+}
+
+/// Reads a file back from disk and checks its hash. Used when the file was
+/// written out of order, so the hash could not be computed while writing.
+pub fn check_file_blake3(path: &Path, expected: &str) -> Result<()> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = blake3::Hasher::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    check_blake3(path, expected, &hasher.finalize())
+}
+
+/// Forwards everything received on `rx` to `writer`, hashing the data as it
+/// goes. Once the sender side is closed, the hash is checked and an error is
+/// sent on mismatch so the consumer fails rather than keeping bad data.
+///
+/// If `passthrough` is given and the forwarded data hashes to it, the source
+/// was passed through untouched (e.g. a lazily checked out pointer file) and
+/// no verification happens.
+pub async fn forward_and_verify(
+    path: &Path,
+    expected: &str,
+    passthrough: Option<blake3::Hash>,
+    mut rx: Receiver<Result<Vec<u8>>>,
+    writer: &Sender<Result<Vec<u8>>>,
+) {
+    let mut hasher = blake3::Hasher::new();
+    let mut failed = false;
+    let mut closed = false;
+
+    while let Some(item) = rx.recv().await {
+        match &item {
+            Ok(data) => {
+                hasher.update(data);
+            }
+            Err(_) => {
+                failed = true;
+            }
+        }
+        if !closed && writer.send(item).await.is_err() {
+            error!("Unable to forward smudged data for {path:?} as channel has closed");
+            closed = true;
+        }
+    }
+
+    if failed || closed {
+        return;
+    }
+
+    let computed = hasher.finalize();
+    if passthrough == Some(computed) {
+        return;
+    }
+
+    if let Err(e) = check_blake3(path, expected, &computed) {
+        let _ = writer.send(Err(e)).await;
+    }
+}
+
+/// Records a cleaned file whose reconstruction is to be verified before the
+/// next push.
+pub fn record_pending_integrity(
+    pending_path: &Path,
+    file_hash: &MerkleHash,
+    blake3: &str,
+) -> Result<()> {
+    if let Some(parent) = pending_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut f = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(pending_path)?;
+    // A single write per entry, so entries appended concurrently by several
+    // filter processes do not interleave.
+    f.write_all(format!("{} {blake3}\n", file_hash.hex()).as_bytes())?;
+    Ok(())
+}
+
+/// Reads the list of (file hash, blake3 hash) pairs pending verification.
+/// Malformed lines are skipped.
+pub fn read_pending_integrity(pending_path: &Path) -> Result<Vec<(MerkleHash, String)>> {
+    if !pending_path.exists() {
+        return Ok(vec![]);
+    }
+    let mut ret = Vec::new();
+    for line in BufReader::new(File::open(pending_path)?).lines() {
+        let line = line?;
+        let mut it = line.split_whitespace();
+        let (Some(file_hash), Some(blake3)) = (it.next(), it.next()) else {
+            continue;
+        };
+        match MerkleHash::from_hex(file_hash) {
+            Ok(h) => ret.push((h, blake3.to_string())),
+            Err(_) => info!("Skipping malformed integrity entry {line:?}"),
+        }
+    }
+    ret.sort();
+    ret.dedup();
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::data_iterators::AsyncFileIterator;
+    use tempfile::TempDir;
+    use tokio::sync::mpsc::channel;
+
+    #[tokio::test]
+    async fn test_hashing_iterator_and_replay() {
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let inner = AsyncFileIterator::new(std::io::Cursor::new(data[10..].to_vec()), 1000);
+        let (mut it, hasher) =
+            HashingDataIterator::new(ReplayDataIterator::new(data[..10].to_vec(), inner));
+
+        let mut collected = Vec::new();
+        while let Some(d) = it.next().await.unwrap() {
+            collected.extend_from_slice(&d);
+        }
+        assert_eq!(collected, data);
+        assert_eq!(hasher.lock().unwrap().finalize(), blake3::hash(&data));
+    }
+
+    #[test]
+    fn test_check_blake3() {
+        let path = Path::new("foo");
+        let h = blake3::hash(b"hello");
+        assert!(check_blake3(path, h.to_hex().as_str(), &h).is_ok());
+        let err = check_blake3(path, h.to_hex().as_str(), &blake3::hash(b"world"));
+        assert!(matches!(err, Err(GitXetRepoError::IntegrityCheckFailed(_))));
+
+        let mut w = HashingWriter::new(Vec::new());
+        w.write_all(b"hello").unwrap();
+        assert_eq!(w.finalize(), h);
+    }
+
+    #[tokio::test]
+    async fn test_forward_and_verify_mismatch() {
+        let (tx, rx) = channel(4);
+        let (out_tx, mut out_rx) = channel(8);
+        let expected = blake3::hash(b"hello world");
+
+        tx.send(Ok(b"hello ".to_vec())).await.unwrap();
+        tx.send(Ok(b"word".to_vec())).await.unwrap();
+        drop(tx);
+        forward_and_verify(
+            Path::new("foo"),
+            expected.to_hex().as_str(),
+            None,
+            rx,
+            &out_tx,
+        )
+        .await;
+        drop(out_tx);
+
+        assert!(out_rx.recv().await.unwrap().is_ok());
+        assert!(out_rx.recv().await.unwrap().is_ok());
+        assert!(matches!(
+            out_rx.recv().await.unwrap(),
+            Err(GitXetRepoError::IntegrityCheckFailed(_))
+        ));
+        assert!(out_rx.recv().await.is_none());
+    }
+
+    #[test]
+    fn test_pending_integrity_roundtrip() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("xet").join("integrity-pending");
+        assert!(read_pending_integrity(&path).unwrap().is_empty());
+
+        let h = MerkleHash::default();
+        record_pending_integrity(&path, &h, "abc").unwrap();
+        record_pending_integrity(&path, &h, "abc").unwrap();
+        assert_eq!(
+            read_pending_integrity(&path).unwrap(),
+            vec![(h, "abc".to_string())]
+        );
+    }
+}
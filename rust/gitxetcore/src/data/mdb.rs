@@ -15,7 +15,8 @@ use crate::git_integration::git_merkledb::get_merkledb_notes_name;
 use crate::git_integration::*;
 
 use crate::utils::*;
-use cas::safeio::{create_temp_file, write_all_file_safe};
+use cas::filelock::{FileLock, DEFAULT_LOCK_TIMEOUT};
+use cas::safeio::{create_temp_file, move_file, write_all_file_safe};
 use mdb_shard::constants::MDB_SHARD_MIN_TARGET_SIZE;
 use parutils::tokio_par_for_each;
 use progress_reporting::DataProgressReporter;
@@ -26,6 +27,7 @@ use cas_client::Staging;
 use git2::Oid;
 use mdb_shard::session_directory::consolidate_shards_in_directory;
 use mdb_shard::shard_file_handle::MDBShardFile;
+use mdb_shard::shard_file_reconstructor::FileReconstructor;
 use mdb_shard::shard_format::MDBShardFileFooter;
 use mdb_shard::shard_format::MDBShardInfo;
 use mdb_shard::shard_version::ShardVersion;
@@ -216,6 +218,7 @@ pub async fn sync_mdb_shards_from_git(
     notesref_v2: &str,
     fetch_all_shards: bool,
 ) -> errors::Result<()> {
+    let _lock = lock_cache_dir(cache_dir).await?;
     let cache_meta = get_cache_meta_file(cache_dir)?;
     let cache_head = get_cache_head_file(cache_dir)?;
 
@@ -244,6 +247,16 @@ pub fn get_cache_head_file(cache_dir: &Path) -> errors::Result<PathBuf> {
     Ok(cache_dir.to_owned().with_extension("HEAD"))
 }
 
+/// This file is locked while the cache dir and its meta and head files
+/// are written, as git operations running in parallel share them.
+pub fn get_cache_lock_file(cache_dir: &Path) -> errors::Result<PathBuf> {
+    Ok(cache_dir.to_owned().with_extension("lock"))
+}
+
+async fn lock_cache_dir(cache_dir: &Path) -> errors::Result<FileLock> {
+    Ok(FileLock::acquire_async(get_cache_lock_file(cache_dir)?, DEFAULT_LOCK_TIMEOUT).await?)
+}
+
 /// Sync MDB v2 ref notes to cache_meta, skip if cache_head matches HEAD of the ref notes.
 /// Return ref notes HEAD if pulling updates from ref notes.
 fn sync_mdb_shards_meta_from_git(
@@ -369,6 +382,71 @@ pub async fn download_shards_to_cache(
     ret
 }
 
+/// Downloads to cache_dir the shards describing the given files that are
+/// not already known locally, asking the shard server which shards those
+/// are. This is how a partial MerkleDB fetch gets the shards needed by the
+/// files at a ref without downloading every shard in the notes.
+///
+/// Returns the number of shards downloaded.
+pub async fn download_shards_for_files(
+    config: &XetConfig,
+    cache_dir: &Path,
+    file_hashes: Vec<MerkleHash>,
+) -> errors::Result<usize> {
+    let remote_shards = RemoteShardInterface::new_query_only(config).await?;
+    let (Some(shard_manager), Some(shard_client)) = (
+        remote_shards.shard_manager.clone(),
+        remote_shards.shard_client.clone(),
+    ) else {
+        info!("download_shards_for_files: no shard server to query, skipping.");
+        return Ok(0);
+    };
+    let shard_manager = &shard_manager;
+    let shard_client = &shard_client;
+
+    let shards = tokio_par_for_each(
+        file_hashes,
+        MAX_CONCURRENT_DOWNLOADS,
+        |file_hash, _| async move {
+            if shard_manager
+                .get_file_reconstruction_info(&file_hash)
+                .await?
+                .is_some()
+            {
+                return Ok(None);
+            }
+            Ok::<_, GitXetRepoError>(
+                shard_client
+                    .get_file_reconstruction_info(&file_hash)
+                    .await?
+                    .and_then(|(_, shard_hash)| shard_hash),
+            )
+        },
+    )
+    .await
+    .map_err(|e| match e {
+        parutils::ParallelError::JoinError => {
+            GitXetRepoError::InternalError(anyhow::anyhow!("Join Error on Shard Query"))
+        }
+        parutils::ParallelError::TaskError(e) => e,
+    })?;
+
+    let shards: Vec<MerkleHash> = shards
+        .into_iter()
+        .flatten()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    info!(
+        "download_shards_for_files: downloading {} shards.",
+        shards.len()
+    );
+
+    Ok(download_shards_to_cache(config, cache_dir, shards)
+        .await?
+        .len())
+}
+
 // Download a shard to local cache if not exists.
 // Returns the path to the downloaded file and the number of bytes transferred.
 // Returns the path to the existing file and 0 (transferred byte) if exists.
@@ -766,6 +844,7 @@ pub async fn move_session_shards_to_local_cache(
     session_dir: &Path,
     cache_dir: &Path,
 ) -> errors::Result<()> {
+    let _lock = lock_cache_dir(cache_dir).await?;
     let dir_walker = fs::read_dir(session_dir)?;
 
     for file in dir_walker.flatten() {
@@ -775,7 +854,7 @@ pub async fn move_session_shards_to_local_cache(
             continue;
         }
 
-        fs::rename(&file_path, cache_dir.join(file_path.file_name().unwrap()))?;
+        move_file(&file_path, &cache_dir.join(file_path.file_name().unwrap()))?;
     }
 
     Ok(())
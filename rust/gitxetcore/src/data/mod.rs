@@ -1,12 +1,19 @@
+pub mod access_policy;
 pub mod cas_interface;
 pub mod data_processing;
 pub mod data_processing_v1;
 pub mod data_processing_v2;
+pub mod fallback;
+pub mod integrity;
 pub mod mdb;
 pub mod mdbv1;
 mod mini_smudger;
+pub mod partial_smudge;
+pub mod pending_upload;
 pub mod pointer_file;
+pub mod preflight;
 pub mod remote_shard_interface;
+pub mod signing;
 mod small_file_determination;
 pub mod standalone_pointer;
 
@@ -18,3 +25,4 @@ pub use pointer_file::*;
 
 pub use cas_interface::create_cas_client;
 pub use mdb::get_mdb_version;
+pub use pending_upload::PendingUpload;
@@ -0,0 +1,327 @@
+//! Interruptible smudges of files in the working directory by
+//! `git xet checkout` and `git xet materialize`.
+//!
+//! A file is smudged into a partial file rather than into the file itself,
+//! which is only replaced, by an atomic rename, once all its data is
+//! written. Killing a smudge part way so never leaves a truncated file for
+//! git to take as clean. Smudges by the git filter stream the data to git,
+//! which writes the file itself, and don't go through here.
+//!
+//! In a repository, the partial files are kept in
+//! [PARTIAL_SMUDGE_SUBDIR](crate::constants::PARTIAL_SMUDGE_SUBDIR) so they
+//! never show up in the working tree; they are named after a hash of the
+//! path of the file being smudged. Next to each, a journal records the file
+//! being smudged and, for smudges with positioned writes, the blocks already
+//! written. The next smudge of the same file picks up where the interrupted
+//! one stopped; a partial file for anything else is started over. Partial
+//! files left by smudges that are never retried are removed by
+//! [sweep_stale].
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use cas::safeio::move_file;
+use merklehash::MerkleHash;
+use tracing::{info, warn};
+
+use crate::constants::PARTIAL_SMUDGE_SYNC_BYTES;
+use crate::errors::Result;
+
+const PARTIAL_SUFFIX: &str = ".xet-partial";
+const JOURNAL_SUFFIX: &str = ".xet-partial.journal";
+
+/// The paths of the partial file and journal in dir the file at path is
+/// smudged through.
+fn partial_paths(dir: &Path, path: &Path) -> (PathBuf, PathBuf) {
+    let key = blake3::hash(path.as_os_str().to_string_lossy().as_bytes()).to_hex();
+    (
+        dir.join(format!("{key}{PARTIAL_SUFFIX}")),
+        dir.join(format!("{key}{JOURNAL_SUFFIX}")),
+    )
+}
+
+/// Removes the partial files and journals in dir not written to for
+/// max_age, left by smudges that were interrupted and never retried.
+/// Returns how many files were removed.
+pub fn sweep_stale(dir: &Path, max_age: Duration) -> usize {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    let now = SystemTime::now();
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !name.ends_with(PARTIAL_SUFFIX) && !name.ends_with(JOURNAL_SUFFIX) {
+            continue;
+        }
+        let Ok(modified) = entry.metadata().and_then(|m| m.modified()) else {
+            continue;
+        };
+        if now.duration_since(modified).unwrap_or_default() < max_age {
+            continue;
+        }
+        match std::fs::remove_file(entry.path()) {
+            Ok(()) => removed += 1,
+            Err(e) => warn!("Unable to remove the stale partial smudge {name}: {e:?}"),
+        }
+    }
+    if removed > 0 {
+        info!("Removed {removed} stale partial smudge files from {dir:?}");
+    }
+    removed
+}
+
+/// A smudge in progress of the file at a path.
+pub struct PartialSmudge {
+    path: PathBuf,
+    partial: PathBuf,
+    journal_path: PathBuf,
+    file: File,
+    journal: BufWriter<File>,
+    /// The offsets and lengths of the blocks recorded as written.
+    written: HashSet<(u64, u64)>,
+    /// Bytes written since the partial file was last synced.
+    unsynced: u64,
+    /// Where a sequential smudge resumes.
+    resume_offset: u64,
+}
+
+impl PartialSmudge {
+    /// Starts, or resumes, the smudge of the file with hash and size into
+    /// path, through a partial file in dir. positioned is whether the blocks
+    /// are written at their offsets, as they arrive, rather than in order;
+    /// the partial file is then preallocated.
+    pub fn open(
+        dir: &Path,
+        path: &Path,
+        hash: &MerkleHash,
+        size: u64,
+        positioned: bool,
+    ) -> Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let (partial, journal_path) = partial_paths(dir, path);
+        let mode = if positioned {
+            "positioned"
+        } else {
+            "sequential"
+        };
+        let header = format!("{hash} {size} {mode}");
+
+        if let Some(written) = read_journal(&journal_path, &header) {
+            if let Ok(file) = OpenOptions::new().write(true).open(&partial) {
+                let len = file.metadata()?.len();
+                if !positioned || len == size {
+                    info!("Resuming the interrupted smudge of {path:?}");
+                    let journal = OpenOptions::new().append(true).open(&journal_path)?;
+                    return Ok(PartialSmudge {
+                        path: path.to_path_buf(),
+                        partial,
+                        journal_path,
+                        file,
+                        journal: BufWriter::new(journal),
+                        written,
+                        unsynced: 0,
+                        resume_offset: if positioned { 0 } else { len.min(size) },
+                    });
+                }
+            }
+        }
+
+        let file = File::create(&partial)?;
+        if positioned {
+            file.set_len(size)?;
+        }
+        let mut journal = BufWriter::new(File::create(&journal_path)?);
+        writeln!(journal, "{header}")?;
+        journal.flush()?;
+        Ok(PartialSmudge {
+            path: path.to_path_buf(),
+            partial,
+            journal_path,
+            file,
+            journal,
+            written: HashSet::new(),
+            unsynced: 0,
+            resume_offset: 0,
+        })
+    }
+
+    /// The partial file the data is written to.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    pub fn partial_path(&self) -> &Path {
+        &self.partial
+    }
+
+    /// The offset a sequential smudge resumes writing from; the data before
+    /// it is already in the partial file.
+    pub fn resume_offset(&self) -> u64 {
+        self.resume_offset
+    }
+
+    /// Whether the block of len bytes at offset is already written.
+    pub fn is_written(&self, offset: u64, len: u64) -> bool {
+        self.written.contains(&(offset, len))
+    }
+
+    /// Records that the block of len bytes at offset is written. Records
+    /// reach the journal in batches, only once the data they describe is
+    /// synced to disk.
+    pub fn record(&mut self, offset: u64, len: u64) -> Result<()> {
+        self.written.insert((offset, len));
+        writeln!(self.journal, "{offset} {len}")?;
+        self.unsynced += len;
+        if self.unsynced >= PARTIAL_SMUDGE_SYNC_BYTES {
+            self.file.sync_data()?;
+            self.journal.flush()?;
+            self.unsynced = 0;
+        }
+        Ok(())
+    }
+
+    /// Completes the smudge, replacing the file with the partial file.
+    pub fn finish(self) -> Result<()> {
+        self.file.sync_all()?;
+        drop(self.file);
+        move_file(&self.partial, &self.path)?;
+        drop(self.journal);
+        std::fs::remove_file(&self.journal_path)?;
+        Ok(())
+    }
+
+    /// Abandons the smudge, so the next one starts over.
+    pub fn discard(self) {
+        for path in [&self.partial, &self.journal_path] {
+            if let Err(e) = std::fs::remove_file(path) {
+                warn!("Unable to remove {path:?}: {e:?}");
+            }
+        }
+    }
+}
+
+/// The blocks recorded as written in the journal, if it's for the smudge
+/// described by header.
+fn read_journal(journal_path: &Path, header: &str) -> Option<HashSet<(u64, u64)>> {
+    let mut lines = BufReader::new(File::open(journal_path).ok()?).lines();
+    if lines.next()?.ok()? != header {
+        return None;
+    }
+    let mut written = HashSet::new();
+    for line in lines {
+        // A record cut short by the interruption ends the journal.
+        let Some((offset, len)) = line.ok().and_then(|l| {
+            let (offset, len) = l.split_once(' ')?;
+            Some((offset.parse().ok()?, len.parse().ok()?))
+        }) else {
+            break;
+        };
+        written.insert((offset, len));
+    }
+    Some(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cas::fileio::write_all_at;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_resume_positioned_smudge() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("data.bin");
+        let hash = MerkleHash::from([1u64, 2, 3, 4]);
+
+        let mut partial = PartialSmudge::open(dir.path(), &path, &hash, 8, true).unwrap();
+        write_all_at(partial.file(), b"abcd", 0).unwrap();
+        partial.record(0, 4).unwrap();
+        partial.journal.flush().unwrap();
+        // Interrupted before the rest is written.
+        drop(partial);
+        assert!(!path.exists());
+
+        let mut partial = PartialSmudge::open(dir.path(), &path, &hash, 8, true).unwrap();
+        assert!(partial.is_written(0, 4));
+        assert!(!partial.is_written(4, 4));
+        write_all_at(partial.file(), b"efgh", 4).unwrap();
+        partial.record(4, 4).unwrap();
+        partial.finish().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"abcdefgh");
+        let (partial_file, journal) = partial_paths(dir.path(), &path);
+        assert!(!partial_file.exists());
+        assert!(!journal.exists());
+
+        // A partial smudge of another file is started over.
+        let mut partial = PartialSmudge::open(dir.path(), &path, &hash, 8, true).unwrap();
+        partial.record(0, 8).unwrap();
+        partial.journal.flush().unwrap();
+        drop(partial);
+        let other = MerkleHash::from([5u64, 6, 7, 8]);
+        let partial = PartialSmudge::open(dir.path(), &path, &other, 8, true).unwrap();
+        assert!(!partial.is_written(0, 8));
+    }
+
+    #[test]
+    fn test_smudge_to_long_path() {
+        // Deeper than the 260 characters of MAX_PATH on Windows.
+        let dir = TempDir::new().unwrap();
+        let parent = dir
+            .path()
+            .join("d".repeat(100))
+            .join("e".repeat(100))
+            .join("f".repeat(100));
+        std::fs::create_dir_all(&parent).unwrap();
+        let path = parent.join("data.bin");
+        let hash = MerkleHash::from([1u64, 2, 3, 4]);
+
+        let mut partial = PartialSmudge::open(&parent, &path, &hash, 4, true).unwrap();
+        write_all_at(partial.file(), b"abcd", 0).unwrap();
+        partial.record(0, 4).unwrap();
+        partial.finish().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"abcd");
+        assert!(!partial_paths(&parent, &path).1.exists());
+    }
+
+    #[test]
+    fn test_resume_sequential_smudge() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("data.bin");
+        let hash = MerkleHash::from([1u64, 2, 3, 4]);
+
+        let partial = PartialSmudge::open(dir.path(), &path, &hash, 8, false).unwrap();
+        write_all_at(partial.file(), b"abc", 0).unwrap();
+        drop(partial);
+
+        let partial = PartialSmudge::open(dir.path(), &path, &hash, 8, false).unwrap();
+        assert_eq!(partial.resume_offset(), 3);
+        write_all_at(partial.file(), b"defgh", 3).unwrap();
+        partial.finish().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"abcdefgh");
+    }
+    #[test]
+    fn test_smudge_outside_working_tree() {
+        let work = TempDir::new().unwrap();
+        let xet_dir = TempDir::new().unwrap();
+        let partials = xet_dir.path().join("partial");
+        let path = work.path().join("data.bin");
+        let hash = MerkleHash::from([1u64, 2, 3, 4]);
+
+        let mut partial = PartialSmudge::open(&partials, &path, &hash, 4, true).unwrap();
+        write_all_at(partial.file(), b"ab", 0).unwrap();
+        partial.record(0, 2).unwrap();
+        partial.journal.flush().unwrap();
+        drop(partial);
+        // Nothing but the file itself is ever in the working tree.
+        assert_eq!(std::fs::read_dir(work.path()).unwrap().count(), 0);
+
+        // Interrupted smudges never retried are swept once stale.
+        assert_eq!(sweep_stale(&partials, Duration::from_secs(3600)), 0);
+        assert_eq!(sweep_stale(&partials, Duration::ZERO), 2);
+        assert_eq!(std::fs::read_dir(&partials).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,103 @@
+use std::path::Path;
+
+use mdb_shard::MDBShardFile;
+use merklehash::MerkleHash;
+
+use crate::errors::Result;
+
+/// What the next push will upload, read from the session shard and CAS
+/// staging directories without contacting the remote.
+#[derive(Debug, Default)]
+pub struct PendingUpload {
+    /// The hash and size of each file cleaned since the last push.
+    pub files: Vec<(MerkleHash, u64)>,
+    pub num_xorbs: usize,
+    pub xorb_bytes: u64,
+    pub num_shards: usize,
+    pub shard_bytes: u64,
+}
+
+impl PendingUpload {
+    pub fn load(session_dir: &Path, staging_path: &Path) -> Result<Self> {
+        let mut pending = PendingUpload::default();
+
+        // Files are chunked when they are cleaned; the session shards record
+        // the files cleaned since the last push.
+        let session_shards = MDBShardFile::load_all(session_dir)?;
+        pending.num_shards = session_shards.len();
+        for shard in session_shards.iter() {
+            pending.shard_bytes += std::fs::metadata(&shard.path)?.len();
+            for file_info in shard
+                .shard
+                .read_all_file_info_sections(&mut shard.get_reader()?)?
+            {
+                let size: u64 = file_info
+                    .segments
+                    .iter()
+                    .map(|s| s.unpacked_segment_bytes as u64)
+                    .sum();
+                pending.files.push((file_info.metadata.file_hash, size));
+            }
+        }
+
+        // Xorbs are only staged when their data was not found in the CAS or
+        // the repository at clean time, so the staged bytes are what remains
+        // to be uploaded after deduplication. They are stored as files named
+        // [prefix].[hash].
+        if staging_path.is_dir() {
+            for entry in std::fs::read_dir(staging_path)? {
+                let entry = entry?;
+                let is_xorb = entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|name| name.rsplit_once('.'))
+                    .map(|(_, hash)| MerkleHash::from_hex(hash).is_ok())
+                    .unwrap_or(false);
+                if is_xorb {
+                    pending.num_xorbs += 1;
+                    pending.xorb_bytes += entry.metadata()?.len();
+                }
+            }
+        }
+
+        Ok(pending)
+    }
+
+    /// The total size of the files cleaned since the last push.
+    pub fn file_bytes(&self) -> u64 {
+        self.files.iter().map(|(_, size)| *size).sum()
+    }
+
+    /// The number of bytes the push will send to the remote.
+    pub fn upload_bytes(&self) -> u64 {
+        self.xorb_bytes + self.shard_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_staged_xorbs() {
+        let staging = tempfile::tempdir().unwrap();
+        let session = tempfile::tempdir().unwrap();
+        let hash = MerkleHash::default();
+        std::fs::write(
+            staging.path().join(format!("default.{}", hash.hex())),
+            [0u8; 100],
+        )
+        .unwrap();
+        std::fs::write(staging.path().join("not-a-xorb.tmp"), [0u8; 10]).unwrap();
+
+        let pending = PendingUpload::load(session.path(), staging.path()).unwrap();
+        assert_eq!(pending.num_xorbs, 1);
+        assert_eq!(pending.xorb_bytes, 100);
+        assert_eq!(pending.num_shards, 0);
+        assert_eq!(pending.upload_bytes(), 100);
+
+        let missing = staging.path().join("missing");
+        let pending = PendingUpload::load(&missing, &missing).unwrap();
+        assert_eq!(pending.upload_bytes(), 0);
+    }
+}
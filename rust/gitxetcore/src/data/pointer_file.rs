@@ -36,6 +36,14 @@ pub struct PointerFile {
 
     /// The size of the file pointed to by this pointer file
     filesize: u64,
+
+    /// The optional blake3 hash of the whole file contents, recorded at clean
+    /// time when integrity verification is enabled.
+    blake3: Option<String>,
+
+    /// The optional detached signature over the hash and filesize, recorded
+    /// at clean time when signing is configured.
+    signature: Option<String>,
 }
 
 impl PointerFile {
@@ -62,6 +70,8 @@ impl PointerFile {
                 is_valid,
                 hash,
                 filesize,
+                blake3: None,
+                signature: None,
             };
         }
 
@@ -76,6 +86,8 @@ impl PointerFile {
                 is_valid,
                 hash,
                 filesize,
+                blake3: None,
+                signature: None,
             };
         }
 
@@ -99,6 +111,26 @@ impl PointerFile {
             }
         }
 
+        let blake3 = match parsed.get("blake3") {
+            Some(Value::String(s)) => Some(s.to_string()),
+            Some(_) => {
+                // found a non-string type for blake3 (unexpected)
+                is_valid = false;
+                None
+            }
+            None => None,
+        };
+
+        let signature = match parsed.get("signature") {
+            Some(Value::String(s)) => Some(s.to_string()),
+            Some(_) => {
+                // found a non-string type for signature (unexpected)
+                is_valid = false;
+                None
+            }
+            None => None,
+        };
+
         match parsed.get("filesize") {
             Some(Value::Integer(i)) => {
                 if *i < 0 {
@@ -120,6 +152,8 @@ impl PointerFile {
             is_valid,
             hash,
             filesize,
+            blake3,
+            signature,
         }
     }
 
@@ -134,6 +168,8 @@ impl PointerFile {
                     is_valid: false,
                     hash: empty_string,
                     filesize: 0,
+                    blake3: None,
+                    signature: None,
                 }
             }
         };
@@ -148,9 +184,15 @@ impl PointerFile {
             is_valid: true,
             hash: hash.to_string(),
             filesize,
+            blake3: None,
+            signature: None,
         }
     }
 
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
     pub fn is_valid(&self) -> bool {
         self.is_valid
     }
@@ -176,6 +218,33 @@ impl PointerFile {
     pub fn filesize(&self) -> u64 {
         self.filesize
     }
+
+    /// The hex encoded blake3 hash of the whole file, if one was recorded.
+    pub fn blake3(&self) -> Option<&str> {
+        self.blake3.as_deref()
+    }
+
+    pub fn set_blake3(&mut self, blake3: &str) {
+        self.blake3 = Some(blake3.to_string());
+    }
+
+    /// The armored detached signature over [PointerFile::signed_payload],
+    /// if the pointer file was signed.
+    pub fn signature(&self) -> Option<&str> {
+        self.signature.as_deref()
+    }
+
+    pub fn set_signature(&mut self, signature: &str) {
+        self.signature = Some(signature.to_string());
+    }
+
+    /// The data a signature covers: the content hash and filesize.
+    pub fn signed_payload(&self) -> String {
+        format!(
+            "xet pointer version {}\nhash {}\nfilesize {}\n",
+            self.version_string, self.hash, self.filesize
+        )
+    }
 }
 
 impl ToString for PointerFile {
@@ -188,6 +257,12 @@ impl ToString for PointerFile {
         contents.insert("hash".to_string(), Value::String(self.hash.clone()));
         assert!(self.filesize <= i64::MAX as u64);
         contents.insert("filesize".to_string(), Value::Integer(self.filesize as i64));
+        if let Some(blake3) = &self.blake3 {
+            contents.insert("blake3".to_string(), Value::String(blake3.clone()));
+        }
+        if let Some(signature) = &self.signature {
+            contents.insert("signature".to_string(), Value::String(signature.clone()));
+        }
         let contents_str = match toml::ser::to_string_pretty(&contents) {
             Ok(s) => s,
             Err(e) => panic!("expected to be able to serialize PointerFile, instead got error {e}"),
@@ -234,7 +309,7 @@ pub async fn pointer_file_from_reader(
         Err(_) => return Ok((None, data)), // can't utf-8. definitely a bad pointer file
     };
 
-    let ptr_file = PointerFile::init_from_string(file_str, path.to_str().unwrap());
+    let ptr_file = PointerFile::init_from_string(file_str, &path.to_string_lossy());
     if ptr_file.is_valid() {
         Ok((Some(ptr_file), data))
     } else {
@@ -310,6 +385,52 @@ mod tests {
         assert_eq!(test, deserialized);
     }
 
+    #[test]
+    fn blake3_roundtrips() {
+        let empty_string = "".to_string();
+        let test_contents = format!(
+            "{}{}\nhash = '12345'\nfilesize = 678",
+            HEADER_PREFIX, POINTER_FILE_VERSION
+        );
+        let mut test = PointerFile::init_from_string(&test_contents, &empty_string);
+        assert!(test.blake3().is_none());
+
+        test.set_blake3("abcdef");
+        let deserialized = PointerFile::init_from_string(&test.to_string(), &empty_string);
+        assert!(deserialized.is_valid());
+        assert_eq!(deserialized.blake3(), Some("abcdef"));
+        assert_eq!(test, deserialized);
+
+        let test_contents = format!(
+            "{}{}\nhash = '12345'\nfilesize = 678\nblake3 = 5",
+            HEADER_PREFIX, POINTER_FILE_VERSION
+        );
+        let test = PointerFile::init_from_string(&test_contents, &empty_string);
+        assert!(!test.is_valid()); // blake3 must be a string
+    }
+
+    #[test]
+    fn signature_roundtrips() {
+        let empty_string = "".to_string();
+        let test_contents = format!(
+            "{}{}\nhash = '12345'\nfilesize = 678",
+            HEADER_PREFIX, POINTER_FILE_VERSION
+        );
+        let mut test = PointerFile::init_from_string(&test_contents, &empty_string);
+        assert!(test.signature().is_none());
+        assert_eq!(
+            test.signed_payload(),
+            "xet pointer version 0\nhash 12345\nfilesize 678\n"
+        );
+
+        let signature = "-----BEGIN SSH SIGNATURE-----\nU1NIU0lH\n-----END SSH SIGNATURE-----\n";
+        test.set_signature(signature);
+        let deserialized = PointerFile::init_from_string(&test.to_string(), &empty_string);
+        assert!(deserialized.is_valid());
+        assert_eq!(deserialized.signature(), Some(signature));
+        assert_eq!(test, deserialized);
+    }
+
     #[test]
     fn test_new_version() {
         let empty_string = "".to_string();
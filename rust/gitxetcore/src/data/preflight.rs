@@ -0,0 +1,106 @@
+//! Checks, before files are materialized, that there's room for them, so a
+//! checkout fails up front rather than partway with a half-hydrated tree.
+use std::path::{Path, PathBuf};
+
+use cas::fileio::available_space;
+use tracing::info;
+use walkdir::WalkDir;
+
+use crate::config::XetConfig;
+use crate::errors::{GitXetRepoError, Result};
+
+/// The bytes materializing files of the sizes at the paths takes: the size of
+/// each, less what the file in its place, e.g. its pointer file, takes.
+pub fn materialized_bytes<'a>(files: impl IntoIterator<Item = (&'a Path, u64)>) -> u64 {
+    files
+        .into_iter()
+        .map(|(path, size)| {
+            let existing = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            size.saturating_sub(existing)
+        })
+        .sum()
+}
+
+/// Checks there's room for bytes more of files in dest, as well as for the
+/// blocks the cache keeps as they're fetched, failing with
+/// [GitXetRepoError::InsufficientDiskSpace] if not. Filesystems that don't
+/// report their free space are assumed to have room.
+pub fn check_disk_space(cfg: &XetConfig, dest: &Path, bytes: u64) -> Result<()> {
+    let mut needed: Vec<(PathBuf, u64)> = vec![(dest.to_path_buf(), bytes)];
+    if cfg.cache.enabled {
+        // The cache evicts blocks past cache.size, so only grows by what it
+        // has left.
+        let cache_bytes = bytes.min(cfg.cache.size.saturating_sub(dir_size(&cfg.cache.path)));
+        match needed
+            .iter_mut()
+            .find(|(path, _)| same_filesystem(path, &cfg.cache.path))
+        {
+            Some((_, n)) => *n = n.saturating_add(cache_bytes),
+            None => needed.push((cfg.cache.path.clone(), cache_bytes)),
+        }
+    }
+
+    for (path, needed) in needed {
+        let Ok(available) = available_space(&path) else {
+            continue;
+        };
+        info!("{needed} bytes needed in {path:?}, {available} available");
+        if available < needed {
+            return Err(GitXetRepoError::InsufficientDiskSpace(format!(
+                "materializing needs {needed} bytes on the volume of {path:?}, \
+                 but only {available} bytes are free; free up {} bytes and try again",
+                needed - available
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn dir_size(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+#[cfg(unix)]
+fn same_filesystem(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_filesystem(a: &Path, b: &Path) -> bool {
+    a.components().next() == b.components().next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_check_disk_space() {
+        let dir = TempDir::new().unwrap();
+        let pointer = dir.path().join("pointer");
+        std::fs::write(&pointer, [0u8; 100]).unwrap();
+        let missing = dir.path().join("missing");
+        assert_eq!(
+            materialized_bytes([(pointer.as_path(), 1000), (missing.as_path(), 50)]),
+            950
+        );
+        // Files already larger don't free up space for the others.
+        assert_eq!(materialized_bytes([(pointer.as_path(), 10)]), 0);
+
+        let cfg = XetConfig::empty();
+        assert!(check_disk_space(&cfg, dir.path(), 1).is_ok());
+        let err = check_disk_space(&cfg, dir.path(), u64::MAX).unwrap_err();
+        assert!(matches!(err, GitXetRepoError::InsufficientDiskSpace(_)));
+    }
+}
@@ -266,9 +266,11 @@ impl RemoteShardInterface {
                 if local_info.is_some() {
                     Ok(local_info)
                 } else {
-                    Ok(self
+                    let server_info = self
                         .query_server_for_file_reconstruction_info(file_hash)
-                        .await?)
+                        .await?;
+                    self.fetch_missing_shard(&server_info);
+                    Ok(server_info)
                 }
             }
             SmudgeQueryPolicy::ServerOnly => {
@@ -289,6 +291,22 @@ impl RemoteShardInterface {
         }
     }
 
+    /// With a partial MerkleDB fetch, the shards not needed at checkout are
+    /// not downloaded. When the server describes a file from such a shard,
+    /// the shard is downloaded in the background so that the other files it
+    /// describes are found locally.
+    fn fetch_missing_shard(&self, server_info: &Option<(MDBFileInfo, Option<MerkleHash>)>) {
+        if !self.config.shard.partial_fetch || self.cas.is_none() {
+            return;
+        }
+        let Some((_, Some(shard_hash))) = server_info else {
+            return;
+        };
+        if let Err(e) = self.download_and_register_shard_background(shard_hash) {
+            warn!("Unable to fetch shard {shard_hash:?} on demand: {e:?}");
+        }
+    }
+
     pub async fn get_file_reconstruction_info(
         &self,
         file_hash: &merklehash::MerkleHash,
@@ -0,0 +1,246 @@
+//! Detached signatures on pointer files, attesting the origin of the data
+//! they point to.
+//!
+//! When signing.key is set, the content hash and filesize of every cleaned
+//! pointer file (see [PointerFile::signed_payload]) are signed with
+//! `ssh-keygen -Y sign` or `gpg --detach-sign`, and the armored signature is
+//! stored in the pointer file. `git xet fsck --verify-signatures` checks them
+//! against the ssh allowed signers file, or the GPG keyring.
+use std::path::Path;
+use std::process::Stdio;
+
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::debug;
+
+use super::PointerFile;
+use crate::config::{SignatureFormat, SigningSettings};
+use crate::errors::{GitXetRepoError, Result};
+
+/// The ssh signature namespace, so that pointer file signatures cannot be
+/// confused with signatures made for other purposes with the same key.
+const SSH_SIGNATURE_NAMESPACE: &str = "xet-pointer";
+
+const SSH_SIGNATURE_HEADER: &str = "-----BEGIN SSH SIGNATURE-----";
+const PGP_SIGNATURE_HEADER: &str = "-----BEGIN PGP SIGNATURE-----";
+
+/// The outcome of verifying the signature of a pointer file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureStatus {
+    /// Signed by the given principal or GPG user id.
+    Valid(String),
+    /// The signature did not verify; the reason is given.
+    Invalid(String),
+    Unsigned,
+}
+
+/// Runs program with args, writing input to its stdin, and returns its
+/// exit status and stdout.
+async fn run_with_input(program: &str, args: &[&str], input: &str) -> Result<(bool, String)> {
+    debug!("Running {program} {args:?}");
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| GitXetRepoError::Other(format!("Unable to run {program}: {e}")))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes()).await?;
+    }
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        debug!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok((
+        output.status.success(),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+    ))
+}
+
+/// Signs the pointer file for path with the configured key.
+pub async fn sign_pointer(
+    settings: &SigningSettings,
+    path: &Path,
+    pointer: &mut PointerFile,
+) -> Result<()> {
+    let Some(key) = &settings.key else {
+        return Ok(());
+    };
+    let payload = pointer.signed_payload();
+    let (success, signature) = match settings.format {
+        SignatureFormat::Ssh => {
+            run_with_input(
+                "ssh-keygen",
+                &["-Y", "sign", "-n", SSH_SIGNATURE_NAMESPACE, "-f", key],
+                &payload,
+            )
+            .await?
+        }
+        SignatureFormat::Gpg => {
+            run_with_input(
+                "gpg",
+                &["--batch", "--armor", "--detach-sign", "--local-user", key],
+                &payload,
+            )
+            .await?
+        }
+    };
+    if !success || signature.is_empty() {
+        return Err(GitXetRepoError::Other(format!(
+            "Unable to sign pointer file for {path:?} with {key}"
+        )));
+    }
+    pointer.set_signature(&signature);
+    Ok(())
+}
+
+/// Verifies the signature of the pointer file. The kind of signature is
+/// taken from the signature itself, so ssh and GPG signed files can be
+/// mixed in a repository.
+pub async fn verify_pointer(
+    settings: &SigningSettings,
+    pointer: &PointerFile,
+) -> Result<SignatureStatus> {
+    let Some(signature) = pointer.signature() else {
+        return Ok(SignatureStatus::Unsigned);
+    };
+    let payload = pointer.signed_payload();
+
+    let dir = tempfile::TempDir::new()?;
+    let signature_path = dir.path().join("pointer.sig");
+    std::fs::write(&signature_path, signature)?;
+    let signature_path = signature_path.to_str().unwrap_or_default();
+
+    if signature.starts_with(SSH_SIGNATURE_HEADER) {
+        let Some(allowed_signers) = &settings.allowed_signers else {
+            return Err(GitXetRepoError::InvalidOperation(
+                "signing.allowed_signers must be set to verify ssh signatures".to_string(),
+            ));
+        };
+        verify_ssh(allowed_signers, signature_path, &payload).await
+    } else if signature.starts_with(PGP_SIGNATURE_HEADER) {
+        verify_gpg(signature_path, &payload).await
+    } else {
+        Ok(SignatureStatus::Invalid(
+            "unrecognized signature format".to_string(),
+        ))
+    }
+}
+
+async fn verify_ssh(
+    allowed_signers: &Path,
+    signature_path: &str,
+    payload: &str,
+) -> Result<SignatureStatus> {
+    let allowed_signers = allowed_signers.to_str().unwrap_or_default();
+    let (found, principals) = run_with_input(
+        "ssh-keygen",
+        &[
+            "-Y",
+            "find-principals",
+            "-f",
+            allowed_signers,
+            "-s",
+            signature_path,
+        ],
+        "",
+    )
+    .await?;
+    let Some(principal) = principals.lines().next().filter(|_| found) else {
+        return Ok(SignatureStatus::Invalid(
+            "signing key is not in the allowed signers".to_string(),
+        ));
+    };
+
+    let (verified, _) = run_with_input(
+        "ssh-keygen",
+        &[
+            "-Y",
+            "verify",
+            "-n",
+            SSH_SIGNATURE_NAMESPACE,
+            "-f",
+            allowed_signers,
+            "-I",
+            principal,
+            "-s",
+            signature_path,
+        ],
+        payload,
+    )
+    .await?;
+    Ok(if verified {
+        SignatureStatus::Valid(principal.to_string())
+    } else {
+        SignatureStatus::Invalid(format!("bad signature from {principal}"))
+    })
+}
+
+async fn verify_gpg(signature_path: &str, payload: &str) -> Result<SignatureStatus> {
+    let (verified, status) = run_with_input(
+        "gpg",
+        &[
+            "--batch",
+            "--status-fd",
+            "1",
+            "--verify",
+            signature_path,
+            "-",
+        ],
+        payload,
+    )
+    .await?;
+    Ok(match parse_gpg_status(&status) {
+        Some(signer) if verified => SignatureStatus::Valid(signer),
+        _ => SignatureStatus::Invalid("bad or untrusted GPG signature".to_string()),
+    })
+}
+
+/// Returns the user id of a good signature from the output of
+/// `gpg --status-fd`.
+fn parse_gpg_status(status: &str) -> Option<String> {
+    status.lines().find_map(|line| {
+        let rest = line.strip_prefix("[GNUPG:] GOODSIG ")?;
+        let (_key_id, user_id) = rest.split_once(' ')?;
+        Some(user_id.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gpg_status() {
+        let status = "[GNUPG:] NEWSIG\n\
+                      [GNUPG:] GOODSIG 0123456789ABCDEF Jane Doe <jane@example.com>\n\
+                      [GNUPG:] VALIDSIG 0123\n";
+        assert_eq!(
+            parse_gpg_status(status).as_deref(),
+            Some("Jane Doe <jane@example.com>")
+        );
+        assert_eq!(parse_gpg_status("[GNUPG:] BADSIG 0123 Jane"), None);
+    }
+
+    #[tokio::test]
+    async fn test_unsigned_and_unrecognized() -> Result<()> {
+        let settings = SigningSettings::default();
+        let mut pointer = PointerFile::init_from_info("a.bin", "1234", 10);
+        assert_eq!(
+            verify_pointer(&settings, &pointer).await?,
+            SignatureStatus::Unsigned
+        );
+
+        pointer.set_signature("not a signature");
+        assert!(matches!(
+            verify_pointer(&settings, &pointer).await?,
+            SignatureStatus::Invalid(_)
+        ));
+        Ok(())
+    }
+}
@@ -91,3 +91,29 @@ pub async fn check_passthrough_status(
         Ok(PassThroughFileStatus::ChunkFile(tempbuf))
     }
 }
+
+/// Reads ahead up to pack_threshold bytes of a file that is going to be
+/// chunked, past the data already read, to determine whether it is small
+/// enough to be packed. Returns the data read so far, and whether the whole
+/// file was read and is smaller than pack_threshold.
+pub async fn check_pack_status(
+    reader: &mut impl AsyncDataIterator,
+    mut starting_data: Vec<Vec<u8>>,
+    pack_threshold: u64,
+) -> Result<(Vec<Vec<u8>>, bool)> {
+    if pack_threshold == 0 {
+        return Ok((starting_data, false));
+    }
+
+    let mut readlen: u64 = starting_data.iter().map(|b| b.len() as u64).sum();
+    while readlen < pack_threshold {
+        match reader.next().await? {
+            Some(buf) => {
+                readlen += buf.len() as u64;
+                starting_data.push(buf);
+            }
+            None => return Ok((starting_data, true)),
+        }
+    }
+    Ok((starting_data, false))
+}
@@ -5,7 +5,7 @@ use anyhow::anyhow;
 use git2::{ErrorCode, Repository};
 
 use crate::data::PointerFile;
-use ::libmagic::libmagic::{summarize_libmagic, LibmagicSummary};
+use ::libmagic::libmagic::{summarize_file_type, LibmagicSummary, TypeDetector};
 
 use crate::config::XetConfig;
 use crate::constants::{GIT_NOTES_SUMMARIES_REF_NAME, POINTER_FILE_LIMIT, SMALL_FILE_THRESHOLD};
@@ -115,8 +115,8 @@ impl SummaryFetcher {
         } else {
             // file is a pass-through, calculate the summary:
             // use libmagic to get the filetype:
-            let libmagic_summary = summarize_libmagic(Path::new(file_path))
-                .map_err(|e| FailedSummaryCalculation(anyhow!(e)))?;
+            let libmagic_summary =
+                summarize_file_type(Path::new(file_path), Some(content), TypeDetector::default());
             let summary_type = get_type_from_libmagic(&libmagic_summary);
             let mut summary = FileSummary::default();
             summary.libmagic = Some(libmagic_summary);
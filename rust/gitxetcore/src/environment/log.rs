@@ -1,23 +1,33 @@
 use std::fmt::Debug;
-use std::fs::OpenOptions;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use std::{env, io, process};
 
 use crate::command::Command;
 use crate::config::LogFormat;
 use crate::config::XetConfig;
+use crate::environment::recent_errors::RecentErrorsLayer;
 use crate::errors::GitXetRepoError::InvalidLogPath;
 use cas::constants::TRACE_ID_HEADER;
 use cas_client::set_trace_forwarding;
+use chrono::{DateTime, Utc};
 use opentelemetry::propagation::text_map_propagator::FieldIter;
 use opentelemetry::propagation::{Extractor, Injector, TextMapPropagator};
 use opentelemetry::sdk::trace;
 use opentelemetry::sdk::trace::XrayIdGenerator;
 use opentelemetry::Context;
-use tracing::{debug, info_span, Span};
+use tracing::{debug, info_span, Level, Span, Subscriber};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::MakeWriter;
 use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::util::SubscriberInitExt;
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// The log file of this invocation, in the log directory.
+static LOG_FILE: OnceLock<PathBuf> = OnceLock::new();
 
 fn log_exception_info(source: &str) {
     tracing::info!(
@@ -33,16 +43,16 @@ fn log_exception_error(source: &str) {
     );
 }
 
-pub fn initialize_tracing_subscriber(config: &XetConfig) -> Result<(), anyhow::Error> {
-    // Logging format
-    let fmt_layer = tracing_subscriber::fmt::layer()
-        .with_line_number(true)
-        .with_file(true)
-        .with_target(false);
-
-    // Level filter
-    let filter_layer = EnvFilter::try_from_default_env()
-        .or_else(|_| EnvFilter::try_new(config.log.level.as_str()))?;
+/// Sets up logging to stderr, or log.path if set, and to a file of its own
+/// in the log directory for the invocation, named after log_name.
+pub fn initialize_tracing_subscriber(
+    config: &XetConfig,
+    log_name: &str,
+) -> Result<(), anyhow::Error> {
+    // Level filter of the logs on stderr or in log.path
+    let level_filter = || {
+        EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new(config.log.level.as_str()))
+    };
 
     let mut otel_layer = None;
     if config.log.with_tracer {
@@ -57,47 +67,44 @@ pub fn initialize_tracing_subscriber(config: &XetConfig) -> Result<(), anyhow::E
             )
             .with_auto_split_batch(true)
             .install_batch(opentelemetry::runtime::Tokio)?;
-        otel_layer = Some(tracing_opentelemetry::layer().with_tracer(jaeger_tracer));
+        otel_layer = Some(
+            tracing_opentelemetry::layer()
+                .with_tracer(jaeger_tracer)
+                .with_filter(level_filter()?),
+        );
         set_trace_forwarding(true);
     }
 
-    // Set the global tracing subscriber.
-    let trace_builder = tracing_subscriber::registry()
-        .with(filter_layer)
-        .with(otel_layer);
-
-    // Due to the type system of Rust, building the fmt_layer based off our config is ugly.
-    // We cannot process this into branching stages like:  "if Json, then layer.json(), else layer.compact()"
-    // because those return different types. The same is true when using `layer.with_writer(T)`
-    // as the returned object is dependent upon type T and thus, cannot be stored in the
-    // same variable... Thus we have to do this combinatorial explosion of choices to build the
-    // logger. Luckily it is small (2x2), but if we have more fields to configure or more choices,
-    // we will need to revisit and think of a better way to solve this.
-    match config.log.path.as_ref() {
+    let main_layer = match config.log.path.as_ref() {
         Some(path) => {
             let file = OpenOptions::new()
                 .append(true)
                 .create(true)
                 .open(path)
                 .map_err(|e| InvalidLogPath(path.clone(), e))?;
-            match config.log.format {
-                LogFormat::Compact => trace_builder
-                    .with(fmt_layer.compact().with_writer(file))
-                    .init(),
-                LogFormat::Json => trace_builder
-                    .with(fmt_layer.json().with_writer(file))
-                    .init(),
-            }
+            fmt_layer(&config.log.format, file, true)
         }
-        None => match config.log.format {
-            LogFormat::Compact => trace_builder
-                .with(fmt_layer.compact().with_writer(io::stderr))
-                .init(),
-            LogFormat::Json => trace_builder
-                .with(fmt_layer.json().with_writer(io::stderr))
-                .init(),
-        },
+        None => fmt_layer(&config.log.format, io::stderr, true),
     }
+    .with_filter(level_filter()?);
+
+    // The file of the invocation is at least at the info level, so that it's
+    // useful in bug reports whatever the level on stderr.
+    let file_layer = open_log_file(config, log_name).map(|(path, file)| {
+        let _ = LOG_FILE.set(path);
+        fmt_layer(&config.log.format, file, false).with_filter(LevelFilter::from_level(
+            std::cmp::max(config.log.level, Level::INFO),
+        ))
+    });
+
+    // Set the global tracing subscriber.
+    tracing_subscriber::registry()
+        .with(otel_layer)
+        .with(main_layer)
+        .with(file_layer)
+        // Kept for the control socket, whatever the level of the other layers.
+        .with(RecentErrorsLayer.with_filter(LevelFilter::WARN))
+        .init();
 
     // Logging the exceptions is really messy, but really useful.   We log it as an error in telemetry applications,
     // so it gets recorded, but at the info level everywhere else to enable useful debugging but also
@@ -110,6 +117,77 @@ pub fn initialize_tracing_subscriber(config: &XetConfig) -> Result<(), anyhow::E
     Ok(())
 }
 
+/// The log file of this invocation, to point to in error messages so that
+/// bug reports come with it. None until logging is initialized, or if there
+/// is no log directory.
+pub fn current_log_file() -> Option<&'static Path> {
+    LOG_FILE.get().map(PathBuf::as_path)
+}
+
+/// The layer printing the logs to writer in the format. A boxed layer, as
+/// each format is a different type.
+fn fmt_layer<S, W>(format: &LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_line_number(true)
+        .with_file(true)
+        .with_target(false)
+        .with_ansi(ansi)
+        .with_writer(writer);
+    match format {
+        LogFormat::Compact => Box::new(layer.compact()),
+        LogFormat::Json => Box::new(layer.json()),
+    }
+}
+
+/// Creates the log file of the invocation in the log directory, and removes
+/// the oldest ones beyond log.maxfiles. Logging there is best effort: None
+/// if there's no log directory or it can't be written to.
+fn open_log_file(config: &XetConfig, log_name: &str) -> Option<(PathBuf, File)> {
+    let dir = config
+        .log
+        .dir
+        .resolve(config.repo_path_if_present.as_deref())?;
+    if config.log.max_files == 0 {
+        return None;
+    }
+    let path = dir.join(log_file_name(log_name, Utc::now(), process::id()));
+    config.permission.create_file(&path).ok()?;
+    let file = OpenOptions::new().append(true).open(&path).ok()?;
+    prune_log_files(&dir, config.log.max_files);
+    Some((path, file))
+}
+
+/// <time>-<pid>-<name>.log, the time first so that the names sort in the
+/// order the files were created.
+fn log_file_name(log_name: &str, time: DateTime<Utc>, pid: u32) -> String {
+    let log_name: String = log_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    format!("{}-{pid}-{log_name}.log", time.format("%Y%m%dT%H%M%S%.3fZ"))
+}
+
+/// Removes the oldest log files in dir, keeping max_files of them.
+fn prune_log_files(dir: &Path, max_files: usize) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut logs: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "log"))
+        .collect();
+    logs.sort();
+    let excess = logs.len().saturating_sub(max_files);
+    for path in &logs[..excess] {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
 pub fn get_trace_span(command: &Command) -> Span {
     let command_name = command.name();
     let span = info_span!("gitxet", "command" = command_name);
@@ -225,3 +303,41 @@ impl Extractor for EnvTraceExtractor {
         self.trace_header.iter().map(String::as_str).collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_log_files() {
+        let time = Utc.timestamp_opt(1_700_000_000, 0).single().unwrap();
+        assert_eq!(
+            log_file_name("repo make", time, 42),
+            "20231114T221320.000Z-42-repo-make.log"
+        );
+
+        let dir = TempDir::new().unwrap();
+        let names = [
+            log_file_name("push", time, 3),
+            log_file_name("checkout", time + chrono::Duration::seconds(1), 1),
+            log_file_name("fsck", time + chrono::Duration::seconds(2), 2),
+        ];
+        for name in &names {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        std::fs::write(dir.path().join("notes.txt"), "").unwrap();
+
+        prune_log_files(dir.path(), 2);
+        let mut left: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        left.sort();
+        assert_eq!(
+            left,
+            vec![names[1].clone(), names[2].clone(), "notes.txt".to_string()]
+        );
+    }
+}
@@ -1,3 +1,5 @@
-pub mod axe;
 pub mod log;
+pub mod recent_errors;
+pub mod retention;
 pub mod upgrade_checks;
+pub mod usage_events;
@@ -0,0 +1,105 @@
+//! The last warnings and errors logged by this process, kept in memory
+//! whatever the log level and destination, so that the control socket can
+//! report them to `git xet top`.
+use std::collections::VecDeque;
+use std::fmt::{Debug, Write};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+/// The number of warnings and errors kept, dropping the oldest first.
+const MAX_RECENT_ERRORS: usize = 20;
+
+lazy_static! {
+    static ref RECENT_ERRORS: Mutex<VecDeque<RecentError>> = Mutex::new(VecDeque::new());
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecentError {
+    pub time: DateTime<Utc>,
+    /// "WARN" or "ERROR".
+    pub level: String,
+    pub message: String,
+}
+
+/// The warnings and errors logged most recently, the oldest first.
+pub fn recent_errors() -> Vec<RecentError> {
+    RECENT_ERRORS.lock().unwrap().iter().cloned().collect()
+}
+
+fn push(error: RecentError) {
+    let mut errors = RECENT_ERRORS.lock().unwrap();
+    if errors.len() == MAX_RECENT_ERRORS {
+        errors.pop_front();
+    }
+    errors.push_back(error);
+}
+
+/// The layer recording the warnings and errors logged.
+pub struct RecentErrorsLayer;
+
+impl<S: Subscriber> Layer<S> for RecentErrorsLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let level = *event.metadata().level();
+        if level > Level::WARN {
+            return;
+        }
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        push(RecentError {
+            time: Utc::now(),
+            level: level.to_string(),
+            message: visitor.message,
+        });
+    }
+}
+
+/// Formats the message of an event, followed by its other fields.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            let fields = std::mem::take(&mut self.message);
+            let _ = write!(self.message, "{value:?}{fields}");
+        } else {
+            let _ = write!(self.message, " {}={value:?}", field.name());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_recent_errors() {
+        let subscriber = tracing_subscriber::registry().with(RecentErrorsLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("not recorded");
+            tracing::warn!(path = "a.csv", "Unable to read");
+            let errors = recent_errors();
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].level, "WARN");
+            assert_eq!(errors[0].message, "Unable to read path=\"a.csv\"");
+
+            for i in 0..MAX_RECENT_ERRORS {
+                tracing::error!("error {i}");
+            }
+        });
+        let errors = recent_errors();
+        assert_eq!(errors.len(), MAX_RECENT_ERRORS);
+        assert_eq!(errors[0].message, "error 0");
+        assert_eq!(errors[0].level, "ERROR");
+    }
+}
@@ -0,0 +1,176 @@
+//! Expiry of locally held data: cached blocks and staged xorbs older than
+//! the configured retention are deleted.
+//!
+//! Staged xorbs are only deleted once the CAS is known to store them, so
+//! data that was never pushed is never lost.
+use crate::config::XetConfig;
+use crate::constants::{RETENTION_MARKER_SUBDIR, RETENTION_PRUNE_INTERVAL_SECS};
+use crate::data::create_cas_client;
+use crate::errors::Result;
+use cas::filelock::FileLock;
+use cas_client::LocalClient;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info};
+
+/// The number and total size of the files pruned.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PruneSummary {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+fn older_than(metadata: &fs::Metadata, ttl: Duration) -> bool {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|mtime| SystemTime::now().duration_since(mtime).ok())
+        .map_or(false, |age| age > ttl)
+}
+
+/// Deletes the cached blocks in dir written more than ttl ago.
+pub fn prune_cache(dir: &Path, ttl: Duration) -> Result<PruneSummary> {
+    let mut summary = PruneSummary::default();
+    if !dir.is_dir() {
+        return Ok(summary);
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        // Blocks being written are hidden temporary files.
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() || !older_than(&metadata, ttl) {
+            continue;
+        }
+        debug!("Pruning expired cache block {:?}", entry.path());
+        fs::remove_file(entry.path())?;
+        summary.files += 1;
+        summary.bytes += metadata.len();
+    }
+    Ok(summary)
+}
+
+/// Deletes the staged xorbs written more than ttl ago that the CAS stores.
+pub async fn prune_staging(cfg: &XetConfig, ttl: Duration) -> Result<PruneSummary> {
+    let mut summary = PruneSummary::default();
+    let Some(staging_path) = cfg.staging_path.as_ref().filter(|p| p.is_dir()) else {
+        return Ok(summary);
+    };
+    let stage = LocalClient::new(staging_path, true);
+    // Skip pruning rather than wait if the staged xorbs are being uploaded.
+    let Ok(_lock) = FileLock::acquire_async(stage.get_lock_path(), Duration::ZERO).await else {
+        debug!("Staging directory is locked, skipping pruning");
+        return Ok(summary);
+    };
+    let expired: Vec<_> = stage
+        .get_all_entries()?
+        .into_iter()
+        .filter_map(|key| {
+            let metadata = fs::metadata(stage.get_path_for_entry(&key.prefix, &key.hash)).ok()?;
+            older_than(&metadata, ttl).then_some((key, metadata.len()))
+        })
+        .collect();
+    if expired.is_empty() {
+        return Ok(summary);
+    }
+
+    let cas = create_cas_client(cfg).await?;
+    for (key, len) in expired {
+        let stored = matches!(cas.get_length_remote(&key.prefix, &key.hash).await, Ok(n) if n > 0);
+        if !stored {
+            debug!("Keeping expired staged xorb {key} as it hasn't been pushed");
+            continue;
+        }
+        debug!("Pruning expired staged xorb {key}");
+        stage.delete(&key.prefix, &key.hash);
+        summary.files += 1;
+        summary.bytes += len;
+    }
+    Ok(summary)
+}
+
+/// Prunes the expired cached blocks and staged xorbs, returning what was
+/// pruned from each.
+pub async fn prune(cfg: &XetConfig) -> Result<(PruneSummary, PruneSummary)> {
+    let cache = match cfg.retention.cache_ttl {
+        Some(ttl) if cfg.cache.enabled => prune_cache(&cfg.cache.path, ttl)?,
+        _ => PruneSummary::default(),
+    };
+    let staging = match cfg.retention.staging_ttl {
+        Some(ttl) => prune_staging(cfg, ttl).await?,
+        None => PruneSummary::default(),
+    };
+    Ok((cache, staging))
+}
+
+/// Prunes the expired data if retention is configured and it hasn't been
+/// pruned from this repository within the last interval.
+pub async fn prune_if_due(cfg: XetConfig) {
+    if !cfg.retention.enabled() {
+        return;
+    }
+    let Some(marker) = cfg
+        .repo_path_if_present
+        .as_ref()
+        .map(|p| p.join(RETENTION_MARKER_SUBDIR))
+    else {
+        return;
+    };
+    let interval = Duration::from_secs(RETENTION_PRUNE_INTERVAL_SECS);
+    if let Ok(metadata) = fs::metadata(&marker) {
+        if !older_than(&metadata, interval) {
+            return;
+        }
+    }
+    if cfg.permission.create_file(&marker).is_err() {
+        return;
+    }
+    // Touch the marker first so concurrent invocations don't prune too.
+    let _ = filetime::set_file_mtime(&marker, filetime::FileTime::now());
+
+    match prune(&cfg).await {
+        Ok((cache, staging)) => info!(
+            "Pruned {} expired cached blocks and {} expired staged xorbs",
+            cache.files, staging.files
+        ),
+        Err(e) => info!("Unable to prune expired data: {e:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_prune_cache() {
+        let dir = TempDir::new().unwrap();
+        let old = dir.path().join("old");
+        let new = dir.path().join("new");
+        fs::write(&old, b"0123456789").unwrap();
+        fs::write(&new, b"0123").unwrap();
+        fs::write(dir.path().join(".tmpXYZ"), b"partial").unwrap();
+
+        let day = Duration::from_secs(24 * 60 * 60);
+        filetime::set_file_mtime(
+            &old,
+            filetime::FileTime::from_system_time(SystemTime::now() - 2 * day),
+        )
+        .unwrap();
+
+        let summary = prune_cache(dir.path(), day).unwrap();
+        assert_eq!(
+            summary,
+            PruneSummary {
+                files: 1,
+                bytes: 10
+            }
+        );
+        assert!(!old.exists());
+        assert!(new.exists());
+        assert!(dir.path().join(".tmpXYZ").exists());
+    }
+}
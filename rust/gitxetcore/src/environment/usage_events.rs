@@ -0,0 +1,292 @@
+//! Anonymous usage events, recorded only when opted in with `usage.enabled`
+//! (or the older `axe.enabled`), and never while the DO_NOT_TRACK
+//! environment variable is set.
+//!
+//! Each command records one event, and nothing else is collected:
+//!
+//! | field              | content                                              |
+//! |--------------------|------------------------------------------------------|
+//! | `command`          | the name of the command, e.g. "push", without its arguments |
+//! | `duration_secs`    | the wall time of the command                         |
+//! | `uploaded_bytes`   | the bytes sent to the CAS                            |
+//! | `downloaded_bytes` | the bytes received from the CAS                      |
+//! | `error`            | the category of the error the command failed with, e.g. "network", or null |
+//!
+//! No path, remote, user, email, token, or machine information is recorded,
+//! and the events are sent under a fixed "anonymous" id, so that events of
+//! the same user can't be told apart from those of any other.
+//!
+//! Events are appended to `~/.xet/usage/events.jsonl`, one JSON line each,
+//! and sent together once `usage.batchsize` of them are spooled. Events that
+//! can't be sent, e.g. while offline, stay in the spool and are sent with
+//! the next batch, up to `usage.spoolmax` events. The spool can be read at
+//! any time to see exactly what will be sent.
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use cas_client::CAS_TRANSFER_STATS;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tracing::debug;
+
+use crate::config::{UsageSettings, XetConfig};
+use crate::errors::{ErrorCategory, GitXetRepoError};
+
+const SPOOL_DIR_HOME: &str = "usage";
+const SPOOL_FILENAME: &str = "events.jsonl";
+const SEND_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The event of a command run.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct UsageEvent {
+    pub command: String,
+    pub duration_secs: f64,
+    pub uploaded_bytes: u64,
+    pub downloaded_bytes: u64,
+    pub error: Option<ErrorCategory>,
+}
+
+impl UsageEvent {
+    /// The event of a command that ran for duration, with the bytes this
+    /// process transferred.
+    pub fn collect(command: &str, duration: Duration, error: Option<&GitXetRepoError>) -> Self {
+        let counts = CAS_TRANSFER_STATS.counts();
+        Self {
+            command: command.to_string(),
+            duration_secs: duration.as_secs_f64(),
+            uploaded_bytes: counts.uploaded_bytes,
+            downloaded_bytes: counts.downloaded_bytes,
+            error: error.map(GitXetRepoError::category),
+        }
+    }
+}
+
+/// Whether the user opted in to usage events, and did not opt out with
+/// DO_NOT_TRACK.
+pub fn is_enabled(cfg: &XetConfig) -> bool {
+    cfg.usage.enabled && !do_not_track()
+}
+
+fn do_not_track() -> bool {
+    std::env::var_os("DO_NOT_TRACK").is_some_and(|v| !v.is_empty() && v != "0")
+}
+
+/// Records the event of a command, and sends the spooled events once a
+/// batch is complete. Failures are only logged: usage events never fail or
+/// hold up a command for long.
+pub async fn record(cfg: &XetConfig, event: &UsageEvent) {
+    if !is_enabled(cfg) {
+        return;
+    }
+    let dir = cfg.xet_home.join(SPOOL_DIR_HOME);
+    if let Err(e) = cfg.permission.create_dir_all(&dir) {
+        debug!("Unable to create the usage event spool in {dir:?}: {e:?}");
+        return;
+    }
+    let spool = Spool::new(&dir, cfg.usage.spool_max);
+    if let Err(e) = record_impl(&spool, &cfg.usage, event).await {
+        debug!("Unable to record usage event: {e:?}");
+    }
+}
+
+async fn record_impl(
+    spool: &Spool,
+    settings: &UsageSettings,
+    event: &UsageEvent,
+) -> io::Result<()> {
+    spool.append(&[serde_json::to_string(event)?])?;
+    if spool.read()?.len() < settings.batch_size {
+        return Ok(());
+    }
+    let events = spool.take()?;
+    if events.is_empty() {
+        // Another process took the batch.
+        return Ok(());
+    }
+    if let Err(e) = send(settings, &events).await {
+        debug!(
+            "Unable to send {} usage events, keeping them: {e:?}",
+            events.len()
+        );
+        spool.append(&events)?;
+    }
+    Ok(())
+}
+
+/// Posts the spooled events in one batch.
+async fn send(settings: &UsageSettings, events: &[String]) -> Result<(), reqwest::Error> {
+    let body = batch_body(&settings.api_key, events);
+    reqwest::Client::builder()
+        .timeout(SEND_TIMEOUT)
+        .build()?
+        .post(&settings.endpoint)
+        .json(&body)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// The body of a batch: each event under the same anonymous id.
+fn batch_body(api_key: &str, events: &[String]) -> Value {
+    let batch: Vec<Value> = events
+        .iter()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .map(|properties| {
+            json!({
+                "event": "command",
+                "distinct_id": "anonymous",
+                "properties": properties,
+            })
+        })
+        .collect();
+    json!({ "api_key": api_key, "batch": batch })
+}
+
+/// The events waiting to be sent, one JSON line each, keeping at most max.
+struct Spool {
+    path: PathBuf,
+    max: usize,
+}
+
+impl Spool {
+    fn new(dir: &Path, max: usize) -> Self {
+        Self {
+            path: dir.join(SPOOL_FILENAME),
+            max,
+        }
+    }
+
+    /// Appends the lines, then drops the oldest beyond max.
+    fn append(&self, lines: &[String]) -> io::Result<()> {
+        let mut data = lines.join("\n");
+        data.push('\n');
+        // A single write, so that the lines of concurrent processes do not
+        // interleave.
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(data.as_bytes())?;
+
+        let spooled = self.read()?;
+        if spooled.len() > self.max {
+            let mut kept = spooled[spooled.len() - self.max..].join("\n");
+            kept.push('\n');
+            // An event appended by another process meanwhile may be lost,
+            // which is fine for events already dropped for being too many.
+            let tmp = self
+                .path
+                .with_extension(format!("{}.tmp", std::process::id()));
+            fs::write(&tmp, kept)?;
+            fs::rename(&tmp, &self.path)?;
+        }
+        Ok(())
+    }
+
+    fn read(&self) -> io::Result<Vec<String>> {
+        match fs::read_to_string(&self.path) {
+            Ok(data) => Ok(data
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Removes and returns all the spooled events. The spool is moved aside
+    /// first, so that concurrent processes don't send the same events.
+    fn take(&self) -> io::Result<Vec<String>> {
+        let taken = Spool {
+            path: self
+                .path
+                .with_extension(format!("{}.sending", std::process::id())),
+            max: self.max,
+        };
+        match fs::rename(&self.path, &taken.path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        }
+        let events = taken.read();
+        let _ = fs::remove_file(&taken.path);
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(command: &str) -> UsageEvent {
+        UsageEvent {
+            command: command.to_string(),
+            duration_secs: 1.5,
+            uploaded_bytes: 100,
+            downloaded_bytes: 0,
+            error: Some(ErrorCategory::Network),
+        }
+    }
+
+    #[test]
+    fn test_usage_event_schema() {
+        let value = serde_json::to_value(event("push")).unwrap();
+        let mut fields: Vec<_> = value.as_object().unwrap().keys().cloned().collect();
+        fields.sort();
+        assert_eq!(
+            fields,
+            [
+                "command",
+                "downloaded_bytes",
+                "duration_secs",
+                "error",
+                "uploaded_bytes"
+            ]
+        );
+        assert_eq!(value["error"], "network");
+
+        let body = batch_body("key", &[value.to_string()]);
+        assert_eq!(body["api_key"], "key");
+        assert_eq!(body["batch"][0]["distinct_id"], "anonymous");
+        assert_eq!(body["batch"][0]["properties"]["command"], "push");
+    }
+
+    #[tokio::test]
+    async fn test_spool_offline() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let spool = Spool::new(dir.path(), 3);
+        let settings = UsageSettings {
+            enabled: true,
+            // Nothing listens there, as when offline.
+            endpoint: "http://127.0.0.1:9/batch/".to_string(),
+            batch_size: 2,
+            spool_max: 3,
+            ..Default::default()
+        };
+
+        record_impl(&spool, &settings, &event("a")).await.unwrap();
+        assert_eq!(spool.read().unwrap().len(), 1);
+        // A complete batch that can't be sent is kept.
+        record_impl(&spool, &settings, &event("b")).await.unwrap();
+        assert_eq!(spool.read().unwrap().len(), 2);
+        // The oldest events are dropped beyond the max.
+        record_impl(&spool, &settings, &event("c")).await.unwrap();
+        record_impl(&spool, &settings, &event("d")).await.unwrap();
+        let commands: Vec<String> = spool
+            .read()
+            .unwrap()
+            .iter()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap()["command"].to_string())
+            .collect();
+        assert_eq!(commands.len(), 3);
+        assert!(!commands.contains(&"\"a\"".to_string()));
+
+        assert_eq!(spool.take().unwrap().len(), 3);
+        assert!(spool.read().unwrap().is_empty());
+        assert!(spool.take().unwrap().is_empty());
+    }
+}
@@ -1,16 +1,22 @@
 use std::any::Any;
 use std::fmt::Debug;
 use std::io;
+use std::net::SocketAddr;
 use std::num::ParseIntError;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{ExitCode, Termination};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use cas::errors::SingleflightError;
 use lazy::error::LazyError;
+use lazy_static::lazy_static;
 use merklehash::MerkleHash;
+use serde::Serialize;
 use xet_error::Error;
 
 use crate::config::ConfigError;
+use crate::environment::log::current_log_file;
 use cas_client::CasClientError;
 use parutils::ParallelError;
 
@@ -118,6 +124,48 @@ pub enum GitXetRepoError {
 
     #[error("Data hash byte translation error.")]
     DataHashBytesParseError(#[from] merklehash::DataHashBytesParseError),
+
+    #[error("Integrity check failed: {0}")]
+    IntegrityCheckFailed(String),
+
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+
+    #[error("Access denied: {0}")]
+    AccessDenied(String),
+
+    #[error("Import source error: {0}")]
+    ImportSourceError(String),
+
+    #[error("Hash algorithm unavailable: {0}")]
+    HashAlgorithmUnavailable(String),
+
+    #[error("Insufficient disk space: {0}")]
+    InsufficientDiskSpace(String),
+
+    #[error("Interrupted: {0}")]
+    Interrupted(String),
+
+    #[error("Error running git command: git {command} err_code={status:?}, stdout={stdout:?}, stderr={stderr:?}")]
+    GitCommandFailed {
+        command: String,
+        status: Option<i32>,
+        stdout: String,
+        stderr: String,
+    },
+
+    /// A git command failed as another process held the lock of a ref it
+    /// updates; running it again may succeed.
+    #[error("Ref locked running git command: git {command}, stderr={stderr:?}")]
+    RefLocked { command: String, stderr: String },
+
+    /// An HTTP server of a long-running command was unable to bind to its
+    /// address, or failed while serving.
+    #[error("HTTP server on {addr}: {source}")]
+    HttpServerError {
+        addr: SocketAddr,
+        source: hyper::Error,
+    },
 }
 
 // Define our own result type here (this seems to be the standard).
@@ -150,45 +198,206 @@ impl From<SingleflightError<GitXetRepoError>> for GitXetRepoError {
     }
 }
 
-impl From<GitXetRepoError> for ExitCode {
-    fn from(value: GitXetRepoError) -> Self {
-        ExitCode::from(match value {
-            GitXetRepoError::IOError(_) => 2,
-            GitXetRepoError::NetworkIOError(_) => 3,
-            GitXetRepoError::HashStringParsingFailure(_) => 4,
-            GitXetRepoError::MerkleDBError(_) => 5,
-            GitXetRepoError::MDBShardError(_) => 6,
-            GitXetRepoError::CasClientError(_) => 7,
-            GitXetRepoError::FileReconstructionFailed(_) => 8,
-            GitXetRepoError::HashNotFound => 9,
-            GitXetRepoError::StreamParseError(_) => 10,
-            GitXetRepoError::StreamParseHeader(_) => 11,
-            GitXetRepoError::DataParsingError(_) => 12,
-            GitXetRepoError::Utf8Parse(_) => 13,
-            GitXetRepoError::GitRepoError(_) => 14,
-            GitXetRepoError::JSONError(_) => 15,
-            GitXetRepoError::ConfigError(_) => 16,
-            GitXetRepoError::InternalError(_) => 17,
-            GitXetRepoError::Other(_) => 18,
-            GitXetRepoError::RefusingToOverwriteLocalChanges(_) => 19,
-            GitXetRepoError::InvalidOperation(_) => 20,
-            GitXetRepoError::RepoNotDiscoverable => 21,
-            GitXetRepoError::RepoHasNoRemotes => 22,
-            GitXetRepoError::InvalidRemote(_) => 23,
-            GitXetRepoError::InvalidLocalCasPath(_) => 24,
-            GitXetRepoError::InvalidLogPath(_, _) => 25,
-            GitXetRepoError::FileNotFound(_) => 26,
+/// The broad class of an error, for tools that handle errors from the CLI
+/// programmatically rather than by message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCategory {
+    /// Missing or rejected credentials.
+    Auth,
+    /// Communication with the CAS or shard server failed.
+    Network,
+    /// A repository, file, or object does not exist.
+    NotFound,
+    /// Data did not match its recorded hash.
+    Integrity,
+    /// The configuration or repository setup is invalid.
+    Config,
+    /// The operation is not allowed in the current state.
+    Usage,
+    /// Local file I/O failed.
+    Io,
+    Internal,
+}
+
+impl GitXetRepoError {
+    /// The process exit code for this error. These are stable: a code is
+    /// never reassigned once released.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::IOError(_) => 2,
+            Self::NetworkIOError(_) => 3,
+            Self::HashStringParsingFailure(_) => 4,
+            Self::MerkleDBError(_) => 5,
+            Self::MDBShardError(_) => 6,
+            Self::CasClientError(_) => 7,
+            Self::FileReconstructionFailed(_) => 8,
+            Self::HashNotFound => 9,
+            Self::StreamParseError(_) => 10,
+            Self::StreamParseHeader(_) => 11,
+            Self::DataParsingError(_) => 12,
+            Self::Utf8Parse(_) => 13,
+            Self::GitRepoError(_) => 14,
+            Self::JSONError(_) => 15,
+            Self::ConfigError(_) => 16,
+            Self::InternalError(_) => 17,
+            Self::Other(_) => 18,
+            Self::RefusingToOverwriteLocalChanges(_) => 19,
+            Self::InvalidOperation(_) => 20,
+            Self::RepoNotDiscoverable => 21,
+            Self::RepoHasNoRemotes => 22,
+            Self::InvalidRemote(_) => 23,
+            Self::InvalidLocalCasPath(_) => 24,
+            Self::InvalidLogPath(_, _) => 25,
+            Self::FileNotFound(_) => 26,
             // S3Error code 27 deprecated
-            GitXetRepoError::WindowsEditionCheckError => 28,
-            GitXetRepoError::AuthError(_) => 29,
-            GitXetRepoError::RepoUninitialized(_) => 30,
-            GitXetRepoError::RepoSaltUnavailable(_) => 31,
-            GitXetRepoError::LazyConfigError(_) => 32,
-            GitXetRepoError::JoinError(_) => 33,
-            GitXetRepoError::ShardClientError(_) => 34,
-            GitXetRepoError::WalkDirError(_) => 35,
-            GitXetRepoError::DataHashBytesParseError(_) => 36,
+            Self::WindowsEditionCheckError => 28,
+            Self::AuthError(_) => 29,
+            Self::RepoUninitialized(_) => 30,
+            Self::RepoSaltUnavailable(_) => 31,
+            Self::LazyConfigError(_) => 32,
+            Self::JoinError(_) => 33,
+            Self::ShardClientError(_) => 34,
+            Self::WalkDirError(_) => 35,
+            Self::DataHashBytesParseError(_) => 36,
+            Self::IntegrityCheckFailed(_) => 37,
+            Self::QuotaExceeded(_) => 38,
+            Self::AccessDenied(_) => 39,
+            Self::ImportSourceError(_) => 40,
+            Self::HashAlgorithmUnavailable(_) => 41,
+            Self::InsufficientDiskSpace(_) => 42,
+            Self::GitCommandFailed { .. } => 43,
+            Self::RefLocked { .. } => 44,
+            Self::HttpServerError { .. } => 45,
+            // 128 + SIGINT, as shells report a process stopped by Ctrl-C
+            Self::Interrupted(_) => 130,
+        }
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Self::AuthError(_) | Self::AccessDenied(_) => ErrorCategory::Auth,
+            Self::NetworkIOError(e) => match e {
+                CasClientError::XORBNotFound(_) => ErrorCategory::NotFound,
+                CasClientError::HashMismatch => ErrorCategory::Integrity,
+                CasClientError::StagingOutOfSpace(..) => ErrorCategory::Io,
+                _ => ErrorCategory::Network,
+            },
+            Self::CasClientError(_) | Self::ShardClientError(_) | Self::ImportSourceError(_) => {
+                ErrorCategory::Network
+            }
+            Self::HashNotFound
+            | Self::FileNotFound(_)
+            | Self::RepoNotDiscoverable
+            | Self::RepoHasNoRemotes => ErrorCategory::NotFound,
+            Self::FileReconstructionFailed(_) | Self::IntegrityCheckFailed(_) => {
+                ErrorCategory::Integrity
+            }
+            Self::ConfigError(_)
+            | Self::LazyConfigError(_)
+            | Self::InvalidRemote(_)
+            | Self::InvalidLocalCasPath(_)
+            | Self::InvalidLogPath(_, _)
+            | Self::RepoUninitialized(_)
+            | Self::RepoSaltUnavailable(_)
+            | Self::HashAlgorithmUnavailable(_) => ErrorCategory::Config,
+            Self::InvalidOperation(_)
+            | Self::RefusingToOverwriteLocalChanges(_)
+            | Self::QuotaExceeded(_)
+            | Self::Interrupted(_)
+            | Self::WindowsEditionCheckError => ErrorCategory::Usage,
+            Self::IOError(_)
+            | Self::WalkDirError(_)
+            | Self::InsufficientDiskSpace(_)
+            | Self::RefLocked { .. }
+            | Self::HttpServerError { .. } => ErrorCategory::Io,
+            Self::HashStringParsingFailure(_)
+            | Self::MerkleDBError(_)
+            | Self::MDBShardError(_)
+            | Self::StreamParseError(_)
+            | Self::StreamParseHeader(_)
+            | Self::DataParsingError(_)
+            | Self::Utf8Parse(_)
+            | Self::GitRepoError(_)
+            | Self::GitCommandFailed { .. }
+            | Self::JSONError(_)
+            | Self::InternalError(_)
+            | Self::Other(_)
+            | Self::JoinError(_)
+            | Self::DataHashBytesParseError(_) => ErrorCategory::Internal,
+        }
+    }
+
+    /// Renders the error as a single line of JSON, as printed on stderr with
+    /// `--error-format json`, with the log file of the command if any.
+    pub fn to_json(&self, log: Option<&Path>) -> String {
+        #[derive(Serialize)]
+        struct JsonError<'a> {
+            category: ErrorCategory,
+            exit_code: u8,
+            message: &'a str,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            log: Option<&'a Path>,
+        }
+        #[derive(Serialize)]
+        struct JsonErrorOutput<'a> {
+            error: JsonError<'a>,
+        }
+
+        let message = self.to_string();
+        serde_json::to_string(&JsonErrorOutput {
+            error: JsonError {
+                category: self.category(),
+                exit_code: self.exit_code(),
+                message: &message,
+                log,
+            },
         })
+        .unwrap_or_default()
+    }
+}
+
+impl From<GitXetRepoError> for ExitCode {
+    fn from(value: GitXetRepoError) -> Self {
+        ExitCode::from(value.exit_code())
+    }
+}
+
+/// How errors ending the process are printed on stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = GitXetRepoError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(ErrorFormat::Text),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(GitXetRepoError::InvalidOperation(format!(
+                "{s} is not one of {{'text'|'json'}}"
+            ))),
+        }
+    }
+}
+
+lazy_static! {
+    static ref JSON_ERROR_OUTPUT: AtomicBool = AtomicBool::new(false);
+}
+
+pub fn set_error_format(format: ErrorFormat) {
+    JSON_ERROR_OUTPUT.store(format == ErrorFormat::Json, Ordering::Relaxed);
+}
+
+pub fn error_format() -> ErrorFormat {
+    if JSON_ERROR_OUTPUT.load(Ordering::Relaxed) {
+        ErrorFormat::Json
+    } else {
+        ErrorFormat::Text
     }
 }
 
@@ -207,7 +416,16 @@ impl Termination for MainReturn {
         match self {
             MainReturn::Success => ExitCode::SUCCESS,
             MainReturn::Error(err) => {
-                eprintln!("{err}");
+                let log = current_log_file();
+                match error_format() {
+                    ErrorFormat::Text => {
+                        eprintln!("{err}");
+                        if let Some(log) = log {
+                            eprintln!("The log of this command is in {}", log.display());
+                        }
+                    }
+                    ErrorFormat::Json => eprintln!("{}", err.to_json(log)),
+                }
                 err.into()
             }
             MainReturn::Panic(e) => {
@@ -235,3 +453,66 @@ pub fn convert_parallel_error(e: ParallelError<GitXetRepoError>) -> GitXetRepoEr
         ParallelError::TaskError(t) => t,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_category_of_cas_errors() {
+        let not_found = GitXetRepoError::from(CasClientError::XORBNotFound(MerkleHash::default()));
+        assert_eq!(not_found.category(), ErrorCategory::NotFound);
+        let mismatch = GitXetRepoError::from(CasClientError::HashMismatch);
+        assert_eq!(mismatch.category(), ErrorCategory::Integrity);
+        let network = GitXetRepoError::from(CasClientError::DataTransferTimeout);
+        assert_eq!(network.category(), ErrorCategory::Network);
+        assert_eq!(network.exit_code(), 3);
+    }
+
+    #[test]
+    fn test_category_of_git_command_errors() {
+        let failed = GitXetRepoError::GitCommandFailed {
+            command: "notes merge".to_string(),
+            status: Some(128),
+            stdout: String::new(),
+            stderr: "fatal: bad revision".to_string(),
+        };
+        assert_eq!(failed.category(), ErrorCategory::Internal);
+        assert_eq!(failed.exit_code(), 43);
+        let locked = GitXetRepoError::RefLocked {
+            command: "notes merge".to_string(),
+            stderr: "error: cannot lock ref 'refs/notes/xet/merkledb'".to_string(),
+        };
+        assert_eq!(locked.category(), ErrorCategory::Io);
+        assert_eq!(locked.exit_code(), 44);
+    }
+
+    #[test]
+    fn test_json_output() {
+        let err = GitXetRepoError::AuthError(anyhow::anyhow!("token \"abc\" expired"));
+        let json: serde_json::Value = serde_json::from_str(&err.to_json(None)).unwrap();
+        assert_eq!(json["error"]["category"], "auth");
+        assert_eq!(json["error"]["exit_code"], 29);
+        assert_eq!(
+            json["error"]["message"],
+            "Authentication Error: token \"abc\" expired"
+        );
+        assert!(json["error"].get("log").is_none());
+        crate::schemas::assert_matches_schema("error", &json);
+
+        let log = Path::new("/repo/.git/xet/logs/20231114T221320.000Z-42-push.log");
+        let json: serde_json::Value = serde_json::from_str(&err.to_json(Some(log))).unwrap();
+        assert_eq!(
+            json["error"]["log"],
+            "/repo/.git/xet/logs/20231114T221320.000Z-42-push.log"
+        );
+        crate::schemas::assert_matches_schema("error", &json);
+    }
+
+    #[test]
+    fn test_parse_error_format() {
+        assert_eq!("json".parse::<ErrorFormat>().unwrap(), ErrorFormat::Json);
+        assert_eq!("TEXT".parse::<ErrorFormat>().unwrap(), ErrorFormat::Text);
+        assert!("yaml".parse::<ErrorFormat>().is_err());
+    }
+}
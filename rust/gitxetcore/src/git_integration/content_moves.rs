@@ -0,0 +1,189 @@
+//! Files renamed or copied between two trees.
+//!
+//! A renamed or copied file has the same pointer file as the original, so
+//! its data is already known: cleaning it adds no data, and pushing it
+//! uploads none. Matching the pointer files of two trees by their hashes
+//! finds these files, to report them rather than leave users wondering
+//! why a large rename pushed nothing.
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+
+use merklehash::MerkleHash;
+use utils::output_bytes::output_bytes;
+
+use super::RepoPath;
+
+/// The pointer files of a tree, with the hash and size of each file.
+pub type PointerFiles = BTreeMap<RepoPath, (MerkleHash, u64)>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContentMoveKind {
+    Rename,
+    Copy,
+}
+
+/// A file of a tree with the content of another one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContentMove {
+    pub kind: ContentMoveKind,
+    pub from: RepoPath,
+    pub to: RepoPath,
+    pub hash: MerkleHash,
+    pub size: u64,
+}
+
+impl fmt::Display for ContentMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            ContentMoveKind::Rename => "renamed",
+            ContentMoveKind::Copy => "copied",
+        };
+        write!(
+            f,
+            "{kind} {} -> {} ({})",
+            self.from,
+            self.to,
+            output_bytes(self.size as usize)
+        )
+    }
+}
+
+/// The files added or changed in new whose content is that of another file.
+/// A file of old removed or changed in new is renamed to the first file of
+/// new with its content; other files with content of old, or content added
+/// more than once, are copies.
+pub fn find_content_moves(old: &PointerFiles, new: &PointerFiles) -> Vec<ContentMove> {
+    let unchanged = |path: &RepoPath, hash: &MerkleHash| {
+        new.get(path).is_some_and(|(h, _)| h == hash)
+            && old.get(path).is_some_and(|(h, _)| h == hash)
+    };
+
+    // The paths of old no longer holding their content can be renamed; the
+    // others, and the files of new, can be copied from.
+    let mut renamed_from = HashMap::<MerkleHash, VecDeque<&RepoPath>>::new();
+    let mut copied_from = HashMap::<MerkleHash, &RepoPath>::new();
+    for (path, (hash, _)) in old {
+        if unchanged(path, hash) {
+            copied_from.entry(*hash).or_insert(path);
+        } else {
+            renamed_from.entry(*hash).or_default().push_back(path);
+        }
+    }
+
+    let mut moves = Vec::new();
+    for (path, (hash, size)) in new {
+        if unchanged(path, hash) {
+            continue;
+        }
+        let (kind, from) =
+            if let Some(from) = renamed_from.get_mut(hash).and_then(|p| p.pop_front()) {
+                (ContentMoveKind::Rename, from)
+            } else if let Some(from) = copied_from.get(hash) {
+                (ContentMoveKind::Copy, *from)
+            } else {
+                copied_from.insert(*hash, path);
+                continue;
+            };
+        copied_from.entry(*hash).or_insert(path);
+        moves.push(ContentMove {
+            kind,
+            from: from.clone(),
+            to: path.clone(),
+            hash: *hash,
+            size: *size,
+        });
+    }
+    moves
+}
+
+/// Prints the renamed and copied files, and the data they reuse.
+pub fn print_content_moves(moves: &[ContentMove]) {
+    if moves.is_empty() {
+        return;
+    }
+    let bytes: u64 = moves.iter().map(|m| m.size).sum();
+    println!(
+        "Files renamed or copied, with no new data: {} ({})",
+        moves.len(),
+        output_bytes(bytes as usize)
+    );
+    for content_move in moves {
+        println!("  {content_move}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(files: &[(&str, u64)]) -> PointerFiles {
+        files
+            .iter()
+            .map(|(path, content)| {
+                let hash = MerkleHash::from([*content, 0, 0, 0]);
+                (RepoPath::from(*path), (hash, 1000 * content))
+            })
+            .collect()
+    }
+
+    fn moves(old: &PointerFiles, new: &PointerFiles) -> Vec<String> {
+        find_content_moves(old, new)
+            .iter()
+            .map(|m| format!("{:?} {} {}", m.kind, m.from, m.to))
+            .collect()
+    }
+
+    #[test]
+    fn test_renames_and_copies() {
+        let old = tree(&[("a.bin", 1), ("b.bin", 2), ("c.bin", 3)]);
+        let new = tree(&[
+            // a.bin is renamed, then copied.
+            ("data/a.bin", 1),
+            ("data/z.bin", 1),
+            // b.bin is kept and copied.
+            ("b.bin", 2),
+            ("b2.bin", 2),
+            // c.bin is modified, and its new content copied.
+            ("c.bin", 4),
+            ("d.bin", 4),
+        ]);
+        assert_eq!(
+            moves(&old, &new),
+            vec![
+                "Copy b.bin b2.bin",
+                "Copy c.bin d.bin",
+                "Rename a.bin data/a.bin",
+                "Copy data/a.bin data/z.bin",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rename_heavy_history() {
+        // Every file moved to a new directory, one commit at a time.
+        let mut trees = vec![tree(&[("a", 1), ("b", 2), ("c", 3), ("d", 3)])];
+        trees.push(tree(&[("x/a", 1), ("b", 2), ("c", 3), ("d", 3)]));
+        trees.push(tree(&[("x/a", 1), ("x/b", 2), ("x/c", 3), ("x/d", 3)]));
+        // Two files swapped.
+        trees.push(tree(&[("x/a", 2), ("x/b", 1), ("x/c", 3), ("x/d", 3)]));
+
+        assert_eq!(moves(&trees[0], &trees[1]), vec!["Rename a x/a"]);
+        assert_eq!(
+            moves(&trees[1], &trees[2]),
+            vec!["Rename b x/b", "Rename c x/c", "Rename d x/d"]
+        );
+        assert_eq!(
+            moves(&trees[2], &trees[3]),
+            vec!["Rename x/b x/a", "Rename x/a x/b"]
+        );
+        // Across the whole history, nothing is new data.
+        assert_eq!(find_content_moves(&trees[0], &trees[3]).len(), 4);
+
+        // A commit of only new data has none.
+        assert!(moves(&PointerFiles::new(), &tree(&[("a", 1), ("b", 2)])).is_empty());
+        assert_eq!(
+            moves(&PointerFiles::new(), &tree(&[("a", 1), ("b", 1)])),
+            vec!["Copy a b"]
+        );
+    }
+}
@@ -1,21 +1,85 @@
 use crate::errors::Result;
 use crate::git_integration::git_process_wrapping;
-use std::path::PathBuf;
+use crate::git_integration::repo_path::RepoPath;
+use git2::{ObjectType, Oid, Repository};
+use lazy_static::lazy_static;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
 
 use tracing::{error, warn};
 
+/// The entries found by a tree walk and not yet consumed; past this, the
+/// walk waits for the consumer.
+const TREE_WALK_BUFFERED_ENTRIES: usize = 4096;
+
+lazy_static! {
+    static ref TREE_WALK_POOL: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
+        .thread_name(|i| format!("tree-walk-{i}"))
+        .build()
+        .expect("Unable to start the tree walking thread pool");
+}
+
+thread_local! {
+    // A Repository can't be shared between threads, so each thread of the
+    // pool opens its own, kept for the next walk of the same repository.
+    static TREE_WALK_REPO: RefCell<Option<(PathBuf, Repository)>> = const { RefCell::new(None) };
+}
+
+/// What an entry of a git tree is, from its mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GitTreeEntryKind {
+    File,
+    Executable,
+    /// A blob holding the path the link points to.
+    Symlink,
+    /// A gitlink, pointing to a commit of a submodule.
+    Submodule,
+    Directory,
+}
+
+impl GitTreeEntryKind {
+    pub fn from_mode(mode: u32) -> Self {
+        match mode & 0o170000 {
+            0o040000 => GitTreeEntryKind::Directory,
+            0o120000 => GitTreeEntryKind::Symlink,
+            0o160000 => GitTreeEntryKind::Submodule,
+            _ if mode & 0o111 != 0 => GitTreeEntryKind::Executable,
+            _ => GitTreeEntryKind::File,
+        }
+    }
+
+    /// Whether the entry stores the contents of a file, which may be a
+    /// pointer file to smudge; the blob of a symlink is its target path, and
+    /// is never cleaned or smudged.
+    pub fn is_file(&self) -> bool {
+        matches!(self, GitTreeEntryKind::File | GitTreeEntryKind::Executable)
+    }
+}
+
 #[derive(Default)]
 pub struct GitTreeListingEntry {
     pub object_id: String,
-    pub path: String,
+    pub path: RepoPath,
     pub permissions: u32,
     pub size: u64,
 }
 
+impl GitTreeListingEntry {
+    pub fn kind(&self) -> GitTreeEntryKind {
+        GitTreeEntryKind::from_mode(self.permissions)
+    }
+}
+
 pub struct GitTreeListing {
     pub base_dir: PathBuf,
     pub sub_directories: Vec<GitTreeListingEntry>,
+    /// The blobs, which are files, executables and symlinks.
     pub files: Vec<GitTreeListingEntry>,
+    /// The submodules, listed whether or not files_only is set; their
+    /// object_id is the commit of the submodule.
+    pub submodules: Vec<GitTreeListingEntry>,
 }
 
 impl GitTreeListing {
@@ -23,6 +87,8 @@ impl GitTreeListing {
     ///
     /// Use PathBuf::default() for the subdir to run in the base directory, and use "HEAD" or HEAD for the current HEAD.
     ///
+    /// A recursive listing is built by [GitTreeListing::walk], and sorted by
+    /// path; as with `git ls-tree -r`, it has no subdirectories.
     pub fn build(
         base_dir: &PathBuf,
         ref_id: Option<&str>,
@@ -30,6 +96,26 @@ impl GitTreeListing {
         files_only: bool,
         fill_size: bool,
     ) -> Result<Self> {
+        if recursive {
+            let mut ret = Self {
+                base_dir: base_dir.to_path_buf(),
+                sub_directories: Vec::new(),
+                files: Vec::new(),
+                submodules: Vec::new(),
+            };
+            for entry in Self::walk(base_dir, ref_id, fill_size)? {
+                let entry = entry?;
+                if entry.kind() == GitTreeEntryKind::Submodule {
+                    ret.submodules.push(entry);
+                } else {
+                    ret.files.push(entry);
+                }
+            }
+            ret.files.sort_by(|a, b| a.path.cmp(&b.path));
+            ret.submodules.sort_by(|a, b| a.path.cmp(&b.path));
+            return Ok(ret);
+        }
+
         let mut args: Vec<&str> = vec!["-z"];
         if recursive {
             args.push("-r");
@@ -41,13 +127,8 @@ impl GitTreeListing {
 
         args.push(ref_id.unwrap_or("HEAD"));
 
-        let mut ret = Self {
-            base_dir: base_dir.clone(),
-            sub_directories: Vec::new(),
-            files: Vec::new(),
-        };
-
-        let (_, output, _) = git_process_wrapping::run_git_captured(
+        // The paths are raw bytes, which need not be UTF-8.
+        let output = git_process_wrapping::run_git_captured_raw(
             Some(base_dir),
             "ls-tree",
             &args[..],
@@ -55,6 +136,69 @@ impl GitTreeListing {
             None,
         )?;
 
+        Ok(Self::parse(base_dir, &output.stdout, files_only, fill_size))
+    }
+
+    /// Walks the tree at ref_id (HEAD if None) recursively, streaming its
+    /// files, symlinks and submodules as they are found, with paths relative
+    /// to base_dir as `git ls-tree -r` run in it lists them. Subtrees are
+    /// walked in parallel on a work-stealing pool, so entries come in no
+    /// particular order; the walk stops when the iterator is dropped.
+    pub fn walk(base_dir: &Path, ref_id: Option<&str>, fill_size: bool) -> Result<GitTreeWalk> {
+        let repo = Repository::discover(base_dir)?;
+        let root = repo
+            .revparse_single(ref_id.unwrap_or("HEAD"))?
+            .peel_to_tree()?;
+        let subdir = match repo.workdir() {
+            Some(workdir) => base_dir
+                .canonicalize()?
+                .strip_prefix(workdir.canonicalize()?)
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+            None => PathBuf::new(),
+        };
+        let (sender, receiver) = mpsc::sync_channel(TREE_WALK_BUFFERED_ENTRIES);
+        let tree_id = if subdir.as_os_str().is_empty() {
+            root.id()
+        } else {
+            match root.get_path(&subdir) {
+                Ok(entry) => entry.id(),
+                // Nothing is listed in a directory not in the tree.
+                Err(e) if e.code() == git2::ErrorCode::NotFound => {
+                    return Ok(GitTreeWalk { receiver });
+                }
+                Err(e) => return Err(e.into()),
+            }
+        };
+        let walk = TreeWalkContext {
+            git_dir: repo.path().to_path_buf(),
+            fill_size,
+            sender,
+            stopped: AtomicBool::new(false),
+        };
+        TREE_WALK_POOL.spawn(move || {
+            let walk = &walk;
+            TREE_WALK_POOL.scope(|scope| walk.walk_subtree(scope, tree_id, RepoPath::root()));
+        });
+        Ok(GitTreeWalk { receiver })
+    }
+
+    /// Keeps only the entries for which keep returns true.
+    fn retain(&mut self, keep: impl Fn(&GitTreeListingEntry) -> bool) {
+        self.files.retain(&keep);
+        self.sub_directories.retain(&keep);
+        self.submodules.retain(&keep);
+    }
+
+    /// Parses the output of `git ls-tree -z`, with -l if fill_size.
+    fn parse(base_dir: &Path, output: &[u8], files_only: bool, fill_size: bool) -> Self {
+        let mut ret = Self {
+            base_dir: base_dir.to_path_buf(),
+            sub_directories: Vec::new(),
+            files: Vec::new(),
+            submodules: Vec::new(),
+        };
+
         #[derive(PartialEq)]
         enum ObjType {
             Blob,
@@ -66,13 +210,13 @@ impl GitTreeListing {
             // This splits on both the newlines and the spaces / tabs in the output.
             // Format is always
             //
-            //   object_permissions object_type object_id\tpath\n
+            //   object_permissions object_type object_id\tpath\0
             //
             // So this output below should solidly be able to iterate through it all.
 
             // Helper function to get the next field by whitespace.
             let next_field =
-                |idx: &mut usize, search_char: char, allow_multiple: bool| -> Option<&str> {
+                |idx: &mut usize, search_char: u8, allow_multiple: bool| -> Option<&[u8]> {
                     let start_index = *idx;
 
                     // See if we're done.
@@ -80,16 +224,17 @@ impl GitTreeListing {
                         return None;
                     }
                     // Search forward.
-                    let next_index = match &output[start_index..].find(search_char) {
-                        Some(idx) => idx + start_index,
-                        None => output.len(),
-                    };
+                    let next_index =
+                        match output[start_index..].iter().position(|&c| c == search_char) {
+                            Some(idx) => idx + start_index,
+                            None => output.len(),
+                        };
 
                     // Find the next index that isn't a whitespace character to form the starting point
                     // of the next search term.
                     if allow_multiple {
                         *idx = if next_index + 1 < output.len() {
-                            match &output[next_index..].find(|c: char| c != search_char) {
+                            match output[next_index..].iter().position(|&c| c != search_char) {
                                 Some(idx) => idx + next_index,
                                 None => output.len(),
                             }
@@ -102,6 +247,11 @@ impl GitTreeListing {
 
                     Some(&output[start_index..next_index])
                 };
+            // The fields other than the path are ASCII.
+            let next_str_field = |idx: &mut usize, search_char: u8, allow_multiple: bool| {
+                next_field(idx, search_char, allow_multiple)
+                    .map(|f| std::str::from_utf8(f).unwrap_or_default())
+            };
 
             // Event loop to parse the input.
 
@@ -114,14 +264,14 @@ impl GitTreeListing {
                     error!(
                         "Premature end in ls-trees output line {:?}: {}",
                         line_number,
-                        &output[line_start_idx..]
+                        String::from_utf8_lossy(&output[line_start_idx..])
                     );
                 };
 
                 let mut entry = GitTreeListingEntry::default();
 
                 // Ignore the file permissions
-                if let Some(file_perm) = next_field(&mut idx, ' ', false) {
+                if let Some(file_perm) = next_str_field(&mut idx, b' ', false) {
                     debug_assert!(file_perm.chars().all(|c| c.is_numeric()));
                     entry.permissions = match u32::from_str_radix(file_perm, 8) {
                         Ok(v) => v,
@@ -139,16 +289,10 @@ impl GitTreeListing {
                 }
 
                 // Determine the type of the file
-                let (obj_type, use_entry) = match next_field(&mut idx, ' ', false) {
+                let (obj_type, use_entry) = match next_str_field(&mut idx, b' ', false) {
                     Some("blob") => (ObjType::Blob, true),
                     Some("tree") => (ObjType::Tree, !files_only),
-                    Some("commit") => {
-                        error!(
-                            "Parsing Error in ls-trees output line, unexpected type \"commit\": {:?}, {}",
-                            line_number, &output[line_start_idx..]
-                        );
-                        (ObjType::Commit, false)
-                    }
+                    Some("commit") => (ObjType::Commit, true),
                     _ => {
                         print_parse_error();
                         break;
@@ -157,13 +301,14 @@ impl GitTreeListing {
 
                 // Go to end and discard the rest.
                 if !use_entry {
-                    let _ = next_field(&mut idx, '\n', false);
+                    let _ = next_field(&mut idx, b'\0', false);
                     line_number += 1;
                     continue;
                 }
 
                 // Next is object ID.  Multiple spaces after this depending on
-                if let Some(s) = next_field(&mut idx, if fill_size { ' ' } else { '\t' }, fill_size)
+                if let Some(s) =
+                    next_str_field(&mut idx, if fill_size { b' ' } else { b'\t' }, fill_size)
                 {
                     entry.object_id = s.to_owned();
                 } else {
@@ -172,7 +317,7 @@ impl GitTreeListing {
                 }
 
                 if fill_size {
-                    if let Some(s) = next_field(&mut idx, '\t', false) {
+                    if let Some(s) = next_str_field(&mut idx, b'\t', false) {
                         entry.size = if obj_type == ObjType::Blob {
                             s.parse::<u64>().unwrap_or(0)
                         } else {
@@ -185,8 +330,8 @@ impl GitTreeListing {
                 }
 
                 // Next is the path.  This is from the query directory.
-                if let Some(s) = next_field(&mut idx, '\0', false) {
-                    entry.path = s.to_owned();
+                if let Some(s) = next_field(&mut idx, b'\0', false) {
+                    entry.path = RepoPath::from_bytes(s);
                 } else {
                     print_parse_error();
                     break;
@@ -194,6 +339,7 @@ impl GitTreeListing {
 
                 match obj_type {
                     ObjType::Blob => ret.files.push(entry),
+                    ObjType::Commit => ret.submodules.push(entry),
                     ObjType::Tree => {
                         if !files_only {
                             debug_assert!(use_entry);
@@ -202,12 +348,207 @@ impl GitTreeListing {
                             debug_assert!(!use_entry);
                         }
                     }
-                    _ => {}
                 }
                 line_number += 1;
             }
         }
-        Ok(ret)
+        ret
+    }
+
+    /// Keeps only the files and subdirectories whose paths match the git
+    /// pathspec, e.g. "data/imagenet/**" or "*.csv". An empty pathspec keeps
+    /// everything.
+    ///
+    /// The paths matched are relative to base_dir, with path_prefix
+    /// prepended; the prefix lets a listing of a subdirectory or submodule be
+    /// matched against a pathspec relative to an outer repository root.
+    pub fn retain_pathspec<T: AsRef<str>>(
+        &mut self,
+        pathspec: &[T],
+        path_prefix: &Path,
+    ) -> Result<()> {
+        let filter = GitTreeEntryFilter::default().retain_pathspec(pathspec, path_prefix)?;
+        self.retain(|e| filter.matches(e));
+        Ok(())
+    }
+
+    /// Removes the files and subdirectories matching any of the exclude
+    /// patterns, which follow .gitignore: a pattern containing a slash is
+    /// matched from the repository root, e.g. "data/raw/*", and any other
+    /// pattern is matched against each path component, so "node_modules"
+    /// removes every node_modules directory and everything under it.
+    ///
+    /// path_prefix is prepended to the paths as in retain_pathspec.
+    pub fn remove_excluded<T: AsRef<str>>(
+        &mut self,
+        patterns: &[T],
+        path_prefix: &Path,
+    ) -> Result<()> {
+        let filter = GitTreeEntryFilter::default().remove_excluded(patterns, path_prefix)?;
+        self.retain(|e| filter.matches(e));
+        Ok(())
+    }
+}
+
+/// The entries of a tree found by [GitTreeListing::walk], as they are found.
+pub struct GitTreeWalk {
+    receiver: mpsc::Receiver<Result<GitTreeListingEntry>>,
+}
+
+impl Iterator for GitTreeWalk {
+    type Item = Result<GitTreeListingEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+struct TreeWalkContext {
+    git_dir: PathBuf,
+    fill_size: bool,
+    sender: mpsc::SyncSender<Result<GitTreeListingEntry>>,
+    /// Set once the consumer is gone, or an error was sent.
+    stopped: AtomicBool,
+}
+
+impl TreeWalkContext {
+    fn walk_subtree<'s>(&'s self, scope: &rayon::Scope<'s>, tree_id: Oid, prefix: RepoPath) {
+        if self.stopped.load(Ordering::Relaxed) {
+            return;
+        }
+        let res = self.with_repo(|repo| {
+            let tree = repo.find_tree(tree_id)?;
+            let odb = repo.odb()?;
+            for entry in tree.iter() {
+                let mut path = prefix.clone();
+                path.push(entry.name_bytes());
+                let id = entry.id();
+                let kind = entry.kind();
+                if kind == Some(ObjectType::Tree) {
+                    scope.spawn(move |scope| self.walk_subtree(scope, id, path));
+                    continue;
+                }
+                let size = if self.fill_size && kind == Some(ObjectType::Blob) {
+                    odb.read_header(id)?.0 as u64
+                } else {
+                    0
+                };
+                let entry = GitTreeListingEntry {
+                    object_id: id.to_string(),
+                    path,
+                    permissions: entry.filemode() as u32,
+                    size,
+                };
+                if !self.send(Ok(entry)) {
+                    break;
+                }
+            }
+            Ok(())
+        });
+        if let Err(e) = res {
+            self.send(Err(e));
+            self.stopped.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Sends an entry to the consumer; false if it's gone.
+    fn send(&self, entry: Result<GitTreeListingEntry>) -> bool {
+        if self.sender.send(entry).is_err() {
+            self.stopped.store(true, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    fn with_repo<T>(&self, f: impl FnOnce(&Repository) -> Result<T>) -> Result<T> {
+        TREE_WALK_REPO.with(|cached| {
+            let mut cached = cached.borrow_mut();
+            if !matches!(&*cached, Some((dir, _)) if *dir == self.git_dir) {
+                *cached = Some((self.git_dir.clone(), Repository::open(&self.git_dir)?));
+            }
+            let (_, repo) = cached.as_ref().unwrap();
+            f(repo)
+        })
+    }
+}
+
+/// Which entries of a tree to keep, for listings built or streamed by
+/// [GitTreeListing::walk]; keeps everything by default.
+#[derive(Default)]
+pub struct GitTreeEntryFilter {
+    pathspec: Option<(git2::Pathspec, PathBuf)>,
+    excluded: Vec<ExcludePatterns>,
+}
+
+struct ExcludePatterns {
+    anchored: Option<git2::Pathspec>,
+    names: Option<git2::Pathspec>,
+    path_prefix: PathBuf,
+}
+
+impl GitTreeEntryFilter {
+    /// Keeps only the entries matching the pathspec, as
+    /// [GitTreeListing::retain_pathspec] does.
+    pub fn retain_pathspec<T: AsRef<str>>(
+        mut self,
+        pathspec: &[T],
+        path_prefix: &Path,
+    ) -> Result<Self> {
+        if !pathspec.is_empty() {
+            let pathspec = git2::Pathspec::new(pathspec.iter().map(|p| p.as_ref()))?;
+            self.pathspec = Some((pathspec, path_prefix.to_path_buf()));
+        }
+        Ok(self)
+    }
+
+    /// Removes the entries matching any of the exclude patterns, as
+    /// [GitTreeListing::remove_excluded] does.
+    pub fn remove_excluded<T: AsRef<str>>(
+        mut self,
+        patterns: &[T],
+        path_prefix: &Path,
+    ) -> Result<Self> {
+        let (anchored, names): (Vec<&str>, Vec<&str>) = patterns
+            .iter()
+            .map(|p| p.as_ref().trim().trim_end_matches('/'))
+            .filter(|p| !p.is_empty())
+            .partition(|p| p.contains('/'));
+        // An empty git2::Pathspec matches every path, so each kind of pattern
+        // is only used if present.
+        let pathspec = |patterns: Vec<&str>| -> Result<Option<git2::Pathspec>> {
+            if patterns.is_empty() {
+                Ok(None)
+            } else {
+                Ok(Some(git2::Pathspec::new(patterns)?))
+            }
+        };
+        self.excluded.push(ExcludePatterns {
+            anchored: pathspec(anchored.iter().map(|p| p.trim_start_matches('/')).collect())?,
+            names: pathspec(names)?,
+            path_prefix: path_prefix.to_path_buf(),
+        });
+        Ok(self)
+    }
+
+    pub fn matches(&self, entry: &GitTreeListingEntry) -> bool {
+        let flags = git2::PathspecFlags::DEFAULT;
+        let entry_path = entry.path.to_path();
+        if let Some((pathspec, path_prefix)) = &self.pathspec {
+            if !pathspec.matches_path(&path_prefix.join(&entry_path), flags) {
+                return false;
+            }
+        }
+        !self.excluded.iter().any(|excluded| {
+            let path = excluded.path_prefix.join(&entry_path);
+            excluded
+                .anchored
+                .as_ref()
+                .map_or(false, |a| a.matches_path(&path, flags))
+                || excluded.names.as_ref().map_or(false, |n| {
+                    path.components()
+                        .any(|c| n.matches_path(Path::new(c.as_os_str()), flags))
+                })
+        })
     }
 }
 
@@ -334,7 +675,7 @@ mod git_file_tools_tests {
                     .into_iter()
                     .map(|e| {
                         assert_eq!(e.size, 100);
-                        e.path
+                        e.path.to_string()
                     })
                     .sorted()
                     .collect(),
@@ -373,7 +714,7 @@ mod git_file_tools_tests {
             v.into_iter()
                 .map(|e| {
                     assert_eq!(e.size, 0); // Not querying the size
-                    e.path
+                    e.path.to_string()
                 })
                 .sorted()
                 .collect()
@@ -402,6 +743,169 @@ mod git_file_tools_tests {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_walk() -> Result<()> {
+        let tr = TestRepo::new()?;
+
+        let mut files = Vec::new();
+        for d in 0..8 {
+            for f in 0..4 {
+                files.push(format!("d{d}/sub{}/f{f}.dat", d % 3));
+            }
+        }
+        files.push("top.dat".to_owned());
+        for (i, f) in files.iter().enumerate() {
+            tr.write_file(f, i as u64, 10 + i)?;
+        }
+        tr.repo.run_git_checked_in_repo("add", &["."])?;
+        tr.repo
+            .run_git_checked_in_repo("commit", &["-m", "Added files"])?;
+
+        let walked: Vec<(String, u64)> = GitTreeListing::walk(&tr.repo.repo_dir, None, true)?
+            .map(|e| e.map(|e| (e.path.to_string(), e.size)))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .sorted()
+            .collect();
+        let expected: Vec<(String, u64)> = files
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (f.clone(), 10 + i as u64))
+            .sorted()
+            .collect();
+        assert_eq!(walked, expected);
+
+        // The recursive listing is the walk, sorted.
+        let listing = GitTreeListing::build(&tr.repo.repo_dir, None, true, true, true)?;
+        let listed: Vec<(String, u64)> = listing
+            .files
+            .iter()
+            .map(|e| (e.path.to_string(), e.size))
+            .collect();
+        assert_eq!(listed, expected);
+
+        // Paths are relative to a subdirectory walked from.
+        let sub: Vec<String> = GitTreeListing::walk(&tr.repo.repo_dir.join("d4"), None, false)?
+            .map(|e| e.unwrap().path.to_string())
+            .sorted()
+            .collect();
+        assert_eq!(
+            sub,
+            ["sub1/f0.dat", "sub1/f1.dat", "sub1/f2.dat", "sub1/f3.dat"]
+        );
+
+        // Stopping early leaves nothing waiting.
+        let mut walk = GitTreeListing::walk(&tr.repo.repo_dir, None, false)?;
+        assert!(walk.next().is_some());
+        drop(walk);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_listing_pathspec() -> Result<()> {
+        let tr = TestRepo::new()?;
+
+        let files = [
+            "a.csv",
+            "data/b.csv",
+            "data/imagenet/c.jpg",
+            "data/imagenet/d/e.jpg",
+        ];
+        for f in files.iter() {
+            tr.write_file(f, 0, 100)?;
+        }
+        tr.repo.run_git_checked_in_repo("add", &["."])?;
+        tr.repo
+            .run_git_checked_in_repo("commit", &["-m", "Added files"])?;
+
+        let out_list = |pathspec: &[&str]| -> Result<Vec<String>> {
+            let mut listing = GitTreeListing::build(&tr.repo.repo_dir, None, true, true, false)?;
+            listing.retain_pathspec(pathspec, Path::new(""))?;
+            Ok(listing
+                .files
+                .into_iter()
+                .map(|e| e.path.to_string())
+                .sorted()
+                .collect())
+        };
+
+        assert_eq!(out_list(&[])?.len(), files.len());
+        assert_eq!(
+            out_list(&["data/imagenet/**"])?,
+            vec!["data/imagenet/c.jpg", "data/imagenet/d/e.jpg"]
+        );
+        assert_eq!(
+            out_list(&["data/imagenet"])?,
+            vec!["data/imagenet/c.jpg", "data/imagenet/d/e.jpg"]
+        );
+        assert_eq!(out_list(&["*.csv"])?, vec!["a.csv", "data/b.csv"]);
+        assert_eq!(
+            out_list(&["a.csv", "data/imagenet/d"])?,
+            vec!["a.csv", "data/imagenet/d/e.jpg"]
+        );
+
+        let mut listing =
+            GitTreeListing::build(&tr.repo.repo_dir.join("data"), None, true, true, false)?;
+        listing.retain_pathspec(&["data/imagenet/d/**"], Path::new("data"))?;
+        assert_eq!(
+            listing
+                .files
+                .into_iter()
+                .map(|e| e.path.to_string())
+                .collect_vec(),
+            vec!["imagenet/d/e.jpg"]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_listing_excluded() -> Result<()> {
+        let tr = TestRepo::new()?;
+
+        let files = [
+            "a.csv",
+            "node_modules/b.js",
+            "web/node_modules/c.js",
+            "data/raw/d.csv",
+            "data/e.csv",
+        ];
+        for f in files.iter() {
+            tr.write_file(f, 0, 100)?;
+        }
+        tr.repo.run_git_checked_in_repo("add", &["."])?;
+        tr.repo
+            .run_git_checked_in_repo("commit", &["-m", "Added files"])?;
+
+        let out_list = |patterns: &[&str]| -> Result<Vec<String>> {
+            let mut listing = GitTreeListing::build(&tr.repo.repo_dir, None, true, true, false)?;
+            listing.remove_excluded(patterns, Path::new(""))?;
+            Ok(listing
+                .files
+                .into_iter()
+                .map(|e| e.path.to_string())
+                .sorted()
+                .collect())
+        };
+
+        assert_eq!(out_list(&[])?.len(), files.len());
+        assert_eq!(
+            out_list(&["node_modules/"])?,
+            vec!["a.csv", "data/e.csv", "data/raw/d.csv"]
+        );
+        assert_eq!(
+            out_list(&["/data/raw", "*.js"])?,
+            vec!["a.csv", "data/e.csv"]
+        );
+        assert_eq!(
+            out_list(&["data/*", "web"])?,
+            vec!["a.csv", "node_modules/b.js"]
+        );
+
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     #[cfg(unix)] // Certain file names below contain forbidden characters
     async fn test_listing_odd_names() -> Result<()> {
@@ -442,7 +946,8 @@ mod git_file_tools_tests {
                     .into_iter()
                     .map(|e| {
                         assert_eq!(e.size, 100);
-                        e.path
+                        // compared to the names as written, not as quoted
+                        String::from_utf8(e.path.as_bytes().to_vec()).unwrap()
                     })
                     .sorted()
                     .collect(),
@@ -452,4 +957,50 @@ mod git_file_tools_tests {
         assert_eq!(out_list(None, false)?, files);
         Ok(())
     }
+
+    #[test]
+    fn test_parse_non_utf8_paths() {
+        let output = b"100644 blob 0123abcd     100\tdata/\xe9t\xe9.csv\0\
+                       100644 blob 4567abcd       7\tdata\\raw.csv\0";
+        let listing = GitTreeListing::parse(Path::new(""), output, true, true);
+        assert_eq!(listing.files.len(), 2);
+        assert_eq!(listing.files[0].path.as_bytes(), b"data/\xe9t\xe9.csv");
+        assert_eq!(listing.files[0].size, 100);
+        assert_eq!(listing.files[0].path.parent(), Some("data".into()));
+        assert_eq!(listing.files[1].path.as_bytes(), b"data\\raw.csv");
+        assert_eq!(listing.files[1].object_id, "4567abcd");
+    }
+
+    #[test]
+    fn test_parse_entry_kinds() {
+        let output = b"100644 blob 01\ta.csv\0\
+                       100755 blob 02\trun.sh\0\
+                       120000 blob 03\tlatest\0\
+                       160000 commit 04\tvendor/lib\0\
+                       040000 tree 05\tdata\0";
+        let listing = GitTreeListing::parse(Path::new(""), output, false, false);
+        let kinds: Vec<_> = listing.files.iter().map(|e| e.kind()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                GitTreeEntryKind::File,
+                GitTreeEntryKind::Executable,
+                GitTreeEntryKind::Symlink
+            ]
+        );
+        assert!(kinds[1].is_file());
+        assert!(!kinds[2].is_file());
+        assert_eq!(listing.submodules.len(), 1);
+        assert_eq!(listing.submodules[0].path, "vendor/lib".into());
+        assert_eq!(listing.submodules[0].kind(), GitTreeEntryKind::Submodule);
+        assert_eq!(
+            listing.sub_directories[0].kind(),
+            GitTreeEntryKind::Directory
+        );
+
+        // Submodules are listed even when only files are.
+        let listing = GitTreeListing::parse(Path::new(""), output, true, false);
+        assert_eq!(listing.submodules.len(), 1);
+        assert!(listing.sub_directories.is_empty());
+    }
 }
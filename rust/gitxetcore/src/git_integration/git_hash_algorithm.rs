@@ -0,0 +1,140 @@
+use super::git_notes_wrapper::GitNotesWrapper;
+use crate::config::XetConfig;
+use crate::constants::*;
+use crate::errors::{GitXetRepoError, Result};
+use crate::git_integration::git_repo_plumbing::open_libgit2_repo;
+use git2::Repository;
+use merklehash::HashAlgorithm;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::info;
+
+pub fn read_hash_algorithm_by_dir(git_dir: &Path, config: &XetConfig) -> Result<HashAlgorithm> {
+    let Ok(repo) = open_libgit2_repo(Some(git_dir)).map_err(|e| {
+        info!("Error opening {git_dir:?} as git repository; error = {e:?}.");
+        e
+    }) else {
+        return Ok(HashAlgorithm::default());
+    };
+
+    read_hash_algorithm(repo, config)
+}
+
+/// Reads the hash algorithm of the repository of config, or the default one
+/// outside of a repository.
+pub fn read_hash_algorithm_from_config(config: &XetConfig) -> Result<HashAlgorithm> {
+    match config.repo_path_if_present.as_ref() {
+        Some(path) => read_hash_algorithm_by_dir(path, config),
+        None => Ok(HashAlgorithm::default()),
+    }
+}
+
+// Read the hash algorithm from the one note in the notesref. Repositories
+// without the notesref use the default algorithm.
+pub fn read_hash_algorithm(repo: Arc<Repository>, config: &XetConfig) -> Result<HashAlgorithm> {
+    let notesref = GIT_NOTES_HASH_ALGORITHM_REF_NAME;
+
+    if repo.find_reference(notesref).is_err() {
+        return Ok(HashAlgorithm::default());
+    }
+
+    let notes_wrapper = GitNotesWrapper::from_repo(repo, config, notesref)?;
+    let mut iter = notes_wrapper.notes_content_iterator()?;
+    let Some((_, data)) = iter.next() else {
+        info!("{notesref} present but empty; using the default hash algorithm.");
+        return Ok(HashAlgorithm::default());
+    };
+
+    if iter.count() != 0 {
+        return Err(GitXetRepoError::Other(
+            "Repository Error: Found more than one hash algorithm.".to_owned(),
+        ));
+    }
+
+    let name = String::from_utf8_lossy(&data);
+    name.parse().map_err(|_| {
+        GitXetRepoError::HashAlgorithmUnavailable(format!(
+            "The repository hashes with {name}, which this version of git-xet doesn't support; \
+             please upgrade"
+        ))
+    })
+}
+
+/// Picks the hash algorithm of a new repository: the one requested, if the
+/// remote accepts it, or otherwise the remote's preference. accepted lists
+/// the algorithms the remote accepts for the repository, most preferred
+/// first; remotes that predate the choice of algorithm list none, and only
+/// accept the default one.
+pub fn negotiate_hash_algorithm(
+    requested: Option<HashAlgorithm>,
+    accepted: &[String],
+) -> Result<HashAlgorithm> {
+    if accepted.is_empty() {
+        return match requested {
+            None | Some(HashAlgorithm::Xet) => Ok(HashAlgorithm::Xet),
+            Some(a) => Err(GitXetRepoError::HashAlgorithmUnavailable(format!(
+                "The remote only accepts the xet hash algorithm, not {a}"
+            ))),
+        };
+    }
+
+    // Algorithms newer than this client are skipped.
+    let known: Vec<HashAlgorithm> = accepted.iter().filter_map(|a| a.parse().ok()).collect();
+    match requested {
+        Some(a) if known.contains(&a) => Ok(a),
+        Some(a) => Err(GitXetRepoError::HashAlgorithmUnavailable(format!(
+            "The remote doesn't accept the {a} hash algorithm; it accepts {}",
+            accepted.join(", ")
+        ))),
+        None => known.first().copied().ok_or_else(|| {
+            GitXetRepoError::HashAlgorithmUnavailable(format!(
+                "The remote requires one of the hash algorithms {}, which this version \
+                 of git-xet doesn't support; please upgrade",
+                accepted.join(", ")
+            ))
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_hash_algorithm() {
+        let accepted = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        // Remotes that predate negotiation.
+        assert_eq!(
+            negotiate_hash_algorithm(None, &[]).unwrap(),
+            HashAlgorithm::Xet
+        );
+        assert!(negotiate_hash_algorithm(Some(HashAlgorithm::Sha256), &[]).is_err());
+
+        // A remote mandating SHA-256.
+        let fips = accepted(&["sha256"]);
+        assert_eq!(
+            negotiate_hash_algorithm(None, &fips).unwrap(),
+            HashAlgorithm::Sha256
+        );
+        assert!(negotiate_hash_algorithm(Some(HashAlgorithm::Xet), &fips).is_err());
+
+        let any = accepted(&["xet", "blake3", "sha256"]);
+        assert_eq!(
+            negotiate_hash_algorithm(None, &any).unwrap(),
+            HashAlgorithm::Xet
+        );
+        assert_eq!(
+            negotiate_hash_algorithm(Some(HashAlgorithm::Blake3), &any).unwrap(),
+            HashAlgorithm::Blake3
+        );
+
+        // Algorithms this client doesn't know are skipped.
+        let newer = accepted(&["sha3", "sha256"]);
+        assert_eq!(
+            negotiate_hash_algorithm(None, &newer).unwrap(),
+            HashAlgorithm::Sha256
+        );
+        assert!(negotiate_hash_algorithm(None, &accepted(&["sha3"])).is_err());
+    }
+}
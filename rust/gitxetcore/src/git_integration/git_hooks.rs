@@ -0,0 +1,506 @@
+//! The git hooks installed by git-xet.
+//!
+//! Each hook is a script of its own, marked with the version of the hook
+//! scripts so that stale ones are found and upgraded. A hook the repository
+//! already had is not overwritten: it is moved aside to `<hook>.pre-xet`,
+//! keeping its mode, and the xet hook runs it first if it is executable,
+//! with the same arguments and input, stopping if it fails. Uninstalling
+//! moves it back.
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+use is_executable::IsExecutable;
+use mdb_shard::shard_version::ShardVersion;
+use tracing::{error, info};
+
+use crate::errors::{GitXetRepoError, Result};
+
+use super::git_xet_repo::file_content_contains_lock;
+
+/// The version of the hook scripts, bumped whenever they change so that
+/// installed hooks get upgraded.
+pub const HOOK_SCRIPT_VERSION: u32 = 2;
+
+const VERSION_MARKER: &str = "# git-xet hook version ";
+
+/// The suffix of a hook moved aside to install the xet hook, run by it.
+pub const CHAINED_HOOK_SUFFIX: &str = ".pre-xet";
+
+/// In every line of a hook calling git-xet. Before the hook scripts were
+/// versioned, these lines were appended to the existing hooks.
+const XET_HOOK_CALL: &str = "git-xet hooks";
+
+const PREPUSH_HOOK_CONTENT: &str =
+    "git-xet hooks pre-push-hook --remote \"$1\" --remote-loc \"$2\"\n";
+
+const REFERENCE_TRANSACTION_HOOK_CONTENT_MDB_V1: &str =
+    "git-xet hooks reference-transaction-hook --action \"$1\"\n";
+const REFERENCE_TRANSACTION_HOOK_CONTENT_MDB_V2: &str =
+    "[[ ! -z \"$XET_DISABLE_HOOKS\" ]] || git-xet hooks reference-transaction-hook --action \"$1\"\n";
+
+const POSTCHECKOUT_HOOK_CONTENT: &str =
+    "git-xet hooks post-checkout-hook --previous \"$1\" --new \"$2\" --flag \"$3\"\n";
+const POSTMERGE_HOOK_CONTENT: &str = "git-xet hooks post-merge-hook --flag \"$1\"\n";
+const POSTCOMMIT_HOOK_CONTENT: &str = "git-xet hooks post-commit-hook\n";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct XetHook {
+    /// The name of the hook, e.g. pre-push.
+    pub name: &'static str,
+    /// The git-xet command the hook runs.
+    pub command: &'static str,
+    /// Only installed with --enable-locking.
+    pub locking: bool,
+}
+
+impl XetHook {
+    /// The full script of the hook.
+    pub fn script(&self) -> String {
+        format!(
+            r#"#!/usr/bin/env bash
+{VERSION_MARKER}{HOOK_SCRIPT_VERSION}; managed by `git xet hooks install`.
+# The hook this one replaced, if any, is at {name}{CHAINED_HOOK_SUFFIX} and is run first.
+chained="$0{CHAINED_HOOK_SUFFIX}"
+if [[ -x "$chained" ]]; then
+    input="$(mktemp)"
+    trap 'rm -f "$input"' EXIT
+    cat > "$input"
+    "$chained" "$@" < "$input" || exit $?
+    exec < "$input"
+fi
+{command}"#,
+            name = self.name,
+            command = self.command
+        )
+    }
+}
+
+/// All the hooks git-xet installs.
+pub fn xet_hooks(mdb_version: ShardVersion) -> Vec<XetHook> {
+    vec![
+        XetHook {
+            name: "pre-push",
+            command: PREPUSH_HOOK_CONTENT,
+            locking: false,
+        },
+        XetHook {
+            name: "reference-transaction",
+            command: match mdb_version {
+                ShardVersion::V1 => REFERENCE_TRANSACTION_HOOK_CONTENT_MDB_V1,
+                ShardVersion::V2 | ShardVersion::Uninitialized => {
+                    REFERENCE_TRANSACTION_HOOK_CONTENT_MDB_V2
+                }
+            },
+            locking: false,
+        },
+        XetHook {
+            name: "post-checkout",
+            command: POSTCHECKOUT_HOOK_CONTENT,
+            locking: true,
+        },
+        XetHook {
+            name: "post-merge",
+            command: POSTMERGE_HOOK_CONTENT,
+            locking: true,
+        },
+        XetHook {
+            name: "post-commit",
+            command: POSTCOMMIT_HOOK_CONTENT,
+            locking: true,
+        },
+    ]
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HookState {
+    /// The current script of the hook.
+    Current,
+    /// An older script, or one modified since it was installed.
+    Stale,
+    /// No hook, or one not calling git-xet.
+    NotInstalled,
+    /// Locked by a "# XET LOCK" comment; left as it is.
+    Locked,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HookStatus {
+    pub name: &'static str,
+    pub state: HookState,
+    /// The version of the installed script; None for scripts from before
+    /// versioning, or hooks not installed.
+    pub version: Option<u32>,
+    /// Whether a hook moved aside is run by the xet hook.
+    pub chained: bool,
+}
+
+impl fmt::Display for HookStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<22}", self.name)?;
+        match (self.state, self.version) {
+            (HookState::Current, _) => write!(f, "installed")?,
+            (HookState::Stale, Some(v)) => write!(f, "stale (version {v})")?,
+            (HookState::Stale, None) => write!(f, "stale (unversioned)")?,
+            (HookState::NotInstalled, _) => write!(f, "not installed")?,
+            (HookState::Locked, _) => write!(f, "locked by # XET LOCK")?,
+        }
+        if self.chained {
+            write!(f, "; runs {}{CHAINED_HOOK_SUFFIX} first", self.name)?;
+        }
+        Ok(())
+    }
+}
+
+fn chained_hook_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(CHAINED_HOOK_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// The version of a hook script, None if it isn't versioned.
+fn script_version(content: &str) -> Option<u32> {
+    content.lines().find_map(|line| {
+        let version = line.strip_prefix(VERSION_MARKER)?;
+        let end = version
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(version.len());
+        version[..end].parse().ok()
+    })
+}
+
+/// A hook from before versioning with the lines calling git-xet removed;
+/// None if only comments are left.
+fn strip_xet_calls(content: &str) -> Option<String> {
+    let lines: Vec<&str> = content
+        .lines()
+        .filter(|line| !line.contains(XET_HOOK_CALL))
+        .collect();
+    let has_commands = lines
+        .iter()
+        .any(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'));
+    has_commands.then(|| lines.join("\n") + "\n")
+}
+
+fn read_hook(path: &Path) -> Result<Option<String>> {
+    match fs::read(path) {
+        Ok(content) => Ok(Some(String::from_utf8_lossy(&content).into_owned())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    path.is_executable()
+}
+
+// Windows file systems have no executable permission.
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    true
+}
+
+/// Makes the file at path executable; returns whether it wasn't.
+fn set_execute_permission(path: &Path) -> Result<bool> {
+    if is_executable(path) {
+        return Ok(false);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::prelude::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(true)
+}
+
+/// The state of a hook in hooks_dir.
+pub fn hook_status(hooks_dir: &Path, hook: &XetHook) -> Result<HookStatus> {
+    let path = hooks_dir.join(hook.name);
+    let content = read_hook(&path)?;
+    let state = match &content {
+        None => HookState::NotInstalled,
+        Some(content) if file_content_contains_lock(content) => HookState::Locked,
+        Some(content) if *content == hook.script() && is_executable(&path) => HookState::Current,
+        Some(content) if content.contains(XET_HOOK_CALL) => HookState::Stale,
+        Some(_) => HookState::NotInstalled,
+    };
+    Ok(HookStatus {
+        name: hook.name,
+        state,
+        version: content.as_deref().and_then(script_version),
+        chained: is_chained(&chained_hook_path(&path)),
+    })
+}
+
+/// Whether the hook moved aside to chained_path is run by the xet hook, which
+/// like git only runs executable hooks.
+fn is_chained(chained_path: &Path) -> bool {
+    chained_path.exists() && is_executable(chained_path)
+}
+
+/// Installs hook in hooks_dir, or upgrades it if stale. A hook already there
+/// is moved aside, and chained if executable. Returns whether anything
+/// changed.
+pub fn install_hook(hooks_dir: &Path, hook: &XetHook) -> Result<bool> {
+    let path = hooks_dir.join(hook.name);
+    let chained_path = chained_hook_path(&path);
+    let script = hook.script();
+
+    match read_hook(&path)? {
+        None => fs::create_dir_all(hooks_dir)?,
+        Some(content) if file_content_contains_lock(&content) => {
+            info!("Hook {:?} contains locking text, not modifying.", hook.name);
+            return Ok(false);
+        }
+        Some(content) if content == script => {
+            return set_execute_permission(&path);
+        }
+        // An older xet hook; the hook it chains, if any, stays.
+        Some(content) if script_version(&content).is_some() => {}
+        Some(content) => {
+            // The existing hook, less the calls appended by older versions.
+            let has_xet_calls = content.contains(XET_HOOK_CALL);
+            let user_hook = if has_xet_calls {
+                strip_xet_calls(&content)
+            } else {
+                Some(content)
+            };
+            if let Some(user_hook) = user_hook {
+                if chained_path.exists() {
+                    return Err(GitXetRepoError::InvalidOperation(format!(
+                        "Unable to install the git-xet {} hook, as {:?} already exists; move it elsewhere and rerun git xet hooks install.",
+                        hook.name, &chained_path
+                    )));
+                }
+                info!(
+                    "XET: moving the existing {} hook to {:?}, to be run by the xet hook.",
+                    hook.name, &chained_path
+                );
+                if has_xet_calls {
+                    let permissions = fs::metadata(&path)?.permissions();
+                    fs::write(&chained_path, user_hook)?;
+                    fs::set_permissions(&chained_path, permissions)?;
+                } else {
+                    // Moved rather than rewritten, keeping hooks that are
+                    // not text intact.
+                    fs::rename(&path, &chained_path)?;
+                }
+                if !is_chained(&chained_path) {
+                    info!(
+                        "XET: {:?} is not executable, so git did not run it; the xet hook won't either.",
+                        &chained_path
+                    );
+                }
+            }
+        }
+    }
+
+    info!("XET: writing the {} hook.", hook.name);
+    fs::write(&path, script)?;
+    set_execute_permission(&path)?;
+    Ok(true)
+}
+
+/// Removes hook from hooks_dir, moving back the hook it chains. A hook from
+/// before versioning only loses the lines calling git-xet. Returns false if
+/// the hook could not be uninstalled.
+pub fn uninstall_hook(hooks_dir: &Path, hook: &XetHook, ignore_locks: bool) -> Result<bool> {
+    let path = hooks_dir.join(hook.name);
+    let chained_path = chained_hook_path(&path);
+
+    let content = match read_hook(&path) {
+        Ok(Some(content)) => content,
+        Ok(None) => return Ok(true),
+        Err(e) => {
+            error!(
+                "Unable to open {:?} to clear git-xet hooks; skipping. (Error: {:?})",
+                &path, &e
+            );
+            return Ok(false);
+        }
+    };
+
+    if !ignore_locks && file_content_contains_lock(&content) {
+        error!("Skipping {:?} in uninstall due to file locking.  Either rerun with --ignore-locks, or remove # XET LOCK from the file.", &path);
+        return Ok(false);
+    }
+    if !content.contains(XET_HOOK_CALL) {
+        return Ok(true);
+    }
+
+    let remaining = if script_version(&content).is_some() {
+        None
+    } else {
+        strip_xet_calls(&content)
+    };
+    let res = match remaining {
+        Some(remaining) => fs::write(&path, remaining),
+        None if chained_path.exists() => fs::rename(&chained_path, &path),
+        None => fs::remove_file(&path),
+    };
+    if let Err(e) = res {
+        error!(
+            "Error attempting to remove hook file at {:?}, skipping (Error={:?})",
+            &path, &e
+        );
+        return Ok(false);
+    }
+    info!("Removed git-xet hook at {:?}", &path);
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn pre_push() -> XetHook {
+        xet_hooks(ShardVersion::V2)[0]
+    }
+
+    #[test]
+    fn test_install_and_uninstall() {
+        let dir = TempDir::new().unwrap();
+        let hook = pre_push();
+        assert_eq!(
+            hook_status(dir.path(), &hook).unwrap().state,
+            HookState::NotInstalled
+        );
+
+        assert!(install_hook(dir.path(), &hook).unwrap());
+        let status = hook_status(dir.path(), &hook).unwrap();
+        assert_eq!(status.state, HookState::Current);
+        assert_eq!(status.version, Some(HOOK_SCRIPT_VERSION));
+        assert!(!status.chained);
+        // Installing again changes nothing.
+        assert!(!install_hook(dir.path(), &hook).unwrap());
+
+        assert!(uninstall_hook(dir.path(), &hook, false).unwrap());
+        assert!(!dir.path().join("pre-push").exists());
+    }
+
+    #[test]
+    fn test_chain_existing_hook() {
+        let dir = TempDir::new().unwrap();
+        let hook = pre_push();
+        let user_hook = "#!/bin/sh\nmake lint\n";
+        fs::write(dir.path().join("pre-push"), user_hook).unwrap();
+        set_execute_permission(&dir.path().join("pre-push")).unwrap();
+
+        assert!(install_hook(dir.path(), &hook).unwrap());
+        let status = hook_status(dir.path(), &hook).unwrap();
+        assert_eq!(status.state, HookState::Current);
+        assert!(status.chained);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("pre-push.pre-xet")).unwrap(),
+            user_hook
+        );
+
+        // Uninstalling puts the user's hook back.
+        assert!(uninstall_hook(dir.path(), &hook, false).unwrap());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("pre-push")).unwrap(),
+            user_hook
+        );
+        assert!(!dir.path().join("pre-push.pre-xet").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_executable_hook_not_chained() {
+        use std::os::unix::prelude::PermissionsExt;
+
+        let dir = TempDir::new().unwrap();
+        let hook = pre_push();
+        let path = dir.path().join("pre-push");
+        fs::write(&path, "#!/bin/sh\nmake lint\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        assert!(install_hook(dir.path(), &hook).unwrap());
+        let status = hook_status(dir.path(), &hook).unwrap();
+        assert_eq!(status.state, HookState::Current);
+        assert!(!status.chained);
+        let chained = fs::metadata(dir.path().join("pre-push.pre-xet")).unwrap();
+        assert_eq!(chained.permissions().mode() & 0o777, 0o640);
+
+        // Uninstalling puts it back as it was.
+        assert!(uninstall_hook(dir.path(), &hook, false).unwrap());
+        let restored = fs::metadata(&path).unwrap();
+        assert_eq!(restored.permissions().mode() & 0o777, 0o640);
+    }
+
+    #[test]
+    fn test_upgrade_stale_hooks() {
+        let dir = TempDir::new().unwrap();
+        let hook = pre_push();
+        let path = dir.path().join("pre-push");
+
+        // A hook from before versioning, appended to the user's hook.
+        fs::write(
+            &path,
+            format!("#!/usr/bin/env bash\necho checking\n{PREPUSH_HOOK_CONTENT}"),
+        )
+        .unwrap();
+        set_execute_permission(&path).unwrap();
+        let status = hook_status(dir.path(), &hook).unwrap();
+        assert_eq!(status.state, HookState::Stale);
+        assert_eq!(status.version, None);
+
+        assert!(install_hook(dir.path(), &hook).unwrap());
+        assert_eq!(
+            hook_status(dir.path(), &hook).unwrap().state,
+            HookState::Current
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("pre-push.pre-xet")).unwrap(),
+            "#!/usr/bin/env bash\necho checking\n"
+        );
+
+        // An older versioned script keeps its chained hook.
+        fs::write(
+            &path,
+            format!("#!/usr/bin/env bash\n{VERSION_MARKER}1\n{PREPUSH_HOOK_CONTENT}"),
+        )
+        .unwrap();
+        let status = hook_status(dir.path(), &hook).unwrap();
+        assert_eq!(status.state, HookState::Stale);
+        assert_eq!(status.version, Some(1));
+        assert!(install_hook(dir.path(), &hook).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), hook.script());
+        assert!(hook_status(dir.path(), &hook).unwrap().chained);
+
+        // A hook from before versioning with nothing else is just removed.
+        fs::write(
+            &path,
+            format!("#!/usr/bin/env bash\n{PREPUSH_HOOK_CONTENT}"),
+        )
+        .unwrap();
+        fs::remove_file(dir.path().join("pre-push.pre-xet")).unwrap();
+        assert!(uninstall_hook(dir.path(), &hook, false).unwrap());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_locked_hook() {
+        let dir = TempDir::new().unwrap();
+        let hook = pre_push();
+        let content = format!("#!/usr/bin/env bash\n# XET LOCK\n{PREPUSH_HOOK_CONTENT}");
+        fs::write(dir.path().join("pre-push"), &content).unwrap();
+
+        assert_eq!(
+            hook_status(dir.path(), &hook).unwrap().state,
+            HookState::Locked
+        );
+        assert!(!install_hook(dir.path(), &hook).unwrap());
+        assert!(!uninstall_hook(dir.path(), &hook, false).unwrap());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("pre-push")).unwrap(),
+            content
+        );
+        assert!(uninstall_hook(dir.path(), &hook, true).unwrap());
+        assert!(!dir.path().join("pre-push").exists());
+    }
+}
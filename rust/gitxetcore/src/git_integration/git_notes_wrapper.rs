@@ -1,6 +1,6 @@
 use base64;
 
-use git2::{Repository, Signature};
+use git2::{Oid, Repository, Signature, Tree};
 use std::sync::Arc;
 
 use std::path::Path;
@@ -8,9 +8,12 @@ use tracing::error;
 
 use crate::config::XetConfig;
 
+use super::git_ref_updates::update_ref;
 use super::git_repo_plumbing::open_libgit2_repo;
 use super::git_user_config::get_repo_signature;
 
+const NOTES_MESSAGE: &str = "Notes added by 'git xet'";
+
 pub struct GitNotesWrapper {
     repo: Arc<Repository>,
     notes_ref: String,
@@ -57,6 +60,12 @@ pub struct GitNotesWrapper {
 ///    create note mapping blob_oid -> escape(blob_oid)
 /// }
 ///
+/// As the note of a blob is the blob itself, adding a note twice, or the
+/// same note in two processes, is harmless. Other processes may update the
+/// notes ref while a note is added; the ref is only moved from the commit
+/// the note was added to, and the note added again on top of theirs
+/// otherwise, so that neither note is lost.
+///
 ///
 /// iterator() {
 ///    for each note (annotated_id, note_oid) {
@@ -185,24 +194,27 @@ impl GitNotesWrapper {
     pub fn add_note<T: AsRef<[u8]>>(&self, content: T) -> Result<(), git2::Error> {
         let content_str = base64::encode(content.as_ref());
         let blob_oid = self.repo.blob(content_str.as_bytes())?;
-        let note_ok = self.repo.note(
-            &self.write_signature,
-            &self.write_signature,
-            Some(&self.notes_ref),
-            blob_oid,
-            &content_str,
-            false,
-        );
-        match note_ok {
-            Ok(_) => Ok(()),
-            Err(e) => {
-                if e.code() == git2::ErrorCode::Exists {
-                    Ok(())
-                } else {
-                    Err(e)
-                }
-            }
-        }
+        update_ref(&self.repo, &self.notes_ref, NOTES_MESSAGE, |current| {
+            let parent = current.map(|id| self.repo.find_commit(id)).transpose()?;
+            let tree = parent.as_ref().map(|c| c.tree()).transpose()?;
+            let Some(new_tree) =
+                insert_note(&self.repo, tree.as_ref(), &blob_oid.to_string(), blob_oid)?
+            else {
+                return Ok(None);
+            };
+            let parents: Vec<_> = parent.iter().collect();
+            self.repo
+                .commit(
+                    None,
+                    &self.write_signature,
+                    &self.write_signature,
+                    NOTES_MESSAGE,
+                    &self.repo.find_tree(new_tree)?,
+                    &parents,
+                )
+                .map(Some)
+        })?;
+        Ok(())
     }
 
     /// Checks if a certain note already exists.
@@ -213,3 +225,109 @@ impl GitNotesWrapper {
         Ok(self.repo.find_note(Some(&self.notes_ref), blob_oid).is_ok())
     }
 }
+
+/// The notes tree with the note of the object named name added, None if
+/// the object already has a note. Large notes trees fan out into subtrees
+/// named by the first two hex digits of the objects; as git and libgit2 do,
+/// a note goes into the subtree for its object if there is one.
+fn insert_note(
+    repo: &Repository,
+    tree: Option<&Tree>,
+    name: &str,
+    note: Oid,
+) -> Result<Option<Oid>, git2::Error> {
+    if let Some(tree) = tree {
+        if tree.get_name(name).is_some() {
+            return Ok(None);
+        }
+        let fanout = &name[..2];
+        if let Some(entry) = tree
+            .get_name(fanout)
+            .filter(|e| e.kind() == Some(git2::ObjectType::Tree))
+        {
+            let subtree = repo.find_tree(entry.id())?;
+            let Some(new_subtree) = insert_note(repo, Some(&subtree), &name[2..], note)? else {
+                return Ok(None);
+            };
+            let mut builder = repo.treebuilder(Some(tree))?;
+            builder.insert(fanout, new_subtree, git2::FileMode::Tree.into())?;
+            return builder.write().map(Some);
+        }
+    }
+    let mut builder = repo.treebuilder(tree)?;
+    builder.insert(name, note, git2::FileMode::Blob.into())?;
+    builder.write().map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_add_note() {
+        let dir = TempDir::new().unwrap();
+        let repo = Arc::new(Repository::init(dir.path()).unwrap());
+        let notes =
+            GitNotesWrapper::from_repo(repo.clone(), &XetConfig::empty(), "refs/notes/xet/test")
+                .unwrap();
+
+        notes.add_note(b"first").unwrap();
+        let head = repo.refname_to_id("refs/notes/xet/test").unwrap();
+        // Adding the same note again writes nothing.
+        notes.add_note(b"first").unwrap();
+        assert_eq!(repo.refname_to_id("refs/notes/xet/test").unwrap(), head);
+
+        // Notes added with git are kept, fanned out or not.
+        let sig = Signature::now("test", "test@example.com").unwrap();
+        let other = repo.blob(base64::encode(b"other").as_bytes()).unwrap();
+        repo.note(
+            &sig,
+            &sig,
+            Some("refs/notes/xet/test"),
+            other,
+            &base64::encode(b"other"),
+            false,
+        )
+        .unwrap();
+        notes.add_note(b"second").unwrap();
+
+        let mut contents: Vec<Vec<u8>> = notes
+            .notes_content_iterator()
+            .unwrap()
+            .map(|(_, c)| c)
+            .collect();
+        contents.sort();
+        assert_eq!(
+            contents,
+            vec![b"first".to_vec(), b"other".to_vec(), b"second".to_vec()]
+        );
+        assert!(notes.find_note(b"second").unwrap());
+    }
+
+    #[test]
+    fn test_insert_note_fanout() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let blob = repo.blob(b"note").unwrap();
+        let name = blob.to_string();
+
+        // A tree fanned out into the subtree for the object.
+        let subtree = repo.treebuilder(None).unwrap().write().unwrap();
+        let mut builder = repo.treebuilder(None).unwrap();
+        builder
+            .insert(&name[..2], subtree, git2::FileMode::Tree.into())
+            .unwrap();
+        let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+
+        let new_tree = insert_note(&repo, Some(&tree), &name, blob)
+            .unwrap()
+            .unwrap();
+        let new_tree = repo.find_tree(new_tree).unwrap();
+        let path = format!("{}/{}", &name[..2], &name[2..]);
+        assert_eq!(new_tree.get_path(Path::new(&path)).unwrap().id(), blob);
+        assert!(insert_note(&repo, Some(&new_tree), &name, blob)
+            .unwrap()
+            .is_none());
+    }
+}
@@ -38,6 +38,11 @@ fn spawn_git_command(
 
     let mut cmd = Command::new(git_executable);
 
+    // Paths deeper than MAX_PATH, as in deep dataset trees, fail on Windows
+    // unless git is told to use long paths.
+    #[cfg(windows)]
+    cmd.args(["-c", "core.longpaths=true"]);
+
     cmd.arg(command).args(args);
     if let Some(env) = env {
         for (k, v) in env.iter() {
@@ -130,10 +135,19 @@ pub fn run_git_captured_raw(
             let res_stderr = std::str::from_utf8(&ret.stderr[..])
                 .unwrap_or("<Binary Data>")
                 .trim();
-            Err(GitXetRepoError::Other(format!(
-                "Error running git command: git {:?} {:?} err_code={:?}, stdout=\"{:?}\", stderr=\"{:?}\"",
-                &command, args.iter().map(|s| format!("\"{s}\"")).join(" "), &ret.status, &res_stdout, &res_stderr
-            )))
+            if is_ref_lock_failure(res_stderr) {
+                Err(GitXetRepoError::RefLocked {
+                    command: command_line(command, args),
+                    stderr: res_stderr.to_owned(),
+                })
+            } else {
+                Err(GitXetRepoError::GitCommandFailed {
+                    command: command_line(command, args),
+                    status: ret.status.code(),
+                    stdout: res_stdout.to_owned(),
+                    stderr: res_stderr.to_owned(),
+                })
+            }
         }
     } else {
         Ok(ret)
@@ -207,20 +221,74 @@ pub fn run_git_passthrough(
         Some(0) => Ok(0),
         Some(r) => {
             if check_result {
-                Err(GitXetRepoError::Other(format!(
-                    "git command returned non-zero exit code: git {:?} {:?} err_code={:?}",
-                    &command,
-                    args.iter().map(|s| format!("\"{s}\"")).join(" "),
-                    &ret
-                )))
+                // The output went to the console, so it isn't in the error.
+                Err(GitXetRepoError::GitCommandFailed {
+                    command: command_line(command, args),
+                    status: ret,
+                    stdout: String::new(),
+                    stderr: String::new(),
+                })
             } else {
                 Ok(r)
             }
         }
-        _ => Err(GitXetRepoError::Other(format!(
-            "Unknown error running git command: git {:?} {:?}",
-            &command,
-            args.iter().map(|s| format!("\"{s}\"")).join(" ")
-        ))),
+        // Killed by a signal.
+        _ => Err(GitXetRepoError::GitCommandFailed {
+            command: command_line(command, args),
+            status: None,
+            stdout: String::new(),
+            stderr: String::new(),
+        }),
+    }
+}
+
+/// The command and its quoted arguments, for error messages.
+fn command_line(command: &str, args: &[&str]) -> String {
+    format!(
+        "{command} {}",
+        args.iter().map(|s| format!("\"{s}\"")).join(" ")
+    )
+}
+
+/// Whether the error output of git says it could not lock a ref.
+fn is_ref_lock_failure(stderr: &str) -> bool {
+    stderr.contains("cannot lock ref")
+        || stderr.contains("unable to lock")
+        || (stderr.contains(".lock'") && stderr.contains("File exists"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_ref_lock_failure_classified() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().to_path_buf();
+        run_git_captured(Some(&path), "init", &[], true, None).unwrap();
+        run_git_captured(
+            Some(&path),
+            "commit",
+            &["--allow-empty", "-m", "first", "--author", "t <t@t>"],
+            true,
+            Some(&[("GIT_COMMITTER_NAME", "t"), ("GIT_COMMITTER_EMAIL", "t@t")][..]),
+        )
+        .unwrap();
+
+        let err =
+            run_git_captured(Some(&path), "rev-parse", &["no-such-rev"], true, None).unwrap_err();
+        assert!(matches!(err, GitXetRepoError::GitCommandFailed { .. }));
+
+        std::fs::write(path.join(".git/refs/heads/locked.lock"), "").unwrap();
+        let err = run_git_captured(
+            Some(&path),
+            "update-ref",
+            &["refs/heads/locked", "HEAD"],
+            true,
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(err, GitXetRepoError::RefLocked { .. }), "{err}");
     }
 }
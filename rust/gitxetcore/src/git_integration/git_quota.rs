@@ -0,0 +1,78 @@
+use git2::Repository;
+use utils::output_bytes::output_bytes;
+
+use crate::xetblob::RepoQuota;
+
+/// Returns the URL of the named remote, or remote itself if it is not the
+/// name of a remote (git passes a URL to the pre-push hook when pushing to
+/// one directly).
+pub fn resolve_remote_url(repo: &Repository, remote: &str) -> String {
+    repo.find_remote(remote)
+        .ok()
+        .and_then(|r| r.url().map(|u| u.to_string()))
+        .unwrap_or_else(|| remote.to_string())
+}
+
+/// Describes each limit of the quota that uploading upload_bytes would
+/// exceed. Uploads count towards both storage and bandwidth.
+pub fn quota_violations(quota: &RepoQuota, upload_bytes: u64) -> Vec<String> {
+    let mut violations = Vec::new();
+    if let Some(limit) = quota.storage_limit {
+        let after = quota.storage_used.saturating_add(upload_bytes);
+        if after > limit {
+            violations.push(format!(
+                "pushing {} would bring storage to {}, over the limit of {}",
+                output_bytes(upload_bytes as usize),
+                output_bytes(after as usize),
+                output_bytes(limit as usize)
+            ));
+        }
+    }
+    if let Some(limit) = quota.bandwidth_limit {
+        let after = quota.bandwidth_used.saturating_add(upload_bytes);
+        if after > limit {
+            violations.push(format!(
+                "pushing {} would bring bandwidth to {}, over the limit of {}",
+                output_bytes(upload_bytes as usize),
+                output_bytes(after as usize),
+                output_bytes(limit as usize)
+            ));
+        }
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quota_violations() {
+        let quota = RepoQuota {
+            storage_used: 900,
+            storage_limit: Some(1000),
+            bandwidth_used: 0,
+            bandwidth_limit: None,
+        };
+        assert!(quota_violations(&quota, 100).is_empty());
+        assert_eq!(quota_violations(&quota, 101).len(), 1);
+
+        let quota = RepoQuota {
+            bandwidth_used: 1000,
+            bandwidth_limit: Some(1000),
+            ..quota
+        };
+        assert_eq!(quota_violations(&quota, 101).len(), 2);
+        assert!(quota_violations(&RepoQuota::default(), u32::MAX as u64).is_empty());
+
+        // Usage reported near u64::MAX doesn't overflow.
+        let quota = RepoQuota {
+            storage_used: u64::MAX - 10,
+            storage_limit: Some(1000),
+            bandwidth_used: u64::MAX,
+            bandwidth_limit: Some(u64::MAX),
+        };
+        assert_eq!(quota_violations(&quota, 100).len(), 1);
+        assert_eq!(quota_violations(&quota, u64::MAX).len(), 1);
+    }
+}
@@ -0,0 +1,199 @@
+//! Updating refs while other git processes may be updating them too.
+//!
+//! Hooks run alongside other git processes: a fetch or a hook in another
+//! worktree may write the same notes ref at the same time. An update is
+//! computed from the value of the ref, and only written if the ref still has
+//! that value, as a compare-and-swap; if it changed, or another process holds
+//! its lock, the update is computed again from the new value and retried.
+//! Updates are idempotent: one with nothing to change writes nothing.
+use std::time::Duration;
+
+use git2::{ErrorClass, ErrorCode, Oid, Repository};
+use tracing::info;
+
+use crate::errors::{GitXetRepoError, Result};
+
+/// The most times an update is attempted before giving up.
+const MAX_REF_UPDATE_ATTEMPTS: u32 = 20;
+const INITIAL_RETRY_TIME_MS: u64 = 10;
+const MAX_RETRY_TIME_MS: u64 = 1000;
+
+/// Points refname at the commit update returns, computed from the commit
+/// refname points at, None if it does not exist. If update returns None,
+/// there is nothing to change. Returns whether refname was updated.
+pub fn update_ref(
+    repo: &Repository,
+    refname: &str,
+    log_message: &str,
+    mut update: impl FnMut(Option<Oid>) -> std::result::Result<Option<Oid>, git2::Error>,
+) -> std::result::Result<bool, git2::Error> {
+    let mut backoff = Backoff::default();
+    loop {
+        let current = match repo.refname_to_id(refname) {
+            Ok(id) => Some(id),
+            Err(e) if e.code() == ErrorCode::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        let Some(new) = update(current)? else {
+            return Ok(false);
+        };
+
+        let written = match current {
+            Some(current) => repo.reference_matching(refname, new, true, current, log_message),
+            // Not forced, so that a ref created meanwhile is not overwritten.
+            None => repo.reference(refname, new, false, log_message),
+        };
+        match written {
+            Ok(_) => return Ok(true),
+            Err(e) if is_concurrent_update(&e) => {
+                if !backoff.wait() {
+                    return Err(git2::Error::new(
+                        ErrorCode::Locked,
+                        ErrorClass::Reference,
+                        format!("Unable to update {refname}, as other processes kept updating it"),
+                    ));
+                }
+                info!("XET update_ref: {refname} was updated concurrently ({e}); retrying.");
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Whether a failed ref update was due to another process updating the ref.
+fn is_concurrent_update(e: &git2::Error) -> bool {
+    matches!(
+        e.code(),
+        ErrorCode::Modified | ErrorCode::Exists | ErrorCode::Locked
+    )
+}
+
+/// Runs a git subprocess updating refs, e.g. `git notes merge`, retrying it
+/// while another process holds the lock of a ref it updates.
+pub fn retry_on_ref_lock<T>(what: &str, mut run: impl FnMut() -> Result<T>) -> Result<T> {
+    let mut backoff = Backoff::default();
+    loop {
+        match run() {
+            Err(GitXetRepoError::RefLocked { .. }) if backoff.wait() => {
+                info!("XET retry_on_ref_lock: {what} found a ref locked; retrying.");
+            }
+            ret => return ret,
+        }
+    }
+}
+
+/// Exponential backoff between attempts, with jitter so that processes
+/// retrying together spread out.
+struct Backoff {
+    attempt: u32,
+    retry_time_ms: u64,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            attempt: 0,
+            retry_time_ms: INITIAL_RETRY_TIME_MS,
+        }
+    }
+}
+
+impl Backoff {
+    /// Sleeps before the next attempt; false if there are none left.
+    fn wait(&mut self) -> bool {
+        self.attempt += 1;
+        if self.attempt >= MAX_REF_UPDATE_ATTEMPTS {
+            return false;
+        }
+        let jitter = rand::random::<u64>() % self.retry_time_ms.max(1);
+        std::thread::sleep(Duration::from_millis(self.retry_time_ms / 2 + jitter));
+        self.retry_time_ms = (self.retry_time_ms * 2).min(MAX_RETRY_TIME_MS);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn commit(repo: &Repository, parent: Option<Oid>, message: &str) -> Oid {
+        let sig = git2::Signature::now("test", "test@example.com").unwrap();
+        let tree_id = repo.treebuilder(None).unwrap().write().unwrap();
+        let tree = repo.find_tree(tree_id).unwrap();
+        let parent = parent.map(|p| repo.find_commit(p).unwrap());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(None, &sig, &sig, message, &tree, &parents)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_update_ref() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let refname = "refs/notes/xet/test";
+
+        // Created if it doesn't exist.
+        let first = commit(&repo, None, "first");
+        assert!(update_ref(&repo, refname, "test", |current| {
+            assert_eq!(current, None);
+            Ok(Some(first))
+        })
+        .unwrap());
+        assert_eq!(repo.refname_to_id(refname).unwrap(), first);
+
+        // Nothing to change writes nothing.
+        assert!(!update_ref(&repo, refname, "test", |_| Ok(None)).unwrap());
+
+        // An update computed from a value changed meanwhile is computed again.
+        let mut attempts = Vec::new();
+        assert!(update_ref(&repo, refname, "test", |current| {
+            attempts.push(current.unwrap());
+            if attempts.len() == 1 {
+                let other = commit(&repo, current, "concurrent");
+                repo.reference(refname, other, true, "concurrent").unwrap();
+            }
+            Ok(Some(commit(&repo, current, "second")))
+        })
+        .unwrap());
+        assert_eq!(attempts.len(), 2);
+        assert_eq!(attempts[0], first);
+        let head = repo
+            .find_commit(repo.refname_to_id(refname).unwrap())
+            .unwrap();
+        assert_eq!(head.message(), Some("second"));
+        // The concurrent update is kept as the parent.
+        assert_eq!(head.parent_id(0).unwrap(), attempts[1]);
+        assert_ne!(attempts[1], first);
+    }
+
+    #[test]
+    fn test_retry_on_ref_lock() {
+        let mut runs = 0;
+        let ret = retry_on_ref_lock("test", || {
+            runs += 1;
+            if runs < 3 {
+                Err(GitXetRepoError::RefLocked {
+                    command: "notes merge".to_string(),
+                    stderr: "error: cannot lock ref 'refs/notes/xet/merkledb'".to_string(),
+                })
+            } else {
+                Ok(runs)
+            }
+        });
+        assert_eq!(ret.unwrap(), 3);
+
+        let mut runs = 0;
+        let ret: Result<()> = retry_on_ref_lock("test", || {
+            runs += 1;
+            Err(GitXetRepoError::GitCommandFailed {
+                command: "notes merge".to_string(),
+                status: Some(128),
+                stdout: String::new(),
+                stderr: "fatal: bad revision".to_string(),
+            })
+        });
+        assert!(ret.is_err());
+        assert_eq!(runs, 1);
+    }
+}
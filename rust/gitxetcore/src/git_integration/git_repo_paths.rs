@@ -113,3 +113,14 @@ pub fn get_repo_path_from_config(config: &XetConfig) -> Result<PathBuf> {
         Err(_) => Ok(std::env::current_dir()?),
     }
 }
+
+/// Joins the path of a submodule to the path of the submodule containing it,
+/// giving its path relative to the root repository. parent is empty for the
+/// root repository.
+pub fn submodule_path(parent: &str, path: &str) -> String {
+    if parent.is_empty() {
+        path.to_string()
+    } else {
+        format!("{parent}/{path}")
+    }
+}
@@ -5,6 +5,7 @@ use crate::config::XetConfig;
 use crate::errors::Result;
 use crate::git_integration::git_commits::atomic_commit_impl;
 use crate::git_integration::git_commits::ManifestEntry;
+use crate::git_integration::git_file_tools::GitTreeEntryKind;
 use crate::git_integration::git_user_config::get_user_info_for_commit;
 use anyhow::anyhow;
 use git2::ObjectType;
@@ -418,7 +419,9 @@ pub fn walk_working_dir(
     Ok(ret)
 }
 
-/// Filter out file paths that are not in the repo index (untracked files).
+/// Filter out file paths that are not in the repo index (untracked files),
+/// or are in it as symlinks or submodules, which are never smudged or
+/// cleaned.
 pub fn filter_files_from_index(
     files: &[PathBuf],
     repo: Arc<Repository>,
@@ -434,10 +437,13 @@ pub fn filter_files_from_index(
         const GIT_INDEX_STAGE_NORMAL: i32 = 0;
 
         let entry_opt = index.get_path(f, GIT_INDEX_STAGE_NORMAL);
-        if let Some(_entry) = entry_opt {
+        if let Some(entry) = entry_opt {
+            if !GitTreeEntryKind::from_mode(entry.mode).is_file() {
+                continue;
+            }
             // In debug mode, verifies the file path found exactly matches the query
             #[cfg(debug_assertions)]
-            assert_eq!(bytes2path(&_entry.path), f);
+            assert_eq!(bytes2path(&entry.path), f);
             ret.push(f.to_owned());
         }
     }
@@ -1,21 +1,16 @@
 use cas_client::Staging;
-#[cfg(unix)]
-use is_executable::IsExecutable;
 use mdb_shard::constants::MDB_SHARD_MIN_TARGET_SIZE;
 use mdb_shard::error::MDBShardError;
 use mdb_shard::session_directory::consolidate_shards_in_directory;
 use mdb_shard::shard_version::ShardVersion;
+use merklehash::{HashAlgorithm, MerkleHash};
 use std::collections::{HashMap, HashSet};
-#[cfg(unix)]
-use std::fs::Permissions;
+use std::fs::create_dir_all;
 use std::fs::{self, File};
-use std::fs::{create_dir_all, OpenOptions};
-#[cfg(unix)]
-use std::os::unix::prelude::PermissionsExt;
 use tokio::sync::{Mutex, RwLock};
 use xet_error::error_hook;
 
-use std::io::Write;
+use std::io::{Cursor, Write};
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -23,13 +18,21 @@ use std::sync::Arc;
 
 use crate::command::init::InitArgs;
 use crate::config::XetConfig;
-use crate::config::{ConfigGitPathOption, UpstreamXetRepo};
+use crate::config::{ConfigGitPathOption, IntegrityVerify, QuotaCheck, UpstreamXetRepo};
 
 use crate::data::*;
+use crate::git_integration::content_moves::{find_content_moves, ContentMove, PointerFiles};
+use crate::git_integration::git_hash_algorithm::negotiate_hash_algorithm;
+use crate::git_integration::git_hooks::{
+    hook_status, install_hook, uninstall_hook, xet_hooks, HookState, HookStatus,
+};
 use crate::git_integration::git_process_wrapping;
+use crate::git_integration::git_quota::{quota_violations, resolve_remote_url};
+use crate::git_integration::git_ref_updates::retry_on_ref_lock;
 use crate::git_integration::git_repo_plumbing::*;
 use crate::git_integration::git_repo_salt::*;
 use crate::git_integration::git_user_config::get_user_info_for_commit;
+use crate::git_integration::repo_path::RepoPath;
 
 use git2::Repository;
 use lazy_static::lazy_static;
@@ -39,7 +42,11 @@ use tracing::{debug, error, info, warn};
 use crate::constants::*;
 use crate::errors::GitXetRepoError::{self};
 use crate::errors::{convert_cas_error, Result};
+use crate::interrupt::check_interrupted;
+use crate::stream::data_iterators::AsyncFileIterator;
 use crate::summaries::{merge_summaries_from_git, update_summaries_to_git};
+use crate::transfer_report;
+use crate::xetblob::{get_remote_repo_info, get_repo_quota};
 
 use super::git_merkledb::get_merkledb_notes_name;
 use super::git_notes_wrapper::GitNotesWrapper;
@@ -62,6 +69,13 @@ fn reference_transaction_hook_regex() -> &'static Regex {
     &REF_REGEX
 }
 
+/// The notes under refs/notes/xet fetched by a partial MerkleDB fetch. The
+/// MerkleDB v1 notes are the bulk of the notes history and are not needed
+/// to smudge in a v2 repository. The hash algorithm is matched by a
+/// pattern, as the notes are only present in repositories not using the
+/// default.
+const PARTIAL_FETCH_NOTES: [&str; 4] = ["merkledbv2", "reposalt", "summaries", "hashalgorithm*"];
+
 ///////////////////////////
 // Git attributes.
 const GITATTRIBUTES_CONTENT: &str =
@@ -73,14 +87,6 @@ lazy_static! {
     .unwrap();
 }
 
-const PREPUSH_HOOK_CONTENT: &str =
-    "git-xet hooks pre-push-hook --remote \"$1\" --remote-loc \"$2\"\n";
-
-const REFERENCE_TRANSACTION_HOOK_CONTENT_MDB_V1: &str =
-    "git-xet hooks reference-transaction-hook --action \"$1\"\n";
-const REFERENCE_TRANSACTION_HOOK_CONTENT_MDB_V2: &str =
-    "[[ ! -z \"$XET_DISABLE_HOOKS\" ]] || git-xet hooks reference-transaction-hook --action \"$1\"\n";
-
 // Provides a mechanism to lock files that can often be modifiied, such as hooks,
 // .gitattributes, etc.  Normally our mechanisms should handle all these files
 // automatically, but this provides a way to force files to never change.  Adding
@@ -94,7 +100,7 @@ lazy_static! {
     static ref CONTENT_LOCKING_REGEX: Regex = Regex::new(r".*XET +LOCK.*").unwrap();
 }
 /// Returns true if the locking text `# XET LOCK` appears in the text, and false otherwise.
-fn file_content_contains_lock(content: &str) -> bool {
+pub(super) fn file_content_contains_lock(content: &str) -> bool {
     CONTENT_LOCKING_REGEX.is_match(content)
 }
 
@@ -467,9 +473,10 @@ impl GitXetRepo {
             self.set_repo_mdb(&mdb_version).await?;
         }
 
-        // Setting the salt is always needed.
-        if args.write_repo_salt {
-            self.set_repo_salt()?;
+        // Setting the salt is always needed.  A new salt means a new repository,
+        // which is when its hash algorithm is chosen.
+        if args.write_repo_salt && self.set_repo_salt()? {
+            self.set_hash_algorithm().await?;
         }
 
         // Do this last, as this turns on the filter invocation, which won't work if the above things aren't set.
@@ -519,6 +526,7 @@ impl GitXetRepo {
         self.sync_note_refs_to_local("merkledb", GIT_NOTES_MERKLEDB_V1_REF_SUFFIX)?;
         self.sync_note_refs_to_local("merkledbv2", GIT_NOTES_MERKLEDB_V2_REF_SUFFIX)?;
         self.sync_note_refs_to_local("reposalt", GIT_NOTES_REPO_SALT_REF_SUFFIX)?;
+        self.sync_note_refs_to_local("hashalgorithm", GIT_NOTES_HASH_ALGORITHM_REF_SUFFIX)?;
 
         // Reset the local shard version
         self.mdb_version = mdb::get_mdb_version(&self.repo_dir, &self.xet_config)?;
@@ -711,11 +719,26 @@ impl GitXetRepo {
             changed |= self.write_postcheckout_hook()?;
             changed |= self.write_postmerge_hook()?;
             changed |= self.write_postcommit_hook()?;
+        } else {
+            // Upgrade the locking hooks if they were installed before.
+            for hook in xet_hooks(self.mdb_version).iter().filter(|h| h.locking) {
+                if hook_status(&self.hooks_dir(), hook)?.state == HookState::Stale {
+                    changed |= install_hook(&self.hooks_dir(), hook)?;
+                }
+            }
         }
 
         Ok(changed)
     }
 
+    /// The state of each of the hooks git-xet installs.
+    pub fn hooks_status(&self) -> Result<Vec<HookStatus>> {
+        xet_hooks(self.mdb_version)
+            .iter()
+            .map(|hook| hook_status(&self.hooks_dir(), hook))
+            .collect()
+    }
+
     // Writes out remote fetch information for the given remote,
     // or the current remote if None.
     pub async fn verify_or_write_repo_fetch_config(&self) -> Result<Vec<String>> {
@@ -732,25 +755,49 @@ impl GitXetRepo {
             &["--get-regex", "remote\\.[a-z]+\\.fetch", ".*/notes/xet/.*"],
         )?;
 
-        let repo_fetch_heads: HashMap<&str, &str> = config_settings
+        let mut repo_fetch_heads: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (name, value) in config_settings
             .split('\n')
-            .map(|line| line.split_once(' '))
-            .filter_map(|e| e.map(|vv| (vv.0.trim(), vv.1.trim())))
-            .collect();
+            .filter_map(|line| line.split_once(' '))
+        {
+            repo_fetch_heads
+                .entry(name.trim())
+                .or_default()
+                .push(value.trim());
+        }
 
         for remote in self.current_remotes()? {
             let config_name = format!("remote.{}.fetch", &remote);
-            let config_value = format!("+refs/notes/xet/*:refs/remotes/{}/notes/xet/*", &remote);
+            let all_notes = format!("+refs/notes/xet/*:refs/remotes/{}/notes/xet/*", &remote);
+            let config_values = if self.partial_merkledb_fetch() {
+                PARTIAL_FETCH_NOTES
+                    .iter()
+                    .map(|n| format!("+refs/notes/xet/{n}:refs/remotes/{}/notes/xet/{n}", &remote))
+                    .collect()
+            } else {
+                vec![all_notes.clone()]
+            };
 
-            if let Some(v) = repo_fetch_heads.get(config_name.as_str()) {
-                if *v == config_value {
-                    debug!("XET: Fetch hooks on remote.{}.fetch is set.", &remote);
-                    continue;
-                }
+            // Refspecs already there are kept, so that switching to a partial
+            // fetch doesn't narrow the notes fetched; all of them are fetched
+            // if any refspec fetches all of them.
+            let existing = repo_fetch_heads
+                .get(config_name.as_str())
+                .cloned()
+                .unwrap_or_default();
+            let missing: Vec<&String> = config_values
+                .iter()
+                .filter(|v| !existing.contains(&v.as_str()))
+                .collect();
+            if missing.is_empty() || existing.contains(&all_notes.as_str()) {
+                debug!("XET: Fetch hooks on remote.{}.fetch is set.", &remote);
+                continue;
             }
             info!("XET: Setting fetch hooks on remote.{}.fetch.", &remote);
 
-            self.run_git_checked_in_repo("config", &["--add", &config_name, &config_value])?;
+            for config_value in missing {
+                self.run_git_checked_in_repo("config", &["--add", &config_name, config_value])?;
+            }
             new_remotes.push(remote);
         }
 
@@ -909,192 +956,50 @@ impl GitXetRepo {
         }
     }
 
-    #[cfg(unix)]
-    fn set_execute_permission(path: &Path) -> Result<()> {
-        fs::set_permissions(path, Permissions::from_mode(0o755))?;
-        Ok(())
+    fn hooks_dir(&self) -> PathBuf {
+        self.git_dir.join("hooks")
     }
 
-    fn write_hook(&self, subpath: &str, script: &str) -> Result<bool> {
-        let path = self.git_dir.join(subpath);
-
-        let mut changed = false;
-
-        // There are three states to handle:
-        // 1. the file doesn't exist -> write out a new script
-        // 2. the file exists and contains the script -> do nothing
-        // 3. the file exists and doesn't contain script -> append
-        if !path.exists() {
-            let parent_path = path
-                .parent()
-                .ok_or_else(|| GitXetRepoError::FileNotFound(path.clone()))?;
-
-            fs::create_dir_all(parent_path)?;
-
-            info!("XET: writing {}.", &subpath);
-            fs::write(&path, format!("#!/usr/bin/env bash\n{script}"))?;
-            changed = true;
-        } else {
-            let content = fs::read(&path).unwrap_or_default();
-            let content = std::str::from_utf8(&content).unwrap_or_default();
-
-            if file_content_contains_lock(content) {
-                info!("Hook {:?} contains locking text, not modifying.", subpath);
-                return Ok(false);
-            }
-
-            if !content.contains(script) {
-                let mut file = OpenOptions::new().write(true).open(&path)?;
-
-                for line in content.lines() {
-                    if !line.contains("git-xet hooks") {
-                        writeln!(file, "{line}")?;
-                    }
-                }
-                writeln!(file, "{script}")?;
-
-                changed = true;
-                info!(
-                    "Adding hooks to file {:?}, filtering and appending to end.",
-                    subpath
-                );
-            }
-        }
-
-        // Make sure the executable status is set.
-        // do nothing on Windows because Windows FS doesn't have a concept of executable
-        #[cfg(unix)]
-        if !path.is_executable() {
-            Self::set_execute_permission(&path)?;
-            changed = true;
-        }
-
-        Ok(changed)
+    /// Installs or upgrades the named hook, chaining any hook already there.
+    fn write_hook(&self, name: &str) -> Result<bool> {
+        let hook = xet_hooks(self.mdb_version)
+            .into_iter()
+            .find(|h| h.name == name)
+            .ok_or_else(|| GitXetRepoError::Other(format!("Unknown git-xet hook {name}")))?;
+        install_hook(&self.hooks_dir(), &hook)
     }
 
     /// Write out the prepush hook.
     pub fn write_prepush_hook(&self) -> Result<bool> {
-        self.write_hook("hooks/pre-push", PREPUSH_HOOK_CONTENT)
+        self.write_hook("pre-push")
     }
 
     /// Write out the post-merge hook
     pub fn write_postmerge_hook(&self) -> Result<bool> {
-        let script = "git-xet hooks post-merge-hook --flag \"$1\"\n";
-        self.write_hook("hooks/post-merge", script)
+        self.write_hook("post-merge")
     }
 
     pub fn write_postcheckout_hook(&self) -> Result<bool> {
-        let script =
-            "git-xet hooks post-checkout-hook --previous \"$1\" --new \"$2\" --flag \"$3\"\n";
-        self.write_hook("hooks/post-checkout", script)
+        self.write_hook("post-checkout")
     }
 
     pub fn write_postcommit_hook(&self) -> Result<bool> {
-        let script = "git-xet hooks post-commit-hook\n";
-        self.write_hook("hooks/post-commit", script)
+        self.write_hook("post-commit")
     }
 
     /// Write out the reference transaction hook.
     pub fn write_reference_transaction_hook(&self) -> Result<bool> {
-        self.write_hook(
-            "hooks/reference-transaction",
-            match self.mdb_version {
-                ShardVersion::V1 => REFERENCE_TRANSACTION_HOOK_CONTENT_MDB_V1,
-                ShardVersion::V2 | ShardVersion::Uninitialized => {
-                    REFERENCE_TRANSACTION_HOOK_CONTENT_MDB_V2
-                }
-            },
-        )
+        self.write_hook("reference-transaction")
     }
 
-    /// Uninstall all the local hooks from the current repository
+    /// Uninstall all the local hooks from the current repository, restoring
+    /// the hooks they chain.
     pub fn uninstall_gitxet_hooks(&self, ignore_locks: bool) -> Result<bool> {
         let mut all_uninstalled_correctly = true;
         eprintln!("Uninstalling git-xet hooks from repository.");
 
-        let mut hook_erase_content: HashSet<&str> = HashSet::new();
-
-        for hook_line in &[
-            PREPUSH_HOOK_CONTENT,
-            REFERENCE_TRANSACTION_HOOK_CONTENT_MDB_V1,
-            REFERENCE_TRANSACTION_HOOK_CONTENT_MDB_V2,
-        ] {
-            for s in hook_line
-                .lines()
-                .map(|s| s.trim())
-                .filter(|s| !s.is_empty())
-            {
-                hook_erase_content.insert(s);
-            }
-        }
-
-        // Clear out the hooks.
-        for hook in &["hooks/pre-push", "hooks/reference-transaction"] {
-            let path = self.git_dir.join(hook);
-            let content = match fs::read(&path) {
-                Ok(s) => s,
-                Err(_) => {
-                    error!(
-                        "Unable to open {:?} to clear git-xet hooks; skipping.",
-                        &path
-                    );
-                    all_uninstalled_correctly = false;
-                    continue;
-                }
-            };
-
-            let content = match std::str::from_utf8(&content) {
-                Err(e) => {
-                    error!(
-                        "Unable to parse contents of {:?} to clear git-xet hooks; skipping. (Error: {:?})",
-                        &path, &e
-                    );
-                    all_uninstalled_correctly = false;
-                    continue;
-                }
-                Ok(content) => content,
-            };
-
-            if !ignore_locks && file_content_contains_lock(content) {
-                error!("Skipping {:?} in uninstall due to file locking.  Either rerun with --ignore-locks, or remove # XET LOCK from the file.", &path);
-                all_uninstalled_correctly = false;
-                continue;
-            }
-
-            let lines: Vec<&str> = content
-                .lines()
-                .filter(|s| !hook_erase_content.contains(s.trim()))
-                .collect();
-            let filtered_lines: Vec<&str> = lines
-                .iter()
-                .filter(|s| !s.trim().is_empty())
-                .copied()
-                .collect();
-
-            // If there is nothing there but the bash script, then remove the file.
-            if filtered_lines.is_empty()
-                || (filtered_lines.len() == 1 && filtered_lines[0].starts_with("#!"))
-            {
-                if let Err(e) = fs::remove_file(&path) {
-                    error!(
-                        "Error attempting to remove hook file at {:?}, skipping (Error={:?})",
-                        &path, &e
-                    );
-                    all_uninstalled_correctly = false;
-                    continue;
-                }
-                info!("Removed git-xet hook at {:?}", &path);
-            } else {
-                if let Err(e) = fs::write(&path, lines.join("\n")) {
-                    error!(
-                        "Error attempting to rewrite hook file at {:?}, skipping (Error={:?})",
-                        &path, &e
-                    );
-                    all_uninstalled_correctly = false;
-                    continue;
-                }
-                info!("Cleared git-xet hook call from {:?}", &hook);
-            }
+        for hook in xet_hooks(self.mdb_version) {
+            all_uninstalled_correctly &= uninstall_hook(&self.hooks_dir(), &hook, ignore_locks)?;
         }
 
         Ok(all_uninstalled_correctly)
@@ -1236,6 +1141,10 @@ impl GitXetRepo {
             self.purge_local_fetch_config()?;
         }
 
+        if args.purge_notes {
+            self.purge_xet_notes()?;
+        }
+
         if (!removed_paths.is_empty() || !modified_paths.is_empty()) && !self.repo_is_clean()? {
             // Check on repo being clean in case this was run twice and parts are already committed.
             for file in removed_paths {
@@ -1347,6 +1256,26 @@ impl GitXetRepo {
         Ok(true)
     }
 
+    /// The refs of the git-xet notes, local and fetched from the remotes.
+    pub fn xet_notes_refs(&self) -> Result<Vec<String>> {
+        let refs = self.run_git_checked_in_repo("for-each-ref", &["--format=%(refname)"])?;
+        Ok(refs
+            .lines()
+            .filter(|r| r.contains("notes/xet/") || r.contains("notes/xet_alt/"))
+            .map(String::from)
+            .collect())
+    }
+
+    /// Delete the git-xet notes, holding the MerkleDB, the repo salt and the
+    /// summaries.
+    pub fn purge_xet_notes(&self) -> Result<()> {
+        info!("XET: Deleting git-xet notes.");
+        for refname in self.xet_notes_refs()? {
+            self.run_git_checked_in_repo("update-ref", &["-d", &refname])?;
+        }
+        Ok(())
+    }
+
     /// Write out the filter config to global settings.
     pub fn write_global_xet_config() -> Result<()> {
         info!("XET: Setting global filter config.");
@@ -1367,6 +1296,16 @@ impl GitXetRepo {
             None,
         )?;
 
+        // So that git checks out, and smudges, files deeper than MAX_PATH.
+        #[cfg(windows)]
+        git_process_wrapping::run_git_captured(
+            None,
+            "config",
+            &["--global", "--bool", "core.longpaths", "true"],
+            true,
+            None,
+        )?;
+
         Ok(())
     }
 
@@ -1403,6 +1342,9 @@ impl GitXetRepo {
             "config",
             &["--local", "--bool", "filter.xet.required", "true"],
         )?;
+        // So that git checks out, and smudges, files deeper than MAX_PATH.
+        #[cfg(windows)]
+        self.run_git_checked_in_repo("config", &["--local", "--bool", "core.longpaths", "true"])?;
 
         Ok(())
     }
@@ -1466,6 +1408,7 @@ impl GitXetRepo {
         self.sync_note_refs_to_local("merkledb", GIT_NOTES_MERKLEDB_V1_REF_SUFFIX)?;
         self.sync_note_refs_to_local("merkledbv2", GIT_NOTES_MERKLEDB_V2_REF_SUFFIX)?;
         self.sync_note_refs_to_local("reposalt", GIT_NOTES_REPO_SALT_REF_SUFFIX)?;
+        self.sync_note_refs_to_local("hashalgorithm", GIT_NOTES_HASH_ALGORITHM_REF_SUFFIX)?;
         self.sync_note_refs_to_local("summaries", GIT_NOTES_SUMMARIES_REF_SUFFIX)?;
 
         debug!("XET sync_notes_to_dbs: merging MDB");
@@ -1479,13 +1422,17 @@ impl GitXetRepo {
                 .await?
             }
             ShardVersion::V2 => {
+                let partial = self.partial_merkledb_fetch();
                 mdb::sync_mdb_shards_from_git(
                     &self.xet_config,
                     &self.merkledb_v2_cache_dir,
                     GIT_NOTES_MERKLEDB_V2_REF_NAME,
-                    true, // with Shard client we can disable this in the future
+                    !partial,
                 )
-                .await?
+                .await?;
+                if partial {
+                    self.fetch_shards_for_ref("HEAD").await?;
+                }
             }
             ShardVersion::Uninitialized => {
                 debug!("sync_notes_to_dbs: skipping due to ShardVersion::Unitialized");
@@ -1520,6 +1467,238 @@ impl GitXetRepo {
         Ok(())
     }
 
+    /// Whether only the MerkleDB notes and shards needed by the checked out
+    /// files are fetched; see ShardSettings::partial_fetch.
+    fn partial_merkledb_fetch(&self) -> bool {
+        self.xet_config.shard.partial_fetch && self.mdb_version == ShardVersion::V2
+    }
+
+    /// Maps the hashes of the pointer files in the tree at reference to
+    /// their paths. Returns an empty map if reference does not resolve, e.g.
+    /// in a repository without commits.
+    pub fn pointer_files_at_ref(&self, reference: &str) -> Result<HashMap<MerkleHash, String>> {
+        let mut paths = HashMap::new();
+        self.for_each_pointer_file(reference, |path, hash, _| {
+            paths.insert(hash, path.to_string());
+        })?;
+        Ok(paths)
+    }
+
+    /// The pointer files the git-xet notes hold in place of large payloads,
+    /// see note_content_for_payload, with the notes ref each is in.
+    pub fn pointer_files_in_notes(&self) -> Result<HashMap<MerkleHash, String>> {
+        let odb = self.repo.odb()?;
+        let mut pointers = HashMap::new();
+        for refname in self.xet_notes_refs()? {
+            let Ok(notes) = self.repo.notes(Some(&refname)) else {
+                continue;
+            };
+            for note in notes {
+                let (blob_id, _) = note?;
+                // Most notes, e.g. the MerkleDB shards, are far larger than
+                // a pointer file, and aren't read.
+                if odb.read_header(blob_id)?.0 > POINTER_FILE_LIMIT {
+                    continue;
+                }
+                let blob = self.repo.find_blob(blob_id)?;
+                let Ok(content) = std::str::from_utf8(blob.content()) else {
+                    continue;
+                };
+                let pointer_file = PointerFile::init_from_string(content, &refname);
+                if let (true, Ok(hash)) = (pointer_file.is_valid(), pointer_file.hash()) {
+                    pointers.insert(hash, refname.clone());
+                }
+            }
+        }
+        Ok(pointers)
+    }
+
+    /// The pointer files in the tree at reference, by path; None if
+    /// reference does not resolve.
+    pub fn pointer_files_by_path(&self, reference: &str) -> Result<Option<PointerFiles>> {
+        let mut files = PointerFiles::new();
+        let resolved = self.for_each_pointer_file(reference, |path, hash, size| {
+            files.insert(path, (hash, size));
+        })?;
+        Ok(resolved.then_some(files))
+    }
+
+    /// The files renamed or copied in the tree at new_ref since the one at
+    /// old_ref, e.g. HEAD since the upstream of the branch; None if old_ref
+    /// does not resolve.
+    pub fn content_moves(&self, old_ref: &str, new_ref: &str) -> Result<Option<Vec<ContentMove>>> {
+        let Some(old) = self.pointer_files_by_path(old_ref)? else {
+            return Ok(None);
+        };
+        let new = self.pointer_files_by_path(new_ref)?.unwrap_or_default();
+        Ok(Some(find_content_moves(&old, &new)))
+    }
+
+    /// Calls f with the path, hash and size of each pointer file in the tree
+    /// at reference. Returns false if reference does not resolve.
+    fn for_each_pointer_file(
+        &self,
+        reference: &str,
+        mut f: impl FnMut(RepoPath, MerkleHash, u64),
+    ) -> Result<bool> {
+        let Some(tree) = self
+            .repo
+            .revparse_single(reference)
+            .ok()
+            .and_then(|o| o.peel_to_tree().ok())
+        else {
+            return Ok(false);
+        };
+        tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+            if let Some(git2::ObjectType::Blob) = entry.kind() {
+                if let Some(blob) = entry
+                    .to_object(&self.repo)
+                    .ok()
+                    .and_then(|x| x.peel_to_blob().ok())
+                    .filter(|b| b.size() <= POINTER_FILE_LIMIT)
+                {
+                    let mut path = RepoPath::from_bytes(root.as_bytes());
+                    path.push(entry.name_bytes());
+                    if let Ok(content) = std::str::from_utf8(blob.content()) {
+                        let pointer_file =
+                            PointerFile::init_from_string(content, &path.to_string());
+                        if let (true, Ok(hash)) = (pointer_file.is_valid(), pointer_file.hash()) {
+                            f(path, hash, pointer_file.filesize());
+                        }
+                    }
+                }
+            }
+            git2::TreeWalkResult::Ok
+        })?;
+        Ok(true)
+    }
+
+    /// Downloads the MerkleDB shards describing the files at reference that
+    /// are not already local. This is all a partial MerkleDB fetch downloads
+    /// up front; any other shard is fetched when a file needs it.
+    pub async fn fetch_shards_for_ref(&self, reference: &str) -> Result<()> {
+        let file_hashes: Vec<MerkleHash> =
+            self.pointer_files_at_ref(reference)?.into_keys().collect();
+        if file_hashes.is_empty() {
+            return Ok(());
+        }
+        let num_shards = mdb::download_shards_for_files(
+            &self.xet_config,
+            &self.merkledb_v2_cache_dir,
+            file_hashes,
+        )
+        .await?;
+        info!("XET fetch_shards_for_ref: fetched {num_shards} shards for {reference}.");
+        Ok(())
+    }
+
+    /// Returns the git note content storing payload. Payloads of at least
+    /// NOTE_PAYLOAD_CAS_THRESHOLD bytes are cleaned into CAS like a file at
+    /// path, and the note holds only the pointer file, so that large payloads
+    /// do not bloat the notes ref. The data is uploaded at once, as the
+    /// commands caching payloads in notes don't push; if that fails, the
+    /// payload is kept in the note.
+    pub async fn note_content_for_payload(&self, path: &str, payload: String) -> Result<String> {
+        if payload.len() < NOTE_PAYLOAD_CAS_THRESHOLD {
+            return Ok(payload);
+        }
+        match self.upload_note_payload(path, payload.as_bytes()).await {
+            Ok(content) => {
+                info!("XET note_content_for_payload: stored {path} in CAS.");
+                Ok(content)
+            }
+            Err(e) => {
+                warn!("Unable to store {path} in CAS, keeping it in the note: {e:?}");
+                Ok(payload)
+            }
+        }
+    }
+
+    /// Cleans payload into CAS and uploads it, returning its pointer file.
+    /// The payload is staged, and its shard written, apart from the rest of
+    /// the repository, so that only its data is uploaded, and nothing of it
+    /// is left to push if the upload fails.
+    async fn upload_note_payload(&self, path: &str, payload: &[u8]) -> Result<String> {
+        let staging = tempfile::TempDir::new()?;
+        let mut config = self.xet_config.clone();
+        config.staging_path = Some(staging.path().join("staging"));
+        config.merkledb_v2_session = staging.path().join("session");
+        create_dir_all(staging.path().join("staging"))?;
+        create_dir_all(&config.merkledb_v2_session)?;
+
+        let translator = PointerFileTranslator::from_config_in_repo(&config).await?;
+        let reader = AsyncFileIterator::new(Cursor::new(payload.to_vec()), GIT_MAX_PACKET_SIZE);
+        let content = translator.clean_file(Path::new(path), reader).await?;
+        translator.finalize_cleaning().await?;
+        translator.upload_cas_staged(false).await?;
+        // The shard is only needed to read the payload back here.
+        mdb::move_session_shards_to_local_cache(
+            &config.merkledb_v2_session,
+            &self.merkledb_v2_cache_dir,
+        )
+        .await?;
+        String::from_utf8(content)
+            .map_err(|_| GitXetRepoError::Other(format!("Note payload {path} is not UTF-8")))
+    }
+
+    /// Reads the payload of a git note written with note_content_for_payload,
+    /// downloading it from CAS if the note holds a pointer file.
+    pub async fn payload_from_note_content(&self, path: &str, content: &str) -> Result<String> {
+        let pointer = PointerFile::init_from_string(content, path);
+        if !pointer.is_valid() {
+            return Ok(content.to_string());
+        }
+        let translator = PointerFileTranslator::from_config_in_repo(&self.xet_config).await?;
+        let mut payload = Vec::new();
+        translator
+            .smudge_file_from_pointer(Path::new(path), &pointer, &mut payload, None)
+            .await?;
+        String::from_utf8(payload)
+            .map_err(|_| GitXetRepoError::Other(format!("Note payload {path} is not UTF-8")))
+    }
+
+    /// Opens the checked out submodules of this repository that are Xet
+    /// repositories, paired with their paths relative to the repository
+    /// root. Nested submodules are not included.
+    ///
+    /// Each submodule is opened with the config for its own path, so the
+    /// profile and credentials matching the submodule's remote are used.
+    pub fn xet_submodules(&self) -> Result<Vec<(String, GitXetRepo)>> {
+        let mut ret = Vec::new();
+        for submodule in self.repo.submodules()? {
+            // Submodules that are not initialized or checked out have no
+            // repository to open.
+            if submodule.open().is_err() {
+                continue;
+            }
+            let path = submodule.path().to_string_lossy().to_string();
+            let config = self.xet_config.switch_repo_path(
+                ConfigGitPathOption::PathDiscover(self.repo_dir.join(&path)),
+                None,
+            )?;
+            let repo = GitXetRepo::open(config)?;
+            if repo.mdb_version == ShardVersion::Uninitialized {
+                debug!("XET xet_submodules: skipping {path}, not a Xet repository.");
+                continue;
+            }
+            ret.push((path, repo));
+        }
+        Ok(ret)
+    }
+
+    /// Resolves the commit the submodule at path is pinned to in the tree at
+    /// reference, or None if reference does not contain the submodule.
+    pub fn submodule_commit_at_ref(&self, reference: &str, path: &str) -> Result<Option<String>> {
+        let tree = self.repo.revparse_single(reference)?.peel_to_tree()?;
+        let Ok(entry) = tree.get_path(Path::new(path)) else {
+            return Ok(None);
+        };
+        if entry.kind() != Some(git2::ObjectType::Commit) {
+            return Ok(None);
+        }
+        Ok(Some(entry.id().to_string()))
+    }
+
     /// Syncronizes any fetched note refs to the local notes
     pub fn sync_note_refs_to_local(&self, note_suffix: &str, notes_ref_suffix: &str) -> Result<()> {
         for xet_p in ["xet", "xet_alt"] {
@@ -1545,10 +1724,15 @@ impl GitXetRepo {
                 info!("XET sync_note_refs_to_local: updating {}", &ref_name);
 
                 if !remote_ref.is_empty() {
-                    self.run_git_checked_in_repo(
-                        "notes",
-                        &[&format!("--ref={notes_ref_suffix}"), "merge", remote_ref],
-                    )?;
+                    // Hooks of other git processes may be writing the notes
+                    // at the same time; the merge is retried while they hold
+                    // the lock of the notes ref.
+                    retry_on_ref_lock("git notes merge", || {
+                        self.run_git_checked_in_repo(
+                            "notes",
+                            &[&format!("--ref={notes_ref_suffix}"), "merge", remote_ref],
+                        )
+                    })?;
                 }
             }
         }
@@ -1575,27 +1759,30 @@ impl GitXetRepo {
 
         let name = destination.unwrap_or(remote);
 
-        let Ok(_) = self
-            .run_git_checked_in_repo(
-                "fetch",
-                &[
-                    remote,
-                    "--refmap=",
-                    "--no-write-fetch-head",
-                    &format!("+refs/notes/xet/*:refs/remotes/{name}/notes/xet_alt/*"),
-                ],
-            )
-            .map_err(|e| {
-                info!("Attempted to fetch Xet notes from {remote}: failed with {e:?}");
-                e
-            })
-        else {
+        let refspecs = if self.partial_merkledb_fetch() {
+            PARTIAL_FETCH_NOTES
+                .iter()
+                .map(|n| format!("+refs/notes/xet/{n}:refs/remotes/{name}/notes/xet_alt/{n}"))
+                .collect()
+        } else {
+            vec![format!(
+                "+refs/notes/xet/*:refs/remotes/{name}/notes/xet_alt/*"
+            )]
+        };
+        let mut args = vec![remote, "--refmap=", "--no-write-fetch-head"];
+        args.extend(refspecs.iter().map(|r| r.as_str()));
+
+        let Ok(_) = self.run_git_checked_in_repo("fetch", &args).map_err(|e| {
+            info!("Attempted to fetch Xet notes from {remote}: failed with {e:?}");
+            e
+        }) else {
             return Ok(());
         };
 
         self.sync_note_refs_to_local("merkledb", GIT_NOTES_MERKLEDB_V1_REF_SUFFIX)?;
         self.sync_note_refs_to_local("merkledbv2", GIT_NOTES_MERKLEDB_V2_REF_SUFFIX)?;
         self.sync_note_refs_to_local("summaries", GIT_NOTES_SUMMARIES_REF_SUFFIX)?;
+        self.sync_note_refs_to_local("hashalgorithm", GIT_NOTES_HASH_ALGORITHM_REF_SUFFIX)?;
 
         Ok(())
     }
@@ -1611,6 +1798,7 @@ impl GitXetRepo {
                 "--refmap=",
                 "--no-write-fetch-head",
                 "+refs/notes/xet/merkledb*:refs/notes/xet/merkledb*",
+                "+refs/notes/xet/hashalgorithm*:refs/notes/xet/hashalgorithm*",
             ],
         )?;
 
@@ -1637,37 +1825,127 @@ impl GitXetRepo {
             }
 
             ShardVersion::V2 | ShardVersion::Uninitialized => {
-                self.run_git_checked_in_repo(
-                    "push",
-                    &[
-                        "--no-verify",
-                        "--porcelain",
-                        remote,
-                        GIT_NOTES_MERKLEDB_V2_REF_NAME,
-                        GIT_NOTES_MERKLEDB_V1_REF_NAME,
-                        GIT_NOTES_SUMMARIES_REF_NAME,
-                        GIT_NOTES_REPO_SALT_REF_NAME,
-                    ],
-                )?;
+                let mut refs = vec![
+                    "--no-verify",
+                    "--porcelain",
+                    remote,
+                    GIT_NOTES_MERKLEDB_V2_REF_NAME,
+                    GIT_NOTES_SUMMARIES_REF_NAME,
+                    GIT_NOTES_REPO_SALT_REF_NAME,
+                ];
+                // A partial fetch does not bring down the v1 notes, and a v2
+                // repository never changes them.
+                if !self.partial_merkledb_fetch() {
+                    refs.push(GIT_NOTES_MERKLEDB_V1_REF_NAME);
+                }
+                if self
+                    .repo
+                    .find_reference(GIT_NOTES_HASH_ALGORITHM_REF_NAME)
+                    .is_ok()
+                {
+                    refs.push(GIT_NOTES_HASH_ALGORITHM_REF_NAME);
+                }
+                self.run_git_checked_in_repo("push", &refs)?;
             }
         };
 
         Ok(())
     }
 
-    /// Pushes all the staged data in the local CAS
+    /// Pushes all the staged data in the local CAS. If interrupted, the data
+    /// not uploaded yet stays staged, and Interrupted is returned.
     pub async fn upload_all_staged(&self) -> Result<()> {
+        let _stage = transfer_report::stage("upload");
         let cas = self.get_staging_cas().await?;
 
-        cas.upload_all_staged(MAX_CONCURRENT_UPLOADS, false)
+        cas.upload_all_staged(self.xet_config.upload.concurrency, false)
             .await
-            .or_else(convert_cas_error)
+            .or_else(convert_cas_error)?;
+        check_interrupted("push again")
+    }
+
+    /// Records the size of the files cleaned since the last push, and of the
+    /// data they added once deduplicated, in the transfer report.
+    pub fn record_pending_dedup(&self) -> Result<()> {
+        if !transfer_report::is_enabled() {
+            return Ok(());
+        }
+        let pending = PendingUpload::load(&self.merkledb_v2_session_dir, &self.cas_staging_path)?;
+        transfer_report::record_dedup(pending.file_bytes(), pending.xorb_bytes);
+        Ok(())
+    }
+
+    /// Reconstructs the files cleaned since the last push and checks them
+    /// against the whole-file hashes recorded at clean time, so that corrupted
+    /// data is caught before it leaves the machine.
+    async fn verify_pending_integrity(&self) -> Result<()> {
+        let pending_path = self.git_dir.join(INTEGRITY_PENDING_SUBDIR);
+        let pending = integrity::read_pending_integrity(&pending_path)?;
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        info!("Verifying integrity of {} files before push", pending.len());
+        let translator = PointerFileTranslator::from_config_in_repo(&self.xet_config).await?;
+        for (file_hash, expected) in pending.iter() {
+            check_interrupted("push again")?;
+            let mut writer = integrity::HashingWriter::new(std::io::sink());
+            translator
+                .smudge_file_from_hash(None, file_hash, &mut writer, None)
+                .await?;
+            integrity::check_blake3(Path::new(&file_hash.hex()), expected, &writer.finalize())?;
+        }
+
+        fs::remove_file(&pending_path)?;
+        Ok(())
+    }
+
+    /// Checks the data waiting to be pushed against the remote's quota,
+    /// warning or, with quota.check = "error", refusing the push if it would
+    /// exceed a limit. A remote that does not report a quota is not checked.
+    async fn check_quota_before_push(&self, remote: &str) -> Result<()> {
+        let pending = PendingUpload::load(&self.merkledb_v2_session_dir, &self.cas_staging_path)?;
+        if pending.upload_bytes() == 0 {
+            return Ok(());
+        }
+
+        let remote_url = resolve_remote_url(&self.repo, remote);
+        let quota = match get_repo_quota(&self.xet_config, &remote_url).await {
+            Ok(quota) => quota,
+            Err(e) => {
+                info!("Unable to query the quota of {remote_url}, skipping the quota check: {e:?}");
+                return Ok(());
+            }
+        };
+
+        let violations = quota_violations(&quota, pending.upload_bytes());
+        if violations.is_empty() {
+            return Ok(());
+        }
+        if self.xet_config.quota.check == QuotaCheck::Error {
+            return Err(GitXetRepoError::QuotaExceeded(violations.join("; ")));
+        }
+        for violation in violations {
+            eprintln!("Warning: {violation}.");
+        }
+        Ok(())
     }
 
     /// The pre-push hook
     pub async fn pre_push_hook(&self, remote: &str) -> Result<()> {
         info!("Running prepush hook with remote = {}", remote);
 
+        if self.xet_config.quota.check != QuotaCheck::Off {
+            let _stage = transfer_report::stage("check quota");
+            self.check_quota_before_push(remote).await?;
+        }
+
+        if self.xet_config.integrity.verify == IntegrityVerify::OnPush {
+            let _stage = transfer_report::stage("verify integrity");
+            self.verify_pending_integrity().await?;
+        }
+        self.record_pending_dedup()?;
+
         match self.mdb_version {
             ShardVersion::V1 => {
                 // upload all staged should start first
@@ -1677,11 +1955,16 @@ impl GitXetRepo {
 
                 self.upload_all_staged().await?;
 
-                self.sync_dbs_to_notes().await?;
+                {
+                    let _stage = transfer_report::stage("sync notes");
+                    self.sync_dbs_to_notes().await?;
+                }
 
                 // the second upload staged is to ensure xorbs associated with large MDBv1
                 // diff as standalone pointer file as synced.
                 self.upload_all_staged().await?;
+
+                let _stage = transfer_report::stage("sync notes");
                 self.sync_notes_to_remote(remote)?;
             }
             ShardVersion::V2 | ShardVersion::Uninitialized => {
@@ -1701,9 +1984,11 @@ impl GitXetRepo {
                 // Begin uploading all the CAS blocks to the remote.
                 let upload_all_jh = {
                     let cas = cas.clone();
+                    let concurrency = self.xet_config.upload.concurrency;
 
                     tokio::spawn(async move {
-                        cas.upload_all_staged(MAX_CONCURRENT_UPLOADS, false)
+                        let _stage = transfer_report::stage("upload");
+                        cas.upload_all_staged(concurrency, false)
                             .await
                             .or_else(convert_cas_error)
                     })
@@ -1724,6 +2009,7 @@ impl GitXetRepo {
                     let config = self.xet_config.clone();
                     let cas = cas.clone();
                     tokio::spawn(async move {
+                        let _stage = transfer_report::stage("upload shards");
                         mdb::sync_session_shards_to_remote(&config, &cas, merged_shards, salt).await
                     })
                 };
@@ -1760,8 +2046,13 @@ impl GitXetRepo {
                 //
                 // This results in a bad repo state.
                 upload_all_jh.await??;
+                // An interrupted upload leaves data staged, so the push stops here too.
+                check_interrupted("push again")?;
 
-                self.sync_notes_to_remote(remote)?;
+                {
+                    let _stage = transfer_report::stage("sync notes");
+                    self.sync_notes_to_remote(remote)?;
+                }
 
                 // Finally, we can move all the mdb shards from the session directory, which is used
                 // by the upload_shard task, to the cache.
@@ -1967,6 +2258,62 @@ impl GitXetRepo {
         Ok(true)
     }
 
+    // Choose the hash algorithm of a new repository with the remote, and add
+    // it to the notes unless it is the default.  Do nothing if one is
+    // already recorded.
+    pub async fn set_hash_algorithm(&self) -> Result<bool> {
+        let notesref = GIT_NOTES_HASH_ALGORITHM_REF_NAME;
+
+        if self.repo.find_reference(notesref).is_ok() {
+            info!("Skipping setting hash algorithm; {notesref} already present.");
+            return Ok(false);
+        }
+
+        let requested = self.xet_config.shard.hash_algorithm;
+        let remotes = Self::list_remote_names(self.repo.clone())?;
+        let remote = remotes
+            .iter()
+            .find(|r| *r == "origin")
+            .or_else(|| remotes.first());
+
+        let hash_algorithm = match remote {
+            Some(remote) => {
+                let remote_url = resolve_remote_url(&self.repo, remote);
+                match get_remote_repo_info(&self.xet_config, &remote_url).await {
+                    Ok(repo_info) => {
+                        negotiate_hash_algorithm(requested, &repo_info.xet.hash_algorithms)?
+                    }
+                    Err(e) => {
+                        info!("Unable to query the repo info of {remote_url}: {e:?}");
+                        if let Some(a) = requested {
+                            warn!(
+                                "Unable to check that {remote_url} accepts the {a} hash algorithm."
+                            );
+                        }
+                        requested.unwrap_or_default()
+                    }
+                }
+            }
+            None => requested.unwrap_or_default(),
+        };
+
+        if hash_algorithm == HashAlgorithm::default() {
+            return Ok(false);
+        }
+
+        info!("Setting hash algorithm to {hash_algorithm}.");
+        let notes_handle =
+            GitNotesWrapper::from_repo(self.repo.clone(), &self.xet_config, notesref)?;
+        notes_handle
+            .add_note(hash_algorithm.name().as_bytes())
+            .map_err(|e| {
+                error!("Error inserting new note in set_hash_algorithm: {e:?}");
+                e
+            })?;
+
+        Ok(true)
+    }
+
     pub async fn get_staging_cas(&self) -> Result<Arc<dyn Staging + Send + Sync>> {
         let mut staging_cas_lg = self.cached_staging_cas.lock().await;
 
@@ -2102,10 +2449,9 @@ pub mod git_repo_test_tools {
 
 #[cfg(test)]
 mod git_repo_tests {
-    use std::fs::OpenOptions;
-
     use super::git_repo_test_tools::TestRepo;
     use super::*;
+    use crate::data::remote_shard_interface::{GlobalDedupPolicy, SmudgeQueryPolicy};
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_repo_query_functions() -> Result<()> {
@@ -2187,58 +2533,87 @@ mod git_repo_tests {
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn test_write_hook() -> Result<()> {
         let tr = TestRepo::new()?;
-
-        let subpath = "foo";
-        let hook_file = tr.repo.git_dir.join(subpath);
-
-        // test writing an empty file
-        let res = tr.repo.write_hook(subpath, "bar")?;
-        assert!(res);
-
-        let contents =
-            fs::read_to_string(hook_file.clone()).expect("Hook file wasn't written correctly");
-
-        assert_eq!(contents, "#!/usr/bin/env bash\nbar");
-
-        // append some other hooks
-        let mut file = OpenOptions::new()
-            .append(true)
-            .open(hook_file.clone())
-            .unwrap();
-        writeln!(file, "\nbaz")?;
-
-        let contents =
-            fs::read_to_string(hook_file.clone()).expect("Hook file wasn't written correctly");
-        assert_eq!(contents, "#!/usr/bin/env bash\nbar\nbaz\n");
-
-        // ensure write_hooks keeps it the same and doesn't write when script is in hooks
-        let res = tr.repo.write_hook(subpath, "bar")?;
-        assert!(!res);
-
-        let contents =
-            fs::read_to_string(hook_file.clone()).expect("Hook file wasn't written correctly");
-
-        assert_eq!(contents, "#!/usr/bin/env bash\nbar\nbaz\n");
-
-        // ensure write_hooks appends our script when it doesn't exist in previous file
-        let mut file = OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .open(hook_file.clone())
-            .unwrap();
-        writeln!(file, "#!/usr/bin/env bash\nbaz")?;
-
-        let contents =
-            fs::read_to_string(hook_file.clone()).expect("Hook file wasn't written correctly");
+        let hook_file = tr.repo.git_dir.join("hooks/pre-push");
+
+        // A hook of the user's, chained by the xet hook.
+        fs::write(&hook_file, "#!/usr/bin/env bash\nbaz\n")?;
+        assert!(tr.repo.write_prepush_hook()?);
+        assert!(!tr.repo.write_prepush_hook()?);
+
+        let status = tr.repo.hooks_status()?;
+        let pre_push = status.iter().find(|s| s.name == "pre-push").unwrap();
+        assert_eq!(pre_push.state, HookState::Current);
+        assert!(pre_push.chained);
+        let post_commit = status.iter().find(|s| s.name == "post-commit").unwrap();
+        assert_eq!(post_commit.state, HookState::NotInstalled);
+
+        // Uninstalling restores it.
+        assert!(tr.repo.uninstall_gitxet_hooks(false)?);
+        let contents = fs::read_to_string(&hook_file).expect("Hook file wasn't restored");
         assert_eq!(contents, "#!/usr/bin/env bash\nbaz\n");
 
-        let res = tr.repo.write_hook(subpath, "bar")?;
-        assert!(res);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_pointer_files_in_notes() -> Result<()> {
+        let tr = TestRepo::new()?;
+        tr.write_file("data.bin", 0, 100)?;
+        tr.repo.run_git_checked_in_repo("add", &["data.bin"])?;
+        tr.repo
+            .run_git_checked_in_repo("commit", &["-m", "Add data."])?;
+        let head = tr.repo.repo.head()?.peel_to_commit()?.id();
+        let sig = tr.repo.signature();
+
+        // A large payload stored in CAS, and a small one held in the note.
+        let hash = MerkleHash::from([1u64, 2, 3, 4]);
+        let pointer = PointerFile::init_from_info("dir-summary.json", &hash.hex(), 2 << 20);
+        let notes = [
+            ("refs/notes/xet/dir-summary", pointer.to_string()),
+            ("refs/notes/xet/dir-summary-recursive", "{}".to_string()),
+        ];
+        for (notes_ref, content) in notes {
+            tr.repo
+                .repo
+                .note(&sig, &sig, Some(notes_ref), head, &content, true)?;
+        }
 
-        let contents = fs::read_to_string(hook_file).expect("Hook file wasn't written correctly");
+        let pointers = tr.repo.pointer_files_in_notes()?;
+        assert_eq!(pointers.len(), 1);
+        assert_eq!(pointers[&hash], "refs/notes/xet/dir-summary");
+        Ok(())
+    }
 
-        assert_eq!(contents, "#!/usr/bin/env bash\nbaz\nbar\n");
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_note_payload_read_back() -> Result<()> {
+        let tr = TestRepo::new()?;
+        let cas_dir = tempfile::TempDir::new()?;
+        let mut config = tr.repo.xet_config.clone();
+        config.cas.endpoint = format!("{LOCAL_CAS_SCHEME}{}", cas_dir.path().display());
+        config.smudge_query_policy = SmudgeQueryPolicy::LocalOnly;
+        config.global_dedup_query_policy = GlobalDedupPolicy::Never;
+        let repo = GitXetRepo::open(config)?;
+
+        // Small payloads are held in the note itself.
+        let small = r#"{"version": 5}"#.to_string();
+        let content = repo
+            .note_content_for_payload("p.json", small.clone())
+            .await?;
+        assert_eq!(content, small);
+        assert_eq!(
+            repo.payload_from_note_content("p.json", &content).await?,
+            small
+        );
 
+        // The data of a pointer in a note may be gone, e.g. from a note
+        // fetched from elsewhere; reading it fails, for the caller to
+        // recompute the payload.
+        let hash = MerkleHash::from([1u64, 2, 3, 4]);
+        let pointer = PointerFile::init_from_info("p.json", &hash.hex(), 2 << 20);
+        assert!(repo
+            .payload_from_note_content("p.json", &pointer.to_string())
+            .await
+            .is_err());
         Ok(())
     }
 
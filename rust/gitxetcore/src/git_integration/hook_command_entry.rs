@@ -2,6 +2,7 @@ use clap::{Args, Subcommand};
 
 use crate::config::XetConfig;
 use crate::errors;
+use crate::git_integration::git_hooks::{HookState, HOOK_SCRIPT_VERSION};
 use crate::git_integration::git_xet_repo::GitXetRepo;
 
 #[derive(Args, Debug)]
@@ -55,11 +56,38 @@ pub struct FilterConfigArg {
     local: bool,
 }
 
+#[derive(Args, Debug)]
+pub struct HooksInstallArg {
+    /// Also install the hooks for LFS style locking; requires git lfs.
+    #[clap(long)]
+    enable_locking: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct HooksUninstallArg {
+    /// Override any hooks locked by a "# XET LOCK" comment, removing them as well.
+    #[clap(long)]
+    ignore_locks: bool,
+}
+
 /// Plumbing commands for the git intergation hooks.
 #[derive(Subcommand, Debug)]
 #[non_exhaustive]
 #[clap(about = "Git Integration Plumbing Commands", long_about = None)]
 enum HookCommand {
+    // Managing the hooks
+    /// Install the git-xet hooks, or upgrade stale ones, along with the config
+    /// fetching the notes of the remotes. Hooks already in the repository are
+    /// kept: they are moved to <hook>.pre-xet and run first by the xet hooks.
+    Install(HooksInstallArg),
+
+    /// Remove all the git-xet hooks and the notes fetch config, restoring the
+    /// hooks they replaced.
+    Uninstall(HooksUninstallArg),
+
+    /// Show whether each of the git-xet hooks is installed and up to date.
+    Status,
+
     // The hooks we install
     /// Run the reference transaction hook; this pulls refspecs from stdin,
     /// triggering an update when any remote is updated to also update the accompaning
@@ -127,6 +155,36 @@ pub async fn handle_hook_plumb_command(
     let repo = || GitXetRepo::open(config.clone());
 
     match &command.subcommand {
+        // Managing the hooks
+        HookCommand::Install(args) => {
+            let repo = repo()?;
+            if repo.verify_or_write_hooks(args.enable_locking)? {
+                eprintln!("Installed git-xet hooks (version {HOOK_SCRIPT_VERSION}).");
+            } else {
+                eprintln!("git-xet hooks are up to date.");
+            }
+            repo.verify_or_write_repo_fetch_config().await?;
+        }
+        HookCommand::Uninstall(args) => {
+            let repo = repo()?;
+            let uninstalled = repo.uninstall_gitxet_hooks(args.ignore_locks)?;
+            repo.purge_local_fetch_config()?;
+            if !uninstalled {
+                return Err(errors::GitXetRepoError::Other(
+                    "Not all git-xet hooks could be removed; see the errors above.".to_string(),
+                ));
+            }
+        }
+        HookCommand::Status => {
+            let status = repo()?.hooks_status()?;
+            for hook in &status {
+                println!("{hook}");
+            }
+            if status.iter().any(|h| h.state == HookState::Stale) {
+                println!("Run git xet hooks install to upgrade the stale hooks.");
+            }
+        }
+
         // Hooks
         HookCommand::ReferenceTransactionHook(action) => {
             repo()?.reference_transaction_hook(&action.action)?
@@ -196,6 +254,9 @@ pub async fn handle_hook_plumb_command(
 impl HookCommandShim {
     pub fn subcommand_name(&self) -> String {
         match &self.subcommand {
+            HookCommand::Install(_) => "install".to_string(),
+            HookCommand::Uninstall(_) => "uninstall".to_string(),
+            HookCommand::Status => "status".to_string(),
             HookCommand::ReferenceTransactionHook(args) => {
                 format!("reference_transaction.{}", args.action)
             }
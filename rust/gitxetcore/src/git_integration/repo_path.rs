@@ -0,0 +1,286 @@
+//! Paths in a git tree, kept byte for byte.
+//!
+//! Git stores a path as the bytes of its components separated by forward
+//! slashes, whatever the platform, and those bytes need not be UTF-8. Going
+//! through `String` or `Path` loses either: lossy conversions turn distinct
+//! non-UTF-8 names into the same string, `to_str().unwrap()` panics on them,
+//! and on Windows a `Path` is separated by backslashes. [RepoPath] keeps the
+//! bytes, and only converts to a string, for display or as a key in JSON
+//! output, with git's own quoting, which is reversible.
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+/// A path relative to the root of a repository, as git stores it. The root
+/// itself is the empty path.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RepoPath(Vec<u8>);
+
+impl RepoPath {
+    /// The path of the repository root.
+    pub fn root() -> Self {
+        RepoPath(Vec::new())
+    }
+
+    /// The path with the bytes git stores, e.g. as listed by
+    /// `git ls-tree -z`.
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Self {
+        let mut bytes = bytes.into();
+        while bytes.last() == Some(&b'/') {
+            bytes.pop();
+        }
+        RepoPath(bytes)
+    }
+
+    /// The path of a relative path on the local filesystem, whose separators
+    /// (backslashes too, on Windows) become forward slashes. None if the
+    /// path is absolute or leaves the root with "..".
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let mut ret = RepoPath::root();
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => ret.push(&os_str_to_bytes(name)),
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    ret = ret.parent()?;
+                }
+                Component::RootDir | Component::Prefix(_) => return None,
+            }
+        }
+        Some(ret)
+    }
+
+    /// Parses a path printed by [RepoPath::to_git_string].
+    pub fn from_git_string(s: &str) -> Self {
+        match s
+            .strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .and_then(unquote)
+        {
+            Some(bytes) => RepoPath::from_bytes(bytes),
+            None => RepoPath::from_bytes(s),
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The names of the directories and file making up the path.
+    pub fn components(&self) -> impl Iterator<Item = &[u8]> {
+        self.0.split(|&b| b == b'/').filter(|c| !c.is_empty())
+    }
+
+    /// The last component of the path, None for the root.
+    pub fn file_name(&self) -> Option<&[u8]> {
+        self.components().last()
+    }
+
+    /// The directory containing the path, which for a file at the top of the
+    /// repository is the root. None for the root itself.
+    pub fn parent(&self) -> Option<RepoPath> {
+        if self.is_root() {
+            return None;
+        }
+        Some(match self.0.iter().rposition(|&b| b == b'/') {
+            Some(i) => RepoPath(self.0[..i].to_vec()),
+            None => RepoPath::root(),
+        })
+    }
+
+    /// The path and each of the directories containing it, up to and
+    /// including the root.
+    pub fn ancestors(&self) -> impl Iterator<Item = RepoPath> {
+        std::iter::successors(Some(self.clone()), |p| p.parent())
+    }
+
+    /// Appends a component, or a path of several, to the path.
+    pub fn push(&mut self, name: &[u8]) {
+        if name.is_empty() {
+            return;
+        }
+        if !self.is_root() {
+            self.0.push(b'/');
+        }
+        self.0.extend_from_slice(name);
+        while self.0.last() == Some(&b'/') {
+            self.0.pop();
+        }
+    }
+
+    pub fn join(&self, other: &RepoPath) -> RepoPath {
+        let mut ret = self.clone();
+        ret.push(other.as_bytes());
+        ret
+    }
+
+    /// The path on the local filesystem, relative to the repository root.
+    pub fn to_path(&self) -> PathBuf {
+        self.components().map(os_string_from_bytes).collect()
+    }
+
+    /// The path as a string: as is if it's UTF-8, otherwise quoted as git
+    /// quotes paths, e.g. "data/\303\251t\351.csv" with the quotes, which
+    /// [RepoPath::from_git_string] parses back to the same bytes.
+    pub fn to_git_string(&self) -> String {
+        match std::str::from_utf8(&self.0) {
+            Ok(s) if !s.starts_with('"') => s.to_string(),
+            _ => quote(&self.0),
+        }
+    }
+}
+
+impl fmt::Display for RepoPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_git_string())
+    }
+}
+
+impl From<&str> for RepoPath {
+    fn from(s: &str) -> Self {
+        RepoPath::from_bytes(s)
+    }
+}
+
+/// The name with the bytes of a component of a git path, on the local
+/// filesystem. Exact on unix; elsewhere, where names are Unicode, names that
+/// aren't UTF-8 are converted lossily.
+pub fn os_string_from_bytes(bytes: &[u8]) -> OsString {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        OsStr::from_bytes(bytes).to_os_string()
+    }
+    #[cfg(not(unix))]
+    {
+        OsString::from(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+/// The bytes git stores for a name on the local filesystem; the inverse of
+/// [os_string_from_bytes].
+pub fn os_str_to_bytes(name: &OsStr) -> Cow<'_, [u8]> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Cow::Borrowed(name.as_bytes())
+    }
+    #[cfg(not(unix))]
+    {
+        match name.to_string_lossy() {
+            Cow::Borrowed(s) => Cow::Borrowed(s.as_bytes()),
+            Cow::Owned(s) => Cow::Owned(s.into_bytes()),
+        }
+    }
+}
+
+fn quote(bytes: &[u8]) -> String {
+    let mut ret = String::from("\"");
+    for &b in bytes {
+        match b {
+            b'"' => ret.push_str("\\\""),
+            b'\\' => ret.push_str("\\\\"),
+            b'\t' => ret.push_str("\\t"),
+            b'\n' => ret.push_str("\\n"),
+            0x20..=0x7e => ret.push(b as char),
+            _ => ret.push_str(&format!("\\{b:03o}")),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+/// The bytes of the contents of a quoted string, None if it isn't quoted as
+/// by quote.
+fn unquote(s: &str) -> Option<Vec<u8>> {
+    let bytes = s.as_bytes();
+    let mut ret = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' {
+            ret.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        let escaped = *bytes.get(i + 1)?;
+        match escaped {
+            b'"' | b'\\' => ret.push(escaped),
+            b't' => ret.push(b'\t'),
+            b'n' => ret.push(b'\n'),
+            b'0'..=b'3' => {
+                let octal = std::str::from_utf8(bytes.get(i + 1..i + 4)?).ok()?;
+                ret.push(u8::from_str_radix(octal, 8).ok()?);
+                i += 2;
+            }
+            _ => return None,
+        }
+        i += 2;
+    }
+    Some(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repo_path() {
+        let path = RepoPath::from("data/raw/a.csv");
+        assert_eq!(path.file_name(), Some(&b"a.csv"[..]));
+        let ancestors: Vec<String> = path.ancestors().map(|p| p.to_string()).collect();
+        assert_eq!(ancestors, vec!["data/raw/a.csv", "data/raw", "data", ""]);
+        assert_eq!(RepoPath::root().parent(), None);
+        assert_eq!(
+            RepoPath::from("data/").join(&"a.csv".into()),
+            "data/a.csv".into()
+        );
+        assert_eq!(path.to_path(), Path::new("data").join("raw").join("a.csv"));
+        assert_eq!(
+            RepoPath::from_path(Path::new("./data/raw/../a.csv")),
+            Some("data/a.csv".into())
+        );
+        assert_eq!(RepoPath::from_path(Path::new("../a.csv")), None);
+    }
+
+    #[test]
+    fn test_non_utf8_paths() {
+        let path = RepoPath::from_bytes(&b"data/\xe9t\xe9.csv"[..]);
+        let other = RepoPath::from_bytes(&b"data/\xe8t\xe8.csv"[..]);
+        assert_eq!(path.to_git_string(), "\"data/\\351t\\351.csv\"");
+        // Lossy conversions would make the two the same.
+        assert_ne!(path.to_git_string(), other.to_git_string());
+        assert_eq!(RepoPath::from_git_string(&path.to_git_string()), path);
+        assert_eq!(path.parent(), Some("data".into()));
+
+        // UTF-8 paths are printed as is, unless they would read as quoted.
+        let path = RepoPath::from("données/é.csv");
+        assert_eq!(path.to_git_string(), "données/é.csv");
+        assert_eq!(RepoPath::from_git_string("données/é.csv"), path);
+        let path = RepoPath::from("\"quoted\\\".txt");
+        assert_eq!(RepoPath::from_git_string(&path.to_git_string()), path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_paths() {
+        let path = RepoPath::from_bytes(&b"data/\xff.bin"[..]);
+        let local = path.to_path();
+        assert_eq!(RepoPath::from_path(&local), Some(path));
+        // A backslash is part of a name on unix, not a separator.
+        let path = RepoPath::from_path(Path::new("data\\a.csv")).unwrap();
+        assert_eq!(path.components().count(), 1);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_windows_paths() {
+        let path = RepoPath::from_path(Path::new("data\\raw\\a.csv")).unwrap();
+        assert_eq!(path, "data/raw/a.csv".into());
+        assert_eq!(RepoPath::from_path(Path::new("C:\\data\\a.csv")), None);
+    }
+}
@@ -0,0 +1,83 @@
+//! Listing and reading GCS objects over the JSON API.
+use serde::Deserialize;
+
+use super::{import_error, ObjectVersion};
+use crate::errors::Result;
+
+const GCS_API: &str = "https://storage.googleapis.com/storage/v1";
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct ObjectList {
+    #[serde(default)]
+    items: Vec<ObjectItem>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ObjectItem {
+    name: String,
+    etag: Option<String>,
+    // The JSON API reports sizes as strings.
+    size: Option<String>,
+    updated: Option<String>,
+}
+
+fn authorize(builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    match std::env::var("GOOGLE_OAUTH_ACCESS_TOKEN") {
+        Ok(token) => builder.bearer_auth(token),
+        Err(_) => builder,
+    }
+}
+
+/// Lists the objects in bucket whose names start with prefix.
+pub async fn list(
+    http: &reqwest::Client,
+    bucket: &str,
+    prefix: &str,
+) -> Result<Vec<(String, ObjectVersion)>> {
+    let url = format!("{GCS_API}/b/{bucket}/o");
+    let mut names = Vec::new();
+    let mut page_token: Option<String> = None;
+    loop {
+        let mut query = vec![("prefix", prefix.to_string())];
+        if let Some(token) = page_token.take() {
+            query.push(("pageToken", token));
+        }
+        let page: ObjectList = authorize(http.get(&url).query(&query))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| import_error(bucket, e))?
+            .json()
+            .await
+            .map_err(|e| import_error(bucket, e))?;
+        names.extend(page.items.into_iter().map(|item| {
+            let version = ObjectVersion {
+                etag: item.etag,
+                size: item.size.and_then(|s| s.parse().ok()),
+                modified: item.updated,
+            };
+            (item.name, version)
+        }));
+        match page.next_page_token {
+            Some(token) => page_token = Some(token),
+            None => return Ok(names),
+        }
+    }
+}
+
+pub async fn get(
+    http: &reqwest::Client,
+    bucket: &str,
+    name: &str,
+) -> reqwest::Result<reqwest::Response> {
+    let mut url =
+        reqwest::Url::parse(&format!("{GCS_API}/b/{bucket}/o/")).expect("the GCS API URL is valid");
+    url.path_segments_mut()
+        .expect("the GCS API URL has a path")
+        .pop_if_empty()
+        .push(name);
+    url.query_pairs_mut().append_pair("alt", "media");
+    authorize(http.get(url)).send().await
+}
@@ -0,0 +1,310 @@
+//! Importing objects from external data sources into a branch.
+//!
+//! Objects are listed from S3 (`s3://bucket/prefix`), GCS
+//! (`gs://bucket/prefix`) or a single HTTP(S) URL, and streamed through the
+//! clean pipeline, so that only their pointer files are committed. Keys are
+//! kept as paths below the last `/` of the prefix, so `s3://b/raw/2024/`
+//! imports `raw/2024/a/b.csv` as `a/b.csv` and `s3://b/raw/2024` as
+//! `2024/a/b.csv`.
+//!
+//! S3 requests are signed with the credentials in AWS_ACCESS_KEY_ID,
+//! AWS_SECRET_ACCESS_KEY and AWS_SESSION_TOKEN if set, and GCS requests
+//! carry the token in GOOGLE_OAUTH_ACCESS_TOKEN if set; otherwise the
+//! objects must be public.
+mod gcs;
+mod s3;
+pub mod sync;
+
+use async_trait::async_trait;
+use parutils::{tokio_par_for_each, AsyncIterator};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use url::Url;
+
+use crate::constants::MAX_CONCURRENT_UPLOADS;
+use crate::data::PointerFileTranslator;
+use crate::errors::{convert_parallel_error, GitXetRepoError, Result};
+use crate::stream::data_iterators::AsyncDataIterator;
+
+/// The commit trailer recording each source an import read from.
+pub const IMPORT_SOURCE_TRAILER: &str = "Xet-Import-Source";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportSource {
+    S3 { bucket: String, prefix: String },
+    Gcs { bucket: String, prefix: String },
+    Http { url: Url },
+}
+
+/// What the source reports about the version of an object, used to tell
+/// whether it changed since it was imported.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObjectVersion {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+}
+
+/// An object to import, and the path it is imported to relative to the
+/// destination directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportObject {
+    pub key: String,
+    pub path: String,
+    pub version: ObjectVersion,
+}
+
+impl ImportSource {
+    pub fn parse(source: &str) -> Result<Self> {
+        let url = Url::parse(source)
+            .map_err(|e| GitXetRepoError::InvalidOperation(format!("{source}: {e}")))?;
+        let bucket = || {
+            url.host_str()
+                .map(str::to_string)
+                .ok_or_else(|| GitXetRepoError::InvalidOperation(format!("{source}: no bucket")))
+        };
+        let prefix = url.path().trim_start_matches('/').to_string();
+        match url.scheme() {
+            "s3" => Ok(ImportSource::S3 {
+                bucket: bucket()?,
+                prefix,
+            }),
+            "gs" => Ok(ImportSource::Gcs {
+                bucket: bucket()?,
+                prefix,
+            }),
+            "http" | "https" => {
+                if url.path().ends_with('/') {
+                    return Err(GitXetRepoError::InvalidOperation(format!(
+                        "{source}: HTTP sources must name a single object"
+                    )));
+                }
+                Ok(ImportSource::Http { url })
+            }
+            scheme => Err(GitXetRepoError::InvalidOperation(format!(
+                "{source}: unsupported scheme {scheme}; expected s3://, gs://, http:// or https://"
+            ))),
+        }
+    }
+
+    /// Lists the objects under the source.
+    pub async fn list(&self, http: &reqwest::Client) -> Result<Vec<ImportObject>> {
+        let (prefix, keys) = match self {
+            ImportSource::S3 { bucket, prefix } => (prefix, s3::list(http, bucket, prefix).await?),
+            ImportSource::Gcs { bucket, prefix } => {
+                (prefix, gcs::list(http, bucket, prefix).await?)
+            }
+            ImportSource::Http { url } => {
+                let response = http
+                    .head(url.clone())
+                    .send()
+                    .await
+                    .and_then(|r| r.error_for_status())
+                    .map_err(|e| import_error(url.as_str(), e))?;
+                let header = |name: reqwest::header::HeaderName| {
+                    response
+                        .headers()
+                        .get(name)
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string)
+                };
+                let version = ObjectVersion {
+                    etag: header(reqwest::header::ETAG),
+                    size: header(reqwest::header::CONTENT_LENGTH).and_then(|l| l.parse().ok()),
+                    modified: header(reqwest::header::LAST_MODIFIED),
+                };
+                return Ok(vec![http_object(url, version)]);
+            }
+        };
+        let strip = prefix.rfind('/').map_or(0, |i| i + 1);
+        Ok(keys
+            .into_iter()
+            .filter(|(key, _)| !key.ends_with('/'))
+            .map(|(key, version)| ImportObject {
+                path: key[strip..].to_string(),
+                key,
+                version,
+            })
+            .collect())
+    }
+
+    /// The URL of an object listed from the source, which [open_url] reads
+    /// it from.
+    pub fn object_url(&self, object: &ImportObject) -> String {
+        match self {
+            ImportSource::S3 { bucket, .. } => format!("s3://{bucket}/{}", object.key),
+            ImportSource::Gcs { bucket, .. } => format!("gs://{bucket}/{}", object.key),
+            ImportSource::Http { url } => url.to_string(),
+        }
+    }
+
+    /// Starts reading an object listed from the source.
+    pub async fn open(
+        &self,
+        http: &reqwest::Client,
+        object: &ImportObject,
+    ) -> Result<ResponseIterator> {
+        let response = match self {
+            ImportSource::S3 { bucket, .. } => s3::get(http, bucket, &object.key).await,
+            ImportSource::Gcs { bucket, .. } => gcs::get(http, bucket, &object.key).await,
+            ImportSource::Http { url } => http.get(url.clone()).send().await,
+        }
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| import_error(&object.key, e))?;
+        Ok(ResponseIterator { response })
+    }
+}
+
+/// Starts reading the object at a URL returned by
+/// [ImportSource::object_url].
+pub async fn open_url(http: &reqwest::Client, url: &str) -> Result<ResponseIterator> {
+    let source = ImportSource::parse(url)?;
+    let key = match &source {
+        ImportSource::S3 { prefix, .. } | ImportSource::Gcs { prefix, .. } => prefix.clone(),
+        ImportSource::Http { url } => url.path().to_string(),
+    };
+    let object = ImportObject {
+        key,
+        ..Default::default()
+    };
+    source.open(http, &object).await
+}
+
+/// The object at an HTTP URL, imported under its file name.
+fn http_object(url: &Url, version: ObjectVersion) -> ImportObject {
+    let key = url.path().trim_start_matches('/').to_string();
+    let path = key.rsplit('/').next().unwrap_or_default().to_string();
+    ImportObject { key, path, version }
+}
+
+/// An object cleaned into the repository.
+#[derive(Debug, Clone)]
+pub struct ImportedFile {
+    /// The path in the repository.
+    pub path: String,
+    /// The pointer file, or the contents of a file too small to be cleaned.
+    pub pointer: Vec<u8>,
+    /// The URL the object was read from.
+    pub url: String,
+}
+
+/// Streams the objects through the clean pipeline.
+pub async fn clean_objects(
+    source: &ImportSource,
+    http: &reqwest::Client,
+    translator: &PointerFileTranslator,
+    dest: &str,
+    objects: Vec<ImportObject>,
+) -> Result<Vec<ImportedFile>> {
+    tokio_par_for_each(objects, MAX_CONCURRENT_UPLOADS, |object, _| async move {
+        let path = repo_path(dest, &object.path);
+        let reader = source.open(http, &object).await?;
+        let pointer = translator.clean_file(Path::new(&path), reader).await?;
+        Ok(ImportedFile {
+            path,
+            pointer,
+            url: source.object_url(&object),
+        })
+    })
+    .await
+    .map_err(convert_parallel_error)
+}
+
+/// Joins a path under the destination directory of an import.
+pub fn repo_path(dest: &str, path: &str) -> String {
+    let dest = dest.trim_matches('/');
+    if dest.is_empty() {
+        path.to_string()
+    } else {
+        format!("{dest}/{path}")
+    }
+}
+
+/// Refuses to commit to the branch checked out in a working tree, whose
+/// files would then no longer match it.
+pub fn ensure_not_checked_out(repo: &git2::Repository, branch: &str) -> Result<()> {
+    if repo.is_bare() {
+        return Ok(());
+    }
+    let head = repo.head().ok();
+    if head.as_ref().and_then(|h| h.shorthand()) == Some(branch) {
+        return Err(GitXetRepoError::InvalidOperation(format!(
+            "{branch} is checked out; use another branch and merge it"
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn import_error(what: &str, e: reqwest::Error) -> GitXetRepoError {
+    GitXetRepoError::ImportSourceError(format!("{what}: {e}"))
+}
+
+/// Streams the body of a response into the clean pipeline.
+pub struct ResponseIterator {
+    response: reqwest::Response,
+}
+
+#[async_trait]
+impl AsyncIterator<GitXetRepoError> for ResponseIterator {
+    type Item = Vec<u8>;
+    async fn next(&mut self) -> Result<Option<Vec<u8>>> {
+        let url = self.response.url().to_string();
+        let chunk = self
+            .response
+            .chunk()
+            .await
+            .map_err(|e| import_error(&url, e))?;
+        Ok(chunk.map(|c| c.to_vec()))
+    }
+}
+impl AsyncDataIterator for ResponseIterator {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_source() {
+        assert_eq!(
+            ImportSource::parse("s3://bucket/raw/2024/").unwrap(),
+            ImportSource::S3 {
+                bucket: "bucket".to_string(),
+                prefix: "raw/2024/".to_string()
+            }
+        );
+        assert_eq!(
+            ImportSource::parse("gs://bucket").unwrap(),
+            ImportSource::Gcs {
+                bucket: "bucket".to_string(),
+                prefix: String::new()
+            }
+        );
+        assert!(ImportSource::parse("https://example.com/data/").is_err());
+        assert!(ImportSource::parse("ftp://example.com/a.csv").is_err());
+
+        let source = ImportSource::parse("s3://bucket/raw/").unwrap();
+        let object = ImportObject {
+            key: "raw/a.csv".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(source.object_url(&object), "s3://bucket/raw/a.csv");
+    }
+
+    #[test]
+    fn test_http_object() {
+        let url = Url::parse("https://example.com/data/a.csv").unwrap();
+        assert_eq!(
+            http_object(&url, ObjectVersion::default()),
+            ImportObject {
+                key: "data/a.csv".to_string(),
+                path: "a.csv".to_string(),
+                version: ObjectVersion::default(),
+            }
+        );
+        assert_eq!(repo_path("/raw/", "a.csv"), "raw/a.csv");
+        assert_eq!(repo_path("", "a.csv"), "a.csv");
+    }
+}
@@ -0,0 +1,206 @@
+//! Mirroring a source into a branch: the objects imported by a sync are
+//! recorded, with their versions, in a manifest committed alongside them, so
+//! the next sync imports only the objects that changed.
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use super::{repo_path, ImportObject, ObjectVersion};
+use crate::errors::{GitXetRepoError, Result};
+
+/// The directory sync manifests are committed in.
+pub const SYNC_MANIFEST_DIR: &str = ".xet/sync";
+
+/// The objects a sync of source into path has imported, keyed by their path
+/// relative to path.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct SyncManifest {
+    pub source: String,
+    pub path: String,
+    pub objects: BTreeMap<String, ObjectVersion>,
+}
+
+impl SyncManifest {
+    pub fn new(source: &str, path: &str) -> Self {
+        Self {
+            source: source.to_string(),
+            path: path.trim_matches('/').to_string(),
+            objects: BTreeMap::new(),
+        }
+    }
+
+    /// The path in the repository of the manifest of a sync of source into
+    /// path, one per pair.
+    pub fn repo_path(source: &str, path: &str) -> String {
+        let id = format!("{source}\n{}", path.trim_matches('/'));
+        format!(
+            "{SYNC_MANIFEST_DIR}/{}.json",
+            merklehash::compute_data_hash(id.as_bytes()).hex()
+        )
+    }
+
+    pub fn parse(contents: &[u8]) -> Result<Self> {
+        serde_json::from_slice(contents).map_err(|e| {
+            GitXetRepoError::DataParsingError(format!("Unable to parse sync manifest: {e}"))
+        })
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = serde_json::to_vec_pretty(self)?;
+        bytes.push(b'\n');
+        Ok(bytes)
+    }
+}
+
+/// Whether an object is unchanged from the version recorded for it. The
+/// ETag decides if both have one; otherwise size and modification time must
+/// be known and match.
+fn unchanged(recorded: &ObjectVersion, listed: &ObjectVersion) -> bool {
+    match (&recorded.etag, &listed.etag) {
+        (Some(a), Some(b)) => a == b && recorded.size == listed.size,
+        _ => {
+            recorded.size.is_some()
+                && recorded.modified.is_some()
+                && recorded.size == listed.size
+                && recorded.modified == listed.modified
+        }
+    }
+}
+
+/// The changes a sync makes to the branch.
+#[derive(Debug, Default)]
+pub struct SyncPlan {
+    pub added: Vec<ImportObject>,
+    pub modified: Vec<ImportObject>,
+    /// Paths relative to the destination directory.
+    pub deleted: Vec<String>,
+}
+
+impl SyncPlan {
+    /// Compares the listed objects with the manifest. exists tells whether a
+    /// path in the repository is present on the branch; recorded objects
+    /// missing from it are imported again. Objects no longer listed are
+    /// deleted only if delete is set.
+    pub fn new(
+        manifest: &SyncManifest,
+        objects: Vec<ImportObject>,
+        delete: bool,
+        exists: impl Fn(&str) -> bool,
+    ) -> Self {
+        let mut plan = SyncPlan::default();
+        let mut listed = std::collections::HashSet::new();
+        for object in objects {
+            listed.insert(object.path.clone());
+            match manifest.objects.get(&object.path) {
+                Some(recorded)
+                    if unchanged(recorded, &object.version)
+                        && exists(&repo_path(&manifest.path, &object.path)) => {}
+                Some(_) => plan.modified.push(object),
+                None => plan.added.push(object),
+            }
+        }
+        if delete {
+            plan.deleted = manifest
+                .objects
+                .keys()
+                .filter(|path| !listed.contains(*path))
+                .cloned()
+                .collect();
+        }
+        plan
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.modified.is_empty() && self.deleted.is_empty()
+    }
+
+    /// Lists the changes, one per line, as `A`, `M` or `D` and the path in
+    /// the repository.
+    pub fn report(&self, dest: &str) -> String {
+        let mut lines: Vec<String> = Vec::new();
+        for (status, objects) in [("A", &self.added), ("M", &self.modified)] {
+            lines.extend(
+                objects
+                    .iter()
+                    .map(|o| format!("{status} {}", repo_path(dest, &o.path))),
+            );
+        }
+        lines.extend(
+            self.deleted
+                .iter()
+                .map(|p| format!("D {}", repo_path(dest, p))),
+        );
+        lines.join("\n")
+    }
+
+    /// Records the imported and deleted objects in the manifest.
+    pub fn apply(&self, manifest: &mut SyncManifest) {
+        for object in self.added.iter().chain(self.modified.iter()) {
+            manifest
+                .objects
+                .insert(object.path.clone(), object.version.clone());
+        }
+        for path in &self.deleted {
+            manifest.objects.remove(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(path: &str, etag: &str) -> ImportObject {
+        ImportObject {
+            key: format!("raw/{path}"),
+            path: path.to_string(),
+            version: ObjectVersion {
+                etag: Some(etag.to_string()),
+                size: Some(10),
+                modified: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_sync_plan() {
+        let mut manifest = SyncManifest::new("s3://b/raw/", "data/");
+        let first = SyncPlan::new(
+            &manifest,
+            vec![
+                object("a.csv", "1"),
+                object("b.csv", "1"),
+                object("c.csv", "1"),
+            ],
+            true,
+            |_| true,
+        );
+        assert_eq!(first.added.len(), 3);
+        first.apply(&mut manifest);
+
+        let second = SyncPlan::new(
+            &manifest,
+            vec![
+                object("a.csv", "1"),
+                object("b.csv", "2"),
+                object("d.csv", "1"),
+            ],
+            true,
+            |_| true,
+        );
+        assert_eq!(
+            second.report("data"),
+            "A data/d.csv\nM data/b.csv\nD data/c.csv"
+        );
+
+        let kept = SyncPlan::new(&manifest, vec![object("a.csv", "1")], false, |_| true);
+        assert!(kept.is_empty());
+
+        let restored = SyncPlan::new(&manifest, vec![object("a.csv", "1")], false, |_| false);
+        assert_eq!(restored.modified.len(), 1);
+
+        second.apply(&mut manifest);
+        let parsed = SyncManifest::parse(&manifest.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed, manifest);
+        assert!(!parsed.objects.contains_key("c.csv"));
+    }
+}
@@ -0,0 +1,86 @@
+//! Stopping long operations with Ctrl-C or SIGTERM.
+//!
+//! The first signal cancels [parutils::shutdown_token]. Transfers then stop
+//! starting new files and xorbs, let the ones in flight finish, flush what
+//! they wrote, and return [GitXetRepoError::Interrupted]; running the
+//! operation again resumes it, as finished work is not redone. A second
+//! signal exits at once, skipping the flushing; files being smudged are left
+//! partial, and are resumed by the next smudge.
+use parutils::{shutdown_token, CancellationToken};
+use tracing::info;
+
+use crate::errors::{GitXetRepoError, Result};
+
+/// The exit code of a hard abort, as of a process killed by SIGINT.
+const ABORT_EXIT_CODE: i32 = 130;
+
+/// Cancels the shutdown token on the first SIGINT or SIGTERM, and exits on
+/// the second. Only for commands stopping cleanly on cancellation; others
+/// keep the default handling, exiting on the first signal.
+pub fn install_interrupt_handler() {
+    tokio::spawn(async {
+        #[cfg(unix)]
+        let mut terminate =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()).ok();
+        let mut interrupted = false;
+        loop {
+            #[cfg(unix)]
+            let received = match terminate.as_mut() {
+                Some(terminate) => tokio::select! {
+                    res = tokio::signal::ctrl_c() => res.is_ok(),
+                    Some(()) = terminate.recv() => true,
+                },
+                None => tokio::signal::ctrl_c().await.is_ok(),
+            };
+            #[cfg(not(unix))]
+            let received = tokio::signal::ctrl_c().await.is_ok();
+            if !received {
+                info!("Unable to listen for interrupts");
+                return;
+            }
+
+            if interrupted {
+                eprintln!("Aborted.");
+                std::process::exit(ABORT_EXIT_CODE);
+            }
+            interrupted = true;
+            eprintln!(
+                "Interrupted; finishing the transfers in progress. Press Ctrl-C again to abort."
+            );
+            shutdown_token().cancel();
+        }
+    });
+}
+
+/// Err(Interrupted) if the process was interrupted, for an operation to
+/// stop at a point it can be resumed from; resume is how to resume it.
+pub fn check_interrupted(resume: &str) -> Result<()> {
+    check_cancelled(shutdown_token(), resume)
+}
+
+fn check_cancelled(token: &CancellationToken, resume: &str) -> Result<()> {
+    if token.is_cancelled() {
+        return Err(GitXetRepoError::Interrupted(format!(
+            "stopped before finishing; {resume} to resume"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_cancelled() {
+        let token = CancellationToken::new();
+        assert!(check_cancelled(&token, "run git xet push").is_ok());
+        token.cancel();
+        let err = check_cancelled(&token, "run git xet push").unwrap_err();
+        assert_eq!(err.exit_code(), 130);
+        assert_eq!(
+            err.to_string(),
+            "Interrupted: stopped before finishing; run git xet push to resume"
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! Newline delimited JSON-RPC 2.0 over a local Unix socket: each request
+//! and response is one line of JSON. Used by `git xet watch` and the control
+//! socket of long-running operations, which `git xet top` is a client of.
+use std::future::Future;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+#[allow(unused_imports)]
+use tracing::{debug, info};
+
+use crate::errors::{GitXetRepoError, Result};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+#[derive(Deserialize, Debug)]
+pub struct Request {
+    #[serde(default)]
+    pub id: Value,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+impl Request {
+    /// Parses a request line, or returns the error response to send back.
+    pub fn parse(line: &str) -> std::result::Result<Self, Response> {
+        serde_json::from_str(line)
+            .map_err(|e| Response::error(Value::Null, PARSE_ERROR, e.to_string()))
+    }
+
+    /// Deserializes the params, using the default if none were given.
+    pub fn params<T: DeserializeOwned + Default>(&self) -> std::result::Result<T, Response> {
+        match &self.params {
+            Value::Null => Ok(T::default()),
+            params => serde_json::from_value(params.clone())
+                .map_err(|e| self.error(INVALID_PARAMS, e.to_string())),
+        }
+    }
+
+    /// The response carrying a result for this request.
+    pub fn result<T: Serialize>(&self, result: T) -> Response {
+        match serde_json::to_value(result) {
+            Ok(result) => Response {
+                jsonrpc: "2.0",
+                id: self.id.clone(),
+                result: Some(result),
+                error: None,
+            },
+            Err(e) => self.error(INTERNAL_ERROR, e.to_string()),
+        }
+    }
+
+    /// The response carrying an error for this request.
+    pub fn error(&self, code: i64, message: String) -> Response {
+        Response::error(self.id.clone(), code, message)
+    }
+
+    pub fn method_not_found(&self) -> Response {
+        self.error(METHOD_NOT_FOUND, format!("Unknown method {}", self.method))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Response {
+    pub jsonrpc: &'static str,
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl Response {
+    pub fn error(id: Value, code: i64, message: String) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result: None,
+            error: Some(RpcError { code, message }),
+        }
+    }
+}
+
+/// Serves requests on the Unix socket at socket_path until the task is
+/// dropped, replacing any socket left there by a previous run. Each request
+/// line is answered by handler.
+#[cfg(unix)]
+pub async fn serve<F, Fut>(socket_path: &Path, handler: F) -> Result<()>
+where
+    F: Fn(String) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = Response> + Send,
+{
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixListener;
+
+    if socket_path.exists() {
+        std::fs::remove_file(socket_path)?;
+    }
+    let listener = UnixListener::bind(socket_path)?;
+    info!("JSON-RPC: serving on {socket_path:?}");
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let handler = handler.clone();
+        tokio::spawn(async move {
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let response = handler(line).await;
+                let Ok(mut out) = serde_json::to_vec(&response) else {
+                    break;
+                };
+                out.push(b'\n');
+                if writer.write_all(&out).await.is_err() {
+                    debug!("JSON-RPC: client disconnected");
+                    break;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn serve<F, Fut>(_socket_path: &Path, _handler: F) -> Result<()>
+where
+    F: Fn(String) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = Response> + Send,
+{
+    Err(GitXetRepoError::InvalidOperation(
+        "JSON-RPC sockets require Unix domain sockets, which are not available on this platform"
+            .to_string(),
+    ))
+}
+
+/// Sends one request for method to the Unix socket at socket_path, and
+/// returns the result of the response.
+#[cfg(unix)]
+pub async fn call(socket_path: &Path, method: &str, params: Value) -> Result<Value> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixStream;
+
+    let mut stream = UnixStream::connect(socket_path).await?;
+    let mut request = serde_json::to_vec(&serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": method,
+        "params": params,
+    }))
+    .map_err(|e| GitXetRepoError::Other(e.to_string()))?;
+    request.push(b'\n');
+    stream.write_all(&request).await?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).await?;
+    let response: ClientResponse = serde_json::from_str(&line).map_err(|e| {
+        GitXetRepoError::Other(format!(
+            "Invalid response to {method} from {socket_path:?}: {e}"
+        ))
+    })?;
+    match (response.result, response.error) {
+        (_, Some(error)) => Err(GitXetRepoError::Other(format!(
+            "{method} failed on {socket_path:?}: {} ({})",
+            error.message, error.code
+        ))),
+        (result, None) => Ok(result.unwrap_or(Value::Null)),
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn call(_socket_path: &Path, _method: &str, _params: Value) -> Result<Value> {
+    Err(GitXetRepoError::InvalidOperation(
+        "JSON-RPC sockets require Unix domain sockets, which are not available on this platform"
+            .to_string(),
+    ))
+}
+
+/// A response as received by a client.
+#[cfg(unix)]
+#[derive(Deserialize, Debug)]
+struct ClientResponse {
+    #[serde(default)]
+    result: Option<Value>,
+    #[serde(default)]
+    error: Option<RpcError>,
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_call() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let socket_path = dir.path().join("test.sock");
+        let server = tokio::spawn({
+            let socket_path = socket_path.clone();
+            async move {
+                serve(&socket_path, |line| async move {
+                    let request = match Request::parse(&line) {
+                        Ok(r) => r,
+                        Err(response) => return response,
+                    };
+                    match request.method.as_str() {
+                        "echo" => request.result(request.params.clone()),
+                        _ => request.method_not_found(),
+                    }
+                })
+                .await
+            }
+        });
+        while !socket_path.exists() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        let result = call(&socket_path, "echo", serde_json::json!({"a": 1})).await?;
+        assert_eq!(result["a"], 1);
+        assert!(call(&socket_path, "unknown", Value::Null).await.is_err());
+        server.abort();
+        Ok(())
+    }
+}
@@ -4,15 +4,29 @@ extern crate more_asserts;
 
 pub mod environment;
 
+pub mod archive;
+pub mod audit;
+pub mod cas_proxy;
 pub mod command;
 pub mod config;
 pub mod constants;
+pub mod control;
 pub mod data;
 mod diff;
 pub mod errors;
 pub mod git_integration;
+pub mod import;
+pub mod interrupt;
+pub mod jsonrpc;
+pub mod lineage;
+pub mod p2p;
+pub mod schemas;
+pub mod shared_store;
 pub mod stream;
 pub mod summaries;
+pub mod transfer_report;
 mod utils;
+pub mod watch;
 pub mod xetblob;
 pub mod xetmnt;
+pub mod xetserve;
@@ -0,0 +1,192 @@
+//! Reproducibility manifests for pipeline steps run through `git xet run`.
+//!
+//! A manifest records the command line of a step along with the size and
+//! hash of each file it read and each output it created or modified, and is
+//! committed under `.xet/runs` alongside the pointer files of the outputs.
+//!
+//! Files that are pointer files, or were cleaned into one, are identified by
+//! their xet hash (`xet:<hex>`); other files by the blake3 hash of their
+//! contents (`blake3:<hex>`).
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::constants::POINTER_FILE_LIMIT;
+use crate::data::PointerFile;
+use crate::errors::{GitXetRepoError, Result};
+use crate::git_integration::walk_working_dir;
+
+/// The directory run manifests are committed in.
+pub const RUN_MANIFEST_DIR: &str = ".xet/runs";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileRecord {
+    /// The path relative to the repository root.
+    pub path: String,
+    pub size: u64,
+    pub hash: String,
+}
+
+impl FileRecord {
+    /// Identifies the file at path in the working tree, relative to root.
+    pub fn from_working_file(root: &Path, path: &str) -> Result<Self> {
+        let full_path = root.join(path);
+        let size = fs::metadata(&full_path)?.len();
+        if size <= POINTER_FILE_LIMIT as u64 {
+            let pointer = PointerFile::init_from_path(&full_path.to_string_lossy());
+            if pointer.is_valid() {
+                return Ok(Self::from_pointer(path, &pointer));
+            }
+        }
+        let mut hasher = blake3::Hasher::new();
+        io::copy(&mut File::open(&full_path)?, &mut hasher)?;
+        Ok(Self {
+            path: path.to_string(),
+            size,
+            hash: format!("blake3:{}", hasher.finalize().to_hex()),
+        })
+    }
+
+    /// Identifies a file from what the clean filter produced for it: a
+    /// pointer file, or the contents of a file too small to be cleaned.
+    pub fn from_cleaned(path: &str, cleaned: &[u8]) -> Self {
+        let pointer = PointerFile::init_from_string(&String::from_utf8_lossy(cleaned), path);
+        if pointer.is_valid() {
+            return Self::from_pointer(path, &pointer);
+        }
+        Self {
+            path: path.to_string(),
+            size: cleaned.len() as u64,
+            hash: format!("blake3:{}", blake3::hash(cleaned).to_hex()),
+        }
+    }
+
+    fn from_pointer(path: &str, pointer: &PointerFile) -> Self {
+        Self {
+            path: path.to_string(),
+            size: pointer.filesize(),
+            hash: format!("xet:{}", pointer.hash_string()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RunManifest {
+    pub command: Vec<String>,
+    /// RFC 3339 timestamp of when the command was started.
+    pub started: String,
+    pub duration_secs: f64,
+    pub inputs: Vec<FileRecord>,
+    pub outputs: Vec<FileRecord>,
+}
+
+impl RunManifest {
+    /// The path in the repository of the manifest of the named run.
+    pub fn repo_path(name: &str) -> String {
+        format!("{RUN_MANIFEST_DIR}/{name}.json")
+    }
+
+    /// The name of a run not given one, derived from its declared outputs so
+    /// that rerunning the same step replaces its manifest.
+    pub fn default_name(outputs: &[String]) -> String {
+        let mut outputs = outputs.to_vec();
+        outputs.sort();
+        let hash = merklehash::compute_data_hash(outputs.join("\n").as_bytes()).hex();
+        hash[..16].to_string()
+    }
+
+    pub fn parse(contents: &[u8]) -> Result<Self> {
+        serde_json::from_slice(contents).map_err(|e| {
+            GitXetRepoError::DataParsingError(format!("Unable to parse run manifest: {e}"))
+        })
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = serde_json::to_vec_pretty(self)?;
+        bytes.push(b'\n');
+        Ok(bytes)
+    }
+}
+
+/// The size and modification time of each file under a set of paths,
+/// keyed by path relative to the repository root.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct OutputSnapshot {
+    files: BTreeMap<String, (u64, Option<SystemTime>)>,
+}
+
+impl OutputSnapshot {
+    /// Lists the files under each of paths, relative to root. Paths that
+    /// don't exist yet are skipped.
+    pub fn take(root: &Path, paths: &[String]) -> Result<Self> {
+        let mut files = BTreeMap::new();
+        for path in paths {
+            let full_path = root.join(path);
+            if !full_path.exists() {
+                continue;
+            }
+            let found: Vec<PathBuf> = walk_working_dir(root, &full_path, true)
+                .map_err(|e| GitXetRepoError::InvalidOperation(e.to_string()))?;
+            for file in found {
+                let metadata = fs::metadata(root.join(&file))?;
+                files.insert(
+                    file.to_string_lossy().replace('\\', "/"),
+                    (metadata.len(), metadata.modified().ok()),
+                );
+            }
+        }
+        Ok(Self { files })
+    }
+
+    /// The files in this snapshot that are new or differ from before.
+    pub fn changed_since(&self, before: &OutputSnapshot) -> Vec<String> {
+        self.files
+            .iter()
+            .filter(|(path, state)| before.files.get(*path) != Some(*state))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_output_snapshot() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("out")).unwrap();
+        fs::write(root.join("out/kept.bin"), b"kept").unwrap();
+        fs::write(root.join("out/changed.bin"), b"old").unwrap();
+
+        let outputs = vec!["out".to_string(), "model.bin".to_string()];
+        let before = OutputSnapshot::take(root, &outputs).unwrap();
+        fs::write(root.join("out/changed.bin"), b"new contents").unwrap();
+        fs::write(root.join("model.bin"), b"model").unwrap();
+        let after = OutputSnapshot::take(root, &outputs).unwrap();
+
+        assert_eq!(
+            after.changed_since(&before),
+            vec!["model.bin".to_string(), "out/changed.bin".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_file_record() {
+        let record = FileRecord::from_cleaned("a.csv", b"a,b\n1,2\n");
+        assert_eq!(record.size, 8);
+        assert!(record.hash.starts_with("blake3:"));
+
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("a.csv"), b"a,b\n1,2\n").unwrap();
+        assert_eq!(
+            FileRecord::from_working_file(dir.path(), "a.csv").unwrap(),
+            record
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use mdb_shard::MDBShardFile;
+use merklehash::MerkleHash;
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+/// The chunk boundaries of the xorbs described by the MDB shards on disk.
+///
+/// Data received from a peer is only trusted once its Merkle root matches the
+/// xorb hash, which needs the chunk boundaries the CAS does not return. They
+/// are recorded in the CAS blocks of the shards, which are present for any
+/// file this machine is able to reconstruct.
+#[derive(Debug)]
+pub struct XorbBoundaries {
+    shard_dirs: Vec<PathBuf>,
+    state: Mutex<IndexState>,
+}
+
+#[derive(Debug, Default)]
+struct IndexState {
+    boundaries: HashMap<MerkleHash, Vec<u64>>,
+    /// The number of shard files the index was last built from, so the
+    /// shards are only scanned again once new ones have been fetched.
+    num_shards: usize,
+}
+
+impl XorbBoundaries {
+    pub fn new(shard_dirs: Vec<PathBuf>) -> Self {
+        Self {
+            shard_dirs,
+            state: Mutex::new(IndexState::default()),
+        }
+    }
+
+    /// Returns the chunk boundaries of the xorb, or None if no shard on disk
+    /// describes it.
+    pub async fn get(&self, hash: &MerkleHash) -> Option<Vec<u64>> {
+        let mut state = self.state.lock().await;
+        if let Some(boundaries) = state.boundaries.get(hash) {
+            return Some(boundaries.clone());
+        }
+
+        let shards = self.load_shards();
+        if shards.len() != state.num_shards {
+            state.num_shards = shards.len();
+            for shard in shards {
+                if let Err(e) = index_shard(&shard, &mut state.boundaries) {
+                    warn!("Unable to read CAS blocks from {:?}: {e:?}", shard.path);
+                }
+            }
+        }
+        state.boundaries.get(hash).cloned()
+    }
+
+    fn load_shards(&self) -> Vec<MDBShardFile> {
+        self.shard_dirs
+            .iter()
+            .filter_map(|dir| match MDBShardFile::load_all(dir) {
+                Ok(shards) => Some(shards),
+                Err(e) => {
+                    debug!("Unable to load shards from {dir:?}: {e:?}");
+                    None
+                }
+            })
+            .flatten()
+            .collect()
+    }
+}
+
+fn index_shard(
+    shard: &MDBShardFile,
+    boundaries: &mut HashMap<MerkleHash, Vec<u64>>,
+) -> mdb_shard::error::Result<()> {
+    for cas_info in shard
+        .shard
+        .read_all_cas_blocks_full(&mut shard.get_reader()?)?
+    {
+        let chunk_boundaries = cas_info
+            .chunks
+            .iter()
+            .map(|c| c.chunk_byte_range_start as u64 + c.unpacked_segment_bytes as u64)
+            .collect();
+        boundaries.insert(cas_info.metadata.cas_hash, chunk_boundaries);
+    }
+    Ok(())
+}
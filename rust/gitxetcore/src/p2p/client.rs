@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use cas::key::Key;
+use cas_client::{validate_root_hash, CasClientError, Client};
+use merklehash::{HashAlgorithm, MerkleHash};
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use super::boundaries::XorbBoundaries;
+use super::coordinator::Announcement;
+use super::store::PeerStore;
+use super::{ANNOUNCE_PATH, P2P_TOKEN_HEADER, PEERS_PATH};
+use crate::config::XetConfig;
+use crate::errors::Result;
+
+/// How long to wait for the coordinator before going to the CAS.
+const COORDINATOR_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for a xorb from a single peer.
+const PEER_FETCH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Wraps the client to the remote CAS, fetching xorbs from peers on the
+/// local network before falling back to the CAS.
+///
+/// Xorbs are always read whole. They are kept in the peer store, which also
+/// serves later reads on this machine, and announced to the coordinator
+/// when this machine serves its store to peers. Data from a peer is only
+/// accepted if its Merkle root matches the xorb hash; xorbs whose chunk
+/// boundaries are unknown are read directly from the CAS.
+#[derive(Debug)]
+pub struct PeerClient<T: Client> {
+    remote: T,
+    coordinator: String,
+    advertise: Option<String>,
+    token: Option<String>,
+    store: PeerStore,
+    boundaries: XorbBoundaries,
+    http: reqwest::Client,
+
+    /// Held while a xorb is fetched, so that concurrent range reads of the
+    /// same xorb download it once.
+    in_flight: Mutex<HashMap<MerkleHash, Arc<Mutex<()>>>>,
+}
+
+impl<T: Client> PeerClient<T> {
+    pub fn new(
+        remote: T,
+        coordinator: &str,
+        advertise: Option<String>,
+        token: Option<String>,
+        store: PeerStore,
+        boundaries: XorbBoundaries,
+    ) -> Self {
+        Self {
+            remote,
+            coordinator: coordinator.to_string(),
+            advertise,
+            token,
+            store,
+            boundaries,
+            http: reqwest::Client::new(),
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Creates a client from the p2p settings of config, which must have a
+    /// coordinator set.
+    /// Xorbs from peers are validated against hash_algorithm, the hash
+    /// algorithm of the repository.
+    pub fn from_config(
+        config: &XetConfig,
+        remote: T,
+        hash_algorithm: HashAlgorithm,
+    ) -> Result<Self> {
+        let coordinator = config.p2p.coordinator.clone().unwrap_or_default();
+        if config.p2p.token.is_none() {
+            warn!("p2p.token is not set, the coordinator and peers will refuse requests");
+        }
+        let store = PeerStore::new(&super::store_path(config), config.p2p.store_size)?
+            .with_hash_algorithm(hash_algorithm);
+        let boundaries = XorbBoundaries::new(vec![
+            config.merkledb_v2_cache.clone(),
+            config.merkledb_v2_session.clone(),
+        ]);
+        Ok(Self::new(
+            remote,
+            &coordinator,
+            config.p2p.advertise.clone(),
+            config.p2p.token.clone(),
+            store,
+            boundaries,
+        ))
+    }
+}
+
+impl<T: Client + Send + Sync> PeerClient<T> {
+    /// Reads a whole xorb from the store, a peer, or the CAS, in that order.
+    /// Returns None if the chunk boundaries of the xorb are unknown, so that
+    /// it can't be verified.
+    async fn fetch(
+        &self,
+        prefix: &str,
+        hash: &MerkleHash,
+    ) -> std::result::Result<Option<Vec<u8>>, CasClientError> {
+        let Some(chunk_boundaries) = self.boundaries.get(hash).await else {
+            debug!("No chunk boundaries known for {hash}, not using peers");
+            return Ok(None);
+        };
+        if let Some(data) = self.store.get(prefix, hash).await {
+            return Ok(Some(data));
+        }
+
+        let lock = self
+            .in_flight
+            .lock()
+            .await
+            .entry(*hash)
+            .or_default()
+            .clone();
+        let _guard = lock.lock().await;
+        let result = self.fetch_missing(prefix, hash, chunk_boundaries).await;
+        self.in_flight.lock().await.remove(hash);
+        result.map(Some)
+    }
+
+    /// Reads a xorb that was not in the store when fetch was called.
+    async fn fetch_missing(
+        &self,
+        prefix: &str,
+        hash: &MerkleHash,
+        chunk_boundaries: Vec<u64>,
+    ) -> std::result::Result<Vec<u8>, CasClientError> {
+        // Another read may have fetched it while this one waited.
+        if let Some(data) = self.store.get(prefix, hash).await {
+            return Ok(data);
+        }
+
+        let key = Key {
+            prefix: prefix.to_string(),
+            hash: *hash,
+        };
+        for peer in self.peers(&key).await {
+            match self.fetch_from_peer(&peer, &key).await {
+                Ok(data)
+                    if validate_root_hash(
+                        &data,
+                        &chunk_boundaries,
+                        hash,
+                        self.store.hash_algorithm(),
+                    ) =>
+                {
+                    info!("Fetched {key} ({} bytes) from peer {peer}", data.len());
+                    self.keep(&key, data.clone(), chunk_boundaries).await;
+                    return Ok(data);
+                }
+                Ok(_) => warn!("Discarding {key} from peer {peer}: hash mismatch"),
+                Err(e) => debug!("Unable to fetch {key} from peer {peer}: {e}"),
+            }
+        }
+
+        let data = self.remote.get(prefix, hash).await?;
+        self.keep(&key, data.clone(), chunk_boundaries).await;
+        Ok(data)
+    }
+
+    async fn peers(&self, key: &Key) -> Vec<String> {
+        let response = with_token(
+            self.http
+                .get(format!("{}{PEERS_PATH}{key}", self.coordinator)),
+            self.token.as_deref(),
+        )
+        .timeout(COORDINATOR_TIMEOUT)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status());
+        let peers = match response {
+            Ok(response) => response.json::<Vec<String>>().await,
+            Err(e) => Err(e),
+        };
+        match peers {
+            // Don't fetch from ourselves.
+            Ok(peers) => peers
+                .into_iter()
+                .filter(|p| Some(p) != self.advertise.as_ref())
+                .collect(),
+            Err(e) => {
+                debug!("Unable to look up peers for {key}: {e}");
+                vec![]
+            }
+        }
+    }
+
+    async fn fetch_from_peer(&self, peer: &str, key: &Key) -> reqwest::Result<Vec<u8>> {
+        let response = with_token(
+            self.http.get(format!("http://{peer}/{key}")),
+            self.token.as_deref(),
+        )
+        .timeout(PEER_FETCH_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    /// Stores a xorb for peers and announces it to the coordinator.
+    async fn keep(&self, key: &Key, data: Vec<u8>, chunk_boundaries: Vec<u64>) {
+        if let Err(e) = self
+            .store
+            .insert(&key.prefix, &key.hash, data, chunk_boundaries)
+            .await
+        {
+            warn!("Unable to keep {key} in the peer store: {e}");
+            return;
+        }
+        let Some(advertise) = self.advertise.as_ref() else {
+            return;
+        };
+        let announcement = Announcement {
+            peer: advertise.clone(),
+            xorbs: vec![key.to_string()],
+        };
+        if let Err(e) = announce(
+            &self.http,
+            &self.coordinator,
+            self.token.as_deref(),
+            &announcement,
+        )
+        .await
+        {
+            debug!("Unable to announce {key}: {e}");
+        }
+    }
+}
+
+/// Adds the p2p token, if any, to a request to the coordinator or a peer.
+fn with_token(request: reqwest::RequestBuilder, token: Option<&str>) -> reqwest::RequestBuilder {
+    match token {
+        Some(token) => request.header(P2P_TOKEN_HEADER, token),
+        None => request,
+    }
+}
+
+/// Tells the coordinator which xorbs a peer serves.
+pub async fn announce(
+    http: &reqwest::Client,
+    coordinator: &str,
+    token: Option<&str>,
+    announcement: &Announcement,
+) -> reqwest::Result<()> {
+    with_token(http.post(format!("{coordinator}{ANNOUNCE_PATH}")), token)
+        .timeout(COORDINATOR_TIMEOUT)
+        .json(announcement)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[async_trait]
+impl<T: Client + Send + Sync> Client for PeerClient<T> {
+    async fn put(
+        &self,
+        prefix: &str,
+        hash: &MerkleHash,
+        data: Vec<u8>,
+        chunk_boundaries: Vec<u64>,
+    ) -> std::result::Result<(), CasClientError> {
+        self.remote.put(prefix, hash, data, chunk_boundaries).await
+    }
+
+    async fn flush(&self) -> std::result::Result<(), CasClientError> {
+        self.remote.flush().await
+    }
+
+    async fn get(
+        &self,
+        prefix: &str,
+        hash: &MerkleHash,
+    ) -> std::result::Result<Vec<u8>, CasClientError> {
+        match self.fetch(prefix, hash).await? {
+            Some(data) => Ok(data),
+            None => self.remote.get(prefix, hash).await,
+        }
+    }
+
+    async fn get_object_range(
+        &self,
+        prefix: &str,
+        hash: &MerkleHash,
+        ranges: Vec<(u64, u64)>,
+    ) -> std::result::Result<Vec<Vec<u8>>, CasClientError> {
+        let Some(data) = self.fetch(prefix, hash).await? else {
+            return self.remote.get_object_range(prefix, hash, ranges).await;
+        };
+        ranges
+            .into_iter()
+            .map(|(start, end)| {
+                data.get(start as usize..end as usize)
+                    .map(|range| range.to_vec())
+                    .ok_or(CasClientError::InvalidRange)
+            })
+            .collect()
+    }
+
+    async fn get_length(
+        &self,
+        prefix: &str,
+        hash: &MerkleHash,
+    ) -> std::result::Result<u64, CasClientError> {
+        // The last chunk boundary is the length of the xorb.
+        match self
+            .boundaries
+            .get(hash)
+            .await
+            .and_then(|b| b.last().copied())
+        {
+            Some(length) => Ok(length),
+            None => self.remote.get_length(prefix, hash).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cas_client::LocalClient;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_unknown_boundaries_read_from_remote() {
+        let remote_dir = TempDir::new().unwrap();
+        let store_dir = TempDir::new().unwrap();
+        let remote = LocalClient::new(remote_dir.path(), false);
+        let data = vec![5u8; 100];
+        let hash = merklehash::compute_data_hash(&data);
+        remote
+            .put("default", &hash, data.clone(), vec![100])
+            .await
+            .unwrap();
+
+        // No shards are present, and the coordinator is never contacted.
+        let client = PeerClient::new(
+            remote,
+            "http://127.0.0.1:1",
+            None,
+            None,
+            PeerStore::new(store_dir.path(), 1000).unwrap(),
+            XorbBoundaries::new(vec![]),
+        );
+        assert_eq!(client.get("default", &hash).await.unwrap(), data);
+        assert_eq!(
+            client
+                .get_object_range("default", &hash, vec![(10, 20)])
+                .await
+                .unwrap(),
+            vec![data[10..20].to_vec()]
+        );
+        assert_eq!(client.get_length("default", &hash).await.unwrap(), 100);
+        assert!(client.store.keys().is_empty());
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use cas::key::Key;
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::{debug, info};
+
+use super::{authorized, ANNOUNCE_PATH, ANNOUNCE_TTL, PEERS_PATH, UNAUTHORIZED_MESSAGE};
+use crate::cas_proxy::parse_key;
+use crate::errors::{GitXetRepoError, Result};
+
+/// The body of an announcement: the xorbs a peer serves, as "prefix/hash".
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Announcement {
+    pub peer: String,
+    pub xorbs: Vec<String>,
+}
+
+/// Tracks which peers hold which xorbs. Peers are forgotten once they
+/// have not announced a xorb for ANNOUNCE_TTL, when the xorb is looked up
+/// or by the periodic eviction of run_coordinator, whichever comes first.
+#[derive(Default)]
+pub struct Coordinator {
+    holders: Mutex<HashMap<Key, HashMap<String, Instant>>>,
+}
+
+impl Coordinator {
+    pub async fn announce(&self, announcement: Announcement) {
+        let now = Instant::now();
+        let mut holders = self.holders.lock().await;
+        for xorb in announcement.xorbs.iter() {
+            match parse_key(xorb) {
+                Some(key) => {
+                    holders
+                        .entry(key)
+                        .or_default()
+                        .insert(announcement.peer.clone(), now);
+                }
+                None => debug!("Ignoring invalid xorb {xorb:?} from {}", announcement.peer),
+            }
+        }
+    }
+
+    /// Returns the peers holding the xorb, most recently announced first.
+    pub async fn peers(&self, key: &Key) -> Vec<String> {
+        self.peers_within(key, ANNOUNCE_TTL).await
+    }
+
+    async fn peers_within(&self, key: &Key, ttl: Duration) -> Vec<String> {
+        let mut holders = self.holders.lock().await;
+        let Some(peers) = holders.get_mut(key) else {
+            return vec![];
+        };
+        peers.retain(|_, announced| announced.elapsed() < ttl);
+        let mut peers: Vec<_> = peers.iter().map(|(p, t)| (p.clone(), *t)).collect();
+        if peers.is_empty() {
+            holders.remove(key);
+        }
+        peers.sort_by(|a, b| b.1.cmp(&a.1));
+        peers.into_iter().map(|(p, _)| p).collect()
+    }
+
+    /// Forgets the peers that have not announced a xorb within ttl, and the
+    /// xorbs no peer holds any more. Returns the number of xorbs forgotten.
+    async fn evict_expired(&self, ttl: Duration) -> usize {
+        let mut holders = self.holders.lock().await;
+        let before = holders.len();
+        holders.retain(|_, peers| {
+            peers.retain(|_, announced| announced.elapsed() < ttl);
+            !peers.is_empty()
+        });
+        before - holders.len()
+    }
+}
+
+/// Evicts expired announcements every ANNOUNCE_TTL, so that xorbs that are
+/// never looked up again don't accumulate.
+async fn evict_periodically(coordinator: Arc<Coordinator>) {
+    let mut interval = tokio::time::interval(ANNOUNCE_TTL);
+    loop {
+        interval.tick().await;
+        let evicted = coordinator.evict_expired(ANNOUNCE_TTL).await;
+        if evicted > 0 {
+            debug!("Evicted {evicted} expired xorbs");
+        }
+    }
+}
+
+fn simple_response(status: StatusCode, msg: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "text/plain")
+        .body(Body::from(format!("{msg}\n")))
+        .unwrap()
+}
+
+async fn handle_request(
+    coordinator: Arc<Coordinator>,
+    token: &str,
+    req: Request<Body>,
+) -> Response<Body> {
+    if !authorized(req.headers(), token) {
+        return simple_response(StatusCode::UNAUTHORIZED, UNAUTHORIZED_MESSAGE);
+    }
+    let path = req.uri().path().to_string();
+    match *req.method() {
+        Method::POST if path == ANNOUNCE_PATH => {
+            let body = match hyper::body::to_bytes(req.into_body()).await {
+                Ok(body) => body,
+                Err(e) => return simple_response(StatusCode::BAD_REQUEST, &e.to_string()),
+            };
+            match serde_json::from_slice::<Announcement>(&body) {
+                Ok(announcement) => {
+                    debug!(
+                        "{} announced {} xorbs",
+                        announcement.peer,
+                        announcement.xorbs.len()
+                    );
+                    coordinator.announce(announcement).await;
+                    simple_response(StatusCode::OK, "")
+                }
+                Err(e) => simple_response(StatusCode::BAD_REQUEST, &e.to_string()),
+            }
+        }
+        Method::GET if path.starts_with(PEERS_PATH) => {
+            let Some(key) = parse_key(&path[PEERS_PATH.len()..]) else {
+                return simple_response(
+                    StatusCode::NOT_FOUND,
+                    "Peers are listed at /peers/<prefix>/<hash>",
+                );
+            };
+            let peers = coordinator.peers(&key).await;
+            Response::builder()
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(serde_json::to_vec(&peers).unwrap_or_default()))
+                .unwrap()
+        }
+        _ => simple_response(StatusCode::NOT_FOUND, "Not found"),
+    }
+}
+
+/// Runs the coordinator on addr until the process is interrupted, answering
+/// only requests that carry token.
+pub async fn run_coordinator(addr: SocketAddr, token: String) -> Result<()> {
+    let coordinator = Arc::new(Coordinator::default());
+    let evicting = coordinator.clone();
+    let token = Arc::new(token);
+    let make_svc = make_service_fn(move |_conn| {
+        let coordinator = coordinator.clone();
+        let token = token.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let coordinator = coordinator.clone();
+                let token = token.clone();
+                async move { Ok::<_, Infallible>(handle_request(coordinator, &token, req).await) }
+            }))
+        }
+    });
+    let server = Server::try_bind(&addr)
+        .map_err(|source| GitXetRepoError::HttpServerError { addr, source })?
+        .serve(make_svc);
+
+    info!("Serving P2P coordinator on http://{addr}");
+    eprintln!("Serving P2P coordinator on http://{}", server.local_addr());
+
+    tokio::select! {
+        result = server => {
+            result.map_err(|source| GitXetRepoError::HttpServerError { addr, source })
+        }
+        _ = evict_periodically(evicting) => Ok(()),
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use merklehash::MerkleHash;
+
+    use super::*;
+    use crate::p2p::P2P_TOKEN_HEADER;
+
+    #[tokio::test]
+    async fn test_announce_and_expire() {
+        let coordinator = Coordinator::default();
+        let key = Key {
+            prefix: "default".to_string(),
+            hash: MerkleHash::default(),
+        };
+        assert!(coordinator.peers(&key).await.is_empty());
+
+        coordinator
+            .announce(Announcement {
+                peer: "worker-1:4051".to_string(),
+                xorbs: vec![key.to_string(), "not a xorb".to_string()],
+            })
+            .await;
+        coordinator
+            .announce(Announcement {
+                peer: "worker-2:4051".to_string(),
+                xorbs: vec![key.to_string()],
+            })
+            .await;
+        let mut peers = coordinator.peers(&key).await;
+        peers.sort();
+        assert_eq!(
+            peers,
+            vec!["worker-1:4051".to_string(), "worker-2:4051".to_string()]
+        );
+
+        assert!(coordinator
+            .peers_within(&key, Duration::ZERO)
+            .await
+            .is_empty());
+        assert!(coordinator.holders.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_evict_expired() {
+        let coordinator = Coordinator::default();
+        let xorbs: Vec<String> = (0..10u64)
+            .map(|i| {
+                Key {
+                    prefix: "default".to_string(),
+                    hash: MerkleHash::from([i, 0, 0, 0]),
+                }
+                .to_string()
+            })
+            .collect();
+        coordinator
+            .announce(Announcement {
+                peer: "worker-1:4051".to_string(),
+                xorbs,
+            })
+            .await;
+
+        assert_eq!(coordinator.evict_expired(ANNOUNCE_TTL).await, 0);
+        assert_eq!(coordinator.holders.lock().await.len(), 10);
+        assert_eq!(coordinator.evict_expired(Duration::ZERO).await, 10);
+        assert!(coordinator.holders.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_requires_token() {
+        let coordinator = Arc::new(Coordinator::default());
+        let request = |token: Option<&str>| {
+            let mut builder =
+                Request::get(format!("{PEERS_PATH}default/{:x}", MerkleHash::default()));
+            if let Some(token) = token {
+                builder = builder.header(P2P_TOKEN_HEADER, token);
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+        for (token, status) in [
+            (None, StatusCode::UNAUTHORIZED),
+            (Some("wrong"), StatusCode::UNAUTHORIZED),
+            (Some("secret"), StatusCode::OK),
+        ] {
+            let response = handle_request(coordinator.clone(), "secret", request(token)).await;
+            assert_eq!(response.status(), status);
+        }
+    }
+}
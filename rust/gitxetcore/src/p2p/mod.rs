@@ -0,0 +1,59 @@
+//! Sharing xorbs between the machines of a local network, such as the nodes
+//! of a training cluster, so that data is downloaded from the CAS once
+//! rather than by every machine.
+//!
+//! A coordinator tracks which machines hold which xorbs. Machines with
+//! `p2p.coordinator` set look a xorb up there and fetch it from a peer
+//! before going to the CAS, verifying it against the xorb hash on receipt.
+//! Xorbs read either way are kept in a local store, which machines with
+//! `p2p.advertise` set serve to their peers with `git xet p2p serve`.
+//!
+//! The coordinator and the peers only answer requests that carry the shared
+//! `p2p.token`.
+mod boundaries;
+mod client;
+mod coordinator;
+mod server;
+mod store;
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use hyper::HeaderMap;
+
+use crate::config::XetConfig;
+pub use boundaries::XorbBoundaries;
+pub use client::PeerClient;
+pub use coordinator::{run_coordinator, Announcement, Coordinator};
+pub use server::run_peer_server;
+pub use store::PeerStore;
+
+/// The header the coordinator and peers expect p2p.token in.
+pub const P2P_TOKEN_HEADER: &str = "xet-p2p-token";
+
+/// The response to requests without the right token.
+const UNAUTHORIZED_MESSAGE: &str = "Missing or wrong p2p token";
+
+/// The coordinator path peers announce the xorbs they hold to.
+pub const ANNOUNCE_PATH: &str = "/announce";
+
+/// The coordinator path prefix the holders of /<prefix>/<hash> are listed at.
+pub const PEERS_PATH: &str = "/peers/";
+
+/// How long the coordinator lists a peer for a xorb after it was announced.
+pub const ANNOUNCE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// How often peers announce their whole store.
+pub const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(3 * 60);
+
+/// The directory xorbs kept for peers are stored in.
+pub fn store_path(config: &XetConfig) -> PathBuf {
+    config.cache.path.join("p2p")
+}
+
+/// Whether the request headers carry the shared token.
+fn authorized(headers: &HeaderMap, token: &str) -> bool {
+    headers
+        .get(P2P_TOKEN_HEADER)
+        .map_or(false, |t| t.as_bytes() == token.as_bytes())
+}
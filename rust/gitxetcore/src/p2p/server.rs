@@ -0,0 +1,118 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use tracing::{debug, info};
+
+use super::client::announce;
+use super::coordinator::Announcement;
+use super::store::PeerStore;
+use super::{authorized, ANNOUNCE_INTERVAL, UNAUTHORIZED_MESSAGE};
+use crate::cas_proxy::parse_key;
+use crate::errors::{GitXetRepoError, Result};
+
+fn simple_response(status: StatusCode, msg: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "text/plain")
+        .body(Body::from(format!("{msg}\n")))
+        .unwrap()
+}
+
+async fn handle_request(store: Arc<PeerStore>, token: &str, req: Request<Body>) -> Response<Body> {
+    if !authorized(req.headers(), token) {
+        return simple_response(StatusCode::UNAUTHORIZED, UNAUTHORIZED_MESSAGE);
+    }
+    if req.method() != Method::GET {
+        return simple_response(StatusCode::METHOD_NOT_ALLOWED, "Only GET is supported");
+    }
+    let Some(key) = parse_key(req.uri().path()) else {
+        return simple_response(
+            StatusCode::NOT_FOUND,
+            "Xorbs are served at /<prefix>/<hash>",
+        );
+    };
+    match store.get(&key.prefix, &key.hash).await {
+        Some(data) => {
+            debug!("Serving {key} to a peer");
+            Response::builder()
+                .header(CONTENT_TYPE, "application/octet-stream")
+                .header(CONTENT_LENGTH, data.len())
+                .body(Body::from(data))
+                .unwrap()
+        }
+        None => simple_response(StatusCode::NOT_FOUND, "Not found"),
+    }
+}
+
+/// Announces every xorb in the store to the coordinator, repeating before
+/// the announcements expire.
+async fn announce_periodically(
+    store: Arc<PeerStore>,
+    coordinator: String,
+    advertise: String,
+    token: Arc<String>,
+) {
+    let http = reqwest::Client::new();
+    let mut interval = tokio::time::interval(ANNOUNCE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let announcement = Announcement {
+            peer: advertise.clone(),
+            xorbs: store.keys().iter().map(|k| k.to_string()).collect(),
+        };
+        if announcement.xorbs.is_empty() {
+            continue;
+        }
+        match announce(&http, &coordinator, Some(&token), &announcement).await {
+            Ok(()) => debug!("Announced {} xorbs", announcement.xorbs.len()),
+            Err(e) => info!("Unable to reach coordinator {coordinator}: {e}"),
+        }
+    }
+}
+
+/// Serves the xorbs in store to peers presenting token on addr until the
+/// process is interrupted, announcing them to the coordinator as advertise.
+pub async fn run_peer_server(
+    store: PeerStore,
+    addr: SocketAddr,
+    coordinator: String,
+    advertise: String,
+    token: String,
+) -> Result<()> {
+    let store = Arc::new(store);
+    let token = Arc::new(token);
+    let server_store = store.clone();
+    let server_token = token.clone();
+    let make_svc = make_service_fn(move |_conn| {
+        let store = server_store.clone();
+        let token = server_token.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let store = store.clone();
+                let token = token.clone();
+                async move { Ok::<_, Infallible>(handle_request(store, &token, req).await) }
+            }))
+        }
+    });
+    let server = Server::try_bind(&addr)
+        .map_err(|source| GitXetRepoError::HttpServerError { addr, source })?
+        .serve(make_svc);
+
+    info!("Serving {:?} to peers on http://{addr}", store.path());
+    eprintln!(
+        "Serving xorbs to peers on http://{}, advertised as {advertise}",
+        server.local_addr()
+    );
+
+    tokio::select! {
+        result = server => {
+            result.map_err(|source| GitXetRepoError::HttpServerError { addr, source })
+        }
+        _ = announce_periodically(store, coordinator, advertise, token) => Ok(()),
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    }
+}
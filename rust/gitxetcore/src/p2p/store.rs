@@ -0,0 +1,155 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use cas::key::Key;
+use cas_client::{CasClientError, Client, LocalClient};
+use merklehash::{HashAlgorithm, MerkleHash};
+use tracing::{debug, info};
+
+/// The xorbs this machine keeps to serve to its peers, stored in the local
+/// CAS format under a size quota. Once over the quota the oldest xorbs are
+/// removed first.
+#[derive(Debug)]
+pub struct PeerStore {
+    client: LocalClient,
+    max_size: u64,
+}
+
+impl PeerStore {
+    pub fn new(path: &Path, max_size: u64) -> std::io::Result<Self> {
+        std::fs::create_dir_all(path)?;
+        Ok(Self {
+            client: LocalClient::new(path, true),
+            max_size,
+        })
+    }
+
+    /// Validates the xorbs inserted against hash_algorithm instead of the
+    /// default.
+    pub fn with_hash_algorithm(mut self, hash_algorithm: HashAlgorithm) -> Self {
+        self.client.hash_algorithm = hash_algorithm;
+        self
+    }
+
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.client.hash_algorithm
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.client.path
+    }
+
+    pub async fn get(&self, prefix: &str, hash: &MerkleHash) -> Option<Vec<u8>> {
+        self.client.get(prefix, hash).await.ok()
+    }
+
+    /// Stores a xorb, which is rejected unless its Merkle root under
+    /// chunk_boundaries is hash.
+    pub async fn insert(
+        &self,
+        prefix: &str,
+        hash: &MerkleHash,
+        data: Vec<u8>,
+        chunk_boundaries: Vec<u64>,
+    ) -> Result<(), CasClientError> {
+        self.client
+            .put(prefix, hash, data, chunk_boundaries)
+            .await?;
+        self.evict();
+        Ok(())
+    }
+
+    /// Lists the xorbs in the store.
+    pub fn keys(&self) -> Vec<Key> {
+        self.client.get_all_entries().unwrap_or_default()
+    }
+
+    fn evict(&self) {
+        let mut entries: Vec<(PathBuf, u64, SystemTime)> = match self.path().read_dir() {
+            Ok(dir) => dir
+                .filter_map(|e| e.ok())
+                .filter_map(|e| {
+                    let metadata = e.metadata().ok()?;
+                    metadata.is_file().then(|| {
+                        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                        (e.path(), metadata.len(), modified)
+                    })
+                })
+                .collect(),
+            Err(e) => {
+                debug!("Unable to list {:?}: {e:?}", self.path());
+                return;
+            }
+        };
+
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        if total <= self.max_size {
+            return;
+        }
+        entries.sort_by_key(|(_, _, modified)| *modified);
+        for (path, len, _) in entries {
+            if total <= self.max_size {
+                break;
+            }
+            info!("Evicting {path:?} from the peer store");
+            // Files are written read-only, which prevents removal on Windows.
+            if let Ok(metadata) = std::fs::metadata(&path) {
+                let mut permissions = metadata.permissions();
+                #[allow(clippy::permissions_set_readonly_false)]
+                permissions.set_readonly(false);
+                let _ = std::fs::set_permissions(&path, permissions);
+            }
+            if std::fs::remove_file(&path).is_ok() {
+                total -= len;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_insert_verifies_and_evicts() {
+        let dir = TempDir::new().unwrap();
+        let store = PeerStore::new(dir.path(), 2500).unwrap();
+
+        let data = vec![1u8; 1000];
+        let hash = merklehash::compute_data_hash(&data);
+        // A single chunk xorb hashes to its chunk hash.
+        assert!(store
+            .insert("default", &hash, data.clone(), vec![1000])
+            .await
+            .is_ok());
+        assert_eq!(store.get("default", &hash).await.unwrap(), data);
+
+        // Data that does not match its hash is not stored.
+        let other = vec![2u8; 1000];
+        let other_hash = merklehash::compute_data_hash(&other);
+        assert!(store
+            .insert("default", &other_hash, data.clone(), vec![1000])
+            .await
+            .is_err());
+
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        store
+            .insert("default", &other_hash, other, vec![1000])
+            .await
+            .unwrap();
+        let third = vec![3u8; 1000];
+        let third_hash = merklehash::compute_data_hash(&third);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        store
+            .insert("default", &third_hash, third, vec![1000])
+            .await
+            .unwrap();
+
+        // Only two xorbs fit, so the oldest was evicted.
+        assert_eq!(store.keys().len(), 2);
+        assert!(store.get("default", &hash).await.is_none());
+        assert!(store.get("default", &third_hash).await.is_some());
+    }
+}
@@ -0,0 +1,81 @@
+//! JSON Schemas of the JSON the CLI outputs, printed by `git xet schema`.
+//!
+//! Each schema has a version, in its `$id`, that tools can pin. Within a
+//! version, outputs only change compatibly: fields may be added, but are
+//! never removed, renamed, or given another type. Any other change is a new
+//! version of the schema. Every output is checked against its schema in the
+//! tests of the module producing it.
+
+/// The JSON Schema of one of the JSON outputs.
+pub struct OutputSchema {
+    /// The name the schema is printed by, with `git xet schema <name>`.
+    pub name: &'static str,
+    pub version: u32,
+    /// What outputs the JSON.
+    pub description: &'static str,
+    pub schema: &'static str,
+}
+
+pub const OUTPUT_SCHEMAS: &[OutputSchema] = &[
+    OutputSchema {
+        name: "dir-summaries",
+        version: 1,
+        description: "git xet dir-summary",
+        schema: include_str!("dir-summaries.schema.json"),
+    },
+    OutputSchema {
+        name: "error",
+        version: 1,
+        description: "errors, with --error-format json",
+        schema: include_str!("error.schema.json"),
+    },
+    OutputSchema {
+        name: "fsck",
+        version: 1,
+        description: "git xet fsck --json",
+        schema: include_str!("fsck.schema.json"),
+    },
+    OutputSchema {
+        name: "status",
+        version: 1,
+        description: "the status method of git xet watch",
+        schema: include_str!("status.schema.json"),
+    },
+];
+
+/// The schema of the output by name, if there is one.
+pub fn output_schema(name: &str) -> Option<&'static OutputSchema> {
+    OUTPUT_SCHEMAS.iter().find(|s| s.name == name)
+}
+
+/// Panics, listing the violations, if output doesn't match the schema by
+/// name.
+#[cfg(test)]
+pub(crate) fn assert_matches_schema(name: &str, output: &serde_json::Value) {
+    let schema: serde_json::Value =
+        serde_json::from_str(output_schema(name).unwrap().schema).unwrap();
+    let compiled = jsonschema::JSONSchema::compile(&schema).unwrap();
+    if let Err(errors) = compiled.validate(output) {
+        let errors: Vec<String> = errors.map(|e| e.to_string()).collect();
+        panic!("{name} output doesn't match its schema: {errors:?}\n{output:#}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schemas_are_versioned() {
+        for schema in OUTPUT_SCHEMAS {
+            let json: serde_json::Value = serde_json::from_str(schema.schema).unwrap();
+            assert_eq!(
+                json["$id"],
+                format!("urn:xet:schema:{}:{}", schema.name, schema.version)
+            );
+            assert!(jsonschema::JSONSchema::compile(&json).is_ok());
+        }
+        assert!(output_schema("fsck").is_some());
+        assert!(output_schema("nonexistent").is_none());
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use cas_client::{CasClientError, Client};
+use merklehash::MerkleHash;
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+use super::store::SharedStore;
+use crate::p2p::XorbBoundaries;
+
+/// Wraps the client to the remote CAS, serving xorbs from the shared store
+/// before falling back to the CAS.
+///
+/// Xorbs are read whole and kept in the shared store, as are xorbs pushed to
+/// the CAS, so every repository on this machine reads them locally. Xorbs
+/// whose chunk boundaries are unknown can't be verified, so they are read
+/// directly from the CAS and not stored.
+#[derive(Debug)]
+pub struct SharedStoreClient<T: Client> {
+    remote: T,
+    store: SharedStore,
+    boundaries: XorbBoundaries,
+
+    /// Held while a xorb is fetched, so that concurrent range reads of the
+    /// same xorb download it once.
+    in_flight: Mutex<HashMap<MerkleHash, Arc<Mutex<()>>>>,
+}
+
+impl<T: Client> SharedStoreClient<T> {
+    pub fn new(remote: T, store: SharedStore, boundaries: XorbBoundaries) -> Self {
+        Self {
+            remote,
+            store,
+            boundaries,
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<T: Client + Send + Sync> SharedStoreClient<T> {
+    /// Reads a whole xorb from the store, or from the CAS into the store.
+    /// Returns None if the chunk boundaries of the xorb are unknown.
+    async fn fetch(
+        &self,
+        prefix: &str,
+        hash: &MerkleHash,
+    ) -> Result<Option<Vec<u8>>, CasClientError> {
+        let Some(chunk_boundaries) = self.boundaries.get(hash).await else {
+            debug!("No chunk boundaries known for {hash}, not using the shared store");
+            return Ok(None);
+        };
+        if let Some(data) = self.store.get(prefix, hash).await {
+            return Ok(Some(data));
+        }
+
+        let lock = self
+            .in_flight
+            .lock()
+            .await
+            .entry(*hash)
+            .or_default()
+            .clone();
+        let _guard = lock.lock().await;
+        let result = match self.store.get(prefix, hash).await {
+            Some(data) => Ok(data),
+            None => {
+                let data = self.remote.get(prefix, hash).await;
+                if let Ok(data) = &data {
+                    self.keep(prefix, hash, data.clone(), chunk_boundaries)
+                        .await;
+                }
+                data
+            }
+        };
+        self.in_flight.lock().await.remove(hash);
+        result.map(Some)
+    }
+
+    async fn keep(
+        &self,
+        prefix: &str,
+        hash: &MerkleHash,
+        data: Vec<u8>,
+        chunk_boundaries: Vec<u64>,
+    ) {
+        if let Err(e) = self
+            .store
+            .insert(prefix, hash, data, chunk_boundaries)
+            .await
+        {
+            warn!("Unable to keep {prefix}/{hash} in the shared store: {e}");
+        }
+    }
+}
+
+#[async_trait]
+impl<T: Client + Send + Sync> Client for SharedStoreClient<T> {
+    async fn put(
+        &self,
+        prefix: &str,
+        hash: &MerkleHash,
+        data: Vec<u8>,
+        chunk_boundaries: Vec<u64>,
+    ) -> Result<(), CasClientError> {
+        self.remote
+            .put(prefix, hash, data.clone(), chunk_boundaries.clone())
+            .await?;
+        self.keep(prefix, hash, data, chunk_boundaries).await;
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), CasClientError> {
+        self.remote.flush().await
+    }
+
+    async fn get(&self, prefix: &str, hash: &MerkleHash) -> Result<Vec<u8>, CasClientError> {
+        match self.fetch(prefix, hash).await? {
+            Some(data) => Ok(data),
+            None => self.remote.get(prefix, hash).await,
+        }
+    }
+
+    async fn get_object_range(
+        &self,
+        prefix: &str,
+        hash: &MerkleHash,
+        ranges: Vec<(u64, u64)>,
+    ) -> Result<Vec<Vec<u8>>, CasClientError> {
+        let Some(data) = self.fetch(prefix, hash).await? else {
+            return self.remote.get_object_range(prefix, hash, ranges).await;
+        };
+        ranges
+            .into_iter()
+            .map(|(start, end)| {
+                data.get(start as usize..end as usize)
+                    .map(|range| range.to_vec())
+                    .ok_or(CasClientError::InvalidRange)
+            })
+            .collect()
+    }
+
+    async fn get_length(&self, prefix: &str, hash: &MerkleHash) -> Result<u64, CasClientError> {
+        match self
+            .boundaries
+            .get(hash)
+            .await
+            .and_then(|b| b.last().copied())
+        {
+            Some(length) => Ok(length),
+            None => self.remote.get_length(prefix, hash).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cas_client::LocalClient;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pushed_xorbs_are_kept() {
+        let remote_dir = TempDir::new().unwrap();
+        let store_dir = TempDir::new().unwrap();
+        let repo_dir = TempDir::new().unwrap();
+        let client = SharedStoreClient::new(
+            LocalClient::new(remote_dir.path(), false),
+            SharedStore::new(store_dir.path(), repo_dir.path()).unwrap(),
+            XorbBoundaries::new(vec![]),
+        );
+
+        let data = vec![5u8; 100];
+        let hash = merklehash::compute_data_hash(&data);
+        client
+            .put("default", &hash, data.clone(), vec![100])
+            .await
+            .unwrap();
+        assert_eq!(client.store.get("default", &hash).await.unwrap(), data);
+
+        // Without known chunk boundaries, reads go to the remote.
+        assert_eq!(
+            client
+                .get_object_range("default", &hash, vec![(10, 20)])
+                .await
+                .unwrap(),
+            vec![data[10..20].to_vec()]
+        );
+    }
+}
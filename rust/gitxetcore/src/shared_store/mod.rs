@@ -0,0 +1,12 @@
+//! A machine-global store of xorbs shared by the repositories on this
+//! machine, so that forks and clones of the same data keep one copy of it.
+//!
+//! With `store.path` set, xorbs read from or pushed to the CAS are kept
+//! whole in the store, and each repository records a reference to the xorbs
+//! it uses. `git xet gc` drops the references of repositories that no longer
+//! exist and deletes the xorbs no repository references.
+mod client;
+mod store;
+
+pub use client::SharedStoreClient;
+pub use store::{gc, GcSummary, SharedStore};
@@ -0,0 +1,230 @@
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use cas::filelock::{FileLock, DEFAULT_LOCK_TIMEOUT};
+use cas_client::{CasClientError, Client, LocalClient};
+use merklehash::{HashAlgorithm, MerkleHash};
+use tracing::{debug, info};
+
+use crate::config::XetConfig;
+use crate::errors::Result;
+
+/// The directory xorbs are stored in, in the local CAS format.
+const XORBS_DIR: &str = "xorbs";
+/// The directory holding, for each repository, an empty file per xorb it
+/// references.
+const REFS_DIR: &str = "refs";
+/// The directory holding, for each repository, a file with its path.
+const REPOS_DIR: &str = "repos";
+/// Locked while xorbs are inserted or collected.
+const STORE_LOCK_FILE: &str = "store.lock";
+
+/// The xorbs shared by the repositories on this machine, as seen from one of
+/// them.
+#[derive(Debug)]
+pub struct SharedStore {
+    root: PathBuf,
+    xorbs: LocalClient,
+    refs: PathBuf,
+}
+
+impl SharedStore {
+    /// Opens the store at root for the repository at repo_path, registering
+    /// the repository with it.
+    pub fn new(root: &Path, repo_path: &Path) -> io::Result<Self> {
+        let repo_id = repo_id(repo_path);
+        let xorbs = root.join(XORBS_DIR);
+        let refs = root.join(REFS_DIR).join(&repo_id);
+        let repos = root.join(REPOS_DIR);
+        fs::create_dir_all(&xorbs)?;
+        fs::create_dir_all(&refs)?;
+        fs::create_dir_all(&repos)?;
+
+        let registration = repos.join(&repo_id);
+        if !registration.exists() {
+            cas::safeio::write_all_file_safe(
+                &registration,
+                repo_path.to_string_lossy().as_bytes(),
+            )?;
+        }
+        Ok(Self {
+            root: root.to_path_buf(),
+            xorbs: LocalClient::new(&xorbs, true),
+            refs,
+        })
+    }
+
+    /// Returns the store configured for this repository, if there is one,
+    /// validating the xorbs inserted against hash_algorithm, the hash
+    /// algorithm of the repository.
+    pub fn from_config(config: &XetConfig, hash_algorithm: HashAlgorithm) -> Result<Option<Self>> {
+        let (Some(root), Some(repo_path)) = (
+            config.store.path.as_ref(),
+            config.repo_path_if_present.as_ref(),
+        ) else {
+            return Ok(None);
+        };
+        let mut store = Self::new(root, repo_path)?;
+        store.xorbs.hash_algorithm = hash_algorithm;
+        Ok(Some(store))
+    }
+
+    /// Reads a xorb from the store, referencing it from this repository.
+    pub async fn get(&self, prefix: &str, hash: &MerkleHash) -> Option<Vec<u8>> {
+        if !self.xorbs.get_path_for_entry(prefix, hash).is_file() {
+            return None;
+        }
+        if let Err(e) = self.add_ref(prefix, hash) {
+            debug!("Unable to reference {prefix}/{hash} in the shared store: {e}");
+            return None;
+        }
+        self.xorbs.get(prefix, hash).await.ok()
+    }
+
+    /// Stores a xorb and references it from this repository. The xorb is
+    /// rejected unless its Merkle root under chunk_boundaries is hash.
+    pub async fn insert(
+        &self,
+        prefix: &str,
+        hash: &MerkleHash,
+        data: Vec<u8>,
+        chunk_boundaries: Vec<u64>,
+    ) -> std::result::Result<(), CasClientError> {
+        let _lock = FileLock::acquire_async(self.root.join(STORE_LOCK_FILE), DEFAULT_LOCK_TIMEOUT)
+            .await
+            .map_err(|e| CasClientError::InternalError(e.into()))?;
+        self.xorbs.put(prefix, hash, data, chunk_boundaries).await?;
+        self.add_ref(prefix, hash)
+            .map_err(|e| CasClientError::InternalError(e.into()))
+    }
+
+    fn add_ref(&self, prefix: &str, hash: &MerkleHash) -> io::Result<()> {
+        let entry = self.xorbs.get_path_for_entry(prefix, hash);
+        let Some(name) = entry.file_name() else {
+            return Ok(());
+        };
+        let marker = self.refs.join(name);
+        if !marker.exists() {
+            OpenOptions::new().create(true).write(true).open(marker)?;
+        }
+        Ok(())
+    }
+}
+
+/// Identifies a repository by its path.
+fn repo_id(repo_path: &Path) -> String {
+    let repo_path = repo_path
+        .canonicalize()
+        .unwrap_or_else(|_| repo_path.to_path_buf());
+    merklehash::compute_data_hash(repo_path.to_string_lossy().as_bytes()).hex()
+}
+
+/// What a collection of the store removed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GcSummary {
+    pub repos: usize,
+    pub xorbs: usize,
+    pub bytes: u64,
+}
+
+/// Drops the references of the repositories that no longer exist, then
+/// deletes the xorbs no repository references.
+pub async fn gc(root: &Path) -> Result<GcSummary> {
+    let mut summary = GcSummary::default();
+    if !root.is_dir() {
+        return Ok(summary);
+    }
+    let _lock = FileLock::acquire_async(root.join(STORE_LOCK_FILE), DEFAULT_LOCK_TIMEOUT).await?;
+
+    let repos = root.join(REPOS_DIR);
+    if repos.is_dir() {
+        for entry in fs::read_dir(&repos)? {
+            let entry = entry?;
+            let repo_path = fs::read_to_string(entry.path())?;
+            if Path::new(repo_path.trim()).exists() {
+                continue;
+            }
+            info!("Dropping the shared store references of {repo_path:?}");
+            let refs = root.join(REFS_DIR).join(entry.file_name());
+            if refs.is_dir() {
+                fs::remove_dir_all(refs)?;
+            }
+            fs::remove_file(entry.path())?;
+            summary.repos += 1;
+        }
+    }
+
+    let mut referenced = HashSet::new();
+    let refs = root.join(REFS_DIR);
+    if refs.is_dir() {
+        for repo_refs in fs::read_dir(&refs)? {
+            for entry in fs::read_dir(repo_refs?.path())? {
+                referenced.insert(entry?.file_name());
+            }
+        }
+    }
+
+    let xorbs = root.join(XORBS_DIR);
+    if xorbs.is_dir() {
+        for entry in fs::read_dir(&xorbs)? {
+            let entry = entry?;
+            if referenced.contains(&entry.file_name()) {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+            debug!("Deleting unreferenced xorb {:?}", entry.path());
+            // Xorbs are written read-only, which prevents removal on Windows.
+            let mut permissions = metadata.permissions();
+            #[allow(clippy::permissions_set_readonly_false)]
+            permissions.set_readonly(false);
+            fs::set_permissions(entry.path(), permissions)?;
+            fs::remove_file(entry.path())?;
+            summary.xorbs += 1;
+            summary.bytes += metadata.len();
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_gc_keeps_referenced_xorbs() {
+        let root = TempDir::new().unwrap();
+        let repo_a = TempDir::new().unwrap();
+        let repo_b = TempDir::new().unwrap();
+        let store_a = SharedStore::new(root.path(), repo_a.path()).unwrap();
+        let store_b = SharedStore::new(root.path(), repo_b.path()).unwrap();
+
+        let data = vec![1u8; 1000];
+        let hash = merklehash::compute_data_hash(&data);
+        store_a
+            .insert("default", &hash, data.clone(), vec![1000])
+            .await
+            .unwrap();
+        assert_eq!(store_b.get("default", &hash).await.unwrap(), data);
+
+        // Both repositories exist, so nothing is collected.
+        assert_eq!(gc(root.path()).await.unwrap(), GcSummary::default());
+
+        // The xorb is still referenced by the second repository.
+        drop(repo_a);
+        let summary = gc(root.path()).await.unwrap();
+        assert_eq!((summary.repos, summary.xorbs), (1, 0));
+        assert_eq!(store_b.get("default", &hash).await.unwrap(), data);
+
+        drop(repo_b);
+        let summary = gc(root.path()).await.unwrap();
+        assert_eq!((summary.repos, summary.xorbs), (1, 1));
+        assert!(store_b.get("default", &hash).await.is_none());
+    }
+}
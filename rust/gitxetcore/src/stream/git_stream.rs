@@ -216,6 +216,13 @@ impl<R: Read + Send + 'static, W: Write> GitStreamInterface<R, W> {
         }
     }
 
+    /// The clean and smudge progress reporters, if progress is reported.
+    pub fn progress_reporters(&self) -> impl Iterator<Item = &Arc<DataProgressReporter>> {
+        [&self.clean_progress, &self.smudge_progress]
+            .into_iter()
+            .flatten()
+    }
+
     pub fn new(io_reader: R, io_writer: W, repo: PointerFileTranslator) -> Self {
         Self {
             reader: GitStreamReadIterator::new(io_reader),
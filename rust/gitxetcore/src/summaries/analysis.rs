@@ -1,14 +1,70 @@
 use super::csv::{CSVAnalyzer, CSVSummary};
+use crate::config::{AnalyzerSettings, SummariesSettings};
 use crate::errors::Result;
 use libmagic::libmagic::LibmagicSummary;
 use serde::{Deserialize, Serialize};
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::path::Path;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering::SeqCst;
-use tracing::{error, warn};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
 #[derive(Default)]
 pub struct FileAnalyzers {
     pub csv: Option<CSVAnalyzer>,
+    csv_limits: AnalyzerLimits,
+}
+
+/// Enforces the settings of an analyzer on the file it summarizes. Some
+/// analyzers are slow or crash on pathological inputs, so the analyzer is
+/// given up on the file once it is excluded at the size read so far, once it
+/// has spent longer than its timeout on it, or if it panics.
+#[derive(Default)]
+pub struct AnalyzerLimits {
+    name: &'static str,
+    settings: AnalyzerSettings,
+    spent: Duration,
+}
+
+impl AnalyzerLimits {
+    pub fn new(name: &'static str, settings: &AnalyzerSettings) -> Self {
+        Self {
+            name,
+            settings: settings.clone(),
+            spent: Duration::ZERO,
+        }
+    }
+
+    /// Runs a step of the analyzer on the file at path, size bytes of which
+    /// were read. None if the analyzer is given up on the file, in which case
+    /// its summary of the file is dropped.
+    pub fn run<T>(&mut self, path: &Path, size: u64, step: impl FnOnce() -> T) -> Option<T> {
+        if !self.settings.runs_on(path, size) {
+            info!(
+                "Not running the {} analyzer on {path:?}, excluded at {size} bytes",
+                self.name
+            );
+            return None;
+        }
+        let start = Instant::now();
+        let result = catch_unwind(AssertUnwindSafe(step));
+        self.spent += start.elapsed();
+        let Ok(result) = result else {
+            error!(
+                "The {} analyzer panicked on {path:?}; skipping its summary of the file",
+                self.name
+            );
+            return None;
+        };
+        if let Some(timeout) = self.settings.timeout.filter(|t| self.spent > *t) {
+            warn!(
+                "The {} analyzer took longer than {timeout:?} on {path:?}; skipping its summary of the file",
+                self.name
+            );
+            return None;
+        }
+        Some(result)
+    }
 }
 
 lazy_static::lazy_static! {
@@ -17,15 +73,45 @@ lazy_static::lazy_static! {
 const CSV_WARNING_THRESHOLD: usize = 3;
 
 impl FileAnalyzers {
-    fn process_chunk_impl(&mut self, chunk: &[u8]) -> Result<()> {
+    /// The analyzers summarizing the file at path as it is cleaned, None if
+    /// none of them do.
+    pub fn for_path(
+        path: &Path,
+        settings: &SummariesSettings,
+        silence_warnings: bool,
+    ) -> Option<Self> {
+        let delimiter = match path.extension().and_then(|e| e.to_str()) {
+            Some("csv") => b',',
+            Some("tsv") => b'\t',
+            _ => return None,
+        };
+        if !settings.csv.runs_on(path, 0) {
+            debug!("CSV analyzer disabled or excluded for {path:?}");
+            return None;
+        }
+        debug!("Including CSV analyzer for {path:?}");
+        Some(Self {
+            csv: Some(CSVAnalyzer::new(silence_warnings, delimiter)),
+            csv_limits: AnalyzerLimits::new("csv", &settings.csv),
+        })
+    }
+
+    fn process_chunk_impl(&mut self, chunk: &[u8], file_path: &Path, size: u64) -> Result<()> {
         if let Some(csv) = &mut self.csv {
-            csv.process_chunk(chunk)?;
+            match self
+                .csv_limits
+                .run(file_path, size, || csv.process_chunk(chunk))
+            {
+                Some(result) => result?,
+                None => self.csv = None,
+            }
         }
         Ok(())
     }
 
     pub fn process_chunk(&mut self, chunk: &[u8], file_path: &Path, chunk_offset: usize) {
-        let result = self.process_chunk_impl(chunk);
+        let size = (chunk_offset + chunk.len()) as u64;
+        let result = self.process_chunk_impl(chunk, file_path, size);
 
         match result {
             Ok(_) => (),
@@ -41,16 +127,20 @@ impl FileAnalyzers {
         };
     }
 
-    fn finalize_impl(&mut self) -> Result<FileSummary> {
+    fn finalize_impl(&mut self, file_path: &Path) -> Result<FileSummary> {
         let mut ret = FileSummary::default();
         if let Some(csv) = &mut self.csv {
-            ret.csv = csv.finalize()?;
+            // Exclusions by size were checked as the chunks were processed.
+            match self.csv_limits.run(file_path, 0, || csv.finalize()) {
+                Some(summary) => ret.csv = summary?,
+                None => self.csv = None,
+            }
         }
         Ok(ret)
     }
 
     pub fn finalize(&mut self, file_path: &Path) -> Option<FileSummary> {
-        let result = self.finalize_impl();
+        let result = self.finalize_impl(file_path);
 
         if let Some(csv) = &mut self.csv {
             if let Some(warning) = csv.get_parse_warnings() {
@@ -132,3 +222,49 @@ impl FileSummary {
         ret
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ExcludeRule;
+
+    #[test]
+    fn test_analyzer_limits() {
+        let settings = AnalyzerSettings {
+            enabled: true,
+            exclude: vec![ExcludeRule::parse("*.bin>1KB").unwrap()],
+            timeout: Some(Duration::from_millis(50)),
+        };
+        let path = Path::new("data/a.bin");
+
+        let mut limits = AnalyzerLimits::new("test", &settings);
+        assert_eq!(limits.run(path, 100, || 1), Some(1));
+        // Given up once the file grows past the excluded size.
+        assert_eq!(limits.run(path, 2000, || 2), None);
+
+        let mut limits = AnalyzerLimits::new("test", &settings);
+        assert_eq!(
+            limits.run(path, 0, || panic!("pathological input")),
+            None::<()>
+        );
+
+        let mut limits = AnalyzerLimits::new("test", &settings);
+        let slow = || std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(limits.run(path, 0, slow), Some(()));
+        // The timeout is for the whole file, across steps.
+        assert_eq!(limits.run(path, 0, slow), None);
+    }
+
+    #[test]
+    fn test_file_analyzers_for_path() {
+        let mut settings = SummariesSettings::default();
+        assert!(FileAnalyzers::for_path(Path::new("a.csv"), &settings, true).is_some());
+        assert!(FileAnalyzers::for_path(Path::new("a.tsv"), &settings, true).is_some());
+        assert!(FileAnalyzers::for_path(Path::new("a.txt"), &settings, true).is_none());
+
+        settings.csv.exclude = vec![ExcludeRule::parse("logs/*").unwrap()];
+        assert!(FileAnalyzers::for_path(Path::new("logs/a.csv"), &settings, true).is_none());
+        settings.csv.enabled = false;
+        assert!(FileAnalyzers::for_path(Path::new("a.csv"), &settings, true).is_none());
+    }
+}
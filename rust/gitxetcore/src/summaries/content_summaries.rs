@@ -0,0 +1,152 @@
+//! Summaries of the content of files stored as pointer files, by the xet
+//! hash of the content.
+//!
+//! The content of a pointer file is not in the repository, so summarizing
+//! it means downloading it. `git xet dir-summary --remote-summaries` asks
+//! the remote for the summaries it has precomputed instead, and only
+//! downloads and analyzes the files it has none for. Summaries from either
+//! source are cached locally, so each content is summarized once.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+
+use super::languages::{summarize_language, LanguageStats};
+
+/// The language of a source file and its lines.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq, Clone)]
+pub struct LanguageSummary {
+    pub name: String,
+    pub stats: LanguageStats,
+}
+
+/// The summary of the content of a file.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq, Clone)]
+pub struct ContentSummary {
+    /// None if the file is not a source file.
+    #[serde(default)]
+    pub language: Option<LanguageSummary>,
+}
+
+impl ContentSummary {
+    /// Summarizes the content of the file at path.
+    pub fn from_content(path: &Path, content: &[u8]) -> Self {
+        Self {
+            language: summarize_language(path, content).map(|(name, stats)| LanguageSummary {
+                name: name.to_string(),
+                stats,
+            }),
+        }
+    }
+}
+
+/// The summaries of file contents computed or fetched so far, by xet hash,
+/// stored in a file.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ContentSummaryCache {
+    #[serde(skip)]
+    backing_file: PathBuf,
+    dict: HashMap<String, ContentSummary>,
+
+    // modification flag
+    #[serde(skip)]
+    is_dirty: bool,
+}
+
+impl ContentSummaryCache {
+    /// Loads the cache stored at path; it's empty if there is none or it
+    /// can't be read.
+    pub fn load(path: &Path) -> Self {
+        let mut cache = File::open(path)
+            .ok()
+            .and_then(|reader| {
+                bincode::deserialize_from::<_, Self>(reader)
+                    .map_err(|e| error!("Failed to load content summary cache: {e}"))
+                    .ok()
+            })
+            .unwrap_or_default();
+        cache.backing_file = path.to_path_buf();
+        cache
+    }
+
+    pub fn get(&self, hash: &str) -> Option<&ContentSummary> {
+        self.dict.get(hash)
+    }
+
+    pub fn insert(&mut self, hash: String, summary: ContentSummary) {
+        if self.dict.get(&hash) != Some(&summary) {
+            self.dict.insert(hash, summary);
+            self.is_dirty = true;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.dict.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dict.is_empty()
+    }
+
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        if !self.is_dirty {
+            return Ok(());
+        }
+        let dir = self.backing_file.parent().ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unable to find the parent directory of {:?}",
+                self.backing_file
+            )
+        })?;
+        std::fs::create_dir_all(dir)?;
+        let tempfile = tempfile::Builder::new()
+            .prefix(&format!("{}.", std::process::id()))
+            .suffix(".db")
+            .tempfile_in(dir)?;
+        {
+            let mut writer = BufWriter::new(&tempfile);
+            bincode::serialize_into(&mut writer, &self)?;
+            writer.flush()?;
+        }
+        tempfile.persist(&self.backing_file).map_err(|e| e.error)?;
+        debug!("Flushed content summary cache to {:?}", self.backing_file);
+        self.is_dirty = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_summary_cache() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("xet/content-summaries.db");
+        let mut cache = ContentSummaryCache::load(&path);
+        assert!(cache.is_empty());
+
+        let source = ContentSummary::from_content(Path::new("main.py"), b"# main\nprint(1)\n");
+        assert_eq!(source.language.as_ref().unwrap().name, "Python");
+        assert_eq!(source.language.as_ref().unwrap().stats.code, 1);
+        cache.insert("a".to_string(), source.clone());
+        // Files that are not source are cached too, to not analyze them again.
+        let data = ContentSummary::from_content(Path::new("data.bin"), &[0, 1, 2]);
+        assert_eq!(data, ContentSummary::default());
+        cache.insert("b".to_string(), data);
+        cache.flush().unwrap();
+
+        let cache = ContentSummaryCache::load(&path);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("a"), Some(&source));
+        assert_eq!(cache.get("b"), Some(&ContentSummary::default()));
+        assert_eq!(cache.get("c"), None);
+
+        // An unreadable cache is empty.
+        std::fs::write(&path, b"not a cache").unwrap();
+        assert!(ContentSummaryCache::load(&path).is_empty());
+    }
+}
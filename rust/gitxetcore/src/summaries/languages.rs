@@ -0,0 +1,417 @@
+//! Detects the programming language of source files and counts their lines
+//! of code, comments and blanks, in the style of tokei.
+//!
+//! The language is picked from the file name or extension, or the
+//! interpreter of a shebang line, and confirmed from the content: files that
+//! look binary are not source files, whatever their name. Comments are
+//! recognized by the line and block comment markers of the language; markers
+//! inside string literals are not told apart.
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// The version of the language analyzer; directory summaries computed with
+/// another version are recomputed.
+pub const LANGUAGES_SUMMARY_VERSION: u32 = 1;
+
+/// Files larger than this are not analyzed; source files rarely are, and
+/// files this large are generated or data.
+pub const LANGUAGES_MAX_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+/// The number of bytes at the start of a file checked for NUL bytes, which
+/// mark it as binary.
+const BINARY_CHECK_LEN: usize = 8000;
+
+pub struct Language {
+    pub name: &'static str,
+    extensions: &'static [&'static str],
+    filenames: &'static [&'static str],
+    interpreters: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+}
+
+const C_BLOCK: Option<(&str, &str)> = Some(("/*", "*/"));
+
+static LANGUAGES: &[Language] = &[
+    Language {
+        name: "C",
+        extensions: &["c", "h"],
+        filenames: &[],
+        interpreters: &[],
+        line_comments: &["//"],
+        block_comment: C_BLOCK,
+    },
+    Language {
+        name: "C++",
+        extensions: &["cc", "cpp", "cxx", "hh", "hpp", "hxx"],
+        filenames: &[],
+        interpreters: &[],
+        line_comments: &["//"],
+        block_comment: C_BLOCK,
+    },
+    Language {
+        name: "C#",
+        extensions: &["cs"],
+        filenames: &[],
+        interpreters: &[],
+        line_comments: &["//"],
+        block_comment: C_BLOCK,
+    },
+    Language {
+        name: "CSS",
+        extensions: &["css", "scss", "less"],
+        filenames: &[],
+        interpreters: &[],
+        line_comments: &[],
+        block_comment: C_BLOCK,
+    },
+    Language {
+        name: "Dockerfile",
+        extensions: &["dockerfile"],
+        filenames: &["dockerfile"],
+        interpreters: &[],
+        line_comments: &["#"],
+        block_comment: None,
+    },
+    Language {
+        name: "Go",
+        extensions: &["go"],
+        filenames: &[],
+        interpreters: &[],
+        line_comments: &["//"],
+        block_comment: C_BLOCK,
+    },
+    Language {
+        name: "Haskell",
+        extensions: &["hs"],
+        filenames: &[],
+        interpreters: &[],
+        line_comments: &["--"],
+        block_comment: Some(("{-", "-}")),
+    },
+    Language {
+        name: "HTML",
+        extensions: &["htm", "html"],
+        filenames: &[],
+        interpreters: &[],
+        line_comments: &[],
+        block_comment: Some(("<!--", "-->")),
+    },
+    Language {
+        name: "Java",
+        extensions: &["java"],
+        filenames: &[],
+        interpreters: &[],
+        line_comments: &["//"],
+        block_comment: C_BLOCK,
+    },
+    Language {
+        name: "JavaScript",
+        extensions: &["cjs", "js", "jsx", "mjs"],
+        filenames: &[],
+        interpreters: &["node"],
+        line_comments: &["//"],
+        block_comment: C_BLOCK,
+    },
+    Language {
+        name: "JSON",
+        extensions: &["json"],
+        filenames: &[],
+        interpreters: &[],
+        line_comments: &[],
+        block_comment: None,
+    },
+    Language {
+        name: "Julia",
+        extensions: &["jl"],
+        filenames: &[],
+        interpreters: &["julia"],
+        line_comments: &["#"],
+        block_comment: Some(("#=", "=#")),
+    },
+    Language {
+        name: "Kotlin",
+        extensions: &["kt", "kts"],
+        filenames: &[],
+        interpreters: &[],
+        line_comments: &["//"],
+        block_comment: C_BLOCK,
+    },
+    Language {
+        name: "Lua",
+        extensions: &["lua"],
+        filenames: &[],
+        interpreters: &["lua"],
+        line_comments: &["--"],
+        block_comment: Some(("--[[", "]]")),
+    },
+    Language {
+        name: "Makefile",
+        extensions: &["mk"],
+        filenames: &["makefile", "gnumakefile"],
+        interpreters: &["make"],
+        line_comments: &["#"],
+        block_comment: None,
+    },
+    Language {
+        name: "Markdown",
+        extensions: &["md", "markdown"],
+        filenames: &[],
+        interpreters: &[],
+        line_comments: &[],
+        block_comment: None,
+    },
+    Language {
+        name: "Perl",
+        extensions: &["pl", "pm"],
+        filenames: &[],
+        interpreters: &["perl"],
+        line_comments: &["#"],
+        block_comment: None,
+    },
+    Language {
+        name: "PHP",
+        extensions: &["php"],
+        filenames: &[],
+        interpreters: &["php"],
+        line_comments: &["//", "#"],
+        block_comment: C_BLOCK,
+    },
+    Language {
+        name: "Python",
+        extensions: &["py", "pyi", "pyx"],
+        filenames: &[],
+        interpreters: &["python", "python2", "python3"],
+        line_comments: &["#"],
+        block_comment: None,
+    },
+    Language {
+        name: "R",
+        extensions: &["r"],
+        filenames: &[],
+        interpreters: &["rscript"],
+        line_comments: &["#"],
+        block_comment: None,
+    },
+    Language {
+        name: "Ruby",
+        extensions: &["rb"],
+        filenames: &["gemfile", "rakefile"],
+        interpreters: &["ruby"],
+        line_comments: &["#"],
+        block_comment: Some(("=begin", "=end")),
+    },
+    Language {
+        name: "Rust",
+        extensions: &["rs"],
+        filenames: &[],
+        interpreters: &[],
+        line_comments: &["//"],
+        block_comment: C_BLOCK,
+    },
+    Language {
+        name: "Scala",
+        extensions: &["sc", "scala"],
+        filenames: &[],
+        interpreters: &[],
+        line_comments: &["//"],
+        block_comment: C_BLOCK,
+    },
+    Language {
+        name: "Shell",
+        extensions: &["bash", "sh", "zsh"],
+        filenames: &[],
+        interpreters: &["bash", "sh", "zsh"],
+        line_comments: &["#"],
+        block_comment: None,
+    },
+    Language {
+        name: "SQL",
+        extensions: &["sql"],
+        filenames: &[],
+        interpreters: &[],
+        line_comments: &["--"],
+        block_comment: C_BLOCK,
+    },
+    Language {
+        name: "Swift",
+        extensions: &["swift"],
+        filenames: &[],
+        interpreters: &[],
+        line_comments: &["//"],
+        block_comment: C_BLOCK,
+    },
+    Language {
+        name: "TOML",
+        extensions: &["toml"],
+        filenames: &[],
+        interpreters: &[],
+        line_comments: &["#"],
+        block_comment: None,
+    },
+    Language {
+        name: "TypeScript",
+        extensions: &["ts", "tsx"],
+        filenames: &[],
+        interpreters: &[],
+        line_comments: &["//"],
+        block_comment: C_BLOCK,
+    },
+    Language {
+        name: "YAML",
+        extensions: &["yaml", "yml"],
+        filenames: &[],
+        interpreters: &[],
+        line_comments: &["#"],
+        block_comment: None,
+    },
+];
+
+/// The files of a language and their lines.
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq, Clone)]
+pub struct LanguageStats {
+    pub files: u64,
+    pub code: u64,
+    pub comments: u64,
+    pub blanks: u64,
+}
+
+impl LanguageStats {
+    pub fn add(&mut self, other: &Self) {
+        self.files += other.files;
+        self.code += other.code;
+        self.comments += other.comments;
+        self.blanks += other.blanks;
+    }
+
+    pub fn lines(&self) -> u64 {
+        self.code + self.comments + self.blanks
+    }
+}
+
+/// The language of the source file at path with the content, if it's one.
+pub fn detect_language(path: &Path, content: &[u8]) -> Option<&'static Language> {
+    if content[..content.len().min(BINARY_CHECK_LEN)].contains(&0) {
+        return None;
+    }
+    let file_name = path.file_name()?.to_string_lossy().to_lowercase();
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+    let by_name = LANGUAGES.iter().find(|l| {
+        l.filenames.contains(&file_name.as_str())
+            || extension
+                .as_deref()
+                .map_or(false, |e| l.extensions.contains(&e))
+    });
+    by_name.or_else(|| shebang_language(content))
+}
+
+/// The language of the interpreter named in the shebang line of content,
+/// e.g. "#!/usr/bin/env python3".
+fn shebang_language(content: &[u8]) -> Option<&'static Language> {
+    let line = content.strip_prefix(b"#!")?.split(|&b| b == b'\n').next()?;
+    let line = std::str::from_utf8(line).ok()?;
+    let mut words = line.split_whitespace();
+    let mut interpreter = words.next()?.rsplit('/').next()?;
+    if interpreter == "env" {
+        interpreter = words.find(|w| !w.starts_with('-'))?;
+    }
+    LANGUAGES
+        .iter()
+        .find(|l| l.interpreters.contains(&interpreter))
+}
+
+/// Counts the lines of code, comments and blanks of a file of the language.
+pub fn count_lines(language: &Language, content: &str) -> LanguageStats {
+    let mut stats = LanguageStats {
+        files: 1,
+        ..Default::default()
+    };
+    let mut in_block = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            stats.blanks += 1;
+            continue;
+        }
+        if in_block {
+            stats.comments += 1;
+            if let Some((_, end)) = language.block_comment {
+                in_block = !line.contains(end);
+            }
+            continue;
+        }
+        // block comments first, as they may start like line comments, e.g.
+        // --[[ in Lua
+        match language.block_comment {
+            Some((start, end)) if line.starts_with(start) => {
+                stats.comments += 1;
+                in_block = !line[start.len()..].contains(end);
+            }
+            _ if language.line_comments.iter().any(|c| line.starts_with(c)) => {
+                stats.comments += 1;
+            }
+            Some((start, end)) => {
+                stats.code += 1;
+                // a block comment opened after code on the line
+                if let Some(i) = line.find(start) {
+                    in_block = !line[i + start.len()..].contains(end);
+                }
+            }
+            None => stats.code += 1,
+        }
+    }
+    stats
+}
+
+/// The language of the source file at path with the content, and its line
+/// counts, if it's a source file.
+pub fn summarize_language(path: &Path, content: &[u8]) -> Option<(&'static str, LanguageStats)> {
+    let language = detect_language(path, content)?;
+    let content = String::from_utf8_lossy(content);
+    Some((language.name, count_lines(language, &content)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_language() {
+        let name =
+            |path: &str, content: &[u8]| detect_language(Path::new(path), content).map(|l| l.name);
+        assert_eq!(name("src/main.rs", b"fn main() {}"), Some("Rust"));
+        assert_eq!(name("SCRIPT.PY", b"print(1)"), Some("Python"));
+        assert_eq!(name("build/Makefile", b"all:"), Some("Makefile"));
+        assert_eq!(name("bin/run", b"#!/usr/bin/env python3\n"), Some("Python"));
+        assert_eq!(name("bin/run", b"#!/bin/bash\n"), Some("Shell"));
+        assert_eq!(name("bin/run", b"echo"), None);
+        assert_eq!(name("data.bin", b"\x00\x01"), None);
+        // Binary content named like source isn't source.
+        assert_eq!(name("weights.c", b"\x7fELF\x00\x00"), None);
+    }
+
+    #[test]
+    fn test_count_lines() {
+        let rust = detect_language(Path::new("a.rs"), b"").unwrap();
+        let content = "// header\n\nfn main() {\n    /* a\n     b */\n    let x = 1; /* trailing\n    */\n}\n";
+        assert_eq!(
+            count_lines(rust, content),
+            LanguageStats {
+                files: 1,
+                code: 3,
+                comments: 4,
+                blanks: 1,
+            }
+        );
+
+        let python = detect_language(Path::new("a.py"), b"").unwrap();
+        let (name, stats) = summarize_language(Path::new("a.py"), b"# c\nx = 1\n\n").unwrap();
+        assert_eq!(name, python.name);
+        assert_eq!((stats.code, stats.comments, stats.blanks), (1, 1, 1));
+        assert_eq!(stats.lines(), 3);
+
+        let lua = detect_language(Path::new("a.lua"), b"").unwrap();
+        let stats = count_lines(lua, "--[[ a\nb ]]\n-- c\nprint(1)\n");
+        assert_eq!((stats.code, stats.comments), (1, 3));
+    }
+}
@@ -1,5 +1,7 @@
 pub mod analysis;
+pub mod content_summaries;
 pub mod csv;
+pub mod languages;
 pub mod summary_type;
 pub use libmagic::libmagic;
 mod summaries_plumb;
@@ -0,0 +1,257 @@
+//! A machine-readable report of the data a command transferred, for tracking
+//! the cost of pushes, fetches and checkouts in CI.
+//!
+//! With `--report <path>`, or by default when the CI environment variable is
+//! set, the commands moving data append one JSON line to the report when they
+//! finish: the bytes sent and received, the data deduplicated, the reads the
+//! cache served, the retried and throttled requests, and the wall time of
+//! each stage of the command.
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use cas_client::CAS_TRANSFER_STATS;
+use lazy_static::lazy_static;
+use serde::Serialize;
+
+use crate::config::XetConfig;
+use crate::constants::TRANSFER_REPORT_SUBDIR;
+use crate::data::{FILTER_BYTES_CLEANED, FILTER_CAS_BYTES_PRODUCED};
+use crate::errors::Result;
+
+static REPORTING: AtomicBool = AtomicBool::new(false);
+static DEDUP_LOGICAL_BYTES: AtomicU64 = AtomicU64::new(0);
+static DEDUP_STORED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+lazy_static! {
+    /// The time spent in each stage, in the order the stages first ran.
+    static ref STAGES: Mutex<Vec<(&'static str, Duration)>> = Mutex::new(Vec::new());
+}
+
+/// Turns on the collection of what is only gathered for the report.
+pub fn enable() {
+    REPORTING.store(true, Ordering::Relaxed);
+}
+
+/// Whether this process writes a report, so that work only needed for it can
+/// be skipped otherwise.
+pub fn is_enabled() -> bool {
+    REPORTING.load(Ordering::Relaxed)
+}
+
+/// Times a stage of the command until dropped. A stage running more than
+/// once, or in several tasks at once, sums the time of each run.
+#[must_use]
+pub struct Stage {
+    name: &'static str,
+    start: Instant,
+}
+
+pub fn stage(name: &'static str) -> Stage {
+    Stage {
+        name,
+        start: Instant::now(),
+    }
+}
+
+impl Drop for Stage {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        let mut stages = STAGES.lock().unwrap();
+        match stages.iter_mut().find(|(name, _)| *name == self.name) {
+            Some((_, total)) => *total += elapsed,
+            None => stages.push((self.name, elapsed)),
+        }
+    }
+}
+
+/// Records files of logical_bytes whose data takes stored_bytes once
+/// deduplicated, cleaned by an earlier process and pushed by this one.
+pub fn record_dedup(logical_bytes: u64, stored_bytes: u64) {
+    DEDUP_LOGICAL_BYTES.fetch_add(logical_bytes, Ordering::Relaxed);
+    DEDUP_STORED_BYTES.fetch_add(stored_bytes, Ordering::Relaxed);
+}
+
+/// Whether the CI environment variable, set by most CI systems, is set.
+pub fn in_ci() -> bool {
+    std::env::var_os("CI").is_some_and(|v| !v.is_empty() && v != "false" && v != "0")
+}
+
+/// Where the report of a command is written: the path given on the CLI, or
+/// in CI, the default path in the repository.
+pub fn report_path(cli_path: Option<&Path>, cfg: &XetConfig) -> Option<PathBuf> {
+    if let Some(path) = cli_path {
+        return Some(path.to_path_buf());
+    }
+    if !in_ci() {
+        return None;
+    }
+    cfg.repo_path_if_present
+        .as_ref()
+        .map(|git_dir| git_dir.join(TRANSFER_REPORT_SUBDIR))
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct DedupReport {
+    /// The size of the files cleaned or pushed.
+    pub logical_bytes: u64,
+    /// The new data they added once deduplicated.
+    pub stored_bytes: u64,
+    pub saved_bytes: u64,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct CacheReport {
+    /// The blocks read from the memory or disk cache, or from a read of the
+    /// same block in flight, and their bytes.
+    pub hits: u64,
+    pub hit_bytes: u64,
+    /// The blocks the cache fetched from the CAS.
+    pub misses: u64,
+    pub miss_bytes: u64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StageReport {
+    pub name: String,
+    pub wall_secs: f64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TransferReport {
+    pub command: String,
+    pub success: bool,
+    pub started_at: String,
+    pub wall_secs: f64,
+    pub uploaded_bytes: u64,
+    pub downloaded_bytes: u64,
+    pub dedup: DedupReport,
+    pub cache: CacheReport,
+    pub retries: u64,
+    pub throttles: u64,
+    pub stages: Vec<StageReport>,
+}
+
+impl TransferReport {
+    /// The report of everything recorded by this process so far.
+    pub fn collect(
+        command: &str,
+        success: bool,
+        started_at: chrono::DateTime<chrono::Utc>,
+        wall_time: Duration,
+    ) -> Self {
+        let counts = CAS_TRANSFER_STATS.counts();
+        let logical_bytes =
+            DEDUP_LOGICAL_BYTES.load(Ordering::Relaxed) + FILTER_BYTES_CLEANED.get();
+        let stored_bytes =
+            DEDUP_STORED_BYTES.load(Ordering::Relaxed) + FILTER_CAS_BYTES_PRODUCED.get();
+        Self {
+            command: command.to_string(),
+            success,
+            started_at: started_at.to_rfc3339(),
+            wall_secs: wall_time.as_secs_f64(),
+            uploaded_bytes: counts.uploaded_bytes,
+            downloaded_bytes: counts.downloaded_bytes,
+            dedup: DedupReport {
+                logical_bytes,
+                stored_bytes,
+                saved_bytes: logical_bytes.saturating_sub(stored_bytes),
+            },
+            cache: CacheReport {
+                hits: counts.cache_hits,
+                hit_bytes: counts.cache_hit_bytes,
+                misses: counts.cache_misses,
+                miss_bytes: counts.cache_miss_bytes,
+            },
+            retries: counts.retries,
+            throttles: counts.throttles,
+            stages: STAGES
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(name, time)| StageReport {
+                    name: name.to_string(),
+                    wall_secs: time.as_secs_f64(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether the command did nothing worth reporting, e.g. a filter process
+    /// git started without any file to clean or smudge.
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+            && self.uploaded_bytes == 0
+            && self.downloaded_bytes == 0
+            && self.dedup.logical_bytes == 0
+            && self.cache.hits == 0
+            && self.cache.misses == 0
+    }
+
+    /// Appends the report to path as a JSON line.
+    pub fn append_to(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let mut line = serde_json::to_string(self)?;
+        line.push('\n');
+        // A single write, so that the lines of concurrent processes, e.g. a
+        // filter process and the hook of the same push, do not interleave.
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transfer_report() {
+        drop(stage("test upload"));
+        {
+            let _sync = stage("test sync notes");
+            let _upload = stage("test upload");
+        }
+        record_dedup(1000, 400);
+
+        let report = TransferReport::collect("push", true, chrono::Utc::now(), Duration::ZERO);
+        assert!(!report.is_empty());
+        let names: Vec<_> = report
+            .stages
+            .iter()
+            .map(|s| s.name.as_str())
+            .filter(|name| name.starts_with("test "))
+            .collect();
+        assert_eq!(names, vec!["test upload", "test sync notes"]);
+        assert!(report.dedup.logical_bytes >= 1000);
+        assert_eq!(
+            report.dedup.saved_bytes,
+            report.dedup.logical_bytes - report.dedup.stored_bytes
+        );
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("xet/transfer-report.jsonl");
+        report.append_to(&path).unwrap();
+        report.append_to(&path).unwrap();
+        let lines: Vec<serde_json::Value> = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["command"], "push");
+        assert_eq!(
+            lines[0]["dedup"]["logical_bytes"],
+            report.dedup.logical_bytes
+        );
+        assert!(lines[0]["stages"].is_array());
+    }
+}
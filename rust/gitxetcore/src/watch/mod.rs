@@ -0,0 +1,17 @@
+//! Keeping a live summary of the working tree for editors and IDE plugins.
+//!
+//! `git xet watch` scans the working tree into a [WatchModel] of the type,
+//! size, and materialization of every file, then keeps it up to date from
+//! filesystem notifications, rescanning only the paths that changed. The
+//! model is queried over a local Unix socket with newline delimited JSON-RPC
+//! 2.0, e.g.
+//!
+//! ```ignore
+//! {"jsonrpc": "2.0", "id": 1, "method": "summary", "params": {"path": "data"}}
+//! ```
+//!
+//! See [rpc] for the methods.
+mod model;
+pub mod rpc;
+
+pub use model::{DirectorySummary, TypeSummary, WatchModel, WatchStatus};
@@ -0,0 +1,266 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use libmagic::libmagic::summarize_libmagic;
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::constants::POINTER_FILE_LIMIT;
+use crate::data::PointerFile;
+use crate::errors::Result;
+use crate::git_integration::is_git_special_files;
+
+/// What the model knows about a file in the working tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct FileEntry {
+    file_type: String,
+    display_name: String,
+    /// The size of the file, or of the file a pointer file stands for.
+    bytes: u64,
+    /// Whether the file is a pointer file that has not been materialized.
+    is_pointer: bool,
+}
+
+impl FileEntry {
+    fn read(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        if !metadata.is_file() {
+            return None;
+        }
+        let mut bytes = metadata.len();
+        let mut is_pointer = false;
+        if bytes <= POINTER_FILE_LIMIT as u64 {
+            let pointer_file = PointerFile::init_from_path(path.to_str().unwrap_or_default());
+            if pointer_file.is_valid() {
+                bytes = pointer_file.filesize();
+                is_pointer = true;
+            }
+        }
+        let libmagic = summarize_libmagic(path).unwrap_or_default();
+        Some(Self {
+            file_type: libmagic.file_type,
+            display_name: libmagic.file_type_simple,
+            bytes,
+            is_pointer,
+        })
+    }
+}
+
+/// The files of a type in a directory.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct TypeSummary {
+    pub file_type: String,
+    pub display_name: String,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// The files in a directory and its subdirectories, with their types by
+/// descending size.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct DirectorySummary {
+    pub path: String,
+    pub files: u64,
+    pub bytes: u64,
+    /// The files that are pointer files, not materialized in the working tree.
+    pub pointer_files: u64,
+    pub types: Vec<TypeSummary>,
+}
+
+/// The state of the whole model.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct WatchStatus {
+    pub root: PathBuf,
+    pub files: u64,
+    pub bytes: u64,
+    pub pointer_files: u64,
+    /// The number of times the model was updated since the initial scan.
+    pub updates: u64,
+    /// When the model was last updated, in seconds since the Unix epoch.
+    pub last_update: u64,
+}
+
+/// A summary of the files in a working tree, kept by `git xet watch`.
+///
+/// Paths are relative to the root of the working tree. The .git directory
+/// and the other git special files are left out.
+pub struct WatchModel {
+    root: PathBuf,
+    // Ordered so that the files under a directory are a contiguous range.
+    files: BTreeMap<PathBuf, FileEntry>,
+    updates: u64,
+    last_update: SystemTime,
+}
+
+impl WatchModel {
+    /// Scans the working tree at root.
+    pub fn scan(root: &Path) -> Result<Self> {
+        let mut model = Self {
+            root: root.to_path_buf(),
+            files: BTreeMap::new(),
+            updates: 0,
+            last_update: SystemTime::now(),
+        };
+        model.scan_path(Path::new(""));
+        Ok(model)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Rescans the files at path, relative or absolute, after a change. The
+    /// path may be a file or directory that was created, modified, or
+    /// removed.
+    pub fn update_path(&mut self, path: &Path) {
+        let path = path.strip_prefix(&self.root).unwrap_or(path);
+        if path.is_absolute() || path.components().any(|c| is_special(c.as_os_str())) {
+            return;
+        }
+        self.remove_under(path);
+        self.scan_path(path);
+        self.updates += 1;
+        self.last_update = SystemTime::now();
+    }
+
+    /// Summarizes the files under the directory at path, relative to the
+    /// root; the empty path summarizes the whole tree.
+    pub fn summary(&self, path: &Path) -> DirectorySummary {
+        let mut ret = DirectorySummary {
+            path: path.to_string_lossy().to_string(),
+            files: 0,
+            bytes: 0,
+            pointer_files: 0,
+            types: Vec::new(),
+        };
+        let mut types: HashMap<&str, TypeSummary> = HashMap::new();
+        for entry in self.entries_under(path) {
+            ret.files += 1;
+            ret.bytes += entry.bytes;
+            ret.pointer_files += entry.is_pointer as u64;
+            let t = types
+                .entry(entry.file_type.as_str())
+                .or_insert_with(|| TypeSummary {
+                    file_type: entry.file_type.clone(),
+                    display_name: entry.display_name.clone(),
+                    files: 0,
+                    bytes: 0,
+                });
+            t.files += 1;
+            t.bytes += entry.bytes;
+        }
+        ret.types = types.into_values().collect();
+        ret.types.sort_by(|a, b| {
+            b.bytes
+                .cmp(&a.bytes)
+                .then_with(|| a.file_type.cmp(&b.file_type))
+        });
+        ret
+    }
+
+    pub fn status(&self) -> WatchStatus {
+        let all = self.summary(Path::new(""));
+        WatchStatus {
+            root: self.root.clone(),
+            files: all.files,
+            bytes: all.bytes,
+            pointer_files: all.pointer_files,
+            updates: self.updates,
+            last_update: self
+                .last_update
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn entries_under<'a>(&'a self, path: &'a Path) -> impl Iterator<Item = &'a FileEntry> + 'a {
+        self.files
+            .range(path.to_path_buf()..)
+            .take_while(move |(p, _)| p.starts_with(path))
+            .map(|(_, e)| e)
+    }
+
+    fn remove_under(&mut self, path: &Path) {
+        let removed: Vec<PathBuf> = self
+            .files
+            .range(path.to_path_buf()..)
+            .take_while(|(p, _)| p.starts_with(path))
+            .map(|(p, _)| p.clone())
+            .collect();
+        for p in removed {
+            self.files.remove(&p);
+        }
+    }
+
+    fn scan_path(&mut self, path: &Path) {
+        let walker = WalkDir::new(self.root.join(path))
+            .into_iter()
+            .filter_entry(|e| !is_special(e.file_name()));
+        for entry in walker.filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let Ok(relative) = entry.path().strip_prefix(&self.root) else {
+                continue;
+            };
+            if let Some(file) = FileEntry::read(entry.path()) {
+                self.files.insert(relative.to_path_buf(), file);
+            }
+        }
+    }
+}
+
+fn is_special(name: &std::ffi::OsStr) -> bool {
+    is_git_special_files(name.to_str().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_watch_model() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let root = dir.path();
+        fs::create_dir_all(root.join("data/raw"))?;
+        fs::create_dir_all(root.join(".git"))?;
+        fs::write(root.join("data/a.csv"), vec![b'a'; 100])?;
+        fs::write(root.join("data/raw/b.csv"), vec![b'b'; 50])?;
+        fs::write(root.join("data.txt"), vec![b'c'; 10])?;
+        fs::write(root.join(".git/config"), vec![b'd'; 10])?;
+
+        let mut model = WatchModel::scan(root)?;
+        let status = model.status();
+        assert_eq!(status.files, 3);
+        assert_eq!(status.bytes, 160);
+
+        let data = model.summary(Path::new("data"));
+        assert_eq!(data.files, 2);
+        assert_eq!(data.bytes, 150);
+        assert_eq!(data.types.len(), 1);
+        assert_eq!(data.types[0].files, 2);
+
+        fs::write(root.join("data/raw/c.csv"), vec![b'c'; 25])?;
+        model.update_path(&root.join("data/raw/c.csv"));
+        assert_eq!(model.summary(Path::new("data/raw")).bytes, 75);
+
+        fs::remove_dir_all(root.join("data/raw"))?;
+        model.update_path(Path::new("data/raw"));
+        assert_eq!(model.summary(Path::new("data")).files, 1);
+        assert_eq!(model.status().files, 2);
+        assert_eq!(model.status().updates, 2);
+
+        // Changes in .git are ignored.
+        model.update_path(&root.join(".git/config"));
+        assert_eq!(model.status().updates, 2);
+
+        crate::schemas::assert_matches_schema(
+            "status",
+            &serde_json::to_value(model.status()).unwrap(),
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+//! The JSON-RPC methods of `git xet watch`, served with [crate::jsonrpc].
+//!
+//! Methods:
+//! - `status`: the [super::WatchStatus] of the whole working tree.
+//! - `summary`: the [super::DirectorySummary] of the directory at
+//!   `params.path`, relative to the working tree root. Defaults to the
+//!   whole tree.
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Deserialize;
+use tokio::sync::RwLock;
+use tracing::debug;
+
+use super::WatchModel;
+use crate::errors::Result;
+use crate::jsonrpc::{self, Request, Response, INVALID_PARAMS};
+
+#[derive(Deserialize, Default, Debug)]
+struct SummaryParams {
+    #[serde(default)]
+    path: PathBuf,
+}
+
+/// Answers one request line against the model.
+pub fn handle_request(model: &WatchModel, line: &str) -> Response {
+    let request = match Request::parse(line) {
+        Ok(r) => r,
+        Err(response) => return response,
+    };
+    debug!("XET watch: {} request", request.method);
+
+    match request.method.as_str() {
+        "status" => request.result(model.status()),
+        "summary" => {
+            let params: SummaryParams = match request.params() {
+                Ok(p) => p,
+                Err(response) => return response,
+            };
+            if params.path.is_absolute() {
+                return request.error(
+                    INVALID_PARAMS,
+                    "path must be relative to the working tree root".to_string(),
+                );
+            }
+            request.result(model.summary(&params.path))
+        }
+        _ => request.method_not_found(),
+    }
+}
+
+/// Serves the model on the Unix socket at socket_path until the task is
+/// dropped.
+pub async fn serve(socket_path: &Path, model: Arc<RwLock<WatchModel>>) -> Result<()> {
+    jsonrpc::serve(socket_path, move |line| {
+        let model = model.clone();
+        async move { handle_request(&*model.read().await, &line) }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jsonrpc::{METHOD_NOT_FOUND, PARSE_ERROR};
+    use serde_json::Value;
+
+    #[test]
+    fn test_handle_request() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::create_dir_all(dir.path().join("data"))?;
+        std::fs::write(dir.path().join("data/a.csv"), "a,b\n1,2\n")?;
+        let model = WatchModel::scan(dir.path())?;
+
+        let response = handle_request(
+            &model,
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "summary", "params": {"path": "data"}}"#,
+        );
+        assert_eq!(response.id, Value::from(1));
+        let result = response.result.unwrap();
+        assert_eq!(result["files"], 1);
+        assert_eq!(result["bytes"], 8);
+
+        let response = handle_request(&model, r#"{"id": 2, "method": "status"}"#);
+        assert_eq!(response.result.unwrap()["files"], 1);
+
+        let response = handle_request(&model, r#"{"id": 3, "method": "commit"}"#);
+        assert_eq!(response.error.unwrap().code, METHOD_NOT_FOUND);
+
+        let response = handle_request(
+            &model,
+            r#"{"id": 4, "method": "summary", "params": {"path": "/etc"}}"#,
+        );
+        assert_eq!(response.error.unwrap().code, INVALID_PARAMS);
+
+        let response = handle_request(&model, "not json");
+        assert_eq!(response.error.unwrap().code, PARSE_ERROR);
+        Ok(())
+    }
+}
@@ -10,11 +10,15 @@ mod xet_repo;
 mod xet_repo_manager;
 
 use anyhow::anyhow;
-use bbq_queries::BbqClient;
+use bbq_queries::{git_remote_to_base_url, BbqClient};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use tracing::debug;
 use url::Url;
 
+use crate::config::XetConfig;
+use crate::summaries::content_summaries::ContentSummary;
+
 pub use dir_entry::DirEntry;
 pub use file_open_flags::*;
 pub use file_operations::*;
@@ -33,6 +37,10 @@ pub struct AuxRepoInfo {
 pub struct XetRepoInfo {
     pub mdb_version: String,
     pub repo_salt: Option<String>,
+    /// The hash algorithms the remote accepts for the repository, most
+    /// preferred first. Empty for remotes that only accept the default one.
+    #[serde(default)]
+    pub hash_algorithms: Vec<String>,
 }
 
 /// this is the JSON structure returned by the xetea repo info function,
@@ -52,3 +60,185 @@ pub async fn get_repo_info(
     debug!("{res_str:?}");
     Ok((serde_json::de::from_slice(&response)?, response))
 }
+
+/// Queries the repo info of the repository at the remote url.
+pub async fn get_remote_repo_info(config: &XetConfig, remote: &str) -> anyhow::Result<RepoInfo> {
+    let remote = config.build_authenticated_remote_url(remote);
+    let url = git_remote_to_base_url(&remote)?;
+    let (repo_info, _) = get_repo_info(&url, &BbqClient::new()?).await?;
+    Ok(repo_info)
+}
+
+/// this is the JSON structure returned by the xetea repo quota function, e.g.
+/// `{"storage_used": 1024, "storage_limit": 10737418240, "bandwidth_used": 0}`.
+/// Usage is in bytes and required; a missing or null limit means the
+/// repository has none.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct RepoQuota {
+    pub storage_used: u64,
+    pub storage_limit: Option<u64>,
+    pub bandwidth_used: u64,
+    pub bandwidth_limit: Option<u64>,
+}
+
+/// Queries the storage and bandwidth quota of the repository at the remote url.
+///
+/// This is a GET of `/api/xet/repos/<user>/<repo>/quota`, answered with a
+/// [RepoQuota]. Usage covers the data already stored in the repository's
+/// CAS, not what the push about to run would add; the pre-push hook adds
+/// that itself. The remote must answer users who can read the repository.
+/// Remotes that don't implement the function answer 404: the pre-push hook
+/// then skips its check, while `git xet quota` fails.
+pub async fn get_repo_quota(config: &XetConfig, remote: &str) -> anyhow::Result<RepoQuota> {
+    let remote = config.build_authenticated_remote_url(remote);
+    let url = git_remote_to_base_url(&remote)?;
+    let response = BbqClient::new()?
+        .perform_api_query(&url, "quota", "get", "")
+        .await?;
+    debug!("{:?}", String::from_utf8_lossy(&response));
+    Ok(serde_json::de::from_slice(&response)?)
+}
+
+/// The body of the xetea presign function.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+struct PresignRequest<'a> {
+    #[serde(rename = "ref")]
+    reference: &'a str,
+    paths: &'a [String],
+    expires_in_secs: u64,
+}
+
+/// A part of a file larger than the server assembles into a single
+/// download; the parts are downloaded and concatenated in order.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PresignedPart {
+    pub url: String,
+    pub length: u64,
+}
+
+/// this is the JSON structure returned by the xetea presign function for
+/// each path: either a single url, or a list of parts.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PresignedFile {
+    pub path: String,
+    /// RFC 3339 timestamp after which the links no longer work.
+    pub expires_at: String,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub parts: Vec<PresignedPart>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PresignResponse {
+    files: Vec<PresignedFile>,
+}
+
+/// Asks the remote url for download links to the files at paths, relative
+/// to the repository root, in the tree at reference. The links need no
+/// credentials and expire after expires_in.
+pub async fn get_presigned_urls(
+    config: &XetConfig,
+    remote: &str,
+    reference: &str,
+    paths: &[String],
+    expires_in: std::time::Duration,
+) -> anyhow::Result<Vec<PresignedFile>> {
+    let remote = config.build_authenticated_remote_url(remote);
+    let url = git_remote_to_base_url(&remote)?;
+    let body = serde_json::to_string(&PresignRequest {
+        reference,
+        paths,
+        expires_in_secs: expires_in.as_secs(),
+    })?;
+    let response = BbqClient::new()?
+        .perform_api_query(&url, "presign", "post", &body)
+        .await?;
+    debug!("{:?}", String::from_utf8_lossy(&response));
+    let response: PresignResponse = serde_json::de::from_slice(&response)?;
+    Ok(response.files)
+}
+
+/// The body of the xetea summaries function.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+struct ContentSummariesRequest<'a> {
+    hashes: &'a [String],
+}
+
+/// this is the JSON structure returned by the xetea summaries function:
+/// the summaries the remote has for the requested hashes, which may be
+/// only some of them.
+#[derive(Deserialize, Debug)]
+struct ContentSummariesResponse {
+    #[serde(default)]
+    summaries: HashMap<String, ContentSummary>,
+}
+
+/// Asks the remote url for its precomputed summaries of the contents with
+/// the xet hashes, so that the files stored as pointer files need not be
+/// downloaded to be summarized. Hashes the remote has no summary for are
+/// missing from the result.
+pub async fn get_content_summaries(
+    config: &XetConfig,
+    remote: &str,
+    hashes: &[String],
+) -> anyhow::Result<HashMap<String, ContentSummary>> {
+    let remote = config.build_authenticated_remote_url(remote);
+    let url = git_remote_to_base_url(&remote)?;
+    let body = serde_json::to_string(&ContentSummariesRequest { hashes })?;
+    let response = BbqClient::new()?
+        .perform_api_query(&url, "summaries", "post", &body)
+        .await?;
+    debug!("{:?}", String::from_utf8_lossy(&response));
+    let response: ContentSummariesResponse = serde_json::de::from_slice(&response)?;
+    Ok(response.summaries)
+}
+
+/// The body of the xetea tier function, e.g.
+/// `{"target": "cold", "xorbs": ["<hex hash>", ...]}`. At most
+/// a thousand xorbs are sent in one request.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+struct TierRequest<'a> {
+    target: &'a str,
+    xorbs: &'a [String],
+}
+
+/// this is the JSON structure returned by the xetea tier function: the
+/// number of xorbs queued to move to the target, and of those already in it.
+/// Xorbs the remote doesn't have are counted in neither. Missing fields
+/// count as 0.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct TierResponse {
+    #[serde(default)]
+    pub queued: u64,
+    #[serde(default)]
+    pub already_moved: u64,
+}
+
+/// Asks the remote url to move the xorbs, by hex hash, to the storage
+/// target: "cold" for the cold storage class of its CAS, or the name of
+/// another of its backends. Reads of the moved xorbs restore them.
+///
+/// This is a POST of a [TierRequest] to
+/// `/api/xet/repos/<user>/<repo>/tier`, answered with a [TierResponse]. The
+/// move is queued, not done, by the time the remote answers, and asking
+/// again for xorbs already queued or moved is harmless. The remote must
+/// only move the xorbs of the repository, and refuse the request, with a
+/// 403, to users who can't write to it; any error status fails the command.
+/// Once moved, reads of the xorbs from the CAS are refused with
+/// [cas_client::CasClientError::XORBArchived] until they are restored.
+pub async fn request_tiering(
+    config: &XetConfig,
+    remote: &str,
+    target: &str,
+    xorbs: &[String],
+) -> anyhow::Result<TierResponse> {
+    let remote = config.build_authenticated_remote_url(remote);
+    let url = git_remote_to_base_url(&remote)?;
+    let body = serde_json::to_string(&TierRequest { target, xorbs })?;
+    let response = BbqClient::new()?
+        .perform_api_query(&url, "tier", "post", &body)
+        .await?;
+    debug!("{:?}", String::from_utf8_lossy(&response));
+    Ok(serde_json::de::from_slice(&response)?)
+}
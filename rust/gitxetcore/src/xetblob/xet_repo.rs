@@ -296,8 +296,12 @@ impl XetRepo {
             // 1. no repo info found; or
             // 2. a V1 repo was deleted and recreated under the same name as a V2 repo;
             None => true,
-            // a V2 repo was deleted and recreated under the same name
-            Some(sri) => sri.xet != repo_info.xet,
+            // a V2 repo was deleted and recreated under the same name; the
+            // accepted hash algorithms may change without that.
+            Some(sri) => {
+                sri.xet.mdb_version != repo_info.xet.mdb_version
+                    || sri.xet.repo_salt != repo_info.xet.repo_salt
+            }
         };
 
         // repo changed, delete all contents and write out the repo info
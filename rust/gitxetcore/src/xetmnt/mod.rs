@@ -1,15 +1,19 @@
 pub mod xetfs_bare;
 
 mod watch;
+pub mod webdav;
 #[cfg(unix)]
 pub mod xetfs_write;
 
 use crate::config::XetConfig;
+use crate::control::{ControlServer, MOUNT_READ_BYTES};
 use crate::errors::{GitXetRepoError, Result};
 use nfsserve::tcp::*;
 use prometheus;
+use prometheus::IntCounter;
 use prometheus_dict_encoder::DictEncoder;
 use std::path::Path;
+use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
@@ -23,6 +27,33 @@ use crate::xetmnt::watch::xetfs_watch;
 #[cfg(unix)]
 use tracing::warn;
 
+/// The protocol the repository is served to the local mount over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MountProtocol {
+    /// A local NFSv3 server. Requires an NFS client.
+    #[default]
+    Nfs,
+
+    /// A local read-only WebDAV server, for systems without an NFS client
+    /// such as Windows Home.
+    WebDav,
+}
+
+impl FromStr for MountProtocol {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "nfs" => Ok(MountProtocol::Nfs),
+            "webdav" => Ok(MountProtocol::WebDav),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid mount protocol, should be one of nfs, webdav: {s}"),
+            )),
+        }
+    }
+}
+
 pub fn check_for_mount_program() -> bool {
     if cfg!(target_os = "macos") {
         // we can always mount on mac
@@ -271,6 +302,97 @@ async fn poll_for_mount_existence(path_to_search: &str) -> bool {
     false
 }
 
+/// Validates the mount target, returning the path to mount on (a drive letter
+/// on windows) and whether the directory was created here, so that it can be
+/// deleted on unmount. Returns None if the directory could not be created.
+fn prepare_mount_path(mount: &Path) -> Result<Option<(String, bool)>> {
+    #[cfg(target_os = "windows")]
+    {
+        let mount_drive = mount.to_str().unwrap().to_uppercase();
+        let mount_drive = mount_drive.strip_prefix("\"").unwrap_or(&mount_drive);
+        let mount_drive = mount_drive.strip_suffix("\"").unwrap_or(&mount_drive);
+        let mount_drive = mount_drive.strip_suffix("/").unwrap_or(&mount_drive);
+
+        // Make sure mount path is a drive letter
+        let mount_drive = mount_drive.strip_suffix(":").unwrap_or(&mount_drive);
+
+        // validate the mountpoint is just a single letter.
+        if mount_drive.len() != 1 {
+            return Err(GitXetRepoError::InvalidOperation(format!(
+            "Currently the mount path on windows for Xet repos must be an unused drive letter (got {:?})", mount_drive)));
+        }
+
+        Ok(Some((mount_drive.to_owned(), false)))
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        let mut mount_path_was_created = false;
+
+        // validate mount point exists
+        if !mount.exists() {
+            if let Err(e) = std::fs::create_dir_all(mount) {
+                error!("Unable to create directory {:?}. Error: {:?}", mount, e);
+                return Ok(None);
+            }
+            mount_path_was_created = true;
+        }
+        // validate mount point is empty
+        assert!(mount.exists());
+
+        let is_empty = mount.read_dir().unwrap().next().is_none();
+        if !is_empty {
+            return Err(GitXetRepoError::InvalidOperation(format!(
+                "Directory {mount:?} is not empty"
+            )));
+        }
+
+        Ok(Some((
+            mount.to_str().unwrap().to_owned(),
+            mount_path_was_created,
+        )))
+    }
+}
+
+/// Runs umount on mount_path, logging how to unmount manually on failure.
+fn unmount(mount_path: &str) {
+    let output = std::process::Command::new("umount")
+        .arg(mount_path)
+        .status();
+    match output {
+        Err(e) => {
+            error!("Failed to unmount: {:?}", e);
+            error!(
+                "You will need to unmount manually with \'umount -f {:?}\'",
+                mount_path
+            );
+        }
+        Ok(v) => {
+            if !v.success() {
+                error!("Failed to unmount");
+                error!(
+                    "You will need to unmount manually with \'umount -f {:?}\'",
+                    mount_path
+                );
+            }
+        }
+    }
+}
+
+/// Reports the bytes read through the mount, from pointer and passthrough
+/// files, on the control socket.
+fn add_read_counter(
+    control: &Option<ControlServer>,
+    pointer: &'static IntCounter,
+    passthrough: &'static IntCounter,
+) {
+    if let Some(control) = control {
+        control
+            .state
+            .add_counter(MOUNT_READ_BYTES, move || pointer.get() + passthrough.get());
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn perform_mount_and_wait_for_ctrlc(
     cfg: XetConfig,
@@ -284,53 +406,8 @@ pub async fn perform_mount_and_wait_for_ctrlc(
     mount_ready_callback: impl FnOnce(),
     autowatch_interval: Option<Duration>,
 ) -> Result<()> {
-    // we remember if the mount path was created so that we can delete
-    // it when we unmount
-    #[allow(unused_mut)] // Not mutated on windows
-    let mut mount_path_was_created = false;
-
-    let mount_path: String = {
-        #[cfg(target_os = "windows")]
-        {
-            let mount_drive = mount.to_str().unwrap().to_uppercase();
-            let mount_drive = mount_drive.strip_prefix("\"").unwrap_or(&mount_drive);
-            let mount_drive = mount_drive.strip_suffix("\"").unwrap_or(&mount_drive);
-            let mount_drive = mount_drive.strip_suffix("/").unwrap_or(&mount_drive);
-
-            // Make sure mount path is a drive letter
-            let mount_drive = mount_drive.strip_suffix(":").unwrap_or(&mount_drive);
-
-            // validate the mountpoint is just a single letter.
-            if mount_drive.len() != 1 {
-                return Err(GitXetRepoError::InvalidOperation(format!(
-                "Currently the mount path on windows for Xet repos must be an unused drive letter (got {:?})", mount_drive)));
-            }
-
-            mount_drive.to_owned()
-        }
-
-        #[cfg(not(target_os = "windows"))]
-        {
-            // validate mount point exists
-            if !mount.exists() {
-                if let Err(e) = std::fs::create_dir_all(mount) {
-                    error!("Unable to create directory {:?}. Error: {:?}", mount, e);
-                    return Ok(());
-                }
-                mount_path_was_created = true;
-            }
-            // validate mount point is empty
-            assert!(mount.exists());
-
-            let is_empty = mount.read_dir().unwrap().next().is_none();
-            if !is_empty {
-                return Err(GitXetRepoError::InvalidOperation(format!(
-                    "Directory {mount:?} is not empty"
-                )));
-            }
-
-            mount.to_str().unwrap().to_owned()
-        }
+    let Some((mount_path, mount_path_was_created)) = prepare_mount_path(mount)? else {
+        return Ok(());
     };
 
     let ip = {
@@ -360,6 +437,8 @@ pub async fn perform_mount_and_wait_for_ctrlc(
         }
     };
 
+    let control = ControlServer::start(&cfg, "mount")?;
+
     // load the xet
     #[allow(unused_mut)] // Not mutated on windows
     let mut listener: Box<dyn NFSTcp> = if writable {
@@ -373,6 +452,11 @@ pub async fn perform_mount_and_wait_for_ctrlc(
         {
             info!("Using XetFSWritable implementation");
             let xfs = xetfs_write::XetFSWritable::new(xet, &cfg, prefetch).await?;
+            add_read_counter(
+                &control,
+                &xetfs_bare::MOUNT_POINTER_BYTES_READ,
+                &xetfs_bare::MOUNT_PASSTHROUGH_BYTES_READ,
+            );
             warn!("Writable mounts are experimental");
             // bind the socket
             let listener = NFSTcpListener::bind(&ip, xfs).await?;
@@ -382,11 +466,24 @@ pub async fn perform_mount_and_wait_for_ctrlc(
         info!("Using XetFSWatch implementation with autowatch: {autowatch_interval:?}");
         let xfs = xetfs_watch::XetFSWatch::new(xet, &cfg, reference, prefetch, autowatch_interval)
             .await?;
+        add_read_counter(
+            &control,
+            &watch::metrics::MOUNT_POINTER_BYTES_READ,
+            &watch::metrics::MOUNT_PASSTHROUGH_BYTES_READ,
+        );
         let listener = NFSTcpListener::bind(&ip, xfs).await?;
         Box::new(listener)
     } else {
         info!("Using XetFSBare implementation");
         let xfs = xetfs_bare::XetFSBare::new(xet, &cfg, reference, prefetch).await?;
+        if let Some(control) = &control {
+            control.state.add_flush_hook(xfs.stat_cache_flusher());
+        }
+        add_read_counter(
+            &control,
+            &xetfs_bare::MOUNT_POINTER_BYTES_READ,
+            &xetfs_bare::MOUNT_PASSTHROUGH_BYTES_READ,
+        );
         let listener = NFSTcpListener::bind(&ip, xfs).await?;
         Box::new(listener)
     };
@@ -461,34 +558,27 @@ pub async fn perform_mount_and_wait_for_ctrlc(
     // actually perform the mount
     perform_mount(ip, hostport, mount_path.clone(), writable).await?;
 
+    // a shutdown requested over the control socket unmounts like ctrl-c
+    if let Some(control) = &control {
+        let requested = control.state.shutdown_requested();
+        let shutdown_tx = shutdown_tx.clone();
+        let mount_path = mount_path.clone();
+        tokio::spawn(async move {
+            if requested.await.is_ok() {
+                eprintln!("Shutdown requested. Unmounting.");
+                unmount(&mount_path);
+                let _ = shutdown_tx.send(false).await;
+            }
+        });
+    }
+
     // this is necessary due to some silliness with FnMut
     // Ex: https://github.com/rustwasm/wasm-bindgen/issues/1269
     let mut wrapped_shutdown_tx = Some(shutdown_tx);
     // if mount is good, we set a ctrl-c handler which runs umount
     ctrlc::set_handler(move || {
         eprintln!("Ctrl-C received. Unmounting.");
-        let output = std::process::Command::new("umount")
-            .arg(&mount_path)
-            .status();
-        match output {
-            Err(e) => {
-                error!("Failed to unmount: {:?}", e);
-                error!(
-                    "You will need to unmount manually with \'umount -f {:?}\'",
-                    mount_path
-                );
-            }
-            Ok(v) => {
-                if !v.success() {
-                    error!("Failed to unmount");
-                    error!(
-                        "You will need to unmount manually with \'umount -f {:?}\'",
-                        mount_path
-                    );
-                }
-            }
-        }
-
+        unmount(&mount_path);
         let _ = wrapped_shutdown_tx.take().unwrap().blocking_send(false);
     })
     .expect("Error setting Ctrl-C handler");
@@ -91,6 +91,21 @@ impl FSMetadata {
             .and_then(|fs| fs.get_entry_ref(id).cloned())
     }
 
+    /// The path of the entry relative to the root of the repository.
+    pub fn entry_path(&self, entry: &FSObject) -> Result<PathBuf, nfsstat3> {
+        let fs = self.lock_read_fs()?;
+        let mut path = match &fs.get_entry_ref(entry.parent)?.contents {
+            EntryContent::Directory(dirmeta) => dirmeta.path.clone(),
+            _ => self.srcpath.clone(),
+        };
+        let name = self.symbol_table.decode_symbol(entry.name)?;
+        path.push(String::from_utf8_lossy(&name).to_string());
+        Ok(match path.strip_prefix(&self.srcpath) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => path,
+        })
+    }
+
     pub fn insert_new_entry(
         &self,
         parent_id: fileid3,
@@ -1,5 +1,5 @@
 mod contents;
 mod metadata;
-mod metrics;
+pub(super) mod metrics;
 mod watcher;
 pub mod xetfs_watch;
@@ -1,5 +1,5 @@
 use std::fmt::Debug;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -12,6 +12,7 @@ use nfsserve::vfs::*;
 use tokio::sync::Mutex;
 use tracing::{debug, error, info};
 
+use crate::audit::AUDIT_EVENT_MOUNT_READ;
 use crate::config::XetConfig;
 use crate::constants as gitxet_constants;
 use crate::data::PointerFileTranslator;
@@ -19,6 +20,7 @@ use crate::xetmnt::watch::contents::EntryContent;
 use crate::xetmnt::watch::metadata::FSMetadata;
 use crate::xetmnt::watch::metrics::{MOUNT_PASSTHROUGH_BYTES_READ, MOUNT_POINTER_BYTES_READ};
 use crate::xetmnt::watch::watcher::RepoWatcher;
+use crate::xetmnt::xetfs_bare::read_error_status;
 use error_printer::ErrorPrinter;
 
 const PREFETCH_LOOKAHEAD: usize = gitxet_constants::PREFETCH_WINDOW_SIZE_BYTES as usize;
@@ -30,6 +32,7 @@ pub struct XetFSWatch {
     repo: Arc<Mutex<git2::Repository>>,
     watcher: Arc<RepoWatcher>,
     prefetch: usize,
+    gitref: String,
 }
 
 impl Debug for XetFSWatch {
@@ -80,6 +83,7 @@ impl XetFSWatch {
             repo,
             watcher,
             prefetch,
+            gitref: reference.to_string(),
         })
     }
 
@@ -200,7 +204,7 @@ impl NFSFileSystem for XetFSWatch {
                 }
                 MOUNT_POINTER_BYTES_READ.inc_by((end - start) as u64);
 
-                let mut output: Vec<u8> = Vec::new();
+                let path = self.fs.entry_path(&entry)?;
                 for ctr in 1..(self.prefetch + 1) {
                     if start + ctr * PREFETCH_LOOKAHEAD >= len {
                         break;
@@ -214,15 +218,18 @@ impl NFSFileSystem for XetFSWatch {
                         break;
                     }
                 }
-                self.pfilereader
-                    .smudge_file_from_pointer(
-                        &PathBuf::new(),
+                let output = self
+                    .pfilereader
+                    .read_range(
+                        AUDIT_EVENT_MOUNT_READ,
+                        Some(&self.gitref),
+                        &path,
                         pointer,
-                        &mut output,
-                        Some((start, end)),
+                        start as u64,
+                        (end - start) as u64,
                     )
                     .await
-                    .or(Err(nfsstat3::NFS3ERR_IO))?;
+                    .map_err(read_error_status)?;
                 Ok((output, eof))
             }
             EntryContent::RegularFile(_) => {
@@ -0,0 +1,220 @@
+//! Mounting over WebDAV instead of NFS.
+//!
+//! The same read-only filesystems used for NFS mounts are served by a local
+//! WebDAV server, which is then mapped with the platform's WebDAV client:
+//! mount_webdav on macOS, the WebClient service (`net use`) on Windows and
+//! davfs2 on Linux. This needs neither an NFS client nor FUSE / WinFsp.
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::process::Command;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time;
+use tracing::{error, info};
+
+use super::watch::xetfs_watch::XetFSWatch;
+use super::xetfs_bare::XetFSBare;
+use super::{handle_mount_command_output, prepare_mount_path};
+use crate::config::XetConfig;
+use crate::errors::{GitXetRepoError, Result};
+use crate::xetserve::bind_http_server;
+use crate::xetserve::webdav::{handle_webdav_request, SharedFileSystem};
+
+/// How often to check whether the share has been unmounted.
+const WEBDAV_MOUNT_POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Checks that the platform's WebDAV client is available.
+pub fn check_for_webdav_mount_program() -> bool {
+    if cfg!(target_os = "macos") {
+        // mount_webdav ships with macOS
+        true
+    } else if cfg!(target_os = "linux") {
+        if !Path::new("/sbin/mount.davfs").exists() && !Path::new("/usr/sbin/mount.davfs").exists()
+        {
+            error!("Unable to locate mount.davfs");
+            error!("Ubuntu: Install the WebDAV client package with 'apt install davfs2'");
+            error!("Redhat/Fedora: Install the WebDAV client package with 'yum install davfs2'");
+            false
+        } else {
+            true
+        }
+    } else if cfg!(target_os = "windows") {
+        // The WebClient service is part of every Windows edition, including Home.
+        true
+    } else {
+        error!("Unsupported");
+        false
+    }
+}
+
+/// Constructs the command mapping the WebDAV share at url onto mount_path,
+/// which is a drive letter on windows.
+fn build_webdav_mount_command(url: &str, mount_path: &str, sudo: bool) -> Command {
+    if cfg!(target_os = "macos") {
+        let mut ret = Command::new("/sbin/mount_webdav");
+        // -S suppresses the Finder's connection dialogs.
+        ret.arg("-S")
+            .arg("-o")
+            .arg("rdonly")
+            .arg(url)
+            .arg(mount_path);
+        ret
+    } else if cfg!(target_os = "windows") {
+        let mut ret = Command::new("net");
+        ret.arg("use").arg(format!("{mount_path}:")).arg(url);
+        ret
+    } else {
+        let mut ret = if sudo {
+            let mut sudocmd = Command::new("sudo");
+            sudocmd.arg("mount.davfs");
+            sudocmd
+        } else {
+            Command::new("mount.davfs")
+        };
+        ret.arg("-o").arg("ro").arg(url).arg(mount_path);
+        ret
+    }
+}
+
+/// Constructs the command unmapping the share from mount_path.
+fn build_webdav_unmount_command(mount_path: &str) -> std::process::Command {
+    if cfg!(target_os = "windows") {
+        let mut ret = std::process::Command::new("net");
+        ret.arg("use")
+            .arg(format!("{mount_path}:"))
+            .arg("/delete")
+            .arg("/y");
+        ret
+    } else {
+        let mut ret = std::process::Command::new("umount");
+        ret.arg(mount_path);
+        ret
+    }
+}
+
+/// Maps the share, retrying as root on Linux where davfs2 mounts are usually
+/// restricted to root.
+async fn perform_webdav_mount(url: &str, mount_path: &str) -> Result<()> {
+    let mut cmd = build_webdav_mount_command(url, mount_path, false);
+    info!("Running command {:?}", cmd);
+    let output = cmd.status().await;
+    let result = handle_mount_command_output(&cmd, output);
+    if result.is_ok() || !cfg!(target_os = "linux") {
+        return result;
+    }
+
+    error!("Failed to mount. Retrying as root with sudo...");
+    let mut cmd = build_webdav_mount_command(url, mount_path, true);
+    info!("Running command {:?}", cmd);
+    let output = cmd.status().await;
+    handle_mount_command_output(&cmd, output)?;
+    eprintln!("Mount command successful as root");
+    Ok(())
+}
+
+/// Returns false once the share is no longer mounted.
+async fn poll_for_webdav_mount_existence(mount_path: &str) -> bool {
+    #[cfg(target_os = "windows")]
+    {
+        Path::new(&format!("{mount_path}:/")).exists()
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        super::poll_for_mount_existence(mount_path).await
+    }
+}
+
+/// Serves the repository at reference over WebDAV and mounts it, returning
+/// once it is unmounted or Ctrl-C is received.
+#[allow(clippy::too_many_arguments)]
+pub async fn perform_webdav_mount_and_wait_for_ctrlc(
+    cfg: XetConfig,
+    xet: &Path,
+    mount: &Path,
+    reference: &str,
+    prefetch: usize,
+    ip_address: String,
+    mount_ready_callback: impl FnOnce(),
+    autowatch_interval: Option<Duration>,
+) -> Result<()> {
+    let Some((mount_path, mount_path_was_created)) = prepare_mount_path(mount)? else {
+        return Ok(());
+    };
+
+    let ip = match ip_address.as_str() {
+        "auto" | "localhost" => "127.0.0.1".to_string(),
+        _ => ip_address,
+    };
+    let ip = if ip.contains(':') { ip } else { ip + ":0" };
+    let addr: SocketAddr = ip
+        .parse()
+        .map_err(|_| GitXetRepoError::InvalidOperation(format!("Invalid address {ip:?}")))?;
+
+    let fs: SharedFileSystem = if autowatch_interval.is_some() {
+        info!("Using XetFSWatch implementation with autowatch: {autowatch_interval:?}");
+        Arc::new(XetFSWatch::new(xet, &cfg, reference, prefetch, autowatch_interval).await?)
+    } else {
+        info!("Using XetFSBare implementation");
+        Arc::new(XetFSBare::new(xet, &cfg, reference, prefetch).await?)
+    };
+
+    let (server_shutdown_tx, server_shutdown_rx) = oneshot::channel::<()>();
+    let (addr, server) = bind_http_server(
+        addr,
+        move |req| handle_webdav_request(fs.clone(), req),
+        async move {
+            let _ = server_shutdown_rx.await;
+        },
+    )?;
+    let server_task = tokio::spawn(server);
+    let url = format!("http://{addr}/");
+    info!("Serving WebDAV on {url}");
+
+    perform_webdav_mount(&url, &mount_path).await?;
+
+    let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<bool>(1);
+    let mut wrapped_shutdown_tx = Some(shutdown_tx);
+    let unmount_path = mount_path.clone();
+    ctrlc::set_handler(move || {
+        eprintln!("Ctrl-C received. Unmounting.");
+        let mut cmd = build_webdav_unmount_command(&unmount_path);
+        if !matches!(cmd.status(), Ok(v) if v.success()) {
+            error!("Failed to unmount");
+            error!("You will need to unmount manually with {:?}", cmd);
+        }
+        if let Some(tx) = wrapped_shutdown_tx.take() {
+            let _ = tx.blocking_send(false);
+        }
+    })
+    .expect("Error setting Ctrl-C handler");
+
+    eprintln!("Mount at {mount:?} successful. Hit Ctrl-C or unmount to stop");
+    mount_ready_callback();
+
+    loop {
+        tokio::select! {
+            _ = shutdown_rx.recv() => break,
+            () = time::sleep(WEBDAV_MOUNT_POLL_INTERVAL) => {
+                info!("Polling for mount existence");
+                if !poll_for_webdav_mount_existence(&mount_path).await {
+                    break;
+                }
+            }
+        }
+    }
+
+    info!("Shutting down");
+    if mount_path_was_created {
+        #[cfg(not(target_os = "windows"))]
+        let _ = std::fs::remove_dir(&mount_path);
+    }
+    let _ = server_shutdown_tx.send(());
+    match server_task.await {
+        Ok(result) => result,
+        Err(e) => Err(GitXetRepoError::Other(format!(
+            "Error in WebDAV server task: {e:?}"
+        ))),
+    }
+}
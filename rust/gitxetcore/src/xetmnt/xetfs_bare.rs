@@ -1,7 +1,11 @@
+use crate::audit::AUDIT_EVENT_MOUNT_READ;
 use crate::config::XetConfig;
 use crate::constants as gitxet_constants;
 use crate::constants::POINTER_FILE_LIMIT;
 use crate::data::{PointerFile, PointerFileTranslator};
+use crate::errors::GitXetRepoError;
+use crate::git_integration::repo_path::{os_str_to_bytes, os_string_from_bytes};
+use crate::git_integration::GitTreeEntryKind;
 use async_trait::async_trait;
 use git2;
 use intaglio::osstr::SymbolTable;
@@ -15,13 +19,12 @@ use std::fmt::Debug;
 #[cfg(unix)]
 use std::fs::Permissions;
 use std::ops::Bound;
-use std::str::FromStr;
 
 #[cfg(unix)]
 use std::os::unix::fs::{MetadataExt, PermissionsExt};
 
 use std::path;
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 use tracing::{debug, error, info};
 
 use lazy_static::lazy_static;
@@ -42,6 +45,14 @@ lazy_static! {
         register_int_gauge!("mount_num_fs_objects", "Number of filesystem objects").unwrap();
 }
 
+/// The status a failed read of a pointer file is reported with.
+pub(crate) fn read_error_status(e: GitXetRepoError) -> nfsstat3 {
+    match e {
+        GitXetRepoError::AccessDenied(_) => nfsstat3::NFS3ERR_ACCES,
+        _ => nfsstat3::NFS3ERR_IO,
+    }
+}
+
 const STAT_CACHE_SIZE: usize = 65536;
 const PREFETCH_LOOKAHEAD: usize = gitxet_constants::PREFETCH_WINDOW_SIZE_BYTES as usize;
 
@@ -78,6 +89,8 @@ struct DirectoryMetadata {
 enum FileObject {
     XetFile((EntryMetadata, PointerFile)),
     RegularFile((EntryMetadata, git2::Oid)),
+    /// A symlink, whose blob is the path it points to.
+    Symlink((EntryMetadata, git2::Oid)),
     Directory(DirectoryMetadata),
 }
 #[derive(Debug, Clone)]
@@ -97,7 +110,7 @@ pub struct XetFSBare {
     rootdir: fileid3,
     srcpath: path::PathBuf,
     pfilereader: PointerFileTranslator,
-    statcache: RwLock<lru::LruCache<fileid3, fattr3>>,
+    statcache: Arc<RwLock<lru::LruCache<fileid3, fattr3>>>,
     repo: tokio::sync::Mutex<git2::Repository>,
     gitref: String,
     #[allow(dead_code)] // Not used on windows
@@ -168,10 +181,9 @@ impl XetFSBare {
 
         let tree = repo.find_tree(oid_to_expand)?;
         for tree_ent in tree.iter() {
-            let filename = String::from_utf8_lossy(tree_ent.name_bytes()).to_string();
-            let filename_osstr = OsString::from_str(&filename).unwrap();
-            let sym = intern.intern(filename_osstr).unwrap();
+            let filename = os_string_from_bytes(tree_ent.name_bytes());
             let ent_path = cur_path.join(&filename);
+            let sym = intern.intern(filename).unwrap();
             let is_directory = matches!(tree_ent.kind(), Some(git2::ObjectType::Tree));
             let maybe_contents = XetFSBare::oid_to_contents(
                 &repo,
@@ -214,10 +226,13 @@ impl XetFSBare {
                 error!("Oid {:?} type Any not supported", oid);
                 Ok(None)
             }
-            git2::ObjectType::Commit => {
-                error!("Oid {:?} type Commit not supported", oid);
-                Ok(None)
-            }
+            // A submodule is shown as an empty directory, as git checks out
+            // uninitialized submodules; it's never expanded, as the oid is
+            // a commit of another repository.
+            git2::ObjectType::Commit => Ok(Some(FileObject::Directory(DirectoryMetadata {
+                path: path.to_path_buf(),
+                oid,
+            }))),
             git2::ObjectType::Tag => {
                 error!("Oid {:?} type Tag not supported", oid);
                 Ok(None)
@@ -226,6 +241,15 @@ impl XetFSBare {
                 path: path.to_path_buf(),
                 oid,
             }))),
+            git2::ObjectType::Blob
+                if GitTreeEntryKind::from_mode(mode) == GitTreeEntryKind::Symlink =>
+            {
+                let size = repo.find_blob(oid)?.size() as u64;
+                Ok(Some(FileObject::Symlink((
+                    EntryMetadata { size, mode },
+                    oid,
+                ))))
+            }
             git2::ObjectType::Blob => Ok(Some(XetFSBare::blob_to_contents(repo, mode, oid)?)),
         }
     }
@@ -263,13 +287,17 @@ impl XetFSBare {
         &self,
         fid: fileid3,
         entrymeta: EntryMetadata,
-        is_file: bool,
+        ftype: ftype3,
     ) -> Result<fattr3, nfsstat3> {
         let size = entrymeta.size;
-        let file_mode = mode_unmask_write(entrymeta.mode);
-        if is_file {
+        // the permissions of symlinks are never checked
+        let file_mode = match ftype {
+            ftype3::NF3LNK => 0o777,
+            _ => mode_unmask_write(entrymeta.mode),
+        };
+        if !matches!(ftype, ftype3::NF3DIR) {
             Ok(fattr3 {
-                ftype: ftype3::NF3REG,
+                ftype,
                 mode: file_mode,
                 nlink: 1,
                 uid: self.metadata.uid(),
@@ -325,12 +353,12 @@ impl XetFSBare {
         &self,
         fid: fileid3,
         entrymeta: EntryMetadata,
-        is_file: bool,
+        ftype: ftype3,
     ) -> Result<fattr3, nfsstat3> {
         let size = entrymeta.size;
-        if is_file {
+        if !matches!(ftype, ftype3::NF3DIR) {
             Ok(fattr3 {
-                ftype: ftype3::NF3REG,
+                ftype,
                 mode: 0555,
                 nlink: 1,
                 uid: 507,
@@ -395,7 +423,7 @@ impl XetFSBare {
             rootdir: 0,
             srcpath: srcpath.to_path_buf(),
             pfilereader: pfile,
-            statcache: RwLock::new(LruCache::new(STAT_CACHE_SIZE)),
+            statcache: Arc::new(RwLock::new(LruCache::new(STAT_CACHE_SIZE))),
             repo: tokio::sync::Mutex::new(repo),
             gitref: reference.into(),
             metadata,
@@ -405,6 +433,23 @@ impl XetFSBare {
         Ok(ret)
     }
 
+    /// The path of the entry relative to the root of the repository.
+    fn entry_path(&self, entry: &FSObject) -> path::PathBuf {
+        let fs = self.fs.read().unwrap();
+        let intern = self.intern.read().unwrap();
+        let mut path = match &fs[entry.parent as usize].contents {
+            FileObject::Directory(dirmeta) => dirmeta.path.clone(),
+            _ => self.srcpath.clone(),
+        };
+        if let Some(name) = intern.get(entry.name) {
+            path.push(name);
+        }
+        match path.strip_prefix(&self.srcpath) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => path,
+        }
+    }
+
     async fn find_root_tree_oid(&mut self) -> Result<git2::Oid, anyhow::Error> {
         let repo = self.repo.lock().await;
 
@@ -425,6 +470,18 @@ impl XetFSBare {
         Ok(())
     }
 
+    /// Returns a function clearing the stat cache and returning the number
+    /// of entries dropped.
+    pub fn stat_cache_flusher(&self) -> impl Fn() -> usize + Send + Sync + 'static {
+        let statcache = self.statcache.clone();
+        move || {
+            let mut statcache = statcache.write().unwrap();
+            let flushed = statcache.len();
+            statcache.clear();
+            flushed
+        }
+    }
+
     pub fn num_objects(&self) -> usize {
         self.fs.read().unwrap().len()
     }
@@ -435,12 +492,26 @@ impl XetFSBare {
             sum += match &f.contents {
                 FileObject::XetFile((meta, _)) => meta.size,
                 FileObject::RegularFile((meta, _)) => meta.size,
+                FileObject::Symlink((meta, _)) => meta.size,
                 FileObject::Directory(_) => 0,
             };
         }
         sum
     }
 
+    /// A stable identifier for the contents of a file: the Xet hash for
+    /// pointer files and the git blob id otherwise. None for directories.
+    pub fn content_id(&self, id: fileid3) -> Option<String> {
+        let fs = self.fs.read().unwrap();
+        match &fs.get(id as usize)?.contents {
+            FileObject::XetFile((_, pointer)) => Some(pointer.hash_string().clone()),
+            FileObject::RegularFile((_, oid)) | FileObject::Symlink((_, oid)) => {
+                Some(oid.to_string())
+            }
+            FileObject::Directory(_) => None,
+        }
+    }
+
     pub fn getattr_sync(&self, id: fileid3) -> Result<fattr3, nfsstat3> {
         {
             // annoyingly this LRU cache implementation requires mut on a read.
@@ -454,9 +525,14 @@ impl XetFSBare {
         let entry = fs.get(id as usize).ok_or(nfsstat3::NFS3ERR_NOENT)?;
         info!("Getattr {:?}", entry);
         let attr = match &entry.contents {
-            FileObject::XetFile((meta, _)) => self.path_to_fattr3(id, meta.clone(), true),
-            FileObject::RegularFile((meta, _)) => self.path_to_fattr3(id, meta.clone(), true),
-            FileObject::Directory(_) => self.path_to_fattr3(id, EntryMetadata::default(), false),
+            FileObject::XetFile((meta, _)) => self.path_to_fattr3(id, meta.clone(), ftype3::NF3REG),
+            FileObject::RegularFile((meta, _)) => {
+                self.path_to_fattr3(id, meta.clone(), ftype3::NF3REG)
+            }
+            FileObject::Symlink((meta, _)) => self.path_to_fattr3(id, meta.clone(), ftype3::NF3LNK),
+            FileObject::Directory(_) => {
+                self.path_to_fattr3(id, EntryMetadata::default(), ftype3::NF3DIR)
+            }
         };
         if let Ok(stat) = &attr {
             self.statcache.write().unwrap().put(id, *stat);
@@ -498,12 +574,7 @@ impl NFSFileSystem for XetFSBare {
                 .intern
                 .read()
                 .unwrap()
-                .check_interned(
-                    &OsString::from_str(
-                        std::str::from_utf8(filename).map_err(|_| nfsstat3::NFS3ERR_INVAL)?,
-                    )
-                    .map_err(|_| nfsstat3::NFS3ERR_INVAL)?,
-                )
+                .check_interned(&os_string_from_bytes(&filename[..]))
                 .and_then(|x| entry.children.get(&x))
             {
                 Ok(*fileid)
@@ -530,49 +601,52 @@ impl NFSFileSystem for XetFSBare {
             fs.get(id as usize).ok_or(nfsstat3::NFS3ERR_NOENT)?.clone()
         };
         //eprintln!("Read {:?} {}, {}", entry, offset, count);
-        match entry.contents {
+        match &entry.contents {
             FileObject::Directory(_) => Err(nfsstat3::NFS3ERR_ISDIR),
+            FileObject::Symlink(_) => Err(nfsstat3::NFS3ERR_INVAL),
             FileObject::XetFile((_, pointer)) => {
-                let mut start = offset as usize;
-                let mut end = offset as usize + count as usize;
+                let start = offset as usize;
                 let len = pointer.filesize() as usize;
-                let eof = end >= len;
-                if start >= len {
-                    start = len;
-                }
-                if end > len {
-                    end = len;
-                }
-                MOUNT_POINTER_BYTES_READ.inc_by((end - start) as u64);
-
-                let mut output: Vec<u8> = Vec::new();
-                for ctr in 1..(self.prefetch + 1) {
+                let eof = start + count as usize >= len;
+                let path = self.entry_path(&entry);
+                // Nothing is prefetched for files the read will be denied.
+                let prefetch = if self.pfilereader.allows(&path) {
+                    self.prefetch
+                } else {
+                    0
+                };
+
+                for ctr in 1..(prefetch + 1) {
                     if start + ctr * PREFETCH_LOOKAHEAD >= len {
                         break;
                     }
                     if self
                         .pfilereader
-                        .prefetch(&pointer, (start + ctr * PREFETCH_LOOKAHEAD) as u64)
+                        .prefetch(pointer, (start + ctr * PREFETCH_LOOKAHEAD) as u64)
                         .await
                         .unwrap()
                     {
                         break;
                     }
                 }
-                self.pfilereader
-                    .smudge_file_from_pointer(
-                        &path::PathBuf::new(),
-                        &pointer,
-                        &mut output,
-                        Some((start, end)),
+                let output = self
+                    .pfilereader
+                    .read_range(
+                        AUDIT_EVENT_MOUNT_READ,
+                        Some(&self.gitref),
+                        &path,
+                        pointer,
+                        offset,
+                        count as u64,
                     )
                     .await
-                    .or(Err(nfsstat3::NFS3ERR_IO))?;
+                    .map_err(read_error_status)?;
+                MOUNT_POINTER_BYTES_READ.inc_by(output.len() as u64);
                 Ok((output, eof))
             }
             FileObject::RegularFile((_, oid)) => {
                 let repo = self.repo.lock().await;
-                let blob = repo.find_blob(oid).map_err(|_| nfsstat3::NFS3ERR_IO)?;
+                let blob = repo.find_blob(*oid).map_err(|_| nfsstat3::NFS3ERR_IO)?;
                 let len = blob.size();
                 let mut start = offset as usize;
                 let mut end = start + (count as usize);
@@ -640,10 +714,9 @@ impl NFSFileSystem for XetFSBare {
                     fileid: *i.1,
                     name: intern
                         .get(fs[*i.1 as usize].name)
+                        .map(os_str_to_bytes)
                         .unwrap()
-                        .to_str()
-                        .unwrap()
-                        .as_bytes()
+                        .as_ref()
                         .into(),
                     attr,
                 });
@@ -713,7 +786,16 @@ impl NFSFileSystem for XetFSBare {
     ) -> Result<(fileid3, fattr3), nfsstat3> {
         Err(nfsstat3::NFS3ERR_ROFS)
     }
-    async fn readlink(&self, _id: fileid3) -> Result<nfspath3, nfsstat3> {
-        Err(nfsstat3::NFS3ERR_NOTSUPP)
+    async fn readlink(&self, id: fileid3) -> Result<nfspath3, nfsstat3> {
+        let oid = {
+            let fs = self.fs.read().unwrap();
+            match &fs.get(id as usize).ok_or(nfsstat3::NFS3ERR_NOENT)?.contents {
+                FileObject::Symlink((_, oid)) => *oid,
+                _ => return Err(nfsstat3::NFS3ERR_INVAL),
+            }
+        };
+        let repo = self.repo.lock().await;
+        let blob = repo.find_blob(oid).map_err(|_| nfsstat3::NFS3ERR_IO)?;
+        Ok(blob.content().into())
     }
 }
@@ -1,8 +1,11 @@
+use crate::audit::AUDIT_EVENT_MOUNT_READ;
 use crate::config::XetConfig;
 use crate::constants as gitxet_constants;
 use crate::constants::POINTER_FILE_LIMIT;
 use crate::data::{PointerFile, PointerFileTranslator};
-use crate::xetmnt::xetfs_bare::{MOUNT_PASSTHROUGH_BYTES_READ, MOUNT_POINTER_BYTES_READ};
+use crate::xetmnt::xetfs_bare::{
+    read_error_status, MOUNT_PASSTHROUGH_BYTES_READ, MOUNT_POINTER_BYTES_READ,
+};
 use async_trait::async_trait;
 use intaglio::osstr::SymbolTable;
 use intaglio::Symbol;
@@ -96,6 +99,7 @@ fn to_xet_metadata(fid: fileid3, path: &Path, metadata: &Metadata) -> fattr3 {
 
 async fn checkout_xetfile(
     pfilereader: &PointerFileTranslator,
+    repo_path: &Path,
     fullpath: &Path,
     pointer: &PointerFile,
 ) -> Result<(), anyhow::Error> {
@@ -108,7 +112,7 @@ async fn checkout_xetfile(
         let mut f = BufWriter::new(&mut tempfile);
 
         pfilereader
-            .smudge_file_from_pointer(fullpath, pointer, &mut f, None)
+            .smudge_file_from_pointer(repo_path, pointer, &mut f, None)
             .await?;
     }
 
@@ -142,6 +146,14 @@ impl FSMap {
         ret
     }
 
+    /// The path of the entry relative to the root of the repository.
+    fn sym_to_repo_path(&self, symlist: &[Symbol]) -> PathBuf {
+        symlist
+            .iter()
+            .map(|i| self.intern.get(*i).unwrap())
+            .collect()
+    }
+
     async fn sym_to_fname(&self, symlist: &[Symbol]) -> OsString {
         if let Some(x) = symlist.last() {
             self.intern.get(*x).unwrap().into()
@@ -476,6 +488,7 @@ impl NFSFileSystem for XetFSWritable {
         let fsmap = self.fsmap.lock().await;
         let ent = fsmap.find_entry(id)?;
         let path = fsmap.sym_to_path(&ent.name).await;
+        let repo_path = fsmap.sym_to_repo_path(&ent.name);
         drop(fsmap);
         // check if its a pointer file
         let pointer = to_xet_pointer_maybe(&path)?;
@@ -493,7 +506,6 @@ impl NFSFileSystem for XetFSWritable {
             }
             MOUNT_POINTER_BYTES_READ.inc_by((end - start) as u64);
 
-            let mut output: Vec<u8> = Vec::new();
             for ctr in 1..(self.prefetch + 1) {
                 if start + ctr * PREFETCH_LOOKAHEAD >= len {
                     break;
@@ -507,15 +519,18 @@ impl NFSFileSystem for XetFSWritable {
                     break;
                 }
             }
-            self.pfilereader
-                .smudge_file_from_pointer(
-                    &PathBuf::new(),
+            let output = self
+                .pfilereader
+                .read_range(
+                    AUDIT_EVENT_MOUNT_READ,
+                    None,
+                    &repo_path,
                     &pointer,
-                    &mut output,
-                    Some((start, end)),
+                    start as u64,
+                    (end - start) as u64,
                 )
                 .await
-                .or(Err(nfsstat3::NFS3ERR_IO))?;
+                .map_err(read_error_status)?;
             Ok((output, eof))
         } else {
             let mut f = File::open(&path).await.or(Err(nfsstat3::NFS3ERR_NOENT))?;
@@ -613,6 +628,7 @@ impl NFSFileSystem for XetFSWritable {
         let fsmap = self.fsmap.lock().await;
         let ent = fsmap.find_entry(id)?;
         let path = fsmap.sym_to_path(&ent.name).await;
+        let repo_path = fsmap.sym_to_repo_path(&ent.name);
         drop(fsmap);
         {
             // we only checkout 1 file at a time to avoid
@@ -624,7 +640,9 @@ impl NFSFileSystem for XetFSWritable {
             if to_xet_pointer_maybe(&path)?.is_some() {
                 let _checkoutlock = self.checkout_lock.lock().await;
                 if let Some(pointer) = to_xet_pointer_maybe(&path)? {
-                    if let Err(e) = checkout_xetfile(&self.pfilereader, &path, &pointer).await {
+                    if let Err(e) =
+                        checkout_xetfile(&self.pfilereader, &repo_path, &path, &pointer).await
+                    {
                         error!("Unable to checkout xet file {:?}", e);
                         return Err(nfsstat3::NFS3ERR_IO);
                     }
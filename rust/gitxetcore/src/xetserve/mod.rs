@@ -0,0 +1,356 @@
+//! HTTP access to the files of a repository, without a checkout.
+//!
+//! Files are resolved through git and, for pointer files, read through the
+//! [PointerFileTranslator] so all reads go through the configured CAS client
+//! and cache. Responses are streamed in bounded chunks so arbitrarily large
+//! files can be served with constant memory.
+pub mod range;
+pub mod raw;
+pub mod s3;
+pub mod vfs;
+pub mod webdav;
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use hyper::body::{Body, Bytes};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Request, Response, Server};
+use tokio::sync::Mutex;
+use tracing::{error, info};
+
+use crate::audit::AUDIT_EVENT_READ;
+use crate::config::XetConfig;
+use crate::constants::POINTER_FILE_LIMIT;
+use crate::data::{PointerFile, PointerFileTranslator};
+use crate::errors::{GitXetRepoError, Result};
+use crate::git_integration::GitXetRepo;
+
+pub use range::{ByteRangeSpec, RangeHeaderError, RequestedRange};
+
+/// The amount of data reconstructed per read when streaming a response.
+const SERVE_READ_CHUNK_SIZE: u64 = 8 * 1024 * 1024;
+
+/// The contents of a file at a particular ref.
+#[derive(Debug, Clone)]
+pub enum RepoFile {
+    /// A file stored as a Xet pointer file.
+    Xet(PointerFile),
+    /// A file stored directly in git.
+    Raw(Arc<Vec<u8>>),
+}
+
+impl RepoFile {
+    pub fn size(&self) -> u64 {
+        match self {
+            RepoFile::Xet(pointer) => pointer.filesize(),
+            RepoFile::Raw(content) => content.len() as u64,
+        }
+    }
+
+    fn from_blob_content(content: &[u8], path: &str) -> Self {
+        if content.len() <= POINTER_FILE_LIMIT {
+            if let Ok(s) = std::str::from_utf8(content) {
+                let pointer = PointerFile::init_from_string(s, path);
+                if pointer.is_valid() {
+                    return RepoFile::Xet(pointer);
+                }
+            }
+        }
+        RepoFile::Raw(Arc::new(content.to_vec()))
+    }
+}
+
+/// Resolves files at arbitrary refs of a repository and reads byte ranges
+/// out of them.
+pub struct RepoFileReader {
+    repo: Mutex<git2::Repository>,
+    translator: PointerFileTranslator,
+}
+
+impl RepoFileReader {
+    pub async fn new(config: &XetConfig) -> Result<Self> {
+        let repo = GitXetRepo::open(config.clone())?.repo;
+        let translator = PointerFileTranslator::from_config_in_repo(config).await?;
+        Ok(Self {
+            repo: Mutex::new(repo),
+            translator,
+        })
+    }
+
+    /// Looks up the file at path in the tree of reference, returning None if
+    /// either cannot be found or the path is not a file.
+    pub async fn resolve(&self, reference: &str, path: &str) -> Result<Option<RepoFile>> {
+        let repo = self.repo.lock().await;
+        let Ok(commit) = repo
+            .revparse_single(reference)
+            .and_then(|o| o.peel_to_commit())
+        else {
+            return Ok(None);
+        };
+        let tree = commit.tree()?;
+        let Ok(entry) = tree.get_path(&PathBuf::from(path)) else {
+            return Ok(None);
+        };
+        if entry.kind() != Some(git2::ObjectType::Blob) {
+            return Ok(None);
+        }
+        let blob = repo.find_blob(entry.id())?;
+        Ok(Some(RepoFile::from_blob_content(blob.content(), path)))
+    }
+
+    /// Resolves a path of the form <ref>/<path>, where the ref may itself contain
+    /// slashes (e.g. feature/branch/data.csv). The shortest ref that resolves
+    /// to a file wins.
+    pub async fn resolve_ref_path(&self, ref_path: &str) -> Result<Option<(String, RepoFile)>> {
+        let parts: Vec<&str> = ref_path.split('/').filter(|s| !s.is_empty()).collect();
+        for i in 1..parts.len() {
+            let reference = parts[..i].join("/");
+            let path = parts[i..].join("/");
+            if let Some(file) = self.resolve(&reference, &path).await? {
+                return Ok(Some((path, file)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads the bytes in [start, end) of the file.
+    pub async fn read_range(&self, file: &RepoFile, start: u64, end: u64) -> Result<Vec<u8>> {
+        let end = end.min(file.size());
+        let start = start.min(end);
+        match file {
+            RepoFile::Xet(pointer) => {
+                self.translator
+                    .read_range(
+                        AUDIT_EVENT_READ,
+                        None,
+                        Path::new(pointer.path()),
+                        pointer,
+                        start,
+                        end - start,
+                    )
+                    .await
+            }
+            RepoFile::Raw(content) => Ok(content[start as usize..end as usize].to_vec()),
+        }
+    }
+}
+
+/// Streams the bytes in [start, end) of the file as a response body, reading
+/// SERVE_READ_CHUNK_SIZE bytes at a time.
+pub fn stream_range(reader: Arc<RepoFileReader>, file: RepoFile, start: u64, end: u64) -> Body {
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        let mut pos = start;
+        while pos < end {
+            let chunk_end = (pos + SERVE_READ_CHUNK_SIZE).min(end);
+            match reader.read_range(&file, pos, chunk_end).await {
+                Ok(data) => {
+                    if sender.send_data(Bytes::from(data)).await.is_err() {
+                        // Client went away.
+                        return;
+                    }
+                }
+                Err(e) => {
+                    error!("Error reading range {pos}-{chunk_end}: {e:?}");
+                    sender.abort();
+                    return;
+                }
+            }
+            pos = chunk_end;
+        }
+    });
+    body
+}
+
+/// Decodes %XX escapes in a URL path component. Invalid escapes are kept as is.
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(v) = u8::from_str_radix(hex, 16) {
+                out.push(v);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// Percent-encodes everything but unreserved characters and '/', so a path
+/// can be embedded in a URL or an S3 listing requested with encoding-type=url.
+pub fn percent_encode_path(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                ret.push(b as char)
+            }
+            b => ret.push_str(&format!("%{b:02X}")),
+        }
+    }
+    ret
+}
+
+/// Escapes text for inclusion in XML content or attribute values.
+pub fn xml_escape(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            '\'' => ret.push_str("&apos;"),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+/// Formats a time as ISO 8601, as used in S3 listings and WebDAV creation dates.
+pub fn format_iso8601(t: &DateTime<Utc>) -> String {
+    t.format("%Y-%m-%dT%H:%M:%S.000Z").to_string()
+}
+
+/// Formats a time as an HTTP date (RFC 1123).
+pub fn format_http_date(t: &DateTime<Utc>) -> String {
+    t.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Binds an HTTP server to addr, dispatching every request to handler.
+/// Returns the bound address, which has the actual port if addr used port 0,
+/// and a future that serves requests until shutdown completes.
+pub fn bind_http_server<H, F, S>(
+    addr: SocketAddr,
+    handler: H,
+    shutdown: S,
+) -> Result<(SocketAddr, impl Future<Output = Result<()>>)>
+where
+    H: Fn(Request<Body>) -> F + Clone + Send + Sync + 'static,
+    F: Future<Output = Response<Body>> + Send + 'static,
+    S: Future<Output = ()>,
+{
+    let make_svc = make_service_fn(move |_conn| {
+        let handler = handler.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let fut = handler(req);
+                async move { Ok::<_, Infallible>(fut.await) }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&addr)
+        .map_err(|source| GitXetRepoError::HttpServerError { addr, source })?
+        .serve(make_svc);
+    let local_addr = server.local_addr();
+
+    let serve = async move {
+        server
+            .with_graceful_shutdown(shutdown)
+            .await
+            .map_err(|source| GitXetRepoError::HttpServerError { addr, source })
+    };
+    Ok((local_addr, serve))
+}
+
+/// Runs an HTTP server on addr, dispatching every request to handler, until
+/// the process is interrupted.
+pub async fn run_http_server<H, F>(addr: SocketAddr, handler: H) -> Result<()>
+where
+    H: Fn(Request<Body>) -> F + Clone + Send + Sync + 'static,
+    F: Future<Output = Response<Body>> + Send + 'static,
+{
+    let (local_addr, serve) = bind_http_server(addr, handler, async {
+        let _ = tokio::signal::ctrl_c().await;
+    })?;
+    info!("Serving on http://{local_addr}");
+    eprintln!("Serving on http://{local_addr}");
+    serve.await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use mdb_shard::shard_version::ShardVersion;
+
+    use super::*;
+    use crate::data::access_policy::{AccessControl, AccessPolicy, TokenClaims};
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("data/a%20b.csv"), "data/a b.csv");
+        assert_eq!(percent_decode("plain"), "plain");
+        assert_eq!(percent_decode("bad%zzescape"), "bad%zzescape");
+        assert_eq!(percent_decode("trailing%2"), "trailing%2");
+        assert_eq!(percent_decode("%E2%9C%93"), "\u{2713}");
+    }
+
+    #[test]
+    fn test_escaping() {
+        assert_eq!(xml_escape("a<b>&\"c'"), "a&lt;b&gt;&amp;&quot;c&apos;");
+        assert_eq!(percent_encode_path("data/a b+c.csv"), "data/a%20b%2Bc.csv");
+        assert_eq!(percent_encode_path("\u{2713}"), "%E2%9C%93");
+        assert_eq!(
+            percent_decode(&percent_encode_path("a b/\u{2713}")),
+            "a b/\u{2713}"
+        );
+    }
+
+    #[test]
+    fn test_date_formats() {
+        let t = Utc.timestamp_opt(1_700_000_000, 0).single().unwrap();
+        assert_eq!(format_iso8601(&t), "2023-11-14T22:13:20.000Z");
+        assert_eq!(format_http_date(&t), "Tue, 14 Nov 2023 22:13:20 GMT");
+    }
+
+    #[test]
+    fn test_repo_file_from_blob() {
+        let pointer = PointerFile::init_from_info("", "12345", 678);
+        let f = RepoFile::from_blob_content(pointer.to_string().as_bytes(), "");
+        assert!(matches!(f, RepoFile::Xet(_)));
+        assert_eq!(f.size(), 678);
+
+        let f = RepoFile::from_blob_content(b"hello", "");
+        assert!(matches!(f, RepoFile::Raw(_)));
+        assert_eq!(f.size(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_restricted_file_denied() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = git2::Repository::init(dir.path()).unwrap();
+        let mut translator = PointerFileTranslator::new_temporary(dir.path(), ShardVersion::V2)
+            .await
+            .unwrap();
+        let policy = AccessPolicy::parse(
+            "[[rule]]\npaths = [\"data/restricted\"]\nscopes = [\"restricted:read\"]",
+        )
+        .unwrap();
+        translator.set_access_control(Some(Arc::new(AccessControl::new(
+            Some(policy),
+            TokenClaims::default(),
+        ))));
+        let reader = RepoFileReader {
+            repo: Mutex::new(repo),
+            translator,
+        };
+
+        let pointer = PointerFile::init_from_info("", "12345", 678);
+        let f =
+            RepoFile::from_blob_content(pointer.to_string().as_bytes(), "data/restricted/a.csv");
+        let result = reader.read_range(&f, 0, 10).await;
+        assert!(matches!(result, Err(GitXetRepoError::AccessDenied(_))));
+    }
+}
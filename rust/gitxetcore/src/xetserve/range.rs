@@ -0,0 +1,189 @@
+use std::str::FromStr;
+
+use hyper::header::{HeaderMap, RANGE};
+use tracing::debug;
+use xet_error::Error;
+
+/// The error for parsing an HTTP Range header.
+#[non_exhaustive]
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum RangeHeaderError {
+    #[error("Unsupported range unit in {0:?}; only bytes are supported")]
+    UnsupportedUnit(String),
+
+    #[error("Malformed range {0:?}")]
+    Malformed(String),
+
+    #[error("Multiple ranges in a single request are not supported")]
+    MultipleRanges,
+}
+
+/// A single byte range from a `Range: bytes=...` header, before it is
+/// resolved against the size of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRangeSpec {
+    /// bytes=start-end, end inclusive.
+    Bounded(u64, u64),
+    /// bytes=start-
+    From(u64),
+    /// bytes=-len, the last len bytes.
+    Suffix(u64),
+}
+
+impl FromStr for ByteRangeSpec {
+    type Err = RangeHeaderError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let Some(spec) = s.strip_prefix("bytes=") else {
+            return Err(RangeHeaderError::UnsupportedUnit(s.to_string()));
+        };
+        if spec.contains(',') {
+            return Err(RangeHeaderError::MultipleRanges);
+        }
+        let malformed = || RangeHeaderError::Malformed(s.to_string());
+        let (start, end) = spec.trim().split_once('-').ok_or_else(malformed)?;
+        let parse = |v: &str| v.trim().parse::<u64>().map_err(|_| malformed());
+
+        match (start.trim().is_empty(), end.trim().is_empty()) {
+            (true, true) => Err(malformed()),
+            (true, false) => Ok(ByteRangeSpec::Suffix(parse(end)?)),
+            (false, true) => Ok(ByteRangeSpec::From(parse(start)?)),
+            (false, false) => {
+                let (start, end) = (parse(start)?, parse(end)?);
+                if end < start {
+                    return Err(malformed());
+                }
+                Ok(ByteRangeSpec::Bounded(start, end))
+            }
+        }
+    }
+}
+
+impl ByteRangeSpec {
+    /// Resolves the range against a file of the given size, returning the
+    /// half-open interval [start, end) to serve, or None if the range cannot
+    /// be satisfied.
+    pub fn resolve(&self, size: u64) -> Option<(u64, u64)> {
+        match *self {
+            ByteRangeSpec::Bounded(start, end) => {
+                (start < size).then(|| (start, end.saturating_add(1).min(size)))
+            }
+            ByteRangeSpec::From(start) => (start < size).then_some((start, size)),
+            ByteRangeSpec::Suffix(len) => {
+                (len > 0 && size > 0).then(|| (size.saturating_sub(len), size))
+            }
+        }
+    }
+}
+
+/// The portion of a file a request asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestedRange {
+    /// The whole file.
+    Full,
+    /// The half-open interval [start, end).
+    Partial(u64, u64),
+    /// A range that lies outside of the file.
+    Unsatisfiable,
+}
+
+impl RequestedRange {
+    /// Resolves the Range header of a request against a file of the given
+    /// size. Ranges that aren't understood are ignored and the whole file is
+    /// served, as permitted by RFC 9110.
+    pub fn from_headers(headers: &HeaderMap, size: u64) -> Self {
+        let Some(Ok(h)) = headers.get(RANGE).map(|h| h.to_str()) else {
+            return RequestedRange::Full;
+        };
+        match h.parse::<ByteRangeSpec>() {
+            Ok(spec) => match spec.resolve(size) {
+                Some((start, end)) => RequestedRange::Partial(start, end),
+                None => RequestedRange::Unsatisfiable,
+            },
+            Err(e) => {
+                debug!("Ignoring range header {h:?}: {e}");
+                RequestedRange::Full
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range_header() {
+        assert_eq!(
+            "bytes=0-99".parse::<ByteRangeSpec>(),
+            Ok(ByteRangeSpec::Bounded(0, 99))
+        );
+        assert_eq!(
+            "bytes=100-".parse::<ByteRangeSpec>(),
+            Ok(ByteRangeSpec::From(100))
+        );
+        assert_eq!(
+            "bytes=-500".parse::<ByteRangeSpec>(),
+            Ok(ByteRangeSpec::Suffix(500))
+        );
+
+        assert!("items=0-1".parse::<ByteRangeSpec>().is_err());
+        assert!("bytes=-".parse::<ByteRangeSpec>().is_err());
+        assert!("bytes=5-2".parse::<ByteRangeSpec>().is_err());
+        assert!("bytes=a-b".parse::<ByteRangeSpec>().is_err());
+        assert_eq!(
+            "bytes=0-1,5-6".parse::<ByteRangeSpec>(),
+            Err(RangeHeaderError::MultipleRanges)
+        );
+    }
+
+    #[test]
+    fn test_resolve_range() {
+        assert_eq!(ByteRangeSpec::Bounded(0, 99).resolve(1000), Some((0, 100)));
+        assert_eq!(
+            ByteRangeSpec::Bounded(900, 2000).resolve(1000),
+            Some((900, 1000))
+        );
+        assert_eq!(ByteRangeSpec::Bounded(1000, 2000).resolve(1000), None);
+        assert_eq!(
+            ByteRangeSpec::Bounded(0, u64::MAX).resolve(1000),
+            Some((0, 1000))
+        );
+        assert_eq!(ByteRangeSpec::From(10).resolve(1000), Some((10, 1000)));
+        assert_eq!(ByteRangeSpec::Suffix(10).resolve(1000), Some((990, 1000)));
+        assert_eq!(ByteRangeSpec::Suffix(5000).resolve(1000), Some((0, 1000)));
+        assert_eq!(ByteRangeSpec::Suffix(0).resolve(1000), None);
+    }
+
+    #[test]
+    fn test_requested_range() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            RequestedRange::from_headers(&headers, 100),
+            RequestedRange::Full
+        );
+
+        headers.insert(RANGE, "bytes=10-19".parse().unwrap());
+        assert_eq!(
+            RequestedRange::from_headers(&headers, 100),
+            RequestedRange::Partial(10, 20)
+        );
+        assert_eq!(
+            RequestedRange::from_headers(&headers, 5),
+            RequestedRange::Unsatisfiable
+        );
+
+        headers.insert(RANGE, "bytes=0-18446744073709551615".parse().unwrap());
+        assert_eq!(
+            RequestedRange::from_headers(&headers, 100),
+            RequestedRange::Partial(0, 100)
+        );
+
+        headers.insert(RANGE, "bytes=0-1,5-6".parse().unwrap());
+        assert_eq!(
+            RequestedRange::from_headers(&headers, 100),
+            RequestedRange::Full
+        );
+    }
+}
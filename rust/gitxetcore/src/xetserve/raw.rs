@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use hyper::header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use tracing::{debug, error};
+
+use super::{percent_decode, stream_range, RepoFileReader, RequestedRange};
+
+/// The url prefix files are served under, as /raw/<ref>/<path>.
+pub const RAW_PATH_PREFIX: &str = "/raw/";
+
+fn simple_response(status: StatusCode, msg: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "text/plain")
+        .body(Body::from(format!("{msg}\n")))
+        .unwrap()
+}
+
+/// Handles GET and HEAD requests for /raw/<ref>/<path>, honoring single
+/// byte-range Range headers.
+pub async fn handle_raw_request(reader: Arc<RepoFileReader>, req: Request<Body>) -> Response<Body> {
+    debug!("{} {}", req.method(), req.uri());
+
+    if req.method() != Method::GET && req.method() != Method::HEAD {
+        return simple_response(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "Only GET and HEAD are supported",
+        );
+    }
+
+    let Some(ref_path) = req.uri().path().strip_prefix(RAW_PATH_PREFIX) else {
+        return simple_response(
+            StatusCode::NOT_FOUND,
+            "Files are served at /raw/<ref>/<path>",
+        );
+    };
+    let ref_path = percent_decode(ref_path);
+
+    let file = match reader.resolve_ref_path(&ref_path).await {
+        Ok(Some((_, file))) => file,
+        Ok(None) => return simple_response(StatusCode::NOT_FOUND, "Not found"),
+        Err(e) => {
+            error!("Error resolving {ref_path:?}: {e:?}");
+            return simple_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string());
+        }
+    };
+    let size = file.size();
+
+    let (status, start, end) = match RequestedRange::from_headers(req.headers(), size) {
+        RequestedRange::Full => (StatusCode::OK, 0, size),
+        RequestedRange::Partial(start, end) => (StatusCode::PARTIAL_CONTENT, start, end),
+        RequestedRange::Unsatisfiable => {
+            return Response::builder()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(CONTENT_RANGE, format!("bytes */{size}"))
+                .body(Body::empty())
+                .unwrap();
+        }
+    };
+
+    let mut builder = Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/octet-stream")
+        .header(ACCEPT_RANGES, "bytes")
+        .header(CONTENT_LENGTH, end - start);
+    if status == StatusCode::PARTIAL_CONTENT {
+        builder = builder.header(CONTENT_RANGE, format!("bytes {start}-{}/{size}", end - 1));
+    }
+
+    let body = if req.method() == Method::HEAD {
+        Body::empty()
+    } else {
+        stream_range(reader, file, start, end)
+    };
+    builder.body(body).unwrap()
+}
@@ -0,0 +1,626 @@
+//! A read-only S3-compatible gateway.
+//!
+//! Buckets map to refs and object keys to paths within the tree of that ref,
+//! so `s3://main/data/train.csv` is `data/train.csv` at `main`. Only
+//! path-style addressing is supported and requests are not authenticated;
+//! any credentials the client signs with are accepted.
+//!
+//! Trees and file contents are read through the same [XetFSBare] used by
+//! `xet mount`, so listing, prefetching and caching behave as they do for a
+//! mounted repository.
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use chrono::{DateTime, TimeZone, Utc};
+use hyper::header::{
+    ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, LAST_MODIFIED,
+};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use lru::LruCache;
+use nfsserve::nfs::{fattr3, fileid3, nfsstat3};
+use nfsserve::vfs::NFSFileSystem;
+use tokio::sync::Mutex;
+use tracing::{debug, error};
+
+use super::vfs::{is_directory, lookup_path, readdir_all, stream_file_range};
+use super::{
+    format_http_date, format_iso8601, percent_decode, percent_encode_path, xml_escape,
+    RequestedRange,
+};
+use crate::config::XetConfig;
+use crate::errors::{GitXetRepoError, Result};
+use crate::git_integration::GitXetRepo;
+use crate::xetmnt::xetfs_bare::XetFSBare;
+
+const S3_XML_NAMESPACE: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+
+/// The default and maximum number of keys returned by a single list request.
+const S3_MAX_KEYS: usize = 1000;
+
+/// The number of commits to keep an open filesystem view of.
+const S3_FS_CACHE_SIZE: usize = 16;
+
+/// A bucket resolved to the commit its ref currently points to.
+struct Bucket {
+    fs: Arc<XetFSBare>,
+    last_modified: DateTime<Utc>,
+}
+
+/// An entry in an object listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ListEntry {
+    Object {
+        key: String,
+        size: u64,
+        etag: String,
+    },
+    CommonPrefix(String),
+}
+
+impl ListEntry {
+    fn key(&self) -> &str {
+        match self {
+            ListEntry::Object { key, .. } => key,
+            ListEntry::CommonPrefix(prefix) => prefix,
+        }
+    }
+}
+
+/// The S3 gateway state shared across requests.
+pub struct S3Gateway {
+    srcpath: PathBuf,
+    config: XetConfig,
+    prefetch: usize,
+    repo: Mutex<git2::Repository>,
+    filesystems: Mutex<LruCache<git2::Oid, Arc<XetFSBare>>>,
+}
+
+impl S3Gateway {
+    pub fn new(config: &XetConfig, prefetch: usize) -> Result<Self> {
+        let repo = GitXetRepo::open(config.clone())?.repo;
+        let srcpath = repo.workdir().unwrap_or_else(|| repo.path()).to_path_buf();
+        Ok(Self {
+            srcpath,
+            config: config.clone(),
+            prefetch,
+            repo: Mutex::new(repo),
+            filesystems: Mutex::new(LruCache::new(S3_FS_CACHE_SIZE)),
+        })
+    }
+
+    /// Resolves a bucket name as a ref, returning None if it does not name a
+    /// commit. The ref is resolved on every request so moving branches are
+    /// picked up; views of a commit are cached.
+    async fn bucket(&self, name: &str) -> Result<Option<Bucket>> {
+        let (oid, seconds) = {
+            let repo = self.repo.lock().await;
+            let Ok(commit) = repo.revparse_single(name).and_then(|o| o.peel_to_commit()) else {
+                return Ok(None);
+            };
+            (commit.id(), commit.time().seconds())
+        };
+        let last_modified = Utc
+            .timestamp_opt(seconds, 0)
+            .single()
+            .unwrap_or_else(Utc::now);
+
+        let mut filesystems = self.filesystems.lock().await;
+        if let Some(fs) = filesystems.get(&oid) {
+            return Ok(Some(Bucket {
+                fs: fs.clone(),
+                last_modified,
+            }));
+        }
+        let fs = XetFSBare::new(&self.srcpath, &self.config, &oid.to_string(), self.prefetch)
+            .await
+            .map_err(|e| GitXetRepoError::Other(format!("Unable to open {name}: {e}")))?;
+        let fs = Arc::new(fs);
+        filesystems.put(oid, fs.clone());
+        Ok(Some(Bucket { fs, last_modified }))
+    }
+
+    /// Lists local branches along with the time of their latest commit.
+    async fn list_branches(&self) -> Result<Vec<(String, DateTime<Utc>)>> {
+        let repo = self.repo.lock().await;
+        let mut ret = Vec::new();
+        for branch in repo.branches(Some(git2::BranchType::Local))? {
+            let (branch, _) = branch?;
+            let Some(name) = branch.name()?.map(|s| s.to_string()) else {
+                continue;
+            };
+            let seconds = branch.get().peel_to_commit()?.time().seconds();
+            let time = Utc
+                .timestamp_opt(seconds, 0)
+                .single()
+                .unwrap_or_else(Utc::now);
+            ret.push((name, time));
+        }
+        Ok(ret)
+    }
+}
+
+/// Lists all entries with keys beginning with prefix, sorted by key. With a
+/// delimiter, keys containing the delimiter after the prefix are rolled up
+/// into common prefixes.
+async fn list_entries(
+    fs: &XetFSBare,
+    prefix: &str,
+    delimiter: Option<&str>,
+) -> std::result::Result<Vec<ListEntry>, nfsstat3> {
+    // Only the directory containing the prefix needs to be walked.
+    let dir = match prefix.rfind('/') {
+        Some(i) => &prefix[..=i],
+        None => "",
+    };
+    let dirid = match lookup_path(fs, dir).await {
+        Ok(id) => id,
+        Err(nfsstat3::NFS3ERR_NOENT) | Err(nfsstat3::NFS3ERR_NOTDIR) => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let object = |key: String, id: fileid3, attr: &fattr3| ListEntry::Object {
+        key,
+        size: attr.size,
+        etag: fs.content_id(id).unwrap_or_default(),
+    };
+
+    let mut objects = Vec::new();
+    if delimiter == Some("/") {
+        // The common case maps directly onto a single directory listing.
+        for (name, id, attr) in readdir_all(fs, dirid).await? {
+            let key = format!("{dir}{name}");
+            if !key.starts_with(prefix) {
+                continue;
+            }
+            if is_directory(&attr) {
+                objects.push(ListEntry::CommonPrefix(format!("{key}/")));
+            } else {
+                objects.push(object(key, id, &attr));
+            }
+        }
+        objects.sort_by(|a, b| a.key().cmp(b.key()));
+        return Ok(objects);
+    }
+
+    let mut pending = vec![(dir.to_string(), dirid)];
+    while let Some((dir, dirid)) = pending.pop() {
+        for (name, id, attr) in readdir_all(fs, dirid).await? {
+            let key = format!("{dir}{name}");
+            if is_directory(&attr) {
+                let subdir = format!("{key}/");
+                if subdir.starts_with(prefix) || prefix.starts_with(&subdir) {
+                    pending.push((subdir, id));
+                }
+            } else if key.starts_with(prefix) {
+                objects.push(object(key, id, &attr));
+            }
+        }
+    }
+    Ok(group_by_delimiter(objects, prefix, delimiter))
+}
+
+/// Sorts the entries by key, rolling up keys that contain the delimiter past
+/// the prefix into common prefixes.
+fn group_by_delimiter(
+    entries: Vec<ListEntry>,
+    prefix: &str,
+    delimiter: Option<&str>,
+) -> Vec<ListEntry> {
+    let mut prefixes = BTreeSet::new();
+    let mut ret = Vec::new();
+    for entry in entries {
+        let key = entry.key();
+        let common = match delimiter {
+            Some(d) if !d.is_empty() && key.len() > prefix.len() => key[prefix.len()..]
+                .find(d)
+                .map(|i| key[..prefix.len() + i + d.len()].to_string()),
+            _ => None,
+        };
+        match common {
+            Some(common) => {
+                prefixes.insert(common);
+            }
+            None => ret.push(entry),
+        }
+    }
+    ret.extend(prefixes.into_iter().map(ListEntry::CommonPrefix));
+    ret.sort_by(|a, b| a.key().cmp(b.key()));
+    ret
+}
+
+/// Returns the page of sorted entries following start_after, and whether
+/// more entries remain after it.
+fn paginate<'a>(
+    entries: &'a [ListEntry],
+    start_after: Option<&str>,
+    max_keys: usize,
+) -> (&'a [ListEntry], bool) {
+    let start = match start_after {
+        Some(s) => entries.partition_point(|e| e.key() <= s),
+        None => 0,
+    };
+    let end = (start + max_keys).min(entries.len());
+    (&entries[start..end], end < entries.len())
+}
+
+fn xml_response(status: StatusCode, body: String) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/xml")
+        .body(Body::from(format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{body}"
+        )))
+        .unwrap()
+}
+
+fn error_response(
+    method: &Method,
+    status: StatusCode,
+    code: &str,
+    message: &str,
+    resource: &str,
+) -> Response<Body> {
+    if method == Method::HEAD {
+        // HEAD responses carry no body; clients go by the status alone.
+        return Response::builder()
+            .status(status)
+            .body(Body::empty())
+            .unwrap();
+    }
+    xml_response(
+        status,
+        format!(
+            "<Error><Code>{code}</Code><Message>{}</Message><Resource>{}</Resource></Error>",
+            xml_escape(message),
+            xml_escape(resource)
+        ),
+    )
+}
+
+fn internal_error(method: &Method, resource: &str, e: impl std::fmt::Debug) -> Response<Body> {
+    error!("Error handling S3 request for {resource:?}: {e:?}");
+    error_response(
+        method,
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "InternalError",
+        "We encountered an internal error. Please try again.",
+        resource,
+    )
+}
+
+/// Handles S3 requests: ListBuckets, HeadBucket, GetBucketLocation,
+/// ListObjects (v1 and v2), HeadObject and GetObject with single byte ranges.
+pub async fn handle_s3_request(gateway: Arc<S3Gateway>, req: Request<Body>) -> Response<Body> {
+    debug!("{} {}", req.method(), req.uri());
+    let method = req.method().clone();
+    let resource = req.uri().path().to_string();
+
+    if method != Method::GET && method != Method::HEAD {
+        return error_response(
+            &method,
+            StatusCode::METHOD_NOT_ALLOWED,
+            "MethodNotAllowed",
+            "This gateway is read-only",
+            &resource,
+        );
+    }
+
+    let path = percent_decode(resource.trim_start_matches('/'));
+    let (bucket_name, key) = path.split_once('/').unwrap_or((path.as_str(), ""));
+
+    if bucket_name.is_empty() {
+        return list_buckets(&gateway, &method).await;
+    }
+
+    let bucket = match gateway.bucket(bucket_name).await {
+        Ok(Some(b)) => b,
+        Ok(None) => {
+            return error_response(
+                &method,
+                StatusCode::NOT_FOUND,
+                "NoSuchBucket",
+                "The specified bucket does not exist",
+                &resource,
+            )
+        }
+        Err(e) => return internal_error(&method, &resource, e),
+    };
+
+    if !key.is_empty() {
+        return get_object(&req, bucket, key, &resource).await;
+    }
+
+    let query: Vec<(String, String)> = req
+        .uri()
+        .query()
+        .map(|q| {
+            url::form_urlencoded::parse(q.as_bytes())
+                .into_owned()
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if method == Method::HEAD {
+        return Response::builder()
+            .status(StatusCode::OK)
+            .body(Body::empty())
+            .unwrap();
+    }
+    if query.iter().any(|(k, _)| k == "location") {
+        return xml_response(
+            StatusCode::OK,
+            format!("<LocationConstraint xmlns=\"{S3_XML_NAMESPACE}\"></LocationConstraint>"),
+        );
+    }
+    list_objects(bucket_name, &bucket, &query, &resource).await
+}
+
+async fn list_buckets(gateway: &S3Gateway, method: &Method) -> Response<Body> {
+    let branches = match gateway.list_branches().await {
+        Ok(b) => b,
+        Err(e) => return internal_error(method, "/", e),
+    };
+    let mut body = format!("<ListAllMyBucketsResult xmlns=\"{S3_XML_NAMESPACE}\">");
+    body.push_str("<Owner><ID>xet</ID><DisplayName>xet</DisplayName></Owner><Buckets>");
+    for (name, time) in branches {
+        body.push_str(&format!(
+            "<Bucket><Name>{}</Name><CreationDate>{}</CreationDate></Bucket>",
+            xml_escape(&name),
+            format_iso8601(&time)
+        ));
+    }
+    body.push_str("</Buckets></ListAllMyBucketsResult>");
+    xml_response(StatusCode::OK, body)
+}
+
+async fn list_objects(
+    bucket_name: &str,
+    bucket: &Bucket,
+    query: &[(String, String)],
+    resource: &str,
+) -> Response<Body> {
+    let param = |name: &str| {
+        query
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    };
+    let v2 = param("list-type") == Some("2");
+    let prefix = param("prefix").unwrap_or("");
+    let delimiter = param("delimiter").filter(|d| !d.is_empty());
+    let url_encoded = param("encoding-type") == Some("url");
+    let max_keys = match param("max-keys").map(|m| m.parse::<usize>()) {
+        None => S3_MAX_KEYS,
+        Some(Ok(m)) => m.min(S3_MAX_KEYS),
+        Some(Err(_)) => {
+            return error_response(
+                &Method::GET,
+                StatusCode::BAD_REQUEST,
+                "InvalidArgument",
+                "max-keys must be a non-negative integer",
+                resource,
+            )
+        }
+    };
+    let start_after = if v2 {
+        param("continuation-token").or(param("start-after"))
+    } else {
+        param("marker")
+    };
+
+    let entries = match list_entries(&bucket.fs, prefix, delimiter).await {
+        Ok(e) => e,
+        Err(e) => return internal_error(&Method::GET, resource, e),
+    };
+    let (page, truncated) = paginate(&entries, start_after, max_keys);
+
+    let encode = |s: &str| {
+        if url_encoded {
+            xml_escape(&percent_encode_path(s))
+        } else {
+            xml_escape(s)
+        }
+    };
+
+    let mut body = format!("<ListBucketResult xmlns=\"{S3_XML_NAMESPACE}\">");
+    body.push_str(&format!("<Name>{}</Name>", xml_escape(bucket_name)));
+    body.push_str(&format!("<Prefix>{}</Prefix>", encode(prefix)));
+    if let Some(d) = delimiter {
+        body.push_str(&format!("<Delimiter>{}</Delimiter>", encode(d)));
+    }
+    if url_encoded {
+        body.push_str("<EncodingType>url</EncodingType>");
+    }
+    body.push_str(&format!("<MaxKeys>{max_keys}</MaxKeys>"));
+    body.push_str(&format!("<IsTruncated>{truncated}</IsTruncated>"));
+
+    // Continuation tokens are opaque to clients; the last key returned is used.
+    let next = page.last().filter(|_| truncated).map(|e| e.key());
+    if v2 {
+        body.push_str(&format!("<KeyCount>{}</KeyCount>", page.len()));
+        if let Some(token) = param("continuation-token") {
+            body.push_str(&format!(
+                "<ContinuationToken>{}</ContinuationToken>",
+                xml_escape(token)
+            ));
+        }
+        if let Some(s) = param("start-after") {
+            body.push_str(&format!("<StartAfter>{}</StartAfter>", encode(s)));
+        }
+        if let Some(next) = next {
+            body.push_str(&format!(
+                "<NextContinuationToken>{}</NextContinuationToken>",
+                xml_escape(next)
+            ));
+        }
+    } else {
+        body.push_str(&format!(
+            "<Marker>{}</Marker>",
+            encode(start_after.unwrap_or(""))
+        ));
+        if let Some(next) = next {
+            body.push_str(&format!("<NextMarker>{}</NextMarker>", encode(next)));
+        }
+    }
+
+    let last_modified = format_iso8601(&bucket.last_modified);
+    for entry in page {
+        match entry {
+            ListEntry::Object { key, size, etag } => body.push_str(&format!(
+                "<Contents><Key>{}</Key><LastModified>{last_modified}</LastModified>\
+                 <ETag>&quot;{etag}&quot;</ETag><Size>{size}</Size>\
+                 <StorageClass>STANDARD</StorageClass></Contents>",
+                encode(key)
+            )),
+            ListEntry::CommonPrefix(p) => body.push_str(&format!(
+                "<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>",
+                encode(p)
+            )),
+        }
+    }
+    body.push_str("</ListBucketResult>");
+    xml_response(StatusCode::OK, body)
+}
+
+async fn get_object(
+    req: &Request<Body>,
+    bucket: Bucket,
+    key: &str,
+    resource: &str,
+) -> Response<Body> {
+    let method = req.method();
+    let no_such_key = || {
+        error_response(
+            method,
+            StatusCode::NOT_FOUND,
+            "NoSuchKey",
+            "The specified key does not exist.",
+            resource,
+        )
+    };
+
+    let id = match lookup_path(&bucket.fs, key).await {
+        Ok(id) => id,
+        Err(nfsstat3::NFS3ERR_NOENT) | Err(nfsstat3::NFS3ERR_NOTDIR) => return no_such_key(),
+        Err(e) => return internal_error(method, resource, e),
+    };
+    let attr = match bucket.fs.getattr(id).await {
+        Ok(attr) => attr,
+        Err(e) => return internal_error(method, resource, e),
+    };
+    if is_directory(&attr) {
+        return no_such_key();
+    }
+    let size = attr.size;
+
+    let (status, start, end) = match RequestedRange::from_headers(req.headers(), size) {
+        RequestedRange::Full => (StatusCode::OK, 0, size),
+        RequestedRange::Partial(start, end) => (StatusCode::PARTIAL_CONTENT, start, end),
+        RequestedRange::Unsatisfiable => {
+            return error_response(
+                method,
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                "InvalidRange",
+                "The requested range is not satisfiable",
+                resource,
+            )
+        }
+    };
+
+    let mut builder = Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/octet-stream")
+        .header(ACCEPT_RANGES, "bytes")
+        .header(CONTENT_LENGTH, end - start)
+        .header(LAST_MODIFIED, format_http_date(&bucket.last_modified));
+    if let Some(etag) = bucket.fs.content_id(id) {
+        builder = builder.header(ETAG, format!("\"{etag}\""));
+    }
+    if status == StatusCode::PARTIAL_CONTENT {
+        builder = builder.header(CONTENT_RANGE, format!("bytes {start}-{}/{size}", end - 1));
+    }
+
+    let body = if method == Method::HEAD {
+        Body::empty()
+    } else {
+        stream_file_range(bucket.fs, id, start, end)
+    };
+    builder.body(body).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(key: &str) -> ListEntry {
+        ListEntry::Object {
+            key: key.to_string(),
+            size: 1,
+            etag: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_group_by_delimiter() {
+        let entries = vec![
+            object("data/b.csv"),
+            object("data/a/1.csv"),
+            object("data/a/2.csv"),
+            object("data-readme"),
+        ];
+        let grouped = group_by_delimiter(entries.clone(), "data", Some("/"));
+        assert_eq!(
+            grouped,
+            vec![
+                object("data-readme"),
+                ListEntry::CommonPrefix("data/".to_string())
+            ]
+        );
+
+        let grouped = group_by_delimiter(entries.clone(), "data/", Some("/"));
+        assert_eq!(
+            grouped,
+            vec![
+                ListEntry::CommonPrefix("data/a/".to_string()),
+                object("data/b.csv"),
+            ]
+        );
+
+        let grouped = group_by_delimiter(entries, "", None);
+        let keys: Vec<_> = grouped.iter().map(|e| e.key()).collect();
+        assert_eq!(
+            keys,
+            vec!["data-readme", "data/a/1.csv", "data/a/2.csv", "data/b.csv"]
+        );
+    }
+
+    #[test]
+    fn test_paginate() {
+        let entries: Vec<_> = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|k| object(k))
+            .collect();
+
+        let (page, truncated) = paginate(&entries, None, 2);
+        assert_eq!(page.len(), 2);
+        assert_eq!(page[1].key(), "b");
+        assert!(truncated);
+
+        let (page, truncated) = paginate(&entries, Some("b"), 2);
+        assert_eq!(page[0].key(), "c");
+        assert!(truncated);
+
+        let (page, truncated) = paginate(&entries, Some("c"), 10);
+        assert_eq!(page.len(), 2);
+        assert!(!truncated);
+
+        // Markers need not be existing keys.
+        let (page, _) = paginate(&entries, Some("bb"), 10);
+        assert_eq!(page[0].key(), "c");
+
+        let (page, truncated) = paginate(&entries, Some("z"), 10);
+        assert!(page.is_empty());
+        assert!(!truncated);
+    }
+}
@@ -0,0 +1,97 @@
+//! Path based access to the read-only filesystems backing `xet mount`.
+//!
+//! The HTTP frontends address files by path rather than by NFS file id, so
+//! these helpers translate between the two on top of the [NFSFileSystem]
+//! interface, letting every mount backend be served without changes.
+use std::sync::Arc;
+
+use hyper::body::{Body, Bytes};
+use nfsserve::nfs::{fattr3, fileid3, ftype3, nfsstat3};
+use nfsserve::vfs::NFSFileSystem;
+use tracing::error;
+
+use super::SERVE_READ_CHUNK_SIZE;
+
+/// The number of directory entries to request per readdir call.
+const READDIR_BATCH_SIZE: usize = 1024;
+
+pub fn is_directory(attr: &fattr3) -> bool {
+    matches!(attr.ftype, ftype3::NF3DIR)
+}
+
+/// Resolves a /-separated path to a file id, starting from the root.
+/// Empty components are ignored, and "." and ".." are not resolved.
+pub async fn lookup_path<F: NFSFileSystem + ?Sized>(
+    fs: &F,
+    path: &str,
+) -> Result<fileid3, nfsstat3> {
+    let mut id = fs.root_dir();
+    for component in path.split('/').filter(|c| !c.is_empty()) {
+        if component == "." || component == ".." {
+            return Err(nfsstat3::NFS3ERR_NOENT);
+        }
+        id = fs.lookup(id, &component.as_bytes().into()).await?;
+    }
+    Ok(id)
+}
+
+/// Lists the (name, id, attributes) of every entry in a directory.
+pub async fn readdir_all<F: NFSFileSystem + ?Sized>(
+    fs: &F,
+    dirid: fileid3,
+) -> Result<Vec<(String, fileid3, fattr3)>, nfsstat3> {
+    let mut ret = Vec::new();
+    let mut start_after = 0;
+    loop {
+        let result = fs.readdir(dirid, start_after, READDIR_BATCH_SIZE).await?;
+        let Some(last) = result.entries.last() else {
+            break;
+        };
+        start_after = last.fileid;
+        for entry in result.entries {
+            let name = String::from_utf8_lossy(&entry.name).to_string();
+            ret.push((name, entry.fileid, entry.attr));
+        }
+        if result.end {
+            break;
+        }
+    }
+    Ok(ret)
+}
+
+/// Streams the bytes in [start, end) of a file as a response body, reading
+/// SERVE_READ_CHUNK_SIZE bytes at a time.
+pub fn stream_file_range<F: NFSFileSystem + ?Sized + Send + Sync + 'static>(
+    fs: Arc<F>,
+    id: fileid3,
+    start: u64,
+    end: u64,
+) -> Body {
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        let mut pos = start;
+        while pos < end {
+            let count = (end - pos).min(SERVE_READ_CHUNK_SIZE) as u32;
+            match fs.read(id, pos, count).await {
+                Ok((data, _)) if !data.is_empty() => {
+                    pos += data.len() as u64;
+                    if sender.send_data(Bytes::from(data)).await.is_err() {
+                        // Client went away.
+                        return;
+                    }
+                }
+                Ok(_) => {
+                    error!("Unexpected end of file reading {id} at {pos}");
+                    sender.abort();
+                    return;
+                }
+                Err(e) => {
+                    error!("Error reading {id} at {pos}: {e:?}");
+                    sender.abort();
+                    return;
+                }
+            }
+        }
+    });
+    body
+}
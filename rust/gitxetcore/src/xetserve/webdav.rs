@@ -0,0 +1,271 @@
+//! A read-only WebDAV (class 1) frontend for the mount filesystems.
+//!
+//! Supports OPTIONS, PROPFIND, GET and HEAD, which is enough for the macOS
+//! Finder, the Windows WebClient service and davfs2 to map the repository as
+//! a network drive. Since no locking is advertised, clients mount it
+//! read-only.
+use std::sync::Arc;
+
+use chrono::{DateTime, TimeZone, Utc};
+use hyper::header::{
+    HeaderValue, ACCEPT_RANGES, ALLOW, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, LAST_MODIFIED,
+};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use nfsserve::nfs::{fattr3, nfsstat3};
+use nfsserve::vfs::NFSFileSystem;
+use tracing::{debug, error};
+
+use super::vfs::{is_directory, lookup_path, readdir_all, stream_file_range};
+use super::{
+    format_http_date, format_iso8601, percent_decode, percent_encode_path, xml_escape,
+    RequestedRange,
+};
+
+/// A read-only mount filesystem shared across requests.
+pub type SharedFileSystem = Arc<dyn NFSFileSystem + Send + Sync>;
+
+const ALLOWED_METHODS: &str = "OPTIONS, GET, HEAD, PROPFIND";
+
+/// The depth of a PROPFIND request. Infinite depth is served as depth 1,
+/// which all common clients use anyway.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Depth {
+    Zero,
+    One,
+}
+
+impl Depth {
+    fn from_request(req: &Request<Body>) -> Self {
+        match req.headers().get("Depth").and_then(|h| h.to_str().ok()) {
+            Some("0") => Depth::Zero,
+            _ => Depth::One,
+        }
+    }
+}
+
+fn simple_response(status: StatusCode, msg: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "text/plain")
+        .body(Body::from(format!("{msg}\n")))
+        .unwrap()
+}
+
+fn error_status(e: &nfsstat3) -> StatusCode {
+    match e {
+        nfsstat3::NFS3ERR_NOENT | nfsstat3::NFS3ERR_NOTDIR => StatusCode::NOT_FOUND,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+fn modified_time(attr: &fattr3) -> DateTime<Utc> {
+    Utc.timestamp_opt(attr.mtime.seconds as i64, attr.mtime.nseconds)
+        .single()
+        .unwrap_or_else(Utc::now)
+}
+
+/// Handles a WebDAV request, with the request path resolved from the root of
+/// the filesystem.
+pub async fn handle_webdav_request(fs: SharedFileSystem, req: Request<Body>) -> Response<Body> {
+    debug!("{} {}", req.method(), req.uri());
+    let path = percent_decode(req.uri().path());
+
+    match req.method().as_str() {
+        "OPTIONS" => Response::builder()
+            .status(StatusCode::OK)
+            .header("DAV", "1")
+            .header("MS-Author-Via", "DAV")
+            .header(ALLOW, ALLOWED_METHODS)
+            .header(CONTENT_LENGTH, 0)
+            .body(Body::empty())
+            .unwrap(),
+        "PROPFIND" => propfind(&fs, &path, Depth::from_request(&req)).await,
+        "GET" | "HEAD" => get(fs, &req, &path).await,
+        _ => {
+            let mut response =
+                simple_response(StatusCode::METHOD_NOT_ALLOWED, "This share is read-only");
+            response
+                .headers_mut()
+                .insert(ALLOW, HeaderValue::from_static(ALLOWED_METHODS));
+            response
+        }
+    }
+}
+
+/// Renders the properties of a single resource as a multistatus response
+/// element. Collections are given hrefs with a trailing slash.
+fn propfind_entry(path: &str, attr: &fattr3) -> String {
+    let is_dir = is_directory(attr);
+    let name = path.trim_end_matches('/').rsplit('/').next().unwrap_or("");
+    let mut href = format!("/{}", path.trim_matches('/'));
+    if is_dir && !href.ends_with('/') {
+        href.push('/');
+    }
+    let modified = modified_time(attr);
+
+    let mut props = format!(
+        "<D:displayname>{}</D:displayname>\
+         <D:getlastmodified>{}</D:getlastmodified>\
+         <D:creationdate>{}</D:creationdate>",
+        xml_escape(name),
+        format_http_date(&modified),
+        format_iso8601(&modified)
+    );
+    if is_dir {
+        props.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+    } else {
+        props.push_str(&format!(
+            "<D:resourcetype/><D:getcontentlength>{}</D:getcontentlength>\
+             <D:getcontenttype>application/octet-stream</D:getcontenttype>",
+            attr.size
+        ));
+    }
+    format!(
+        "<D:response><D:href>{}</D:href><D:propstat><D:prop>{props}</D:prop>\
+         <D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>",
+        xml_escape(&percent_encode_path(&href))
+    )
+}
+
+/// Answers a PROPFIND with all properties of the resource and, at depth 1,
+/// of its children. The properties requested in the body are not inspected.
+async fn propfind(fs: &SharedFileSystem, path: &str, depth: Depth) -> Response<Body> {
+    let result = async {
+        let id = lookup_path(fs.as_ref(), path).await?;
+        let attr = fs.getattr(id).await?;
+        let mut body = propfind_entry(path, &attr);
+        if depth == Depth::One && is_directory(&attr) {
+            let dir = path.trim_end_matches('/');
+            for (name, _, attr) in readdir_all(fs.as_ref(), id).await? {
+                body.push_str(&propfind_entry(&format!("{dir}/{name}"), &attr));
+            }
+        }
+        Ok::<_, nfsstat3>(body)
+    }
+    .await;
+
+    match result {
+        Ok(entries) => Response::builder()
+            .status(StatusCode::MULTI_STATUS)
+            .header(CONTENT_TYPE, "application/xml; charset=utf-8")
+            .body(Body::from(format!(
+                "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+                 <D:multistatus xmlns:D=\"DAV:\">{entries}</D:multistatus>"
+            )))
+            .unwrap(),
+        Err(e) => {
+            let status = error_status(&e);
+            if status == StatusCode::INTERNAL_SERVER_ERROR {
+                error!("Error in PROPFIND {path:?}: {e:?}");
+            }
+            simple_response(status, "Not found")
+        }
+    }
+}
+
+/// Serves a file with support for single byte-range requests. Directories
+/// are rendered as a plain listing for browsers.
+async fn get(fs: SharedFileSystem, req: &Request<Body>, path: &str) -> Response<Body> {
+    let (id, attr) = match async {
+        let id = lookup_path(fs.as_ref(), path).await?;
+        Ok::<_, nfsstat3>((id, fs.getattr(id).await?))
+    }
+    .await
+    {
+        Ok(r) => r,
+        Err(e) => return simple_response(error_status(&e), "Not found"),
+    };
+
+    if is_directory(&attr) {
+        let entries = match readdir_all(fs.as_ref(), id).await {
+            Ok(entries) => entries,
+            Err(e) => return simple_response(error_status(&e), "Unable to list directory"),
+        };
+        let mut listing = String::new();
+        for (name, _, attr) in entries {
+            let suffix = if is_directory(&attr) { "/" } else { "" };
+            listing.push_str(&format!("{name}{suffix}\n"));
+        }
+        return simple_response(StatusCode::OK, listing.trim_end());
+    }
+
+    let size = attr.size;
+    let (status, start, end) = match RequestedRange::from_headers(req.headers(), size) {
+        RequestedRange::Full => (StatusCode::OK, 0, size),
+        RequestedRange::Partial(start, end) => (StatusCode::PARTIAL_CONTENT, start, end),
+        RequestedRange::Unsatisfiable => {
+            return Response::builder()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(CONTENT_RANGE, format!("bytes */{size}"))
+                .body(Body::empty())
+                .unwrap();
+        }
+    };
+
+    let mut builder = Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/octet-stream")
+        .header(ACCEPT_RANGES, "bytes")
+        .header(CONTENT_LENGTH, end - start)
+        .header(LAST_MODIFIED, format_http_date(&modified_time(&attr)));
+    if status == StatusCode::PARTIAL_CONTENT {
+        builder = builder.header(CONTENT_RANGE, format!("bytes {start}-{}/{size}", end - 1));
+    }
+
+    let body = if req.method() == Method::HEAD {
+        Body::empty()
+    } else {
+        stream_file_range(fs, id, start, end)
+    };
+    builder.body(body).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use nfsserve::nfs::{ftype3, nfstime3, specdata3};
+
+    use super::*;
+
+    fn attr(ftype: ftype3, size: u64) -> fattr3 {
+        let time = nfstime3 {
+            seconds: 1_700_000_000,
+            nseconds: 0,
+        };
+        fattr3 {
+            ftype,
+            mode: 0o444,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            size,
+            used: size,
+            rdev: specdata3::default(),
+            fsid: 0,
+            fileid: 1,
+            atime: time,
+            mtime: time,
+            ctime: time,
+        }
+    }
+
+    #[test]
+    fn test_propfind_entry_file() {
+        let entry = propfind_entry("data/a b.csv", &attr(ftype3::NF3REG, 1234));
+        assert!(entry.contains("<D:href>/data/a%20b.csv</D:href>"));
+        assert!(entry.contains("<D:displayname>a b.csv</D:displayname>"));
+        assert!(entry.contains("<D:getcontentlength>1234</D:getcontentlength>"));
+        assert!(entry.contains("<D:resourcetype/>"));
+        assert!(entry.contains("Tue, 14 Nov 2023 22:13:20 GMT"));
+    }
+
+    #[test]
+    fn test_propfind_entry_directory() {
+        let entry = propfind_entry("/data", &attr(ftype3::NF3DIR, 0));
+        assert!(entry.contains("<D:href>/data/</D:href>"));
+        assert!(entry.contains("<D:collection/>"));
+        assert!(!entry.contains("getcontentlength"));
+
+        let root = propfind_entry("/", &attr(ftype3::NF3DIR, 0));
+        assert!(root.contains("<D:href>/</D:href>"));
+    }
+}
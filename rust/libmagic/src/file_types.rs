@@ -26,6 +26,11 @@ pub fn get_summary_from_extension(extension: &str) -> LibmagicSummary {
     }
 }
 
+/// Whether files with the extension are of a type known by name.
+pub fn is_known_extension(extension: &str) -> bool {
+    FILE_TYPES.contains_key(extension)
+}
+
 // File type info from https://github.com/lukaszsliwa/friendly_mime/blob/587ebd146b3b177229e7f10c55095c54e5e2590e/mimes.csv
 // With some additions like e.g. nwb (Neurodata Without Borders)
 static FILE_TYPES: phf::Map<&'static str, FileTypeInfo> = phf_map! {
@@ -59,6 +64,7 @@ static FILE_TYPES: phf::Map<&'static str, FileTypeInfo> = phf_map! {
     "apk" => FileTypeInfo { friendly_type: "Android Package Archive", mime_type: "application/vnd.android.package-archive"},
     "application" => FileTypeInfo { friendly_type: "Microsoft ClickOnce", mime_type: "application/x-ms-application"},
     "apr" => FileTypeInfo { friendly_type: "Lotus Approach", mime_type: "application/vnd.lotus-approach"},
+    "arrow" => FileTypeInfo { friendly_type: "Apache Arrow", mime_type: "application/vnd.apache.arrow.file"},
     "asf" => FileTypeInfo { friendly_type: "Microsoft Advanced Systems Format (ASF)", mime_type: "video/x-ms-asf"},
     "aso" => FileTypeInfo { friendly_type: "Simply Accounting", mime_type: "application/vnd.accpac.simply.aso"},
     "atc" => FileTypeInfo { friendly_type: "ACU Cobol", mime_type: "application/vnd.acucorp"},
@@ -188,6 +194,7 @@ static FILE_TYPES: phf::Map<&'static str, FileTypeInfo> = phf_map! {
     "efif" => FileTypeInfo { friendly_type: "Pcsel eFIF File", mime_type: "application/vnd.picsel"},
     "ei6" => FileTypeInfo { friendly_type: "Proprietary P&G Standard Reporting System", mime_type: "application/vnd.pg.osasli"},
     "el" => FileTypeInfo { friendly_type: "Emacs Lisp Source File", mime_type: "text/x-lisp"},
+    "elf" => FileTypeInfo { friendly_type: "ELF Executable", mime_type: "application/x-elf"},
     "eml" => FileTypeInfo { friendly_type: "Email Message", mime_type: "message/rfc822"},
     "emma" => FileTypeInfo { friendly_type: "Extensible MultiModal Annotation", mime_type: "application/emma+xml"},
     "env" => FileTypeInfo { friendly_type: "Environment Variables", mime_type: "text/plain"},
@@ -215,6 +222,7 @@ static FILE_TYPES: phf::Map<&'static str, FileTypeInfo> = phf_map! {
     "fg5" => FileTypeInfo { friendly_type: "Fujitsu Oasys", mime_type: "application/vnd.fujitsu.oasysgp"},
     "fh" => FileTypeInfo { friendly_type: "FreeHand MX", mime_type: "image/x-freehand"},
     "fig" => FileTypeInfo { friendly_type: "Xfig", mime_type: "application/x-xfig"},
+    "flac" => FileTypeInfo { friendly_type: "FLAC Audio", mime_type: "audio/flac"},
     "fli" => FileTypeInfo { friendly_type: "FLI/FLC Animation Format", mime_type: "video/x-fli"},
     "flo" => FileTypeInfo { friendly_type: "Micrografx", mime_type: "application/vnd.micrografx.flo"},
     "flv" => FileTypeInfo { friendly_type: "Flash Video", mime_type: "video/x-flv"},
@@ -263,6 +271,7 @@ static FILE_TYPES: phf::Map<&'static str, FileTypeInfo> = phf_map! {
     "h261" => FileTypeInfo { friendly_type: "H.261", mime_type: "video/h261"},
     "h263" => FileTypeInfo { friendly_type: "H.263", mime_type: "video/h263"},
     "h264" => FileTypeInfo { friendly_type: "H.264", mime_type: "video/h264"},
+    "h5" => FileTypeInfo { friendly_type: "HDF5", mime_type: "application/x-hdf5"},
     "hal" => FileTypeInfo { friendly_type: "Hypertext Application Language", mime_type: "application/vnd.hal+xml"},
     "hbci" => FileTypeInfo { friendly_type: "Homebanking Computer Interface (HBCI)", mime_type: "application/vnd.hbci"},
     "hdf" => FileTypeInfo { friendly_type: "Hierarchical Data Format", mime_type: "application/x-hdf"},
@@ -424,6 +433,7 @@ static FILE_TYPES: phf::Map<&'static str, FileTypeInfo> = phf_map! {
     "nns" => FileTypeInfo { friendly_type: "NobleNet Sealer", mime_type: "application/vnd.noblenet-sealer"},
     "nnw" => FileTypeInfo { friendly_type: "NobleNet Web", mime_type: "application/vnd.noblenet-web"},
     "npx" => FileTypeInfo { friendly_type: "FlashPix", mime_type: "image/vnd.net-fpx"},
+    "npy" => FileTypeInfo { friendly_type: "NumPy Array", mime_type: "application/octet-stream"},
     "nsf" => FileTypeInfo { friendly_type: "Lotus Notes", mime_type: "application/vnd.lotus-notes"},
     "nwb" => FileTypeInfo { friendly_type: "Neurodata Without Borders", mime_type: "application/x-hdf"},
     "oa2" => FileTypeInfo { friendly_type: "Fujitsu Oasys", mime_type: "application/vnd.fujitsu.oasys2"},
@@ -442,6 +452,7 @@ static FILE_TYPES: phf::Map<&'static str, FileTypeInfo> = phf_map! {
     "ods" => FileTypeInfo { friendly_type: "OpenDocument Spreadsheet", mime_type: "application/vnd.oasis.opendocument.spreadsheet"},
     "odt" => FileTypeInfo { friendly_type: "OpenDocument Text", mime_type: "application/vnd.oasis.opendocument.text"},
     "oga" => FileTypeInfo { friendly_type: "Ogg Audio", mime_type: "audio/ogg"},
+    "ogg" => FileTypeInfo { friendly_type: "Ogg Media", mime_type: "application/ogg"},
     "ogv" => FileTypeInfo { friendly_type: "Ogg Video", mime_type: "video/ogg"},
     "ogx" => FileTypeInfo { friendly_type: "Ogg", mime_type: "application/ogg"},
     "onetoc" => FileTypeInfo { friendly_type: "Microsoft OneNote", mime_type: "application/onenote"},
@@ -613,6 +624,7 @@ static FILE_TYPES: phf::Map<&'static str, FileTypeInfo> = phf_map! {
     "spot" => FileTypeInfo { friendly_type: "In3D - 3DML", mime_type: "text/vnd.in3d.spot"},
     "spp" => FileTypeInfo { friendly_type: "Server-Based Certificate Validation Protocol - Validation Policies - Response", mime_type: "application/scvp-vp-response"},
     "spq" => FileTypeInfo { friendly_type: "Server-Based Certificate Validation Protocol - Validation Policies - Request", mime_type: "application/scvp-vp-request"},
+    "sqlite" => FileTypeInfo { friendly_type: "SQLite Database", mime_type: "application/vnd.sqlite3"},
     "src" => FileTypeInfo { friendly_type: "WAIS Source", mime_type: "application/x-wais-source"},
     "sru" => FileTypeInfo { friendly_type: "Search/Retrieve via URL Response Format", mime_type: "application/sru+xml"},
     "srx" => FileTypeInfo { friendly_type: "SPARQL - Results", mime_type: "application/sparql-results+xml"},
@@ -712,6 +724,7 @@ static FILE_TYPES: phf::Map<&'static str, FileTypeInfo> = phf_map! {
     "vtu" => FileTypeInfo { friendly_type: "Virtue VTU", mime_type: "model/vnd.vtu"},
     "vxml" => FileTypeInfo { friendly_type: "VoiceXML", mime_type: "application/voicexml+xml"},
     "wad" => FileTypeInfo { friendly_type: "Doom Video Game", mime_type: "application/x-doom"},
+    "wasm" => FileTypeInfo { friendly_type: "WebAssembly Binary", mime_type: "application/wasm"},
     "wav" => FileTypeInfo { friendly_type: "Waveform Audio File Format (WAV)", mime_type: "audio/x-wav"},
     "wax" => FileTypeInfo { friendly_type: "Microsoft Windows Media Audio Redirector", mime_type: "audio/x-ms-wax"},
     "wbmp" => FileTypeInfo { friendly_type: "WAP Bitamp (WBMP)", mime_type: "image/vnd.wap.wbmp"},
@@ -782,6 +795,7 @@ static FILE_TYPES: phf::Map<&'static str, FileTypeInfo> = phf_map! {
     "xul" => FileTypeInfo { friendly_type: "XUL - XML User Interface Language", mime_type: "application/vnd.mozilla.xul+xml"},
     "xwd" => FileTypeInfo { friendly_type: "X Window Dump", mime_type: "image/x-xwindowdump"},
     "xyz" => FileTypeInfo { friendly_type: "XYZ File Format", mime_type: "chemical/x-xyz"},
+    "xz" => FileTypeInfo { friendly_type: "XZ Compressed File", mime_type: "application/x-xz"},
     "yang" => FileTypeInfo { friendly_type: "YANG Data Modeling Language", mime_type: "application/yang"},
     "yin" => FileTypeInfo { friendly_type: "YIN (YANG - XML)", mime_type: "application/yin+xml"},
     "yaml" => FileTypeInfo { friendly_type: "YAML", mime_type: "application/x-yaml"},
@@ -1,2 +1,3 @@
 pub mod file_types;
 pub mod libmagic;
+pub mod signatures;
@@ -1,9 +1,19 @@
+use std::fs::File;
+use std::io::Read;
 use std::path::Path;
+use std::str::FromStr;
 
 use anyhow::anyhow;
 use serde::{Deserialize, Serialize};
 
-use crate::file_types::get_summary_from_extension;
+use crate::file_types::{get_summary_from_extension, is_known_extension};
+use crate::signatures::{detect_signature, SIGNATURE_HEAD_LEN};
+
+/// The version of the file type rules behind LibmagicSummary. Bump this when
+/// the summary produced for a file changes, so that summaries cached from
+/// the previous rules are recomputed.
+pub const LIBMAGIC_SUMMARY_VERSION: u32 = 2;
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct LibmagicSummary {
     pub file_type: String,
@@ -27,12 +37,38 @@ impl Default for LibmagicSummary {
     }
 }
 
+/// How the type of a file is named: from its extension, or from the
+/// signature at the start of its content. Either way falls back to the
+/// other when it does not know the type.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TypeDetector {
+    #[default]
+    Extension,
+    Signature,
+}
+
+impl FromStr for TypeDetector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "extension" => Ok(TypeDetector::Extension),
+            "signature" => Ok(TypeDetector::Signature),
+            _ => Err(anyhow!("{s} is not one of 'extension' or 'signature'")),
+        }
+    }
+}
+
 // Produces a "libmagic" summary (libmagic file type results and heuristics on top of that).
 // args.file can be a path to any file within the repo.
 // Assumes the _real_ file contents are at the given path, not a pointer file.
 // The expected use case is that this utility is called during (immediately after?) smudge.
 pub fn print_libmagic_summary(file_path: &Path) -> anyhow::Result<()> {
-    let result = summarize_libmagic(file_path)?;
+    let mut head = Vec::with_capacity(SIGNATURE_HEAD_LEN);
+    File::open(file_path)?
+        .take(SIGNATURE_HEAD_LEN as u64)
+        .read_to_end(&mut head)?;
+    let result = summarize_file_type(file_path, Some(&head), TypeDetector::default());
     let content_str = serde_json::to_string_pretty(&result)
         .map_err(|_| anyhow!("Failed to serialize libmagic summary to JSON"))?;
     println!("{content_str}");
@@ -44,11 +80,60 @@ pub fn print_libmagic_summary(file_path: &Path) -> anyhow::Result<()> {
 // Assumes the _real_ file contents are at the given path, not a pointer file.
 // The expected use case is that this utility is called during (immediately after?) smudge.
 pub fn summarize_libmagic(file_path: &Path) -> anyhow::Result<LibmagicSummary> {
-    if let Some(os_ext) = file_path.extension() {
-        if let Some(ext) = os_ext.to_str() {
-            let summary = get_summary_from_extension(ext);
-            return Ok(summary);
-        }
+    Ok(summarize_file_type(
+        file_path,
+        None,
+        TypeDetector::default(),
+    ))
+}
+
+/// Summarizes the type of the file at file_path, whose content starts with
+/// head if known. Only the first SIGNATURE_HEAD_LEN bytes of head are
+/// looked at.
+pub fn summarize_file_type(
+    file_path: &Path,
+    head: Option<&[u8]>,
+    detector: TypeDetector,
+) -> LibmagicSummary {
+    let ext = file_path.extension().and_then(|ext| ext.to_str());
+    let signature = || head.and_then(detect_signature);
+    let detected = match detector {
+        TypeDetector::Extension => ext.filter(|ext| is_known_extension(ext)).or_else(signature),
+        TypeDetector::Signature => signature().or(ext),
+    };
+    match detected.or(ext) {
+        Some(ext) => get_summary_from_extension(ext),
+        None => LibmagicSummary::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_file_type() {
+        let png: &[u8] = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR";
+        let csv: &[u8] = b"a,b\n";
+        let summary = |path: &str, head: Option<&[u8]>, detector| {
+            summarize_file_type(Path::new(path), head, detector).file_type
+        };
+
+        // The extension wins when known, the signature otherwise.
+        assert_eq!(summary("a.csv", Some(png), TypeDetector::Extension), "csv");
+        assert_eq!(summary("a.dat", Some(png), TypeDetector::Extension), "png");
+        assert_eq!(summary("a", Some(png), TypeDetector::Extension), "png");
+        assert_eq!(summary("a.dat", None, TypeDetector::Extension), "dat");
+
+        // The signature wins when recognized, the extension otherwise.
+        assert_eq!(summary("a.csv", Some(png), TypeDetector::Signature), "png");
+        assert_eq!(summary("a.csv", Some(csv), TypeDetector::Signature), "csv");
+        assert_eq!(summary("a", Some(csv), TypeDetector::Signature), "");
+
+        assert_eq!(
+            "Signature".parse::<TypeDetector>().unwrap(),
+            TypeDetector::Signature
+        );
+        assert!("magic".parse::<TypeDetector>().is_err());
     }
-    Ok(LibmagicSummary::default())
 }
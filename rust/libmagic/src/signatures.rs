@@ -0,0 +1,86 @@
+/// The bytes at the start of a file needed to recognize any of the
+/// signatures below.
+pub const SIGNATURE_HEAD_LEN: usize = 512;
+
+/// Signatures found at an offset of the file, and the extension of the
+/// files starting with them.
+static SIGNATURES: &[(usize, &[u8], &str)] = &[
+    (0, b"\x89PNG\r\n\x1a\n", "png"),
+    (0, b"\xff\xd8\xff", "jpg"),
+    (0, b"GIF87a", "gif"),
+    (0, b"GIF89a", "gif"),
+    (0, b"BM", "bmp"),
+    (0, b"II*\x00", "tiff"),
+    (0, b"MM\x00*", "tiff"),
+    (0, b"%PDF-", "pdf"),
+    (0, b"PK\x03\x04", "zip"),
+    (0, b"PK\x05\x06", "zip"),
+    (0, b"\x1f\x8b", "gz"),
+    (0, b"BZh", "bz2"),
+    (0, b"\xfd7zXZ\x00", "xz"),
+    (0, b"\x28\xb5\x2f\xfd", "zst"),
+    (0, b"7z\xbc\xaf\x27\x1c", "7z"),
+    (257, b"ustar", "tar"),
+    (0, b"PAR1", "parquet"),
+    (0, b"ARROW1", "arrow"),
+    (0, b"\x89HDF\r\n\x1a\n", "h5"),
+    (0, b"\x93NUMPY", "npy"),
+    (0, b"SQLite format 3\x00", "sqlite"),
+    (0, b"\x7fELF", "elf"),
+    (0, b"MZ", "exe"),
+    (0, b"\xca\xfe\xba\xbe", "class"),
+    (0, b"\x00asm", "wasm"),
+    (0, b"fLaC", "flac"),
+    (0, b"OggS", "ogg"),
+    (0, b"ID3", "mp3"),
+    (4, b"ftyp", "mp4"),
+];
+
+/// RIFF files, and the extension of each form of them.
+static RIFF_FORMS: &[(&[u8], &str)] = &[(b"WAVE", "wav"), (b"AVI ", "avi"), (b"WEBP", "webp")];
+
+/// The extension of the files of the type that the start of a file, head,
+/// is the signature of, if known. Only looks at the bytes in head, so that
+/// a truncated or corrupt file is at worst not recognized.
+pub fn detect_signature(head: &[u8]) -> Option<&'static str> {
+    if head.starts_with(b"RIFF") {
+        let form = head.get(8..12)?;
+        return RIFF_FORMS
+            .iter()
+            .find(|(signature, _)| *signature == form)
+            .map(|(_, ext)| *ext);
+    }
+    SIGNATURES
+        .iter()
+        .find(|(offset, signature, _)| {
+            head.get(*offset..offset + signature.len()) == Some(*signature)
+        })
+        .map(|(_, _, ext)| *ext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_signature() {
+        assert_eq!(detect_signature(b"\x89PNG\r\n\x1a\n\x00\x00"), Some("png"));
+        assert_eq!(detect_signature(b"PAR1\x15\x04"), Some("parquet"));
+        assert_eq!(
+            detect_signature(b"RIFF\x24\x08\x00\x00WAVEfmt "),
+            Some("wav")
+        );
+        assert_eq!(detect_signature(b"RIFF\x24\x08\x00\x00????"), None);
+
+        let mut tar = vec![0u8; SIGNATURE_HEAD_LEN];
+        tar[257..262].copy_from_slice(b"ustar");
+        assert_eq!(detect_signature(&tar), Some("tar"));
+        assert_eq!(detect_signature(&tar[..260]), None);
+
+        // Truncated or unknown starts are not recognized.
+        assert_eq!(detect_signature(b""), None);
+        assert_eq!(detect_signature(b"RIFF"), None);
+        assert_eq!(detect_signature(b"\x89PN"), None);
+        assert_eq!(detect_signature(b"a,b,c\n1,2,3\n"), None);
+    }
+}
@@ -39,6 +39,9 @@ pub enum MDBShardError {
     #[error("Smudge query policy Error: {0}")]
     SmudgeQueryPolicyError(String),
 
+    #[error("Hash algorithm error: {0}")]
+    HashAlgorithmError(String),
+
     #[error("Error: {0}")]
     Other(String),
 }
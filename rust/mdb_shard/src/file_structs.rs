@@ -8,6 +8,10 @@ use std::mem::size_of;
 #[allow(dead_code)]
 pub const MDB_DEFAULT_FILE_FLAG: u32 = 0;
 
+/// The low byte of the file flags is the id of the chunker the file was
+/// chunked with; 0, for files recorded before there were others, is CDC.
+pub const MDB_FILE_FLAG_CHUNKER_MASK: u32 = 0xff;
+
 /// Each file consists of a FileDataSequenceHeader following
 /// a sequence of FileDataSequenceEntry.
 
@@ -35,6 +39,16 @@ impl FileDataSequenceHeader {
         }
     }
 
+    /// The id of the chunker the file was chunked with, as a
+    /// merkledb::ChunkerId.
+    pub fn chunker_id(&self) -> u8 {
+        (self.file_flags & MDB_FILE_FLAG_CHUNKER_MASK) as u8
+    }
+
+    pub fn set_chunker_id(&mut self, chunker_id: u8) {
+        self.file_flags = (self.file_flags & !MDB_FILE_FLAG_CHUNKER_MASK) | chunker_id as u32;
+    }
+
     pub fn serialize<W: Write>(&self, writer: &mut W) -> Result<usize, std::io::Error> {
         let mut buf = [0u8; size_of::<Self>()];
         {
@@ -162,3 +176,23 @@ impl MDBFileInfo {
             + self.segments.len() * size_of::<FileDataSequenceEntry>()) as u64
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunker_id() {
+        let mut header = FileDataSequenceHeader::new(MerkleHash::default(), 3);
+        assert_eq!(header.chunker_id(), 0);
+        header.file_flags |= 0x100;
+        header.set_chunker_id(2);
+        assert_eq!(header.chunker_id(), 2);
+        assert_eq!(header.file_flags, 0x102);
+
+        let mut buf = Vec::new();
+        header.serialize(&mut buf).unwrap();
+        let read = FileDataSequenceHeader::deserialize(&mut Cursor::new(&buf)).unwrap();
+        assert_eq!(read.chunker_id(), 2);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::error::Result;
+use crate::error::{MDBShardError, Result};
 use crate::{
     cas_structs::{CASChunkSequenceEntry, CASChunkSequenceHeader},
     file_structs::{FileDataSequenceEntry, FileDataSequenceHeader},
@@ -72,7 +72,17 @@ fn set_operation<R: Read + Seek, W: Write>(
 ) -> Result<MDBShardInfo> {
     let mut out_offset = 0u64;
 
-    let mut footer = MDBShardFileFooter::default();
+    if s[0].metadata.hash_algorithm != s[1].metadata.hash_algorithm {
+        return Err(MDBShardError::HashAlgorithmError(format!(
+            "Shards hashed with different algorithms ({} and {}) can't be combined",
+            s[0].metadata.hash_algorithm, s[1].metadata.hash_algorithm
+        )));
+    }
+
+    let mut footer = MDBShardFileFooter {
+        hash_algorithm: s[0].metadata.hash_algorithm,
+        ..Default::default()
+    };
 
     // Write out the header to the output.
     let header = MDBShardFileHeader::default();
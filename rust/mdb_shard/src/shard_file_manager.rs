@@ -3,7 +3,7 @@ use crate::shard_file_handle::MDBShardFile;
 use crate::shard_file_reconstructor::FileReconstructor;
 use crate::utils::truncate_hash;
 use async_trait::async_trait;
-use merklehash::MerkleHash;
+use merklehash::{HashAlgorithm, MerkleHash};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -126,6 +126,12 @@ impl ShardFileManager {
         Ok(s)
     }
 
+    /// Sets the algorithm recorded in the shards written from now on as the
+    /// one their hashes were computed with.
+    pub async fn set_hash_algorithm(&self, hash_algorithm: HashAlgorithm) {
+        self.current_state.write().await.shard.hash_algorithm = hash_algorithm;
+    }
+
     // Clear out everything; used mainly for debugging.
     pub async fn clear(&self) {
         {
@@ -418,7 +424,10 @@ impl MDBShardFlushGuard {
 
         if let Some(sd) = &self.session_directory {
             let path = self.shard.write_to_directory(sd)?;
-            self.shard = MDBInMemoryShard::default();
+            self.shard = MDBInMemoryShard {
+                hash_algorithm: self.shard.hash_algorithm,
+                ..Default::default()
+            };
 
             info!("Shard manager flushed new shard to {path:?}.");
 
@@ -3,7 +3,7 @@ use crate::error::{MDBShardError, Result};
 use crate::intershard_reference_structs::IntershardReferenceSequence;
 use crate::serialization_utils::*;
 use merkledb::MerkleMemDB;
-use merklehash::MerkleHash;
+use merklehash::{HashAlgorithm, MerkleHash};
 
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
@@ -92,8 +92,12 @@ pub struct MDBShardFileFooter {
     // This may be zero if this section does not exist.
     pub intershard_reference_offset: u64,
 
+    // The HashAlgorithm the hashes in the shard were computed with; zero,
+    // the default, in shards written before this was recorded.
+    pub hash_algorithm: u64,
+
     // More locations to stick in here if needed.
-    _buffer: [u64; 7],
+    _buffer: [u64; 6],
     pub materialized_bytes: u64,
     pub stored_bytes: u64,
     pub footer_offset: u64, // Always last.
@@ -112,7 +116,8 @@ impl Default for MDBShardFileFooter {
             chunk_lookup_offset: 0,
             chunk_lookup_num_entry: 0,
             intershard_reference_offset: 0,
-            _buffer: [0u64; 7],
+            hash_algorithm: 0,
+            _buffer: [0u64; 6],
             materialized_bytes: 0,
             stored_bytes: 0,
             footer_offset: 0,
@@ -132,6 +137,7 @@ impl MDBShardFileFooter {
         write_u64(writer, self.chunk_lookup_offset)?;
         write_u64(writer, self.chunk_lookup_num_entry)?;
         write_u64(writer, self.intershard_reference_offset)?;
+        write_u64(writer, self.hash_algorithm)?;
         write_u64s(writer, &self._buffer)?;
         write_u64(writer, self.materialized_bytes)?;
         write_u64(writer, self.stored_bytes)?;
@@ -152,6 +158,7 @@ impl MDBShardFileFooter {
             chunk_lookup_offset: read_u64(reader)?,
             chunk_lookup_num_entry: read_u64(reader)?,
             intershard_reference_offset: read_u64(reader)?,
+            hash_algorithm: read_u64(reader)?,
             ..Default::default()
         };
         read_u64s(reader, &mut obj._buffer)?;
@@ -323,6 +330,8 @@ impl MDBShardInfo {
             bytes_pos += intershard_ref.serialize(writer)?;
         }
 
+        shard.metadata.hash_algorithm = mdb.hash_algorithm.get_value();
+
         // Update repo size information.
         shard.metadata.materialized_bytes = mdb.materialized_bytes();
         shard.metadata.stored_bytes = mdb.stored_bytes();
@@ -806,6 +815,12 @@ impl MDBShardInfo {
         self.metadata.stored_bytes
     }
 
+    /// The algorithm the hashes in the shard were computed with.
+    pub fn hash_algorithm(&self) -> Result<HashAlgorithm> {
+        HashAlgorithm::try_from(self.metadata.hash_algorithm)
+            .map_err(|e| MDBShardError::HashAlgorithmError(e.to_string()))
+    }
+
     /// returns the number of bytes that is fixed and not part of any content; i.e. would be part of an empty shard.
     pub fn non_content_byte_size() -> u64 {
         (size_of::<MDBShardFileFooter>() + size_of::<MDBShardFileHeader>()) as u64 // Header and footer
@@ -1205,8 +1220,11 @@ pub mod test_routines {
 #[cfg(test)]
 mod tests {
     use crate::error::Result;
+    use merklehash::HashAlgorithm;
+    use std::io::Cursor;
 
     use super::test_routines::*;
+    use super::MDBShardInfo;
 
     #[test]
     fn test_simple() -> Result<()> {
@@ -1249,4 +1267,18 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_hash_algorithm() -> Result<()> {
+        let mut shard = gen_random_shard(0, &[1, 5], &[4, 3])?;
+        let buffer = convert_to_file(&shard)?;
+        let shard_info = MDBShardInfo::load_from_file(&mut Cursor::new(&buffer))?;
+        assert_eq!(shard_info.hash_algorithm()?, HashAlgorithm::Xet);
+
+        shard.hash_algorithm = HashAlgorithm::Sha256;
+        let buffer = convert_to_file(&shard)?;
+        let shard_info = MDBShardInfo::load_from_file(&mut Cursor::new(&buffer))?;
+        assert_eq!(shard_info.hash_algorithm()?, HashAlgorithm::Sha256);
+        verify_mdb_shards_match(&shard, Cursor::new(&buffer))
+    }
 }
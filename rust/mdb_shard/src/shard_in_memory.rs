@@ -10,7 +10,7 @@ use std::{
 
 use merkledb::MerkleMemDB;
 use merkledb::{aggregate_hashes::with_salt, prelude_v2::MerkleDBReconstruction};
-use merklehash::{HashedWrite, MerkleHash};
+use merklehash::{HashAlgorithm, HashedWrite, MerkleHash};
 use tracing::{debug, info};
 
 use crate::{
@@ -29,6 +29,9 @@ pub struct MDBInMemoryShard {
     pub file_content: BTreeMap<MerkleHash, MDBFileInfo>,
     pub chunk_hash_lookup: HashMap<MerkleHash, (Arc<MDBCASInfo>, u64)>,
     pub intershard_dedup_counts: HashMap<MerkleHash, usize>,
+    /// The algorithm the hashes in the shard are computed with, recorded in
+    /// the shard when it is written.
+    pub hash_algorithm: HashAlgorithm,
     current_shard_file_size: u64,
 }
 
@@ -161,6 +164,7 @@ impl MDBInMemoryShard {
             current_shard_file_size: 0,
             chunk_hash_lookup,
             intershard_dedup_counts,
+            hash_algorithm: self.hash_algorithm,
         };
 
         s.recalculate_shard_size();
@@ -210,6 +214,7 @@ impl MDBInMemoryShard {
                 .collect(),
             current_shard_file_size: 0,
             intershard_dedup_counts: <_>::default(),
+            hash_algorithm: self.hash_algorithm,
         };
         s.recalculate_shard_size();
         Ok(s)
@@ -0,0 +1,77 @@
+use async_trait::async_trait;
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use merkledb::constants::*;
+use merkledb::{async_chunk_target_default, chunk_target_default};
+use merklehash::compute_data_hash;
+use parutils::AsyncIterator;
+use rand_chacha::rand_core::RngCore;
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaChaRng;
+use std::collections::VecDeque;
+use std::io::Cursor;
+
+const INPUT_SIZE: usize = 64 * 1024 * 1024;
+const READ_SIZE: usize = 1024 * 1024;
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut rng = ChaChaRng::seed_from_u64(0);
+    let mut data = vec![0u8; len];
+    rng.fill_bytes(&mut data[..]);
+    data
+}
+
+/// Feeds the input to the async chunker in READ_SIZE pieces, like a file
+/// being read.
+struct AsyncReads {
+    reads: VecDeque<Vec<u8>>,
+}
+
+#[async_trait]
+impl AsyncIterator<std::io::Error> for AsyncReads {
+    type Item = Vec<u8>;
+
+    async fn next(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        Ok(self.reads.pop_front())
+    }
+}
+
+fn bench_chunk_hashing(c: &mut Criterion) {
+    let chunk = random_bytes(TARGET_CDC_CHUNK_SIZE);
+    let mut group = c.benchmark_group("Chunk Hash");
+    group.throughput(Throughput::Bytes(chunk.len() as u64));
+    group.bench_function("compute_data_hash", |b| {
+        b.iter(|| compute_data_hash(black_box(&chunk[..])))
+    });
+    group.finish();
+}
+
+fn bench_chunking(c: &mut Criterion) {
+    let input = random_bytes(INPUT_SIZE);
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let mut group = c.benchmark_group("Chunk and Hash");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.sample_size(10);
+    group.bench_function("chunk_target_default", |b| {
+        b.iter(|| chunk_target_default(&mut Cursor::new(&input[..])).len())
+    });
+    group.bench_function("async_chunk_target_default", |b| {
+        b.iter(|| {
+            let reads = AsyncReads {
+                reads: input.chunks(READ_SIZE).map(|r| r.to_vec()).collect(),
+            };
+            runtime.block_on(async {
+                let mut chunker = async_chunk_target_default(reads);
+                let mut num_chunks = 0;
+                while chunker.next().await.unwrap().is_some() {
+                    num_chunks += 1;
+                }
+                num_chunks
+            })
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_chunk_hashing, bench_chunking);
+criterion_main!(benches);
@@ -1,4 +1,5 @@
-use merklehash::MerkleHash;
+use merklehash::{HashAlgorithm, MerkleHash};
+use std::fmt::Write;
 
 use blake3;
 
@@ -45,6 +46,53 @@ pub fn file_node_hash(chunks: &[(MerkleHash, usize)], salt: &[u8; 32]) -> Result
     with_salt(m.hash(), salt)
 }
 
+/// cas_node_hash for a repository hashing with hash_algorithm. Other than
+/// with the default algorithm, the hash of a node is not that of a
+/// MerkleTree over its chunks but of the list of all of them, hashed once
+/// as an interior node.
+pub fn cas_node_hash_with_algorithm(
+    chunks: &[(MerkleHash, (usize, usize))],
+    hash_algorithm: HashAlgorithm,
+) -> Result<MerkleHash> {
+    if hash_algorithm == HashAlgorithm::Xet {
+        return cas_node_hash(chunks);
+    }
+    Ok(flat_node_hash(
+        chunks.iter().map(|(h, (lb, ub))| (h, ub - lb)),
+        hash_algorithm,
+    ))
+}
+
+/// file_node_hash for a repository hashing with hash_algorithm; see
+/// cas_node_hash_with_algorithm.
+pub fn file_node_hash_with_algorithm(
+    chunks: &[(MerkleHash, usize)],
+    salt: &[u8; 32],
+    hash_algorithm: HashAlgorithm,
+) -> Result<MerkleHash> {
+    if hash_algorithm == HashAlgorithm::Xet {
+        return file_node_hash(chunks, salt);
+    }
+    let hash = flat_node_hash(chunks.iter().map(|(h, size)| (h, *size)), hash_algorithm);
+    Ok(hash_algorithm.salted_hash(&hash, salt))
+}
+
+/// Hashes the lines "[hash] : [len]" of each chunk, as a MerkleNode hashes
+/// those of its children.
+fn flat_node_hash<'a>(
+    chunks: impl ExactSizeIterator<Item = (&'a MerkleHash, usize)>,
+    hash_algorithm: HashAlgorithm,
+) -> MerkleHash {
+    if chunks.len() == 0 {
+        return MerkleHash::default();
+    }
+    let mut buf = String::with_capacity(chunks.len() * 80);
+    for (hash, len) in chunks {
+        writeln!(buf, "{hash:x} : {len}").unwrap();
+    }
+    hash_algorithm.internal_node_hash(buf.as_bytes())
+}
+
 pub fn with_salt(hash: &MerkleHash, salt: &[u8; 32]) -> Result<MerkleHash> {
     let salted_hash = blake3::keyed_hash(salt, hash.as_bytes());
 
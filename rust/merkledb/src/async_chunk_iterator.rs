@@ -1,17 +1,18 @@
 use super::constants::*;
+use crate::chunk_hashing::ChunkHashQueue;
 // we reexport Chunk so that you can import it
 // from crate::async_chunk_iterator as well
 pub use crate::chunk_iterator::Chunk;
 use crate::chunk_iterator::HASH_SEED;
+use crate::format_chunking::{ChunkerId, FormatBoundaries};
 use async_trait::async_trait;
 use lazy_static::lazy_static;
-use merklehash::*;
+use merklehash::HashAlgorithm;
 use parutils::AsyncIterator;
 use rand_chacha::rand_core::RngCore;
 use rand_chacha::rand_core::SeedableRng;
 use rand_chacha::ChaChaRng;
 use std::cmp::min;
-use std::collections::VecDeque;
 use std::marker::PhantomData;
 use std::pin::Pin;
 
@@ -31,7 +32,8 @@ where
     // generator state
     chunkbuf: Vec<u8>,
     cur_chunk_len: usize,
-    yield_queue: VecDeque<ChunkYieldType>,
+    // chunks cut from the input whose hashes are being computed
+    yield_queue: ChunkHashQueue,
     complete_after_queue: bool,
     _e: PhantomData<E>,
 }
@@ -72,10 +74,9 @@ where
     /// We can implement the Generator trait in the future when it stabilizes.
     async fn next(&mut self) -> Result<Option<Self::Item>, E> {
         const MAX_WINDOW_SIZE: usize = 64;
-        if let Some(res) = self.yield_queue.pop_front() {
-            return Ok(Some(res));
-        }
-        while !self.complete_after_queue && self.yield_queue.is_empty() {
+        // read ahead so that the hashing pool has chunks to work on while
+        // we look for the next boundary
+        while !self.complete_after_queue && !self.yield_queue.is_full() {
             match self.iter.next().await? {
                 Some(readbuf) => {
                     let readbuf = readbuf.as_ref();
@@ -123,14 +124,7 @@ where
                             self.chunkbuf
                                 .extend_from_slice(&readbuf[chunk_buf_copy_start..cur_pos]);
                             if create_chunk {
-                                let res = (
-                                    Chunk {
-                                        length: self.chunkbuf.len(),
-                                        hash: compute_data_hash(&self.chunkbuf[..]),
-                                    },
-                                    std::mem::take(&mut self.chunkbuf),
-                                );
-                                self.yield_queue.push_back(res);
+                                self.yield_queue.push(std::mem::take(&mut self.chunkbuf));
 
                                 // reset chunk buffer state and continue to find the next chunk
                                 self.chunkbuf.clear();
@@ -142,25 +136,13 @@ where
                 }
                 None => {
                     self.complete_after_queue = true;
+                    if !self.chunkbuf.is_empty() {
+                        self.yield_queue.push(std::mem::take(&mut self.chunkbuf));
+                    }
                 }
             }
         }
-        if let Some(res) = self.yield_queue.pop_front() {
-            return Ok(Some(res));
-        }
-        // main loop complete
-        self.complete_after_queue = true;
-        if !self.chunkbuf.is_empty() {
-            let res = (
-                Chunk {
-                    length: self.chunkbuf.len(),
-                    hash: compute_data_hash(&self.chunkbuf[..]),
-                },
-                std::mem::take(&mut self.chunkbuf),
-            );
-            return Ok(Some(res));
-        }
-        Ok(None)
+        Ok(self.yield_queue.pop().await)
     }
 }
 
@@ -200,7 +182,7 @@ where
         // generator state init
         chunkbuf: Vec::with_capacity(maximum_chunk),
         cur_chunk_len: 0,
-        yield_queue: VecDeque::new(),
+        yield_queue: ChunkHashQueue::default(),
         complete_after_queue: false,
         _e: Default::default(),
     }
@@ -237,7 +219,10 @@ where
     // but this is in fact a core inner loop and ends up as a perf bottleneck.
     cur_hasher: HasherPointerBox<'static>,
     cur_hash_index: usize,
-    yield_queue: VecDeque<ChunkYieldType>,
+    // where the format of the input requires chunks to end, if anywhere
+    format: FormatBoundaries,
+    // chunks cut from the input whose hashes are being computed
+    yield_queue: ChunkHashQueue,
     complete_after_queue: bool,
     _e: PhantomData<E>,
 }
@@ -280,14 +265,14 @@ where
     async fn next(&mut self) -> Result<Option<Self::Item>, E> {
         const MAX_WINDOW_SIZE: usize = 64;
 
-        if let Some(res) = self.yield_queue.pop_front() {
-            return Ok(Some(res));
-        }
-        while !self.complete_after_queue && self.yield_queue.is_empty() {
+        // read ahead so that the hashing pool has chunks to work on while
+        // we look for the next boundary
+        while !self.complete_after_queue && !self.yield_queue.is_full() {
             match self.iter.next().await? {
                 Some(readbuf) => {
                     let readbuf: &[u8] = readbuf.as_ref();
                     let read_bytes = readbuf.len();
+                    self.format.feed(readbuf);
                     if read_bytes > 0 {
                         let mut cur_pos = 0;
                         while cur_pos < read_bytes {
@@ -296,13 +281,16 @@ where
                             // OR
                             // 2: consume the entire buffer
                             let chunk_buf_copy_start = cur_pos;
+                            // chunks also end at the boundaries of the format
+                            let format_end = self.format.next_in_buffer(cur_pos);
+                            let end = format_end.unwrap_or(read_bytes);
                             // skip the minimum chunk size
                             // and noting that the hash has a window size of 64
                             // so we should be careful to skip only minimum_chunk - 64 - 1
                             if self.cur_chunk_len < self.minimum_chunk - MAX_WINDOW_SIZE {
                                 let max_advance = min(
                                     self.minimum_chunk - self.cur_chunk_len - MAX_WINDOW_SIZE - 1,
-                                    read_bytes - cur_pos,
+                                    end - cur_pos,
                                 );
                                 cur_pos += max_advance;
                                 self.cur_chunk_len += max_advance;
@@ -311,13 +299,12 @@ where
                             let mut create_chunk = false;
                             // find a chunk boundary after minimum chunk
                             if let Some(boundary) = unsafe {
-                                (*self.cur_hasher.0)
-                                    .next_match(&readbuf[cur_pos..read_bytes], self.mask)
+                                (*self.cur_hasher.0).next_match(&readbuf[cur_pos..end], self.mask)
                             } {
                                 consume_len = boundary;
                                 create_chunk = true;
                             } else {
-                                consume_len = read_bytes - cur_pos;
+                                consume_len = end - cur_pos;
                             }
 
                             // if we hit maximum chunk we must create a chunk
@@ -329,7 +316,15 @@ where
                             cur_pos += consume_len;
                             self.chunkbuf
                                 .extend_from_slice(&readbuf[chunk_buf_copy_start..cur_pos]);
-                            if create_chunk {
+                            if format_end == Some(cur_pos) {
+                                // a boundary of the format ends the chunk
+                                // whichever hasher we are at
+                                unsafe { (*self.cur_hasher.0).set_hash(0) };
+                                self.yield_queue.push(std::mem::take(&mut self.chunkbuf));
+                                self.cur_hash_index = 0;
+                                self.cur_hasher = HasherPointerBox(self.hash.as_mut_ptr());
+                                self.cur_chunk_len = 0;
+                            } else if create_chunk {
                                 // advance the current hash index.
                                 // we actually create a chunk when we run out of hashers
                                 unsafe { (*self.cur_hasher.0).set_hash(0) };
@@ -340,15 +335,8 @@ where
                                     );
                                 }
                                 if self.cur_hash_index >= self.hash.len() {
-                                    let res = (
-                                        Chunk {
-                                            length: self.chunkbuf.len(),
-                                            hash: compute_data_hash(&self.chunkbuf[..]),
-                                        },
-                                        std::mem::take(&mut self.chunkbuf),
-                                    );
                                     // reset chunk buffer state and continue to find the next chunk
-                                    self.yield_queue.push_back(res);
+                                    self.yield_queue.push(std::mem::take(&mut self.chunkbuf));
 
                                     self.chunkbuf.clear();
                                     self.cur_hash_index = 0;
@@ -361,24 +349,13 @@ where
                 }
                 None => {
                     self.complete_after_queue = true;
+                    if !self.chunkbuf.is_empty() {
+                        self.yield_queue.push(std::mem::take(&mut self.chunkbuf));
+                    }
                 }
             }
         }
-        if let Some(res) = self.yield_queue.pop_front() {
-            return Ok(Some(res));
-        }
-        // main loop complete
-        if !self.chunkbuf.is_empty() {
-            let res = (
-                Chunk {
-                    length: self.chunkbuf.len(),
-                    hash: compute_data_hash(&self.chunkbuf[..]),
-                },
-                std::mem::take(&mut self.chunkbuf),
-            );
-            return Ok(Some(res));
-        }
-        Ok(None)
+        Ok(self.yield_queue.pop().await)
     }
 }
 
@@ -432,6 +409,27 @@ pub fn async_low_variance_chunk_target<T: AsyncIterator<E> + 'static, E: Send +
     target_chunk_size: usize,
     num_hashers: usize,
 ) -> Pin<Box<AsyncLowVarianceChunker<T, E>>>
+where
+    T::Item: AsRef<[u8]>,
+{
+    low_variance_chunker(
+        iter,
+        target_chunk_size,
+        num_hashers,
+        FormatBoundaries::default(),
+        ChunkHashQueue::default(),
+    )
+}
+
+/// Builds the chunker of async_low_variance_chunk_target, ending chunks at
+/// the boundaries found by format and hashing them with yield_queue.
+fn low_variance_chunker<T: AsyncIterator<E> + 'static, E: Send + Sync + 'static>(
+    iter: T,
+    target_chunk_size: usize,
+    num_hashers: usize,
+    format: FormatBoundaries,
+    yield_queue: ChunkHashQueue,
+) -> Pin<Box<AsyncLowVarianceChunker<T, E>>>
 where
     T::Item: AsRef<[u8]>,
 {
@@ -479,7 +477,8 @@ where
         cur_chunk_len: 0,
         cur_hasher: HasherPointerBox(std::ptr::null_mut()),
         cur_hash_index: 0,
-        yield_queue: VecDeque::new(),
+        format,
+        yield_queue,
         complete_after_queue: false,
         _e: Default::default(),
     });
@@ -503,3 +502,47 @@ where
 {
     async_low_variance_chunk_target(iter, TARGET_CDC_CHUNK_SIZE, N_LOW_VARIANCE_CDC_CHUNKERS)
 }
+
+/// Chunks an input stream with the default low variance configuration,
+/// hashing the chunks with hash_algorithm instead of the default.
+/// Returns a Generator. See `AsyncLowVarianceChunker`
+pub fn async_chunk_target_with_hash_algorithm<
+    T: AsyncIterator<E> + 'static,
+    E: Send + Sync + 'static,
+>(
+    iter: T,
+    hash_algorithm: HashAlgorithm,
+) -> Pin<Box<AsyncLowVarianceChunker<T, E>>>
+where
+    T::Item: AsRef<[u8]>,
+{
+    low_variance_chunker(
+        iter,
+        TARGET_CDC_CHUNK_SIZE,
+        N_LOW_VARIANCE_CDC_CHUNKERS,
+        FormatBoundaries::default(),
+        ChunkHashQueue::new(hash_algorithm),
+    )
+}
+
+/// Chunks an input stream with the default low variance configuration,
+/// also ending chunks at the boundaries of the format of the chunker, e.g.
+/// around the data of each entry of a tar archive. Entries smaller than a
+/// chunk are chunked along with their neighbours.
+/// Returns a Generator. See `AsyncLowVarianceChunker`
+pub fn async_chunk_target_with_format<T: AsyncIterator<E> + 'static, E: Send + Sync + 'static>(
+    iter: T,
+    hash_algorithm: HashAlgorithm,
+    chunker: ChunkerId,
+) -> Pin<Box<AsyncLowVarianceChunker<T, E>>>
+where
+    T::Item: AsRef<[u8]>,
+{
+    low_variance_chunker(
+        iter,
+        TARGET_CDC_CHUNK_SIZE,
+        N_LOW_VARIANCE_CDC_CHUNKERS,
+        FormatBoundaries::new(chunker.boundary_detector(TARGET_CDC_CHUNK_SIZE)),
+        ChunkHashQueue::new(hash_algorithm),
+    )
+}
@@ -0,0 +1,95 @@
+use crate::chunk_iterator::Chunk;
+use crate::constants::MAX_PENDING_CHUNK_HASHES;
+use lazy_static::lazy_static;
+use merklehash::HashAlgorithm;
+use std::collections::VecDeque;
+use tokio::sync::oneshot;
+
+lazy_static! {
+    /// The pool chunk hashes are computed on, shared by all chunkers so that
+    /// cleaning many files at once does not oversubscribe the CPU. blake3
+    /// picks the widest SIMD implementation the CPU supports at runtime.
+    static ref CHUNK_HASH_POOL: rayon::ThreadPool = rayon::ThreadPoolBuilder::new()
+        .thread_name(|i| format!("chunk-hash-{i}"))
+        .build()
+        .expect("Unable to start the chunk hashing thread pool");
+}
+
+/// Chunks waiting for their hash, in the order they were cut from the
+/// input. Hashing happens on CHUNK_HASH_POOL, so the chunker can look for
+/// the next boundary while earlier chunks are hashed.
+#[derive(Default)]
+pub(crate) struct ChunkHashQueue {
+    pending: VecDeque<oneshot::Receiver<(Chunk, Vec<u8>)>>,
+    hash_algorithm: HashAlgorithm,
+}
+
+impl ChunkHashQueue {
+    pub fn new(hash_algorithm: HashAlgorithm) -> Self {
+        Self {
+            pending: VecDeque::new(),
+            hash_algorithm,
+        }
+    }
+
+    /// Starts hashing a chunk.
+    pub fn push(&mut self, data: Vec<u8>) {
+        let (tx, rx) = oneshot::channel();
+        let hash_algorithm = self.hash_algorithm;
+        CHUNK_HASH_POOL.spawn(move || {
+            let chunk = Chunk {
+                length: data.len(),
+                hash: hash_algorithm.data_hash(&data[..]),
+            };
+            // The receiver is gone only if the chunker was dropped.
+            let _ = tx.send((chunk, data));
+        });
+        self.pending.push_back(rx);
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.pending.len() >= MAX_PENDING_CHUNK_HASHES
+    }
+
+    /// Waits for the oldest chunk's hash.
+    pub async fn pop(&mut self) -> Option<(Chunk, Vec<u8>)> {
+        let rx = self.pending.pop_front()?;
+        // The sender is only dropped without sending if hashing panicked.
+        Some(rx.await.expect("Chunk hashing task failed"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use merklehash::compute_data_hash;
+
+    #[tokio::test]
+    async fn test_hashes_in_order() {
+        let mut queue = ChunkHashQueue::default();
+        let chunks: Vec<Vec<u8>> = (0..1000u32)
+            .map(|i| i.to_le_bytes().repeat(i as usize))
+            .collect();
+        for c in chunks.iter() {
+            queue.push(c.clone());
+        }
+        assert_eq!(queue.pending.len(), chunks.len());
+        for c in chunks.iter() {
+            let (chunk, data) = queue.pop().await.unwrap();
+            assert_eq!(&data, c);
+            assert_eq!(chunk.length, c.len());
+            assert_eq!(chunk.hash, compute_data_hash(&c[..]));
+        }
+        assert!(queue.pending.is_empty());
+        assert!(queue.pop().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_hash_algorithm() {
+        let mut queue = ChunkHashQueue::new(HashAlgorithm::Sha256);
+        queue.push(b"hello world".to_vec());
+        let (chunk, _) = queue.pop().await.unwrap();
+        assert_eq!(chunk.hash, HashAlgorithm::Sha256.data_hash(b"hello world"));
+        assert_ne!(chunk.hash, compute_data_hash(b"hello world"));
+    }
+}
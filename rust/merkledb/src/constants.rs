@@ -3,6 +3,9 @@ pub const TARGET_CAS_BLOCK_SIZE: usize = 15 * 1024 * 1024;
 pub const IDEAL_CAS_BLOCK_SIZE: usize = 16 * 1024 * 1024;
 pub const TARGET_CDC_CHUNK_SIZE: usize = 16384;
 pub const N_LOW_VARIANCE_CDC_CHUNKERS: usize = 8;
+/// The number of chunks an async chunker reads ahead of the one it yields,
+/// so that the chunk hashing pool can hash them in parallel.
+pub const MAX_PENDING_CHUNK_HASHES: usize = 64;
 
 /// TARGET_CDC_CHUNK_SIZE / MINIMUM_CHUNK_DIVISOR is the smallest chunk size
 pub const MINIMUM_CHUNK_DIVISOR: usize = 4;
@@ -0,0 +1,512 @@
+//! Chunk boundaries aligned to the entries of container files.
+//!
+//! A file rewritten into a tar or zip archive keeps its data, but not its
+//! place: entries before it grow or shrink, and its header (e.g. its mtime)
+//! changes. CDC finds the same chunks again within the data, but the chunks
+//! straddling the header and the data around it are new. The chunkers here
+//! also cut at the start and the end of the data of each entry, found by
+//! parsing the headers as the file streams through, so the data of an entry
+//! is chunked the same wherever it is; CDC still chunks between these cuts.
+//!
+//! Formats whose layout is only known from the end of the file, such as the
+//! row groups of parquet listed in its footer, can't be parsed as the file
+//! streams through, and are chunked with CDC alone.
+use std::collections::VecDeque;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// How a file is chunked, recorded with the file so that it is chunked the
+/// same way again.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ChunkerId {
+    /// Content defined chunking alone.
+    #[default]
+    Cdc,
+    /// CDC within the entries of a tar archive.
+    Tar,
+    /// CDC within the entries of a zip archive.
+    Zip,
+}
+
+impl ChunkerId {
+    /// How many of the first bytes of a file detect looks at.
+    pub const DETECT_LEN: usize = 512;
+
+    /// The id stored in the file metadata; 0 is CDC, as for files recorded
+    /// before there were other chunkers.
+    pub fn id(self) -> u8 {
+        match self {
+            ChunkerId::Cdc => 0,
+            ChunkerId::Tar => 1,
+            ChunkerId::Zip => 2,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(ChunkerId::Cdc),
+            1 => Some(ChunkerId::Tar),
+            2 => Some(ChunkerId::Zip),
+            _ => None,
+        }
+    }
+
+    /// The chunker for a file from its first bytes: the one for its format,
+    /// or CDC if it isn't of a format with one.
+    pub fn detect(head: &[u8]) -> Self {
+        if head.len() >= TarFormat::HEADER_LEN
+            && head[257..262] == *b"ustar"
+            && TarFormat::parse_entry(&head[..TarFormat::HEADER_LEN]).is_some()
+        {
+            ChunkerId::Tar
+        } else if head.len() >= ZipFormat::HEADER_LEN && head.starts_with(ZIP_LOCAL_HEADER) {
+            ChunkerId::Zip
+        } else {
+            ChunkerId::Cdc
+        }
+    }
+
+    /// The detector of the boundaries of the format, None for CDC. Entries
+    /// smaller than min_entry_size are chunked along with their neighbours.
+    pub(crate) fn boundary_detector(
+        self,
+        min_entry_size: usize,
+    ) -> Option<Box<dyn BoundaryDetector>> {
+        match self {
+            ChunkerId::Cdc => None,
+            ChunkerId::Tar => Some(Box::new(EntryBoundaries::<TarFormat>::new(min_entry_size))),
+            ChunkerId::Zip => Some(Box::new(EntryBoundaries::<ZipFormat>::new(min_entry_size))),
+        }
+    }
+}
+
+impl fmt::Display for ChunkerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ChunkerId::Cdc => "cdc",
+            ChunkerId::Tar => "tar",
+            ChunkerId::Zip => "zip",
+        })
+    }
+}
+
+/// Finds the offsets in a file at which chunks must end, from the bytes of
+/// the file fed to it in order.
+pub trait BoundaryDetector: Send + Sync {
+    /// Takes the next bytes of the file, returning the offsets in the file
+    /// of the boundaries found, in increasing order. A boundary is always
+    /// found before the bytes up to it are fed.
+    fn feed(&mut self, data: &[u8]) -> Vec<usize>;
+}
+
+/// The boundaries in a chunker's input, and where it is in the input.
+#[derive(Default)]
+pub(crate) struct FormatBoundaries {
+    detector: Option<Box<dyn BoundaryDetector>>,
+    pending: VecDeque<usize>,
+    /// The offset in the input of the buffer being chunked.
+    buffer_start: usize,
+    /// The offset in the input of the next buffer.
+    buffer_end: usize,
+}
+
+impl FormatBoundaries {
+    pub fn new(detector: Option<Box<dyn BoundaryDetector>>) -> Self {
+        Self {
+            detector,
+            ..Default::default()
+        }
+    }
+
+    /// Takes the next buffer of the input, before it's chunked.
+    pub fn feed(&mut self, buf: &[u8]) {
+        self.buffer_start = self.buffer_end;
+        self.buffer_end += buf.len();
+        if let Some(detector) = self.detector.as_mut() {
+            self.pending.extend(detector.feed(buf));
+        }
+    }
+
+    /// The offset in the buffer being chunked of the first boundary after
+    /// pos, if it's in the buffer, possibly at its very end.
+    pub fn next_in_buffer(&mut self, pos: usize) -> Option<usize> {
+        let pos = self.buffer_start + pos;
+        while self.pending.front().is_some_and(|&b| b <= pos) {
+            self.pending.pop_front();
+        }
+        self.pending
+            .front()
+            .filter(|&&b| b <= self.buffer_end)
+            .map(|&b| b - self.buffer_start)
+    }
+}
+
+/// An entry of a container file, from its header.
+struct Entry {
+    /// The offset of the data from the start of the header.
+    data_offset: usize,
+    data_len: usize,
+    /// The offset of the next header from the start of this one, None if
+    /// there are no more entries or where the next one is isn't known.
+    next: Option<usize>,
+}
+
+/// A container format whose entries each start with a header of a fixed
+/// size, telling where the next one starts.
+trait EntryFormat: Send + Sync {
+    const HEADER_LEN: usize;
+
+    /// The entry of the header, None if it isn't a header of an entry.
+    fn parse_entry(header: &[u8]) -> Option<Entry>;
+}
+
+/// Cuts at the start and the end of the data of each entry of a format.
+struct EntryBoundaries<F: EntryFormat> {
+    /// The offset of the next header, None past the last one.
+    next_header: Option<usize>,
+    /// The bytes of the next header fed so far.
+    header: Vec<u8>,
+    /// The offset of the next byte fed.
+    pos: usize,
+    min_entry_size: usize,
+    _format: PhantomData<F>,
+}
+
+impl<F: EntryFormat> EntryBoundaries<F> {
+    fn new(min_entry_size: usize) -> Self {
+        Self {
+            next_header: Some(0),
+            header: Vec::with_capacity(F::HEADER_LEN),
+            pos: 0,
+            min_entry_size: min_entry_size.max(1),
+            _format: PhantomData,
+        }
+    }
+}
+
+impl<F: EntryFormat> BoundaryDetector for EntryBoundaries<F> {
+    fn feed(&mut self, data: &[u8]) -> Vec<usize> {
+        let start = self.pos;
+        self.pos += data.len();
+        let mut boundaries = Vec::new();
+        while let Some(at) = self.next_header {
+            let from = at + self.header.len();
+            if from >= self.pos {
+                break;
+            }
+            let take = (F::HEADER_LEN - self.header.len()).min(self.pos - from);
+            self.header
+                .extend_from_slice(&data[from - start..from - start + take]);
+            if self.header.len() < F::HEADER_LEN {
+                break;
+            }
+
+            self.next_header = None;
+            if let Some(entry) = F::parse_entry(&self.header) {
+                let data_start = at.checked_add(entry.data_offset);
+                let data_end = data_start.and_then(|s| s.checked_add(entry.data_len));
+                if let (Some(data_start), Some(data_end)) = (data_start, data_end) {
+                    if entry.data_len >= self.min_entry_size {
+                        boundaries.push(data_start);
+                        boundaries.push(data_end);
+                    }
+                    self.next_header = entry.next.and_then(|n| at.checked_add(n));
+                }
+            }
+            self.header.clear();
+        }
+        boundaries
+    }
+}
+
+/// The entries of a tar archive: 512 byte headers, each followed by the data
+/// of the entry padded to 512 bytes.
+struct TarFormat;
+
+impl EntryFormat for TarFormat {
+    const HEADER_LEN: usize = 512;
+
+    fn parse_entry(header: &[u8]) -> Option<Entry> {
+        // Zero blocks end the archive, and anything else is no header.
+        let checksum = parse_tar_number(&header[148..156])?;
+        let sum: u64 = header
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| if (148..156).contains(&i) { b' ' } else { b } as u64)
+            .sum();
+        if checksum != sum {
+            return None;
+        }
+
+        // Links, devices, directories and fifos have no data, whatever their
+        // size says.
+        let data_len = match header[156] {
+            b'1'..=b'6' => 0,
+            _ => usize::try_from(parse_tar_number(&header[124..136])?).ok()?,
+        };
+        let padded = data_len.checked_next_multiple_of(Self::HEADER_LEN)?;
+        Some(Entry {
+            data_offset: Self::HEADER_LEN,
+            data_len,
+            next: Self::HEADER_LEN.checked_add(padded),
+        })
+    }
+}
+
+/// A number in a tar header: octal digits, or big-endian binary if the
+/// first byte has its high bit set.
+fn parse_tar_number(field: &[u8]) -> Option<u64> {
+    if field[0] & 0x80 != 0 {
+        return field[1..]
+            .iter()
+            .try_fold(0u64, |n, &b| n.checked_mul(256)?.checked_add(b as u64));
+    }
+    let digits = field
+        .iter()
+        .skip_while(|&&b| b == b' ')
+        .take_while(|&&b| b != 0 && b != b' ');
+    let mut n: u64 = 0;
+    let mut any = false;
+    for &b in digits {
+        if !(b'0'..=b'7').contains(&b) {
+            return None;
+        }
+        n = n.checked_mul(8)?.checked_add((b - b'0') as u64)?;
+        any = true;
+    }
+    any.then_some(n)
+}
+
+const ZIP_LOCAL_HEADER: &[u8] = b"PK\x03\x04";
+
+/// The entries of a zip archive: local file headers, each followed by the
+/// name and extra field of the entry, then its compressed data.
+struct ZipFormat;
+
+impl EntryFormat for ZipFormat {
+    const HEADER_LEN: usize = 30;
+
+    fn parse_entry(header: &[u8]) -> Option<Entry> {
+        // The central directory, after the last entry, has another signature.
+        if !header.starts_with(ZIP_LOCAL_HEADER) {
+            return None;
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([header[i], header[i + 1]]) as usize;
+        let u32_at = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap());
+
+        let flags = u16_at(6);
+        let compressed_size = u32_at(18);
+        let data_offset = Self::HEADER_LEN + u16_at(26) + u16_at(28);
+        // The sizes of zip64 entries are in their extra field.
+        if compressed_size == u32::MAX {
+            return None;
+        }
+        let data_len = compressed_size as usize;
+        // With a data descriptor, of varying length, after the data, the
+        // sizes in the header may be zero, and where the next header is
+        // isn't known.
+        let has_descriptor = flags & 0x08 != 0;
+        if has_descriptor && data_len == 0 {
+            return None;
+        }
+        Some(Entry {
+            data_offset,
+            data_len,
+            next: (!has_descriptor).then_some(data_offset + data_len),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::async_chunk_iterator::async_chunk_target_with_format;
+    use merklehash::{HashAlgorithm, MerkleHash};
+    use parutils::AsyncIterator;
+    use rand::{RngCore, SeedableRng};
+
+    fn tar_header(name: &str, size: usize, typeflag: u8) -> Vec<u8> {
+        let mut header = vec![0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[124..136].copy_from_slice(format!("{size:011o}\0").as_bytes());
+        header[156] = typeflag;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[148..156].copy_from_slice(b"        ");
+        let sum: u64 = header.iter().map(|&b| b as u64).sum();
+        header[148..156].copy_from_slice(format!("{sum:06o}\0 ").as_bytes());
+        header
+    }
+
+    /// A tar archive of files of random contents, the same for the same
+    /// size, and the offsets of the data of each.
+    fn tar(files: &[(&str, usize)]) -> (Vec<u8>, Vec<(usize, usize)>) {
+        let mut archive = Vec::new();
+        let mut data = Vec::new();
+        for (name, size) in files {
+            archive.extend(tar_header(name, *size, b'0'));
+            data.push((archive.len(), archive.len() + size));
+            let mut contents = vec![0u8; *size];
+            rand::rngs::StdRng::seed_from_u64(*size as u64).fill_bytes(&mut contents);
+            archive.extend(contents);
+            archive.resize(archive.len().next_multiple_of(512), 0);
+        }
+        archive.resize(archive.len() + 1024, 0);
+        (archive, data)
+    }
+
+    fn zip_entry(name: &str, data: &[u8], flags: u16) -> Vec<u8> {
+        let mut entry = ZIP_LOCAL_HEADER.to_vec();
+        entry.extend(20u16.to_le_bytes());
+        entry.extend(flags.to_le_bytes());
+        entry.extend([0u8; 10]);
+        entry.extend((data.len() as u32).to_le_bytes());
+        entry.extend((data.len() as u32).to_le_bytes());
+        entry.extend((name.len() as u16).to_le_bytes());
+        entry.extend(0u16.to_le_bytes());
+        entry.extend(name.as_bytes());
+        entry.extend(data);
+        entry
+    }
+
+    fn feed_in_pieces(
+        detector: &mut dyn BoundaryDetector,
+        data: &[u8],
+        piece: usize,
+    ) -> Vec<usize> {
+        data.chunks(piece).flat_map(|d| detector.feed(d)).collect()
+    }
+
+    #[test]
+    fn test_detect() {
+        let (archive, _) = tar(&[("a.bin", 10)]);
+        assert_eq!(ChunkerId::detect(&archive), ChunkerId::Tar);
+        assert_eq!(
+            ChunkerId::detect(&zip_entry("a", b"abc", 0)),
+            ChunkerId::Zip
+        );
+        assert_eq!(ChunkerId::detect(b"PAR1 not a container"), ChunkerId::Cdc);
+        // The magic alone isn't enough without a valid checksum.
+        let mut bad = archive.clone();
+        bad[0] ^= 1;
+        assert_eq!(ChunkerId::detect(&bad), ChunkerId::Cdc);
+
+        for chunker in [ChunkerId::Cdc, ChunkerId::Tar, ChunkerId::Zip] {
+            assert_eq!(ChunkerId::from_id(chunker.id()), Some(chunker));
+        }
+        assert_eq!(ChunkerId::from_id(200), None);
+    }
+
+    #[test]
+    fn test_tar_boundaries() {
+        let (archive, data) = tar(&[("big", 100_000), ("small", 10), ("other", 70_001)]);
+        let expected = vec![data[0].0, data[0].1, data[2].0, data[2].1];
+        for piece in [1, 100, 511, 512, 4096, archive.len()] {
+            let mut detector = ChunkerId::Tar.boundary_detector(1024).unwrap();
+            assert_eq!(
+                feed_in_pieces(detector.as_mut(), &archive, piece),
+                expected,
+                "fed {piece} bytes at a time"
+            );
+        }
+    }
+
+    #[test]
+    fn test_zip_boundaries() {
+        let big = vec![7u8; 5000];
+        let mut archive = zip_entry("a", &big, 0);
+        let second = archive.len() + 30 + 1;
+        archive.extend(zip_entry("b", &big, 0));
+        archive.extend(zip_entry("c", b"small", 0));
+        // A central directory header ends the entries.
+        archive.extend(b"PK\x01\x02");
+        archive.extend([0u8; 100]);
+
+        let expected = vec![31, 31 + 5000, second, second + 5000];
+        for piece in [1, 29, 30, 1000, archive.len()] {
+            let mut detector = ChunkerId::Zip.boundary_detector(1024).unwrap();
+            assert_eq!(feed_in_pieces(detector.as_mut(), &archive, piece), expected);
+        }
+
+        // Past an entry with a data descriptor, the entries aren't followed.
+        let mut archive = zip_entry("a", &big, 0x08);
+        archive.extend(zip_entry("b", &big, 0));
+        let mut detector = ChunkerId::Zip.boundary_detector(1024).unwrap();
+        assert_eq!(detector.feed(&archive), vec![31, 31 + 5000]);
+    }
+
+    #[test]
+    fn test_format_boundaries() {
+        let mut boundaries = FormatBoundaries::new(None);
+        boundaries.feed(b"abcdef");
+        assert_eq!(boundaries.next_in_buffer(0), None);
+
+        struct Fixed(Vec<usize>);
+        impl BoundaryDetector for Fixed {
+            fn feed(&mut self, _: &[u8]) -> Vec<usize> {
+                std::mem::take(&mut self.0)
+            }
+        }
+        let mut boundaries = FormatBoundaries::new(Some(Box::new(Fixed(vec![3, 10, 12]))));
+        boundaries.feed(&[0; 10]);
+        assert_eq!(boundaries.next_in_buffer(0), Some(3));
+        assert_eq!(boundaries.next_in_buffer(3), Some(10));
+        boundaries.feed(&[0; 10]);
+        assert_eq!(boundaries.next_in_buffer(0), Some(2));
+        assert_eq!(boundaries.next_in_buffer(2), None);
+    }
+
+    struct Pieces(std::collections::VecDeque<Vec<u8>>);
+
+    #[async_trait::async_trait]
+    impl AsyncIterator<std::io::Error> for Pieces {
+        type Item = Vec<u8>;
+
+        async fn next(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+            Ok(self.0.pop_front())
+        }
+    }
+
+    /// The offsets at which the chunks of data end, and their hashes.
+    async fn chunk(data: &[u8], chunker: ChunkerId) -> Vec<(usize, MerkleHash)> {
+        let pieces = Pieces(data.chunks(3001).map(|p| p.to_vec()).collect());
+        let mut chunks = async_chunk_target_with_format(pieces, HashAlgorithm::default(), chunker);
+        let mut ret = Vec::new();
+        let mut end = 0;
+        while let Some((chunk, bytes)) = chunks.next().await.unwrap() {
+            assert_eq!(chunk.length, bytes.len());
+            end += chunk.length;
+            ret.push((end, chunk.hash));
+        }
+        assert_eq!(end, data.len());
+        ret
+    }
+
+    #[tokio::test]
+    async fn test_chunk_tar() {
+        let (archive, data) = tar(&[("a", 50_000), ("b", 300_000)]);
+        assert_eq!(ChunkerId::detect(&archive), ChunkerId::Tar);
+        let chunks = chunk(&archive, ChunkerId::Tar).await;
+        for (start, end) in &data {
+            assert!(chunks.iter().any(|(e, _)| e == start));
+            assert!(chunks.iter().any(|(e, _)| e == end));
+        }
+
+        // The same file after another one is chunked the same.
+        let (other, other_data) = tar(&[("c", 70_001), ("b", 300_000)]);
+        let other_chunks = chunk(&other, ChunkerId::Tar).await;
+        let chunks_of = |chunks: &[(usize, MerkleHash)], (start, end): (usize, usize)| {
+            chunks
+                .iter()
+                .filter(|(e, _)| *e > start && *e <= end)
+                .map(|(_, h)| *h)
+                .collect::<Vec<_>>()
+        };
+        let b_chunks = chunks_of(&chunks, data[1]);
+        assert!(!b_chunks.is_empty());
+        assert_eq!(b_chunks, chunks_of(&other_chunks, other_data[1]));
+
+        // CDC alone doesn't cut at the entries.
+        let cdc_chunks = chunk(&archive, ChunkerId::Cdc).await;
+        assert_ne!(cdc_chunks, chunks);
+    }
+}
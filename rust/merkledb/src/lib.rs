@@ -1,7 +1,9 @@
 #![cfg_attr(feature = "strict", deny(warnings))]
 
 mod async_chunk_iterator;
+mod chunk_hashing;
 mod chunk_iterator;
+mod format_chunking;
 pub mod constants;
 
 pub mod aggregate_hashes;
@@ -26,9 +28,11 @@ pub mod error;
 
 pub use crate::merkledb_highlevel_v1::InsertionStaging;
 pub use async_chunk_iterator::{
-    async_chunk_target, async_chunk_target_default, async_low_variance_chunk_target,
+    async_chunk_target, async_chunk_target_default, async_chunk_target_with_format,
+    async_chunk_target_with_hash_algorithm, async_low_variance_chunk_target,
 };
 pub use chunk_iterator::{chunk_target, chunk_target_default, low_variance_chunk_target, Chunk};
+pub use format_chunking::{BoundaryDetector, ChunkerId};
 pub use merkledbv1::MerkleDBV1;
 pub use merkledbv2::MerkleDBV2;
 pub use merklememdb::MerkleMemDB;
@@ -0,0 +1,221 @@
+use crate::data_hash::{
+    compute_data_hash, compute_internal_node_hash, DataHash, DataHashHexParseError,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/**************************************************************************/
+/*                                                                        */
+/*                             HashAlgorithm                              */
+/*                                                                        */
+/**************************************************************************/
+
+/// The hash function chunks, CAS blocks and files of a repository are
+/// hashed with. Each repository uses one, agreed on with the remote when the
+/// repository is initialized and recorded in the MerkleDB shards.
+///
+/// [HashAlgorithm::Xet] is the keyed blake3 of [compute_data_hash] and
+/// [compute_internal_node_hash], which repositories have always used. The
+/// others hash a leading 0 byte before data and a leading 1 byte before
+/// interior nodes (as in RFC 6962) so that, as with the differently keyed
+/// default, a data hash can't collide with an interior node hash.
+///
+/// ```ignore
+/// let algorithm: HashAlgorithm = "sha256".parse()?;
+/// let hash = algorithm.data_hash("hello world".as_bytes());
+/// println!("{}", algorithm.format_hash(&hash)); // sha256:...
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    #[default]
+    Xet = 0,
+    Blake3 = 1,
+    /// For deployments that must only use FIPS-approved hash functions.
+    Sha256 = 2,
+}
+
+const DATA_PREFIX: u8 = 0;
+const INTERNAL_NODE_PREFIX: u8 = 1;
+
+impl HashAlgorithm {
+    pub const ALL: [HashAlgorithm; 3] = [Self::Xet, Self::Blake3, Self::Sha256];
+
+    /// The identifier of the algorithm in serialized structures.
+    pub fn get_value(&self) -> u64 {
+        *self as u64
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Xet => "xet",
+            Self::Blake3 => "blake3",
+            Self::Sha256 => "sha256",
+        }
+    }
+
+    /// Hashes user-provided data, i.e. the leaves of a MerkleTree; see
+    /// [compute_data_hash].
+    pub fn data_hash(&self, slice: &[u8]) -> DataHash {
+        match self {
+            Self::Xet => compute_data_hash(slice),
+            _ => self.prefixed_hash(DATA_PREFIX, slice),
+        }
+    }
+
+    /// Hashes the description of an interior node; see
+    /// [compute_internal_node_hash].
+    pub fn internal_node_hash(&self, slice: &[u8]) -> DataHash {
+        match self {
+            Self::Xet => compute_internal_node_hash(slice),
+            _ => self.prefixed_hash(INTERNAL_NODE_PREFIX, slice),
+        }
+    }
+
+    /// Hashes a hash with a repository salt, so that file hashes can't be
+    /// guessed from their contents by other repositories.
+    pub fn salted_hash(&self, hash: &DataHash, salt: &[u8; 32]) -> DataHash {
+        match self {
+            Self::Xet | Self::Blake3 => {
+                DataHash::from(*blake3::keyed_hash(salt, hash.as_bytes()).as_bytes())
+            }
+            Self::Sha256 => sha256(&[salt.as_slice(), hash.as_bytes()]),
+        }
+    }
+
+    fn prefixed_hash(&self, prefix: u8, slice: &[u8]) -> DataHash {
+        match self {
+            Self::Sha256 => sha256(&[&[prefix][..], slice]),
+            _ => {
+                let mut hasher = blake3::Hasher::new();
+                hasher.update(&[prefix]);
+                hasher.update(slice);
+                DataHash::from(*hasher.finalize().as_bytes())
+            }
+        }
+    }
+
+    /// The versioned identifier of a hash computed with this algorithm:
+    /// `<algorithm>:<hex>`, or just the hex for [HashAlgorithm::Xet] so that
+    /// existing identifiers keep their meaning.
+    pub fn format_hash(&self, hash: &DataHash) -> String {
+        match self {
+            Self::Xet => hash.hex(),
+            _ => format!("{}:{}", self.name(), hash.hex()),
+        }
+    }
+
+    /// Parses an identifier written by [HashAlgorithm::format_hash].
+    pub fn parse_hash(h: &str) -> Result<(HashAlgorithm, DataHash), DataHashHexParseError> {
+        match h.split_once(':') {
+            Some((name, hex)) => {
+                let algorithm = name.parse().map_err(|_| DataHashHexParseError)?;
+                Ok((algorithm, DataHash::from_hex(hex)?))
+            }
+            None => Ok((Self::Xet, DataHash::from_hex(h)?)),
+        }
+    }
+}
+
+fn sha256(parts: &[&[u8]]) -> DataHash {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    let mut digest = [0u8; 32];
+    digest.copy_from_slice(&hasher.finalize());
+    DataHash::from(digest)
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownHashAlgorithm(pub String);
+
+impl Error for UnknownHashAlgorithm {}
+
+impl fmt::Display for UnknownHashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Unknown hash algorithm {}; expected one of xet, blake3 or sha256",
+            self.0
+        )
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = UnknownHashAlgorithm;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|a| a.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| UnknownHashAlgorithm(s.to_string()))
+    }
+}
+
+impl TryFrom<u64> for HashAlgorithm {
+    type Error = UnknownHashAlgorithm;
+
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|a| a.get_value() == value)
+            .ok_or_else(|| UnknownHashAlgorithm(value.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_algorithms_differ() {
+        let data = b"hello world";
+        let hashes: Vec<DataHash> = HashAlgorithm::ALL
+            .iter()
+            .flat_map(|a| [a.data_hash(data), a.internal_node_hash(data)])
+            .collect();
+        for (i, h) in hashes.iter().enumerate() {
+            assert!(!hashes[i + 1..].contains(h));
+        }
+        assert_eq!(HashAlgorithm::Xet.data_hash(data), compute_data_hash(data));
+    }
+
+    #[test]
+    fn test_versioned_hash_identifiers() {
+        for algorithm in HashAlgorithm::ALL {
+            let hash = algorithm.data_hash(b"hello world");
+            let id = algorithm.format_hash(&hash);
+            assert_eq!(HashAlgorithm::parse_hash(&id).unwrap(), (algorithm, hash));
+            assert!(id.ends_with(&hash.hex()));
+        }
+        assert!(
+            HashAlgorithm::format_hash(&HashAlgorithm::Sha256, &DataHash::default())
+                .starts_with("sha256:")
+        );
+        assert!(HashAlgorithm::parse_hash("md5:00").is_err());
+    }
+
+    #[test]
+    fn test_parse_hash_algorithm() {
+        for algorithm in HashAlgorithm::ALL {
+            assert_eq!(algorithm.name().parse::<HashAlgorithm>(), Ok(algorithm));
+            assert_eq!(
+                HashAlgorithm::try_from(algorithm.get_value()),
+                Ok(algorithm)
+            );
+        }
+        assert_eq!("SHA256".parse(), Ok(HashAlgorithm::Sha256));
+        assert!("md5".parse::<HashAlgorithm>().is_err());
+        assert!(HashAlgorithm::try_from(3).is_err());
+    }
+}
@@ -41,9 +41,15 @@
 //! are keyed differently such the same inputs will produce different outputs.
 //! And in particular, it should be difficult to find a collision where
 //! a `compute_data_hash(a) == compute_internal_node_hash(b)`
+//!
+//! Both are those of the default [HashAlgorithm]; a repository can instead
+//! be set up to hash with blake3 or SHA-256, through the equivalent methods
+//! of [HashAlgorithm].
 
 #![cfg_attr(feature = "strict", deny(warnings))]
 
 pub mod data_hash;
+pub mod hash_algorithm;
 pub use data_hash::*;
+pub use hash_algorithm::{HashAlgorithm, UnknownHashAlgorithm};
 pub type MerkleHash = DataHash;
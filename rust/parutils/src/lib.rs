@@ -8,3 +8,6 @@ pub use async_iterator::*;
 
 mod buffered_async_iterator;
 pub use buffered_async_iterator::*;
+
+mod cancellation;
+pub use cancellation::*;
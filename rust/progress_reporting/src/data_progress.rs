@@ -3,7 +3,7 @@ use crossterm::{cursor, QueueableCommand};
 use std::io::{stderr, Write};
 use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 const MAX_PRINT_INTERVAL_MS: u64 = 250;
 
@@ -13,6 +13,19 @@ struct DPRPrintInfo {
     buffer: Vec<u8>,
 }
 
+/// A point in time copy of the progress, for reporting it other than by
+/// printing to the terminal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataProgressSnapshot {
+    pub message: String,
+    pub is_active: bool,
+    pub current_count: usize,
+    pub total_count: usize,
+    pub current_bytes: usize,
+    pub total_bytes: usize,
+    pub elapsed: Duration,
+}
+
 #[derive(Debug)]
 pub struct DataProgressReporter {
     is_active: AtomicBool,
@@ -136,6 +149,19 @@ impl DataProgressReporter {
         let _ = self.print(false);
     }
 
+    /// Returns the current progress.
+    pub fn snapshot(&self) -> DataProgressSnapshot {
+        DataProgressSnapshot {
+            message: self.message.clone(),
+            is_active: self.is_active.load(Ordering::Relaxed),
+            current_count: self.current_count.load(Ordering::Relaxed),
+            total_count: self.total_count.load(Ordering::Relaxed),
+            current_bytes: self.current_bytes.load(Ordering::Relaxed),
+            total_bytes: self.total_bytes.load(Ordering::Relaxed),
+            elapsed: self.time_at_start.elapsed(),
+        }
+    }
+
     /// Call when done with all progress.
     pub fn finalize(&self) {
         let _ = self.print(true);
@@ -1,3 +1,3 @@
 mod data_progress;
 
-pub use data_progress::DataProgressReporter;
+pub use data_progress::{DataProgressReporter, DataProgressSnapshot};
@@ -0,0 +1,85 @@
+use cas::fileio::{read_exact_at, set_io_backend, IoBackend, PositionedWriter};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use tempfile::TempDir;
+
+/// Many small files, like a checkout of a dataset of images, and a few
+/// large ones.
+const WORKLOADS: [(&str, usize, usize); 2] = [
+    ("small_files", 2000, 16 * 1024),
+    ("large_files", 4, 64 * 1024 * 1024),
+];
+
+fn backends() -> Vec<IoBackend> {
+    let mut backends = vec![IoBackend::Std];
+    if set_io_backend(IoBackend::IoUring) == IoBackend::IoUring {
+        backends.push(IoBackend::IoUring);
+    } else {
+        eprintln!("io_uring is unavailable; benchmarking the standard backend only");
+    }
+    backends
+}
+
+fn bench_materialize(c: &mut Criterion) {
+    let backends = backends();
+    for (name, count, size) in WORKLOADS {
+        let data = vec![7u8; size];
+        let mut group = c.benchmark_group(format!("write_{name}"));
+        group.throughput(Throughput::Bytes((count * size) as u64));
+        group.sample_size(10);
+        for backend in &backends {
+            group.bench_with_input(
+                BenchmarkId::from_parameter(format!("{backend:?}")),
+                backend,
+                |b, backend| {
+                    set_io_backend(*backend);
+                    let dir = TempDir::new().unwrap();
+                    b.iter(|| {
+                        for i in 0..count {
+                            let file = File::create(dir.path().join(i.to_string())).unwrap();
+                            let mut writer =
+                                BufWriter::with_capacity(1024 * 1024, PositionedWriter::new(file));
+                            writer.write_all(&data).unwrap();
+                            writer.flush().unwrap();
+                        }
+                    });
+                },
+            );
+        }
+        group.finish();
+    }
+}
+
+fn bench_read(c: &mut Criterion) {
+    let backends = backends();
+    for (name, count, size) in WORKLOADS {
+        let dir = TempDir::new().unwrap();
+        for i in 0..count {
+            std::fs::write(dir.path().join(i.to_string()), vec![7u8; size]).unwrap();
+        }
+        let mut buf = vec![0u8; size];
+        let mut group = c.benchmark_group(format!("read_{name}"));
+        group.throughput(Throughput::Bytes((count * size) as u64));
+        group.sample_size(10);
+        for backend in &backends {
+            group.bench_with_input(
+                BenchmarkId::from_parameter(format!("{backend:?}")),
+                backend,
+                |b, backend| {
+                    set_io_backend(*backend);
+                    b.iter(|| {
+                        for i in 0..count {
+                            let file = File::open(dir.path().join(i.to_string())).unwrap();
+                            read_exact_at(&file, &mut buf, 0).unwrap();
+                        }
+                    });
+                },
+            );
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_materialize, bench_read);
+criterion_main!(benches);
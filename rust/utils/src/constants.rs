@@ -9,6 +9,7 @@ pub const TRACE_ID_HEADER: &str = "uber-trace-id";
 pub const CONTENT_TYPE_HEADER: &str = "Content-Type";
 pub const JSON_CONTENT_TYPE: &str = "application/json";
 pub const CAS_PROTOCOL_VERSION_HEADER: &str = "xet-cas-protocol-version";
+pub const PROXY_TOKEN_HEADER: &str = "xet-proxy-token";
 pub const CLIENT_IP_HEADER: &str = "x-forwarded-for";
 pub const UNKNOWN_IP: &str = "0.0.0.0";
 pub const DEFAULT_USER: &str = "anonymous";
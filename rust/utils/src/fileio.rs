@@ -0,0 +1,522 @@
+//! Positioned reads and writes of files, used where git-xet moves a lot of
+//! file data: the block cache and the files materialized by smudge.
+//!
+//! On Linux kernels that support it, the reads and writes go through an
+//! io_uring owned by each thread, and a large write is split into segments
+//! submitted together with a single syscall. Elsewhere, or if io_uring is
+//! unavailable (e.g. disabled by a seccomp policy in a container), they
+//! fall back to pread/pwrite.
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use tracing::{info, warn};
+
+/// The I/O backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IoBackend {
+    /// io_uring if the kernel supports it, and the standard library otherwise.
+    #[default]
+    Auto,
+    /// The standard library's positioned reads and writes.
+    Std,
+    /// io_uring, on Linux.
+    IoUring,
+}
+
+impl FromStr for IoBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" | "" => Ok(IoBackend::Auto),
+            "std" => Ok(IoBackend::Std),
+            "io_uring" | "uring" => Ok(IoBackend::IoUring),
+            _ => Err(s.to_string()),
+        }
+    }
+}
+
+const BACKEND_STD: u8 = 0;
+const BACKEND_IO_URING: u8 = 1;
+
+static BACKEND: AtomicU8 = AtomicU8::new(BACKEND_STD);
+
+/// Selects the backend for the process, returning the one in use: Auto
+/// resolves to io_uring only if it's available, and a request for io_uring
+/// on a system without it falls back to Std.
+pub fn set_io_backend(requested: IoBackend) -> IoBackend {
+    let backend = match requested {
+        IoBackend::Std => IoBackend::Std,
+        IoBackend::Auto if uring::available() => IoBackend::IoUring,
+        IoBackend::Auto => IoBackend::Std,
+        IoBackend::IoUring if uring::available() => IoBackend::IoUring,
+        IoBackend::IoUring => {
+            warn!("io_uring is not available on this system; using standard file I/O");
+            IoBackend::Std
+        }
+    };
+    let value = match backend {
+        IoBackend::IoUring => BACKEND_IO_URING,
+        _ => BACKEND_STD,
+    };
+    BACKEND.store(value, Ordering::Relaxed);
+    info!("File I/O backend: {backend:?}");
+    backend
+}
+
+/// The backend in use, Std until set_io_backend selects another.
+pub fn io_backend() -> IoBackend {
+    match BACKEND.load(Ordering::Relaxed) {
+        BACKEND_IO_URING => IoBackend::IoUring,
+        _ => IoBackend::Std,
+    }
+}
+
+/// Reads exactly buf.len() bytes of the file, starting at offset.
+pub fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    match io_backend() {
+        IoBackend::IoUring => uring::read_exact_at(file, buf, offset),
+        _ => std_io::read_exact_at(file, buf, offset),
+    }
+}
+
+/// Writes all of buf to the file, starting at offset.
+pub fn write_all_at(file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+    match io_backend() {
+        IoBackend::IoUring => uring::write_all_at(file, buf, offset),
+        _ => std_io::write_all_at(file, buf, offset),
+    }
+}
+
+/// The bytes available to the current user on the filesystem holding path.
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    fs2::available_space(path)
+}
+
+/// Writes a file sequentially with positioned writes through the backend.
+/// Wrap it in a BufWriter so that small writes are gathered into larger
+/// ones.
+#[derive(Debug)]
+pub struct PositionedWriter {
+    file: File,
+    offset: u64,
+}
+
+impl PositionedWriter {
+    pub fn new(file: File) -> Self {
+        PositionedWriter { file, offset: 0 }
+    }
+
+    /// A writer continuing the file from offset.
+    pub fn at(file: File, offset: u64) -> Self {
+        PositionedWriter { file, offset }
+    }
+}
+
+impl Write for PositionedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        write_all_at(&self.file, buf, self.offset)?;
+        self.offset += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+mod std_io {
+    use std::fs::File;
+    use std::io;
+
+    #[cfg(unix)]
+    pub fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        use std::os::unix::fs::FileExt;
+        file.read_exact_at(buf, offset)
+    }
+
+    #[cfg(unix)]
+    pub fn write_all_at(file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+        use std::os::unix::fs::FileExt;
+        file.write_all_at(buf, offset)
+    }
+
+    #[cfg(windows)]
+    pub fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+        use std::os::windows::fs::FileExt;
+        while !buf.is_empty() {
+            match file.seek_read(buf, offset)? {
+                0 => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "failed to fill whole buffer",
+                    ))
+                }
+                n => {
+                    buf = &mut buf[n..];
+                    offset += n as u64;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    pub fn write_all_at(file: &File, mut buf: &[u8], mut offset: u64) -> io::Result<()> {
+        use std::os::windows::fs::FileExt;
+        while !buf.is_empty() {
+            match file.seek_write(buf, offset)? {
+                0 => return Err(io::ErrorKind::WriteZero.into()),
+                n => {
+                    buf = &buf[n..];
+                    offset += n as u64;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod uring {
+    use io_uring::{opcode, types, IoUring, Probe};
+    use lazy_static::lazy_static;
+    use std::cell::RefCell;
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+    use tracing::warn;
+
+    const RING_ENTRIES: u32 = 64;
+
+    /// Writes larger than this are split into segments submitted together.
+    const SEGMENT_SIZE: usize = 1024 * 1024;
+
+    lazy_static! {
+        static ref AVAILABLE: bool = probe();
+    }
+
+    thread_local! {
+        static RING: RefCell<Option<IoUring>> = const { RefCell::new(None) };
+    }
+
+    /// Whether the kernel lets this process create a ring supporting reads
+    /// and writes.
+    pub fn available() -> bool {
+        *AVAILABLE
+    }
+
+    fn probe() -> bool {
+        let Ok(ring) = IoUring::new(2) else {
+            return false;
+        };
+        let mut probe = Probe::new();
+        if ring.submitter().register_probe(&mut probe).is_err() {
+            return false;
+        }
+        probe.is_supported(opcode::Read::CODE) && probe.is_supported(opcode::Write::CODE)
+    }
+
+    fn with_ring<T>(f: impl FnOnce(&mut IoUring) -> io::Result<T>) -> io::Result<T> {
+        RING.with(|ring| {
+            let mut ring = ring.borrow_mut();
+            if ring.is_none() {
+                *ring = Some(IoUring::new(RING_ENTRIES)?);
+            }
+            f(ring.as_mut().unwrap())
+        })
+    }
+
+    /// Submits one operation per segment and waits for all of them, returning
+    /// the bytes transferred by each. Segments are submitted at most
+    /// RING_ENTRIES at a time.
+    fn submit_segments(
+        ring: &mut IoUring,
+        entries: Vec<io_uring::squeue::Entry>,
+    ) -> io::Result<Vec<usize>> {
+        submit_segments_with(ring, entries, |ring, want| ring.submit_and_wait(want))
+    }
+
+    /// submit_segments, submitting each batch with submit_and_wait, so that
+    /// tests can make submissions fail.
+    pub(super) fn submit_segments_with(
+        ring: &mut IoUring,
+        entries: Vec<io_uring::squeue::Entry>,
+        mut submit_and_wait: impl FnMut(&mut IoUring, usize) -> io::Result<usize>,
+    ) -> io::Result<Vec<usize>> {
+        let mut done = vec![0usize; entries.len()];
+        for (batch_start, batch) in entries
+            .chunks(RING_ENTRIES as usize)
+            .enumerate()
+            .map(|(i, b)| (i * RING_ENTRIES as usize, b))
+        {
+            let mut pushed = 0;
+            let mut submitted = Ok(());
+            for (i, entry) in batch.iter().enumerate() {
+                let entry = entry.clone().user_data((batch_start + i) as u64);
+                // Safety: the buffers the entries point to outlive the wait
+                // for their completion below, which happens even if the
+                // submission fails.
+                match unsafe { ring.submission().push(&entry) } {
+                    Ok(()) => pushed += 1,
+                    Err(e) => {
+                        submitted = Err(io::Error::new(io::ErrorKind::Other, e));
+                        break;
+                    }
+                }
+            }
+            if submitted.is_ok() {
+                submitted = loop {
+                    match submit_and_wait(ring, pushed) {
+                        Ok(_) => break Ok(()),
+                        // Signals, e.g. SIGINT, interrupt the wait.
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(e) => break Err(e),
+                    }
+                };
+            }
+
+            let mut error = None;
+            let completed = reap(ring, &mut done, &mut error);
+            if let Err(e) = submitted {
+                drain(ring, pushed.saturating_sub(completed));
+                return Err(e);
+            }
+            if let Some(e) = error {
+                return Err(e);
+            }
+        }
+        Ok(done)
+    }
+
+    /// Records the completions in the ring into done, by the index of their
+    /// segment, and the first error into error. Returns the number of
+    /// completions.
+    fn reap(ring: &mut IoUring, done: &mut [usize], error: &mut Option<io::Error>) -> usize {
+        let mut completed = 0;
+        for cqe in ring.completion() {
+            completed += 1;
+            let result = cqe.result();
+            if result < 0 {
+                error.get_or_insert(io::Error::from_raw_os_error(-result));
+            } else if let Some(n) = done.get_mut(cqe.user_data() as usize) {
+                *n = result as usize;
+            }
+        }
+        completed
+    }
+
+    /// Waits for the outstanding operations of a failed submission to
+    /// complete, so that neither their buffers nor their completions outlive
+    /// it. If the ring can't be waited on, it is replaced, which cancels them.
+    fn drain(ring: &mut IoUring, mut outstanding: usize) {
+        while outstanding > 0 {
+            match ring.submit_and_wait(outstanding) {
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    warn!("Unable to wait for {outstanding} io_uring operations ({e}); replacing the ring");
+                    if let Ok(fresh) = IoUring::new(RING_ENTRIES) {
+                        *ring = fresh;
+                    }
+                    return;
+                }
+            }
+            outstanding = outstanding.saturating_sub(ring.completion().count());
+        }
+    }
+
+    pub fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+        let fd = types::Fd(file.as_raw_fd());
+        with_ring(|ring| {
+            while !buf.is_empty() {
+                let entry = opcode::Read::new(fd, buf.as_mut_ptr(), buf.len() as u32)
+                    .offset(offset)
+                    .build();
+                match submit_segments(ring, vec![entry])?[0] {
+                    0 => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "failed to fill whole buffer",
+                        ))
+                    }
+                    n => {
+                        buf = &mut buf[n..];
+                        offset += n as u64;
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    pub fn write_all_at(file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+        let fd = types::Fd(file.as_raw_fd());
+        with_ring(|ring| {
+            let segments: Vec<(usize, &[u8])> = buf
+                .chunks(SEGMENT_SIZE)
+                .enumerate()
+                .map(|(i, s)| (i * SEGMENT_SIZE, s))
+                .collect();
+            let entries = segments
+                .iter()
+                .map(|(start, s)| {
+                    opcode::Write::new(fd, s.as_ptr(), s.len() as u32)
+                        .offset(offset + *start as u64)
+                        .build()
+                })
+                .collect();
+            let written = submit_segments(ring, entries)?;
+
+            // Finish any segment the kernel wrote only part of.
+            for ((start, segment), n) in segments.into_iter().zip(written) {
+                if n < segment.len() {
+                    super::std_io::write_all_at(file, &segment[n..], offset + (start + n) as u64)?;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod uring {
+    use std::fs::File;
+    use std::io;
+
+    pub fn available() -> bool {
+        false
+    }
+
+    pub fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        super::std_io::read_exact_at(file, buf, offset)
+    }
+
+    pub fn write_all_at(file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+        super::std_io::write_all_at(file, buf, offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempfile;
+
+    fn check_roundtrip(backend: IoBackend) {
+        let data: Vec<u8> = (0..3 * 1024 * 1024 + 17).map(|i| i as u8).collect();
+        let file = tempfile().unwrap();
+        match backend {
+            IoBackend::IoUring => uring::write_all_at(&file, &data, 5).unwrap(),
+            _ => std_io::write_all_at(&file, &data, 5).unwrap(),
+        }
+
+        let mut buf = vec![0u8; 1000];
+        std_io::read_exact_at(&file, &mut buf, 5 + 2 * 1024 * 1024).unwrap();
+        assert_eq!(buf, &data[2 * 1024 * 1024..2 * 1024 * 1024 + 1000]);
+
+        let mut buf = vec![0u8; data.len()];
+        match backend {
+            IoBackend::IoUring => uring::read_exact_at(&file, &mut buf, 5).unwrap(),
+            _ => std_io::read_exact_at(&file, &mut buf, 5).unwrap(),
+        }
+        assert_eq!(buf, data);
+
+        // Reading past the end of the file fails.
+        let mut buf = vec![0u8; 100];
+        assert_eq!(
+            std_io::read_exact_at(&file, &mut buf, data.len() as u64 - 10)
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn test_std_roundtrip() {
+        check_roundtrip(IoBackend::Std);
+    }
+
+    #[test]
+    fn test_uring_roundtrip() {
+        // Skipped where the kernel or sandbox doesn't allow io_uring.
+        if uring::available() {
+            check_roundtrip(IoBackend::IoUring);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_uring_failed_submission() {
+        use io_uring::{opcode, types, IoUring};
+        use std::os::unix::io::AsRawFd;
+
+        if !uring::available() {
+            return;
+        }
+        let file = tempfile().unwrap();
+        std_io::write_all_at(&file, b"hello world", 0).unwrap();
+        let fd = types::Fd(file.as_raw_fd());
+        let read = |buf: &mut [u8]| {
+            opcode::Read::new(fd, buf.as_mut_ptr(), buf.len() as u32)
+                .offset(0)
+                .build()
+        };
+        let mut ring = IoUring::new(8).unwrap();
+
+        // Interrupted submissions are retried.
+        let mut buf = vec![0u8; 11];
+        let mut calls = 0;
+        let done = uring::submit_segments_with(&mut ring, vec![read(&mut buf)], |ring, want| {
+            calls += 1;
+            if calls == 1 {
+                Err(io::ErrorKind::Interrupted.into())
+            } else {
+                ring.submit_and_wait(want)
+            }
+        })
+        .unwrap();
+        assert_eq!((done, calls), (vec![11], 2));
+        assert_eq!(&buf, b"hello world");
+
+        // Other failures return once the operations submitted complete,
+        // leaving no completions behind for the next call.
+        let mut buf = vec![0u8; 5];
+        let err = uring::submit_segments_with(&mut ring, vec![read(&mut buf)], |ring, _| {
+            ring.submit()?;
+            Err(io::Error::new(io::ErrorKind::Other, "injected"))
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), "injected");
+        assert!(ring.completion().is_empty());
+
+        let (mut a, mut b) = (vec![0u8; 5], vec![0u8; 11]);
+        let done = uring::submit_segments_with(
+            &mut ring,
+            vec![read(&mut a), read(&mut b)],
+            |ring, want| ring.submit_and_wait(want),
+        )
+        .unwrap();
+        assert_eq!(done, vec![5, 11]);
+    }
+
+    #[test]
+    fn test_positioned_writer() {
+        let file = tempfile().unwrap();
+        let mut writer = PositionedWriter::new(file.try_clone().unwrap());
+        writer.write_all(b"hello ").unwrap();
+        writer.write_all(b"world").unwrap();
+        let mut buf = vec![0u8; 11];
+        read_exact_at(&file, &mut buf, 0).unwrap();
+        assert_eq!(&buf, b"hello world");
+    }
+
+    #[test]
+    fn test_parse_backend() {
+        assert_eq!("auto".parse(), Ok(IoBackend::Auto));
+        assert_eq!("STD".parse(), Ok(IoBackend::Std));
+        assert_eq!("io_uring".parse(), Ok(IoBackend::IoUring));
+        assert!("aio".parse::<IoBackend>().is_err());
+    }
+}
@@ -0,0 +1,132 @@
+//! Advisory locks on files, used to serialize git-xet processes writing the
+//! same staging, cache, or merkledb directories.
+//!
+//! Locks are held through the OS (flock on unix), so a lock is released when
+//! the process holding it exits, even if it crashes. The holder writes its
+//! pid into the lock file so that a process timing out waiting for the lock
+//! can say who holds it.
+use fs2::FileExt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// How long to wait for a lock held by another process before giving up.
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An exclusive lock on a file, released on drop.
+#[derive(Debug)]
+pub struct FileLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl FileLock {
+    /// Acquires an exclusive lock on the file at path, creating it if needed,
+    /// waiting up to timeout for another process to release it.
+    ///
+    /// On timeout, returns an error of kind TimedOut naming the lock file and
+    /// the process holding it.
+    pub fn acquire(path: &Path, timeout: Duration) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let start = Instant::now();
+        let mut waiting = false;
+        loop {
+            match file.try_lock_exclusive() {
+                Ok(()) => break,
+                Err(e) if e.kind() == fs2::lock_contended_error().kind() => {}
+                Err(e) => return Err(e),
+            }
+            if start.elapsed() >= timeout {
+                let holder = fs::read_to_string(path).unwrap_or_default();
+                let holder = match holder.trim() {
+                    "" => "another process".to_string(),
+                    pid => format!("process {pid}"),
+                };
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!(
+                        "Timed out after {}s waiting for the lock on {path:?} held by {holder}; \
+                         another git-xet operation may be running on this repository",
+                        timeout.as_secs()
+                    ),
+                ));
+            }
+            if !waiting {
+                info!("Waiting for the lock on {path:?}");
+                waiting = true;
+            }
+            std::thread::sleep(LOCK_POLL_INTERVAL);
+        }
+
+        let mut lock = Self {
+            file,
+            path: path.to_path_buf(),
+        };
+        lock.write_pid()?;
+        debug!("Acquired the lock on {path:?}");
+        Ok(lock)
+    }
+
+    /// Acquires the lock from an async context without blocking the runtime
+    /// while waiting.
+    pub async fn acquire_async(path: PathBuf, timeout: Duration) -> io::Result<Self> {
+        tokio::task::spawn_blocking(move || Self::acquire(&path, timeout))
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?
+    }
+
+    fn write_pid(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        write!(self.file, "{}", std::process::id())?;
+        self.file.flush()
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = self.file.set_len(0);
+        let _ = self.file.unlock();
+        debug!("Released the lock on {:?}", self.path);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_lock_excludes_and_times_out() {
+        let tmp_dir = TempDir::new().unwrap();
+        let path = tmp_dir.path().join("staging.lock");
+
+        let lock = FileLock::acquire(&path, Duration::ZERO).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            std::process::id().to_string()
+        );
+
+        let err = FileLock::acquire(&path, Duration::from_millis(200)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(err
+            .to_string()
+            .contains(&format!("process {}", std::process::id())));
+
+        drop(lock);
+        FileLock::acquire(&path, Duration::ZERO).unwrap();
+    }
+}
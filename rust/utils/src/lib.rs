@@ -34,6 +34,8 @@ pub mod shard {
 pub mod consistenthash;
 pub mod constants;
 pub mod errors;
+pub mod fileio;
+pub mod filelock;
 pub mod gitbaretools;
 pub mod key;
 pub mod safeio;
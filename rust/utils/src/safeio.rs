@@ -27,6 +27,41 @@ pub fn write_all_file_safe(path: &Path, bytes: &[u8]) -> io::Result<()> {
     Ok(())
 }
 
+/// Moves the file at from to to, replacing it. Files can't be renamed across
+/// volumes, e.g. to a staging or cache directory configured on another
+/// drive, so they are copied to a temporary file next to to instead, which
+/// is renamed into place before from is removed. Readers of to therefore
+/// never see a partial copy.
+pub fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match std::fs::rename(from, to) {
+        Err(e) if crosses_devices(&e) => {}
+        result => return result,
+    }
+    let dir = to.parent().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Unable to find parent path from {to:?}"),
+        )
+    })?;
+    let tempfile = create_temp_file(dir, "")?;
+    std::fs::copy(from, tempfile.path())?;
+    tempfile.persist(to).map_err(|e| e.error)?;
+    std::fs::remove_file(from)
+}
+
+/// Whether a rename failed because the source and destination are on
+/// different volumes.
+fn crosses_devices(e: &io::Error) -> bool {
+    // EXDEV on Unix, ERROR_NOT_SAME_DEVICE on Windows.
+    #[cfg(unix)]
+    const CROSS_DEVICE_ERROR: i32 = 18;
+    #[cfg(windows)]
+    const CROSS_DEVICE_ERROR: i32 = 17;
+    #[cfg(not(any(unix, windows)))]
+    const CROSS_DEVICE_ERROR: i32 = -1;
+    e.raw_os_error() == Some(CROSS_DEVICE_ERROR)
+}
+
 pub fn create_temp_file(dir: &Path, suffix: &str) -> io::Result<NamedTempFile> {
     let tempfile = tempfile::Builder::new()
         .prefix(&format!("{}.", std::process::id()))
@@ -42,7 +77,7 @@ mod test {
     use std::fs;
     use tempfile::TempDir;
 
-    use super::write_all_file_safe;
+    use super::{crosses_devices, move_file, write_all_file_safe};
 
     #[test]
     fn test_small_file_write() -> Result<()> {
@@ -56,4 +91,48 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_long_path_write_and_move() -> Result<()> {
+        // Deeper than the 260 characters of MAX_PATH on Windows.
+        let tmp_dir = TempDir::new()?;
+        let dir = tmp_dir
+            .path()
+            .join("d".repeat(100))
+            .join("e".repeat(100))
+            .join("f".repeat(100));
+        let bytes = vec![2u8; 1000];
+        let file_name = dir.join("data");
+
+        write_all_file_safe(&file_name, &bytes)?;
+        assert_eq!(fs::read(&file_name)?, bytes);
+
+        let moved = dir.join("moved");
+        move_file(&file_name, &moved)?;
+        assert!(!file_name.exists());
+        assert_eq!(fs::read(&moved)?, bytes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_move_keeps_destination() -> Result<()> {
+        let tmp_dir = TempDir::new()?;
+        let to = tmp_dir.path().join("to");
+        write_all_file_safe(&to, b"kept")?;
+
+        // Only renames across volumes are retried as a copy.
+        assert!(move_file(&tmp_dir.path().join("missing"), &to).is_err());
+        assert_eq!(fs::read(&to)?, b"kept");
+        Ok(())
+    }
+
+    #[test]
+    fn test_crosses_devices() {
+        assert!(!crosses_devices(&std::io::Error::from(
+            std::io::ErrorKind::NotFound
+        )));
+        #[cfg(unix)]
+        assert!(crosses_devices(&std::io::Error::from_raw_os_error(18)));
+    }
 }
@@ -57,6 +57,25 @@ pub struct Cfg {
     pub log: Option<Log>,
     pub user: Option<User>,
     pub axe: Option<Axe>,
+    pub io: Option<Io>,
+    pub integrity: Option<Integrity>,
+    pub p2p: Option<P2p>,
+    pub quota: Option<Quota>,
+    pub upload: Option<Upload>,
+    pub shard: Option<Shard>,
+    pub control: Option<Control>,
+    pub signing: Option<Signing>,
+    pub audit: Option<Audit>,
+    pub retention: Option<Retention>,
+    pub store: Option<Store>,
+    pub fallback: Option<Fallback>,
+    pub mirror: Option<Mirror>,
+    pub pack: Option<Pack>,
+    pub staging: Option<Staging>,
+    pub chunking: Option<Chunking>,
+    pub summaries: Option<Summaries>,
+    pub usage: Option<Usage>,
+    pub tier: Option<Tier>,
     /// profiles is a map of different profiles a user might have    
     /// created identified by some key.
     /// Note that `.` characters can't be used as a key since they
@@ -133,6 +152,7 @@ impl Cfg {
             cache: Some(Cache {
                 path: Some(default_cache_path),
                 size: Some(DEFAULT_CACHE_SIZE),
+                memory: None,
                 blocksize: None, // Keeping the default blocksize to None
                                  // instead of a constant to indicate that
                                  // this is something we may tune internally
@@ -145,6 +165,8 @@ impl Cfg {
                 tracing: None,
                 silentsummary: None,
                 exceptions: None,
+                dir: None,
+                maxfiles: None,
             }),
             user: Some(User {
                 ssh: None,
@@ -158,6 +180,25 @@ impl Cfg {
                 enabled: Some(DEFAULT_AXE_ENABLED.to_string()),
                 axe_code: Some("5454".to_string()),
             }),
+            io: None,
+            integrity: None,
+            p2p: None,
+            quota: None,
+            upload: None,
+            shard: None,
+            control: None,
+            signing: None,
+            audit: None,
+            retention: None,
+            store: None,
+            fallback: None,
+            mirror: None,
+            pack: None,
+            staging: None,
+            chunking: None,
+            summaries: None,
+            usage: None,
+            tier: None,
             profiles: HashMap::default(), // Default serialization of the flattened map is to return Some empty map
         }
     }
@@ -220,6 +261,25 @@ impl Default for Cfg {
             log: None,
             user: None,
             axe: None,
+            io: None,
+            integrity: None,
+            p2p: None,
+            quota: None,
+            upload: None,
+            shard: None,
+            control: None,
+            signing: None,
+            audit: None,
+            retention: None,
+            store: None,
+            fallback: None,
+            mirror: None,
+            pack: None,
+            staging: None,
+            chunking: None,
+            summaries: None,
+            usage: None,
+            tier: None,
             profiles: HashMap::default(),
         }
     }
@@ -254,6 +314,10 @@ pub struct Cache {
     pub path: Option<PathBuf>,
     /// Size in bytes
     pub size: Option<u64>,
+    /// Size of the blocks held in memory across all reads of the process,
+    /// e.g. "2GB" or "512MiB", in front of the cache on disk. Unset or 0 to
+    /// not hold blocks in memory.
+    pub memory: Option<String>,
     /// block size in bytes.
     /// blocksize instead of block_size here because the Config environment
     /// parser doesn't seem to like underscores very much, and it doesn't
@@ -277,6 +341,13 @@ pub struct Log {
 
     /// Whether or not to log exceptions when they occur.
     pub exceptions: Option<bool>,
+
+    /// Directory each command writes its own log file in, at least at the
+    /// info level whatever `level` is. Defaults to .git/xet/logs in a
+    /// repository; set to the empty string to not write these files.
+    pub dir: Option<PathBuf>,
+    /// The number of log files kept in `dir`, the oldest being removed.
+    pub maxfiles: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
@@ -299,6 +370,257 @@ pub struct Axe {
     pub axe_code: Option<String>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Io {
+    /// Some(true) to materialize large files by preallocating the destination
+    /// and writing the reconstructed blocks at their final offsets, instead of
+    /// streaming them through a sequential writer.
+    pub preallocate: Option<bool>,
+    /// How files are read and written by the cache and smudge: `auto`,
+    /// `std`, or `io_uring`. Default is `auto`, which uses io_uring on Linux
+    /// kernels that support it.
+    pub backend: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Integrity {
+    /// When files are verified against the whole-file hash recorded in their
+    /// pointer file: one of "always", "never" or "on_push".
+    pub verify: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct P2p {
+    /// The URL of the coordinator that peers announce the xorbs they hold to,
+    /// e.g. "http://head-node:4050". Setting it enables fetching from peers.
+    pub coordinator: Option<String>,
+    /// The address this machine serves xorbs to peers on, as "host:port".
+    pub advertise: Option<String>,
+    /// The maximum total size in bytes of the xorbs kept for peers.
+    pub store_size: Option<u64>,
+    /// The token shared by the coordinator and all peers, which they present
+    /// to each other.
+    pub token: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Quota {
+    /// What the pre-push hook does when a push would exceed the remote's
+    /// storage quota: one of "off", "warn" or "error". Remotes that don't
+    /// report a quota are not checked.
+    pub check: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Upload {
+    /// The number of xorbs checked against the CAS at once before uploading.
+    pub dedup_check_concurrency: Option<usize>,
+    /// The number of staged xorbs read from disk at once.
+    pub read_concurrency: Option<usize>,
+    /// The number of xorbs uploaded at once.
+    pub concurrency: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Shard {
+    /// Some(true) to fetch only the MerkleDB shards needed by the checked out
+    /// files; the others are fetched when a file that needs them is smudged.
+    pub partial: Option<bool>,
+    /// The hash algorithm ("xet", "blake3" or "sha256") to ask the remote
+    /// for when a repository is initialized.
+    pub hash_algorithm: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Control {
+    /// Some(true) to serve the JSON-RPC control socket from mounts and the
+    /// filter process, under .git/xet/control.
+    pub enabled: Option<bool>,
+    /// The initial limit in bytes per second on data transferred to and
+    /// from the CAS, adjustable at runtime through the control socket.
+    pub bandwidth_limit: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Signing {
+    /// The key pointer files are signed with when cleaned: the path to an
+    /// ssh private key, or a GPG key id. Setting it enables signing.
+    pub key: Option<String>,
+    /// The kind of key: one of "ssh" or "gpg". Defaults to "ssh".
+    pub format: Option<String>,
+    /// The ssh allowed signers file used to verify ssh signatures, in the
+    /// format of ssh-keygen(1).
+    pub allowed_signers: Option<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Audit {
+    /// The JSONL file that files smudged or read through a mount are
+    /// recorded in. Setting it enables the audit log.
+    pub path: Option<PathBuf>,
+    /// The size in bytes at which the log is rotated.
+    pub max_size: Option<u64>,
+    /// The number of rotated logs kept, as <path>.1 to <path>.<max_files>.
+    pub max_files: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Retention {
+    /// Cached blocks older than this many days are deleted.
+    pub cache_days: Option<u64>,
+    /// Staged xorbs older than this many days are deleted once the CAS is
+    /// known to store them.
+    pub staging_days: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Store {
+    /// A directory shared by all repositories on this machine, in which
+    /// xorbs are kept once and referenced by every repository reading them.
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Fallback {
+    /// Whether blocks missing from CAS are rebuilt from the sources the
+    /// files were imported from.
+    pub enabled: Option<bool>,
+    /// Rewrites of recorded source URLs to mirrors, as "<prefix>=<mirror>",
+    /// e.g. "s3://datasets/=https://mirror.example.com/datasets/".
+    pub mirrors: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Mirror {
+    /// CAS remotes the data is replicated to besides cas.server, as
+    /// "<name>=<endpoint>"; managed by `git xet remote add-cas`.
+    pub remotes: Option<Vec<String>>,
+    /// How blocks are written to the remotes: "all" (default) writes each to
+    /// every remote before it is considered uploaded, "async" writes to
+    /// cas.server and copies to the others in the background.
+    pub replication: Option<String>,
+    /// The remote blocks are read from first, e.g. an on-premise mirror;
+    /// the others are tried in turn if it fails. Defaults to cas.server,
+    /// named "primary".
+    pub fetch: Option<String>,
+    /// The most hedged reads in flight at once: reads slower than the p95
+    /// latency are also sent to the next remote, and the first answer is
+    /// used. 0 disables hedging; defaults to 16.
+    pub hedge_inflight: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Pack {
+    /// Files smaller than this many bytes are packed: their data is kept
+    /// together in shared xorbs without a dedup query to the server per
+    /// file, and checkout smudges them in batches. 0, the default, disables
+    /// packing; e.g. 262144 packs files under 256KiB.
+    pub threshold: Option<u64>,
+    /// The most bytes of a xorb fetched in one request when smudging packed
+    /// files. Defaults to 16MiB.
+    pub readsize: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Staging {
+    /// A directory to stage data in instead of the repository's .git, e.g.
+    /// on a larger scratch volume. Chunks waiting to be pushed are staged
+    /// in a subdirectory per repository, and the block cache, unless
+    /// cache.path is set, is kept in its cache subdirectory.
+    pub path: Option<PathBuf>,
+    /// The space to leave free on the staging volume, e.g. 10GB. Staging
+    /// stops with an error rather than fill it up further. Defaults to 0.
+    pub minfree: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Chunking {
+    /// Whether chunks of tar and zip archives also end at the start and the
+    /// end of the data of each entry, so that a file is chunked the same
+    /// wherever it is in an archive, and dedups across rewritten archives.
+    /// Files of other formats are chunked as usual. Defaults to false, as
+    /// archives already stored would chunk differently when cleaned again.
+    pub formataware: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Summaries {
+    /// Files no analyzer summarizes, as git pathspecs, each optionally
+    /// followed by ">" and a size to only exclude the larger files, e.g.
+    /// "*.bin>1GB".
+    pub exclude: Option<Vec<String>>,
+    /// The seconds an analyzer may spend on a file before its summary of the
+    /// file is given up. Unset or 0 for no limit.
+    pub timeout: Option<u64>,
+    /// How libmagic names the type of a file: "extension" (the default) from
+    /// its extension, or "signature" from the signature at the start of its
+    /// content. Either falls back to the other for types it does not know.
+    pub typedetector: Option<String>,
+    pub csv: Option<SummaryAnalyzer>,
+    pub libmagic: Option<SummaryAnalyzer>,
+    pub languages: Option<SummaryAnalyzer>,
+}
+
+/// The settings of one of the analyzers summarizing files.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct SummaryAnalyzer {
+    /// Whether the analyzer runs at all. Defaults to true.
+    pub enabled: Option<bool>,
+    /// Files this analyzer does not summarize, in addition to
+    /// summaries.exclude, in the same format.
+    pub exclude: Option<Vec<String>>,
+    /// Overrides summaries.timeout for this analyzer.
+    pub timeout: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Usage {
+    /// Some(true) to opt in to sending anonymous usage events, Some(false)
+    /// to opt out. Replaces axe.enabled, which is used while this is unset.
+    pub enabled: Option<bool>,
+    /// The URL batches of events are posted to.
+    pub endpoint: Option<String>,
+    /// The number of events spooled before they are sent together.
+    pub batchsize: Option<usize>,
+    /// The most events kept in the spool while they can't be sent; the
+    /// oldest are dropped beyond it.
+    pub spoolmax: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Tier {
+    /// Data referenced by a branch or tag committed within this many days
+    /// stays in the hot tier when `git xet tier` runs. Defaults to 90.
+    pub hotdays: Option<u64>,
+    /// Where `git xet tier` moves the other data: "cold", the cold storage
+    /// class of the remote CAS (the default), or the name of another
+    /// backend the remote is configured with.
+    pub target: Option<String>,
+    /// The seconds a read waits for data to be restored from cold storage
+    /// before failing. Defaults to 3600.
+    pub restoretimeout: Option<u64>,
+}
+
 #[cfg(test)]
 mod serialization_tests {
     use crate::cfg::{Axe, Cache, Cas, Cfg, Log, User, CURRENT_VERSION};
@@ -350,6 +672,7 @@ mod serialization_tests {
             cache: Some(Cache {
                 path: Some("/tmp/xet.log".into()),
                 size: Some(2000),
+                memory: None,
                 blocksize: Some(3000),
             }),
             log: Some(Log {
@@ -359,6 +682,8 @@ mod serialization_tests {
                 tracing: None,
                 silentsummary: None,
                 exceptions: None,
+                dir: None,
+                maxfiles: None,
             }),
             user: Some(User {
                 ssh: Some("mojombo".to_string()),
@@ -372,6 +697,25 @@ mod serialization_tests {
                 enabled: Some("true".to_string()),
                 axe_code: Some("5454".to_string()),
             }),
+            io: None,
+            integrity: None,
+            p2p: None,
+            quota: None,
+            upload: None,
+            shard: None,
+            control: None,
+            signing: None,
+            audit: None,
+            retention: None,
+            store: None,
+            fallback: None,
+            mirror: None,
+            pack: None,
+            staging: None,
+            chunking: None,
+            summaries: None,
+            usage: None,
+            tier: None,
             profiles: HashMap::default(),
         };
         let toml_string = toml::to_string(&cfg).unwrap();
@@ -421,6 +765,25 @@ axe_code = "5454"
                 ..Default::default()
             }),
             axe: None,
+            io: None,
+            integrity: None,
+            p2p: None,
+            quota: None,
+            upload: None,
+            shard: None,
+            control: None,
+            signing: None,
+            audit: None,
+            retention: None,
+            store: None,
+            fallback: None,
+            mirror: None,
+            pack: None,
+            staging: None,
+            chunking: None,
+            summaries: None,
+            usage: None,
+            tier: None,
             profiles: HashMap::from([(
                 "dev".to_string(),
                 Cfg {
@@ -468,6 +831,25 @@ token = "abc123"
                 ..Default::default()
             }),
             axe: None,
+            io: None,
+            integrity: None,
+            p2p: None,
+            quota: None,
+            upload: None,
+            shard: None,
+            control: None,
+            signing: None,
+            audit: None,
+            retention: None,
+            store: None,
+            fallback: None,
+            mirror: None,
+            pack: None,
+            staging: None,
+            chunking: None,
+            summaries: None,
+            usage: None,
+            tier: None,
             profiles: HashMap::from([(
                 "dev".to_string(),
                 Cfg {
@@ -514,6 +896,7 @@ token = "abc123"
             cache: Some(Cache {
                 path: Some("/tmp/xet.log".into()),
                 size: Some(4356),
+                memory: None,
                 blocksize: None,
             }),
             log: Some(Log {
@@ -523,6 +906,8 @@ token = "abc123"
                 tracing: None,
                 silentsummary: None,
                 exceptions: None,
+                dir: None,
+                maxfiles: None,
             }),
             user: Some(User {
                 ssh: Some("mojombo".to_string()),
@@ -536,6 +921,25 @@ token = "abc123"
                 enabled: Some("true".to_string()),
                 axe_code: Some("5454".to_string()),
             }),
+            io: None,
+            integrity: None,
+            p2p: None,
+            quota: None,
+            upload: None,
+            shard: None,
+            control: None,
+            signing: None,
+            audit: None,
+            retention: None,
+            store: None,
+            fallback: None,
+            mirror: None,
+            pack: None,
+            staging: None,
+            chunking: None,
+            summaries: None,
+            usage: None,
+            tier: None,
             profiles: HashMap::default(),
         };
         let data = r#"version = 1
@@ -610,6 +1014,7 @@ pth = "localhost"
             cache: Some(Cache {
                 path: Some("/tmp/xet.log".into()),
                 size: Some(4356),
+                memory: None,
                 blocksize: None,
             }),
             log: Some(Log {
@@ -619,6 +1024,8 @@ pth = "localhost"
                 tracing: None,
                 silentsummary: None,
                 exceptions: None,
+                dir: None,
+                maxfiles: None,
             }),
             user: Some(User {
                 ssh: Some("mojombo".to_string()),
@@ -632,6 +1039,25 @@ pth = "localhost"
                 enabled: Some("true".to_string()),
                 axe_code: Some("5454".to_string()),
             }),
+            io: None,
+            integrity: None,
+            p2p: None,
+            quota: None,
+            upload: None,
+            shard: None,
+            control: None,
+            signing: None,
+            audit: None,
+            retention: None,
+            store: None,
+            fallback: None,
+            mirror: None,
+            pack: None,
+            staging: None,
+            chunking: None,
+            summaries: None,
+            usage: None,
+            tier: None,
             profiles: HashMap::default(),
         };
 
@@ -648,6 +1074,8 @@ pth = "localhost"
                 tracing: None,
                 silentsummary: None,
                 exceptions: None,
+                dir: None,
+                maxfiles: None,
             }),
             user: Some(User {
                 ssh: None,
@@ -661,6 +1089,25 @@ pth = "localhost"
                 enabled: Some("false".to_string()),
                 axe_code: Some("5454".to_string()),
             }),
+            io: None,
+            integrity: None,
+            p2p: None,
+            quota: None,
+            upload: None,
+            shard: None,
+            control: None,
+            signing: None,
+            audit: None,
+            retention: None,
+            store: None,
+            fallback: None,
+            mirror: None,
+            pack: None,
+            staging: None,
+            chunking: None,
+            summaries: None,
+            usage: None,
+            tier: None,
             profiles: HashMap::default(),
         };
         let mut profiles = HashMap::new();
@@ -6,7 +6,11 @@ mod error;
 mod level;
 mod loader;
 
-pub use cfg::{Axe, Cache, Cas, Cfg, Log, User};
+pub use cfg::{
+    Audit, Axe, Cache, Cas, Cfg, Chunking, Control, Fallback, Integrity, Io, Log, Mirror, P2p,
+    Pack, Quota, Retention, Shard, Signing, Staging, Store, Summaries, SummaryAnalyzer, Tier,
+    Upload, Usage, User,
+};
 pub use cfg::{
     DEFAULT_CACHE_PATH_UNDER_HOME, DEFAULT_CAS_PREFIX, DEFAULT_XET_HOME, PROD_AXE_CODE,
     PROD_CAS_ENDPOINT,
@@ -264,6 +264,7 @@ mod tests {
             cache: Some(Cache {
                 path: Default::default(),
                 size: Some(4_294_967_296),
+                memory: None,
                 blocksize: Some(12345),
             }),
             log: Some(Log {
@@ -273,6 +274,8 @@ mod tests {
                 tracing: None,
                 silentsummary: None,
                 exceptions: None,
+                dir: None,
+                maxfiles: None,
             }),
             user: Some(User {
                 ssh: Some("mojombo".to_string()),
@@ -286,6 +289,25 @@ mod tests {
                 enabled: Some("true".to_string()),
                 axe_code: Some("123456".to_string()),
             }),
+            io: None,
+            integrity: None,
+            p2p: None,
+            quota: None,
+            upload: None,
+            shard: None,
+            control: None,
+            signing: None,
+            audit: None,
+            retention: None,
+            store: None,
+            fallback: None,
+            mirror: None,
+            pack: None,
+            staging: None,
+            chunking: None,
+            summaries: None,
+            usage: None,
+            tier: None,
             profiles: HashMap::new(),
         };
         let local_cfg = serialize_cfg_to_tmp(&test_cfg);
@@ -95,7 +95,7 @@ async fn run() -> anyhow::Result<()> {
     let args = XetCmdCommand::parse();
     let mut repo_manager = XetRepoManager::new(None, None)?;
     let config = XetConfig::new(None, None, ConfigGitPathOption::NoPath)?;
-    let _ = log::initialize_tracing_subscriber(&config);
+    let _ = log::initialize_tracing_subscriber(&config, "xetcmd");
 
     match args.command {
         Command::Ls(ls) => {